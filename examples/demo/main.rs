@@ -0,0 +1,504 @@
+//! The demo: [`state::AppState`] shows most of what the crate does, and the
+//! flags below switch on the rest, e.g. `--scene-demo` or `--embed`.
+
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(feature = "remote-control")]
+use egui_wgpu_winit_example::remote_control;
+use egui_wgpu_winit_example::{
+    background_drag::DragRegion, bug_report, crash_report, debug_window,
+    drag_pointer::DragPointerMode, embed, event_trace, gpu::LimitsPreset, kiosk::KioskOptions,
+    log_console, metrics, open_url::UrlDecision, painter, paths, titlebar::Backdrop, App, Options,
+};
+#[cfg(feature = "hot-reload")]
+use egui_wgpu_winit_example::{hot_reload, SimpleApp};
+use state::{AppState, TextureDemos};
+
+mod state;
+
+fn parse_present_mode(value: &str) -> anyhow::Result<wgpu::PresentMode> {
+    Ok(match value {
+        "auto-vsync" => wgpu::PresentMode::AutoVsync,
+        "auto-no-vsync" => wgpu::PresentMode::AutoNoVsync,
+        "fifo" => wgpu::PresentMode::Fifo,
+        "fifo-relaxed" => wgpu::PresentMode::FifoRelaxed,
+        "mailbox" => wgpu::PresentMode::Mailbox,
+        "immediate" => wgpu::PresentMode::Immediate,
+        _ => anyhow::bail!("unknown present mode: {value}"),
+    })
+}
+
+/// Parses a comma separated list such as `validation,debug`; `none` clears all flags.
+fn parse_instance_flags(value: &str) -> anyhow::Result<wgpu::InstanceFlags> {
+    let mut flags = wgpu::InstanceFlags::empty();
+    for name in value
+        .split(',')
+        .filter(|name| !name.is_empty() && *name != "none")
+    {
+        flags |= wgpu::InstanceFlags::from_name(&name.to_uppercase().replace('-', "_"))
+            .ok_or_else(|| anyhow::anyhow!("unknown instance flag: {name}"))?;
+    }
+    Ok(flags)
+}
+
+/// The command line for crash reports, with the values of flags that take
+/// secrets replaced.
+fn mask_secret_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut masked = Vec::new();
+    let mut secret_next = false;
+    for arg in args {
+        if std::mem::take(&mut secret_next) {
+            masked.push("<redacted>".to_owned());
+        } else {
+            secret_next = arg == "--remote-control-token";
+            masked.push(arg);
+        }
+    }
+    masked
+}
+
+fn main() -> anyhow::Result<()> {
+    // The Tracy spans expect a running client; it starts broadcasting here.
+    #[cfg(feature = "tracy")]
+    profiling::tracy_client::Client::start();
+    let mut options = Options::default();
+    let mut simple = false;
+    #[cfg(feature = "hot-reload")]
+    let mut hot_reload = None;
+    let mut slow_init = None;
+    let mut url_decision = UrlDecision::Allow;
+    let mut drag_pointer_mode = DragPointerMode::Wrap;
+    let mut background_drag = None;
+    let mut kiosk: Option<KioskOptions> = None;
+    let mut painter = None;
+    let mut asset_directory = None;
+    let mut task = None;
+    let mut texture_demos = TextureDemos::default();
+    let mut embed_thread = false;
+    #[cfg(windows)]
+    let mut embed_demo = false;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--profile" => options.gpu_profiling = true,
+            "--overlay" => options.show_frame_overlay = true,
+            "--render-stats" => options.show_render_stats = true,
+            "--frames" => options.frame_limit = Some(value()?.parse()?),
+            "--timings" => options.timing_export = Some(value()?.into()),
+            "--latency" => options.measure_latency = true,
+            "--no-warm-up" => options.warm_up = false,
+            "--no-frame-skip" => options.skip_unchanged_frames = false,
+            "--unified-titlebar" => options.unified_titlebar = true,
+            "--custom-titlebar" => options.custom_titlebar = true,
+            "--window-shadow" => {
+                options.window_shadow = true;
+                options.custom_titlebar = true;
+            }
+            "--exclude-from-capture" => options.exclude_from_capture = true,
+            "--title-pattern" => options.title_pattern = value()?,
+            "--click-through" => options.click_through = true,
+            "--backdrop" => {
+                options.backdrop = Some(match value()?.as_str() {
+                    "none" => Backdrop::None,
+                    "mica" => Backdrop::Mica,
+                    "acrylic" => Backdrop::Acrylic,
+                    other => anyhow::bail!("unknown backdrop: {other}"),
+                })
+            }
+            "--no-tessellation-cache" => options.tessellation_cache = false,
+            "--render-scale" => {
+                let scale: f32 = value()?.parse()?;
+                anyhow::ensure!(
+                    (1.0..=4.0).contains(&scale),
+                    "--render-scale must be between 1 and 4"
+                );
+                options.render_scale = scale;
+            }
+            "--design-resolution" => {
+                let value = value()?;
+                let (width, height) = value
+                    .split_once('x')
+                    .ok_or_else(|| anyhow::anyhow!("expected WIDTHxHEIGHT, got {value}"))?;
+                options.design_resolution = Some([width.parse()?, height.parse()?]);
+            }
+            "--design-filter" => {
+                options.design_filter = match value()?.as_str() {
+                    "nearest" => wgpu::FilterMode::Nearest,
+                    "linear" => wgpu::FilterMode::Linear,
+                    other => anyhow::bail!("unknown filter: {other}"),
+                }
+            }
+            "--mirror" => {
+                options.mirror = true;
+                options.mirror_monitor = args.next_if(|arg| !arg.starts_with("--"));
+            }
+            "--magnifier" => options.magnifier = true,
+            "--frame-budget" => {
+                options.frame_budget = Some(Duration::try_from_secs_f64(
+                    value()?.parse::<f64>()? / 1000.,
+                )?)
+            }
+            "--max-passes" => options.egui.max_passes = value()?.parse()?,
+            "--embed-viewports" => options.egui.embed_viewports = true,
+            "--repaint-on-widget-change" => options.egui.repaint_on_widget_change = true,
+            "--no-feathering" => options.egui.tessellation.feathering = false,
+            "--feathering-size" => {
+                options.egui.tessellation.feathering_size_in_pixels = value()?.parse()?
+            }
+            "--no-coarse-culling" => options.egui.tessellation.coarse_tessellation_culling = false,
+            "--coalesce-pointer" => options.coalesce_pointer_moves = true,
+            "--rich-paste" => options.rich_paste = true,
+            "--record-input" => options.record_input = Some(value()?.into()),
+            "--trace-events" => options.trace_events = true,
+            "--trace-redact" => options.trace_redact = true,
+            "--right-click-as-middle" => options.right_click_as_middle = true,
+            "--log-input-events" => options.log_input_events = true,
+            "--trace-to-replay" => {
+                let trace = std::path::PathBuf::from(value()?);
+                let replay = std::path::PathBuf::from(value()?);
+                return event_trace::trace_to_replay(&trace, &replay);
+            }
+            "--replay-input" => options.replay_input = Some(value()?.into()),
+            "--replay-fast" => options.replay_fast = true,
+            #[cfg(feature = "remote-control")]
+            "--remote-control" => {
+                let remote_control = options.remote_control.get_or_insert_with(Default::default);
+                if let Some(address) = args.next_if(|arg| !arg.starts_with("--")) {
+                    remote_control.address = address.parse()?;
+                }
+            }
+            #[cfg(feature = "remote-control")]
+            "--remote-control-token" => {
+                options
+                    .remote_control
+                    .get_or_insert_with(Default::default)
+                    .token = Some(value()?)
+            }
+            "--metrics-file" => {
+                let textfile = match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(path) => path.into(),
+                    None => metrics::default_textfile().ok_or_else(|| {
+                        anyhow::anyhow!("no data directory for --metrics-file, pass a path")
+                    })?,
+                };
+                options
+                    .metrics
+                    .get_or_insert_with(Default::default)
+                    .textfile = Some(textfile);
+            }
+            "--metrics-interval" => {
+                options
+                    .metrics
+                    .get_or_insert_with(Default::default)
+                    .interval = Duration::try_from_secs_f64(value()?.parse()?)?
+            }
+            #[cfg(feature = "metrics-server")]
+            "--metrics-server" => {
+                let address = match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(address) => address.parse()?,
+                    None => metrics::DEFAULT_ADDRESS.parse()?,
+                };
+                options.metrics.get_or_insert_with(Default::default).address = Some(address);
+            }
+            "--present-mode" => options.present_mode = parse_present_mode(&value()?)?,
+            "--frame-pacing" => options.frame_pacing = true,
+            "--vsync-pacing" => options.vsync_pacing = true,
+            "--idle-after" => {
+                options.idle_after = Some(Duration::try_from_secs_f64(value()?.parse()?)?)
+            }
+            "--idle-stop-animations" => options.idle_stop_animations = true,
+            "--benchmark" => {
+                options.benchmark = Some(match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(length) => length.parse()?,
+                    None => Default::default(),
+                })
+            }
+            "--benchmark-warm-up" => {
+                options.benchmark_warm_up = Duration::try_from_secs_f64(value()?.parse()?)?
+            }
+            "--benchmark-report" => options.benchmark_report = Some(value()?.into()),
+            "--stress-ui" => options.stress_ui = true,
+            "--gpu-memory-warning" => {
+                let mib: u64 = value()?.parse()?;
+                options.gpu_memory_warning = Some(mib * 1024 * 1024);
+            }
+            "--native-texture" => texture_demos.native = true,
+            "--scene-demo" => texture_demos.scene = true,
+            "--compressed-texture" => texture_demos.compressed = true,
+            #[cfg(feature = "texture-containers")]
+            "--compressed-texture-file" => {
+                texture_demos.compressed = true;
+                texture_demos.compressed_file = Some(value()?.into());
+            }
+            "--textures" => options.show_textures = true,
+            "--log-console" => options.show_log_console = true,
+            "--debug-window" => options.debug_window = true,
+            "--no-debug-window" => options.debug_window = false,
+            "--error-overlay" => options.error_overlay = true,
+            "--no-error-overlay" => options.error_overlay = false,
+            "--repaint-flash" => options.repaint_flash = true,
+            "--debug-hotkey" => {
+                options.debug_hotkey = debug_window::parse_hotkey(&value()?)?;
+                options.debug_window = true;
+            }
+            "--command-palette" => {
+                options.command_palette = Some(debug_window::parse_hotkey(&value()?)?)
+            }
+            "--no-command-palette" => options.command_palette = None,
+            "--log-level" => options.log_level = value()?.parse()?,
+            "--log-file" => options.log_file = Some(value()?.into()),
+            "--force-close-requests" => options.force_close_requests = value()?.parse()?,
+            "--force-close-within" => {
+                options.force_close_within = Duration::try_from_secs_f64(value()?.parse()?)?
+            }
+            "--exit-after" => {
+                options.exit_after = Some(Duration::try_from_secs_f64(value()?.parse()?)?)
+            }
+            "--simple" => simple = true,
+            "--assets" => asset_directory = Some(PathBuf::from(value()?)),
+            "--task" => task = Some(value()?),
+            #[cfg(feature = "hot-reload")]
+            "--hot-reload" => hot_reload = Some(std::path::PathBuf::from(value()?)),
+            "--painter" => painter = Some(value()?),
+            "--parent-window" => options.parent_window = Some(value()?.parse()?),
+            "--embed-thread" => embed_thread = true,
+            #[cfg(windows)]
+            "--embed-demo" => embed_demo = true,
+            "--slow-init" => slow_init = Some(Duration::try_from_secs_f64(value()?.parse()?)?),
+            "--kiosk" => {
+                kiosk.get_or_insert_with(KioskOptions::default);
+            }
+            "--kiosk-monitor" => {
+                kiosk.get_or_insert_with(KioskOptions::default).monitor = Some(value()?)
+            }
+            "--kiosk-hide-cursor" => {
+                kiosk
+                    .get_or_insert_with(KioskOptions::default)
+                    .hide_cursor_after = Some(Duration::try_from_secs_f64(value()?.parse()?)?)
+            }
+            "--kiosk-reset-after" => {
+                let minutes: f64 = value()?.parse()?;
+                kiosk.get_or_insert_with(KioskOptions::default).reset_after =
+                    Some(Duration::try_from_secs_f64(minutes * 60.)?)
+            }
+            "--kiosk-exit-chord" => {
+                kiosk.get_or_insert_with(KioskOptions::default).exit_chord =
+                    debug_window::parse_hotkey(&value()?)?
+            }
+            "--background-drag" => {
+                background_drag = Some(match value()?.as_str() {
+                    "everywhere" => DragRegion::Everywhere,
+                    height => DragRegion::TopStrip(height.parse()?),
+                })
+            }
+            "--drag-pointer" => {
+                drag_pointer_mode = match value()?.as_str() {
+                    "wrap" => DragPointerMode::Wrap,
+                    "confine" => DragPointerMode::Confine,
+                    other => anyhow::bail!("unknown drag pointer mode: {other}"),
+                }
+            }
+            "--open-urls" => {
+                url_decision = match value()?.as_str() {
+                    "allow" => UrlDecision::Allow,
+                    "deny" => UrlDecision::Deny,
+                    "ask" => UrlDecision::Ask,
+                    other => anyhow::bail!("unknown URL policy: {other}"),
+                }
+            }
+            "--no-crash-report" => options.crash_reports = false,
+            "--crash-report-dir" => options.crash_report_dir = Some(value()?.into()),
+            "--portable" => options.portable = Some(true),
+            "--no-portable" => options.portable = Some(false),
+            "--render-thread" => options.render_thread = true,
+            "--backends" => {
+                let backends = wgpu::util::parse_backends_from_comma_list(&value()?);
+                anyhow::ensure!(!backends.is_empty(), "no known backend in --backends");
+                options.backends = Some(backends);
+            }
+            "--backend-fallback" => options.backend_fallback = true,
+            "--monitor-scales" => options.monitor_scales = Some(value()?.into()),
+            "--backend-cache" => options.backend_cache = Some(value()?.into()),
+            "--force-software" => options.force_software = true,
+            #[cfg(feature = "software-renderer")]
+            "--cpu-rendering" => options.cpu_rendering = true,
+            #[cfg(feature = "software-renderer")]
+            "--no-cpu-fallback" => options.cpu_fallback = false,
+            "--instance-flags" => options.instance_flags = parse_instance_flags(&value()?)?,
+            "--memory-hints" => {
+                options.memory_hints = match value()?.as_str() {
+                    "performance" => wgpu::MemoryHints::Performance,
+                    "memory-usage" => wgpu::MemoryHints::MemoryUsage,
+                    other => anyhow::bail!("unknown memory hints: {other}"),
+                }
+            }
+            "--limits" => {
+                options.limits_preset = match value()?.as_str() {
+                    "webgl2" => LimitsPreset::DownlevelWebgl2,
+                    "downlevel" => LimitsPreset::Downlevel,
+                    "default" => LimitsPreset::Default,
+                    "adapter" => LimitsPreset::AdapterMax,
+                    other => anyhow::bail!("unknown limits preset: {other}"),
+                }
+            }
+            "--max-textures" => options.max_live_textures = Some(value()?.parse()?),
+            "--texture-growth-warning" => {
+                let minutes: f64 = value()?.parse()?;
+                options.texture_growth_warning = Some(Duration::try_from_secs_f64(minutes * 60.)?);
+            }
+            _ => anyhow::bail!("unknown argument: {arg}"),
+        }
+    }
+    if let Some(kiosk) = kiosk {
+        options.set_kiosk(kiosk);
+    }
+    if options.benchmark.is_some() && options.present_mode == wgpu::PresentMode::AutoVsync {
+        options.present_mode = wgpu::PresentMode::AutoNoVsync;
+    }
+
+    log_console::install(options.log_level, options.log_file.clone())?;
+    if options.render_thread && texture_demos.any() {
+        // egui's renderer lives on the render thread, see
+        // `UserApp::on_gpu_textures`.
+        log::warn!("the texture demos do not work with the render thread");
+    }
+    paths::init(options.portable);
+    if options.crash_reports {
+        crash_report::install(options.crash_report_dir.clone());
+        let args = mask_secret_args(std::env::args());
+        crash_report::set_hook(move || format!("command line: {args:?}"));
+    }
+    // Resolved here rather than when the server starts, so reports can hide it.
+    #[cfg(feature = "remote-control")]
+    let token = options
+        .remote_control
+        .as_mut()
+        .map(|remote_control| {
+            remote_control
+                .token
+                .get_or_insert_with(remote_control::resolve_token)
+                .clone()
+        })
+        .filter(|token| !token.is_empty());
+    #[cfg(not(feature = "remote-control"))]
+    let token: Option<String> = None;
+    let home = std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(|home| home.to_string_lossy().into_owned());
+    if token.is_some() || home.is_some() {
+        bug_report::set_redactor(move |text| {
+            let mut text = text.to_owned();
+            if let Some(token) = &token {
+                text = text.replace(token, "<token>");
+            }
+            if let Some(home) = &home {
+                text = text.replace(home, "~");
+            }
+            text
+        });
+    }
+    if simple {
+        return Ok(App::run_simple(options.window_size, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello from run_simple"));
+        })?);
+    }
+    #[cfg(feature = "hot-reload")]
+    if let Some(path) = hot_reload {
+        let mut hot_reload = hot_reload::HotReload::new(path);
+        return Ok(App::run(
+            options,
+            SimpleApp(move |ctx: &egui::Context| hot_reload.update(ctx)),
+        )?);
+    }
+    let custom_titlebar = options.custom_titlebar;
+    let new_state = move || {
+        let mut state = AppState::new(custom_titlebar);
+        state.url_decision = url_decision;
+        state.drag_pointer_mode = drag_pointer_mode;
+        state.background_drag = background_drag;
+        state.asset_directory = asset_directory;
+        state.task = task;
+        state.texture_demos = texture_demos;
+        state
+    };
+    if let Some(slow_init) = slow_init {
+        // Stands in for opening databases and the like.
+        return Ok(App::run_loading(options, move |progress| {
+            const STEPS: u32 = 10;
+            for step in 0..STEPS {
+                progress.report(
+                    Some(step as f32 / STEPS as f32),
+                    format!("Loading part {} of {STEPS}", step + 1),
+                );
+                std::thread::sleep(slow_init / STEPS);
+            }
+            Ok(new_state())
+        })?);
+    }
+    #[cfg(windows)]
+    if embed_demo {
+        return Ok(embed::demo_host::run(options, new_state)?);
+    }
+    if options.parent_window.is_some() && embed_thread {
+        let embedded = embed::spawn(options, new_state)?;
+        embed::read_requests(embedded.handle.clone())?;
+        return Ok(embedded.join()?);
+    }
+    #[cfg(not(target_os = "ios"))]
+    if options.parent_window.is_some() {
+        let mut embedded = embed::Embedded::new(options, new_state())?;
+        embed::read_requests(embedded.handle())?;
+        // A host would pump from its own loop, between its own work.
+        loop {
+            if let Some(result) = embedded.pump(None) {
+                return Ok(result?);
+            }
+        }
+    }
+    // The built-in renderer paints unless a painter is picked, which shows
+    // how an app's own renderer plugs in.
+    match painter.as_deref() {
+        None => Ok(App::run(options, new_state())?),
+        Some("noop") => Ok(App::run_with_painter(options, new_state(), |_| {
+            Ok(Box::new(painter::NoopPainter))
+        })?),
+        Some("wgpu") => {
+            let painter_options = options.clone();
+            Ok(App::run_with_painter(
+                options,
+                new_state(),
+                move |window| {
+                    Ok(Box::new(painter::WgpuPainter::new(
+                        window,
+                        &painter_options,
+                    )?))
+                },
+            )?)
+        }
+        Some(name) => anyhow::bail!("unknown painter: {name}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_args_are_masked() {
+        let args = ["app", "--remote-control-token", "s3cret", "--kiosk"].map(String::from);
+        assert_eq!(
+            mask_secret_args(args),
+            ["app", "--remote-control-token", "<redacted>", "--kiosk"]
+        );
+    }
+
+    #[test]
+    fn other_args_are_kept() {
+        let args = ["app", "--log-level", "debug"].map(String::from);
+        assert_eq!(mask_secret_args(args.clone()), args);
+    }
+}
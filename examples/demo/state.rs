@@ -0,0 +1,1081 @@
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "dialogs")]
+use egui_wgpu_winit_example::file_dialog::{self, DialogId, DialogResult, FileFilter};
+#[cfg(feature = "notifications")]
+use egui_wgpu_winit_example::notifications::{self, Notification, NotificationEvent};
+use egui_wgpu_winit_example::{
+    adaptive_quality::{self, QualityChange, Step},
+    assets::{self, Assets},
+    background_drag::{self, DragRegion},
+    bug_report, capture,
+    clipboard::{self, ClipboardContent},
+    close,
+    command_palette::Command,
+    compressed_texture, dock, document,
+    drag_pointer::{self, DragPointerMode},
+    event::EventResult,
+    frame_stats::FrameMetrics,
+    gpu,
+    input_middleware::{self, InputBlocker, InputMiddleware},
+    jump_list, letterbox, magnifier, mirror, monitor_scale,
+    monitors::Monitors,
+    open_url::{self, UrlDecision},
+    pointer, scene, sleep_inhibit, titlebar, window_handles, GpuTextures, Orientation, UserApp,
+};
+use egui_winit::winit::{
+    event::{DeviceEvent, DeviceId, ElementState, MouseScrollDelta, RawKeyEvent},
+    keyboard::{KeyCode, PhysicalKey},
+    window::Window,
+};
+use web_time::Instant;
+use wgpu::util::DeviceExt;
+
+/// The `--native-texture`, `--scene-demo` and `--compressed-texture`
+/// demos to set up once the GPU is ready.
+#[derive(Default)]
+pub struct TextureDemos {
+    pub native: bool,
+    /// Shows a triangle rendered offscreen in a resizable window, see
+    /// [`scene::TriangleScene`].
+    pub scene: bool,
+    /// Shows a block-compressed image with odd dimensions and a mip chain,
+    /// see [`compressed_texture::upload`].
+    pub compressed: bool,
+    /// A DDS or KTX2 file shown instead of the generated image.
+    #[cfg(feature = "texture-containers")]
+    pub compressed_file: Option<PathBuf>,
+}
+
+impl TextureDemos {
+    pub fn any(&self) -> bool {
+        self.native || self.scene || self.compressed
+    }
+}
+
+pub struct AppState {
+    name: String,
+    age: i32,
+    strokes: Vec<Vec<egui::Pos2>>,
+    pub texture_demos: TextureDemos,
+    native_texture: Option<egui::TextureId>,
+    /// Shown in its own window.
+    scene: Option<scene::TriangleScene>,
+    /// With its size in texels and the part of it to show.
+    compressed_texture: Option<(egui::TextureId, egui::Vec2, egui::Rect)>,
+    gpu_notice: Option<String>,
+    orientation: Orientation,
+    titlebar_inset: f32,
+    custom_titlebar: bool,
+    monitors: Monitors,
+    window: Option<Arc<Window>>,
+    pub url_decision: UrlDecision,
+    distance: f64,
+    pub drag_pointer_mode: DragPointerMode,
+    pub background_drag: Option<DragRegion>,
+    keep_awake: bool,
+    raw_keys: BTreeSet<KeyCode>,
+    raw_wheel: f32,
+    pasted_html: Option<usize>,
+    /// The last files dropped or pasted.
+    dropped_files: Vec<PathBuf>,
+    quality_change: Option<QualityChange>,
+    frame_metrics: FrameMetrics,
+    /// When the simulated long operation finishes.
+    busy_until: Option<Instant>,
+    bug_report: Option<Result<PathBuf, String>>,
+    /// Long operations that finished while the window was in the
+    /// background, shown as the dock badge.
+    unseen_finished: usize,
+    /// Why the window cannot be hidden from screen capture.
+    capture_error: Option<String>,
+    /// The jump list task the app was started for, run on the first frame.
+    pub task: Option<String>,
+    /// The open dialog and the last answer of one.
+    #[cfg(feature = "dialogs")]
+    file_dialog: Option<DialogId>,
+    #[cfg(feature = "dialogs")]
+    picked_file: Option<DialogResult>,
+    /// Shown in the window when the notification could not be.
+    #[cfg(feature = "notifications")]
+    toast: Option<String>,
+    assets: Assets,
+    /// Registered with [`Assets::directory`] on the first frame.
+    pub asset_directory: Option<PathBuf>,
+    /// The images in `asset_directory`, shown in the "Assets" window.
+    asset_images: Arc<Mutex<BTreeSet<PathBuf>>>,
+}
+
+impl AppState {
+    pub fn new(custom_titlebar: bool) -> Self {
+        Self {
+            name: "John Doe".to_owned(),
+            age: 42,
+            strokes: Vec::new(),
+            texture_demos: TextureDemos::default(),
+            native_texture: None,
+            scene: None,
+            compressed_texture: None,
+            gpu_notice: None,
+            orientation: Orientation::Landscape,
+            titlebar_inset: 0.,
+            custom_titlebar,
+            monitors: Monitors::default(),
+            window: None,
+            url_decision: UrlDecision::Allow,
+            distance: 0.,
+            drag_pointer_mode: DragPointerMode::Wrap,
+            background_drag: None,
+            keep_awake: false,
+            raw_keys: BTreeSet::new(),
+            raw_wheel: 0.,
+            pasted_html: None,
+            dropped_files: Vec::new(),
+            quality_change: None,
+            frame_metrics: FrameMetrics::default(),
+            busy_until: None,
+            bug_report: None,
+            unseen_finished: 0,
+            capture_error: None,
+            task: None,
+            #[cfg(feature = "dialogs")]
+            file_dialog: None,
+            #[cfg(feature = "dialogs")]
+            picked_file: None,
+            #[cfg(feature = "notifications")]
+            toast: None,
+            assets: Assets::default(),
+            asset_directory: None,
+            asset_images: Arc::default(),
+        }
+    }
+
+    fn bug_report_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(result) = bug_report::take_result(ui.ctx()) {
+            self.bug_report = Some(result);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Create bug report").clicked() {
+                bug_report::create_bug_report(ui.ctx(), None);
+            }
+            match &self.bug_report {
+                Some(Ok(path)) => {
+                    ui.label(path.display().to_string());
+                    if let Some(dir) = path.parent() {
+                        if ui.button("Show").clicked() {
+                            ui.ctx().open_url(egui::OpenUrl::same_tab(format!(
+                                "file://{}",
+                                dir.display()
+                            )));
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                None => {}
+            }
+        });
+    }
+
+    #[cfg(feature = "dialogs")]
+    fn file_dialog_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(result) = self
+            .file_dialog
+            .and_then(|id| file_dialog::take_result(ui.ctx(), id))
+        {
+            self.file_dialog = None;
+            if let Ok(Some(path)) = &result {
+                jump_list::add_recent_document(path);
+            }
+            self.picked_file = Some(result);
+        }
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(self.file_dialog.is_none(), |ui| {
+                let ctx = ui.ctx().clone();
+                if ui.button("Open…").clicked() {
+                    let filters = vec![
+                        FileFilter::new("Images", &["png", "jpg", "jpeg"]),
+                        FileFilter::new("Text", &["txt", "md"]),
+                    ];
+                    self.file_dialog = Some(file_dialog::pick_file(&ctx, filters));
+                }
+                if ui.button("Folder…").clicked() {
+                    self.file_dialog = Some(file_dialog::pick_folder(&ctx));
+                }
+                if ui.button("Save as…").clicked() {
+                    self.file_dialog = Some(file_dialog::save_file(&ctx, "notes.txt"));
+                }
+            });
+            match &self.picked_file {
+                Some(Ok(Some(path))) => {
+                    ui.label(format!("📄 {}", path.display()));
+                }
+                Some(Ok(None)) => {
+                    ui.label("Cancelled");
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn start_long_operation(&mut self, ctx: &egui::Context) {
+        self.busy_until = Some(Instant::now() + Duration::from_secs(5));
+        input_middleware::set_input_blocked(ctx, true);
+    }
+
+    /// The dock menu offers what buttons in the window do.
+    fn dock_ui(&mut self, ctx: &egui::Context) {
+        dock::set_dock_menu(
+            ctx,
+            vec![
+                dock::DockMenuItem::new("long_operation", "Run a long operation")
+                    .enabled(self.busy_until.is_none()),
+                dock::DockMenuItem::new("mirror", "Toggle the mirror window"),
+            ],
+        );
+        dock::clear_dock_badge_on_focus(ctx, true);
+        if ctx.input(|i| i.focused) {
+            self.unseen_finished = 0;
+        }
+        for id in dock::take_selections(ctx) {
+            self.run_task(ctx, &id);
+        }
+    }
+
+    /// The same tasks as the dock menu, which start a new instance.
+    fn jump_list_ui(&mut self, ctx: &egui::Context) {
+        jump_list::set_jump_list_tasks(
+            ctx,
+            vec![
+                jump_list::JumpListTask::new("Run a long operation", "--task long_operation"),
+                jump_list::JumpListTask::new("Open with the mirror window", "--task mirror"),
+            ],
+        );
+        if let Some(task) = self.task.take() {
+            self.run_task(ctx, &task);
+        }
+    }
+
+    fn run_task(&mut self, ctx: &egui::Context, task: &str) {
+        match task {
+            "long_operation" if self.busy_until.is_none() => self.start_long_operation(ctx),
+            "mirror" => mirror::set_mirror_open(ctx, !mirror::is_mirror_open(ctx)),
+            _ => log::warn!("unknown task {task:?}"),
+        }
+    }
+
+    /// Everything but Escape is blocked while busy, see
+    /// [`input_middleware::InputBlocker`].
+    fn busy_ui(&mut self, ctx: &egui::Context) {
+        let Some(busy_until) = self.busy_until else {
+            return;
+        };
+        let canceled = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
+        let remaining = busy_until.saturating_duration_since(Instant::now());
+        if canceled || remaining.is_zero() {
+            self.busy_until = None;
+            input_middleware::set_input_blocked(ctx, false);
+            if !canceled && !ctx.input(|i| i.focused) {
+                self.unseen_finished += 1;
+                dock::set_dock_badge(ctx, Some(&self.unseen_finished.to_string()));
+            }
+            #[cfg(feature = "notifications")]
+            if !canceled {
+                notifications::notify(
+                    ctx,
+                    Notification {
+                        title: "The long operation finished".to_owned(),
+                        body: "It took five seconds.".to_owned(),
+                        action: Some("Show".to_owned()),
+                    },
+                );
+            }
+            return;
+        }
+        egui::Modal::new(egui::Id::new("busy_dialog")).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(format!(
+                    "Working for {:.0} s more, press Escape to cancel",
+                    remaining.as_secs_f32().ceil()
+                ));
+            });
+        });
+    }
+
+    /// Falls back to a toast in the window when there is no notification
+    /// service.
+    #[cfg(feature = "notifications")]
+    fn notifications_ui(&mut self, ctx: &egui::Context) {
+        for (_, event) in notifications::take_events(ctx) {
+            match event {
+                NotificationEvent::Failed(err) => {
+                    self.toast = Some(format!("The long operation finished ({err})"));
+                }
+                event => log::info!("notification: {event:?}"),
+            }
+        }
+        let Some(toast) = &self.toast else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Area::new(egui::Id::new("toast"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-12., -12.])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(toast);
+                        dismissed = ui.button("✖").clicked();
+                    });
+                });
+            });
+        if dismissed {
+            self.toast = None;
+        }
+    }
+
+    fn table(&self) -> ClipboardContent {
+        let escape = |text: &str| {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+        ClipboardContent {
+            text: Some(format!("Name\tAge\n{}\t{}\n", self.name, self.age)),
+            html: Some(format!(
+                "<table><tr><th>Name</th><th>Age</th></tr>\
+                 <tr><td>{}</td><td>{}</td></tr></table>",
+                escape(&self.name),
+                self.age
+            )),
+            ..Default::default()
+        }
+    }
+
+    fn window_ui(&self, ui: &mut egui::Ui) {
+        let Some(window) = &self.window else {
+            return;
+        };
+        ui.label(format!("{:?}", window.id()));
+        if let Some((window_handle, display_handle)) = window_handles::raw_handles(window) {
+            let kind = |debug: String| debug.split('(').next().unwrap_or_default().to_owned();
+            ui.label(format!(
+                "{} window on {} display",
+                kind(format!("{window_handle:?}")),
+                kind(format!("{display_handle:?}"))
+            ));
+        }
+        if let Some(transform) = letterbox::transform(ui.ctx()) {
+            let picture = transform.picture_rect();
+            let [width, height] = transform.design_size;
+            ui.label(format!(
+                "{width}x{height} scaled {:.2}x to {:.0}x{:.0} at {:.0},{:.0} px",
+                transform.scale,
+                picture.width(),
+                picture.height(),
+                picture.min.x,
+                picture.min.y,
+            ));
+            if let Some(pointer) = ui.ctx().pointer_latest_pos() {
+                let pos = transform.design_to_window(pointer);
+                ui.label(format!(
+                    "Pointer at {:.0},{:.0} px in the window",
+                    pos.x, pos.y
+                ));
+            }
+        }
+    }
+
+    fn monitors_ui(&self, ui: &mut egui::Ui) {
+        let current = self.monitors.current().map(|monitor| monitor.id);
+        for monitor in &self.monitors.monitors {
+            let [x, y] = monitor.position;
+            let [width, height] = monitor.size;
+            let refresh_rate = monitor.refresh_millihertz.map_or_else(String::new, |mhz| {
+                format!(" @ {:.1} Hz", mhz as f32 / 1000.)
+            });
+            let origin = self.monitors.desktop_to_window(monitor.position);
+            ui.label(format!(
+                "{}{}: {width}x{height} at {x},{y}, scale {}{refresh_rate}, origin {:.0},{:.0} in window",
+                if Some(monitor.id) == current { "▶ " } else { "" },
+                monitor.name.as_deref().unwrap_or("Unknown"),
+                monitor.scale_factor,
+                origin.x,
+                origin.y,
+            ));
+        }
+        if let Some(pointer) = ui.ctx().pointer_latest_pos() {
+            let [x, y] = self.monitors.window_to_desktop(pointer);
+            ui.label(format!("Pointer at {x},{y} on the desktop"));
+        }
+        if current.is_some() {
+            ui.horizontal(|ui| {
+                ui.label("UI scale on this monitor: ");
+                let mut scale = monitor_scale::current(ui.ctx());
+                let response = ui.add(egui::DragValue::new(&mut scale).range(0.5..=3.).speed(0.01));
+                if response.changed() {
+                    monitor_scale::set_current(ui.ctx(), scale);
+                }
+            });
+        }
+    }
+
+    fn assets_ui(&mut self, ctx: &egui::Context) {
+        if let Some(directory) = self.asset_directory.take() {
+            let images = self.asset_images.clone();
+            self.assets.directory(ctx, directory, move |path, _| {
+                if assets::is_image(path) {
+                    images.lock().unwrap().insert(path.to_owned());
+                }
+            });
+        }
+        self.assets.update();
+        let images: Vec<_> = self.asset_images.lock().unwrap().iter().cloned().collect();
+        if images.is_empty() {
+            return;
+        }
+        egui::Window::new("Assets").vscroll(true).show(ctx, |ui| {
+            for path in images {
+                let texture = self.assets.image(ctx, &path);
+                ui.label(path.file_name().unwrap_or_default().to_string_lossy());
+                ui.add(egui::Image::from_texture(texture).max_width(200.));
+            }
+        });
+    }
+
+    fn scribble(&mut self, ui: &mut egui::Ui) {
+        let size = match self.orientation {
+            Orientation::Landscape => egui::vec2(200., 100.),
+            Orientation::Portrait => egui::vec2(100., 200.),
+        };
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::drag());
+        painter.rect_stroke(
+            response.rect,
+            0.,
+            ui.visuals().widgets.noninteractive.bg_stroke,
+        );
+        if response.hovered() {
+            pointer::request_full_pointer_path(ui.ctx());
+        }
+        if response.drag_started() {
+            self.strokes.push(Vec::new());
+        }
+        if let (true, Some(pos), Some(stroke)) = (
+            response.dragged(),
+            response.interact_pointer_pos(),
+            self.strokes.last_mut(),
+        ) {
+            if stroke.last() != Some(&pos) {
+                stroke.push(pos);
+            }
+        }
+        let stroke = ui.visuals().widgets.active.fg_stroke;
+        for points in &self.strokes {
+            painter.add(egui::Shape::line(points.clone(), stroke));
+        }
+    }
+}
+
+impl UserApp for AppState {
+    fn update(&mut self, ctx: &egui::Context) {
+        let metrics = self.frame_metrics;
+        if let Some(region) = self.background_drag {
+            background_drag::enable(ctx, region);
+        }
+        // Scribbles count as unsaved work.
+        document::set_document_state(ctx, None, !self.strokes.iter().all(Vec::is_empty));
+        if self.custom_titlebar {
+            titlebar::show(ctx, titlebar::DEFAULT_HEIGHT, true);
+        } else if self.titlebar_inset > 0. {
+            titlebar::show(ctx, self.titlebar_inset, false);
+        }
+        egui::Window::new("My Window")
+            .resizable(true)
+            .vscroll(true)
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.heading("My egui Application");
+                if let Some(notice) = &self.gpu_notice {
+                    ui.colored_label(ui.visuals().warn_fg_color, notice);
+                }
+                ui.horizontal(|ui| {
+                    let name_label = ui.label("Your name: ");
+                    ui.text_edit_singleline(&mut self.name)
+                        .labelled_by(name_label.id);
+                });
+                ui.add(egui::Slider::new(&mut self.age, 0..=120).text("age"));
+                if ui.button("Increment").clicked() {
+                    self.age += 1;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Distance: ");
+                    // Keeps counting when the cursor reaches the screen edge.
+                    let distance = ui.add(egui::DragValue::new(&mut self.distance));
+                    if distance.drag_started() {
+                        drag_pointer::request(ctx, self.drag_pointer_mode, true);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("Hello '{}', age {}", self.name, self.age));
+                    if ui.button("📋").on_hover_text("Copy as a table").clicked() {
+                        clipboard::set_clipboard(ctx, self.table());
+                    }
+                });
+                if let Some(bytes) = self.pasted_html {
+                    ui.label(format!("Last paste had {bytes} bytes of HTML"));
+                }
+                let dropped: Vec<PathBuf> = ctx.input(|i| {
+                    i.raw
+                        .dropped_files
+                        .iter()
+                        .filter_map(|file| file.path.clone())
+                        .collect()
+                });
+                if !dropped.is_empty() {
+                    for path in &dropped {
+                        jump_list::add_recent_document(path);
+                    }
+                    self.dropped_files = dropped;
+                }
+                for path in &self.dropped_files {
+                    ui.label(format!("📄 {}", path.display()));
+                }
+                ui.label(format!(
+                    "Raw keys held: {:?}, wheel: {:.1}",
+                    self.raw_keys, self.raw_wheel
+                ));
+                ui.hyperlink_to("egui on GitHub", "https://github.com/emilk/egui");
+                ui.separator();
+                self.scribble(ui);
+                if let Some(texture) = self.native_texture {
+                    ui.image((texture, egui::vec2(64., 64.)));
+                }
+                ui.collapsing("Monitors", |ui| self.monitors_ui(ui));
+                ui.collapsing("Window", |ui| self.window_ui(ui));
+                ui.collapsing("Magnifier", magnifier_ui);
+                ui.collapsing("Adaptive quality", |ui| {
+                    quality_ui(ui, self.quality_change.as_ref())
+                });
+                ui.collapsing("Frame metrics", |ui| frame_metrics_ui(ui, &metrics));
+                let mut excluded = capture::is_excluded(ctx);
+                if ui
+                    .checkbox(&mut excluded, "Hide from screen capture")
+                    .changed()
+                {
+                    if let Err(err) = capture::set_excluded(ctx, excluded) {
+                        self.capture_error = Some(err.to_string());
+                    }
+                }
+                if let Some(err) = &self.capture_error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                if capture::is_excluded(ctx) {
+                    ui.label("🔒 Capture-protected");
+                }
+                let mut mirrored = mirror::is_mirror_open(ctx);
+                if ui
+                    .checkbox(&mut mirrored, "Mirror to a second window")
+                    .changed()
+                {
+                    mirror::set_mirror_open(ctx, mirrored);
+                }
+                if ui
+                    .checkbox(&mut self.keep_awake, "Keep the display awake")
+                    .changed()
+                {
+                    sleep_inhibit::set_sleep_inhibited(ctx, self.keep_awake);
+                }
+                if ui.button("Run a long operation").clicked() {
+                    self.start_long_operation(ctx);
+                }
+                self.bug_report_ui(ui);
+                #[cfg(feature = "dialogs")]
+                self.file_dialog_ui(ui);
+            });
+        if let Some(texture) = self.scene.as_ref().map(|scene| scene.texture_id) {
+            egui::Window::new("Scene")
+                .resizable(true)
+                .default_size([240., 180.])
+                .show(ctx, |ui| {
+                    let size = ui.available_size();
+                    let pixels = size * ctx.pixels_per_point();
+                    scene::request_size(ctx, [pixels.x.round() as u32, pixels.y.round() as u32]);
+                    ui.image((texture, size));
+                });
+        }
+        if let Some((texture, size, uv)) = self.compressed_texture {
+            egui::Window::new("Compressed texture")
+                .resizable(true)
+                .default_size(size)
+                .show(ctx, |ui| {
+                    // Shrinking the window samples smaller mip levels.
+                    let scale = (ui.available_width() / size.x).min(ui.available_height() / size.y);
+                    ui.add(egui::Image::new((texture, size * scale.max(0.05))).uv(uv));
+                });
+        }
+        self.assets_ui(ctx);
+        self.dock_ui(ctx);
+        self.jump_list_ui(ctx);
+        self.busy_ui(ctx);
+        #[cfg(feature = "notifications")]
+        self.notifications_ui(ctx);
+        if close::is_pending(ctx) {
+            egui::Modal::new(egui::Id::new("close_dialog")).show(ctx, |ui| {
+                ui.label("Discard your scribbles and quit?");
+                ui.horizontal(|ui| {
+                    if ui.button("Quit").clicked() {
+                        close::confirm_close(ctx);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close::dismiss_close(ctx);
+                    }
+                });
+            });
+        }
+        if let Some(url) = open_url::pending(ctx) {
+            egui::Modal::new(egui::Id::new("open_url_dialog")).show(ctx, |ui| {
+                ui.label(format!(
+                    "Open {}{}?",
+                    url.url,
+                    if url.new_tab { " in a new tab" } else { "" }
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        open_url::open_url_now(ctx, url.clone());
+                    }
+                    if ui.button("Cancel").clicked() {
+                        open_url::dismiss(ctx);
+                    }
+                });
+            });
+        }
+    }
+
+    fn on_frame_metrics(&mut self, metrics: &FrameMetrics) {
+        self.frame_metrics = *metrics;
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command {
+                id: "demo.increment_age",
+                title: "Increment age".to_owned(),
+                category: "Demo".to_owned(),
+                shortcut: None,
+            },
+            Command {
+                id: "demo.clear_scribbles",
+                title: "Clear scribbles".to_owned(),
+                category: "Demo".to_owned(),
+                shortcut: Some(egui::KeyboardShortcut::new(
+                    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+                    egui::Key::K,
+                )),
+            },
+            Command {
+                id: "demo.bug_report",
+                title: "Create bug report".to_owned(),
+                category: "Demo".to_owned(),
+                shortcut: None,
+            },
+            Command {
+                id: "demo.keep_awake",
+                title: "Toggle keeping the display awake".to_owned(),
+                category: "Demo".to_owned(),
+                shortcut: None,
+            },
+        ]
+    }
+
+    fn input_middleware(&mut self) -> Vec<Box<dyn InputMiddleware>> {
+        vec![Box::new(InputBlocker::new([egui::Key::Escape]))]
+    }
+
+    fn is_command_enabled(&self, id: &str) -> bool {
+        match id {
+            "demo.increment_age" => self.age < 120,
+            "demo.clear_scribbles" => !self.strokes.iter().all(Vec::is_empty),
+            _ => true,
+        }
+    }
+
+    fn on_command(&mut self, ctx: &egui::Context, id: &str) {
+        match id {
+            "demo.increment_age" => self.age += 1,
+            "demo.clear_scribbles" => self.strokes.clear(),
+            "demo.bug_report" => bug_report::create_bug_report(ctx, None),
+            "demo.keep_awake" => {
+                self.keep_awake = !self.keep_awake;
+                sleep_inhibit::set_sleep_inhibited(ctx, self.keep_awake);
+            }
+            _ => log::warn!("unknown command {id}"),
+        }
+    }
+
+    fn bug_report_context(&self) -> String {
+        format!(
+            "name: {}\nage: {}\nscribbles: {}\n",
+            self.name,
+            self.age,
+            self.strokes.len()
+        )
+    }
+
+    fn on_open_url(&mut self, _url: &egui::OpenUrl) -> UrlDecision {
+        self.url_decision
+    }
+
+    fn on_paste(&mut self, content: &ClipboardContent) -> bool {
+        self.pasted_html = content.html.as_ref().map(String::len);
+        false
+    }
+
+    fn on_device_event(
+        &mut self,
+        _device_id: DeviceId,
+        event: &DeviceEvent,
+    ) -> Option<EventResult> {
+        let focused = || {
+            self.window
+                .as_ref()
+                .is_some_and(|window| window.has_focus())
+        };
+        let changed = match event {
+            DeviceEvent::Key(RawKeyEvent {
+                physical_key: PhysicalKey::Code(code),
+                state,
+            }) => match state {
+                // Releases count even when unfocused, so that no key sticks.
+                ElementState::Pressed => focused() && self.raw_keys.insert(*code),
+                ElementState::Released => self.raw_keys.remove(code),
+            },
+            DeviceEvent::MouseWheel { delta } if focused() => {
+                self.raw_wheel += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(delta) => delta.y as f32,
+                };
+                true
+            }
+            _ => false,
+        };
+        changed.then_some(EventResult::RepaintNext)
+    }
+
+    fn on_inactivity_reset(&mut self) {
+        log::info!("resetting after inactivity");
+        self.name = "John Doe".to_owned();
+        self.age = 42;
+        self.strokes.clear();
+        self.distance = 0.;
+    }
+
+    fn on_exit(&mut self, forced: bool) {
+        if !forced && !self.strokes.is_empty() {
+            log::info!("exiting with {} strokes", self.strokes.len());
+        }
+    }
+
+    fn on_gpu_ready(&mut self, adapter_info: &wgpu::AdapterInfo, downlevel_fallback: bool) {
+        self.gpu_notice = if downlevel_fallback {
+            Some(format!(
+                "Running on {} ({:?}) in compatibility mode",
+                adapter_info.name, adapter_info.backend
+            ))
+        } else if gpu::is_software(adapter_info) {
+            Some(format!(
+                "Running on the software renderer {}, expect reduced performance",
+                adapter_info.name
+            ))
+        } else {
+            None
+        };
+    }
+
+    fn on_software_rendering(&mut self) {
+        self.gpu_notice =
+            Some("Software rendering: no GPU was found, expect it to be slow".to_owned());
+    }
+
+    fn on_memory_warning(&mut self) {
+        self.strokes.shrink_to_fit();
+    }
+
+    fn on_titlebar_inset(&mut self, inset: f32) {
+        self.titlebar_inset = inset;
+    }
+
+    fn on_orientation_changed(&mut self, orientation: Orientation) {
+        log::debug!("orientation changed to {orientation:?}");
+        self.orientation = orientation;
+    }
+
+    fn on_monitors_changed(&mut self, monitors: &Monitors) {
+        self.monitors = monitors.clone();
+    }
+
+    fn on_quality_changed(&mut self, change: &QualityChange) {
+        self.quality_change = Some(*change);
+    }
+
+    fn on_idle(&mut self, idle: bool) {
+        log::debug!("app is {}", if idle { "idle" } else { "active" });
+    }
+
+    fn on_gpu_textures(&mut self, gpu: &mut GpuTextures) {
+        if self.texture_demos.native {
+            let texture = create_gradient_texture(gpu.device, gpu.queue);
+            self.native_texture = Some(gpu.register(&texture, wgpu::FilterMode::Linear));
+        }
+        if self.texture_demos.scene {
+            self.scene = Some(scene::TriangleScene::new(gpu));
+        }
+        if self.texture_demos.compressed {
+            match upload_compressed_demo(gpu.device, gpu.queue, &self.texture_demos) {
+                Ok(uploaded) => {
+                    let id = gpu.register(&uploaded.texture, wgpu::FilterMode::Linear);
+                    let size = uploaded.uv.max.to_vec2()
+                        * egui::vec2(
+                            uploaded.texture.width() as f32,
+                            uploaded.texture.height() as f32,
+                        );
+                    self.compressed_texture = Some((id, size, uploaded.uv));
+                }
+                Err(err) => log::error!("compressed texture demo: {err:#}"),
+            }
+        }
+    }
+
+    fn prepare_frame(
+        &mut self,
+        ctx: &egui::Context,
+        gpu: &mut GpuTextures,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if let Some(scene) = &mut self.scene {
+            scene.update(ctx, gpu);
+            scene.render(encoder);
+        }
+    }
+
+    fn on_window_ready(&mut self, window: &Arc<Window>) {
+        self.window = Some(window.clone());
+    }
+}
+
+fn create_gradient_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    const SIZE: u32 = 64;
+    let texels: Vec<u8> = (0..SIZE * SIZE)
+        .flat_map(|i| {
+            let (x, y) = (i % SIZE, i / SIZE);
+            [(x * 255 / SIZE) as u8, (y * 255 / SIZE) as u8, 128, 255]
+        })
+        .collect();
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("native texture demo"),
+            size: wgpu::Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &texels,
+    )
+}
+
+fn upload_compressed_demo(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    demos: &TextureDemos,
+) -> anyhow::Result<compressed_texture::UploadedTexture> {
+    #[cfg(feature = "texture-containers")]
+    let image = match &demos.compressed_file {
+        Some(path) => compressed_texture::read_container(path)?,
+        None => compressed_texture::demo_image(),
+    };
+    #[cfg(not(feature = "texture-containers"))]
+    let image = {
+        let _ = demos;
+        compressed_texture::demo_image()
+    };
+    let uploaded = compressed_texture::upload(device, queue, &image)?;
+    log::info!(
+        "uploaded a {}x{} {:?} image with {} mip levels{}",
+        image.size[0],
+        image.size[1],
+        image.format,
+        image.levels.len(),
+        if uploaded.decoded {
+            ", decoded to RGBA8"
+        } else {
+            ""
+        }
+    );
+    Ok(uploaded)
+}
+
+fn magnifier_ui(ui: &mut egui::Ui) {
+    let mut settings = magnifier::settings(ui.ctx());
+    let before = settings;
+    ui.checkbox(&mut settings.enabled, "Enabled (Ctrl+Alt+M)");
+    ui.add(
+        egui::Slider::new(
+            &mut settings.zoom,
+            magnifier::MIN_ZOOM..=magnifier::MAX_ZOOM,
+        )
+        .suffix("×"),
+    );
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut settings.filter, wgpu::FilterMode::Nearest, "Nearest");
+        ui.radio_value(&mut settings.filter, wgpu::FilterMode::Linear, "Linear");
+    });
+    ui.checkbox(&mut settings.docked, "Docked panel");
+    if settings != before {
+        magnifier::set_settings(ui.ctx(), settings);
+    }
+}
+
+fn quality_ui(ui: &mut egui::Ui, change: Option<&QualityChange>) {
+    let mut settings = adaptive_quality::settings(ui.ctx());
+    let before = settings;
+    let mut enabled = settings.budget.is_some();
+    ui.checkbox(&mut enabled, "Keep within a frame budget");
+    let mut budget_ms = settings
+        .budget
+        .map_or(16., |budget| budget.as_secs_f32() * 1000.);
+    ui.add_enabled(
+        enabled,
+        egui::Slider::new(&mut budget_ms, 4.0..=50.0).suffix(" ms"),
+    );
+    settings.budget = enabled.then(|| Duration::from_secs_f32(budget_ms / 1000.));
+    ui.label("Pinned:");
+    ui.horizontal_wrapped(|ui| {
+        for step in Step::LADDER {
+            ui.checkbox(&mut settings.pinned[step as usize], step.name());
+        }
+    });
+    if settings != before {
+        adaptive_quality::set_settings(ui.ctx(), settings);
+    }
+    if let Some(change) = change {
+        ui.label(format!(
+            "{} {}, level {}{}",
+            if change.degraded {
+                "Reduced"
+            } else {
+                "Restored"
+            },
+            change.step.name(),
+            change.level,
+            change.p95.map_or_else(String::new, |p95| format!(
+                " (p95 {:.1} ms)",
+                p95.as_secs_f32() * 1000.
+            ))
+        ));
+    }
+}
+
+fn frame_metrics_ui(ui: &mut egui::Ui, metrics: &FrameMetrics) {
+    let ms = |duration: Duration| format!("{:.2} ms", duration.as_secs_f64() * 1000.);
+    egui::Grid::new("frame_metrics").show(ui, |ui| {
+        ui.label("CPU");
+        ui.label(format!(
+            "{} (smoothed {})",
+            ms(metrics.cpu_time),
+            ms(metrics.smoothed_cpu_time)
+        ));
+        ui.end_row();
+        if let (Some(gpu_time), Some(smoothed)) = (metrics.gpu_time, metrics.smoothed_gpu_time) {
+            ui.label("GPU");
+            ui.label(format!("{} (smoothed {})", ms(gpu_time), ms(smoothed)));
+            ui.end_row();
+        }
+        ui.label("Events");
+        ui.label(metrics.events.to_string());
+        ui.end_row();
+        ui.label("Frames in the last second");
+        ui.label(metrics.frames_last_second.to_string());
+        ui.end_row();
+        ui.label("Primitives");
+        ui.label(metrics.clipped_primitives.to_string());
+        ui.end_row();
+        ui.label("Textures set / freed");
+        ui.label(format!(
+            "{} / {}",
+            metrics.textures_set, metrics.textures_freed
+        ));
+        ui.end_row();
+        ui.label("Elided");
+        ui.label(if metrics.elided { "yes" } else { "no" });
+        ui.end_row();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_wgpu_winit_example::testing::TestApp;
+
+    use super::*;
+
+    #[test]
+    fn typing_a_name_updates_the_greeting() {
+        let Some(mut test_app) = TestApp::new(AppState::new(false)) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        let frame = test_app.step(Vec::new());
+        // The window starts collapsed.
+        let show = frame.widget("Show").unwrap();
+        test_app.click_widget(&show);
+        let frame = test_app.settle();
+        let name = frame.widget("Your name: ").unwrap();
+        assert_eq!(name.role, egui::accesskit::Role::TextInput);
+        assert_eq!(name.value.as_deref(), Some("John Doe"));
+        // The cursor lands at the end.
+        let frame = test_app.type_into(&name, "!");
+        let name = frame.widget("Your name: ").unwrap();
+        assert_eq!(name.value.as_deref(), Some("John Doe!"));
+        let text = frame.find_text("John Doe!").unwrap();
+        assert!(name.rect.unwrap().contains(text.center()));
+        assert!(frame.find_text("Hello 'John Doe!', age 42").is_some());
+        let increment = frame.widget("Increment").unwrap();
+        let frame = test_app.click_widget(&increment);
+        assert!(frame.find_text("Hello 'John Doe!', age 43").is_some());
+    }
+
+    #[test]
+    fn increment_changes_label() {
+        let Some(mut test_app) = TestApp::new(AppState::new(false)) else {
+            return;
+        };
+        // The window starts collapsed, and is only laid out in the first frame.
+        test_app.step(Vec::new());
+        let title = test_app.step(Vec::new()).find_text("My Window").unwrap();
+        // A double click on the title expands it.
+        test_app.click(title.center());
+        test_app.click(title.center());
+        let frame = test_app.settle();
+        assert!(frame.find_text("Hello 'John Doe', age 42").is_some());
+        let increment = frame.find_text("Increment").unwrap();
+        test_app.click(increment.center());
+        let frame = test_app.step(Vec::new());
+        assert!(frame.find_text("Hello 'John Doe', age 43").is_some());
+    }
+}
@@ -0,0 +1,140 @@
+//! A UI for `--hot-reload`. Build it with `cargo build` in this directory
+//! while the app runs, and the window picks up the new library.
+//!
+//! The functions below are the boundary with the app, see the app's
+//! `hot_reload` module. The UI runs in an egui context of its own: the input
+//! comes in and the tessellated output goes out as `hot_reload_abi` bytes.
+//! Everything else is free to change between reloads, except that the state
+//! has to read what the previous build saved.
+
+use std::{
+    ffi::{c_char, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use hot_reload_abi::{decode_input, encode_output, Output, ABI};
+
+/// Survives reloads through [`State::save`] and [`State::load`].
+#[derive(Default)]
+struct State {
+    name: String,
+    age: u32,
+}
+
+/// What the app holds between calls.
+struct Instance {
+    state: State,
+    ctx: egui::Context,
+}
+
+// The app moves the instance to its UI thread.
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<Instance>();
+};
+
+impl State {
+    /// One `key=value` line per field. Unknown keys are skipped, so fields
+    /// can be added; a value that does not parse refuses the reload.
+    fn load(bytes: &[u8]) -> Option<Self> {
+        let mut state = Self::default();
+        for line in std::str::from_utf8(bytes).ok()?.lines() {
+            match line.split_once('=')? {
+                ("name", name) => state.name = name.to_owned(),
+                ("age", age) => state.age = age.parse().ok()?,
+                _ => {}
+            }
+        }
+        Some(state)
+    }
+
+    fn save(&self) -> String {
+        format!("name={}\nage={}\n", self.name.replace('\n', " "), self.age)
+    }
+
+    fn update(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Hot reloaded UI");
+            ui.horizontal(|ui| {
+                ui.label("Your name: ");
+                ui.text_edit_singleline(&mut self.name);
+            });
+            ui.add(egui::Slider::new(&mut self.age, 0..=120).text("age"));
+            ui.label(format!("Hello '{}', age {}", self.name, self.age));
+        });
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn egui_hot_abi() -> *const c_char {
+    ABI.as_ptr()
+}
+
+/// # Safety
+///
+/// `state` is readable for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn egui_hot_new(state: *const u8, len: usize) -> *mut c_void {
+    let bytes = std::slice::from_raw_parts(state, len);
+    match State::load(bytes) {
+        Some(state) => {
+            let ctx = egui::Context::default();
+            Box::into_raw(Box::new(Instance { state, ctx })).cast()
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Runs a frame for the encoded input and writes the encoded output. Panics
+/// must not unwind into the app, so they end up as `false`, as does input
+/// that does not decode.
+///
+/// # Safety
+///
+/// `app` came from [`egui_hot_new`], `input` is readable for `len` bytes,
+/// and `write` accepts `out`.
+#[no_mangle]
+pub unsafe extern "C" fn egui_hot_update(
+    app: *mut c_void,
+    input: *const u8,
+    len: usize,
+    write: extern "C" fn(*mut c_void, *const u8, usize),
+    out: *mut c_void,
+) -> bool {
+    let Instance { state, ctx } = &mut *app.cast::<Instance>();
+    let Some(raw_input) = decode_input(std::slice::from_raw_parts(input, len)) else {
+        return false;
+    };
+    let output = catch_unwind(AssertUnwindSafe(|| {
+        let full_output = ctx.run(raw_input, |ctx| state.update(ctx));
+        encode_output(&Output::new(ctx, full_output))
+    }));
+    match output {
+        Ok(bytes) => {
+            write(out, bytes.as_ptr(), bytes.len());
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// # Safety
+///
+/// `app` came from [`egui_hot_new`], and `write` accepts `out`.
+#[no_mangle]
+pub unsafe extern "C" fn egui_hot_save(
+    app: *const c_void,
+    write: extern "C" fn(*mut c_void, *const u8, usize),
+    out: *mut c_void,
+) {
+    let bytes = (*app.cast::<Instance>()).state.save();
+    write(out, bytes.as_ptr(), bytes.len());
+}
+
+/// # Safety
+///
+/// `app` came from [`egui_hot_new`] and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn egui_hot_drop(app: *mut c_void) {
+    drop(Box::from_raw(app.cast::<Instance>()));
+}
@@ -0,0 +1,55 @@
+//! A client for the app's `--remote-control` server:
+//!
+//! ```sh
+//! REMOTE_CONTROL_TOKEN=secret cargo run --example remote_control -- tap 120 80
+//! REMOTE_CONTROL_TOKEN=secret cargo run --example remote_control -- screenshot shot.png
+//! REMOTE_CONTROL_TOKEN=secret cargo run --example remote_control -- state
+//! ```
+//!
+//! Any other arguments are sent as one request, e.g. `key Enter`. The server
+//! address comes from `REMOTE_CONTROL_ADDRESS`, by default `127.0.0.1:7870`.
+
+use std::{
+    io::{BufRead as _, BufReader, Read as _, Write as _},
+    net::TcpStream,
+};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address =
+        std::env::var("REMOTE_CONTROL_ADDRESS").unwrap_or_else(|_| "127.0.0.1:7870".to_owned());
+    let token = std::env::var("REMOTE_CONTROL_TOKEN")
+        .map_err(|_| "set REMOTE_CONTROL_TOKEN to the app's token")?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (request, output) = match &args[..] {
+        [] => return Err("usage: remote_control <request...> | screenshot <file.png>".into()),
+        [screenshot, path] if screenshot == "screenshot" => ("screenshot".to_owned(), Some(path)),
+        args => (args.join(" "), None),
+    };
+
+    let mut stream = TcpStream::connect(&address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut reply =
+        |stream: &mut TcpStream, line: &str| -> Result<String, Box<dyn std::error::Error>> {
+            writeln!(stream, "{line}")?;
+            let mut reply = String::new();
+            reader.read_line(&mut reply)?;
+            let reply = reply.trim_end().to_owned();
+            match reply.strip_prefix("error ") {
+                Some(err) => Err(err.into()),
+                None => Ok(reply),
+            }
+        };
+    reply(&mut stream, &format!("auth {token}"))?;
+    let answer = reply(&mut stream, &request)?;
+
+    match (output, answer.strip_prefix("ok png ")) {
+        (Some(path), Some(len)) => {
+            let mut png = vec![0; len.parse()?];
+            reader.read_exact(&mut png)?;
+            std::fs::write(path, png)?;
+            println!("saved {path}");
+        }
+        _ => println!("{answer}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,703 @@
+//! What crosses between the app and a hot reloaded UI library, see the app's
+//! `hot_reload` module. Each side has an egui of its own, built with its own
+//! features, so no egui type crosses: the app encodes the frame's input to
+//! bytes, and the library answers with its tessellated meshes, its texture
+//! changes and the parts of its platform output the app applies.
+
+use std::{ffi::CStr, sync::Arc, time::Duration};
+
+use egui::{
+    epaint::{ClippedPrimitive, ImageDelta, Primitive, Vertex},
+    Color32, ColorImage, CursorIcon, Event, ImageData, Key, Mesh, Modifiers, MouseWheelUnit,
+    OpenUrl, PointerButton, Pos2, RawInput, Rect, TextureFilter, TextureId, TextureOptions,
+    TextureWrapMode, TexturesDelta, Vec2, ViewportId, ViewportInfo,
+};
+
+/// What the library's `egui_hot_abi` returns. Bumped whenever the functions
+/// or the encoding change.
+pub const ABI: &CStr = c"egui-hot-reload/2";
+
+/// The library's answer for one frame.
+#[derive(Default)]
+pub struct Output {
+    /// In points, with the library's texture ids.
+    pub meshes: Vec<(Rect, Mesh)>,
+    pub textures_delta: TexturesDelta,
+    pub cursor_icon: CursorIcon,
+    pub copied_text: String,
+    pub open_url: Option<OpenUrl>,
+    pub repaint_after: Option<Duration>,
+}
+
+impl Output {
+    /// Tessellates the library's frame.
+    pub fn new(ctx: &egui::Context, full_output: egui::FullOutput) -> Self {
+        let repaint_after = full_output
+            .viewport_output
+            .get(&ViewportId::ROOT)
+            .map(|viewport| viewport.repaint_delay)
+            .filter(|delay| *delay != Duration::MAX);
+        let meshes = ctx
+            .tessellate(full_output.shapes, full_output.pixels_per_point)
+            .into_iter()
+            .filter_map(
+                |ClippedPrimitive {
+                     clip_rect,
+                     primitive,
+                 }| match primitive {
+                    Primitive::Mesh(mesh) => Some((clip_rect, mesh)),
+                    // Paint callbacks hold code from the library.
+                    Primitive::Callback(_) => None,
+                },
+            )
+            .collect();
+        let platform_output = full_output.platform_output;
+        Self {
+            meshes,
+            textures_delta: full_output.textures_delta,
+            cursor_icon: platform_output.cursor_icon,
+            copied_text: platform_output.copied_text,
+            open_url: platform_output.open_url,
+            repaint_after,
+        }
+    }
+}
+
+/// The frame's input for the library. Only the screen, the time, the focus,
+/// the modifiers and the pointer, keyboard, text and clipboard events
+/// cross; touch, IME and file drops do not.
+pub fn encode_input(raw_input: &RawInput, pixels_per_point: f32) -> Vec<u8> {
+    let mut w = Writer::default();
+    let screen_rect = raw_input.screen_rect.unwrap_or(Rect::ZERO);
+    w.rect(screen_rect);
+    w.f32(pixels_per_point);
+    w.f64(raw_input.time.unwrap_or(-1.));
+    w.f32(raw_input.predicted_dt);
+    w.modifiers(raw_input.modifiers);
+    w.bool(raw_input.focused);
+    let events: Vec<&Event> = raw_input
+        .events
+        .iter()
+        .filter(|event| encodable(event))
+        .collect();
+    w.len(events.len());
+    for event in events {
+        w.event(event);
+    }
+    w.0
+}
+
+pub fn decode_input(bytes: &[u8]) -> Option<RawInput> {
+    let mut r = Reader(bytes);
+    let screen_rect = r.rect()?;
+    let pixels_per_point = r.f32()?;
+    let time = r.f64()?;
+    let predicted_dt = r.f32()?;
+    let modifiers = r.modifiers()?;
+    let focused = r.bool()?;
+    let events = (0..r.len()?)
+        .map(|_| r.event())
+        .collect::<Option<Vec<_>>>()?;
+    r.finish()?;
+    let mut raw_input = RawInput {
+        screen_rect: Some(screen_rect),
+        time: (time >= 0.).then_some(time),
+        predicted_dt,
+        modifiers,
+        events,
+        focused,
+        ..Default::default()
+    };
+    raw_input.viewports.insert(
+        ViewportId::ROOT,
+        ViewportInfo {
+            native_pixels_per_point: Some(pixels_per_point),
+            focused: Some(focused),
+            ..Default::default()
+        },
+    );
+    Some(raw_input)
+}
+
+pub fn encode_output(output: &Output) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.len(output.meshes.len());
+    for (clip_rect, mesh) in &output.meshes {
+        w.rect(*clip_rect);
+        w.texture_id(mesh.texture_id);
+        w.len(mesh.indices.len());
+        for &index in &mesh.indices {
+            w.u32(index);
+        }
+        w.len(mesh.vertices.len());
+        for vertex in &mesh.vertices {
+            w.pos(vertex.pos);
+            w.pos(vertex.uv);
+            w.color(vertex.color);
+        }
+    }
+    w.len(output.textures_delta.set.len());
+    for (id, delta) in &output.textures_delta.set {
+        w.texture_id(*id);
+        w.image_delta(delta);
+    }
+    w.len(output.textures_delta.free.len());
+    for id in &output.textures_delta.free {
+        w.texture_id(*id);
+    }
+    let cursor_icon = CursorIcon::ALL
+        .iter()
+        .position(|icon| *icon == output.cursor_icon)
+        .unwrap_or(0);
+    w.len(cursor_icon);
+    w.str(&output.copied_text);
+    match &output.open_url {
+        Some(open_url) => {
+            w.bool(true);
+            w.str(&open_url.url);
+            w.bool(open_url.new_tab);
+        }
+        None => w.bool(false),
+    }
+    w.f64(
+        output
+            .repaint_after
+            .map_or(-1., |delay| delay.as_secs_f64()),
+    );
+    w.0
+}
+
+pub fn decode_output(bytes: &[u8]) -> Option<Output> {
+    let mut r = Reader(bytes);
+    let meshes = (0..r.len()?)
+        .map(|_| {
+            let clip_rect = r.rect()?;
+            let texture_id = r.texture_id()?;
+            let indices = (0..r.len()?).map(|_| r.u32()).collect::<Option<Vec<_>>>()?;
+            let vertices = (0..r.len()?)
+                .map(|_| {
+                    Some(Vertex {
+                        pos: r.pos()?,
+                        uv: r.pos()?,
+                        color: r.color()?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let mesh = Mesh {
+                indices,
+                vertices,
+                texture_id,
+            };
+            Some((clip_rect, mesh))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let set = (0..r.len()?)
+        .map(|_| Some((r.texture_id()?, r.image_delta()?)))
+        .collect::<Option<Vec<_>>>()?;
+    let free = (0..r.len()?)
+        .map(|_| r.texture_id())
+        .collect::<Option<Vec<_>>>()?;
+    let cursor_icon = *CursorIcon::ALL.get(r.len()?)?;
+    let copied_text = r.string()?;
+    let open_url = match r.bool()? {
+        true => Some(OpenUrl {
+            url: r.string()?,
+            new_tab: r.bool()?,
+        }),
+        false => None,
+    };
+    let repaint_after = r.f64()?;
+    r.finish()?;
+    Some(Output {
+        meshes,
+        textures_delta: TexturesDelta { set, free },
+        cursor_icon,
+        copied_text,
+        open_url,
+        repaint_after: (repaint_after >= 0.).then(|| Duration::from_secs_f64(repaint_after)),
+    })
+}
+
+fn encodable(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Copy
+            | Event::Cut
+            | Event::Paste(_)
+            | Event::Text(_)
+            | Event::Key { .. }
+            | Event::PointerMoved(_)
+            | Event::PointerButton { .. }
+            | Event::PointerGone
+            | Event::Zoom(_)
+            | Event::MouseWheel { .. }
+            | Event::WindowFocused(_)
+    )
+}
+
+const POINTER_BUTTONS: [PointerButton; 5] = [
+    PointerButton::Primary,
+    PointerButton::Secondary,
+    PointerButton::Middle,
+    PointerButton::Extra1,
+    PointerButton::Extra2,
+];
+
+const FILTERS: [TextureFilter; 2] = [TextureFilter::Nearest, TextureFilter::Linear];
+
+const WRAP_MODES: [TextureWrapMode; 3] = [
+    TextureWrapMode::ClampToEdge,
+    TextureWrapMode::Repeat,
+    TextureWrapMode::MirroredRepeat,
+];
+
+fn index_of<T: PartialEq>(all: &[T], value: &T) -> u8 {
+    all.iter().position(|v| v == value).unwrap_or(0) as u8
+}
+
+/// Little-endian fields, lengths as `u32`.
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value.into());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, text: &str) {
+        self.len(text.len());
+        self.0.extend_from_slice(text.as_bytes());
+    }
+
+    fn pos(&mut self, pos: Pos2) {
+        self.f32(pos.x);
+        self.f32(pos.y);
+    }
+
+    fn vec(&mut self, vec: Vec2) {
+        self.f32(vec.x);
+        self.f32(vec.y);
+    }
+
+    fn rect(&mut self, rect: Rect) {
+        self.pos(rect.min);
+        self.pos(rect.max);
+    }
+
+    fn color(&mut self, color: Color32) {
+        self.0.extend_from_slice(&color.to_array());
+    }
+
+    fn modifiers(&mut self, modifiers: Modifiers) {
+        let Modifiers {
+            alt,
+            ctrl,
+            shift,
+            mac_cmd,
+            command,
+        } = modifiers;
+        let bits = [alt, ctrl, shift, mac_cmd, command]
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &set)| bits | (u8::from(set) << i));
+        self.u8(bits);
+    }
+
+    fn key(&mut self, key: Key) {
+        self.str(key.name());
+    }
+
+    fn texture_id(&mut self, id: TextureId) {
+        match id {
+            TextureId::Managed(id) => {
+                self.u8(0);
+                self.u64(id);
+            }
+            TextureId::User(id) => {
+                self.u8(1);
+                self.u64(id);
+            }
+        }
+    }
+
+    /// Always as RGBA, which is what font images become on upload too.
+    fn image_delta(&mut self, delta: &ImageDelta) {
+        let ImageDelta {
+            image,
+            options,
+            pos,
+        } = delta;
+        let size = image.size();
+        self.len(size[0]);
+        self.len(size[1]);
+        match image {
+            ImageData::Color(image) => {
+                for pixel in &image.pixels {
+                    self.color(*pixel);
+                }
+            }
+            ImageData::Font(image) => {
+                for pixel in image.srgba_pixels(None) {
+                    self.color(pixel);
+                }
+            }
+        }
+        self.u8(index_of(&FILTERS, &options.magnification));
+        self.u8(index_of(&FILTERS, &options.minification));
+        self.u8(index_of(&WRAP_MODES, &options.wrap_mode));
+        match options.mipmap_mode {
+            Some(filter) => self.u8(1 + index_of(&FILTERS, &filter)),
+            None => self.u8(0),
+        }
+        match pos {
+            Some([x, y]) => {
+                self.bool(true);
+                self.len(*x);
+                self.len(*y);
+            }
+            None => self.bool(false),
+        }
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Copy => self.u8(0),
+            Event::Cut => self.u8(1),
+            Event::Paste(text) => {
+                self.u8(2);
+                self.str(text);
+            }
+            Event::Text(text) => {
+                self.u8(3);
+                self.str(text);
+            }
+            Event::Key {
+                key,
+                physical_key,
+                pressed,
+                repeat,
+                modifiers,
+            } => {
+                self.u8(4);
+                self.key(*key);
+                self.bool(physical_key.is_some());
+                if let Some(physical_key) = physical_key {
+                    self.key(*physical_key);
+                }
+                self.bool(*pressed);
+                self.bool(*repeat);
+                self.modifiers(*modifiers);
+            }
+            Event::PointerMoved(pos) => {
+                self.u8(5);
+                self.pos(*pos);
+            }
+            Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                self.u8(6);
+                self.pos(*pos);
+                self.u8(index_of(&POINTER_BUTTONS, button));
+                self.bool(*pressed);
+                self.modifiers(*modifiers);
+            }
+            Event::PointerGone => self.u8(7),
+            Event::Zoom(factor) => {
+                self.u8(8);
+                self.f32(*factor);
+            }
+            Event::MouseWheel {
+                unit,
+                delta,
+                modifiers,
+            } => {
+                self.u8(9);
+                self.u8(match unit {
+                    MouseWheelUnit::Point => 0,
+                    MouseWheelUnit::Line => 1,
+                    MouseWheelUnit::Page => 2,
+                });
+                self.vec(*delta);
+                self.modifiers(*modifiers);
+            }
+            Event::WindowFocused(focused) => {
+                self.u8(10);
+                self.bool(*focused);
+            }
+            _ => unreachable!("filtered by `encodable`"),
+        }
+    }
+}
+
+/// Reads what [`Writer`] wrote; `None` once the bytes do not fit.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn finish(&self) -> Option<()> {
+        self.0.is_empty().then_some(())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[byte]| byte)
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        self.u32().map(|len| len as usize)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.bytes().map(f64::from_le_bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.len()?;
+        if len > self.0.len() {
+            return None;
+        }
+        let (text, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(text.to_vec()).ok()
+    }
+
+    fn pos(&mut self) -> Option<Pos2> {
+        Some(Pos2::new(self.f32()?, self.f32()?))
+    }
+
+    fn vec(&mut self) -> Option<Vec2> {
+        Some(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn rect(&mut self) -> Option<Rect> {
+        Some(Rect::from_min_max(self.pos()?, self.pos()?))
+    }
+
+    fn color(&mut self) -> Option<Color32> {
+        let [r, g, b, a] = self.bytes()?;
+        Some(Color32::from_rgba_premultiplied(r, g, b, a))
+    }
+
+    fn modifiers(&mut self) -> Option<Modifiers> {
+        let bits = self.u8()?;
+        let set = |i: u8| bits & (1 << i) != 0;
+        Some(Modifiers {
+            alt: set(0),
+            ctrl: set(1),
+            shift: set(2),
+            mac_cmd: set(3),
+            command: set(4),
+        })
+    }
+
+    fn key(&mut self) -> Option<Key> {
+        Key::from_name(&self.string()?)
+    }
+
+    fn texture_id(&mut self) -> Option<TextureId> {
+        match self.u8()? {
+            0 => Some(TextureId::Managed(self.u64()?)),
+            1 => Some(TextureId::User(self.u64()?)),
+            _ => None,
+        }
+    }
+
+    fn image_delta(&mut self) -> Option<ImageDelta> {
+        let size = [self.len()?, self.len()?];
+        let pixel_count = size[0].checked_mul(size[1])?;
+        if pixel_count.checked_mul(4)? > self.0.len() {
+            return None;
+        }
+        let pixels = (0..pixel_count)
+            .map(|_| self.color())
+            .collect::<Option<Vec<_>>>()?;
+        let filter = |index: u8| FILTERS.get(usize::from(index)).copied();
+        let options = TextureOptions {
+            magnification: filter(self.u8()?)?,
+            minification: filter(self.u8()?)?,
+            wrap_mode: *WRAP_MODES.get(usize::from(self.u8()?))?,
+            mipmap_mode: match self.u8()? {
+                0 => None,
+                index => Some(filter(index - 1)?),
+            },
+        };
+        let pos = match self.bool()? {
+            true => Some([self.len()?, self.len()?]),
+            false => None,
+        };
+        Some(ImageDelta {
+            image: ImageData::Color(Arc::new(ColorImage { size, pixels })),
+            options,
+            pos,
+        })
+    }
+
+    fn event(&mut self) -> Option<Event> {
+        Some(match self.u8()? {
+            0 => Event::Copy,
+            1 => Event::Cut,
+            2 => Event::Paste(self.string()?),
+            3 => Event::Text(self.string()?),
+            4 => Event::Key {
+                key: self.key()?,
+                physical_key: match self.bool()? {
+                    true => Some(self.key()?),
+                    false => None,
+                },
+                pressed: self.bool()?,
+                repeat: self.bool()?,
+                modifiers: self.modifiers()?,
+            },
+            5 => Event::PointerMoved(self.pos()?),
+            6 => Event::PointerButton {
+                pos: self.pos()?,
+                button: *POINTER_BUTTONS.get(usize::from(self.u8()?))?,
+                pressed: self.bool()?,
+                modifiers: self.modifiers()?,
+            },
+            7 => Event::PointerGone,
+            8 => Event::Zoom(self.f32()?),
+            9 => Event::MouseWheel {
+                unit: match self.u8()? {
+                    0 => MouseWheelUnit::Point,
+                    1 => MouseWheelUnit::Line,
+                    2 => MouseWheelUnit::Page,
+                    _ => return None,
+                },
+                delta: self.vec()?,
+                modifiers: self.modifiers()?,
+            },
+            10 => Event::WindowFocused(self.bool()?),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_and_output_round_trip() {
+        let modifiers = Modifiers::SHIFT | Modifiers::COMMAND;
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::new(0., 20.),
+                Vec2::new(400., 300.),
+            )),
+            time: Some(1.5),
+            modifiers,
+            focused: true,
+            events: vec![
+                Event::Text("é".to_owned()),
+                Event::Key {
+                    key: Key::A,
+                    physical_key: None,
+                    pressed: true,
+                    repeat: false,
+                    modifiers,
+                },
+                Event::PointerButton {
+                    pos: Pos2::new(3., 4.),
+                    button: PointerButton::Secondary,
+                    pressed: true,
+                    modifiers,
+                },
+                // Skipped.
+                Event::Ime(egui::ImeEvent::Enabled),
+                Event::MouseWheel {
+                    unit: MouseWheelUnit::Line,
+                    delta: Vec2::new(0., -1.),
+                    modifiers,
+                },
+            ],
+            ..Default::default()
+        };
+        let decoded = decode_input(&encode_input(&raw_input, 2.)).unwrap();
+        assert_eq!(decoded.screen_rect, raw_input.screen_rect);
+        assert_eq!(decoded.time, Some(1.5));
+        assert_eq!(decoded.modifiers, modifiers);
+        assert!(decoded.focused);
+        let mut events = raw_input.events.clone();
+        events.remove(3);
+        assert_eq!(decoded.events, events);
+        assert_eq!(
+            decoded.viewports[&ViewportId::ROOT].native_pixels_per_point,
+            Some(2.)
+        );
+
+        let ctx = egui::Context::default();
+        let full_output = ctx.run(raw_input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.hyperlink("https://example.com");
+                ui.ctx().copy_text("copied".to_owned());
+            });
+        });
+        let output = Output::new(&ctx, full_output);
+        let bytes = encode_output(&output);
+        let decoded = decode_output(&bytes).unwrap();
+        assert_eq!(decoded.meshes, output.meshes);
+        assert_eq!(decoded.textures_delta.free, output.textures_delta.free);
+        let ids = |delta: &TexturesDelta| delta.set.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(ids(&decoded.textures_delta), ids(&output.textures_delta));
+        assert_eq!(decoded.copied_text, "copied");
+        assert_eq!(decoded.repaint_after, output.repaint_after);
+
+        // Cut short, or with something after the end, it does not decode.
+        assert!(decode_output(&bytes[..bytes.len() - 1]).is_none());
+        assert!(
+            decode_input(&[encode_input(&RawInput::default(), 1.), vec![0]].concat()).is_none()
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use std::{collections::VecDeque, num::NonZeroUsize};
+
+use egui::Id;
+use web_time::{Duration, Instant};
+
+/// Frames the p95 is taken over.
+const WINDOW: usize = 120;
+/// Frames needed after a change before the next decision, so that it is
+/// based on frames rendered with the new settings only.
+const MIN_SAMPLES: usize = 30;
+/// The p95 has to stay below this fraction of the budget for a step to be
+/// taken back.
+const HEADROOM: f64 = 0.7;
+const RELAX_HOLD: Duration = Duration::from_secs(3);
+const MAX_RELAX_HOLD: Duration = Duration::from_secs(60);
+
+fn settings_id() -> Id {
+    Id::new("adaptive_quality_settings")
+}
+
+/// What is given up, cheapest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Sets egui's animation time to zero.
+    Animations,
+    /// Limits egui to a single pass per frame.
+    Passes,
+    /// Renders at the surface's size instead of supersampling.
+    RenderScale,
+    /// Caps the frame rate at half the budget's rate.
+    FrameRate,
+}
+
+impl Step {
+    pub const LADDER: [Self; 4] = [
+        Self::Animations,
+        Self::Passes,
+        Self::RenderScale,
+        Self::FrameRate,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Animations => "animations",
+            Self::Passes => "multi-pass",
+            Self::RenderScale => "render scale",
+            Self::FrameRate => "frame rate",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QualitySettings {
+    /// The frame time to stay within; `None` turns adaptation off and
+    /// restores everything.
+    pub budget: Option<Duration>,
+    /// Knobs left as the app set them, indexed by [`Step`]. Pinning a step
+    /// that is in effect takes it back.
+    pub pinned: [bool; Step::LADDER.len()],
+}
+
+pub fn settings(ctx: &egui::Context) -> QualitySettings {
+    ctx.data(|d| d.get_temp(settings_id())).unwrap_or_default()
+}
+
+/// Applied after the next presented frame.
+pub fn set_settings(ctx: &egui::Context, settings: QualitySettings) {
+    ctx.data_mut(|d| d.insert_temp(settings_id(), settings));
+}
+
+/// A step taken or taken back.
+#[derive(Clone, Copy, Debug)]
+pub struct QualityChange {
+    pub step: Step,
+    pub degraded: bool,
+    /// Steps in effect after the change.
+    pub level: usize,
+    /// The frame time that triggered it, `None` when it was requested.
+    pub p95: Option<Duration>,
+}
+
+/// Shown in the frame overlay.
+#[derive(Clone, Copy, Debug)]
+pub struct QualityStatus {
+    pub budget: Duration,
+    pub p95: Option<Duration>,
+    pub level: usize,
+    pub last_change: Option<QualityChange>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Degrade,
+    Restore,
+}
+
+/// Decides when to step down or up from per-frame costs. Steps down as
+/// soon as the p95 exceeds the budget, and up only after it stayed well
+/// below it for a while; that while doubles each time a step up had to be
+/// undone, so a setting that cannot hold the budget is not retried every
+/// few seconds.
+pub struct QualityController {
+    budget: Duration,
+    frame_times: VecDeque<Duration>,
+    p95: Option<Duration>,
+    headroom_since: Option<Instant>,
+    last_restore: Option<Instant>,
+    relax_hold: Duration,
+}
+
+impl QualityController {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            frame_times: VecDeque::with_capacity(WINDOW),
+            p95: None,
+            headroom_since: None,
+            last_restore: None,
+            relax_hold: RELAX_HOLD,
+        }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Of the frames since the last change, once there are enough of them;
+    /// the one before that until then.
+    pub fn p95(&self) -> Option<Duration> {
+        self.p95
+    }
+
+    /// Records a frame's cost. `can_degrade` and `can_restore` say whether
+    /// there is a step left to take either way.
+    pub fn record(
+        &mut self,
+        now: Instant,
+        frame_time: Duration,
+        can_degrade: bool,
+        can_restore: bool,
+    ) -> Option<Decision> {
+        if self.frame_times.len() == WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        if self.frame_times.len() < MIN_SAMPLES {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.frame_times.iter().copied().collect();
+        sorted.sort_unstable();
+        let p95 = sorted[(sorted.len() - 1) * 95 / 100];
+        self.p95 = Some(p95);
+
+        let decision = if p95 > self.budget {
+            self.headroom_since = None;
+            if !can_degrade {
+                return None;
+            }
+            if self
+                .last_restore
+                .is_some_and(|restore| now.saturating_duration_since(restore) < self.relax_hold)
+            {
+                self.relax_hold = (self.relax_hold * 2).min(MAX_RELAX_HOLD);
+            }
+            Decision::Degrade
+        } else if p95.as_secs_f64() < self.budget.as_secs_f64() * HEADROOM {
+            let since = *self.headroom_since.get_or_insert(now);
+            if !can_restore || now.saturating_duration_since(since) < self.relax_hold {
+                return None;
+            }
+            self.last_restore = Some(now);
+            Decision::Restore
+        } else {
+            self.headroom_since = None;
+            return None;
+        };
+        self.frame_times.clear();
+        self.headroom_since = None;
+        Some(decision)
+    }
+
+    /// Forgets the frames so far, e.g. after the knobs changed for another
+    /// reason.
+    pub fn reset(&mut self) {
+        self.frame_times.clear();
+        self.headroom_since = None;
+    }
+}
+
+/// The value a step replaced, put back when it is taken back.
+#[derive(Clone, Copy, Debug)]
+pub enum Saved {
+    AnimationTime(f32),
+    MaxPasses(NonZeroUsize),
+    RenderScale(f32),
+    FrameRate,
+}
+
+/// The steps in effect, most recent last, and the frame cap they set.
+#[derive(Default)]
+pub struct AdaptiveQuality {
+    pub controller: Option<QualityController>,
+    pub active: Vec<(Step, Saved)>,
+    pub settings: QualitySettings,
+    pub frame_cap: Option<Duration>,
+    pub last_present: Option<Instant>,
+    pub last_change: Option<QualityChange>,
+}
+
+impl AdaptiveQuality {
+    pub fn is_active(&self, step: Step) -> bool {
+        self.active.iter().any(|(active, _)| *active == step)
+    }
+
+    /// Delays a repaint to keep to the frame cap.
+    pub fn paced(&self, when: Instant) -> Instant {
+        match (self.frame_cap, self.last_present) {
+            (Some(cap), Some(last_present)) => when.max(last_present + cap),
+            _ => when,
+        }
+    }
+
+    pub fn status(&self) -> Option<QualityStatus> {
+        self.controller.as_ref().map(|controller| QualityStatus {
+            budget: controller.budget(),
+            p95: controller.p95(),
+            level: self.active.len(),
+            last_change: self.last_change,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_micros(16_667);
+    const BUDGET: Duration = Duration::from_millis(16);
+
+    /// Feeds a trace of frame costs at 60 Hz through the ladder, starting at
+    /// `level`. Returns the decisions with the frame they were made at.
+    fn replay(
+        controller: &mut QualityController,
+        start: Instant,
+        level: &mut usize,
+        trace: impl IntoIterator<Item = Duration>,
+    ) -> Vec<(usize, Decision)> {
+        let mut decisions = Vec::new();
+        for (frame, cost) in trace.into_iter().enumerate() {
+            let now = start + FRAME * frame as u32;
+            let Some(decision) =
+                controller.record(now, cost, *level < Step::LADDER.len(), *level > 0)
+            else {
+                continue;
+            };
+            match decision {
+                Decision::Degrade => *level += 1,
+                Decision::Restore => *level -= 1,
+            }
+            decisions.push((frame, decision));
+        }
+        decisions
+    }
+
+    fn constant(cost_ms: u64, frames: usize) -> impl Iterator<Item = Duration> {
+        std::iter::repeat_n(Duration::from_millis(cost_ms), frames)
+    }
+
+    #[test]
+    fn escalates_one_step_per_window() {
+        let mut controller = QualityController::new(BUDGET);
+        let mut level = 0;
+        let decisions = replay(
+            &mut controller,
+            Instant::now(),
+            &mut level,
+            constant(25, 600),
+        );
+        // Each step waits for frames rendered with the previous one.
+        let frames: Vec<_> = decisions.iter().map(|(frame, _)| *frame).collect();
+        assert_eq!(frames, [29, 59, 89, 119]);
+        assert!(decisions
+            .iter()
+            .all(|(_, decision)| *decision == Decision::Degrade));
+        // Nothing is left to give up.
+        assert_eq!(level, Step::LADDER.len());
+        assert_eq!(controller.p95(), Some(Duration::from_millis(25)));
+    }
+
+    #[test]
+    fn a_few_slow_frames_are_ignored() {
+        let mut controller = QualityController::new(BUDGET);
+        let mut level = 0;
+        // One in 25 frames is slow, below the 5% the p95 ignores.
+        let trace =
+            (0..600).map(|frame| Duration::from_millis(if frame % 25 == 0 { 40 } else { 12 }));
+        assert!(replay(&mut controller, Instant::now(), &mut level, trace).is_empty());
+    }
+
+    #[test]
+    fn holds_inside_the_hysteresis_band() {
+        let mut controller = QualityController::new(BUDGET);
+        let mut level = 2;
+        // Between 70% of the budget and the budget: neither slow enough to
+        // degrade nor fast enough to restore, for a minute.
+        let trace = (0..3600).map(|frame| Duration::from_micros(11_500 + frame % 4 * 1_000));
+        assert!(replay(&mut controller, Instant::now(), &mut level, trace).is_empty());
+        assert_eq!(level, 2);
+    }
+
+    #[test]
+    fn restores_after_the_hold_and_backs_off() {
+        let start = Instant::now();
+        let mut controller = QualityController::new(BUDGET);
+        let mut level = 2;
+        let decisions = replay(&mut controller, start, &mut level, constant(5, 300));
+        // Restores once the first 30 frames showed headroom for 3 seconds.
+        assert_eq!(decisions, [(29 + 180, Decision::Restore)]);
+        assert_eq!(level, 1);
+
+        // The restored step cannot hold the budget: it is given up again as
+        // soon as slow frames are over 5% of those since the restore, and
+        // the next restore waits twice as long.
+        let start = start + FRAME * 300;
+        let decisions = replay(&mut controller, start, &mut level, constant(20, 6));
+        assert_eq!(decisions, [(5, Decision::Degrade)]);
+        let start = start + FRAME * 6;
+        let decisions = replay(&mut controller, start, &mut level, constant(5, 600));
+        assert_eq!(decisions, [(29 + 360, Decision::Restore)]);
+    }
+
+    #[test]
+    fn reset_forgets_frames() {
+        let mut controller = QualityController::new(BUDGET);
+        let mut level = 0;
+        let start = Instant::now();
+        assert!(replay(&mut controller, start, &mut level, constant(25, 29)).is_empty());
+        controller.reset();
+        assert!(replay(&mut controller, start, &mut level, constant(25, 29)).is_empty());
+    }
+
+    /// A frame recorder export (`--timing-export`) of the software renderer,
+    /// 240 frames of a light UI, 360 with 12000 circles more, then the light
+    /// UI again. Recorded with the quality fixed, so the heavy frames stay
+    /// heavy whatever the controller decides.
+    const RECORDED: &str = include_str!("../testdata/software-frames.csv");
+
+    /// The frames' start times and costs. The export has no acquire time,
+    /// so a frame's cost runs from its start to its submit.
+    fn recorded_trace() -> Vec<(Duration, Duration)> {
+        let seconds = |value: &str| Duration::from_secs_f64(value.parse().unwrap());
+        RECORDED
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let columns: Vec<&str> = line.split(',').collect();
+                let start = seconds(columns[1]);
+                (start, seconds(columns[6]) - start)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recorded_trace_degrades_while_heavy_and_restores_after() {
+        let trace = recorded_trace();
+        assert_eq!(trace.len(), 1101);
+        // Light frames take about 12 ms on the software renderer.
+        let budget = Duration::from_millis(20);
+        let mut controller = QualityController::new(budget);
+        let start = Instant::now();
+        let mut level = 0;
+        let mut decisions = Vec::new();
+        for (frame, (at, cost)) in trace.into_iter().enumerate() {
+            let Some(decision) =
+                controller.record(start + at, cost, level < Step::LADDER.len(), level > 0)
+            else {
+                continue;
+            };
+            match decision {
+                Decision::Degrade => level += 1,
+                Decision::Restore => level -= 1,
+            }
+            decisions.push((frame, decision));
+        }
+        // Steps down one window after another once the heavy frames begin,
+        // until nothing is left, and takes one step back once the light
+        // frames stayed well below the budget for the hold.
+        assert_eq!(
+            decisions,
+            [
+                (246, Decision::Degrade),
+                (276, Decision::Degrade),
+                (306, Decision::Degrade),
+                (336, Decision::Degrade),
+                (958, Decision::Restore),
+            ]
+        );
+        assert_eq!(level, Step::LADDER.len() - 1);
+    }
+}
@@ -1,166 +1,1466 @@
 use std::{
-    num::NonZeroU32,
-    sync::{Arc, Mutex},
-    time::Instant,
+    any::Any,
+    collections::VecDeque,
+    num::{NonZeroU32, NonZeroUsize},
+    sync::Arc,
 };
 
-use egui::{Vec2, ViewportId, ViewportInfo};
+use egui::{ViewportId, ViewportInfo};
 use egui_wgpu::ScreenDescriptor;
 use egui_winit::winit::{
     self,
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::DeviceEvent,
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    event_loop::{ActiveEventLoop, EventLoop},
+    raw_window_handle::RawWindowHandle,
     window::Window,
 };
+use web_time::{Duration, Instant};
 
+#[cfg(feature = "dialogs")]
+use crate::file_dialog;
+#[cfg(feature = "notifications")]
+use crate::notifications;
+#[cfg(feature = "remote-control")]
+use crate::remote_control::{self, RemoteControl, RemoteRequest};
+#[cfg(feature = "software-renderer")]
+use crate::software::SoftwarePainter;
 use crate::{
-    egui_renderer::EguiRenderer,
-    event::{EventResult, UserEvent},
-    AppState,
+    adaptive_quality::{
+        self, AdaptiveQuality, Decision, QualityChange, QualityController, QualitySettings, Saved,
+        Step,
+    },
+    benchmark::Benchmark,
+    bug_report::BugReporter,
+    capture::CaptureExclusion,
+    click_through::ClickThrough,
+    close::{self, ForceClose},
+    crash_report,
+    dock::{self, Dock},
+    document::DocumentTitle,
+    egui_renderer::{EguiRenderer, WgpuTarget},
+    embed::{self, HostHandle, HostRequest},
+    error::AppError,
+    event::{EventResult, EventSender, UserEvent},
+    event_trace::EventTrace,
+    frame_pacer::FramePacer,
+    frame_recorder::FrameRecorder,
+    frame_stats::FrameTimeline,
+    gpu::{self, GpuContext},
+    gpu_poll::GpuPoller,
+    gpu_timer::GpuTimer,
+    host_window::{self, HostWindow},
+    idle::{self, IdleTracker},
+    input_middleware::{self, EventLogger},
+    input_replay::{InputRecorder, InputReplay},
+    jump_list::JumpList,
+    kiosk::Kiosk,
+    latency::{InputClass, LatencyTracker},
+    letterbox::{self, DesignResolution, Letterbox},
+    loading::{self, LoadingApp, LoadingProgress},
+    magnifier::{self, Magnifier, MagnifierSource},
+    metrics::{self, Metrics},
+    mirror::{self, Mirror},
+    monitor_scale::MonitorScales,
+    monitors::MonitorTracker,
+    options::Options,
+    painter::{EguiPainter, PainterFactory, PainterFrontend},
+    pointer::PointerCoalescer,
+    render_thread::{RenderJob, RenderThread},
+    scheduler::{Action, Scheduler},
+    sleep_inhibit::{self, SleepInhibitor},
+    supersample::{self, Supersampler},
+    surface::RenderSurface,
+    tessellation_cache::TessellationCache,
+    titlebar::{self, TitlebarHitTest},
+    user_app::{GpuTextures, Orientation, SimpleApp, UserApp},
+    window_background,
+    window_shadow::{self, WindowShadow},
 };
 
-pub struct Renderer {
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+/// Height of a standard macOS titlebar in points. winit does not report it.
+const MACOS_TITLEBAR_HEIGHT: f32 = 28.;
+/// Window events kept while the GPU initializes; older ones are dropped.
+const MAX_PENDING_EVENTS: usize = 256;
+
+/// Window events that arrive while the GPU initializes, replayed once the
+/// renderer exists.
+#[derive(Default)]
+struct PendingEvents(VecDeque<winit::event::WindowEvent>);
+
+impl PendingEvents {
+    fn push(&mut self, event: &winit::event::WindowEvent) {
+        if self.0.len() == MAX_PENDING_EVENTS {
+            self.0.pop_front();
+        }
+        self.0.push_back(event.clone());
+    }
+
+    /// In the order they arrived. Exits if any of them did, and paints
+    /// otherwise.
+    fn replay(
+        &mut self,
+        mut on_window_event: impl FnMut(&winit::event::WindowEvent) -> EventResult,
+    ) -> EventResult {
+        let mut result = EventResult::RepaintNow;
+        for event in self.0.drain(..) {
+            if let EventResult::Exit = on_window_event(&event) {
+                result = EventResult::Exit;
+            }
+        }
+        result
+    }
+}
+
+pub struct Renderer<T> {
     surface_config: wgpu::SurfaceConfiguration,
-    surface: wgpu::Surface<'static>,
-    state: AppState,
+    /// `None` while the render thread owns the surface or while suspended.
+    surface: Option<RenderSurface>,
+    suspended: bool,
+    render_thread: Option<RenderThread>,
+    state: T,
     egui_renderer: EguiRenderer,
+    gpu_poller: GpuPoller,
+    gpu_timer: Option<GpuTimer>,
+    frame_recorder: Option<FrameRecorder>,
+    frame_limit: Option<u64>,
+    frames_presented: u64,
+    continuous_repaint: bool,
+    skip_unchanged_frames: bool,
+    force_paint: bool,
+    pending_surface_size: Option<[u32; 2]>,
+    /// Set after the surface reported itself outdated or lost.
+    reconfigure_surface: bool,
+    /// Set until the first frame after a scale factor change, which takes
+    /// the surface size from the window instead of the last Resized event.
+    scale_factor_changed: bool,
+    /// Whether egui consumed the last window event.
+    last_event_consumed: bool,
+    frame_pacer: Option<FramePacer>,
+    benchmark: Option<Benchmark>,
+    /// What the app registered through [`GpuTextures`], freed at exit.
+    app_textures: Vec<egui::TextureId>,
+    idle: Option<IdleTracker>,
+    kiosk: Option<Kiosk>,
+    sleep_inhibitor: SleepInhibitor,
+    orientation: Orientation,
+    titlebar_hit_test: Option<TitlebarHitTest>,
+    click_through: Option<ClickThrough>,
+    capture_exclusion: CaptureExclusion,
+    document_title: DocumentTitle,
+    bug_reporter: BugReporter,
+    monitors: MonitorTracker,
+    monitor_scales: MonitorScales,
+    dark_titlebar: Option<bool>,
+    /// Kept for [`Renderer::replace_state`].
+    adapter_info: wgpu::AdapterInfo,
+    downlevel_fallback: bool,
+    titlebar_inset: Option<f32>,
+    render_scale: f32,
+    /// `None` without supersampling or while the render thread owns it.
+    supersampler: Option<Supersampler>,
+    /// `None` without [`Options::design_resolution`].
+    letterbox: Option<Letterbox>,
+    /// Open while [`mirror::is_mirror_open`].
+    mirror: Option<Mirror>,
+    magnifier: Magnifier,
+    adaptive_quality: AdaptiveQuality,
+    /// Shared with [`App`], which starts the export.
+    metrics: Option<Metrics>,
+    // Declared last so that they drop after everything created from them.
+    queue: Arc<wgpu::Queue>,
+    device: Arc<wgpu::Device>,
+    instance: Arc<wgpu::Instance>,
 }
 
-impl Renderer {
-    async fn new(
-        instance: &wgpu::Instance,
+impl<T> Drop for Renderer<T> {
+    fn drop(&mut self) {
+        if let Some(render_thread) = self.render_thread.take() {
+            // `finish` did not run, e.g. while unwinding.
+            let _ = render_thread.join();
+        }
+        self.gpu_poller.stop();
+        log::debug!("shutdown: waiting for the GPU to go idle");
+        self.device.poll(wgpu::Maintain::Wait);
+        log::debug!("shutdown: dropping the surface");
+        self.surface = None;
+        log::debug!("shutdown: dropping textures, then the device");
+    }
+}
+
+impl<T: UserApp> Renderer<T> {
+    pub(crate) fn new(
+        gpu: GpuContext,
         egui_ctx: egui::Context,
-        window: Arc<Window>,
-        width: u32,
-        height: u32,
-        state: AppState,
-    ) -> anyhow::Result<Self> {
-        let surface = instance.create_surface(window.clone())?;
-
-        let power_pref = wgpu::PowerPreference::default();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: power_pref,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
-
-        let features = wgpu::Features::empty();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: features,
-                    required_limits: Default::default(),
-                    memory_hints: Default::default(),
-                },
-                None,
+        window: &dyn HostWindow,
+        options: &Options,
+        mut state: T,
+    ) -> Result<Self, AppError> {
+        let GpuContext {
+            instance,
+            device,
+            queue,
+            mut surface,
+            mut surface_config,
+            timestamp_queries,
+            adapter_info,
+            downlevel_fallback,
+        } = gpu;
+        let device = Arc::new(device);
+        let queue = Arc::new(queue);
+
+        // The window may have been resized while the GPU was initializing.
+        let size = window.inner_size();
+        let mut surface_scale = 1.;
+        if size.width > 0 && size.height > 0 {
+            let (size, scale) = gpu::clamp_surface_size(&device, [size.width, size.height]);
+            surface_scale = scale;
+            if size != [surface_config.width, surface_config.height] {
+                [surface_config.width, surface_config.height] = size;
+                surface.configure(&device, &surface_config)?;
+            }
+        }
+
+        let limit_rate =
+            options.frame_pacing && FramePacer::uses_present_mode(surface_config.present_mode);
+        let frame_pacer = (limit_rate || options.vsync_pacing).then(|| {
+            FramePacer::new(
+                refresh_rate_millihertz(window),
+                limit_rate,
+                options.vsync_pacing,
             )
-            .await
-            .expect("Failed to create device");
-
-        let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let selected_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-        let swapchain_format = swapchain_capabilities
-            .formats
-            .iter()
-            .find(|d| **d == selected_format)
-            .expect("failed to select proper surface texture format!");
-
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: *swapchain_format,
-            width,
-            height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            desired_maximum_frame_latency: 0,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
-            view_formats: vec![],
+        });
+        state.on_gpu_ready(&adapter_info, downlevel_fallback);
+        crash_report::set_section(
+            "adapter",
+            format!("{adapter_info:#?}\ndownlevel fallback: {downlevel_fallback}"),
+        );
+        crash_report::set_section("window", window_report(window, &surface_config));
+        let orientation = Orientation::from_size(surface_config.width, surface_config.height);
+        state.on_orientation_changed(orientation);
+        let titlebar_inset = (options.unified_titlebar && cfg!(target_os = "macos"))
+            .then_some(MACOS_TITLEBAR_HEIGHT);
+        if let Some(inset) = titlebar_inset {
+            state.on_titlebar_inset(inset);
+        }
+        // The same pixels per point egui will use: native scale times zoom,
+        // times the render and surface scales that adjust the native one.
+        let pixels_per_point =
+            host_window::pixels_per_point(&egui_ctx, window) * options.render_scale * surface_scale;
+        if let Some(native) = window.native() {
+            state.on_window_ready(native);
+        }
+        let mut monitors = MonitorTracker::default();
+        let mut monitor_scales = MonitorScales::load(options.monitor_scales.clone());
+        if let Some(monitors) = window
+            .native()
+            .and_then(|native| monitors.refresh(native, &egui_ctx))
+        {
+            monitor_scales.on_monitor(&egui_ctx, monitors.current());
+            state.on_monitors_changed(monitors);
+        }
+        let gpu_poller = GpuPoller::new(device.clone(), egui_ctx.clone())?;
+        let mut egui_renderer = EguiRenderer::new(&device, egui_ctx, window, &surface_config)?;
+        if options.warm_up {
+            egui_renderer.warm_up(&device, &queue, surface_config.format, pixels_per_point);
+        }
+        egui_renderer.set_frame_overlay_visible(options.show_frame_overlay);
+        egui_renderer.set_render_stats_visible(options.show_render_stats);
+        egui_renderer.set_textures_visible(options.show_textures);
+        egui_renderer.set_log_console_visible(options.show_log_console);
+        egui_renderer.force_close =
+            ForceClose::new(options.force_close_requests, options.force_close_within);
+        egui_renderer.set_debug_window(options.debug_window.then_some(options.debug_hotkey));
+        egui_renderer.set_error_overlay(options.error_overlay);
+        egui_renderer.command_palette.hotkey = options.command_palette;
+        for command in state.commands() {
+            egui_renderer.command_palette.register(command);
+        }
+        egui_renderer.set_app_input_middleware(state.input_middleware());
+        egui_renderer.repaint_flash.enabled = options.repaint_flash;
+        egui_renderer.textures.max_live = options.max_live_textures;
+        egui_renderer.textures.growth_window = options.texture_growth_warning;
+        if options.measure_latency {
+            egui_renderer.latency = Some(LatencyTracker::default());
+        }
+        if options.coalesce_pointer_moves {
+            egui_renderer.pointer_coalescer = Some(PointerCoalescer::default());
+        }
+        egui_renderer.stress_ui = options.stress_ui;
+        egui_renderer.clipboard.rich_paste = options.rich_paste;
+        egui_renderer.opaque = !options.transparent_window();
+        egui_renderer.input_recorder = options.record_input.clone().map(InputRecorder::new);
+        egui_renderer.trace_input = options.trace_events;
+        egui_renderer.redact_trace = options.trace_redact;
+        if options.right_click_as_middle {
+            egui_renderer.add_input_middleware(input_middleware::remap_button(
+                egui::PointerButton::Secondary,
+                egui::PointerButton::Middle,
+            ));
+        }
+        if options.log_input_events {
+            egui_renderer.add_input_middleware(EventLogger);
+        }
+        if let Some(path) = &options.replay_input {
+            egui_renderer.input_replay = Some(InputReplay::load(path, !options.replay_fast)?);
+        }
+        if options.tessellation_cache {
+            egui_renderer.tessellation_cache = Some(TessellationCache::default());
+        }
+        let letterbox = match options.design_resolution {
+            Some(_) if options.render_thread => {
+                log::warn!("the design resolution does not work with the render thread");
+                None
+            }
+            Some(size) => Some(Letterbox::new(
+                &device,
+                surface_config.format,
+                size,
+                options.design_filter,
+            )),
+            None => None,
         };
+        if options.mirror {
+            mirror::set_mirror_open(egui_renderer.egui_winit.egui_ctx(), true);
+        }
+        let mut magnifier_settings = magnifier::load_settings();
+        magnifier_settings.enabled |= options.magnifier;
+        if magnifier_settings.enabled && options.render_thread {
+            log::warn!("the magnifier does not work with the render thread");
+        }
+        let magnifier = Magnifier::new(egui_renderer.egui_winit.egui_ctx(), magnifier_settings);
+        adaptive_quality::set_settings(
+            egui_renderer.egui_winit.egui_ctx(),
+            QualitySettings {
+                budget: options.frame_budget,
+                ..Default::default()
+            },
+        );
+        egui_renderer.design_resolution = letterbox
+            .as_ref()
+            .map(|letterbox| DesignResolution::new(letterbox.size()));
+        // The letterbox's target has a fixed size of its own.
+        let render_scale = if letterbox.is_some() {
+            1.
+        } else {
+            options.render_scale
+        };
+        egui_renderer.render_scale = render_scale;
+        egui_renderer.surface_scale = surface_scale;
+        egui_renderer.render_stats.render_scale = render_scale;
+        let mut supersampler = (render_scale != 1.).then(|| {
+            Supersampler::new(
+                &device,
+                surface_config.format,
+                render_scale,
+                [surface_config.width, surface_config.height],
+            )
+        });
+        egui_renderer.render_stats.instance_flags = options.instance_flags;
+        egui_renderer.render_stats.limits = device.limits();
+        egui_renderer.gpu_memory.warning_threshold = options.gpu_memory_warning;
+
+        let benchmark = options
+            .benchmark
+            .map(|length| Benchmark::new(length, options, adapter_info.clone(), &surface_config));
+        let continuous_repaint = options.frame_limit.is_some() || benchmark.is_some();
 
-        surface.configure(&device, &surface_config);
+        let mut gpu_timer = timestamp_queries
+            .then(|| GpuTimer::new(&device, &queue, gpu_poller.clone(), adapter_info.backend));
 
-        let egui_renderer = EguiRenderer::new(&device, egui_ctx, window, &surface_config)?;
+        // Features of the native window, which a headless one does without.
+        let native = window.native();
+        let kiosk = options
+            .kiosk
+            .clone()
+            .filter(|_| native.is_some())
+            .map(Kiosk::new);
+        if let (Some(kiosk), Some(native)) = (&kiosk, native) {
+            kiosk.ensure_fullscreen(native);
+        }
+        egui_renderer.ignore_close_requests = kiosk.is_some();
+
+        let click_through = native
+            .filter(|_| options.click_through)
+            .map(|native| ClickThrough::new(native));
+        egui_renderer.window_shadow = native
+            .filter(|_| options.drawn_window_shadow())
+            .map(|native| WindowShadow::new(native));
+        let titlebar_hit_test = native
+            .filter(|_| options.custom_titlebar)
+            .map(|native| TitlebarHitTest::new(native));
+
+        let can_capture =
+            !options.render_thread && surface_config.usage.contains(wgpu::TextureUsages::COPY_SRC);
+        let bug_reporter = BugReporter::new(can_capture);
+        #[cfg(feature = "remote-control")]
+        if options.remote_control.is_some() {
+            egui_renderer.remote_control = Some(RemoteControl::new(can_capture));
+        }
+
+        let (surface, render_thread) = match native.filter(|_| options.render_thread) {
+            Some(native) => {
+                let render_thread = RenderThread::spawn(
+                    device.clone(),
+                    queue.clone(),
+                    surface,
+                    egui_renderer.take_wgpu_renderer(),
+                    gpu_timer.take(),
+                    supersampler.take(),
+                    native.clone(),
+                )?;
+                (None, Some(render_thread))
+            }
+            None => (Some(surface), None),
+        };
+        let mut app_textures = Vec::new();
+        if render_thread.is_none() {
+            state.on_gpu_textures(&mut GpuTextures {
+                device: &device,
+                queue: &queue,
+                egui_renderer: &mut egui_renderer,
+                registered: &mut app_textures,
+            });
+        }
 
         Ok(Self {
+            instance,
             device,
             queue,
             surface,
+            suspended: false,
             surface_config,
+            render_thread,
             state,
             egui_renderer,
+            gpu_poller,
+            gpu_timer,
+            frame_recorder: options.timing_export.clone().map(FrameRecorder::new),
+            frame_limit: options.frame_limit,
+            frames_presented: 0,
+            continuous_repaint,
+            skip_unchanged_frames: options.skip_unchanged_frames && !continuous_repaint,
+            force_paint: true,
+            pending_surface_size: None,
+            reconfigure_surface: false,
+            scale_factor_changed: false,
+            last_event_consumed: false,
+            frame_pacer,
+            benchmark,
+            app_textures,
+            orientation,
+            titlebar_hit_test,
+            click_through,
+            capture_exclusion: CaptureExclusion::new(options.exclude_from_capture),
+            document_title: DocumentTitle::new(options.title_pattern.clone()),
+            bug_reporter,
+            monitors,
+            monitor_scales,
+            kiosk,
+            sleep_inhibitor: SleepInhibitor::default(),
+            dark_titlebar: None,
+            adapter_info,
+            downlevel_fallback,
+            titlebar_inset,
+            render_scale,
+            supersampler,
+            letterbox,
+            mirror: None,
+            magnifier,
+            adaptive_quality: AdaptiveQuality::default(),
+            metrics: None,
+            idle: options
+                .idle_after
+                .filter(|_| !continuous_repaint)
+                .map(|threshold| {
+                    IdleTracker::new(
+                        threshold,
+                        options.idle_repaint_interval,
+                        options.idle_stop_animations,
+                    )
+                }),
         })
     }
 
-    fn run_ui_and_paint(&mut self, window: &Window) -> anyhow::Result<EventResult> {
+    /// Swaps in the state built by [`App::new_loading`], with the hooks
+    /// [`Renderer::new`] called on the one it got.
+    fn replace_state(&mut self, mut state: T, window: &dyn HostWindow) {
+        let egui_ctx = self.egui_renderer.egui_winit.egui_ctx().clone();
+        state.on_gpu_ready(&self.adapter_info, self.downlevel_fallback);
+        state.on_orientation_changed(self.orientation);
+        if let Some(inset) = self.titlebar_inset {
+            state.on_titlebar_inset(inset);
+        }
+        if let Some(native) = window.native() {
+            state.on_window_ready(native);
+        }
+        state.on_monitors_changed(self.monitors.monitors());
+        for command in state.commands() {
+            self.egui_renderer.command_palette.register(command);
+        }
+        self.egui_renderer
+            .set_app_input_middleware(state.input_middleware());
+        if self.render_thread.is_none() {
+            state.on_gpu_textures(&mut GpuTextures {
+                device: &self.device,
+                queue: &self.queue,
+                egui_renderer: &mut self.egui_renderer,
+                registered: &mut self.app_textures,
+            });
+        }
+        self.state = state;
+        input_middleware::set_input_blocked(&egui_ctx, false);
+        self.force_paint = true;
+    }
+
+    pub(crate) fn run_ui_and_paint(
+        &mut self,
+        window: &dyn HostWindow,
+    ) -> Result<EventResult, AppError> {
+        profiling::function_scope!();
+
+        // winit still delivers the rest of the iteration after an exit.
+        if self.egui_renderer.close {
+            return Ok(EventResult::Exit);
+        }
+        if self.collect_render_outputs(window)? {
+            return Ok(EventResult::Exit);
+        }
+        if self.suspended {
+            return Ok(EventResult::Wait);
+        }
+
         let Self {
             device,
             queue,
             surface_config,
             surface,
+            render_thread,
             state,
             egui_renderer,
+            gpu_poller,
+            gpu_timer,
+            skip_unchanged_frames,
+            force_paint,
+            pending_surface_size,
+            reconfigure_surface,
+            scale_factor_changed,
+            idle,
+            render_scale,
+            supersampler,
+            letterbox,
+            mirror,
+            magnifier,
+            orientation,
+            titlebar_hit_test,
+            click_through,
+            capture_exclusion,
+            document_title,
+            bug_reporter,
+            monitor_scales,
+            kiosk,
+            sleep_inhibitor,
+            benchmark,
+            dark_titlebar,
+            app_textures,
+            metrics,
+            ..
         } = self;
 
+        egui_renderer.timeline = FrameTimeline::new(Instant::now());
+        if let Some(idle) = idle {
+            if let Some(is_idle) = idle.on_frame(egui_renderer.egui_winit.egui_ctx()) {
+                state.on_idle(is_idle);
+                egui_renderer.frame_stats.idle = is_idle;
+                if let Some(metrics) = metrics {
+                    metrics.set_idle(is_idle);
+                }
+            }
+        }
+        if let Some(latency) = &mut egui_renderer.latency {
+            latency.begin_frame();
+        }
+
+        if let Some(gpu_timer) = gpu_timer {
+            egui_renderer.frame_stats.gpu_time = gpu_timer.poll();
+        }
+        egui_renderer
+            .gpu_memory
+            .poll(device, egui_renderer.render_stats.texture_bytes);
+
+        // egui takes the scale factor and the size in points from the window,
+        // so the surface has to follow the window's size in the same frame
+        // even if the Resized event that goes with the new scale has not
+        // arrived yet (X11) or never will (same physical size).
+        if std::mem::take(scale_factor_changed) {
+            let size = window.inner_size();
+            if size.width > 0 && size.height > 0 {
+                *pending_surface_size = Some([size.width, size.height]);
+            }
+        }
+
+        // Rotating a device may report transient sizes; only the latest one
+        // before this frame decides the layout.
+        if let Some([width, height]) = *pending_surface_size {
+            let new_orientation = Orientation::from_size(width, height);
+            if new_orientation != *orientation {
+                *orientation = new_orientation;
+                state.on_orientation_changed(new_orientation);
+            }
+        }
+
+        let frame = egui_renderer.run_ui(window, state);
+        if let Some(native) = window.native() {
+            let egui_ctx = egui_renderer.egui_winit.egui_ctx();
+            if let Some(titlebar_hit_test) = titlebar_hit_test {
+                titlebar_hit_test.update(egui_ctx, native);
+            }
+            if let Some(click_through) = click_through {
+                click_through.update(egui_ctx, egui_renderer.overlay_ctx(), native);
+            }
+            capture_exclusion.update(egui_ctx, native);
+            document_title.update(egui_ctx, native);
+            titlebar::show_window_menu(egui_ctx, native);
+            if let Some(kiosk) = kiosk {
+                if kiosk.update(egui_ctx, native) {
+                    state.on_inactivity_reset();
+                }
+            }
+        }
+        // A screensaver would skew the numbers.
+        sleep_inhibit::inhibit_sleep_while(
+            egui_renderer.egui_winit.egui_ctx(),
+            benchmark.is_some(),
+        );
+        sleep_inhibitor.update(egui_renderer.egui_winit.egui_ctx());
+        egui_renderer.render_stats.sleep_inhibited = sleep_inhibitor.is_active();
+        monitor_scales.update(egui_renderer.egui_winit.egui_ctx());
+        if bug_reporter.update(egui_renderer.egui_winit.egui_ctx(), state) {
+            *force_paint = true;
+        }
+        if magnifier.update(egui_renderer.egui_winit.egui_ctx()) {
+            *force_paint = true;
+        }
+        #[cfg(feature = "remote-control")]
+        if let Some(remote_control) = &mut egui_renderer.remote_control {
+            remote_control.poll();
+            if remote_control.wants_capture() {
+                *force_paint = true;
+            }
+        }
+        let dark_mode = egui_renderer
+            .egui_winit
+            .egui_ctx()
+            .style()
+            .visuals
+            .dark_mode;
+        if *dark_titlebar != Some(dark_mode) {
+            *dark_titlebar = Some(dark_mode);
+            if let Some(native) = window.native() {
+                titlebar::set_dark_titlebar(native, dark_mode);
+            }
+        }
+
+        if frame.unchanged && *skip_unchanged_frames && !std::mem::take(force_paint) {
+            egui_renderer.frame_stats.frames_elided += 1;
+            egui_renderer.frame_metrics.elided = true;
+            return Ok(egui_renderer.finish_frame(window, frame.viewport_output));
+        }
+        *force_paint = false;
+        egui_renderer.frame_metrics.elided = false;
+
+        let mut reconfigure = std::mem::take(reconfigure_surface);
+        if let Some(size) = pending_surface_size.take() {
+            let (size, surface_scale) = gpu::clamp_surface_size(device, size);
+            if surface_scale != egui_renderer.surface_scale {
+                // Only takes effect from the next frame on.
+                egui_renderer.surface_scale = surface_scale;
+                egui_renderer.egui_winit.egui_ctx().request_repaint();
+            }
+            if size != [surface_config.width, surface_config.height] {
+                [surface_config.width, surface_config.height] = size;
+                if let Some(supersampler) = supersampler {
+                    supersampler.resize(device, size);
+                }
+                reconfigure = true;
+            }
+        }
+        if reconfigure {
+            egui_renderer.render_stats.surface_configures += 1;
+            crash_report::set_section("window", window_report(window, surface_config));
+            if let Some(surface) = surface {
+                if let Err(err) = surface.configure(device, surface_config) {
+                    egui_renderer.discard_frame(device, queue, frame.textures_delta);
+                    return Err(err);
+                }
+            }
+        }
+
+        let mut size_in_pixels = [surface_config.width, surface_config.height];
+        if let Some(letterbox) = letterbox {
+            size_in_pixels = letterbox.size();
+        } else if *render_scale != 1. {
+            size_in_pixels = supersample::scaled_size(device, size_in_pixels, *render_scale);
+            let stats = &mut egui_renderer.render_stats;
+            stats.supersample_size = size_in_pixels;
+            stats.supersample_bytes =
+                supersample::target_bytes(surface_config.format, size_in_pixels);
+        }
         let screen_descriptor = ScreenDescriptor {
-            size_in_pixels: [surface_config.width, surface_config.height],
-            pixels_per_point: window.scale_factor() as f32,
+            size_in_pixels,
+            pixels_per_point: frame.pixels_per_point,
         };
 
-        let surface_texture = surface.get_current_texture()?;
+        if let Some(render_thread) = render_thread {
+            egui_renderer.record_frame(&frame.clipped_primitives, &frame.textures_delta);
+            let result = egui_renderer.finish_frame(window, frame.viewport_output);
+            render_thread.submit(RenderJob {
+                timeline: egui_renderer.timeline,
+                clipped_primitives: frame.clipped_primitives,
+                textures_delta: frame.textures_delta,
+                tessellation_generation: frame.tessellation_generation,
+                screen_descriptor,
+                clear_color: egui_renderer.clear_color(),
+                surface_config: reconfigure.then(|| surface_config.clone()),
+            })?;
+            return Ok(self.next_event_result(result, false));
+        }
+        let Some(surface) = surface.as_ref() else {
+            egui_renderer.discard_frame(device, queue, frame.textures_delta);
+            return Err(wgpu::SurfaceError::Lost.into());
+        };
+
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(err) => {
+                egui_renderer.discard_frame(device, queue, frame.textures_delta);
+                return Err(err.into());
+            }
+        };
+        egui_renderer.timeline.acquire = Instant::now();
 
         let surface_view = surface_texture
-            .texture
+            .texture()
             .create_view(&wgpu::TextureViewDescriptor::default());
+        // The mirror samples egui's target, so without a letterbox egui
+        // paints into the mirror's.
+        let mirror_target = match (letterbox.as_ref(), mirror.as_mut()) {
+            (None, Some(mirror)) => {
+                mirror.prepare_target(
+                    device,
+                    surface_config.format,
+                    [surface_config.width, surface_config.height],
+                );
+                mirror.target()
+            }
+            _ => None,
+        };
+        // The lens samples egui's target, so egui needs one even without a
+        // letterbox, supersampling or mirror.
+        magnifier.prepare(
+            device,
+            surface_config.format,
+            [surface_config.width, surface_config.height],
+            letterbox.is_none() && supersampler.is_none() && mirror_target.is_none(),
+        );
+        let intermediate_target = mirror_target.or(magnifier.target());
 
-        let mut encoder =
-            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("egui frame encoder"),
+        });
+        if render_thread.is_none() {
+            let egui_ctx = egui_renderer.egui_winit.egui_ctx().clone();
+            state.prepare_frame(
+                &egui_ctx,
+                &mut GpuTextures {
+                    device,
+                    queue,
+                    egui_renderer,
+                    registered: app_textures,
+                },
+                &mut encoder,
+            );
+        }
 
-        let result = egui_renderer.run_ui_and_paint(
-            device,
-            queue,
-            &mut encoder,
-            &surface_view,
+        egui_renderer.paint_frame(
+            WgpuTarget {
+                device,
+                queue,
+                encoder: &mut encoder,
+                view: match (
+                    letterbox.as_ref(),
+                    supersampler.as_ref(),
+                    intermediate_target,
+                ) {
+                    (Some(letterbox), ..) => letterbox.target(),
+                    (None, Some(supersampler), _) => supersampler.target(),
+                    (None, None, Some(target)) => target,
+                    (None, None, None) => &surface_view,
+                },
+                tessellation_generation: frame.tessellation_generation,
+                timestamp_writes: gpu_timer.as_mut().and_then(GpuTimer::timestamp_writes),
+            },
             screen_descriptor,
+            frame.clipped_primitives,
+            frame.textures_delta,
+        );
+        if let Some(supersampler) = supersampler {
+            supersampler.downsample(&mut encoder, mirror_target.unwrap_or(&surface_view));
+        }
+        if let Some(letterbox) = letterbox {
+            letterbox.blit(
+                &mut encoder,
+                &surface_view,
+                [surface_config.width, surface_config.height],
+            );
+        } else if let Some(mirror) = mirror {
+            mirror.show_target(
+                &mut encoder,
+                &surface_view,
+                [surface_config.width, surface_config.height],
+            );
+        } else {
+            magnifier.show_target(
+                &mut encoder,
+                &surface_view,
+                [surface_config.width, surface_config.height],
+            );
+        }
+        if magnifier.is_enabled() {
+            let surface_size = [surface_config.width, surface_config.height];
+            let source = match (letterbox.as_ref(), supersampler.as_ref()) {
+                (Some(letterbox), _) => letterbox::transform(egui_renderer.egui_winit.egui_ctx())
+                    .map(|transform| MagnifierSource {
+                        view: letterbox.target(),
+                        size: letterbox.size(),
+                        pixels_per_point: frame.pixels_per_point,
+                        scale: transform.scale,
+                        offset: transform.offset,
+                    }),
+                (None, Some(supersampler)) => Some(MagnifierSource {
+                    view: supersampler.target(),
+                    size: supersampler.size(),
+                    pixels_per_point: frame.pixels_per_point,
+                    scale: surface_size[0] as f32 / supersampler.size()[0] as f32,
+                    offset: egui::Vec2::ZERO,
+                }),
+                (None, None) => mirror
+                    .as_ref()
+                    .and_then(Mirror::target)
+                    .or(magnifier.target())
+                    .map(|view| MagnifierSource {
+                        view,
+                        size: surface_size,
+                        pixels_per_point: frame.pixels_per_point,
+                        scale: 1.,
+                        offset: egui::Vec2::ZERO,
+                    }),
+            };
+            if let Some(source) = source {
+                magnifier.draw(
+                    device,
+                    queue,
+                    &mut encoder,
+                    source,
+                    &surface_view,
+                    surface_size,
+                );
+            }
+        }
+        bug_reporter.capture(device, &mut encoder, surface_texture.texture());
+        #[cfg(feature = "remote-control")]
+        if let Some(remote_control) = &mut egui_renderer.remote_control {
+            remote_control.capture(device, &mut encoder, surface_texture.texture());
+        }
+        let mirror_frame = mirror
+            .as_mut()
+            .and_then(|mirror| mirror.blit(device, &mut encoder, letterbox.as_ref()));
+        let result = egui_renderer.finish_frame(window, frame.viewport_output);
+
+        if let Some(gpu_timer) = gpu_timer {
+            gpu_timer.resolve(&mut encoder);
+        }
+
+        {
+            profiling::scope!("submit");
+            queue.submit(Some(encoder.finish()));
+        }
+        egui_renderer.timeline.submit = Instant::now();
+        if let Some(gpu_timer) = gpu_timer {
+            gpu_timer.after_submit();
+        }
+        bug_reporter.after_submit(gpu_poller);
+        #[cfg(feature = "remote-control")]
+        if let Some(remote_control) = &mut egui_renderer.remote_control {
+            remote_control.after_submit(gpu_poller);
+        }
+        gpu_poller.poll();
+        window.pre_present_notify();
+        {
+            profiling::scope!("present");
+            surface_texture.present();
+        }
+        egui_renderer.timeline.present = Instant::now();
+        if egui_renderer.on_frame_presented(window) {
+            *force_paint = true;
+            egui_renderer.egui_winit.egui_ctx().request_repaint();
+        }
+        if let (Some(mirror), Some(mirror_frame)) = (mirror.as_ref(), mirror_frame) {
+            mirror.present(mirror_frame);
+        }
+
+        let timeline = egui_renderer.timeline;
+        let done = self.on_presented(&timeline);
+        Ok(self.next_event_result(result, done))
+    }
+
+    /// Processes frames the render thread has presented since the last call.
+    /// Returns `true` once the frame limit or benchmark is done.
+    fn collect_render_outputs(&mut self, window: &dyn HostWindow) -> Result<bool, AppError> {
+        let Some(render_thread) = &self.render_thread else {
+            return Ok(false);
+        };
+        let outputs: Vec<_> = render_thread.outputs().collect();
+        let mut done = false;
+        for output in outputs {
+            output.result?;
+            if output.gpu_time.is_some() {
+                self.egui_renderer.frame_stats.gpu_time = output.gpu_time;
+            }
+            if self.egui_renderer.on_frame_presented(window) {
+                self.force_paint = true;
+                self.egui_renderer.egui_winit.egui_ctx().request_repaint();
+            }
+            done |= self.on_presented(&output.timeline);
+        }
+        Ok(done)
+    }
+
+    fn on_presented(&mut self, timeline: &FrameTimeline) -> bool {
+        let Self {
+            surface_config,
+            egui_renderer,
+            frame_recorder,
+            frame_limit,
+            frames_presented,
+            frame_pacer,
+            benchmark,
+            metrics,
+            ..
+        } = self;
+
+        if let Some(latency) = &mut egui_renderer.latency {
+            latency.end_frame(timeline.present);
+        }
+        if let Some(frame_pacer) = frame_pacer {
+            frame_pacer.on_present(timeline);
+            egui_renderer.frame_stats.pacer_interval = Some(frame_pacer.interval());
+            egui_renderer.frame_stats.pacer_resyncs = frame_pacer.resyncs;
+            egui_renderer.frame_stats.vsync_snapping = frame_pacer.snapping();
+            egui_renderer.frame_stats.vsync_prediction_error = frame_pacer.prediction_error;
+        }
+        profiling::finish_frame!();
+
+        egui_renderer.frame_stats.record(timeline);
+        egui_renderer
+            .frame_metrics
+            .on_presented(&egui_renderer.frame_stats);
+        if let Some(frame_recorder) = frame_recorder {
+            frame_recorder.record(timeline, &egui_renderer.render_stats);
+        }
+        if let Some(metrics) = metrics {
+            metrics.on_presented(
+                timeline.present,
+                timeline.busy_time(),
+                egui_renderer.gpu_memory.total_bytes(),
+            );
+        }
+
+        if *frames_presented == 0 {
+            log::info!("first visible frame took {:?}", timeline.cpu_time());
+        }
+        *frames_presented += 1;
+        let benchmark_done = benchmark.as_mut().is_some_and(|benchmark| {
+            benchmark.record(
+                timeline,
+                egui_renderer.frame_stats.gpu_time,
+                &egui_renderer.render_stats,
+                [surface_config.width, surface_config.height],
+            )
+        });
+        let done = benchmark_done || frame_limit.is_some_and(|limit| *frames_presented >= limit);
+        self.adapt_quality(timeline);
+        done
+    }
+
+    /// Steps quality down or up to keep frames within the budget, see
+    /// [`adaptive_quality`].
+    fn adapt_quality(&mut self, timeline: &FrameTimeline) {
+        let settings = adaptive_quality::settings(self.egui_renderer.egui_winit.egui_ctx());
+        self.adaptive_quality.last_present = Some(timeline.present);
+        if settings != self.adaptive_quality.settings {
+            self.adaptive_quality.settings = settings;
+            let taken_back: Vec<_> = self
+                .adaptive_quality
+                .active
+                .iter()
+                .map(|(step, _)| *step)
+                .filter(|step| settings.budget.is_none() || settings.pinned[*step as usize])
+                .collect();
+            for step in taken_back.into_iter().rev() {
+                self.change_quality(step, false, None);
+            }
+            self.adaptive_quality.controller =
+                settings
+                    .budget
+                    .map(|budget| match self.adaptive_quality.controller.take() {
+                        Some(mut controller) if controller.budget() == budget => {
+                            controller.reset();
+                            controller
+                        }
+                        _ => QualityController::new(budget),
+                    });
+        }
+
+        // Idle frames are sparse, and the idle tracker saves and restores
+        // the animation time too.
+        if !self.egui_renderer.frame_stats.idle {
+            let frame_time = timeline
+                .busy_time()
+                .max(self.egui_renderer.frame_stats.gpu_time.unwrap_or_default());
+            let next_step = self.next_quality_step();
+            let last_step = self.adaptive_quality.active.last().map(|(step, _)| *step);
+            if let Some(controller) = &mut self.adaptive_quality.controller {
+                let decision = controller.record(
+                    timeline.present,
+                    frame_time,
+                    next_step.is_some(),
+                    last_step.is_some(),
+                );
+                let p95 = controller.p95();
+                match (decision, next_step, last_step) {
+                    (Some(Decision::Degrade), Some(step), _) => {
+                        self.change_quality(step, true, p95)
+                    }
+                    (Some(Decision::Restore), _, Some(step)) => {
+                        self.change_quality(step, false, p95)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.egui_renderer.frame_stats.quality = self.adaptive_quality.status();
+    }
+
+    /// The first step down the ladder that is not taken, pinned, or moot
+    /// with the current settings.
+    fn next_quality_step(&self) -> Option<Step> {
+        let ctx = self.egui_renderer.egui_winit.egui_ctx();
+        Step::LADDER.into_iter().find(|&step| {
+            !self.adaptive_quality.is_active(step)
+                && !self.adaptive_quality.settings.pinned[step as usize]
+                && match step {
+                    Step::Animations => ctx.style().animation_time > 0.,
+                    Step::Passes => ctx.options(|o| o.max_passes.get() > 1),
+                    Step::RenderScale => self.supersampler.is_some(),
+                    Step::FrameRate => true,
+                }
+        })
+    }
+
+    fn change_quality(&mut self, step: Step, degrade: bool, p95: Option<Duration>) {
+        let ctx = self.egui_renderer.egui_winit.egui_ctx().clone();
+        if degrade {
+            let saved = match step {
+                Step::Animations => {
+                    let animation_time = ctx.style().animation_time;
+                    ctx.style_mut(|style| style.animation_time = 0.);
+                    Saved::AnimationTime(animation_time)
+                }
+                Step::Passes => {
+                    let max_passes = ctx.options(|o| o.max_passes);
+                    ctx.options_mut(|o| o.max_passes = NonZeroUsize::MIN);
+                    Saved::MaxPasses(max_passes)
+                }
+                Step::RenderScale => {
+                    let render_scale = self.render_scale;
+                    self.supersampler = None;
+                    self.set_render_scale(1.);
+                    Saved::RenderScale(render_scale)
+                }
+                Step::FrameRate => {
+                    self.adaptive_quality.frame_cap = self
+                        .adaptive_quality
+                        .controller
+                        .as_ref()
+                        .map(|controller| controller.budget() * 2);
+                    Saved::FrameRate
+                }
+            };
+            self.adaptive_quality.active.push((step, saved));
+        } else {
+            let Some(index) = self
+                .adaptive_quality
+                .active
+                .iter()
+                .position(|(active, _)| *active == step)
+            else {
+                return;
+            };
+            match self.adaptive_quality.active.remove(index).1 {
+                Saved::AnimationTime(animation_time) => {
+                    ctx.style_mut(|style| style.animation_time = animation_time)
+                }
+                Saved::MaxPasses(max_passes) => ctx.options_mut(|o| o.max_passes = max_passes),
+                Saved::RenderScale(render_scale) => {
+                    self.supersampler = Some(Supersampler::new(
+                        &self.device,
+                        self.surface_config.format,
+                        render_scale,
+                        [self.surface_config.width, self.surface_config.height],
+                    ));
+                    self.set_render_scale(render_scale);
+                }
+                Saved::FrameRate => self.adaptive_quality.frame_cap = None,
+            }
+        }
+
+        let change = QualityChange {
+            step,
+            degraded: degrade,
+            level: self.adaptive_quality.active.len(),
+            p95,
+        };
+        log::info!(
+            "{} {} at level {}{}",
+            if degrade { "reduced" } else { "restored" },
+            step.name(),
+            change.level,
+            p95.map_or_else(String::new, |p95| format!(", p95 frame time {p95:?}"))
+        );
+        self.state.on_quality_changed(&change);
+        self.adaptive_quality.last_change = Some(change);
+        self.force_paint = true;
+        ctx.request_repaint();
+    }
+
+    fn set_render_scale(&mut self, render_scale: f32) {
+        self.render_scale = render_scale;
+        self.egui_renderer.render_scale = render_scale;
+        let stats = &mut self.egui_renderer.render_stats;
+        stats.render_scale = render_scale;
+        if render_scale == 1. {
+            stats.supersample_size = [0, 0];
+            stats.supersample_bytes = 0;
+        }
+    }
+
+    fn next_event_result(&self, result: EventResult, done: bool) -> EventResult {
+        if done {
+            EventResult::Exit
+        } else if self.continuous_repaint && !matches!(result, EventResult::Exit) {
+            EventResult::RepaintNext
+        } else {
+            result
+        }
+    }
+
+    /// Carries on after a recoverable error with a fresh frame, on a
+    /// reconfigured surface if need be.
+    pub(crate) fn recover(&mut self, err: &AppError) {
+        log::warn!("{err}");
+        self.reconfigure_surface |= err.needs_reconfigure();
+        self.force_paint = true;
+        if let Some(metrics) = &self.metrics {
+            metrics.on_surface_error_recovered();
+        }
+    }
+
+    /// Releases what can be rebuilt on demand when the OS (iOS, Android)
+    /// reports memory pressure.
+    fn on_memory_warning(&mut self) {
+        log::warn!("memory warning, dropping caches");
+        self.state.on_memory_warning();
+        self.egui_renderer
+            .egui_winit
+            .egui_ctx()
+            .memory_mut(|mem| mem.caches = Default::default());
+        self.egui_renderer.invalidate_tessellation_cache();
+        self.force_paint = true;
+    }
+
+    fn mirror_window_id(&self) -> Option<winit::window::WindowId> {
+        self.mirror.as_ref().and_then(Mirror::window_id)
+    }
+
+    /// What the app asked of the mirror during the last frame, if it differs
+    /// from what is showing.
+    pub(crate) fn take_mirror_request(&mut self) -> Option<bool> {
+        let open = mirror::take_request(self.egui_renderer.egui_winit.egui_ctx())?;
+        if open && self.render_thread.is_some() {
+            log::warn!("the mirror does not work with the render thread");
+            return None;
+        }
+        (open != self.mirror.is_some()).then_some(open)
+    }
+
+    fn open_mirror(&mut self, window: Window) {
+        match Mirror::new(
+            &self.instance,
+            &self.device,
             window,
-            state,
-        )?;
+            self.surface_config.format,
+        ) {
+            Ok(mirror) => self.mirror = Some(mirror),
+            Err(err) => log::warn!("cannot open the mirror: {err}"),
+        }
+        self.on_mirror_changed();
+    }
+
+    /// Opens a mirror of `size` that draws into a texture, as if the app
+    /// had asked for one.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn open_offscreen_mirror(&mut self, size: [u32; 2]) {
+        match Mirror::offscreen(&self.device, size, self.surface_config.format) {
+            Ok(mirror) => self.mirror = Some(mirror),
+            Err(err) => log::warn!("cannot open the mirror: {err}"),
+        }
+        self.on_mirror_changed();
+    }
 
-        queue.submit(Some(encoder.finish()));
-        surface_texture.present();
+    /// What the offscreen mirror last drew.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn mirror_texture(&self) -> Option<&Arc<wgpu::Texture>> {
+        self.mirror.as_ref()?.texture()
+    }
 
-        Ok(result)
+    pub(crate) fn close_mirror(&mut self) {
+        self.mirror = None;
+        self.on_mirror_changed();
     }
 
-    fn on_window_event(
+    fn on_mirror_changed(&mut self) {
+        let egui_ctx = self.egui_renderer.egui_winit.egui_ctx();
+        mirror::set_state(egui_ctx, self.mirror.is_some());
+        egui_ctx.request_repaint();
+        self.force_paint = true;
+    }
+
+    /// Input to the mirror is ignored; only the main window takes it.
+    fn on_mirror_event(&mut self, event: &winit::event::WindowEvent) -> EventResult {
+        match event {
+            winit::event::WindowEvent::CloseRequested => {
+                self.close_mirror();
+                EventResult::RepaintNext
+            }
+            winit::event::WindowEvent::Resized(size) => {
+                if let Some(mirror) = &mut self.mirror {
+                    mirror.resize(*size);
+                }
+                self.force_paint = true;
+                EventResult::RepaintNext
+            }
+            _ => EventResult::Wait,
+        }
+    }
+
+    /// Drops the surface, which must not outlive the native window on
+    /// mobile platforms. Everything else is kept for `resume`.
+    fn suspend(&mut self) -> Result<(), AppError> {
+        self.suspended = true;
+        self.mirror = None;
+        self.sleep_inhibitor.set_suspended(true);
+        self.surface = None;
+        if let Some(render_thread) = &self.render_thread {
+            render_thread.set_surface(None)?;
+        }
+        Ok(())
+    }
+
+    fn resume(&mut self, window: Arc<Window>) -> Result<(), AppError> {
+        let size = window.inner_size();
+        if size.width > 0 && size.height > 0 {
+            let (clamped, surface_scale) =
+                gpu::clamp_surface_size(&self.device, [size.width, size.height]);
+            [self.surface_config.width, self.surface_config.height] = clamped;
+            self.egui_renderer.surface_scale = surface_scale;
+            let orientation = Orientation::from_size(size.width, size.height);
+            if orientation != self.orientation {
+                self.orientation = orientation;
+                self.state.on_orientation_changed(orientation);
+            }
+        }
+        self.sleep_inhibitor.set_suspended(false);
+        self.pending_surface_size = None;
+        let mut surface = RenderSurface::Window(self.instance.create_surface(window)?);
+        if let Some(render_thread) = &self.render_thread {
+            render_thread.set_surface(Some((surface, self.surface_config.clone())))?;
+        } else {
+            surface.configure(&self.device, &self.surface_config)?;
+            if let Some(supersampler) = &mut self.supersampler {
+                supersampler.resize(
+                    &self.device,
+                    [self.surface_config.width, self.surface_config.height],
+                );
+            }
+            self.surface = Some(surface);
+        }
+        self.egui_renderer.render_stats.surface_configures += 1;
+        self.egui_renderer.invalidate_tessellation_cache();
+        self.capture_exclusion.reapply();
+        self.document_title.reapply();
+        self.force_paint = true;
+        self.suspended = false;
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<(), AppError> {
+        self.state.on_exit(self.egui_renderer.forced_close);
+        if let Some(render_thread) = self.render_thread.take() {
+            let (renderer, outputs) = render_thread.join()?;
+            self.egui_renderer.restore_wgpu_renderer(renderer);
+            for output in outputs {
+                self.on_presented(&output.timeline);
+            }
+        }
+        for id in self.app_textures.drain(..) {
+            self.egui_renderer.free_native_texture(id);
+        }
+        self.egui_renderer.textures.log_live();
+        if let Some(frame_recorder) = self.frame_recorder.take() {
+            frame_recorder.write(self.egui_renderer.latency.as_ref())?;
+        }
+        if let Some(input_recorder) = self.egui_renderer.input_recorder.take() {
+            input_recorder.write()?;
+        }
+        if let Some(benchmark) = self.benchmark.take() {
+            benchmark.finish()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn on_window_event(
         &mut self,
         event: &winit::event::WindowEvent,
-        window: &Window,
+        window: &dyn HostWindow,
+    ) -> EventResult {
+        profiling::function_scope!();
+
+        let arrival = Instant::now();
+        self.last_event_consumed = false;
+        let mut result = self.handle_window_event(event, window);
+        if idle::is_user_input(event) {
+            if let (Some(kiosk), Some(native)) = (&mut self.kiosk, window.native()) {
+                kiosk.on_input(native);
+            }
+            if let Some(metrics) = &self.metrics {
+                metrics.on_input();
+            }
+        }
+        if let (winit::event::WindowEvent::Occluded(occluded), Some(metrics)) =
+            (event, &self.metrics)
+        {
+            metrics.set_hidden(*occluded);
+        }
+        if idle::is_user_input(event)
+            && self.idle.as_mut().is_some_and(IdleTracker::on_input)
+            && !matches!(result, EventResult::Exit)
+        {
+            result = EventResult::RepaintNow;
+        }
+        if let (Some(latency), Some(class)) =
+            (&mut self.egui_renderer.latency, InputClass::of(event))
+        {
+            latency.on_input(class, arrival, &result);
+        }
+        result
+    }
+
+    fn handle_window_event(
+        &mut self,
+        event: &winit::event::WindowEvent,
+        window: &dyn HostWindow,
     ) -> EventResult {
         let Self {
-            device,
-            surface_config,
-            surface,
+            state,
             egui_renderer,
+            force_paint,
+            pending_surface_size,
+            scale_factor_changed,
+            frame_pacer,
+            monitors,
+            monitor_scales,
+            kiosk,
+            last_event_consumed,
             ..
         } = self;
         let mut repaint_asap = false;
+        // Kiosk mode and monitors need the native window.
+        let native = window.native();
 
         match event {
             winit::event::WindowEvent::Resized(physical_size) => {
+                if let (Some(kiosk), Some(native)) = (kiosk, native) {
+                    kiosk.ensure_fullscreen(native);
+                }
                 if let (Some(width), Some(height)) = (
                     NonZeroU32::new(physical_size.width),
                     NonZeroU32::new(physical_size.height),
                 ) {
                     repaint_asap = true;
-                    surface_config.width = width.get();
-                    surface_config.height = height.get();
-                    surface.configure(device, surface_config);
+                    *force_paint = true;
+                    egui_renderer.invalidate_tessellation_cache();
+                    *pending_surface_size = Some([width.get(), height.get()]);
+                }
+            }
+
+            winit::event::WindowEvent::ScaleFactorChanged { .. } => {
+                // On Wayland the preferred (possibly fractional) scale only
+                // arrives after the window was created; the size change that
+                // goes with it follows as a Resized event. On Windows that
+                // Resized is dispatched right after this event, so painting
+                // now would draw at the new scale into the old surface size;
+                // the next redraw picks up both.
+                *scale_factor_changed = true;
+                *force_paint = true;
+                egui_renderer.invalidate_tessellation_cache();
+                if let Some(frame_pacer) = frame_pacer {
+                    frame_pacer.set_refresh_rate(refresh_rate_millihertz(window));
+                }
+                let egui_ctx = egui_renderer.egui_winit.egui_ctx();
+                if let Some(monitors) = native.and_then(|native| monitors.refresh(native, egui_ctx))
+                {
+                    monitor_scales.on_monitor(egui_ctx, monitors.current());
+                    state.on_monitors_changed(monitors);
+                }
+                if let (Some(kiosk), Some(native)) = (kiosk, native) {
+                    kiosk.ensure_fullscreen(native);
+                }
+            }
+
+            winit::event::WindowEvent::Occluded(false) => {
+                *force_paint = true;
+                egui_renderer.invalidate_tessellation_cache();
+                if let Some(frame_pacer) = frame_pacer {
+                    frame_pacer.set_refresh_rate(refresh_rate_millihertz(window));
+                }
+            }
+
+            winit::event::WindowEvent::Moved(_) => {
+                if let Some(frame_pacer) = frame_pacer {
+                    frame_pacer.set_refresh_rate(refresh_rate_millihertz(window));
+                }
+                // Monitors can only be enumerated through a window, so
+                // hot-plugged ones show up the next time it moves.
+                let egui_ctx = egui_renderer.egui_winit.egui_ctx();
+                if let Some(monitors) = native.and_then(|native| monitors.refresh(native, egui_ctx))
+                {
+                    monitor_scales.on_monitor(egui_ctx, monitors.current());
+                    state.on_monitors_changed(monitors);
+                }
+                if let (Some(kiosk), Some(native)) = (kiosk, native) {
+                    kiosk.ensure_fullscreen(native);
                 }
             }
 
@@ -180,6 +1480,7 @@ impl Renderer {
         };
 
         let event_response = egui_renderer.on_window_event(window, event);
+        *last_event_consumed = event_response.consumed;
 
         if egui_renderer.close {
             EventResult::Exit
@@ -194,15 +1495,41 @@ impl Renderer {
         }
     }
 
-    fn on_device_event(&mut self, event: DeviceEvent) -> EventResult {
+    pub(crate) fn paced(&self, when: Instant) -> Instant {
+        let when = self
+            .frame_pacer
+            .as_ref()
+            .map_or(when, |frame_pacer| frame_pacer.paced(when));
+        self.adaptive_quality.paced(when)
+    }
+
+    pub(crate) fn snap_to_vsync(&mut self, when: Instant) -> Instant {
+        self.frame_pacer
+            .as_mut()
+            .map_or(when, |frame_pacer| frame_pacer.snap(when))
+    }
+
+    fn on_device_event(
+        &mut self,
+        device_id: winit::event::DeviceId,
+        event: DeviceEvent,
+        window: &dyn HostWindow,
+    ) -> EventResult {
+        let mut result = EventResult::Wait;
         if let winit::event::DeviceEvent::MouseMotion { delta } = event {
-            self.egui_renderer.egui_winit.on_mouse_motion(delta);
-            return EventResult::RepaintNext;
+            self.egui_renderer.on_mouse_motion(window, delta);
+            result = EventResult::RepaintNext;
+        }
+        match self.state.on_device_event(device_id, &event) {
+            Some(app_result) => result.merge(app_result),
+            None => result,
         }
-        EventResult::Wait
     }
 
-    fn on_user_event(&self, event: UserEvent) -> EventResult {
+    pub(crate) fn on_user_event(&self, event: UserEvent) -> EventResult {
+        if self.suspended {
+            return EventResult::Wait;
+        }
         match event {
             UserEvent::RequestRepaint {
                 when,
@@ -216,41 +1543,230 @@ impl Renderer {
                 if current_pass_nr == cumulative_pass_nr
                     || current_pass_nr == cumulative_pass_nr + 1
                 {
-                    EventResult::RepaintAt(when)
+                    EventResult::RepaintAt(
+                        self.idle.as_ref().map_or(when, |idle| idle.throttle(when)),
+                    )
                 } else {
                     EventResult::Wait
                 }
             }
+            UserEvent::GpuReady(_)
+            | UserEvent::StateReady(_)
+            | UserEvent::Host(_)
+            | UserEvent::MetricsHeartbeat => EventResult::Wait,
+            #[cfg(feature = "remote-control")]
+            UserEvent::Remote(_) => EventResult::Wait,
+            #[cfg(feature = "dialogs")]
+            UserEvent::Dialog(..) => EventResult::Wait,
+            #[cfg(feature = "notifications")]
+            UserEvent::Notification(..) => EventResult::Wait,
+            UserEvent::DockMenu(_) => EventResult::Wait,
         }
     }
+
+    #[cfg(feature = "remote-control")]
+    fn on_remote_request(&mut self, request: RemoteRequest, window: &Window) -> EventResult {
+        let egui_ctx = self.egui_renderer.egui_winit.egui_ctx().clone();
+        let fps = self.egui_renderer.frame_stats.smoothed_fps();
+        match &mut self.egui_renderer.remote_control {
+            Some(remote_control) => remote_control.request(request, &egui_ctx, window, fps),
+            None => EventResult::Wait,
+        }
+    }
+}
+
+/// What `testing::TestApp` looks at.
+#[cfg(any(test, feature = "testing"))]
+impl<T> Renderer<T> {
+    pub(crate) fn state(&self) -> &T {
+        &self.state
+    }
+
+    pub(crate) fn state_mut(&mut self) -> &mut T {
+        &mut self.state
+    }
+
+    pub(crate) fn egui_ctx(&self) -> &egui::Context {
+        self.egui_renderer.egui_winit.egui_ctx()
+    }
+
+    pub(crate) fn egui_renderer(&mut self) -> &mut EguiRenderer {
+        &mut self.egui_renderer
+    }
+
+    pub(crate) fn surface_config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.surface_config
+    }
+
+    pub(crate) fn device(&self) -> &Arc<wgpu::Device> {
+        &self.device
+    }
+
+    pub(crate) fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// What frames are presented to, with an offscreen surface.
+    pub(crate) fn offscreen_texture(&self) -> Option<&Arc<wgpu::Texture>> {
+        match self.surface.as_ref()? {
+            RenderSurface::Offscreen(texture) => texture.as_ref(),
+            RenderSurface::Window(_) => None,
+        }
+    }
+}
+
+fn window_report(window: &dyn HostWindow, surface_config: &wgpu::SurfaceConfiguration) -> String {
+    format!(
+        "inner size: {:?}\nscale factor: {}\nmonitor: {:?}\nsurface: {}x{} {:?} {:?}",
+        window.inner_size(),
+        window.scale_factor(),
+        window
+            .native()
+            .and_then(|native| native.current_monitor())
+            .and_then(|monitor| monitor.name()),
+        surface_config.width,
+        surface_config.height,
+        surface_config.format,
+        surface_config.present_mode,
+    )
+}
+
+fn refresh_rate_millihertz(window: &dyn HostWindow) -> Option<u32> {
+    window
+        .native()?
+        .current_monitor()
+        .and_then(|monitor| monitor.refresh_rate_millihertz())
+}
+
+/// AppKit and UIKit only work on the main thread, and winit panics creating
+/// the event loop anywhere else. The thread's name says nothing, as any
+/// thread can be named "main".
+fn check_main_thread() -> Result<(), AppError> {
+    #[cfg(target_vendor = "apple")]
+    {
+        extern "C" {
+            fn pthread_main_np() -> std::ffi::c_int;
+        }
+        // SAFETY: takes no arguments and only looks at the calling thread.
+        if unsafe { pthread_main_np() } == 0 {
+            return Err(AppError::NotMainThread);
+        }
+    }
+    Ok(())
 }
 
-pub struct App {
-    windows_next_repaint_time: Option<Instant>,
-    repaint_proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
-    instance: wgpu::Instance,
-    renderer: Option<Renderer>,
+pub struct App<T> {
+    scheduler: Scheduler,
+    event_sender: EventSender,
+    // Declared before the window so that exits that skip `shutdown` still
+    // drop the surface first.
+    renderer: Option<Renderer<T>>,
+    /// Stands in for the renderer with a custom painter, or when no GPU
+    /// adapter works.
+    painter: Option<PainterFrontend<T>>,
+    make_painter: Option<PainterFactory>,
     window: Option<Arc<Window>>,
-    window_size: Vec2,
-    app_state: Option<AppState>,
-    pub return_result: anyhow::Result<()>,
+    pending_egui_ctx: Option<egui::Context>,
+    init_started: Option<Instant>,
+    options: Options,
+    app_state: Option<T>,
+    /// Set while the app's state is built in the background.
+    loading: Option<LoadingProgress>,
+    suspended: bool,
+    event_trace: Option<EventTrace>,
+    /// Window events that arrived before the renderer, replayed once it
+    /// exists.
+    pending_events: PendingEvents,
+    /// The size the host asked for before the window existed.
+    host_size: Option<[u32; 2]>,
+    /// Windows created again after a suspend keep the first server.
+    #[cfg(feature = "remote-control")]
+    remote_server_started: bool,
+    /// Started with the first window, like the remote control server.
+    metrics: Option<Metrics>,
+    dock: Dock,
+    jump_list: JumpList,
+    pub return_result: Result<(), AppError>,
 }
 
-impl App {
-    pub fn new(
-        event_loop: &EventLoop<UserEvent>,
-        window_size: impl Into<Vec2>,
-        app_state: AppState,
-    ) -> Self {
-        let instance = egui_wgpu::wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+impl<T: UserApp> App<T> {
+    /// The app's window, once created. Integrations can keep a clone.
+    pub fn window(&self) -> Option<&Arc<Window>> {
+        self.window.as_ref()
+    }
+
+    /// Lets external event routers tell the app's window events apart.
+    pub fn window_id(&self) -> Option<winit::window::WindowId> {
+        self.window.as_ref().map(|window| window.id())
+    }
+
+    /// For the application embedding this one, see [`crate::embed`].
+    pub fn host_handle(&self) -> HostHandle {
+        HostHandle::new(self.event_sender.clone())
+    }
+
+    /// Creates the event loop and runs the app to completion. Hosts that
+    /// need the event loop themselves use [`App::new`] with `run_app`.
+    pub fn run(options: Options, app_state: T) -> Result<(), AppError> {
+        check_main_thread()?;
+        let event_loop = EventLoop::with_user_event().build()?;
+        let mut app = App::new(&event_loop, options, app_state);
+        event_loop.run_app(&mut app)?;
+        app.return_result
+    }
+
+    /// Like [`App::run`], painting with `make_painter`'s painter instead of
+    /// the built-in wgpu renderer.
+    pub fn run_with_painter(
+        options: Options,
+        app_state: T,
+        make_painter: impl FnOnce(&Arc<Window>) -> anyhow::Result<Box<dyn EguiPainter>> + 'static,
+    ) -> Result<(), AppError> {
+        check_main_thread()?;
+        let event_loop = EventLoop::with_user_event().build()?;
+        let mut app = App::new(&event_loop, options, app_state);
+        app.set_painter(make_painter);
+        event_loop.run_app(&mut app)?;
+        app.return_result
+    }
+
+    /// Paints with `make_painter`'s painter, created once the window exists,
+    /// instead of the built-in wgpu renderer. Call before the event loop
+    /// runs.
+    pub fn set_painter(
+        &mut self,
+        make_painter: impl FnOnce(&Arc<Window>) -> anyhow::Result<Box<dyn EguiPainter>> + 'static,
+    ) {
+        self.make_painter = Some(Box::new(make_painter));
+    }
+
+    pub fn new(event_loop: &EventLoop<UserEvent>, options: Options, app_state: T) -> Self {
+        log::info!("wgpu instance flags: {:?}", options.instance_flags);
+        crash_report::set_section("settings", format!("{options:#?}"));
+        let event_trace = options
+            .trace_events
+            .then(|| EventTrace::new(options.trace_redact));
         Self {
-            windows_next_repaint_time: None,
-            repaint_proxy: Arc::new(Mutex::new(event_loop.create_proxy())),
-            instance,
+            scheduler: Scheduler::new(cfg!(target_os = "windows")),
+            event_sender: EventSender::new(event_loop.create_proxy()),
             renderer: None,
+            painter: None,
+            make_painter: None,
             window: None,
-            window_size: window_size.into(),
+            pending_egui_ctx: None,
+            init_started: None,
+            options,
             app_state: Some(app_state),
+            loading: None,
+            suspended: false,
+            event_trace,
+            pending_events: PendingEvents::default(),
+            host_size: None,
+            #[cfg(feature = "remote-control")]
+            remote_server_started: false,
+            metrics: None,
+            dock: Dock::default(),
+            jump_list: JumpList::default(),
             return_result: Ok(()),
         }
     }
@@ -259,176 +1775,658 @@ impl App {
         &self,
         egui_ctx: &egui::Context,
         event_loop: &ActiveEventLoop,
+        parent_window: Option<RawWindowHandle>,
     ) -> Result<Window, winit::error::OsError> {
-        let viewport_builder = egui::ViewportBuilder::default()
-            .with_inner_size(self.window_size)
+        let mut viewport_builder = egui::ViewportBuilder::default()
+            .with_title(env!("CARGO_PKG_NAME"))
+            .with_inner_size(self.options.window_size)
             .with_visible(false);
-        let window = egui_winit::create_window(egui_ctx, event_loop, &viewport_builder)?;
+        let native_shadow =
+            self.options.window_shadow && self.options.custom_titlebar && window_shadow::NATIVE;
+        if native_shadow && cfg!(target_os = "macos") {
+            // Borderless windows lose the shadow and the rounded corners;
+            // a hidden titlebar keeps both.
+            viewport_builder = viewport_builder
+                .with_fullsize_content_view(true)
+                .with_titlebar_shown(false)
+                .with_title_shown(false)
+                .with_titlebar_buttons_shown(false);
+        } else if self.options.custom_titlebar || self.options.kiosk.is_some() {
+            viewport_builder = viewport_builder.with_decorations(false);
+        }
+        if self.options.kiosk.is_some() || self.options.click_through {
+            viewport_builder = viewport_builder.with_window_level(egui::WindowLevel::AlwaysOnTop);
+        }
+        if self.options.click_through {
+            viewport_builder = viewport_builder.with_decorations(false);
+        }
+        if self.options.transparent_window() {
+            viewport_builder = viewport_builder.with_transparent(true);
+        }
+        if parent_window.is_some() {
+            viewport_builder = viewport_builder
+                .with_decorations(false)
+                .with_position(egui::Pos2::ZERO);
+        }
+        if self.options.unified_titlebar {
+            // Only applied on macOS.
+            viewport_builder = viewport_builder
+                .with_fullsize_content_view(true)
+                .with_titlebar_shown(false)
+                .with_title_shown(false);
+        }
+        #[allow(unused_mut)]
+        let mut window_attributes = egui_winit::create_winit_window_attributes(
+            egui_ctx,
+            event_loop,
+            viewport_builder.clone(),
+        );
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))]
+        {
+            use winit::platform::startup_notify::{
+                self, EventLoopExtStartupNotify, WindowAttributesExtStartupNotify,
+            };
+            // Ends the launcher's startup feedback once the window maps, and
+            // lets Wayland compositors give the window initial focus.
+            if let Some(token) = event_loop.read_token_from_env() {
+                startup_notify::reset_activation_token_env();
+                window_attributes = window_attributes.with_activation_token(token);
+            }
+        }
+        #[cfg(windows)]
+        if native_shadow {
+            use winit::platform::windows::WindowAttributesExtWindows;
+            window_attributes = window_attributes.with_undecorated_shadow(true);
+        }
+        if let Some(parent_window) = parent_window {
+            // SAFETY: hosts keep their window alive while the app runs, see
+            // `embed::ParentWindow`.
+            window_attributes =
+                unsafe { window_attributes.with_parent_window(Some(parent_window)) };
+        }
+        let window = event_loop.create_window(window_attributes)?;
+        egui_winit::apply_viewport_builder_to_window(egui_ctx, &window, &viewport_builder);
+        if !self.options.transparent_window() {
+            window_background::set_background_color(
+                &window,
+                window_background::initial_color(egui_ctx, window.theme()),
+            );
+        }
+        if let Some(backdrop) = self.options.backdrop {
+            titlebar::set_backdrop(&window, backdrop);
+        }
+        if native_shadow {
+            titlebar::set_rounded_corners(&window, true);
+        }
         Ok(window)
     }
 
-    fn initialize(&mut self, event_loop: &ActiveEventLoop) -> anyhow::Result<()> {
-        let egui_ctx = EguiRenderer::create_egui_context();
-        let window = self.create_window(&egui_ctx, event_loop)?;
+    fn initialize(&mut self, event_loop: &ActiveEventLoop) -> Result<(), AppError> {
+        let init_started = Instant::now();
+        self.init_started = Some(init_started);
+        let egui_ctx = EguiRenderer::create_egui_context(&self.options.egui);
+        let parent_window = self
+            .options
+            .parent_window
+            .map(|parent_window| parent_window.raw_window_handle(event_loop))
+            .transpose()?;
+        let window = self.create_window(&egui_ctx, event_loop, parent_window)?;
+        if let Some([width, height]) = self.host_size.take() {
+            let _ = window.request_inner_size(PhysicalSize::new(width, height));
+        }
         self.init_run_state(egui_ctx, window)?;
+        #[cfg(feature = "remote-control")]
+        if let Some(remote_control) = &self.options.remote_control {
+            if !self.remote_server_started {
+                remote_control::spawn_server(remote_control, self.event_sender.clone())?;
+                self.remote_server_started = true;
+            }
+        }
+        if let (Some(options), None) = (&self.options.metrics, &self.metrics) {
+            self.metrics = Some(metrics::start(options, self.event_sender.clone())?);
+        }
+        log::info!(
+            "event loop blocked for {:?} during initialization",
+            init_started.elapsed()
+        );
         Ok(())
     }
 
-    fn init_run_state(&mut self, egui_ctx: egui::Context, window: Window) -> anyhow::Result<()> {
+    fn init_run_state(&mut self, egui_ctx: egui::Context, window: Window) -> Result<(), AppError> {
         let window = Arc::new(window);
 
         {
-            let event_loop_proxy = self.repaint_proxy.clone();
+            let event_sender = self.event_sender.clone();
             egui_ctx.set_request_repaint_callback(move |info| {
                 let when = Instant::now() + info.delay;
                 let cumulative_pass_nr = info.current_cumulative_pass_nr;
-                event_loop_proxy
-                    .lock()
-                    .unwrap()
-                    .send_event(UserEvent::RequestRepaint {
-                        when,
-                        cumulative_pass_nr,
-                    })
-                    .ok();
+                event_sender.send(UserEvent::RequestRepaint {
+                    when,
+                    cumulative_pass_nr,
+                });
             });
         }
 
         let mut info = ViewportInfo::default();
         egui_winit::update_viewport_info(&mut info, &egui_ctx, &window, true);
 
-        let state = pollster::block_on(Renderer::new(
-            &self.instance,
-            egui_ctx,
-            window.clone(),
-            self.window_size.x as u32,
-            self.window_size.y as u32,
-            self.app_state.take().unwrap(),
-        ))?;
-        self.renderer = Some(state);
+        #[cfg(feature = "software-renderer")]
+        let gpu = !self.options.cpu_rendering;
+        #[cfg(not(feature = "software-renderer"))]
+        let gpu = true;
+        if self.make_painter.is_some() {
+            self.event_sender
+                .send(UserEvent::GpuReady(Box::new(Err(AppError::NoAdapter(
+                    vec!["skipped for a custom painter".to_owned()],
+                )))));
+        } else if gpu {
+            let (candidates, errors) = gpu::candidates(&window, &self.options);
+            let options = self.options.clone();
+            let event_sender = self.event_sender.clone();
+            std::thread::Builder::new()
+                .name("gpu-init".to_owned())
+                .spawn(move || {
+                    profiling::register_thread!("gpu-init");
+                    let result = pollster::block_on(gpu::init(candidates, errors, &options));
+                    event_sender.send(UserEvent::GpuReady(Box::new(result)));
+                })?;
+        } else {
+            self.event_sender
+                .send(UserEvent::GpuReady(Box::new(Err(AppError::NoAdapter(
+                    vec!["skipped for --cpu-rendering".to_owned()],
+                )))));
+        }
+        if let Some(exit_after) = self.options.exit_after {
+            let egui_ctx = egui_ctx.clone();
+            std::thread::Builder::new()
+                .name("exit-watchdog".to_owned())
+                .spawn(move || {
+                    std::thread::sleep(exit_after);
+                    log::warn!("exiting after {exit_after:?}");
+                    close::force_exit(&egui_ctx);
+                })?;
+        }
+
+        self.pending_egui_ctx = Some(egui_ctx);
         self.window = Some(window);
 
         Ok(())
     }
 
-    fn run_ui_and_paint(&mut self, window: &Window) -> anyhow::Result<EventResult> {
+    fn on_gpu_ready(&mut self, gpu: Result<GpuContext, AppError>) -> Result<EventResult, AppError> {
+        let gpu = match gpu {
+            Err(AppError::NoAdapter(_)) if self.make_painter.is_some() => {
+                let make_painter = self.make_painter.take().unwrap();
+                return self.start_painter(make_painter);
+            }
+            #[cfg(feature = "software-renderer")]
+            Err(err @ AppError::NoAdapter(_)) if self.options.cpu_fallback => {
+                log::warn!("{err}");
+                log::warn!("painting on the CPU");
+                return self
+                    .start_painter(Box::new(|window| {
+                        Ok(Box::new(SoftwarePainter::new(window)?))
+                    }))
+                    .map_err(|software_err| {
+                        log::error!("cannot paint on the CPU either: {software_err}");
+                        err
+                    });
+            }
+            gpu => gpu?,
+        };
+        let (Some(window), Some(egui_ctx), Some(app_state)) = (
+            self.window.clone(),
+            self.pending_egui_ctx.take(),
+            self.app_state.take(),
+        ) else {
+            return Ok(EventResult::Wait);
+        };
+
+        let mut renderer = Renderer::new(gpu, egui_ctx, &window, &self.options, app_state)?;
+        renderer.metrics = self.metrics.clone();
+        if self.suspended {
+            renderer.suspend()?;
+        }
+        let result = self
+            .pending_events
+            .replay(|event| renderer.on_window_event(event, &window));
+        self.renderer = Some(renderer);
+        if let Some(init_started) = self.init_started.take() {
+            log::info!("GPU initialized in {:?}", init_started.elapsed());
+        }
+
+        Ok(result)
+    }
+
+    fn start_painter(&mut self, make_painter: PainterFactory) -> Result<EventResult, AppError> {
+        let (Some(window), Some(egui_ctx), Some(app_state)) = (
+            self.window.clone(),
+            self.pending_egui_ctx.take(),
+            self.app_state.take(),
+        ) else {
+            return Ok(EventResult::Wait);
+        };
+        let painter = make_painter(&window)?;
+        let mut frontend =
+            PainterFrontend::new(egui_ctx, &window, &self.options, app_state, painter);
+        let result = self
+            .pending_events
+            .replay(|event| frontend.on_window_event(event, &window));
+        self.painter = Some(frontend);
+        Ok(result)
+    }
+
+    fn on_state_ready(&mut self, state: Box<dyn Any + Send>) -> EventResult {
+        let Some(loading) = self.loading.take() else {
+            return EventResult::Wait;
+        };
+        // Sent by `new_loading`, for which `T` is the loading app.
+        let Ok(state) = state.downcast::<Result<T, String>>() else {
+            return EventResult::Wait;
+        };
+        let state = match *state {
+            Ok(state) => state,
+            Err(message) => {
+                log::error!("initialization failed: {message}");
+                loading.fail(message.clone());
+                self.fail(AppError::User(anyhow::anyhow!(message)));
+                return EventResult::RepaintNow;
+            }
+        };
+        if let (Some(painter), Some(window)) = (&mut self.painter, &self.window) {
+            painter.replace_state(state, window);
+            return EventResult::RepaintNow;
+        }
+        match (&mut self.renderer, &self.window) {
+            (Some(renderer), Some(window)) => renderer.replace_state(state, window),
+            // Still waiting for the GPU, so the loading view never showed.
+            _ => self.app_state = Some(state),
+        }
+        EventResult::RepaintNow
+    }
+
+    fn run_ui_and_paint(&mut self, window: &Arc<Window>) -> Result<EventResult, AppError> {
         if let Some(renderer) = &mut self.renderer {
-            renderer.run_ui_and_paint(window)
-        } else {
-            Ok(EventResult::Wait)
+            return renderer.run_ui_and_paint(window);
         }
+        if let Some(painter) = &mut self.painter {
+            return painter.run_ui_and_paint(window);
+        }
+        Ok(EventResult::Wait)
     }
 
     fn handle_event_result(
         &mut self,
         event_loop: &ActiveEventLoop,
-        event_result: anyhow::Result<EventResult>,
+        mut event_result: Result<EventResult, AppError>,
     ) {
         let mut exit = false;
-
-        let combined_result = event_result.and_then(|event_result| match event_result {
-            EventResult::Wait => {
-                event_loop.set_control_flow(ControlFlow::Wait);
-                Ok(event_result)
-            }
-            EventResult::RepaintNow => {
-                if cfg!(target_os = "windows") {
-                    if let Some(ref window) = self.window.as_ref().cloned() {
-                        self.run_ui_and_paint(window)
-                    } else {
-                        event_loop.set_control_flow(ControlFlow::Wait);
-                        Ok(event_result)
+        let mut painted = false;
+        loop {
+            let result = match event_result {
+                Ok(result) => result,
+                Err(err) if err.is_recoverable() => {
+                    if let Some(renderer) = &mut self.renderer {
+                        renderer.recover(&err);
                     }
+                    self.scheduler.request(Instant::now());
+                    break;
+                }
+                Err(err) => {
+                    exit = true;
+                    self.fail(err);
+                    break;
+                }
+            };
+            let Self {
+                scheduler,
+                renderer,
+                ..
+            } = self;
+            let action = scheduler.on_result(result, Instant::now(), painted, |when, snap| {
+                let Some(renderer) = renderer else {
+                    return when;
+                };
+                let when = renderer.paced(when);
+                if snap {
+                    renderer.snap_to_vsync(when)
                 } else {
-                    self.windows_next_repaint_time = Some(Instant::now());
-                    Ok(event_result)
+                    when
+                }
+            });
+            match action {
+                Action::None => break,
+                Action::Exit => {
+                    exit = true;
+                    break;
+                }
+                Action::PaintNow => {
+                    let Some(window) = self.window.clone() else {
+                        break;
+                    };
+                    painted = true;
+                    event_result = self.run_ui_and_paint(&window);
                 }
             }
-            EventResult::RepaintNext => {
-                self.windows_next_repaint_time = Some(Instant::now());
-                Ok(event_result)
+        }
+
+        if exit {
+            event_loop.exit();
+        } else {
+            self.update_mirror(event_loop);
+            if let Some(egui_ctx) = self.egui_ctx().cloned() {
+                self.dock.update(&egui_ctx, &self.event_sender);
+                self.jump_list.update(&egui_ctx);
             }
-            EventResult::RepaintAt(repaint_time) => {
-                self.windows_next_repaint_time = Some(
-                    self.windows_next_repaint_time
-                        .map_or(repaint_time, |last| last.min(repaint_time)),
+            #[cfg(feature = "dialogs")]
+            self.open_file_dialogs();
+            #[cfg(feature = "notifications")]
+            self.show_notifications();
+        }
+
+        self.check_redraw_requests(event_loop);
+    }
+
+    fn update_mirror(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(renderer), Some(window)) = (&mut self.renderer, &self.window) else {
+            return;
+        };
+        match renderer.take_mirror_request() {
+            Some(true) => {
+                let attributes = mirror::window_attributes(
+                    event_loop,
+                    window,
+                    self.options.mirror_monitor.as_deref(),
                 );
-                Ok(event_result)
-            }
-            EventResult::Exit => {
-                exit = true;
-                Ok(event_result)
+                match event_loop.create_window(attributes) {
+                    Ok(mirror_window) => renderer.open_mirror(mirror_window),
+                    Err(err) => log::warn!("cannot create the mirror window: {err}"),
+                }
             }
-        });
+            Some(false) => renderer.close_mirror(),
+            None => {}
+        }
+    }
 
-        if let Err(err) = combined_result {
-            exit = true;
-            self.return_result = Err(err);
+    /// The context of whichever of the renderer and the painter runs the UI.
+    fn egui_ctx(&self) -> Option<&egui::Context> {
+        if let Some(renderer) = &self.renderer {
+            return Some(renderer.egui_renderer.egui_winit.egui_ctx());
+        }
+        self.painter.as_ref().map(PainterFrontend::egui_ctx)
+    }
+
+    #[cfg(feature = "dialogs")]
+    fn open_file_dialogs(&self) {
+        let (Some(egui_ctx), Some(window)) = (self.egui_ctx(), &self.window) else {
+            return;
         };
+        for request in file_dialog::take_requests(egui_ctx) {
+            file_dialog::open(request, window, self.event_sender.clone());
+        }
+    }
 
-        if exit {
-            event_loop.exit();
+    #[cfg(feature = "notifications")]
+    fn show_notifications(&self) {
+        let Some(egui_ctx) = self.egui_ctx() else {
+            return;
+        };
+        for (id, notification) in notifications::take_requests(egui_ctx) {
+            notifications::show(id, notification, self.event_sender.clone());
         }
+    }
 
-        self.check_redraw_requests(event_loop);
+    /// Shared by normal and error exits: writes what the renderer collected,
+    /// then releases the GPU resources before the window they refer to.
+    fn shutdown(&mut self) {
+        if let Some(mut renderer) = self.renderer.take() {
+            if let Err(err) = renderer.finish() {
+                self.fail(err);
+            }
+        }
+        if let Some(mut painter) = self.painter.take() {
+            painter.finish();
+        }
+        log::debug!("shutdown: releasing the window");
+        self.window = None;
+    }
+
+    /// Records a fatal error for `run_app` to return; only the first counts.
+    fn fail(&mut self, err: AppError) {
+        // `run_app` never returns on iOS, so the error would be lost.
+        if cfg!(target_os = "ios") {
+            log::error!("{err:?}");
+        }
+        crash_report::write(&format!("{err:?}"));
+        if self.return_result.is_ok() {
+            self.return_result = Err(err);
+        }
     }
 
     fn check_redraw_requests(&mut self, event_loop: &ActiveEventLoop) {
-        let now = Instant::now();
-        if let Some(next_repaint_time) = self.windows_next_repaint_time {
-            if now >= next_repaint_time {
-                self.windows_next_repaint_time = None;
-                if let Some(ref window) = self.window {
-                    window.request_redraw();
-                }
-            } else {
-                event_loop.set_control_flow(ControlFlow::WaitUntil(next_repaint_time));
+        let poll = self.scheduler.poll(Instant::now());
+        event_loop.set_control_flow(poll.control_flow);
+        if poll.redraw {
+            if let Some(window) = &self.window {
+                window.request_redraw();
             }
         }
     }
 
     fn on_window_event(
         &mut self,
-        event: winit::event::WindowEvent,
-        window: &Window,
-    ) -> anyhow::Result<EventResult> {
+        event: &winit::event::WindowEvent,
+        window: &Arc<Window>,
+    ) -> Result<EventResult, AppError> {
+        if let winit::event::WindowEvent::MouseInput {
+            state: winit::event::ElementState::Pressed,
+            ..
+        } = event
+        {
+            if self.options.parent_window.is_some() {
+                embed::focus(window);
+            }
+        }
         if let Some(renderer) = &mut self.renderer {
-            Ok(renderer.on_window_event(&event, window))
-        } else {
-            Ok(EventResult::Wait)
+            return Ok(renderer.on_window_event(event, window));
         }
+        if let Some(painter) = &mut self.painter {
+            return Ok(painter.on_window_event(event, window));
+        }
+        // Closing has to work during a slow startup too.
+        if let winit::event::WindowEvent::CloseRequested = event {
+            return Ok(EventResult::Exit);
+        }
+        self.pending_events.push(event);
+        Ok(EventResult::Wait)
     }
 
     fn on_device_event(
         &mut self,
         _event_loop: &ActiveEventLoop,
-        _device_id: winit::event::DeviceId,
+        device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
-    ) -> anyhow::Result<EventResult> {
-        if let Some(renderer) = &mut self.renderer {
-            Ok(renderer.on_device_event(event))
+    ) -> Result<EventResult, AppError> {
+        if let (Some(renderer), Some(window)) = (&mut self.renderer, &self.window) {
+            Ok(renderer.on_device_event(device_id, event, window))
         } else {
             Ok(EventResult::Wait)
         }
     }
 
-    fn on_resumed(&mut self, event_loop: &ActiveEventLoop) -> Result<EventResult, anyhow::Error> {
-        if self.window.is_none() {
-            self.initialize(event_loop)?;
+    fn on_resumed(&mut self, event_loop: &ActiveEventLoop) -> Result<EventResult, AppError> {
+        self.suspended = false;
+        if let Some(metrics) = &self.metrics {
+            metrics.set_suspended(false);
+        }
+        match (&self.window, &mut self.renderer) {
+            (None, _) => self.initialize(event_loop)?,
+            (Some(window), Some(renderer)) if renderer.suspended => {
+                renderer.resume(window.clone())?;
+            }
+            _ => {}
         }
         Ok(EventResult::RepaintNow)
     }
 
-    fn on_user_event(&mut self, event: UserEvent) -> Result<EventResult, anyhow::Error> {
+    fn on_suspended(&mut self) -> Result<EventResult, AppError> {
+        self.suspended = true;
+        self.scheduler.clear();
+        if let Some(metrics) = &self.metrics {
+            metrics.set_suspended(true);
+        }
+        if let Some(renderer) = &mut self.renderer {
+            renderer.suspend()?;
+        }
+        Ok(EventResult::Wait)
+    }
+
+    fn on_host_request(&mut self, request: HostRequest) -> Result<EventResult, AppError> {
+        match request {
+            HostRequest::Resize([width, height]) => {
+                let size = [width.max(1), height.max(1)];
+                let Some(window) = self.window.clone() else {
+                    self.host_size = Some(size);
+                    return Ok(EventResult::Wait);
+                };
+                match window.request_inner_size(PhysicalSize::new(size[0], size[1])) {
+                    // Applied right away, maybe without a resize event.
+                    Some(size) => {
+                        self.on_window_event(&winit::event::WindowEvent::Resized(size), &window)
+                    }
+                    None => Ok(EventResult::Wait),
+                }
+            }
+            HostRequest::Close => {
+                log::info!("the host closed the app");
+                Ok(EventResult::Exit)
+            }
+        }
+    }
+
+    fn on_user_event(&mut self, event: UserEvent) -> Result<EventResult, AppError> {
+        match event {
+            UserEvent::GpuReady(gpu) => return self.on_gpu_ready(*gpu),
+            UserEvent::StateReady(state) => return Ok(self.on_state_ready(state)),
+            UserEvent::Host(request) => return self.on_host_request(request),
+            UserEvent::MetricsHeartbeat => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.on_heartbeat();
+                }
+                return Ok(EventResult::Wait);
+            }
+            #[cfg(feature = "remote-control")]
+            UserEvent::Remote(request) => {
+                // Dropping the request tells the client that nothing renders.
+                return Ok(match (&mut self.renderer, &self.window) {
+                    (Some(renderer), Some(window)) => renderer.on_remote_request(request, window),
+                    _ => EventResult::Wait,
+                });
+            }
+            #[cfg(feature = "dialogs")]
+            UserEvent::Dialog(id, result) => {
+                if let Some(egui_ctx) = self.egui_ctx() {
+                    file_dialog::set_result(egui_ctx, id, result);
+                }
+                return Ok(EventResult::RepaintNow);
+            }
+            #[cfg(feature = "notifications")]
+            UserEvent::Notification(id, event) => {
+                if let (true, Some(window)) = (event.activates(), &self.window) {
+                    window.set_minimized(false);
+                    window.focus_window();
+                }
+                if let Some(egui_ctx) = self.egui_ctx() {
+                    notifications::push_event(egui_ctx, id, event);
+                }
+                return Ok(EventResult::RepaintNow);
+            }
+            UserEvent::DockMenu(id) => {
+                if let Some(egui_ctx) = self.egui_ctx() {
+                    dock::push_selection(egui_ctx, id);
+                }
+                return Ok(EventResult::RepaintNow);
+            }
+            _ => {}
+        }
         if let Some(renderer) = &mut self.renderer {
             return Ok(renderer.on_user_event(event));
         }
+        if let Some(painter) = &self.painter {
+            return Ok(painter.on_user_event(event));
+        }
         Ok(EventResult::Wait)
     }
 }
 
-impl ApplicationHandler<UserEvent> for App {
+impl<F: FnMut(&egui::Context) + 'static> App<SimpleApp<F>> {
+    /// Runs a closure as the whole UI in a window of the given size.
+    pub fn run_simple(window_size: egui::Vec2, update: F) -> Result<(), AppError> {
+        let options = Options {
+            window_size,
+            ..Default::default()
+        };
+        Self::run(options, SimpleApp(update))
+    }
+}
+
+impl<T: UserApp + Send> App<LoadingApp<T>> {
+    /// Like [`App::run`] with [`App::new_loading`].
+    pub fn run_loading(
+        options: Options,
+        init: impl FnOnce(LoadingProgress) -> anyhow::Result<T> + Send + 'static,
+    ) -> Result<(), AppError> {
+        check_main_thread()?;
+        let event_loop = EventLoop::with_user_event().build()?;
+        let mut app = App::new_loading(&event_loop, options, init)?;
+        event_loop.run_app(&mut app)?;
+        app.return_result
+    }
+
+    /// Shows a loading view right away while `init` builds the app's state
+    /// on a thread, for apps whose initialization would otherwise keep the
+    /// window from responding. An error is shown in the view and returned
+    /// once the window is closed.
+    pub fn new_loading(
+        event_loop: &EventLoop<UserEvent>,
+        options: Options,
+        init: impl FnOnce(LoadingProgress) -> anyhow::Result<T> + Send + 'static,
+    ) -> Result<Self, AppError> {
+        let (progress, view) = loading::channel();
+        let mut app = Self::new(event_loop, options, LoadingApp::Loading(view));
+        let event_sender = app.event_sender.clone();
+        app.loading = Some(progress.clone());
+        std::thread::Builder::new()
+            .name("app-init".to_owned())
+            .spawn(move || {
+                profiling::register_thread!("app-init");
+                let result: Result<LoadingApp<T>, String> = init(progress)
+                    .map(LoadingApp::Ready)
+                    .map_err(|err| format!("{err:#}"));
+                event_sender.send(UserEvent::StateReady(Box::new(result)));
+            })?;
+        Ok(app)
+    }
+}
+
+impl<T: UserApp> ApplicationHandler<UserEvent> for App<T> {
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.shutdown();
+    }
+
+    fn memory_warning(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(renderer) = &mut self.renderer {
+            renderer.on_memory_warning();
+        }
+        self.handle_event_result(event_loop, Ok(EventResult::RepaintNow));
+    }
+
     fn suspended(&mut self, event_loop: &ActiveEventLoop) {
-        self.handle_event_result(event_loop, Ok(EventResult::Wait));
+        let event_result = self.on_suspended();
+        self.handle_event_result(event_loop, event_result);
     }
 
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
@@ -442,12 +2440,23 @@ impl ApplicationHandler<UserEvent> for App {
         device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
+        let traced = self.event_trace.is_some().then(|| event.clone());
         let event_result = self.on_device_event(event_loop, device_id, event);
+        if let (Some(event_trace), Some(event)) = (&mut self.event_trace, traced) {
+            event_trace.device_event(&event, &event_result);
+        }
         self.handle_event_result(event_loop, event_result);
     }
 
     fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        let traced = self.event_trace.is_some().then(|| match &event {
+            UserEvent::GpuReady(gpu) => format!("GpuReady(ok: {})", gpu.is_ok()),
+            event => format!("{event:?}"),
+        });
         let event_result = self.on_user_event(event);
+        if let (Some(event_trace), Some(event)) = (&mut self.event_trace, traced) {
+            event_trace.user_event(&format_args!("{event}"), &event_result);
+        }
         self.handle_event_result(event_loop, event_result);
     }
 
@@ -458,19 +2467,225 @@ impl ApplicationHandler<UserEvent> for App {
     fn window_event(
         &mut self,
         event_loop: &egui_winit::winit::event_loop::ActiveEventLoop,
-        _: egui_winit::winit::window::WindowId,
+        window_id: egui_winit::winit::window::WindowId,
         event: egui_winit::winit::event::WindowEvent,
     ) {
+        if let Some(renderer) = &mut self.renderer {
+            if renderer.mirror_window_id() == Some(window_id) {
+                let event_result = Ok(renderer.on_mirror_event(&event));
+                self.handle_event_result(event_loop, event_result);
+                return;
+            }
+        }
+        if self.window_id().is_some_and(|id| id != window_id) {
+            // A window created by the app itself, e.g. through a handle.
+            return;
+        }
         let event_result = {
-            if let Some(window) = self.window.as_ref().cloned() {
+            if let Some(window) = self.window().cloned() {
                 match event {
                     winit::event::WindowEvent::RedrawRequested => self.run_ui_and_paint(&window),
-                    _ => self.on_window_event(event, &window),
+                    _ => self.on_window_event(&event, &window),
                 }
             } else {
                 Ok(EventResult::Wait)
             }
         };
+        if let Some(event_trace) = &mut self.event_trace {
+            let consumed = self
+                .renderer
+                .as_ref()
+                .is_some_and(|renderer| renderer.last_event_consumed);
+            event_trace.window_event(&event, consumed, &event_result);
+        }
         self.handle_event_result(event_loop, event_result);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    fn cursor_moved(x: f64) -> winit::event::WindowEvent {
+        winit::event::WindowEvent::CursorMoved {
+            device_id: winit::event::DeviceId::dummy(),
+            position: winit::dpi::PhysicalPosition::new(x, 0.),
+        }
+    }
+
+    #[test]
+    fn pending_events_keep_the_latest_in_order() {
+        let mut pending = PendingEvents::default();
+        for x in 0..MAX_PENDING_EVENTS + 10 {
+            pending.push(&cursor_moved(x as f64));
+        }
+        let mut replayed = Vec::new();
+        let result = pending.replay(|event| {
+            if let winit::event::WindowEvent::CursorMoved { position, .. } = event {
+                replayed.push(position.x as usize);
+            }
+            EventResult::Wait
+        });
+        assert!(matches!(result, EventResult::RepaintNow));
+        assert_eq!(replayed, (10..MAX_PENDING_EVENTS + 10).collect::<Vec<_>>());
+        // Replaying empties the queue.
+        assert!(matches!(
+            pending.replay(|_| unreachable!()),
+            EventResult::RepaintNow
+        ));
+    }
+
+    #[test]
+    fn pending_events_exit_if_any_did() {
+        let mut pending = PendingEvents::default();
+        for x in 0..3 {
+            pending.push(&cursor_moved(x as f64));
+        }
+        let mut replayed = 0;
+        let result = pending.replay(|_| {
+            replayed += 1;
+            if replayed == 2 {
+                EventResult::Exit
+            } else {
+                EventResult::Wait
+            }
+        });
+        assert!(matches!(result, EventResult::Exit));
+        assert_eq!(replayed, 3);
+    }
+
+    #[test]
+    fn replayed_resize_sizes_the_first_frame() {
+        let Some(mut test_app) = TestApp::new(SimpleApp(|_: &egui::Context| {})) else {
+            return;
+        };
+        let mut pending = PendingEvents::default();
+        pending.push(&test_app.resize_event([320, 200]));
+        pending.replay(|event| {
+            test_app.window_event(event.clone());
+            EventResult::Wait
+        });
+        let frame = test_app.step(Vec::new());
+        assert_eq!(test_app.surface_size(), [320, 200]);
+        assert_eq!(frame.pixels.unwrap().0, [320, 200]);
+    }
+
+    #[test]
+    fn main_thread_is_checked_by_more_than_its_name() {
+        let result = std::thread::Builder::new()
+            .name("main".to_owned())
+            .spawn(check_main_thread)
+            .unwrap()
+            .join()
+            .unwrap();
+        if cfg!(target_vendor = "apple") {
+            assert!(matches!(result, Err(AppError::NotMainThread)));
+        } else {
+            assert!(result.is_ok());
+        }
+    }
+
+    /// Shows a texture of its own across the window, cleared to `color`
+    /// before each frame and recreated when `side` changes.
+    struct Swatch {
+        color: wgpu::Color,
+        side: u32,
+        texture: Option<(egui::TextureId, wgpu::Texture)>,
+    }
+
+    fn swatch_texture(device: &wgpu::Device, side: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("swatch"),
+            size: wgpu::Extent3d {
+                width: side,
+                height: side,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    }
+
+    impl UserApp for Swatch {
+        fn update(&mut self, ctx: &egui::Context) {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none())
+                .show(ctx, |ui| {
+                    if let Some((id, _)) = &self.texture {
+                        ui.add(egui::Image::new((*id, ui.available_size())));
+                    }
+                });
+        }
+
+        fn on_gpu_textures(&mut self, gpu: &mut GpuTextures) {
+            let texture = swatch_texture(gpu.device, self.side);
+            self.texture = Some((gpu.register(&texture, wgpu::FilterMode::Nearest), texture));
+        }
+
+        fn prepare_frame(
+            &mut self,
+            _ctx: &egui::Context,
+            gpu: &mut GpuTextures,
+            encoder: &mut wgpu::CommandEncoder,
+        ) {
+            let (id, texture) = self.texture.as_mut().unwrap();
+            if texture.width() != self.side {
+                *texture = swatch_texture(gpu.device, self.side);
+                gpu.update(*id, texture, wgpu::FilterMode::Nearest);
+            }
+            let view = texture.create_view(&Default::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("swatch pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        }
+    }
+
+    #[test]
+    fn app_renders_into_its_own_texture() {
+        let swatch = Swatch {
+            color: wgpu::Color::RED,
+            side: 4,
+            texture: None,
+        };
+        // The UI stays the same while the texture changes.
+        let options = Options {
+            window_size: egui::vec2(800., 600.),
+            skip_unchanged_frames: false,
+            ..Default::default()
+        };
+        let Some(mut test_app) = TestApp::with_options(swatch, options) else {
+            return;
+        };
+        let center = |frame: crate::testing::Frame| {
+            let ([width, height], pixels) = frame.pixels.unwrap();
+            let i = 4 * (height / 2 * width + width / 2) as usize;
+            pixels[i..i + 4].to_vec()
+        };
+        assert_eq!(center(test_app.step(Vec::new())), [255, 0, 0, 255]);
+
+        // The new texture takes over the id egui shows.
+        let app = test_app.app_mut();
+        app.color = wgpu::Color::GREEN;
+        app.side = 8;
+        let id = app.texture.as_ref().unwrap().0;
+        assert_eq!(center(test_app.step(Vec::new())), [0, 255, 0, 255]);
+        let (new_id, texture) = test_app.app().texture.as_ref().unwrap();
+        assert_eq!((*new_id, texture.width()), (id, 8));
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use anyhow::Context as _;
+use egui::{load::SizedTexture, ColorImage, TextureHandle, TextureOptions};
+
+#[cfg(feature = "asset-watch")]
+use crate::file_watch::FileWatcher;
+
+type FileCallback = Box<dyn FnMut(&Path, &[u8]) + Send>;
+
+enum Job {
+    Image(PathBuf),
+    File(PathBuf),
+}
+
+enum Content {
+    Image(ColorImage),
+    Bytes(Vec<u8>),
+}
+
+struct Loaded {
+    path: PathBuf,
+    content: anyhow::Result<Content>,
+}
+
+/// Images and other files the UI uses, read and decoded off the UI thread.
+/// With the `asset-watch` feature they are loaded again whenever they change
+/// on disk; a file that is deleted or fails to load keeps its last good
+/// version. Call [`Self::update`] once per frame.
+#[derive(Default)]
+pub struct Assets {
+    /// Started by the first asset, which brings the context.
+    loader: Option<Loader>,
+}
+
+struct Loader {
+    images: HashMap<PathBuf, TextureHandle>,
+    directories: Vec<(PathBuf, FileCallback)>,
+    jobs: Sender<Job>,
+    loaded: Receiver<Loaded>,
+    #[cfg(feature = "asset-watch")]
+    watcher: Option<FileWatcher>,
+}
+
+impl Assets {
+    /// The texture of the image at `path`. It stays the same across reloads,
+    /// and is transparent until the image has loaded.
+    pub fn image(&mut self, ctx: &egui::Context, path: impl AsRef<Path>) -> SizedTexture {
+        let path = path.as_ref();
+        let Some(loader) = self.loader(ctx) else {
+            return SizedTexture::new(egui::TextureId::default(), egui::Vec2::ZERO);
+        };
+        let handle = match loader.images.get(path) {
+            Some(handle) => handle,
+            None => {
+                let handle = ctx.load_texture(
+                    path.display().to_string(),
+                    ColorImage::new([1, 1], egui::Color32::TRANSPARENT),
+                    TextureOptions::LINEAR,
+                );
+                loader.watch(path);
+                let _ = loader.jobs.send(Job::Image(path.to_owned()));
+                loader.images.entry(path.to_owned()).or_insert(handle)
+            }
+        };
+        SizedTexture::from_handle(handle)
+    }
+
+    /// Calls `on_file` with the contents of each file directly in
+    /// `directory`, now and whenever one changes or is added.
+    pub fn directory(
+        &mut self,
+        ctx: &egui::Context,
+        directory: impl Into<PathBuf>,
+        on_file: impl FnMut(&Path, &[u8]) + Send + 'static,
+    ) {
+        let directory = directory.into();
+        let Some(loader) = self.loader(ctx) else {
+            return;
+        };
+        match std::fs::read_dir(&directory) {
+            Ok(entries) => {
+                for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                    if path.is_file() {
+                        let _ = loader.jobs.send(Job::File(path));
+                    }
+                }
+            }
+            Err(err) => log::warn!("{}: {err}", directory.display()),
+        }
+        loader.watch(&directory);
+        loader.directories.push((directory, Box::new(on_file)));
+    }
+
+    /// Hands finished loads to their textures and callbacks, and queues
+    /// changed files.
+    pub fn update(&mut self) {
+        let Some(loader) = &mut self.loader else {
+            return;
+        };
+        #[cfg(feature = "asset-watch")]
+        if let Some(watcher) = &loader.watcher {
+            for path in watcher.changes() {
+                let job = if loader.images.contains_key(&path) {
+                    Job::Image(path)
+                } else {
+                    Job::File(path)
+                };
+                let _ = loader.jobs.send(job);
+            }
+        }
+        for Loaded { path, content } in loader.loaded.try_iter() {
+            match content {
+                Ok(Content::Image(image)) => {
+                    if let Some(handle) = loader.images.get_mut(&path) {
+                        handle.set(image, TextureOptions::LINEAR);
+                    }
+                }
+                Ok(Content::Bytes(bytes)) => {
+                    let parent = path.parent();
+                    for (directory, on_file) in &mut loader.directories {
+                        if parent == Some(directory.as_path()) {
+                            on_file(&path, &bytes);
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!("{}: {err:#}, keeping the last good version", path.display())
+                }
+            }
+        }
+    }
+
+    fn loader(&mut self, ctx: &egui::Context) -> Option<&mut Loader> {
+        if self.loader.is_none() {
+            match Loader::start(ctx) {
+                Ok(loader) => self.loader = Some(loader),
+                Err(err) => log::error!("cannot start the asset loader: {err}"),
+            }
+        }
+        self.loader.as_mut()
+    }
+}
+
+impl Loader {
+    fn start(ctx: &egui::Context) -> std::io::Result<Self> {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (loaded_sender, loaded) = mpsc::channel();
+        let loader_ctx = ctx.clone();
+        let max_side = ctx.input(|i| i.max_texture_side);
+        std::thread::Builder::new()
+            .name("asset-loader".to_owned())
+            .spawn(move || {
+                profiling::register_thread!("asset-loader");
+                for job in job_receiver {
+                    let (path, content) = match job {
+                        Job::Image(path) => {
+                            let content = std::fs::read(&path)
+                                .map_err(anyhow::Error::from)
+                                .and_then(|bytes| decode_image(&bytes, max_side))
+                                .map(Content::Image);
+                            (path, content)
+                        }
+                        Job::File(path) => {
+                            let content =
+                                std::fs::read(&path).map(Content::Bytes).map_err(Into::into);
+                            (path, content)
+                        }
+                    };
+                    if loaded_sender.send(Loaded { path, content }).is_err() {
+                        return;
+                    }
+                    loader_ctx.request_repaint();
+                }
+            })?;
+        #[cfg(feature = "asset-watch")]
+        let watcher = {
+            let watcher_ctx = ctx.clone();
+            FileWatcher::new(move || watcher_ctx.request_repaint())
+                .map_err(|err| log::error!("cannot watch assets: {err}"))
+                .ok()
+        };
+        Ok(Self {
+            images: HashMap::new(),
+            directories: Vec::new(),
+            jobs,
+            loaded,
+            #[cfg(feature = "asset-watch")]
+            watcher,
+        })
+    }
+
+    #[cfg_attr(not(feature = "asset-watch"), allow(unused_variables))]
+    fn watch(&self, path: &Path) {
+        #[cfg(feature = "asset-watch")]
+        if let Some(watcher) = &self.watcher {
+            watcher.watch(path);
+        }
+    }
+}
+
+/// Whether [`decode_image`] reads files like `path`.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["pgm", "ppm", "pnm"].contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+/// Binary PGM and PPM (`P5`, `P6`) with up to 8 bits per channel, which need
+/// no decoder crate. Images with a side over `max_side`, the device's largest
+/// texture, are refused.
+pub fn decode_image(bytes: &[u8], max_side: usize) -> anyhow::Result<ColorImage> {
+    let mut fields = Vec::new();
+    let mut position = 0;
+    // The magic number, width, height and maximum value, each followed by
+    // whitespace, with comments running to the end of their line.
+    while fields.len() < 4 {
+        match bytes.get(position) {
+            Some(b'#') => {
+                while bytes.get(position).is_some_and(|&byte| byte != b'\n') {
+                    position += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => position += 1,
+            Some(_) => {
+                let start = position;
+                while bytes
+                    .get(position)
+                    .is_some_and(|byte| !byte.is_ascii_whitespace())
+                {
+                    position += 1;
+                }
+                fields.push(std::str::from_utf8(&bytes[start..position])?);
+                position += 1;
+            }
+            None => anyhow::bail!("truncated header"),
+        }
+    }
+    let channels = match fields[0] {
+        "P5" => 1,
+        "P6" => 3,
+        other => anyhow::bail!("not a binary PGM or PPM: {other}"),
+    };
+    let width: usize = fields[1].parse()?;
+    let height: usize = fields[2].parse()?;
+    let max: u32 = fields[3].parse()?;
+    anyhow::ensure!((1..=255).contains(&max), "unsupported maximum value {max}");
+    anyhow::ensure!(
+        width <= max_side && height <= max_side,
+        "image too large: {width}x{height}, the GPU takes up to {max_side}x{max_side}"
+    );
+    let len = width
+        .checked_mul(height)
+        .and_then(|len| len.checked_mul(channels))
+        .context("image too large")?;
+    let pixels = bytes
+        .get(position.min(bytes.len())..)
+        .and_then(|pixels| pixels.get(..len))
+        .context("truncated pixels")?;
+    let scale = |value: u8| (u32::from(value) * 255 / max).min(255) as u8;
+    let pixels = pixels
+        .chunks_exact(channels)
+        .map(|pixel| match *pixel {
+            [gray] => egui::Color32::from_gray(scale(gray)),
+            [r, g, b] => egui::Color32::from_rgb(scale(r), scale(g), scale(b)),
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok(ColorImage {
+        size: [width, height],
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-assets-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Updates `assets` until `done`, for up to five seconds.
+    fn wait_for(assets: &mut Assets, mut done: impl FnMut(&mut Assets) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            assets.update();
+            if done(assets) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn gray(width: usize) -> Vec<u8> {
+        let mut bytes = format!("P5 {width} 1 255\n").into_bytes();
+        bytes.resize(bytes.len() + width, 128);
+        bytes
+    }
+
+    #[test]
+    fn decodes_gray_with_comments() {
+        let image = decode_image(b"P5\n# a comment\n2 1\n255\n\x00\xff", 16).unwrap();
+        assert_eq!(image.size, [2, 1]);
+        assert_eq!(
+            image.pixels,
+            [egui::Color32::from_gray(0), egui::Color32::from_gray(255)]
+        );
+    }
+
+    #[test]
+    fn scales_colors_to_the_maximum_value() {
+        let image = decode_image(b"P6 1 1 15\n\x0f\x00\x05", 16).unwrap();
+        assert_eq!(image.pixels, [egui::Color32::from_rgb(255, 0, 85)]);
+    }
+
+    #[test]
+    fn refuses_broken_and_oversized_images() {
+        let error = |bytes: &[u8], max_side| decode_image(bytes, max_side).unwrap_err().to_string();
+        assert!(
+            error(b"P6 18446744073709551615 2 255\n", usize::MAX).starts_with("image too large")
+        );
+        assert!(error(b"P5 5 1 255\n\0\0\0\0\0", 4).starts_with("image too large"));
+        assert_eq!(error(b"P5 2 2 255\n\0\0", 16), "truncated pixels");
+        assert_eq!(error(b"P5 2 2", 16), "truncated header");
+        assert_eq!(error(b"P3 1 1 255\n", 16), "not a binary PGM or PPM: P3");
+        assert_eq!(
+            error(b"P5 1 1 1023\n", 16),
+            "unsupported maximum value 1023"
+        );
+    }
+
+    #[test]
+    fn image_keeps_its_texture_across_loads() {
+        let ctx = egui::Context::default();
+        let dir = temp_dir("image");
+        let path = dir.join("icon.pgm");
+        std::fs::write(&path, gray(2)).unwrap();
+        let mut assets = Assets::default();
+        let id = assets.image(&ctx, &path).id;
+        assert!(wait_for(&mut assets, |assets| {
+            assets.image(&ctx, &path).size == egui::vec2(2.0, 1.0)
+        }));
+        assert_eq!(assets.image(&ctx, &path).id, id);
+
+        #[cfg(feature = "asset-watch")]
+        {
+            std::fs::write(&path, gray(3)).unwrap();
+            assert!(wait_for(&mut assets, |assets| {
+                assets.image(&ctx, &path).size == egui::vec2(3.0, 1.0)
+            }));
+            assert_eq!(assets.image(&ctx, &path).id, id);
+
+            // Broken and deleted files keep the last good version.
+            std::fs::write(&path, b"P5 3").unwrap();
+            std::thread::sleep(Duration::from_secs(1));
+            std::fs::remove_file(&path).unwrap();
+            for _ in 0..100 {
+                assets.update();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!(assets.image(&ctx, &path).size, egui::vec2(3.0, 1.0));
+            assert_eq!(assets.image(&ctx, &path).id, id);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directory_reports_its_files() {
+        let ctx = egui::Context::default();
+        let dir = temp_dir("directory");
+        std::fs::write(dir.join("a.txt"), b"a").unwrap();
+        let mut assets = Assets::default();
+        let (sender, files) = mpsc::channel();
+        assets.directory(&ctx, &dir, move |path, bytes| {
+            let _ = sender.send((path.file_name().unwrap().to_owned(), bytes.to_vec()));
+        });
+        let mut seen = Vec::new();
+        let mut wait_for_file = |assets: &mut Assets, name: &str, content: &[u8]| {
+            wait_for(assets, |_| {
+                seen.extend(files.try_iter());
+                seen.iter()
+                    .any(|(file, bytes)| *file == *name && bytes == content)
+            })
+        };
+        assert!(wait_for_file(&mut assets, "a.txt", b"a"));
+
+        #[cfg(feature = "asset-watch")]
+        {
+            std::fs::write(dir.join("b.txt"), b"b").unwrap();
+            assert!(wait_for_file(&mut assets, "b.txt", b"b"));
+            std::fs::write(dir.join("a.txt"), b"changed").unwrap();
+            assert!(wait_for_file(&mut assets, "a.txt", b"changed"));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use egui_winit::winit::window::Window;
+use web_time::Instant;
+
+fn request_id() -> egui::Id {
+    egui::Id::new("background_drag_request")
+}
+
+/// Where a press on empty background moves the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DragRegion {
+    Everywhere,
+    /// Only this many points from the top, like a titlebar.
+    TopStrip(f32),
+}
+
+/// Lets a primary press on empty background move the window, and a double
+/// click there toggle maximize. Call every frame it should apply, so that it
+/// can be left out e.g. while a canvas tool is active.
+pub fn enable(ctx: &egui::Context, region: DragRegion) {
+    ctx.data_mut(|d| d.insert_temp(request_id(), Some(region)));
+}
+
+/// Takes presses on empty background away from egui and starts an OS move
+/// instead. Whatever senses clicks or drags counts as not empty, which
+/// includes resize handles, scroll areas and windows.
+#[derive(Default)]
+pub struct BackgroundDrag {
+    /// Where the pointer was over empty background after the last pass.
+    background: Option<egui::Pos2>,
+    last_press: Option<(Instant, egui::Pos2)>,
+}
+
+impl BackgroundDrag {
+    /// Call after each pass.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        let region = ctx
+            .data_mut(|d| d.remove_temp::<Option<DragRegion>>(request_id()))
+            .flatten();
+        self.background = region.and_then(|region| background_under_pointer(ctx, region));
+        if self.background.is_none() {
+            self.last_press = None;
+        }
+    }
+
+    /// Returns whether the press moved or maximized the window, in which
+    /// case egui must not see it.
+    pub fn on_press(&mut self, ctx: &egui::Context, window: &Window) -> bool {
+        let Some(pos) = self.background else {
+            return false;
+        };
+        let now = Instant::now();
+        let (max_delay, max_dist) = ctx.options(|o| {
+            (
+                o.input_options.max_double_click_delay,
+                o.input_options.max_click_dist,
+            )
+        });
+        let double_click = self.last_press.take().is_some_and(|(time, last)| {
+            now.duration_since(time).as_secs_f64() <= max_delay && last.distance(pos) <= max_dist
+        });
+        if double_click {
+            window.set_maximized(!window.is_maximized());
+            return true;
+        }
+        match window.drag_window() {
+            Ok(()) => {
+                self.last_press = Some((now, pos));
+                true
+            }
+            Err(err) => {
+                log::debug!("cannot drag the window: {err}");
+                false
+            }
+        }
+    }
+}
+
+fn background_under_pointer(ctx: &egui::Context, region: DragRegion) -> Option<egui::Pos2> {
+    let pos = ctx.pointer_hover_pos()?;
+    if let DragRegion::TopStrip(height) = region {
+        if pos.y > ctx.screen_rect().top() + height {
+            return None;
+        }
+    }
+    if ctx.is_using_pointer() {
+        return None;
+    }
+    // Hovered holds the top interactive widget and anything above it.
+    let hovered = ctx.interaction_snapshot(|snapshot| snapshot.hovered.clone());
+    let interactive = hovered.into_iter().any(|id| {
+        ctx.read_response(id).is_some_and(|response| {
+            response.enabled() && (response.sense.click || response.sense.drag)
+        })
+    });
+    (!interactive).then_some(pos)
+}
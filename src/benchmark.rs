@@ -0,0 +1,339 @@
+use std::{fmt::Write as _, path::PathBuf, str::FromStr};
+
+use web_time::{Duration, Instant};
+
+use crate::{
+    frame_recorder::ExportFormat, frame_stats::FrameTimeline, idle, options::Options,
+    render_stats::RenderStats,
+};
+
+const STRESS_COLUMNS: usize = 12;
+const STRESS_ROWS: usize = 40;
+
+#[derive(Clone, Copy, Debug)]
+pub enum BenchmarkLength {
+    Duration(Duration),
+    Frames(u64),
+}
+
+impl Default for BenchmarkLength {
+    fn default() -> Self {
+        Self::Duration(Duration::from_secs(10))
+    }
+}
+
+impl FromStr for BenchmarkLength {
+    type Err = anyhow::Error;
+
+    /// Accepts `10`, `10s` (seconds) or `600f` (frames).
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(frames) = s.strip_suffix('f') {
+            Ok(Self::Frames(frames.parse()?))
+        } else {
+            let secs: f64 = s.strip_suffix('s').unwrap_or(s).parse()?;
+            Ok(Self::Duration(Duration::try_from_secs_f64(secs)?))
+        }
+    }
+}
+
+struct BenchmarkFrame {
+    interval: Duration,
+    cpu_time: Duration,
+    gpu_time: Option<Duration>,
+}
+
+pub struct Benchmark {
+    length: BenchmarkLength,
+    warm_up: Duration,
+    report_path: Option<PathBuf>,
+    stress_ui: bool,
+    adapter_info: wgpu::AdapterInfo,
+    present_mode: wgpu::PresentMode,
+    surface_size: [u32; 2],
+    started: Instant,
+    measuring_since: Option<Instant>,
+    last_present: Option<Instant>,
+    frames: Vec<BenchmarkFrame>,
+    clipped_primitives: u64,
+    vertices: u64,
+    indices: u64,
+}
+
+impl Benchmark {
+    pub fn new(
+        length: BenchmarkLength,
+        options: &Options,
+        adapter_info: wgpu::AdapterInfo,
+        surface_config: &wgpu::SurfaceConfiguration,
+    ) -> Self {
+        Self {
+            length,
+            warm_up: options.benchmark_warm_up,
+            report_path: options.benchmark_report.clone(),
+            stress_ui: options.stress_ui,
+            adapter_info,
+            present_mode: surface_config.present_mode,
+            surface_size: [surface_config.width, surface_config.height],
+            started: Instant::now(),
+            measuring_since: None,
+            last_present: None,
+            frames: Vec::new(),
+            clipped_primitives: 0,
+            vertices: 0,
+            indices: 0,
+        }
+    }
+
+    /// Returns `true` once the configured length has been measured.
+    pub fn record(
+        &mut self,
+        timeline: &FrameTimeline,
+        gpu_time: Option<Duration>,
+        render_stats: &RenderStats,
+        surface_size: [u32; 2],
+    ) -> bool {
+        if timeline.present.saturating_duration_since(self.started) < self.warm_up {
+            return false;
+        }
+        self.surface_size = surface_size;
+        let measuring_since = *self.measuring_since.get_or_insert(timeline.present);
+        if let Some(last_present) = self.last_present.replace(timeline.present) {
+            self.frames.push(BenchmarkFrame {
+                interval: timeline.present.saturating_duration_since(last_present),
+                cpu_time: timeline.cpu_time(),
+                gpu_time,
+            });
+            self.clipped_primitives += render_stats.clipped_primitives as u64;
+            self.vertices += render_stats.vertices as u64;
+            self.indices += render_stats.indices as u64;
+        }
+
+        match self.length {
+            BenchmarkLength::Duration(length) => {
+                timeline.present.saturating_duration_since(measuring_since) >= length
+            }
+            BenchmarkLength::Frames(frames) => self.frames.len() as u64 >= frames,
+        }
+    }
+
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let report = Report::new(self);
+        println!("{}", report.to_text());
+        if let Some(path) = &self.report_path {
+            let contents = match ExportFormat::from_path(path) {
+                ExportFormat::Csv => report.to_text(),
+                ExportFormat::Json => report.to_json(),
+            };
+            std::fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+}
+
+struct Percentiles {
+    mean: Duration,
+    p50: Duration,
+    p95: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl Percentiles {
+    fn new(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        Some(Self {
+            mean: samples.iter().sum::<Duration>() / samples.len() as u32,
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: percentile(1.),
+        })
+    }
+
+    fn to_text(&self) -> String {
+        format!(
+            "mean {:.3}, p50 {:.3}, p95 {:.3}, p99 {:.3}, max {:.3}",
+            ms(self.mean),
+            ms(self.p50),
+            ms(self.p95),
+            ms(self.p99),
+            ms(self.max)
+        )
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"mean\": {:.3}, \"p50\": {:.3}, \"p95\": {:.3}, \"p99\": {:.3}, \"max\": {:.3}}}",
+            ms(self.mean),
+            ms(self.p50),
+            ms(self.p95),
+            ms(self.p99),
+            ms(self.max)
+        )
+    }
+}
+
+struct Report<'a> {
+    benchmark: &'a Benchmark,
+    frames: usize,
+    duration: Duration,
+    frame_time: Option<Percentiles>,
+    cpu_time: Option<Percentiles>,
+    gpu_time: Option<Percentiles>,
+}
+
+impl<'a> Report<'a> {
+    fn new(benchmark: &'a Benchmark) -> Self {
+        let frames = &benchmark.frames;
+        Self {
+            benchmark,
+            frames: frames.len(),
+            duration: frames.iter().map(|f| f.interval).sum(),
+            frame_time: Percentiles::new(frames.iter().map(|f| f.interval).collect()),
+            cpu_time: Percentiles::new(frames.iter().map(|f| f.cpu_time).collect()),
+            gpu_time: Percentiles::new(frames.iter().filter_map(|f| f.gpu_time).collect()),
+        }
+    }
+
+    fn fps(&self) -> f64 {
+        if self.duration.is_zero() {
+            0.
+        } else {
+            self.frames as f64 / self.duration.as_secs_f64()
+        }
+    }
+
+    fn per_frame(&self, total: u64) -> f64 {
+        total as f64 / self.frames.max(1) as f64
+    }
+
+    fn ui_name(&self) -> &'static str {
+        if self.benchmark.stress_ui {
+            "stress"
+        } else {
+            "app"
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let b = self.benchmark;
+        let info = &b.adapter_info;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "benchmark: {} frames in {:.2} s ({:.1} s warm-up excluded)",
+            self.frames,
+            self.duration.as_secs_f64(),
+            b.warm_up.as_secs_f64()
+        );
+        let _ = writeln!(
+            out,
+            "adapter: {} ({:?}, {:?}, driver {} {})",
+            info.name, info.backend, info.device_type, info.driver, info.driver_info
+        );
+        let _ = writeln!(
+            out,
+            "surface: {}x{}, {:?}, {} ui",
+            b.surface_size[0],
+            b.surface_size[1],
+            b.present_mode,
+            self.ui_name()
+        );
+        let _ = writeln!(out, "fps: {:.1}", self.fps());
+        let rows = [
+            ("frame time ms", &self.frame_time),
+            ("cpu time ms", &self.cpu_time),
+            ("gpu time ms", &self.gpu_time),
+        ];
+        for (name, percentiles) in rows {
+            let value = percentiles
+                .as_ref()
+                .map_or_else(|| "n/a".to_owned(), Percentiles::to_text);
+            let _ = writeln!(out, "{name}: {value}");
+        }
+        let _ = write!(
+            out,
+            "per frame: {:.1} clipped primitives, {:.0} vertices, {:.0} indices",
+            self.per_frame(b.clipped_primitives),
+            self.per_frame(b.vertices),
+            self.per_frame(b.indices)
+        );
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let b = self.benchmark;
+        let info = &b.adapter_info;
+        let json = |percentiles: &Option<Percentiles>| {
+            percentiles
+                .as_ref()
+                .map_or_else(|| "null".to_owned(), Percentiles::to_json)
+        };
+        format!(
+            "{{\n  \"frames\": {},\n  \"duration_s\": {:.3},\n  \"warm_up_s\": {:.3},\n  \"fps\": {:.3},\n  \"adapter\": {{\"name\": {:?}, \"backend\": \"{:?}\", \"device_type\": \"{:?}\", \"driver\": {:?}, \"driver_info\": {:?}}},\n  \"surface\": {{\"width\": {}, \"height\": {}, \"present_mode\": \"{:?}\"}},\n  \"ui\": \"{}\",\n  \"frame_time_ms\": {},\n  \"cpu_time_ms\": {},\n  \"gpu_time_ms\": {},\n  \"per_frame\": {{\"clipped_primitives\": {:.1}, \"vertices\": {:.1}, \"indices\": {:.1}}}\n}}\n",
+            self.frames,
+            self.duration.as_secs_f64(),
+            b.warm_up.as_secs_f64(),
+            self.fps(),
+            info.name,
+            info.backend,
+            info.device_type,
+            info.driver,
+            info.driver_info,
+            b.surface_size[0],
+            b.surface_size[1],
+            b.present_mode,
+            self.ui_name(),
+            json(&self.frame_time),
+            json(&self.cpu_time),
+            json(&self.gpu_time),
+            self.per_frame(b.clipped_primitives),
+            self.per_frame(b.vertices),
+            self.per_frame(b.indices)
+        )
+    }
+}
+
+/// A fixed grid of widgets whose values animate every frame, so that
+/// results are comparable between machines regardless of the app's UI.
+pub fn stress_ui(ctx: &egui::Context) {
+    let time = ctx.input(|i| i.time);
+    egui::CentralPanel::default().show(ctx, |ui| {
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("stress_grid").striped(true).show(ui, |ui| {
+                for row in 0..STRESS_ROWS {
+                    for column in 0..STRESS_COLUMNS {
+                        let phase = time + (row * STRESS_COLUMNS + column) as f64 * 0.1;
+                        let mut value = (phase.sin() * 0.5 + 0.5) as f32;
+                        match column % 4 {
+                            0 => {
+                                ui.label(format!("{row}:{column} {value:.3}"));
+                            }
+                            1 => {
+                                let _ = ui.button(format!("Button {row}"));
+                            }
+                            2 => {
+                                let mut checked = value > 0.5;
+                                ui.checkbox(&mut checked, "");
+                            }
+                            _ => {
+                                ui.add(egui::Slider::new(&mut value, 0.0..=1.0));
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    });
+    idle::report_activity(ctx);
+    ctx.request_repaint();
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
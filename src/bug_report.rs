@@ -0,0 +1,362 @@
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use crate::{
+    crash_report, event_trace,
+    frame_capture::{self, FrameCapture, Pixels},
+    gpu_poll::GpuPoller,
+    log_console, paths,
+    user_app::UserApp,
+};
+
+const LOG_ENTRIES: usize = 4096;
+
+type Redactor = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+static REDACTOR: Mutex<Option<Redactor>> = Mutex::new(None);
+
+fn request_id() -> egui::Id {
+    egui::Id::new("bug_report_request")
+}
+
+fn result_id() -> egui::Id {
+    egui::Id::new("bug_report_result")
+}
+
+/// Gathers a screenshot of the current frame, the log, the event trace,
+/// the adapter, OS and settings, and [`UserApp::bug_report_context`] into
+/// `bug-report-<time>.zip` in `dir`, or in the data directory's
+/// `bug-reports`. The path or the error comes from [`take_result`] a few
+/// frames later.
+pub fn create_bug_report(ctx: &egui::Context, dir: Option<PathBuf>) {
+    ctx.data_mut(|d| d.insert_temp(request_id(), Some(dir)));
+    ctx.request_repaint();
+}
+
+pub fn take_result(ctx: &egui::Context) -> Option<Result<PathBuf, String>> {
+    ctx.data_mut(|d| d.remove_temp::<Option<Result<PathBuf, String>>>(result_id()))
+        .flatten()
+}
+
+/// Runs over every text file of a report before it is written, e.g. to
+/// scrub names, paths or tokens.
+pub fn set_redactor(redactor: impl Fn(&str) -> String + Send + Sync + 'static) {
+    *REDACTOR.lock().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(redactor));
+}
+
+struct Pending {
+    dir: Option<PathBuf>,
+    context: String,
+    capture: Option<FrameCapture>,
+}
+
+/// Drives bug reports from [`create_bug_report`]: copies the next frame
+/// out of the surface, maps it without waiting for the GPU and writes the
+/// archive on a thread.
+pub struct BugReporter {
+    can_capture: bool,
+    pending: Option<Pending>,
+}
+
+impl BugReporter {
+    /// Reports go without a screenshot unless `can_capture`, which needs a
+    /// surface that allows copies and no render thread.
+    pub fn new(can_capture: bool) -> Self {
+        Self {
+            can_capture,
+            pending: None,
+        }
+    }
+
+    /// Takes a request after each pass. Returns whether the next painted
+    /// frame is to be captured.
+    pub fn update(&mut self, ctx: &egui::Context, app: &impl UserApp) -> bool {
+        let request = ctx
+            .data_mut(|d| d.remove_temp::<Option<Option<PathBuf>>>(request_id()))
+            .flatten();
+        if let Some(dir) = request {
+            if self.pending.is_some() {
+                log::warn!("a bug report is already being created");
+            } else {
+                let pending = Pending {
+                    dir,
+                    context: app.bug_report_context(),
+                    capture: None,
+                };
+                if self.can_capture {
+                    self.pending = Some(pending);
+                } else {
+                    spawn_writer(ctx.clone(), pending, None);
+                }
+            }
+        }
+        self.poll(ctx);
+        self.pending
+            .as_ref()
+            .is_some_and(|pending| pending.capture.is_none())
+    }
+
+    /// Copies the frame painted to `texture`. Call before submitting.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        let Some(pending) = self.pending.as_mut().filter(|p| p.capture.is_none()) else {
+            return;
+        };
+        match FrameCapture::new(device, encoder, texture, "bug report capture") {
+            Ok(capture) => pending.capture = Some(capture),
+            Err(format) => {
+                log::warn!("cannot capture a {format:?} surface for the bug report");
+                self.can_capture = false;
+            }
+        }
+    }
+
+    /// Maps the copy. Call after submitting.
+    pub fn after_submit(&mut self, gpu_poller: &GpuPoller) {
+        if let Some(capture) = self.pending.as_mut().and_then(|p| p.capture.as_mut()) {
+            capture.after_submit(gpu_poller);
+        }
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some(result) = self
+            .pending
+            .as_ref()
+            .and_then(|p| p.capture.as_ref()?.try_read())
+        else {
+            return;
+        };
+        let Some(mut pending) = self.pending.take() else {
+            return;
+        };
+        pending.capture = None;
+        let pixels = result
+            .map_err(|err| log::warn!("cannot read the frame for the bug report: {err}"))
+            .ok();
+        spawn_writer(ctx.clone(), pending, pixels);
+    }
+}
+
+fn spawn_writer(ctx: egui::Context, pending: Pending, pixels: Option<Pixels>) {
+    let spawned = std::thread::Builder::new()
+        .name("bug-report".to_owned())
+        .spawn({
+            let ctx = ctx.clone();
+            move || {
+                let result = write_report(pending, pixels).map_err(|err| err.to_string());
+                match &result {
+                    Ok(path) => log::info!("bug report written to {}", path.display()),
+                    Err(err) => log::warn!("failed to write a bug report: {err}"),
+                }
+                ctx.data_mut(|d| d.insert_temp(result_id(), Some(result)));
+                ctx.request_repaint();
+            }
+        });
+    if let Err(err) = spawned {
+        let result: Result<PathBuf, String> =
+            Err(format!("cannot start the bug report thread: {err}"));
+        ctx.data_mut(|d| d.insert_temp(result_id(), Some(result)));
+    }
+}
+
+fn write_report(pending: Pending, pixels: Option<Pixels>) -> std::io::Result<PathBuf> {
+    let dir = pending
+        .dir
+        .or_else(|| Some(paths::data_dir()?.join("bug-reports")))
+        .ok_or_else(|| std::io::Error::other("no directory for bug reports"))?;
+    let redactor = REDACTOR
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let redact = |text: String| match &redactor {
+        Some(redactor) => redactor(&text),
+        None => text,
+    };
+
+    let mut system = String::new();
+    let _ = writeln!(
+        system,
+        "version: {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        system,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    for (name, text) in crash_report::sections() {
+        let _ = writeln!(system, "\n[{name}]\n{text}");
+    }
+    let mut log = String::new();
+    let mut trace = String::new();
+    for entry in log_console::recent(LOG_ENTRIES) {
+        let out = if entry.target == event_trace::TARGET {
+            &mut trace
+        } else {
+            &mut log
+        };
+        let _ = writeln!(
+            out,
+            "{:>8.3} {:<5} {}: {}",
+            entry.time.as_secs_f32(),
+            entry.level,
+            entry.target,
+            entry.message
+        );
+    }
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut zip = ZipWriter::new(now);
+    zip.add("system.txt", redact(system).as_bytes());
+    zip.add("log.txt", redact(log).as_bytes());
+    if !trace.is_empty() {
+        zip.add("event-trace.txt", redact(trace).as_bytes());
+    }
+    zip.add("app.txt", redact(pending.context).as_bytes());
+    if let Some((size, pixels)) = pixels {
+        zip.add("screenshot.png", &frame_capture::encode_png(size, &pixels));
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("bug-report-{now}.zip"));
+    std::fs::write(&path, zip.finish())?;
+    Ok(path)
+}
+
+/// A zip archive of stored, uncompressed files.
+struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl ZipWriter {
+    fn new(unix_time: u64) -> Self {
+        let (dos_time, dos_date) = dos_date_time(unix_time);
+        Self {
+            data: Vec::new(),
+            directory: Vec::new(),
+            entries: 0,
+            dos_time,
+            dos_date,
+        }
+    }
+
+    fn add(&mut self, name: &str, contents: &[u8]) {
+        let offset = self.data.len() as u32;
+        let crc = frame_capture::crc32(&[contents]);
+        let size = contents.len() as u32;
+        let mut common = Vec::new();
+        // Version needed, flags, stored, time, date, CRC, both sizes, name
+        // length and no extra field.
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&self.dos_time.to_le_bytes());
+        common.extend_from_slice(&self.dos_date.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        self.data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&common);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        self.directory
+            .extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.directory.extend_from_slice(&20u16.to_le_bytes());
+        self.directory.extend_from_slice(&common);
+        // No comment, disk 0, no attributes.
+        self.directory.extend_from_slice(&[0; 10]);
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        self.data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data
+    }
+}
+
+/// MS-DOS time and date in UTC, as zip stores them.
+fn dos_date_time(unix_time: u64) -> (u16, u16) {
+    let days = (unix_time / 86400) as i64;
+    let seconds = unix_time % 86400;
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let time = (seconds / 3600) << 11 | (seconds % 3600 / 60) << 5 | (seconds % 60 / 2);
+    let date = ((year - 1980).clamp(0, 127) << 9) | (month << 5) | day;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redactor_runs_over_every_text_file() {
+        let dir = std::env::temp_dir().join(format!("{}-bug-report", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        set_redactor(|text| text.replace("hunter2", "<secret>"));
+        crash_report::set_section("bug report test", "token: hunter2".to_owned());
+        let pending = Pending {
+            dir: Some(dir.clone()),
+            context: "last login: hunter2".to_owned(),
+            capture: None,
+        };
+        let path = write_report(pending, None).unwrap();
+        *REDACTOR.lock().unwrap() = None;
+
+        assert!(path.starts_with(&dir));
+        let archive = std::fs::read(&path).unwrap();
+        let count = |needle: &[u8]| {
+            archive
+                .windows(needle.len())
+                .filter(|w| *w == needle)
+                .count()
+        };
+        // Files are stored, so their text is in the archive as is.
+        assert_eq!(count(b"hunter2"), 0);
+        assert_eq!(count(b"token: <secret>"), 1);
+        assert_eq!(count(b"last login: <secret>"), 1);
+        assert_eq!(count(b"app.txt"), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
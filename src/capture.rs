@@ -0,0 +1,199 @@
+use egui_winit::winit::window::Window;
+
+fn request_id() -> egui::Id {
+    egui::Id::new("capture_exclusion_request")
+}
+
+fn state_id() -> egui::Id {
+    egui::Id::new("capture_exclusion")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CaptureError {
+    #[cfg_attr(any(windows, target_os = "macos"), allow(dead_code))]
+    #[error("excluding the window from screen capture is not supported on this platform")]
+    Unsupported,
+    #[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+    #[error("cannot exclude the window from screen capture: {0}")]
+    Failed(String),
+}
+
+/// Asks for the window to be hidden from (or shown again in) screenshots and
+/// screen sharing. Applied after the current frame, after which
+/// [`is_excluded`] tells whether it worked; platforms that cannot do it at
+/// all say so right away, for apps that must not show the window then.
+pub fn set_excluded(ctx: &egui::Context, excluded: bool) -> Result<(), CaptureError> {
+    if !SUPPORTED {
+        return Err(CaptureError::Unsupported);
+    }
+    ctx.data_mut(|d| d.insert_temp(request_id(), excluded));
+    Ok(())
+}
+
+const SUPPORTED: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Whether the window is currently hidden from screen capture.
+pub fn is_excluded(ctx: &egui::Context) -> bool {
+    ctx.data(|d| d.get_temp(state_id())).unwrap_or(false)
+}
+
+/// Keeps the window's capture exclusion in sync with what the app asked for.
+pub struct CaptureExclusion {
+    requested: bool,
+    /// What was last applied to the window, `None` if nothing was applied to
+    /// the current window yet.
+    applied: Option<bool>,
+    excluded: bool,
+}
+
+impl CaptureExclusion {
+    pub fn new(requested: bool) -> Self {
+        Self {
+            requested,
+            applied: None,
+            excluded: false,
+        }
+    }
+
+    /// The window handle may have changed, e.g. after a resume.
+    pub fn reapply(&mut self) {
+        self.applied = None;
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, window: &Window) {
+        if let Some(requested) = ctx.data_mut(|d| d.remove_temp::<bool>(request_id())) {
+            self.requested = requested;
+        }
+        if self.applied != Some(self.requested) {
+            // Only attempted once per request, so an unsupported platform
+            // does not log every frame. A new window is never excluded, so
+            // there is nothing to undo then.
+            self.applied = Some(self.requested);
+            if self.requested || self.excluded {
+                match apply(window, self.requested) {
+                    Ok(()) => self.excluded = self.requested,
+                    Err(err) => log::warn!("{err}"),
+                }
+            }
+        }
+        ctx.data_mut(|d| d.insert_temp(state_id(), self.excluded));
+    }
+}
+
+fn apply(window: &Window, excluded: bool) -> Result<(), CaptureError> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE, WDA_NONE,
+        };
+
+        let hwnd = crate::titlebar::hwnd(window)
+            .ok_or_else(|| CaptureError::Failed("the window has no Win32 handle".to_owned()))?;
+        let affinity = if excluded {
+            WDA_EXCLUDEFROMCAPTURE
+        } else {
+            WDA_NONE
+        };
+        // SAFETY: `hwnd` belongs to a live window owned by this thread.
+        if unsafe { SetWindowDisplayAffinity(hwnd, affinity) } == 0 {
+            return Err(CaptureError::Failed(format!(
+                "SetWindowDisplayAffinity failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::set_sharing_type(window, excluded)
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        let _ = (window, excluded);
+        Err(CaptureError::Unsupported)
+    }
+}
+
+/// `NSWindow.sharingType`, which keeps the window out of screenshots,
+/// recordings and screen sharing when it is `NSWindowSharingNone`.
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void};
+
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+
+    use super::CaptureError;
+
+    type Id = *mut c_void;
+    type Sel = *const c_void;
+
+    const NS_WINDOW_SHARING_NONE: usize = 0;
+    const NS_WINDOW_SHARING_READ_ONLY: usize = 1;
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    pub fn set_sharing_type(window: &Window, excluded: bool) -> Result<(), CaptureError> {
+        let handle = window
+            .window_handle()
+            .map_err(|err| CaptureError::Failed(err.to_string()))?;
+        let RawWindowHandle::AppKit(handle) = handle.as_raw() else {
+            return Err(CaptureError::Failed(
+                "the window has no AppKit handle".to_owned(),
+            ));
+        };
+        let sharing_type = if excluded {
+            NS_WINDOW_SHARING_NONE
+        } else {
+            NS_WINDOW_SHARING_READ_ONLY
+        };
+        // SAFETY: the view belongs to a live window, the selectors exist on
+        // NSView and NSWindow with these signatures, and winit runs this on
+        // the main thread.
+        unsafe {
+            let send_id: unsafe extern "C" fn(Id, Sel) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_usize: unsafe extern "C" fn(Id, Sel, usize) =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+            let ns_window = send_id(
+                handle.ns_view.as_ptr(),
+                sel_registerName(c"window".as_ptr()),
+            );
+            if ns_window.is_null() {
+                return Err(CaptureError::Failed("the view has no window".to_owned()));
+            }
+            send_usize(
+                ns_window,
+                sel_registerName(c"setSharingType:".as_ptr()),
+                sharing_type,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_platforms_refuse_right_away() {
+        let ctx = egui::Context::default();
+        let result = set_excluded(&ctx, true);
+        let requested = ctx.data(|d| d.get_temp::<bool>(request_id()));
+        if SUPPORTED {
+            assert_eq!(result, Ok(()));
+            assert_eq!(requested, Some(true));
+        } else {
+            assert_eq!(result, Err(CaptureError::Unsupported));
+            assert_eq!(requested, None);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use egui_winit::winit::window::Window;
+use web_time::Duration;
+
+/// How often the cursor is looked up while the window lets every click
+/// through and so hears nothing of the cursor.
+const CURSOR_POLL_INTERVAL: Duration = Duration::from_millis(30);
+
+/// A rectangle in physical pixels, relative to the window's client area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelRect {
+    pub min: [i32; 2],
+    pub max: [i32; 2],
+}
+
+impl PixelRect {
+    pub fn new(rect: egui::Rect, pixels_per_point: f32) -> Self {
+        Self {
+            min: [
+                (rect.min.x * pixels_per_point).floor() as i32,
+                (rect.min.y * pixels_per_point).floor() as i32,
+            ],
+            max: [
+                (rect.max.x * pixels_per_point).ceil() as i32,
+                (rect.max.y * pixels_per_point).ceil() as i32,
+            ],
+        }
+    }
+
+    fn contains(&self, [x, y]: [f64; 2]) -> bool {
+        (f64::from(self.min[0])..f64::from(self.max[0])).contains(&x)
+            && (f64::from(self.min[1])..f64::from(self.max[1])).contains(&y)
+    }
+}
+
+/// Rects of egui's areas above the background from the last pass: windows,
+/// popups, tooltips and plain areas, of each context. Widgets in panels do
+/// not count.
+fn interactive_rects(contexts: &[&egui::Context], pixels_per_point: f32) -> Vec<PixelRect> {
+    let mut rects: Vec<_> = contexts
+        .iter()
+        .flat_map(|ctx| {
+            ctx.memory(|memory| {
+                memory
+                    .areas()
+                    .visible_layer_ids()
+                    .into_iter()
+                    .filter(|layer| layer.order != egui::Order::Background)
+                    .filter_map(|layer| memory.area_rect(layer.id))
+                    .map(|rect| PixelRect::new(rect, pixels_per_point))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    // The set comes in no particular order.
+    rects.sort_by_key(|rect| (rect.min, rect.max));
+    rects
+}
+
+enum Backend {
+    /// Gives the window an input shape made of the regions.
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    InputShape(Box<x11::InputShape>),
+    /// Turns hit testing of the whole window on while the cursor is over a
+    /// region and off otherwise.
+    #[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+    Toggle,
+    None,
+}
+
+/// Lets clicks through a transparent overlay window except over egui's
+/// areas, see [`interactive_rects`]. winit only turns hit testing on or off
+/// for the whole window, so this is best effort: X11 gets an input shape,
+/// Windows and macOS toggle hit testing by where the cursor is, and
+/// elsewhere, e.g. on Wayland, the whole window keeps taking clicks.
+pub struct ClickThrough {
+    backend: Backend,
+    hittest: bool,
+}
+
+impl ClickThrough {
+    pub fn new(window: &Window) -> Self {
+        let backend = Self::backend(window);
+        if matches!(backend, Backend::None) {
+            log::warn!("clicks cannot pass through the window on this platform");
+        }
+        Self {
+            backend,
+            hittest: true,
+        }
+    }
+
+    fn backend(window: &Window) -> Backend {
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))]
+        {
+            x11::InputShape::for_window(window)
+                .map_or(Backend::None, |shape| Backend::InputShape(Box::new(shape)))
+        }
+        #[cfg(any(windows, target_os = "macos"))]
+        {
+            let _ = window;
+            Backend::Toggle
+        }
+        #[cfg(not(any(
+            windows,
+            target_os = "macos",
+            all(
+                unix,
+                not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+            )
+        )))]
+        {
+            let _ = window;
+            Backend::None
+        }
+    }
+
+    /// Call after each pass, with the overlay context's areas counting too.
+    pub fn update(&mut self, ctx: &egui::Context, overlay_ctx: &egui::Context, window: &Window) {
+        let pixels_per_point = egui_winit::pixels_per_point(ctx, window);
+        let regions = interactive_rects(&[ctx, overlay_ctx], pixels_per_point);
+        match &mut self.backend {
+            #[cfg(all(
+                unix,
+                not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+            ))]
+            Backend::InputShape(shape) => shape.set(regions),
+            Backend::Toggle => {
+                let over_region = platform::cursor_position(window)
+                    .zip(window.inner_position().ok())
+                    .map(|(cursor, origin)| {
+                        let cursor = [
+                            cursor.x - f64::from(origin.x),
+                            cursor.y - f64::from(origin.y),
+                        ];
+                        regions.iter().any(|region| region.contains(cursor))
+                    });
+                // Without a cursor position the window keeps taking clicks.
+                let hittest = over_region.unwrap_or(true);
+                if hittest != self.hittest {
+                    match window.set_cursor_hittest(hittest) {
+                        Ok(()) => self.hittest = hittest,
+                        Err(err) => log::warn!("cannot change cursor hit testing: {err}"),
+                    }
+                }
+                if !self.hittest {
+                    ctx.request_repaint_after(CURSOR_POLL_INTERVAL);
+                }
+            }
+            Backend::None => {}
+        }
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+pub(crate) use x11::InputShape;
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod x11 {
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+    use x11rb::{
+        connection::{Connection, RequestConnection},
+        protocol::{
+            shape::{self, ConnectionExt},
+            xproto::{ClipOrdering, Rectangle},
+        },
+        rust_connection::RustConnection,
+    };
+
+    use super::PixelRect;
+
+    /// A connection of our own: any client may shape any window.
+    pub struct InputShape {
+        connection: RustConnection,
+        window: u32,
+        regions: Option<Vec<PixelRect>>,
+    }
+
+    impl InputShape {
+        /// `None` where the window is not an X11 one, e.g. on Wayland.
+        pub fn for_window(window: &Window) -> Option<Self> {
+            match window.window_handle().ok()?.as_raw() {
+                RawWindowHandle::Xlib(handle) => Self::new(handle.window as u32),
+                RawWindowHandle::Xcb(handle) => Self::new(handle.window.get()),
+                _ => None,
+            }
+        }
+
+        fn new(window: u32) -> Option<Self> {
+            let (connection, _) = x11rb::connect(None)
+                .inspect_err(|err| log::warn!("cannot connect to the X server: {err}"))
+                .ok()?;
+            let shape = connection
+                .extension_information(shape::X11_EXTENSION_NAME)
+                .ok()
+                .flatten();
+            if shape.is_none() {
+                log::warn!("the X server has no shape extension");
+                return None;
+            }
+            Some(Self {
+                connection,
+                window,
+                regions: None,
+            })
+        }
+
+        /// Only talks to the server when the regions changed.
+        pub fn set(&mut self, regions: Vec<PixelRect>) {
+            if self.regions.as_ref() == Some(&regions) {
+                return;
+            }
+            let rectangles: Vec<_> = regions
+                .iter()
+                .map(|region| Rectangle {
+                    x: region.min[0].clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                    y: region.min[1].clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                    width: (region.max[0] - region.min[0]).clamp(0, u16::MAX.into()) as u16,
+                    height: (region.max[1] - region.min[1]).clamp(0, u16::MAX.into()) as u16,
+                })
+                .collect();
+            let result = self
+                .connection
+                .shape_rectangles(
+                    shape::SO::SET,
+                    shape::SK::INPUT,
+                    ClipOrdering::UNSORTED,
+                    self.window,
+                    0,
+                    0,
+                    &rectangles,
+                )
+                .map(drop)
+                .and_then(|()| self.connection.flush());
+            if let Err(err) = result {
+                log::warn!("cannot set the window's input shape: {err}");
+            }
+            self.regions = Some(regions);
+        }
+    }
+
+    impl Drop for InputShape {
+        fn drop(&mut self) {
+            // No mask restores the default shape, the whole window.
+            let _ = self
+                .connection
+                .shape_mask(
+                    shape::SO::SET,
+                    shape::SK::INPUT,
+                    self.window,
+                    0,
+                    0,
+                    x11rb::NONE,
+                )
+                .map(drop)
+                .and_then(|()| self.connection.flush());
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use egui_winit::winit::{dpi::PhysicalPosition, window::Window};
+    use windows_sys::Win32::{Foundation::POINT, UI::WindowsAndMessaging::GetCursorPos};
+
+    /// In physical screen coordinates, as winit makes the process DPI aware.
+    pub fn cursor_position(_window: &Window) -> Option<PhysicalPosition<f64>> {
+        let mut point = POINT { x: 0, y: 0 };
+        // SAFETY: `point` outlives the call.
+        if unsafe { GetCursorPos(&mut point) } == 0 {
+            return None;
+        }
+        Some(PhysicalPosition::new(point.x.into(), point.y.into()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::c_void;
+
+    use egui_winit::winit::{dpi::PhysicalPosition, window::Window};
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventCreate(source: *const c_void) -> *const c_void;
+        fn CGEventGetLocation(event: *const c_void) -> CGPoint;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(object: *const c_void);
+    }
+
+    /// CoreGraphics reports points; they are scaled by the window's monitor,
+    /// which is off while the cursor is on a monitor with another scale.
+    pub fn cursor_position(window: &Window) -> Option<PhysicalPosition<f64>> {
+        // SAFETY: A null source is allowed; the event is released once.
+        let location = unsafe {
+            let event = CGEventCreate(std::ptr::null());
+            if event.is_null() {
+                return None;
+            }
+            let location = CGEventGetLocation(event);
+            CFRelease(event);
+            location
+        };
+        let scale = window.scale_factor();
+        Some(PhysicalPosition::new(
+            location.x * scale,
+            location.y * scale,
+        ))
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use egui_winit::winit::{dpi::PhysicalPosition, window::Window};
+
+    pub fn cursor_position(_window: &Window) -> Option<PhysicalPosition<f64>> {
+        None
+    }
+}
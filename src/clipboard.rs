@@ -0,0 +1,678 @@
+use std::{path::PathBuf, sync::mpsc};
+
+use egui_winit::winit::{
+    event::KeyEvent,
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+};
+
+fn request_id() -> egui::Id {
+    egui::Id::new("clipboard_request")
+}
+
+/// What is on the clipboard in the formats the app deals with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClipboardContent {
+    pub text: Option<String>,
+    pub html: Option<String>,
+    /// Files copied in a file manager. Pasted as dropped files unless the
+    /// app takes the paste. Only read: [`set_clipboard`] ignores them.
+    pub files: Vec<PathBuf>,
+}
+
+/// Puts `content` on the clipboard after the current pass, replacing what
+/// egui copied in it. Call when handling [`egui::Event::Copy`] or a copy
+/// button. HTML goes with a plain text alternative where given.
+pub fn set_clipboard(ctx: &egui::Context, content: ClipboardContent) {
+    ctx.data_mut(|d| d.insert_temp(request_id(), Some(content)));
+}
+
+/// The paste shortcuts egui-winit knows, which read the clipboard right away.
+pub fn is_paste_command(modifiers: egui::Modifiers, event: &KeyEvent) -> bool {
+    if !event.state.is_pressed() {
+        return false;
+    }
+    let v = match &event.logical_key {
+        Key::Character(c) if c.is_ascii() => c.eq_ignore_ascii_case("v"),
+        _ => event.physical_key == PhysicalKey::Code(KeyCode::KeyV),
+    };
+    event.logical_key == Key::Named(NamedKey::Paste)
+        || (modifiers.command && v)
+        || (cfg!(windows) && modifiers.shift && event.logical_key == Key::Named(NamedKey::Insert))
+}
+
+enum Job {
+    Set(ClipboardContent),
+    Get,
+}
+
+/// Reads and writes the clipboard on a worker thread, so that large
+/// payloads do not block the UI. The thread starts on first use.
+pub struct Clipboard {
+    /// Pastes go to [`crate::user_app::UserApp::on_paste`] with every format, rather
+    /// than straight to egui as text.
+    pub rich_paste: bool,
+    jobs: Option<mpsc::Sender<Job>>,
+    pasted_sender: mpsc::Sender<ClipboardContent>,
+    pasted: mpsc::Receiver<ClipboardContent>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        let (pasted_sender, pasted) = mpsc::channel();
+        Self {
+            rich_paste: false,
+            jobs: None,
+            pasted_sender,
+            pasted,
+        }
+    }
+}
+
+impl Clipboard {
+    /// Reads the clipboard; the result comes from [`Self::take_pasted`] in
+    /// a later frame.
+    pub fn request_paste(&mut self, ctx: &egui::Context) {
+        self.send(ctx, Job::Get);
+    }
+
+    pub fn take_pasted(&self) -> Option<ClipboardContent> {
+        self.pasted.try_recv().ok()
+    }
+
+    /// Takes content from [`set_clipboard`]. Call after each pass, once
+    /// egui's own output went to the clipboard.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        if let Some(content) = take_request(ctx) {
+            self.send(ctx, Job::Set(content));
+        }
+    }
+
+    fn send(&mut self, ctx: &egui::Context, job: Job) {
+        if self.jobs.is_none() {
+            self.jobs = spawn(ctx.clone(), self.pasted_sender.clone());
+        }
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send(job);
+        }
+    }
+}
+
+/// The content [`set_clipboard`] asked for during the last pass, once.
+fn take_request(ctx: &egui::Context) -> Option<ClipboardContent> {
+    ctx.data_mut(|d| d.remove_temp::<Option<ClipboardContent>>(request_id()))
+        .flatten()
+}
+
+fn spawn(ctx: egui::Context, pasted: mpsc::Sender<ClipboardContent>) -> Option<mpsc::Sender<Job>> {
+    let (jobs, job_receiver) = mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("clipboard".to_owned())
+        .spawn(move || {
+            // Kept for the thread's lifetime: on X11 the clipboard is only
+            // served while it exists.
+            let mut clipboard = arboard::Clipboard::new()
+                .inspect_err(|err| log::warn!("cannot open the clipboard: {err}"))
+                .ok();
+            for job in job_receiver {
+                match job {
+                    Job::Set(content) => {
+                        let Some(clipboard) = &mut clipboard else {
+                            continue;
+                        };
+                        let result = match (content.html, content.text) {
+                            (Some(html), text) => clipboard.set().html(html, text),
+                            (None, Some(text)) => clipboard.set().text(text),
+                            (None, None) => clipboard.clear(),
+                        };
+                        if let Err(err) = result {
+                            log::warn!("failed to set the clipboard: {err}");
+                        }
+                    }
+                    Job::Get => {
+                        let content = ClipboardContent {
+                            text: clipboard
+                                .as_mut()
+                                .and_then(|clipboard| clipboard.get().text().ok()),
+                            html: platform::html(),
+                            files: platform::files(),
+                        };
+                        if pasted.send(content).is_err() {
+                            break;
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            }
+        });
+    match spawned {
+        Ok(_) => Some(jobs),
+        Err(err) => {
+            log::warn!("cannot start the clipboard thread: {err}");
+            None
+        }
+    }
+}
+
+/// The local paths in a `text/uri-list` (RFC 2483): one URI per line,
+/// comments starting with `#`. Other URIs, and `file` URIs naming another
+/// host, are skipped; lines that are not URIs too, like the "copy" heading
+/// of GNOME's list. Percent-escapes are decoded to bytes, so paths need not
+/// be UTF-8.
+#[cfg(all(unix, not(any(target_os = "ios", target_os = "android"))))]
+pub fn parse_uri_list(list: &[u8]) -> Vec<PathBuf> {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    list.split(|&byte| byte == b'\n')
+        .map(<[u8]>::trim_ascii)
+        .filter(|line| !line.starts_with(b"#"))
+        .filter_map(|line| {
+            let rest = line
+                .get(..7)
+                .filter(|scheme| scheme.eq_ignore_ascii_case(b"file://"))
+                .map(|_| &line[7..])?;
+            let path = match rest.iter().position(|&byte| byte == b'/')? {
+                0 => rest,
+                host_end if &rest[..host_end] == b"localhost" => &rest[host_end..],
+                _ => return None,
+            };
+            let mut bytes = Vec::with_capacity(path.len());
+            let mut rest = path;
+            while let Some((&byte, tail)) = rest.split_first() {
+                let escaped = (byte == b'%')
+                    .then(|| std::str::from_utf8(tail.get(..2)?).ok())
+                    .flatten()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match escaped {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        rest = &tail[2..];
+                    }
+                    None => {
+                        bytes.push(byte);
+                        rest = tail;
+                    }
+                }
+            }
+            Some(PathBuf::from(OsString::from_vec(bytes)))
+        })
+        .collect()
+}
+
+/// Extracts the fragment from Windows' CF_HTML format, whose header gives
+/// byte offsets into the whole payload.
+#[cfg(any(windows, test))]
+fn parse_cf_html(data: &[u8]) -> Option<String> {
+    let header = String::from_utf8_lossy(&data[..data.len().min(512)]);
+    let offset = |name: &str| -> Option<usize> {
+        header
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .trim()
+            .parse()
+            .ok()
+    };
+    let (start, end) = offset("StartFragment")
+        .zip(offset("EndFragment"))
+        .or_else(|| offset("StartHTML").zip(offset("EndHTML")))?;
+    let fragment = data.get(start..end.min(data.len()))?;
+    Some(String::from_utf8_lossy(fragment).into_owned())
+}
+
+/// Decodes AppleScript's `«data HTML3C68746D6C3E…»`, the pasteboard's HTML
+/// as hex.
+#[cfg(any(target_os = "macos", test))]
+fn parse_applescript_html(output: &str) -> Option<String> {
+    let hex = output
+        .trim()
+        .strip_prefix("«data HTML")?
+        .strip_suffix('»')?;
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// How long [`run_helper`] waits. An X11 clipboard owner that never
+/// answers leaves `xclip` waiting forever, and the clipboard thread with it.
+#[cfg(all(unix, not(any(target_os = "ios", target_os = "android"))))]
+const HELPER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Runs a program that prints clipboard content, and returns its output if
+/// it succeeds within [`HELPER_TIMEOUT`]; it is killed otherwise.
+#[cfg(all(unix, not(any(target_os = "ios", target_os = "android"))))]
+fn run_helper(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+    use std::{
+        io::Read as _,
+        process::{Command, Stdio},
+        time::Instant,
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|err| log::debug!("cannot run {program}: {err}"))
+        .ok()?;
+    // Read while waiting, so that large payloads do not fill the pipe.
+    let mut stdout = child.stdout.take()?;
+    let reader = std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        stdout.read_to_end(&mut bytes).map(|_| bytes)
+    });
+    let deadline = Instant::now() + HELPER_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            Ok(None) => {
+                log::warn!("{program} did not finish within {HELPER_TIMEOUT:?}, stopping it");
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            Err(err) => {
+                log::warn!("cannot wait for {program}: {err}");
+                return None;
+            }
+        }
+    };
+    let bytes = reader.join().ok()?.ok()?;
+    status.success().then_some(bytes)
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+
+    use windows_sys::Win32::{
+        Globalization::{MultiByteToWideChar, CP_ACP},
+        System::{
+            DataExchange::{
+                CloseClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+                RegisterClipboardFormatW,
+            },
+            Memory::{GlobalLock, GlobalSize, GlobalUnlock},
+        },
+    };
+
+    /// The standard format Explorer copies files in.
+    const CF_HDROP: u32 = 15;
+
+    pub fn html() -> Option<String> {
+        let name: Vec<u16> = "HTML Format".encode_utf16().chain([0]).collect();
+        // SAFETY: `name` is a null-terminated UTF-16 string.
+        let format = unsafe { RegisterClipboardFormatW(name.as_ptr()) };
+        if format == 0 {
+            return None;
+        }
+        super::parse_cf_html(&read(format)?)
+    }
+
+    pub fn files() -> Vec<PathBuf> {
+        read(CF_HDROP)
+            .map(|data| parse_drop_files(&data))
+            .unwrap_or_default()
+    }
+
+    fn read(format: u32) -> Option<Vec<u8>> {
+        // SAFETY: Only takes the format.
+        if unsafe { IsClipboardFormatAvailable(format) } == 0 {
+            return None;
+        }
+        // Another app may hold the clipboard for a moment.
+        let opened = (0..5).any(|attempt| {
+            if attempt > 0 {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            // SAFETY: A null owner is allowed.
+            unsafe { OpenClipboard(std::ptr::null_mut()) != 0 }
+        });
+        if !opened {
+            log::warn!("cannot open the clipboard to read it");
+            return None;
+        }
+        // SAFETY: The clipboard is open; the handle is only used until it
+        // is closed below.
+        unsafe {
+            let handle = GetClipboardData(format);
+            let pointer = GlobalLock(handle);
+            let data = (!pointer.is_null()).then(|| {
+                std::slice::from_raw_parts(pointer.cast::<u8>(), GlobalSize(handle)).to_vec()
+            });
+            if !pointer.is_null() {
+                GlobalUnlock(handle);
+            }
+            CloseClipboard();
+            data
+        }
+    }
+
+    /// Reads a DROPFILES struct: the list's offset, the drop point, and
+    /// whether the names are UTF-16, then the names, each ending with a null
+    /// and the list with an empty one.
+    fn parse_drop_files(data: &[u8]) -> Vec<PathBuf> {
+        let field = |at: usize| Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+        let (Some(offset), Some(wide)) = (field(0), field(16)) else {
+            return Vec::new();
+        };
+        let Some(list) = data.get(offset as usize..) else {
+            return Vec::new();
+        };
+        if wide != 0 {
+            let units: Vec<u16> = list
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect();
+            // Keeps names that are not valid UTF-16 as they are.
+            units
+                .split(|&unit| unit == 0)
+                .take_while(|name| !name.is_empty())
+                .map(|name| PathBuf::from(OsString::from_wide(name)))
+                .collect()
+        } else {
+            list.split(|&byte| byte == 0)
+                .take_while(|name| !name.is_empty())
+                .map(|name| PathBuf::from(from_ansi(name)))
+                .collect()
+        }
+    }
+
+    /// Converts a name in the ANSI code page, which only matches UTF-8 for
+    /// ASCII.
+    fn from_ansi(name: &[u8]) -> OsString {
+        let len = i32::try_from(name.len()).unwrap_or(i32::MAX);
+        // SAFETY: Reads `len` bytes of `name`; a null output asks for the
+        // length.
+        let units =
+            unsafe { MultiByteToWideChar(CP_ACP, 0, name.as_ptr(), len, std::ptr::null_mut(), 0) };
+        let mut wide = vec![0; units.max(0) as usize];
+        // SAFETY: As above, writing up to `units` units into `wide`.
+        let written =
+            unsafe { MultiByteToWideChar(CP_ACP, 0, name.as_ptr(), len, wide.as_mut_ptr(), units) };
+        wide.truncate(written.max(0) as usize);
+        OsString::from_wide(&wide)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A DROPFILES struct at the start, then `list`.
+        fn drop_files(wide: bool, list: &[u8]) -> Vec<u8> {
+            let mut data = Vec::new();
+            data.extend(20u32.to_le_bytes());
+            data.extend([0; 12]);
+            data.extend(u32::from(wide).to_le_bytes());
+            data.extend(list);
+            data
+        }
+
+        #[test]
+        fn reads_wide_names() {
+            let mut list = Vec::new();
+            // An unpaired surrogate, which is kept.
+            let names: [&[u16]; 2] = [&[b'C' as u16, b':' as u16, 0xd800], &[0x00e9]];
+            for name in names {
+                list.extend(name.iter().chain(&[0]).flat_map(|unit| unit.to_le_bytes()));
+            }
+            list.extend([0, 0]);
+            assert_eq!(
+                parse_drop_files(&drop_files(true, &list)),
+                [
+                    PathBuf::from(OsString::from_wide(names[0])),
+                    PathBuf::from("\u{e9}")
+                ]
+            );
+        }
+
+        #[test]
+        fn reads_narrow_names() {
+            let data = drop_files(false, b"C:\\a.txt\0C:\\b\0\0ignored\0");
+            assert_eq!(
+                parse_drop_files(&data),
+                [PathBuf::from("C:\\a.txt"), PathBuf::from("C:\\b")]
+            );
+        }
+
+        #[test]
+        fn ignores_truncated_structs() {
+            assert!(parse_drop_files(&[0; 8]).is_empty());
+            let mut data = drop_files(false, b"");
+            data[0] = 200;
+            assert!(parse_drop_files(&data).is_empty());
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::PathBuf;
+
+    /// Lists the pasteboard's file URLs, one per line, in JavaScript for
+    /// Automation, which reaches AppKit without a compiled helper.
+    const FILE_URLS_SCRIPT: &str = "ObjC.import('AppKit');
+const urls = $.NSPasteboard.generalPasteboard
+    .readObjectsForClassesOptions($([$.NSURL]), $({}));
+const lines = [];
+for (let i = 0; urls && i < urls.count; i++) {
+    const url = urls.objectAtIndex(i);
+    if (url.isFileURL) lines.push(url.absoluteString.js);
+}
+lines.join('\\n');";
+
+    /// Asks AppleScript, which prints the pasteboard's HTML as
+    /// `«data HTML3C68746D6C3E…»`.
+    pub fn html() -> Option<String> {
+        let output = super::run_helper("osascript", &["-e", "the clipboard as «class HTML»"])?;
+        super::parse_applescript_html(&String::from_utf8(output).ok()?)
+    }
+
+    pub fn files() -> Vec<PathBuf> {
+        super::run_helper("osascript", &["-l", "JavaScript", "-e", FILE_URLS_SCRIPT])
+            .map(|output| super::parse_uri_list(&output))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod platform {
+    use std::path::PathBuf;
+
+    /// Reads the `text/html` target with `wl-paste` on Wayland or `xclip`
+    /// on X11, whichever is installed.
+    pub fn html() -> Option<String> {
+        read(&["text/html"]).map(|html| String::from_utf8_lossy(&html).into_owned())
+    }
+
+    /// File managers offer `text/uri-list`; GNOME's older ones only their
+    /// own list, which starts with a line saying "copy" or "cut".
+    pub fn files() -> Vec<PathBuf> {
+        read(&["text/uri-list", "x-special/gnome-copied-files"])
+            .map(|list| super::parse_uri_list(&list))
+            .unwrap_or_default()
+    }
+
+    /// The first of `targets` the clipboard offers.
+    fn read(targets: &[&str]) -> Option<Vec<u8>> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let (program, list): (_, &[&str]) = if wayland {
+            ("wl-paste", &["--list-types"])
+        } else {
+            (
+                "xclip",
+                &["-selection", "clipboard", "-target", "TARGETS", "-out"],
+            )
+        };
+        let types = String::from_utf8_lossy(&super::run_helper(program, list)?).into_owned();
+        let target = targets
+            .iter()
+            .find(|target| types.lines().any(|line| line.trim() == **target))?;
+        if wayland {
+            super::run_helper(program, &["--no-newline", "--type", target])
+        } else {
+            super::run_helper(
+                program,
+                &["-selection", "clipboard", "-target", target, "-out"],
+            )
+        }
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "macos",
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+mod platform {
+    pub fn html() -> Option<String> {
+        None
+    }
+
+    pub fn files() -> Vec<std::path::PathBuf> {
+        Vec::new()
+    }
+}
+
+#[cfg(all(test, unix, not(any(target_os = "ios", target_os = "android"))))]
+mod tests {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use super::*;
+
+    #[test]
+    fn reads_local_file_uris() {
+        let list = b"# a comment\r\nfile:///home/a%20b.txt\r\nFILE://localhost/tmp/c\r\n";
+        assert_eq!(
+            parse_uri_list(list),
+            [PathBuf::from("/home/a b.txt"), PathBuf::from("/tmp/c")]
+        );
+    }
+
+    #[test]
+    fn skips_empty_lines() {
+        // Nautilus ends the list with a newline, Dolphin separates with CRLF
+        // and some apps leave blank lines between entries.
+        let list = b"\r\nfile:///a\r\n\r\n  \n\nfile:///b\n";
+        assert_eq!(
+            parse_uri_list(list),
+            [PathBuf::from("/a"), PathBuf::from("/b")]
+        );
+        assert!(parse_uri_list(b"").is_empty());
+        assert!(parse_uri_list(b"\n\r\n").is_empty());
+    }
+
+    #[test]
+    fn reads_gnome_copied_files() {
+        let list = b"cut\nfile:///home/user/Documents/report%202024.pdf\n\
+                     file:///home/user/%E3%83%A1%E3%83%A2.txt";
+        assert_eq!(
+            parse_uri_list(list),
+            [
+                PathBuf::from("/home/user/Documents/report 2024.pdf"),
+                PathBuf::from("/home/user/\u{30e1}\u{30e2}.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_other_hosts_and_schemes() {
+        let list = b"copy\nfile://server/share/a\nhttps://example.com/b\nfile:/c\nfile:///d";
+        assert_eq!(parse_uri_list(list), [PathBuf::from("/d")]);
+    }
+
+    #[test]
+    fn decodes_escapes_to_bytes() {
+        // Latin-1, not UTF-8, and a stray percent sign kept as it is.
+        let list = b"file:///caf%E9/100%/%zz%4";
+        assert_eq!(
+            parse_uri_list(list),
+            [PathBuf::from(OsStr::from_bytes(b"/caf\xe9/100%/%zz%4"))]
+        );
+        // Unescaped bytes that are not UTF-8 survive too.
+        assert_eq!(
+            parse_uri_list(b"file:///\xff"),
+            [PathBuf::from(OsStr::from_bytes(b"/\xff"))]
+        );
+    }
+
+    #[test]
+    fn helpers_are_bounded() {
+        let big = run_helper("head", &["-c", "1000000", "/dev/zero"]).unwrap();
+        assert_eq!(big.len(), 1_000_000);
+        assert_eq!(run_helper("sh", &["-c", "printf x; exit 1"]), None);
+        assert_eq!(run_helper("no-such-clipboard-helper", &[]), None);
+
+        let start = std::time::Instant::now();
+        assert_eq!(run_helper("sleep", &["10"]), None);
+        assert!(start.elapsed() < HELPER_TIMEOUT * 2);
+    }
+}
+
+#[cfg(test)]
+mod html_tests {
+    use super::*;
+
+    #[test]
+    fn copies_the_last_content_once() {
+        let ctx = egui::Context::default();
+        assert_eq!(take_request(&ctx), None);
+        let table = ClipboardContent {
+            text: Some("a\tb".to_owned()),
+            html: Some("<table><tr><td>a</td><td>b</td></tr></table>".to_owned()),
+            files: Vec::new(),
+        };
+        set_clipboard(&ctx, ClipboardContent::default());
+        set_clipboard(&ctx, table.clone());
+        assert_eq!(take_request(&ctx), Some(table));
+        assert_eq!(take_request(&ctx), None);
+    }
+
+    #[test]
+    fn extracts_the_cf_html_fragment() {
+        let html = "Version:0.9\r\nStartHTML:0000000105\r\nEndHTML:0000000178\r\n\
+                    StartFragment:0000000137\r\nEndFragment:0000000146\r\n\
+                    <html><body><!--StartFragment--><b>hi</b><!--EndFragment--></body></html>";
+        assert_eq!(parse_cf_html(html.as_bytes()).unwrap(), "<b>hi</b>");
+    }
+
+    #[test]
+    fn falls_back_to_the_cf_html_document() {
+        // Some apps leave the fragment offsets at -1.
+        let body = "<p>whole</p>";
+        let header = |start: usize, end: usize| {
+            format!(
+                "Version:0.9\r\nStartHTML:{start:010}\r\nEndHTML:{end:010}\r\n\
+                 StartFragment:-1\r\nEndFragment:-1\r\n"
+            )
+        };
+        let start = header(0, 0).len();
+        let html = header(start, start + body.len()) + body;
+        assert_eq!(parse_cf_html(html.as_bytes()).unwrap(), body);
+        assert_eq!(parse_cf_html(b"Version:0.9\r\n<p>no header</p>"), None);
+        // Offsets past the end are cut at the end.
+        let html = "StartFragment:34\r\nEndFragment:99\r\n<i>";
+        assert_eq!(parse_cf_html(html.as_bytes()).unwrap(), "<i>");
+    }
+
+    #[test]
+    fn decodes_applescript_html() {
+        assert_eq!(
+            parse_applescript_html("«data HTML3C623E6869C3A93C2F623E»\n").unwrap(),
+            "<b>hi\u{e9}</b>"
+        );
+        assert_eq!(parse_applescript_html("«data HTML3C6»"), None);
+        assert_eq!(parse_applescript_html("«data utf83C62»"), None);
+        assert_eq!(parse_applescript_html(""), None);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+use web_time::{Duration, Instant};
+
+use crate::{document, user_app::UserApp};
+
+fn pending_id() -> egui::Id {
+    egui::Id::new("close_pending")
+}
+
+fn confirmed_id() -> egui::Id {
+    egui::Id::new("close_confirmed")
+}
+
+fn forced_id() -> egui::Id {
+    egui::Id::new("close_forced")
+}
+
+/// What the app wants done about a close request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseDecision {
+    Close,
+    Cancel,
+    /// Keeps running so the app can ask, e.g. about unsaved changes, and
+    /// then call [`confirm_close`] or [`dismiss_close`].
+    ShowDialogFirst,
+}
+
+/// Whether a close request waits for the app's dialog.
+pub fn is_pending(ctx: &egui::Context) -> bool {
+    ctx.data(|d| d.get_temp(pending_id())).unwrap_or(false)
+}
+
+/// Completes a pending close after the current frame.
+pub fn confirm_close(ctx: &egui::Context) {
+    ctx.data_mut(|d| {
+        d.insert_temp(pending_id(), false);
+        d.insert_temp(confirmed_id(), true);
+    });
+}
+
+/// Keeps the app open after all.
+pub fn dismiss_close(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(pending_id(), false));
+}
+
+/// Exits without asking the app, e.g. from a watchdog thread holding a clone
+/// of the context.
+pub fn force_exit(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(forced_id(), true));
+    ctx.request_repaint();
+}
+
+fn take_forced(ctx: &egui::Context) -> bool {
+    ctx.data_mut(|d| d.remove_temp(forced_id()))
+        .unwrap_or(false)
+}
+
+fn set_pending(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(pending_id(), true));
+}
+
+fn take_confirmed(ctx: &egui::Context) -> bool {
+    ctx.data_mut(|d| d.remove_temp(confirmed_id()))
+        .unwrap_or(false)
+}
+
+/// A frame's close request, asked before the UI runs so that the app can
+/// show its dialog in the same frame, and resolved after it.
+pub struct CloseRequest {
+    decision: Option<CloseDecision>,
+    forced: bool,
+}
+
+impl CloseRequest {
+    /// `ignore` leaves the request unanswered, e.g. in kiosk mode; forced
+    /// closes still go through.
+    pub fn ask(
+        ctx: &egui::Context,
+        raw_input: &egui::RawInput,
+        ignore: bool,
+        force_close: &mut ForceClose,
+        app: &mut impl UserApp,
+    ) -> Self {
+        let requested = raw_input.viewport().close_requested() && !ignore;
+        let forced = requested && force_close.on_request(Instant::now(), raw_input.modifiers.shift);
+        let decision = if !requested || forced {
+            None
+        } else if is_pending(ctx) {
+            // Already asking; further requests do not stack.
+            Some(CloseDecision::Cancel)
+        } else {
+            Some(app.on_close_requested(document::default_close_decision(ctx)))
+        };
+        if decision == Some(CloseDecision::ShowDialogFirst) {
+            set_pending(ctx);
+        }
+        Self { decision, forced }
+    }
+
+    /// Whether to exit after this frame, and if so whether the app's
+    /// confirmation was skipped.
+    pub fn resolve(self, ctx: &egui::Context, full_output: &egui::FullOutput) -> Option<bool> {
+        let mut close = false;
+        if self.decision == Some(CloseDecision::Close) {
+            close = !full_output.viewport_output[&egui::ViewportId::ROOT]
+                .commands
+                .contains(&egui::ViewportCommand::CancelClose);
+        }
+        if take_confirmed(ctx) {
+            close = true;
+        }
+        if self.forced || take_forced(ctx) {
+            log::warn!("closing without the app's confirmation");
+            return Some(true);
+        }
+        close.then_some(false)
+    }
+}
+
+/// Bypasses the app's close confirmation when the user insists: several
+/// close requests in a short time, or Shift held while closing. Keeps a
+/// stuck dialog from making the window unclosable.
+pub struct ForceClose {
+    requests: VecDeque<Instant>,
+    count: usize,
+    within: Duration,
+}
+
+impl ForceClose {
+    pub fn new(count: usize, within: Duration) -> Self {
+        Self {
+            requests: VecDeque::with_capacity(count),
+            count,
+            within,
+        }
+    }
+
+    /// Returns whether this request forces the close.
+    pub fn on_request(&mut self, now: Instant, shift: bool) -> bool {
+        while self
+            .requests
+            .front()
+            .is_some_and(|&time| now.duration_since(time) > self.within)
+        {
+            self.requests.pop_front();
+        }
+        self.requests.push_back(now);
+        shift || (self.count > 0 && self.requests.len() >= self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    /// Asks before closing, and answers the dialog as told.
+    #[derive(Default)]
+    struct Asking {
+        asked: u32,
+        answer: Option<bool>,
+        exited: Option<bool>,
+    }
+
+    impl UserApp for Asking {
+        fn update(&mut self, ctx: &egui::Context) {
+            match self.answer.take() {
+                Some(true) => confirm_close(ctx),
+                Some(false) => dismiss_close(ctx),
+                None => {}
+            }
+        }
+
+        fn on_close_requested(&mut self, _default: CloseDecision) -> CloseDecision {
+            self.asked += 1;
+            CloseDecision::ShowDialogFirst
+        }
+
+        fn on_exit(&mut self, forced: bool) {
+            self.exited = Some(forced);
+        }
+    }
+
+    #[test]
+    fn dialog_keeps_running_until_confirmed() {
+        let Some(mut test_app) = TestApp::new(Asking::default()) else {
+            return;
+        };
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(is_pending(test_app.ctx()));
+        assert!(!test_app.exit);
+
+        // Requests while the dialog is open are not asked again.
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert_eq!(test_app.app().asked, 1);
+        assert!(!test_app.exit);
+
+        test_app.app_mut().answer = Some(true);
+        test_app.step(Vec::new());
+        assert!(test_app.exit);
+        assert!(!is_pending(test_app.ctx()));
+        assert_eq!(test_app.app().exited, Some(false));
+    }
+
+    #[test]
+    fn dismissed_dialog_asks_again_next_time() {
+        let Some(mut test_app) = TestApp::new(Asking::default()) else {
+            return;
+        };
+        test_app.request_close();
+        test_app.step(Vec::new());
+        test_app.app_mut().answer = Some(false);
+        test_app.step(Vec::new());
+        assert!(!is_pending(test_app.ctx()));
+        assert!(!test_app.exit);
+
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert_eq!(test_app.app().asked, 2);
+        assert!(is_pending(test_app.ctx()));
+        assert_eq!(test_app.app().exited, None);
+    }
+
+    #[test]
+    fn cancel_close_command_overrides_the_decision() {
+        struct Vetoing;
+
+        impl UserApp for Vetoing {
+            fn update(&mut self, ctx: &egui::Context) {
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                }
+            }
+        }
+
+        let Some(mut test_app) = TestApp::new(Vetoing) else {
+            return;
+        };
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(!test_app.exit);
+    }
+
+    #[test]
+    fn edited_documents_ask_by_default() {
+        #[derive(Default)]
+        struct Defaults(Vec<CloseDecision>);
+
+        impl UserApp for Defaults {
+            fn update(&mut self, _ctx: &egui::Context) {}
+
+            fn on_close_requested(&mut self, default: CloseDecision) -> CloseDecision {
+                self.0.push(default);
+                CloseDecision::Cancel
+            }
+        }
+
+        let Some(mut test_app) = TestApp::new(Defaults::default()) else {
+            return;
+        };
+        test_app.request_close();
+        test_app.step(Vec::new());
+        document::set_document_state(test_app.ctx(), None, true);
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert_eq!(
+            test_app.app().0,
+            [CloseDecision::Close, CloseDecision::ShowDialogFirst]
+        );
+    }
+}
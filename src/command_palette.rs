@@ -0,0 +1,390 @@
+use std::cmp::Reverse;
+
+use egui::{Id, Key, KeyboardShortcut, Modifiers};
+
+use crate::{paths, user_app::UserApp};
+
+pub const DEFAULT_HOTKEY: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::P);
+
+/// How many recently used commands are remembered.
+const MAX_RECENT: usize = 16;
+
+const RECENT_FILE: &str = "recent-commands";
+
+fn query_id() -> Id {
+    Id::new("command_palette_query")
+}
+
+/// Something the user can run from the palette or by its shortcut.
+#[derive(Clone)]
+pub struct Command {
+    /// Stable across runs: recently used commands are remembered by it.
+    pub id: &'static str,
+    pub title: String,
+    pub category: String,
+    pub shortcut: Option<KeyboardShortcut>,
+}
+
+impl Command {
+    fn label(&self) -> String {
+        format!("{}: {}", self.category, self.title)
+    }
+}
+
+struct Open {
+    query: String,
+    selected: usize,
+    /// What had the keyboard focus before the palette took it.
+    restore_focus: Option<Id>,
+}
+
+/// Runs registered commands by their shortcuts, or by picking them from a
+/// fuzzy searched list that the hotkey opens. Recently used commands are
+/// listed first and remembered across runs.
+pub struct CommandPalette {
+    /// Opens and closes the palette; `None` leaves only the shortcuts.
+    pub hotkey: Option<KeyboardShortcut>,
+    commands: Vec<Command>,
+    recent: Vec<String>,
+    open: Option<Open>,
+}
+
+impl Default for CommandPalette {
+    fn default() -> Self {
+        Self {
+            hotkey: Some(DEFAULT_HOTKEY),
+            commands: Vec::new(),
+            recent: load_recent(),
+            open: None,
+        }
+    }
+}
+
+impl CommandPalette {
+    /// Replaces any command with the same id.
+    pub fn register(&mut self, command: Command) {
+        match self.commands.iter_mut().find(|c| c.id == command.id) {
+            Some(existing) => *existing = command,
+            None => self.commands.push(command),
+        }
+    }
+
+    /// Call at the start of each pass, before any other UI. Returns the ids
+    /// of the commands to run, whether activated by shortcut or picked. While
+    /// open, the palette takes all keyboard input of the pass.
+    pub fn update(&mut self, ctx: &egui::Context, app: &impl UserApp) -> Vec<&'static str> {
+        let was_open = self.open.is_some();
+        let toggled = self
+            .hotkey
+            .is_some_and(|hotkey| ctx.input_mut(|i| i.consume_shortcut(&hotkey)));
+        if toggled {
+            if was_open {
+                self.close(ctx);
+            } else {
+                self.open = Some(Open {
+                    query: String::new(),
+                    selected: 0,
+                    restore_focus: ctx.memory(|m| m.focused()),
+                });
+            }
+        }
+
+        let mut run = Vec::new();
+        if self.open.is_some() {
+            run.extend(self.show(ctx, app));
+        } else if !was_open {
+            for command in &self.commands {
+                let Some(shortcut) = command.shortcut else {
+                    continue;
+                };
+                if app.is_command_enabled(command.id)
+                    && ctx.input_mut(|i| i.consume_shortcut(&shortcut))
+                {
+                    run.push(command.id);
+                }
+            }
+        }
+        if was_open || self.open.is_some() {
+            ctx.input_mut(|i| {
+                i.events.retain(|event| {
+                    !matches!(
+                        event,
+                        egui::Event::Key { .. }
+                            | egui::Event::Text(_)
+                            | egui::Event::Paste(_)
+                            | egui::Event::Copy
+                            | egui::Event::Cut
+                            | egui::Event::Ime(_)
+                    )
+                });
+                i.keys_down.clear();
+            });
+        }
+        for id in &run {
+            self.record_recent(id);
+        }
+        run
+    }
+
+    fn close(&mut self, ctx: &egui::Context) {
+        let Some(open) = self.open.take() else {
+            return;
+        };
+        ctx.memory_mut(|m| match open.restore_focus {
+            Some(id) => m.request_focus(id),
+            None => m.surrender_focus(query_id()),
+        });
+    }
+
+    /// Indices of the commands matching `query`, best first.
+    fn matches(&self, query: &str) -> Vec<usize> {
+        let mut matches: Vec<_> = self
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(index, command)| {
+                let score = fuzzy_score(query, &command.label())?;
+                let recent = self.recent.iter().position(|id| id == command.id);
+                Some(((recent.unwrap_or(usize::MAX), Reverse(score), index), index))
+            })
+            .collect();
+        matches.sort_by_key(|(key, _)| *key);
+        matches.into_iter().map(|(_, index)| index).collect()
+    }
+
+    fn show(&mut self, ctx: &egui::Context, app: &impl UserApp) -> Option<&'static str> {
+        // Taken before the text edit sees them.
+        let (up, down, enter, escape) = ctx.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.consume_key(Modifiers::NONE, Key::Enter),
+                i.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+        if escape {
+            self.close(ctx);
+            return None;
+        }
+        let matches = self.matches(&self.open.as_ref()?.query);
+        let open = self.open.as_mut()?;
+        if down {
+            open.selected += 1;
+        }
+        open.selected = open.selected.saturating_sub(usize::from(up));
+        open.selected = open.selected.min(matches.len().saturating_sub(1));
+
+        let mut chosen = None;
+        let area = egui::Area::new(Id::new("command_palette"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0., 64.))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(420.);
+                    let query = ui.add(
+                        egui::TextEdit::singleline(&mut open.query)
+                            .id(query_id())
+                            .hint_text("Type a command")
+                            .desired_width(f32::INFINITY),
+                    );
+                    query.request_focus();
+                    if query.changed() {
+                        open.selected = 0;
+                    }
+                    egui::ScrollArea::vertical()
+                        .max_height(320.)
+                        .show(ui, |ui| {
+                            if matches.is_empty() {
+                                ui.weak("No matching commands");
+                            }
+                            for (row, &index) in matches.iter().enumerate() {
+                                let command = &self.commands[index];
+                                let enabled = app.is_command_enabled(command.id);
+                                let selected = row == open.selected;
+                                ui.add_enabled_ui(enabled, |ui| {
+                                    ui.horizontal(|ui| {
+                                        let response =
+                                            ui.selectable_label(selected, command.label());
+                                        if let Some(shortcut) = &command.shortcut {
+                                            ui.weak(ui.ctx().format_shortcut(shortcut));
+                                        }
+                                        if selected && (up || down) {
+                                            response.scroll_to_me(None);
+                                        }
+                                        if response.clicked() {
+                                            chosen = Some(command.id);
+                                        }
+                                    });
+                                });
+                                if selected && enter && enabled {
+                                    chosen = Some(command.id);
+                                }
+                            }
+                        });
+                });
+            });
+        ctx.move_to_top(area.response.layer_id);
+        if chosen.is_some() || area.response.clicked_elsewhere() {
+            self.close(ctx);
+        }
+        chosen
+    }
+
+    fn record_recent(&mut self, id: &str) {
+        if !push_recent(&mut self.recent, id) {
+            return;
+        }
+        let Some(dir) = paths::data_dir() else {
+            return;
+        };
+        let result = std::fs::create_dir_all(&dir)
+            .and_then(|()| std::fs::write(dir.join(RECENT_FILE), self.recent.join("\n")));
+        if let Err(err) = result {
+            log::warn!("cannot save recently used commands: {err}");
+        }
+    }
+}
+
+/// Moves `id` to the front. Returns whether anything changed.
+fn push_recent(recent: &mut Vec<String>, id: &str) -> bool {
+    if recent.first().is_some_and(|recent| recent == id) {
+        return false;
+    }
+    recent.retain(|recent| recent != id);
+    recent.insert(0, id.to_owned());
+    recent.truncate(MAX_RECENT);
+    true
+}
+
+fn load_recent() -> Vec<String> {
+    let Some(dir) = paths::data_dir() else {
+        return Vec::new();
+    };
+    std::fs::read_to_string(dir.join(RECENT_FILE))
+        .map(|text| text.lines().take(MAX_RECENT).map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Matches when the query's characters appear in `text` in order, ignoring
+/// case and spaces in the query. Runs of characters and matches at the start
+/// of words score higher.
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let mut text = text.chars();
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    let mut score = 0;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        loop {
+            let c = text.next()?;
+            let word_start = previous.is_none_or(|p| !p.is_alphanumeric());
+            previous = Some(c);
+            if c.to_lowercase().eq(wanted.to_lowercase()) {
+                score += 1 + 2 * u32::from(previous_matched) + 3 * u32::from(word_start);
+                previous_matched = true;
+                break;
+            }
+            previous_matched = false;
+        }
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(labels: &[(&'static str, &str)], recent: &[&str]) -> CommandPalette {
+        let mut palette = CommandPalette {
+            hotkey: None,
+            commands: Vec::new(),
+            recent: recent.iter().map(|id| id.to_string()).collect(),
+            open: None,
+        };
+        for (id, title) in labels {
+            palette.register(Command {
+                id,
+                title: title.to_string(),
+                category: "File".to_owned(),
+                shortcut: None,
+            });
+        }
+        palette
+    }
+
+    #[test]
+    fn fuzzy_matches_in_order_ignoring_case_and_spaces() {
+        assert!(fuzzy_score("fop", "File: Open project").is_some());
+        assert!(fuzzy_score("FILE OPEN", "file: open").is_some());
+        assert!(fuzzy_score("", "anything").is_some());
+        assert_eq!(fuzzy_score("pof", "File: Open project"), None);
+        assert_eq!(fuzzy_score("opens", "File: Open"), None);
+    }
+
+    #[test]
+    fn runs_and_word_starts_score_higher() {
+        let score = |query| fuzzy_score(query, "File: Save all").unwrap();
+        // Word starts beat letters inside words.
+        assert!(score("sa") > score("ae"));
+        // A run beats the same letters spread out.
+        assert!(score("sav") > score("sal"));
+        assert_eq!(
+            fuzzy_score("fs", "File: Save"),
+            fuzzy_score("fs", "file save")
+        );
+    }
+
+    #[test]
+    fn recent_commands_come_first() {
+        let palette = palette(
+            &[
+                ("open", "Open"),
+                ("save", "Save"),
+                ("save_as", "Save as"),
+                ("close", "Close"),
+            ],
+            &["close", "save_as"],
+        );
+        let ids = |query| {
+            palette
+                .matches(query)
+                .into_iter()
+                .map(|index| palette.commands[index].id)
+                .collect::<Vec<_>>()
+        };
+        // Most recent first, then by score, then in registration order.
+        assert_eq!(ids(""), ["close", "save_as", "open", "save"]);
+        assert_eq!(ids("save"), ["save_as", "save"]);
+        assert_eq!(ids("o"), ["close", "open"]);
+        assert!(ids("xyz").is_empty());
+    }
+
+    #[test]
+    fn push_recent_moves_to_the_front_and_caps() {
+        let mut recent = Vec::new();
+        assert!(push_recent(&mut recent, "a"));
+        assert!(push_recent(&mut recent, "b"));
+        assert!(!push_recent(&mut recent, "b"));
+        assert!(push_recent(&mut recent, "a"));
+        assert_eq!(recent, ["a", "b"]);
+        for i in 0..MAX_RECENT {
+            push_recent(&mut recent, &i.to_string());
+        }
+        assert_eq!(recent.len(), MAX_RECENT);
+        assert_eq!(recent[0], (MAX_RECENT - 1).to_string());
+        assert!(!recent.contains(&"a".to_owned()));
+    }
+
+    #[test]
+    fn register_replaces_by_id() {
+        let mut palette = palette(&[("open", "Open"), ("save", "Save")], &[]);
+        palette.register(Command {
+            id: "open",
+            title: "Open file".to_owned(),
+            category: "File".to_owned(),
+            shortcut: None,
+        });
+        assert_eq!(palette.commands.len(), 2);
+        assert_eq!(palette.commands[0].label(), "File: Open file");
+    }
+}
@@ -0,0 +1,706 @@
+#[cfg(feature = "texture-containers")]
+use anyhow::Context as _;
+use anyhow::{bail, ensure};
+use wgpu::{util::DeviceExt, TextureFormat};
+
+/// A block-compressed image with its mip chain, level 0 first. Each level
+/// holds whole blocks, so a 70x45 BC1 level 0 is 18x12 blocks.
+pub struct CompressedImage {
+    pub format: TextureFormat,
+    pub size: [u32; 2],
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// A texture made from a [`CompressedImage`], ready to register with
+/// [`crate::egui_renderer::EguiRenderer::register_native_texture`].
+pub struct UploadedTexture {
+    pub texture: wgpu::Texture,
+    /// The part of the texture that holds the image. Compressed textures are
+    /// padded to whole blocks, which egui should not show.
+    pub uv: egui::Rect,
+    /// Set when the adapter could not sample the format and the image was
+    /// decoded to RGBA8 on the CPU.
+    pub decoded: bool,
+}
+
+impl CompressedImage {
+    fn level_size(&self, level: usize) -> [u32; 2] {
+        let [width, height] = self.size;
+        [(width >> level).max(1), (height >> level).max(1)]
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.format.is_compressed(),
+            "{:?} is not a compressed format",
+            self.format
+        );
+        ensure!(self.size[0] > 0 && self.size[1] > 0, "the image is empty");
+        ensure!(!self.levels.is_empty(), "the image has no mip levels");
+        let max_levels = 32 - self.size[0].max(self.size[1]).leading_zeros();
+        ensure!(
+            self.levels.len() as u32 <= max_levels,
+            "{} mip levels for a {}x{} image",
+            self.levels.len(),
+            self.size[0],
+            self.size[1]
+        );
+        for (level, data) in self.levels.iter().enumerate() {
+            let expected = level_bytes(self.format, self.level_size(level));
+            ensure!(
+                data.len() as u64 == expected,
+                "mip level {level} has {} bytes instead of {expected}",
+                data.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn blocks(format: TextureFormat, [width, height]: [u32; 2]) -> [u32; 2] {
+    let (block_width, block_height) = format.block_dimensions();
+    [width.div_ceil(block_width), height.div_ceil(block_height)]
+}
+
+fn level_bytes(format: TextureFormat, size: [u32; 2]) -> u64 {
+    let [columns, rows] = blocks(format, size);
+    columns as u64 * rows as u64 * format.block_copy_size(None).unwrap_or(0) as u64
+}
+
+/// Uploads `image` in its compressed format when the device has the
+/// matching `TEXTURE_COMPRESSION_*` feature, and otherwise decodes it to
+/// RGBA8 first. Only BC1 to BC5 can be decoded; other formats fail on
+/// adapters that cannot sample them.
+pub fn upload(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &CompressedImage,
+) -> anyhow::Result<UploadedTexture> {
+    image.validate()?;
+    if device.features().contains(image.format.required_features()) {
+        Ok(upload_compressed(device, queue, image))
+    } else {
+        log::info!(
+            "the adapter cannot sample {:?}, decoding {}x{} on the CPU",
+            image.format,
+            image.size[0],
+            image.size[1]
+        );
+        upload_decoded(device, queue, image)
+    }
+}
+
+fn upload_compressed(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &CompressedImage,
+) -> UploadedTexture {
+    // wgpu wants level 0 in whole blocks. Smaller levels round up to blocks
+    // on their own, but from the padded size, which can be a block more than
+    // the image's own level has: such levels repeat their last block.
+    let (block_width, block_height) = image.format.block_dimensions();
+    let padded = [
+        image.size[0].next_multiple_of(block_width),
+        image.size[1].next_multiple_of(block_height),
+    ];
+    let block_size = image.format.block_copy_size(None).unwrap_or(0) as usize;
+    let mut data = Vec::new();
+    for (level, level_data) in image.levels.iter().enumerate() {
+        let [columns, rows] = blocks(image.format, image.level_size(level));
+        let padded_size = [(padded[0] >> level).max(1), (padded[1] >> level).max(1)];
+        let [padded_columns, padded_rows] = blocks(image.format, padded_size);
+        for row in 0..padded_rows {
+            let row = row.min(rows - 1) as usize;
+            for column in 0..padded_columns {
+                let column = column.min(columns - 1) as usize;
+                let start = (row * columns as usize + column) * block_size;
+                data.extend_from_slice(&level_data[start..start + block_size]);
+            }
+        }
+    }
+    let texture = device.create_texture_with_data(
+        queue,
+        &texture_descriptor(image.format, padded, image.levels.len() as u32),
+        wgpu::util::TextureDataOrder::MipMajor,
+        &data,
+    );
+    UploadedTexture {
+        texture,
+        uv: egui::Rect::from_min_max(
+            egui::Pos2::ZERO,
+            egui::pos2(
+                image.size[0] as f32 / padded[0] as f32,
+                image.size[1] as f32 / padded[1] as f32,
+            ),
+        ),
+        decoded: false,
+    }
+}
+
+fn upload_decoded(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &CompressedImage,
+) -> anyhow::Result<UploadedTexture> {
+    let (decode_block, format): (DecodeBlock, _) = match image.format {
+        TextureFormat::Bc1RgbaUnorm => (decode_bc1, TextureFormat::Rgba8Unorm),
+        TextureFormat::Bc1RgbaUnormSrgb => (decode_bc1, TextureFormat::Rgba8UnormSrgb),
+        TextureFormat::Bc2RgbaUnorm => (decode_bc2, TextureFormat::Rgba8Unorm),
+        TextureFormat::Bc2RgbaUnormSrgb => (decode_bc2, TextureFormat::Rgba8UnormSrgb),
+        TextureFormat::Bc3RgbaUnorm => (decode_bc3, TextureFormat::Rgba8Unorm),
+        TextureFormat::Bc3RgbaUnormSrgb => (decode_bc3, TextureFormat::Rgba8UnormSrgb),
+        TextureFormat::Bc4RUnorm => (decode_bc4, TextureFormat::Rgba8Unorm),
+        TextureFormat::Bc5RgUnorm => (decode_bc5, TextureFormat::Rgba8Unorm),
+        format => bail!("the adapter cannot sample {format:?} and it has no CPU decoder"),
+    };
+    let mut data = Vec::new();
+    for (level, level_data) in image.levels.iter().enumerate() {
+        data.extend(decode_level(
+            level_data,
+            image.format.block_copy_size(None).unwrap_or(0) as usize,
+            image.level_size(level),
+            decode_block,
+        ));
+    }
+    let texture = device.create_texture_with_data(
+        queue,
+        &texture_descriptor(format, image.size, image.levels.len() as u32),
+        wgpu::util::TextureDataOrder::MipMajor,
+        &data,
+    );
+    Ok(UploadedTexture {
+        texture,
+        uv: egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1., 1.)),
+        decoded: true,
+    })
+}
+
+fn texture_descriptor(
+    format: TextureFormat,
+    [width, height]: [u32; 2],
+    mip_level_count: u32,
+) -> wgpu::TextureDescriptor<'static> {
+    wgpu::TextureDescriptor {
+        label: Some("compressed image"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    }
+}
+
+/// Decodes one block to its 4x4 texels, row by row.
+type DecodeBlock = fn(&[u8]) -> [[u8; 4]; 16];
+
+/// Decodes one level of 4x4 blocks to RGBA8 rows, dropping the texels
+/// past the level's edge.
+fn decode_level(
+    data: &[u8],
+    block_size: usize,
+    [width, height]: [u32; 2],
+    decode_block: DecodeBlock,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let columns = width.div_ceil(4);
+    let mut rgba = vec![0; width * height * 4];
+    for (index, block) in data.chunks_exact(block_size).enumerate() {
+        let texels = decode_block(block);
+        let (block_x, block_y) = (index % columns * 4, index / columns * 4);
+        for (i, texel) in texels.iter().enumerate() {
+            let (x, y) = (block_x + i % 4, block_y + i / 4);
+            if x < width && y < height {
+                let start = (y * width + x) * 4;
+                rgba[start..start + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    rgba
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let (r, g, b) = (color >> 11, (color >> 5) & 0x3f, color & 0x1f);
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+    ]
+}
+
+/// The color half shared by BC1 to BC3. Only BC1 has the three-color mode
+/// with transparent black.
+fn decode_color(block: &[u8], allow_transparent: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u16, wb: u16| -> [u8; 4] {
+        let channel = |i: usize| ((a[i] as u16 * wa + b[i] as u16 * wb) / (wa + wb)) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if c0 > c1 || !allow_transparent {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0; 4]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i) & 3) as usize])
+}
+
+/// The 8-byte block of BC4, and of alpha in BC3.
+fn decode_channel(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let palette: [u8; 8] = std::array::from_fn(|i| match i {
+        0 => a0 as u8,
+        1 => a1 as u8,
+        _ if a0 > a1 => ((a0 * (8 - i as u32) + a1 * (i as u32 - 1)) / 7) as u8,
+        2..=5 => ((a0 * (6 - i as u32) + a1 * (i as u32 - 1)) / 5) as u8,
+        6 => 0,
+        _ => 255,
+    });
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[(indices >> (3 * i) & 7) as usize])
+}
+
+fn decode_bc1(block: &[u8]) -> [[u8; 4]; 16] {
+    decode_color(block, true)
+}
+
+fn decode_bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color(&block[8..], false);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = (alpha >> (4 * i) & 0xf) as u8 * 17;
+    }
+    texels
+}
+
+fn decode_bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color(&block[8..], false);
+    for (texel, alpha) in texels.iter_mut().zip(decode_channel(&block[..8])) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+fn decode_bc4(block: &[u8]) -> [[u8; 4]; 16] {
+    decode_channel(block).map(|r| [r, 0, 0, 255])
+}
+
+fn decode_bc5(block: &[u8]) -> [[u8; 4]; 16] {
+    let (red, green) = (decode_channel(&block[..8]), decode_channel(&block[8..]));
+    std::array::from_fn(|i| [red[i], green[i], 0, 255])
+}
+
+/// A BC1 test card whose size is no multiple of 4, with a full mip chain
+/// tinted per level so the level the sampler picks is visible.
+pub fn demo_image() -> CompressedImage {
+    const TINTS: [[u16; 3]; 4] = [[31, 40, 8], [8, 63, 31], [31, 63, 0], [31, 20, 31]];
+    let format = TextureFormat::Bc1RgbaUnormSrgb;
+    let size = [70, 45];
+    let mut image = CompressedImage {
+        format,
+        size,
+        levels: Vec::new(),
+    };
+    let level_count = 32 - size[0].max(size[1]).leading_zeros();
+    for level in 0..level_count as usize {
+        let [columns, rows] = blocks(format, image.level_size(level));
+        let [r, g, b] = TINTS[level % TINTS.len()];
+        let mut data = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let divisor = if (row + column) % 2 == 0 { 1 } else { 2 };
+                let rgb565 =
+                    |divisor: u16| (r / divisor) << 11 | (g / divisor) << 5 | (b / divisor);
+                // c1 is darker than c0, so the block stays in four-color mode.
+                let (c0, c1) = (rgb565(divisor), rgb565(divisor * 4));
+                data.extend_from_slice(&c0.to_le_bytes());
+                data.extend_from_slice(&c1.to_le_bytes());
+                // Fades from c0 to c1 down each block.
+                data.extend_from_slice(&[0x00, 0xaa, 0xff, 0x55]);
+            }
+        }
+        image.levels.push(data);
+    }
+    image
+}
+
+/// Reads a DDS or KTX2 file holding a single 2D image.
+#[cfg(feature = "texture-containers")]
+pub fn read_container(path: &std::path::Path) -> anyhow::Result<CompressedImage> {
+    let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let image = if bytes.starts_with(b"DDS ") {
+        from_dds(&bytes)
+    } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+        from_ktx2(&bytes)
+    } else {
+        Err(anyhow::anyhow!("not a DDS or KTX2 file"))
+    };
+    image.with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(feature = "texture-containers")]
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+#[cfg(feature = "texture-containers")]
+fn read_u32(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let field = bytes
+        .get(offset..offset + 4)
+        .context("the file is truncated")?;
+    Ok(u32::from_le_bytes(field.try_into().unwrap()))
+}
+
+#[cfg(feature = "texture-containers")]
+fn read_u64(bytes: &[u8], offset: usize) -> anyhow::Result<u64> {
+    let field = bytes
+        .get(offset..offset + 8)
+        .context("the file is truncated")?;
+    Ok(u64::from_le_bytes(field.try_into().unwrap()))
+}
+
+/// Splits tightly packed levels, level 0 first, as DDS stores them.
+#[cfg(feature = "texture-containers")]
+fn split_levels(
+    format: TextureFormat,
+    size: [u32; 2],
+    level_count: u32,
+    mut data: &[u8],
+) -> anyhow::Result<CompressedImage> {
+    let mut image = CompressedImage {
+        format,
+        size,
+        levels: Vec::new(),
+    };
+    for level in 0..level_count as usize {
+        let len = level_bytes(format, image.level_size(level)) as usize;
+        ensure!(data.len() >= len, "mip level {level} is truncated");
+        let (level_data, rest) = data.split_at(len);
+        image.levels.push(level_data.to_vec());
+        data = rest;
+    }
+    image.validate()?;
+    Ok(image)
+}
+
+#[cfg(feature = "texture-containers")]
+fn from_dds(bytes: &[u8]) -> anyhow::Result<CompressedImage> {
+    const MIPMAP_COUNT: u32 = 0x20000;
+    const CUBEMAP: u32 = 0x200;
+    let flags = read_u32(bytes, 8)?;
+    let size = [read_u32(bytes, 16)?, read_u32(bytes, 12)?];
+    let level_count = if flags & MIPMAP_COUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    ensure!(
+        read_u32(bytes, 112)? & CUBEMAP == 0,
+        "cube maps are not supported"
+    );
+    let four_cc = bytes.get(84..88).context("the file is truncated")?;
+    // Files without a DX10 header do not say whether they are sRGB; color
+    // images usually are.
+    let (format, data_offset) = match four_cc {
+        b"DXT1" => (TextureFormat::Bc1RgbaUnormSrgb, 128),
+        b"DXT2" | b"DXT3" => (TextureFormat::Bc2RgbaUnormSrgb, 128),
+        b"DXT4" | b"DXT5" => (TextureFormat::Bc3RgbaUnormSrgb, 128),
+        b"ATI1" | b"BC4U" => (TextureFormat::Bc4RUnorm, 128),
+        b"ATI2" | b"BC5U" => (TextureFormat::Bc5RgUnorm, 128),
+        b"DX10" => {
+            ensure!(
+                read_u32(bytes, 140)? <= 1,
+                "texture arrays are not supported"
+            );
+            let format = match read_u32(bytes, 128)? {
+                71 => TextureFormat::Bc1RgbaUnorm,
+                72 => TextureFormat::Bc1RgbaUnormSrgb,
+                74 => TextureFormat::Bc2RgbaUnorm,
+                75 => TextureFormat::Bc2RgbaUnormSrgb,
+                77 => TextureFormat::Bc3RgbaUnorm,
+                78 => TextureFormat::Bc3RgbaUnormSrgb,
+                80 => TextureFormat::Bc4RUnorm,
+                81 => TextureFormat::Bc4RSnorm,
+                83 => TextureFormat::Bc5RgUnorm,
+                84 => TextureFormat::Bc5RgSnorm,
+                95 => TextureFormat::Bc6hRgbUfloat,
+                96 => TextureFormat::Bc6hRgbFloat,
+                98 => TextureFormat::Bc7RgbaUnorm,
+                99 => TextureFormat::Bc7RgbaUnormSrgb,
+                format => bail!("unsupported DXGI format {format}"),
+            };
+            (format, 148)
+        }
+        four_cc => bail!(
+            "unsupported DDS format {:?}",
+            String::from_utf8_lossy(four_cc)
+        ),
+    };
+    let data = bytes.get(data_offset..).context("the file is truncated")?;
+    split_levels(format, size, level_count, data)
+}
+
+#[cfg(feature = "texture-containers")]
+fn from_ktx2(bytes: &[u8]) -> anyhow::Result<CompressedImage> {
+    let format = match read_u32(bytes, 12)? {
+        131 | 133 => TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => TextureFormat::Bc1RgbaUnormSrgb,
+        135 => TextureFormat::Bc2RgbaUnorm,
+        136 => TextureFormat::Bc2RgbaUnormSrgb,
+        137 => TextureFormat::Bc3RgbaUnorm,
+        138 => TextureFormat::Bc3RgbaUnormSrgb,
+        139 => TextureFormat::Bc4RUnorm,
+        140 => TextureFormat::Bc4RSnorm,
+        141 => TextureFormat::Bc5RgUnorm,
+        142 => TextureFormat::Bc5RgSnorm,
+        143 => TextureFormat::Bc6hRgbUfloat,
+        144 => TextureFormat::Bc6hRgbFloat,
+        145 => TextureFormat::Bc7RgbaUnorm,
+        146 => TextureFormat::Bc7RgbaUnormSrgb,
+        147 => TextureFormat::Etc2Rgb8Unorm,
+        148 => TextureFormat::Etc2Rgb8UnormSrgb,
+        149 => TextureFormat::Etc2Rgb8A1Unorm,
+        150 => TextureFormat::Etc2Rgb8A1UnormSrgb,
+        151 => TextureFormat::Etc2Rgba8Unorm,
+        152 => TextureFormat::Etc2Rgba8UnormSrgb,
+        153 => TextureFormat::EacR11Unorm,
+        154 => TextureFormat::EacR11Snorm,
+        155 => TextureFormat::EacRg11Unorm,
+        156 => TextureFormat::EacRg11Snorm,
+        157 => TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::Unorm,
+        },
+        158 => TextureFormat::Astc {
+            block: wgpu::AstcBlock::B4x4,
+            channel: wgpu::AstcChannel::UnormSrgb,
+        },
+        format => bail!("unsupported VkFormat {format}"),
+    };
+    let size = [read_u32(bytes, 20)?, read_u32(bytes, 24)?];
+    ensure!(read_u32(bytes, 28)? == 0, "3D textures are not supported");
+    ensure!(
+        read_u32(bytes, 32)? == 0,
+        "texture arrays are not supported"
+    );
+    ensure!(read_u32(bytes, 36)? == 1, "cube maps are not supported");
+    ensure!(
+        read_u32(bytes, 44)? == 0,
+        "supercompressed files are not supported"
+    );
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let mut image = CompressedImage {
+        format,
+        size,
+        levels: Vec::new(),
+    };
+    for level in 0..level_count as usize {
+        let entry = 80 + level * 24;
+        let offset = read_u64(bytes, entry)? as usize;
+        let len = read_u64(bytes, entry + 8)? as usize;
+        let data = offset
+            .checked_add(len)
+            .and_then(|end| bytes.get(offset..end))
+            .with_context(|| format!("mip level {level} is truncated"))?;
+        image.levels.push(data.to_vec());
+    }
+    image.validate()?;
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gpu_memory, testing};
+
+    /// One four-color BC1 block from white to black, with the texels' palette
+    /// indices 0, 1, 2 and 3 in turn.
+    const GRADIENT_BLOCK: [u8; 8] = [0xff, 0xff, 0x00, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+
+    #[test]
+    fn decodes_bc1() {
+        let texels = decode_bc1(&GRADIENT_BLOCK);
+        assert_eq!(
+            texels[..4],
+            [
+                [255; 4],
+                [0, 0, 0, 255],
+                [170, 170, 170, 255],
+                [85, 85, 85, 255]
+            ]
+        );
+        // c0 <= c1 switches to three colors and transparent black.
+        let texels = decode_bc1(&[0x00, 0x00, 0xff, 0xff, 0xe4, 0, 0, 0]);
+        assert_eq!(
+            texels[..4],
+            [[0, 0, 0, 255], [255; 4], [127, 127, 127, 255], [0; 4]]
+        );
+    }
+
+    #[test]
+    fn decodes_bc4() {
+        // Eight values from 255 down to 0, the first texels 0 to 7 in turn.
+        let block = [255, 0, 0b1000_1000, 0b1100_0110, 0b1111_1010, 0, 0, 0];
+        let reds: Vec<u8> = decode_bc4(&block)[..8]
+            .iter()
+            .map(|texel| texel[0])
+            .collect();
+        assert_eq!(reds, [255, 0, 218, 182, 145, 109, 72, 36]);
+        // a0 <= a1 has six values, then 0 and 255.
+        let block = [0, 100, 0b1000_1000, 0b1100_0110, 0b1111_1010, 0, 0, 0];
+        let reds: Vec<u8> = decode_bc4(&block)[..8]
+            .iter()
+            .map(|texel| texel[0])
+            .collect();
+        assert_eq!(reds, [0, 100, 20, 40, 60, 80, 0, 255]);
+    }
+
+    #[test]
+    fn decode_drops_texels_past_the_edge() {
+        let rgba = decode_level(&GRADIENT_BLOCK, 8, [3, 2], decode_bc1);
+        assert_eq!(rgba.len(), 3 * 2 * 4);
+        assert_eq!(rgba[..4], [255; 4]);
+        // The second row starts with the block's fifth texel.
+        assert_eq!(rgba[12..16], [255; 4]);
+    }
+
+    #[test]
+    fn demo_image_is_valid() {
+        let image = demo_image();
+        image.validate().unwrap();
+        assert_eq!(image.levels.len(), 7);
+        // 18x12 blocks of 8 bytes.
+        assert_eq!(image.levels[0].len(), 1728);
+        assert_eq!(image.levels[6].len(), 8);
+    }
+
+    #[test]
+    fn rejects_levels_of_the_wrong_size() {
+        let mut image = demo_image();
+        image.levels[1].pop();
+        assert!(image.validate().is_err());
+        let mut image = demo_image();
+        image.levels.push(vec![0; 8]);
+        assert!(image.validate().is_err());
+    }
+
+    #[test]
+    fn uploads_compressed_with_padding() {
+        let Some((device, queue)) =
+            testing::software_device(wgpu::Features::TEXTURE_COMPRESSION_BC)
+        else {
+            return;
+        };
+        if !device
+            .features()
+            .contains(wgpu::Features::TEXTURE_COMPRESSION_BC)
+        {
+            return;
+        }
+        let uploaded = upload(&device, &queue, &demo_image()).unwrap();
+        assert!(!uploaded.decoded);
+        let texture = &uploaded.texture;
+        assert_eq!([texture.width(), texture.height()], [72, 48]);
+        assert_eq!(texture.mip_level_count(), 7);
+        // 294 blocks from 18x12 down to the padded 2x1 and 1x1 levels.
+        assert_eq!(gpu_memory::texture_bytes(texture), 2352);
+        assert_eq!(uploaded.uv.max, egui::pos2(70. / 72., 45. / 48.));
+    }
+
+    #[test]
+    fn uploads_decoded_without_the_feature() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let uploaded = upload(&device, &queue, &demo_image()).unwrap();
+        assert!(uploaded.decoded);
+        let texture = &uploaded.texture;
+        assert_eq!(texture.format(), TextureFormat::Rgba8UnormSrgb);
+        assert_eq!([texture.width(), texture.height()], [70, 45]);
+        assert_eq!(texture.mip_level_count(), 7);
+        assert_eq!(uploaded.uv.max, egui::pos2(1., 1.));
+
+        let mut bc7 = demo_image();
+        bc7.format = TextureFormat::Bc7RgbaUnorm;
+        bc7.levels = bc7
+            .levels
+            .iter()
+            .map(|level| [level.as_slice(); 2].concat())
+            .collect();
+        assert!(upload(&device, &queue, &bc7).is_err());
+    }
+
+    #[cfg(feature = "texture-containers")]
+    fn dds(image: &CompressedImage) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        let mut put = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(4, 124);
+        put(8, 0x2_1007);
+        put(12, image.size[1]);
+        put(16, image.size[0]);
+        put(28, image.levels.len() as u32);
+        bytes[0..4].copy_from_slice(b"DDS ");
+        bytes[84..88].copy_from_slice(b"DXT1");
+        bytes.extend(image.levels.concat());
+        bytes
+    }
+
+    #[cfg(feature = "texture-containers")]
+    fn ktx2(image: &CompressedImage) -> Vec<u8> {
+        let index_end = 80 + 24 * image.levels.len();
+        let mut bytes = vec![0; index_end];
+        bytes[..12].copy_from_slice(&KTX2_IDENTIFIER);
+        let mut put = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(12, 132);
+        put(16, 1);
+        put(20, image.size[0]);
+        put(24, image.size[1]);
+        put(36, 1);
+        put(40, image.levels.len() as u32);
+        // KTX2 stores the smallest level first.
+        let mut offset = index_end;
+        let mut offsets = vec![0; image.levels.len()];
+        for (level, data) in image.levels.iter().enumerate().rev() {
+            offsets[level] = offset;
+            offset += data.len();
+        }
+        for (level, data) in image.levels.iter().enumerate() {
+            let entry = 80 + level * 24;
+            bytes[entry..entry + 8].copy_from_slice(&(offsets[level] as u64).to_le_bytes());
+            bytes[entry + 8..entry + 16].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        }
+        for data in image.levels.iter().rev() {
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    #[cfg(feature = "texture-containers")]
+    #[test]
+    fn containers_round_trip() {
+        let image = demo_image();
+        type Parse = fn(&[u8]) -> anyhow::Result<CompressedImage>;
+        let containers: [(Vec<u8>, Parse); 2] =
+            [(dds(&image), from_dds), (ktx2(&image), from_ktx2)];
+        for (bytes, parse) in containers {
+            let parsed = parse(&bytes).unwrap();
+            assert_eq!(parsed.format, image.format);
+            assert_eq!(parsed.size, image.size);
+            assert_eq!(parsed.levels, image.levels);
+            assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::Cell,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    log_console::{self, LogEntry},
+    paths,
+};
+
+/// Report generation gives up after this, e.g. when a lock is held by the
+/// thread that panicked.
+const TIMEOUT: Duration = Duration::from_secs(1);
+const LOG_ENTRIES: usize = 200;
+const REPAINT_CAUSES: usize = 32;
+
+type Hook = Arc<dyn Fn() -> String + Send + Sync>;
+
+#[derive(Clone)]
+struct Context {
+    dir: Option<PathBuf>,
+    sections: BTreeMap<&'static str, String>,
+    repaint_causes: VecDeque<String>,
+    hook: Option<Hook>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    dir: None,
+    sections: BTreeMap::new(),
+    repaint_causes: VecDeque::new(),
+    hook: None,
+});
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static WRITTEN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn context() -> std::sync::MutexGuard<'static, Context> {
+    CONTEXT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Where reports go by default, see [`paths::data_dir`].
+fn default_dir() -> Option<PathBuf> {
+    Some(paths::data_dir()?.join("crashes"))
+}
+
+/// Enables reports for fatal errors and panics. The previous panic hook
+/// still runs first.
+pub fn install(dir: Option<PathBuf>) {
+    context().dir = dir.or_else(default_dir);
+    INSTALLED.store(true, Ordering::Relaxed);
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if !CATCHING.get() {
+            write(&info.to_string());
+        }
+    }));
+}
+
+/// [`std::panic::catch_unwind`] for panics that are handled, which are not
+/// reported.
+pub fn catch_unwind<R>(f: impl FnOnce() -> R) -> Result<R, Box<dyn Any + Send>> {
+    let catching = CATCHING.replace(true);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    CATCHING.set(catching);
+    result
+}
+
+/// Replaces a named section of the report, e.g. the adapter or the settings.
+/// Kept even without [`install`], as bug reports include them too.
+pub fn set_section(name: &'static str, text: String) {
+    context().sections.insert(name, text);
+}
+
+/// The sections set so far, by name.
+pub fn sections() -> Vec<(&'static str, String)> {
+    context()
+        .sections
+        .iter()
+        .map(|(name, text)| (*name, text.clone()))
+        .collect()
+}
+
+/// Lets the app add its own context, collected only when a report is
+/// written.
+pub fn set_hook(hook: impl Fn() -> String + Send + Sync + 'static) {
+    context().hook = Some(Arc::new(hook));
+}
+
+/// Keeps the causes of the last repaints. Frames without any are skipped.
+pub fn record_repaint_causes(ctx: &egui::Context) {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let causes = ctx.repaint_causes();
+    if causes.is_empty() {
+        return;
+    }
+    let mut line = format!("pass {}:", ctx.cumulative_pass_nr());
+    for cause in causes {
+        let _ = write!(line, " {cause}");
+    }
+    let mut context = context();
+    if context.repaint_causes.len() == REPAINT_CAUSES {
+        context.repaint_causes.pop_front();
+    }
+    context.repaint_causes.push_back(line);
+}
+
+/// Writes a report for the error and prints its path to stderr. Only the
+/// first error of a run is reported; later ones are usually consequences.
+pub fn write(error: &str) {
+    if !INSTALLED.load(Ordering::Relaxed) || WRITTEN.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    // Symbolized by the worker, as that can take long.
+    let backtrace = Backtrace::force_capture();
+    let error = error.to_owned();
+    let (sender, receiver) = mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("crash-report".to_owned())
+        .spawn(move || {
+            let written = std::panic::catch_unwind(|| write_report(&error, &backtrace));
+            let _ = sender.send(written);
+        });
+    if spawned.is_err() {
+        return;
+    }
+    match receiver.recv_timeout(TIMEOUT) {
+        Ok(Ok(Ok(path))) => eprintln!("crash report written to {}", path.display()),
+        Ok(Ok(Err(err))) => eprintln!("failed to write a crash report: {err}"),
+        Ok(Err(_)) => eprintln!("writing the crash report panicked"),
+        Err(_) => eprintln!("writing the crash report timed out"),
+    }
+}
+
+fn write_report(error: &str, backtrace: &Backtrace) -> std::io::Result<PathBuf> {
+    let context = context().clone();
+    let dir = context
+        .dir
+        .clone()
+        .ok_or_else(|| std::io::Error::other("no directory for crash reports"))?;
+    let report = format_report(
+        error,
+        backtrace,
+        &context,
+        &log_console::recent(LOG_ENTRIES),
+    );
+
+    std::fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = dir.join(format!("crash-{timestamp}.txt"));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn format_report(
+    error: &str,
+    backtrace: &Backtrace,
+    context: &Context,
+    log: &[LogEntry],
+) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "error: {error}");
+    let _ = writeln!(
+        report,
+        "version: {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(
+        report,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report, "\n[backtrace]\n{backtrace}");
+    for (name, text) in &context.sections {
+        let _ = writeln!(report, "\n[{name}]\n{text}");
+    }
+    let _ = writeln!(report, "\n[repaint causes]");
+    for line in &context.repaint_causes {
+        let _ = writeln!(report, "{line}");
+    }
+    let _ = writeln!(report, "\n[log]");
+    for entry in log {
+        let _ = writeln!(
+            report,
+            "{:>8.3} {:<5} {}: {}",
+            entry.time.as_secs_f32(),
+            entry.level,
+            entry.target,
+            entry.message
+        );
+    }
+    if let Some(hook) = &context.hook {
+        let _ = writeln!(report, "\n[app]\n{}", hook());
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn capture_here() -> Backtrace {
+        Backtrace::force_capture()
+    }
+
+    #[test]
+    fn report_lists_its_sections_in_order() {
+        let context = Context {
+            dir: None,
+            sections: BTreeMap::from([
+                ("settings", "vsync: true".to_owned()),
+                ("adapter", "llvmpipe".to_owned()),
+            ]),
+            repaint_causes: VecDeque::from(["pass 3: app.rs:10".to_owned()]),
+            hook: Some(Arc::new(|| "documents: 2".to_owned())),
+        };
+        let log = [LogEntry {
+            time: Duration::from_millis(1500),
+            level: log::Level::Warn,
+            target: "app".to_owned(),
+            message: "slow frame".to_owned(),
+        }];
+        let report = format_report("boom", &capture_here(), &context, &log);
+
+        let expected = [
+            "error: boom\n",
+            concat!("version: ", env!("CARGO_PKG_NAME")),
+            "\n[backtrace]\n",
+            "capture_here",
+            "\n[adapter]\nllvmpipe\n",
+            "\n[settings]\nvsync: true\n",
+            "\n[repaint causes]\npass 3: app.rs:10\n",
+            "\n[log]\n   1.500 WARN  app: slow frame\n",
+            "\n[app]\ndocuments: 2\n",
+        ];
+        let mut rest = report.as_str();
+        for part in expected {
+            let at = rest
+                .find(part)
+                .unwrap_or_else(|| panic!("{part:?} missing or out of order in:\n{report}"));
+            rest = &rest[at + part.len()..];
+        }
+    }
+
+    #[test]
+    fn panic_writes_a_report_once() {
+        let dir = std::env::temp_dir().join(format!("{}-crash-report", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        WRITTEN.store(false, Ordering::Relaxed);
+        set_section("adapter", "llvmpipe".to_owned());
+        install(Some(dir.clone()));
+
+        let panicked = std::thread::spawn(|| panic!("boom")).join();
+        assert!(panicked.is_err());
+        // Handled panics and later errors add nothing.
+        assert!(catch_unwind(|| panic!("handled")).is_err());
+        write("later");
+        // The hook stays installed for the process; this keeps it inert.
+        INSTALLED.store(false, Ordering::Relaxed);
+
+        let reports: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(reports.len(), 1, "{reports:?}");
+        let name = reports[0].file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with("crash-") && name.ends_with(".txt"),
+            "{name}"
+        );
+        let report = std::fs::read_to_string(&reports[0]).unwrap();
+        for part in [
+            "error: ",
+            "boom",
+            "\n[backtrace]\n",
+            "\n[adapter]\nllvmpipe\n",
+            "\n[log]\n",
+        ] {
+            assert!(report.contains(part), "{part:?} missing in:\n{report}");
+        }
+        assert!(!report.contains("later"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
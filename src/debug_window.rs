@@ -0,0 +1,94 @@
+use egui::{Id, Key, KeyboardShortcut, Modifiers};
+
+use crate::repaint_flash::RepaintFlash;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Tab {
+    #[default]
+    Settings,
+    Inspection,
+    Memory,
+    Repaint,
+}
+
+/// egui's own settings, inspection and memory UIs for the app's context in
+/// one window, drawn by the overlay context and toggled with a hotkey, see
+/// [`DEFAULT_HOTKEY`].
+#[derive(Default)]
+pub struct DebugWindow {
+    open: bool,
+}
+
+pub const DEFAULT_HOTKEY: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::CTRL.plus(Modifiers::SHIFT), Key::D);
+
+/// Parses shortcuts like `Ctrl+Shift+D`.
+pub fn parse_hotkey(text: &str) -> anyhow::Result<KeyboardShortcut> {
+    let mut modifiers = Modifiers::NONE;
+    let mut parts: Vec<&str> = text.split('+').collect();
+    let key = parts.pop().unwrap_or_default();
+    for part in parts {
+        let modifier = match part.to_lowercase().as_str() {
+            "ctrl" => Modifiers::CTRL,
+            "shift" => Modifiers::SHIFT,
+            "alt" => Modifiers::ALT,
+            "cmd" | "command" => Modifiers::COMMAND,
+            other => anyhow::bail!("unknown modifier {other}"),
+        };
+        modifiers = modifiers | modifier;
+    }
+    let key = Key::from_name(key).ok_or_else(|| anyhow::anyhow!("unknown key {key}"))?;
+    Ok(KeyboardShortcut::new(modifiers, key))
+}
+
+impl DebugWindow {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// `ctx` is the overlay's, `app_ctx` the one inspected.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        app_ctx: &egui::Context,
+        repaint_flash: &mut RepaintFlash,
+    ) {
+        if !self.open {
+            return;
+        }
+        let tab_id = Id::new("debug_window_tab");
+        let mut tab: Tab = ctx.data(|d| d.get_temp(tab_id)).unwrap_or_default();
+        egui::Window::new("Debug")
+            .id(Id::new("debug_window"))
+            .open(&mut self.open)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut tab, Tab::Settings, "Settings");
+                    ui.selectable_value(&mut tab, Tab::Inspection, "Inspection");
+                    ui.selectable_value(&mut tab, Tab::Memory, "Memory");
+                    ui.selectable_value(&mut tab, Tab::Repaint, "Repaint");
+                });
+                ui.separator();
+                match tab {
+                    Tab::Settings => app_ctx.settings_ui(ui),
+                    Tab::Inspection => app_ctx.inspection_ui(ui),
+                    Tab::Memory => app_ctx.memory_ui(ui),
+                    Tab::Repaint => repaint_ui(ui, app_ctx, repaint_flash),
+                }
+            });
+        ctx.data_mut(|d| d.insert_temp(tab_id, tab));
+    }
+}
+
+fn repaint_ui(ui: &mut egui::Ui, ctx: &egui::Context, repaint_flash: &mut RepaintFlash) {
+    ui.checkbox(&mut repaint_flash.enabled, "Flash on repaint");
+    ui.label(format!("Pass {}", ctx.cumulative_pass_nr()));
+    let causes = ctx.repaint_causes();
+    if causes.is_empty() {
+        ui.label("Nothing requested a repaint this frame.");
+    }
+    for cause in causes {
+        ui.monospace(cause.to_string());
+    }
+}
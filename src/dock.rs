@@ -0,0 +1,408 @@
+//! The dock icon's badge and menu on macOS, e.g. an unread count and "New
+//! Window". Both can be set from any frame and [`Dock`] applies them after
+//! it; a selection from the menu arrives as
+//! [`crate::event::UserEvent::DockMenu`] for [`take_selections`]. Elsewhere
+//! they do nothing, so app code needs no `cfg`.
+
+use crate::event::EventSender;
+#[cfg(target_os = "macos")]
+use crate::event::UserEvent;
+
+fn state_id() -> egui::Id {
+    egui::Id::new("dock_state")
+}
+
+fn selections_id() -> egui::Id {
+    egui::Id::new("dock_selections")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DockMenuItem {
+    /// What [`take_selections`] returns for it.
+    pub id: String,
+    pub title: String,
+    pub enabled: bool,
+}
+
+impl DockMenuItem {
+    pub fn new(id: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            enabled: true,
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+/// What the app asked for, applied by [`Dock::update`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct DockState {
+    badge: Option<String>,
+    menu: Vec<DockMenuItem>,
+    clear_badge_on_focus: bool,
+}
+
+/// Shows `badge` on the dock icon, e.g. `Some("3")`, or removes it.
+pub fn set_dock_badge(ctx: &egui::Context, badge: Option<&str>) {
+    let changed = ctx.data_mut(|d| {
+        let state = d.get_temp_mut_or_default::<DockState>(state_id());
+        let changed = state.badge.as_deref() != badge;
+        state.badge = badge.map(str::to_owned);
+        changed
+    });
+    if changed {
+        ctx.request_repaint();
+    }
+}
+
+/// Removes the badge whenever the window gains focus, for badges that count
+/// what the user has not seen yet.
+pub fn clear_dock_badge_on_focus(ctx: &egui::Context, clear: bool) {
+    ctx.data_mut(|d| {
+        d.get_temp_mut_or_default::<DockState>(state_id())
+            .clear_badge_on_focus = clear;
+    });
+}
+
+/// Replaces the items above the system's own in the dock icon's menu. Can
+/// be called every frame, e.g. to disable an item while it does not apply.
+pub fn set_dock_menu(ctx: &egui::Context, items: Vec<DockMenuItem>) {
+    let changed = ctx.data_mut(|d| {
+        let state = d.get_temp_mut_or_default::<DockState>(state_id());
+        let changed = state.menu != items;
+        state.menu = items;
+        changed
+    });
+    if changed {
+        ctx.request_repaint();
+    }
+}
+
+/// The [`DockMenuItem::id`]s the user selected since the last call, oldest
+/// first.
+pub fn take_selections(ctx: &egui::Context) -> Vec<String> {
+    ctx.data_mut(|d| d.remove_temp(selections_id()))
+        .unwrap_or_default()
+}
+
+/// Stores a selection from [`crate::event::UserEvent::DockMenu`] for
+/// [`take_selections`].
+pub fn push_selection(ctx: &egui::Context, id: String) {
+    ctx.data_mut(|d| {
+        d.get_temp_mut_or_default::<Vec<String>>(selections_id())
+            .push(id)
+    });
+    ctx.request_repaint();
+}
+
+/// Keeps the dock in step with what the app asked for.
+#[derive(Default)]
+pub struct Dock {
+    applied: DockState,
+    focused: bool,
+}
+
+impl Dock {
+    /// Call after each pass, on the event loop's thread.
+    pub fn update(&mut self, ctx: &egui::Context, sender: &EventSender) {
+        let (badge, menu) = self.changes(ctx);
+        if let Some(badge) = badge {
+            platform::set_badge(badge.as_deref());
+        }
+        if let Some(menu) = menu {
+            platform::set_menu(&menu, sender.clone());
+        }
+    }
+
+    /// The badge and the menu, where they differ from the applied ones.
+    #[allow(clippy::type_complexity)]
+    fn changes(
+        &mut self,
+        ctx: &egui::Context,
+    ) -> (Option<Option<String>>, Option<Vec<DockMenuItem>>) {
+        let focused = ctx.input(|i| i.focused);
+        let gained_focus = focused && !self.focused;
+        self.focused = focused;
+        let wanted = ctx.data_mut(|d| {
+            let state = d.get_temp_mut_or_default::<DockState>(state_id());
+            if gained_focus && state.clear_badge_on_focus {
+                state.badge = None;
+            }
+            state.clone()
+        });
+        let badge = (wanted.badge != self.applied.badge).then(|| wanted.badge.clone());
+        let menu = (wanted.menu != self.applied.menu).then(|| wanted.menu.clone());
+        self.applied = wanted;
+        (badge, menu)
+    }
+}
+
+/// Through the Objective-C runtime: the badge is `NSDockTile`'s label, and
+/// the menu comes from `applicationDockMenu:`, which is added to winit's
+/// application delegate.
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::{
+        ffi::{c_char, c_void, CStr, CString},
+        sync::{Mutex, Once},
+    };
+
+    use super::{DockMenuItem, EventSender, UserEvent};
+
+    type Id = *mut c_void;
+    type Sel = *const c_void;
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn object_getClass(object: Id) -> Id;
+        fn class_addMethod(class: Id, name: Sel, imp: *const c_void, types: *const c_char) -> u8;
+        fn objc_allocateClassPair(superclass: Id, name: *const c_char, extra_bytes: usize) -> Id;
+        fn objc_registerClassPair(class: Id);
+        fn objc_msgSend();
+    }
+
+    fn sel(name: &CStr) -> Sel {
+        // SAFETY: `name` is a valid C string; selectors live forever.
+        unsafe { sel_registerName(name.as_ptr()) }
+    }
+
+    /// `objc_msgSend` has to be called through the method's own signature.
+    macro_rules! send {
+        ($receiver:expr, $sel:literal $(, $arg:expr => $ty:ty)* ; $ret:ty) => {{
+            let send: unsafe extern "C" fn(Id, Sel $(, $ty)*) -> $ret =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            send($receiver, sel($sel) $(, $arg)*)
+        }};
+    }
+
+    /// A retained `NSString`.
+    struct NsString(Id);
+
+    impl NsString {
+        fn new(text: &str) -> Self {
+            let text = CString::new(text.replace('\0', "")).unwrap_or_default();
+            // SAFETY: `NSString` exists with AppKit, and `text` is a valid C
+            // string for the duration of the call.
+            Self(unsafe {
+                let string: Id = send!(objc_getClass(c"NSString".as_ptr()), c"alloc"; Id);
+                send!(string, c"initWithUTF8String:", text.as_ptr() => *const c_char; Id)
+            })
+        }
+    }
+
+    impl Drop for NsString {
+        fn drop(&mut self) {
+            // SAFETY: Created retained by `new` and released once.
+            unsafe { send!(self.0, c"release"; ()) };
+        }
+    }
+
+    /// The menu `applicationDockMenu:` returns, and whom to tell about a
+    /// selection.
+    struct Menu {
+        menu: Id,
+        ids: Vec<String>,
+        sender: EventSender,
+    }
+
+    // SAFETY: `menu` is only messaged on the main thread.
+    unsafe impl Send for Menu {}
+
+    static MENU: Mutex<Option<Menu>> = Mutex::new(None);
+    /// The items' target, an instance of a class registered for it.
+    static TARGET: Mutex<usize> = Mutex::new(0);
+    static REGISTER: Once = Once::new();
+
+    fn shared_application() -> Id {
+        // SAFETY: `NSApplication` exists with AppKit; winit created the
+        // shared instance.
+        unsafe { send!(objc_getClass(c"NSApplication".as_ptr()), c"sharedApplication"; Id) }
+    }
+
+    pub fn set_badge(badge: Option<&str>) {
+        let label = badge.map(NsString::new);
+        // SAFETY: On the main thread, with a nil or valid label, which the
+        // tile copies.
+        unsafe {
+            let tile: Id = send!(shared_application(), c"dockTile"; Id);
+            let label = label.as_ref().map_or(std::ptr::null_mut(), |label| label.0);
+            send!(tile, c"setBadgeLabel:", label => Id; ());
+        }
+    }
+
+    extern "C" fn dock_menu(_this: Id, _cmd: Sel, _sender: Id) -> Id {
+        let menu = MENU.lock().ok();
+        menu.as_ref()
+            .and_then(|menu| menu.as_ref())
+            .map_or(std::ptr::null_mut(), |menu| menu.menu)
+    }
+
+    extern "C" fn select_item(_this: Id, _cmd: Sel, item: Id) {
+        // SAFETY: `item` is the `NSMenuItem` that was selected.
+        let tag: isize = unsafe { send!(item, c"tag"; isize) };
+        let Ok(menu) = MENU.lock() else {
+            return;
+        };
+        if let Some(menu) = &*menu {
+            if let Some(id) = usize::try_from(tag).ok().and_then(|tag| menu.ids.get(tag)) {
+                menu.sender.send(UserEvent::DockMenu(id.clone()));
+            }
+        }
+    }
+
+    /// Registers the items' target class and adds `applicationDockMenu:` to
+    /// winit's delegate, which has none of its own.
+    fn register() {
+        // SAFETY: The classes exist with AppKit, and both functions match
+        // the type encodings they are added with.
+        unsafe {
+            let class = objc_allocateClassPair(
+                objc_getClass(c"NSObject".as_ptr()),
+                c"EguiWgpuDockMenuTarget".as_ptr(),
+                0,
+            );
+            if class.is_null() {
+                log::warn!("cannot register the dock menu's target class");
+                return;
+            }
+            class_addMethod(
+                class,
+                sel(c"selectDockMenuItem:"),
+                select_item as *const c_void,
+                c"v@:@".as_ptr(),
+            );
+            objc_registerClassPair(class);
+            let target: Id = send!(class, c"new"; Id);
+            *TARGET.lock().unwrap_or_else(|err| err.into_inner()) = target as usize;
+
+            let delegate: Id = send!(shared_application(), c"delegate"; Id);
+            if delegate.is_null() {
+                log::warn!("no application delegate to add the dock menu to");
+                return;
+            }
+            let added = class_addMethod(
+                object_getClass(delegate),
+                sel(c"applicationDockMenu:"),
+                dock_menu as *const c_void,
+                c"@@:@".as_ptr(),
+            );
+            if added == 0 {
+                log::warn!("the application delegate has a dock menu of its own");
+            }
+        }
+    }
+
+    pub fn set_menu(items: &[DockMenuItem], sender: EventSender) {
+        REGISTER.call_once(register);
+        let target = *TARGET.lock().unwrap_or_else(|err| err.into_inner()) as Id;
+        let empty = NsString::new("");
+        // SAFETY: On the main thread; every object is created retained and
+        // released once the menu holds it.
+        let menu = unsafe {
+            let menu: Id = send!(objc_getClass(c"NSMenu".as_ptr()), c"alloc"; Id);
+            let menu: Id = send!(menu, c"initWithTitle:", empty.0 => Id; Id);
+            send!(menu, c"setAutoenablesItems:", 0 => i8; ());
+            for (tag, item) in items.iter().enumerate() {
+                let title = NsString::new(&item.title);
+                let menu_item: Id = send!(objc_getClass(c"NSMenuItem".as_ptr()), c"alloc"; Id);
+                let menu_item: Id = send!(
+                    menu_item,
+                    c"initWithTitle:action:keyEquivalent:",
+                    title.0 => Id,
+                    sel(c"selectDockMenuItem:") => Sel,
+                    empty.0 => Id;
+                    Id
+                );
+                send!(menu_item, c"setTarget:", target => Id; ());
+                send!(menu_item, c"setTag:", tag as isize => isize; ());
+                send!(menu_item, c"setEnabled:", i8::from(item.enabled) => i8; ());
+                send!(menu, c"addItem:", menu_item => Id; ());
+                send!(menu_item, c"release"; ());
+            }
+            menu
+        };
+        let menu = Menu {
+            menu,
+            ids: items.iter().map(|item| item.id.clone()).collect(),
+            sender,
+        };
+        let previous = MENU
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .replace(menu);
+        if let Some(previous) = previous {
+            // SAFETY: Created retained above and released once; AppKit
+            // retains a menu while it shows it.
+            unsafe { send!(previous.menu, c"release"; ()) };
+        }
+    }
+}
+
+/// Windows' taskbar and Linux docks have no counterpart that works across
+/// them, so apps call the same functions and nothing shows.
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    use super::{DockMenuItem, EventSender};
+
+    pub fn set_badge(_badge: Option<&str>) {}
+
+    pub fn set_menu(_items: &[DockMenuItem], _sender: EventSender) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn focus(ctx: &egui::Context, focused: bool) {
+        let input = egui::RawInput {
+            focused,
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |_| {});
+    }
+
+    #[test]
+    fn changes_are_applied_once_and_focus_clears_the_badge() {
+        let ctx = egui::Context::default();
+        let mut dock = Dock::default();
+        assert_eq!(dock.changes(&ctx), (None, None));
+
+        set_dock_badge(&ctx, Some("3"));
+        let menu = vec![
+            DockMenuItem::new("new_window", "New Window"),
+            DockMenuItem::new("pause", "Pause").enabled(false),
+        ];
+        set_dock_menu(&ctx, menu.clone());
+        assert_eq!(
+            dock.changes(&ctx),
+            (Some(Some("3".to_owned())), Some(menu.clone()))
+        );
+        set_dock_menu(&ctx, menu);
+        assert_eq!(dock.changes(&ctx), (None, None));
+
+        // Only with the option, and only when focus is gained.
+        focus(&ctx, true);
+        assert_eq!(dock.changes(&ctx), (None, None));
+        clear_dock_badge_on_focus(&ctx, true);
+        assert_eq!(dock.changes(&ctx), (None, None));
+        focus(&ctx, false);
+        assert_eq!(dock.changes(&ctx), (None, None));
+        focus(&ctx, true);
+        assert_eq!(dock.changes(&ctx), (Some(None), None));
+
+        push_selection(&ctx, "pause".to_owned());
+        push_selection(&ctx, "new_window".to_owned());
+        assert_eq!(take_selections(&ctx), ["pause", "new_window"]);
+        assert!(take_selections(&ctx).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use std::path::PathBuf;
+
+use egui_winit::winit::window::Window;
+
+fn state_id() -> egui::Id {
+    egui::Id::new("document_state")
+}
+
+/// What the window shows about the document being edited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentState {
+    /// `None` for a document that was never saved.
+    pub path: Option<PathBuf>,
+    /// Has unsaved changes.
+    pub edited: bool,
+}
+
+/// The default for [`crate::options::Options::title_pattern`].
+pub const DEFAULT_TITLE_PATTERN: &str = "{name}{edited:*} — {app}";
+
+/// Shows the document in the title, and on macOS in the close button's dot
+/// and the titlebar's proxy icon. Applied after the current frame, and only
+/// when something changed, so apps can call it every frame. An edited
+/// document makes closing ask first, see [`default_close_decision`].
+pub fn set_document_state(ctx: &egui::Context, path: Option<PathBuf>, edited: bool) {
+    ctx.data_mut(|d| d.insert_temp(state_id(), DocumentState { path, edited }));
+}
+
+/// Whether the document has unsaved changes.
+pub fn is_edited(ctx: &egui::Context) -> bool {
+    ctx.data(|d| d.get_temp::<DocumentState>(state_id()))
+        .is_some_and(|state| state.edited)
+}
+
+/// What [`crate::user_app::UserApp::on_close_requested`] gets as its default: ask
+/// first while the document has unsaved changes.
+pub fn default_close_decision(ctx: &egui::Context) -> crate::close::CloseDecision {
+    if is_edited(ctx) {
+        crate::close::CloseDecision::ShowDialogFirst
+    } else {
+        crate::close::CloseDecision::Close
+    }
+}
+
+/// Fills in `{name}` (the file name, or "Untitled"), `{app}`, and
+/// `{edited:TEXT}`, which is TEXT while the document is edited and empty
+/// otherwise. Anything else in braces stays as it is.
+pub fn format_title(pattern: &str, state: &DocumentState, app: &str) -> String {
+    let name = state
+        .path
+        .as_deref()
+        .and_then(|path| path.file_name())
+        .map_or_else(|| "Untitled".into(), |name| name.to_string_lossy());
+    let mut title = String::with_capacity(pattern.len() + name.len() + app.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        title.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            rest = &rest[start..];
+            break;
+        };
+        match &rest[start + 1..end] {
+            "name" => title.push_str(&name),
+            "app" => title.push_str(app),
+            field => match field.strip_prefix("edited:") {
+                Some(text) if state.edited => title.push_str(text),
+                Some(_) => {}
+                None => title.push_str(&rest[start..=end]),
+            },
+        }
+        rest = &rest[end + 1..];
+    }
+    title.push_str(rest);
+    title
+}
+
+/// Keeps the window's title and macOS document state in sync with
+/// [`set_document_state`].
+pub struct DocumentTitle {
+    pattern: String,
+    /// What was last applied to the window, `None` if nothing was applied to
+    /// the current window yet.
+    applied: Option<DocumentState>,
+}
+
+impl DocumentTitle {
+    pub fn new(pattern: String) -> Self {
+        Self {
+            pattern,
+            applied: None,
+        }
+    }
+
+    /// The window handle may have changed, e.g. after a resume.
+    pub fn reapply(&mut self) {
+        self.applied = None;
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, window: &Window) {
+        // The window keeps its initial title until the app sets a state.
+        let Some(state) = ctx.data(|d| d.get_temp::<DocumentState>(state_id())) else {
+            return;
+        };
+        if self.applied.as_ref() == Some(&state) {
+            return;
+        }
+        window.set_title(&format_title(&self.pattern, &state, env!("CARGO_PKG_NAME")));
+        #[cfg(target_os = "macos")]
+        {
+            use egui_winit::winit::platform::macos::WindowExtMacOS;
+
+            window.set_document_edited(state.edited);
+            let path = state
+                .path
+                .as_deref()
+                .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.to_owned()));
+            if let Err(err) = macos::set_represented_filename(window, path.as_deref()) {
+                log::warn!("{err}");
+            }
+        }
+        self.applied = Some(state);
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{
+        ffi::{c_char, c_void, CString},
+        path::Path,
+    };
+
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+
+    type Id = *mut c_void;
+    type Sel = *const c_void;
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    /// Shows the file's proxy icon in the titlebar, or removes it for
+    /// `None`.
+    pub fn set_represented_filename(window: &Window, path: Option<&Path>) -> anyhow::Result<()> {
+        let RawWindowHandle::AppKit(handle) = window.window_handle()?.as_raw() else {
+            anyhow::bail!("the window has no AppKit handle");
+        };
+        let path = CString::new(
+            path.map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        )?;
+        // SAFETY: the view belongs to a live window, the selectors exist on
+        // NSView, NSString and NSWindow with these signatures, and winit
+        // runs this on the main thread.
+        unsafe {
+            let send_id: unsafe extern "C" fn(Id, Sel) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_str: unsafe extern "C" fn(Id, Sel, *const c_char) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_set: unsafe extern "C" fn(Id, Sel, Id) =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+            let ns_window = send_id(
+                handle.ns_view.as_ptr(),
+                sel_registerName(c"window".as_ptr()),
+            );
+            anyhow::ensure!(!ns_window.is_null(), "the view has no window");
+            let filename = send_str(
+                objc_getClass(c"NSString".as_ptr()),
+                sel_registerName(c"stringWithUTF8String:".as_ptr()),
+                path.as_ptr(),
+            );
+            send_set(
+                ns_window,
+                sel_registerName(c"setRepresentedFilename:".as_ptr()),
+                filename,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::close::CloseDecision;
+
+    fn state(path: Option<&str>, edited: bool) -> DocumentState {
+        DocumentState {
+            path: path.map(PathBuf::from),
+            edited,
+        }
+    }
+
+    #[test]
+    fn default_pattern_shows_name_and_edited_marker() {
+        let title = |state| format_title(DEFAULT_TITLE_PATTERN, &state, "Notes");
+        assert_eq!(
+            title(state(Some("/home/a/todo.md"), false)),
+            "todo.md — Notes"
+        );
+        assert_eq!(
+            title(state(Some("/home/a/todo.md"), true)),
+            "todo.md* — Notes"
+        );
+        assert_eq!(title(state(None, true)), "Untitled* — Notes");
+    }
+
+    #[test]
+    fn custom_patterns() {
+        let edited = state(Some("docs/plan.txt"), true);
+        let saved = state(Some("plan.txt"), false);
+        let pattern = "{app}: {name}{edited: (unsaved)}";
+        assert_eq!(format_title(pattern, &saved, "Ed"), "Ed: plan.txt");
+        assert_eq!(
+            format_title(pattern, &edited, "Ed"),
+            "Ed: plan.txt (unsaved)"
+        );
+        assert_eq!(
+            format_title("{edited:● }{name}", &state(None, true), ""),
+            "● Untitled"
+        );
+        assert_eq!(format_title("no fields", &saved, "Ed"), "no fields");
+        assert_eq!(format_title("", &saved, "Ed"), "");
+    }
+
+    #[test]
+    fn unknown_and_unclosed_fields_stay() {
+        let saved = state(Some("a.txt"), false);
+        assert_eq!(
+            format_title("{name} {size} {}", &saved, "App"),
+            "a.txt {size} {}"
+        );
+        assert_eq!(format_title("{app} {name", &saved, "App"), "App {name");
+        assert_eq!(format_title("}{name}{", &saved, "App"), "}a.txt{");
+        // Names keep braces of their own.
+        assert_eq!(
+            format_title("{name}", &state(Some("{app}.txt"), false), "App"),
+            "{app}.txt"
+        );
+    }
+
+    #[test]
+    fn edited_documents_ask_before_closing() {
+        let ctx = egui::Context::default();
+        assert_eq!(default_close_decision(&ctx), CloseDecision::Close);
+        set_document_state(&ctx, Some("a.txt".into()), true);
+        assert!(is_edited(&ctx));
+        assert_eq!(default_close_decision(&ctx), CloseDecision::ShowDialogFirst);
+        set_document_state(&ctx, Some("a.txt".into()), false);
+        assert_eq!(default_close_decision(&ctx), CloseDecision::Close);
+    }
+}
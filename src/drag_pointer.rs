@@ -0,0 +1,172 @@
+use egui_winit::winit::{
+    dpi::PhysicalPosition,
+    event::DeviceId,
+    window::{CursorGrabMode, Window},
+};
+
+fn request_id() -> egui::Id {
+    egui::Id::new("drag_pointer_request")
+}
+
+/// How the cursor behaves while a flagged drag goes on, so that the drag
+/// does not stop at the edge of the screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DragPointerMode {
+    /// Keeps the cursor in the window and follows the raw mouse deltas.
+    Confine,
+    /// Warps the cursor to the opposite edge when it reaches one. Where the
+    /// cursor cannot be warped, e.g. on Wayland, this confines it instead.
+    Wrap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Request {
+    mode: DragPointerMode,
+    restore_position: bool,
+}
+
+/// Call when a drag starts on a widget that should not stop at the screen
+/// edge, e.g. on [`egui::Response::drag_started`]. Lasts until the drag
+/// ends or the window loses focus. With `restore_position` the cursor
+/// returns to where the drag started.
+pub fn request(ctx: &egui::Context, mode: DragPointerMode, restore_position: bool) {
+    ctx.data_mut(|d| {
+        d.insert_temp(
+            request_id(),
+            Some(Request {
+                mode,
+                restore_position,
+            }),
+        )
+    });
+}
+
+struct Active {
+    /// Set when the position follows the raw mouse deltas rather than the
+    /// cursor.
+    raw_deltas: bool,
+    start: PhysicalPosition<f64>,
+    restore_position: bool,
+    /// Where egui thinks the pointer is; leaves the window freely.
+    position: PhysicalPosition<f64>,
+}
+
+/// Feeds egui a pointer position that keeps moving while the real cursor
+/// is confined or wrapped.
+#[derive(Default)]
+pub struct DragPointer {
+    device_id: Option<DeviceId>,
+    cursor: PhysicalPosition<f64>,
+    active: Option<Active>,
+}
+
+impl DragPointer {
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Starts a requested mode and stops it once the drag ended. Call after
+    /// each pass; returns what [`Self::stop`] returns.
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        window: &Window,
+    ) -> Option<(DeviceId, PhysicalPosition<f64>)> {
+        let request = ctx
+            .data_mut(|d| d.remove_temp::<Option<Request>>(request_id()))
+            .flatten();
+        if let Some(request) = request {
+            if self.active.is_none() && ctx.dragged_id().is_some() {
+                self.start(window, request);
+            }
+        }
+        if self.active.is_some() && ctx.dragged_id().is_none() {
+            return self.stop(window);
+        }
+        None
+    }
+
+    fn start(&mut self, window: &Window, request: Request) {
+        let raw_deltas = match request.mode {
+            DragPointerMode::Confine => true,
+            DragPointerMode::Wrap => window.set_cursor_position(self.cursor).is_err(),
+        };
+        if raw_deltas
+            && window
+                .set_cursor_grab(CursorGrabMode::Confined)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+                .is_err()
+        {
+            log::warn!("cannot confine the cursor, drags stop at the screen edge");
+        }
+        self.active = Some(Active {
+            raw_deltas,
+            start: self.cursor,
+            restore_position: request.restore_position,
+            position: self.cursor,
+        });
+    }
+
+    /// Releases the cursor. Returns the position to report to egui when the
+    /// cursor moved back to where the drag started.
+    pub fn stop(&mut self, window: &Window) -> Option<(DeviceId, PhysicalPosition<f64>)> {
+        let active = self.active.take()?;
+        if active.raw_deltas {
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+        }
+        if !active.restore_position || window.set_cursor_position(active.start).is_err() {
+            return None;
+        }
+        self.cursor = active.start;
+        Some((self.device_id?, active.start))
+    }
+
+    /// Returns the position egui should see for a cursor move, if any.
+    pub fn on_cursor_moved(
+        &mut self,
+        window: &Window,
+        device_id: DeviceId,
+        position: PhysicalPosition<f64>,
+    ) -> Option<PhysicalPosition<f64>> {
+        self.device_id = Some(device_id);
+        let previous = std::mem::replace(&mut self.cursor, position);
+        let Some(active) = &mut self.active else {
+            return Some(position);
+        };
+        if active.raw_deltas {
+            return None;
+        }
+        active.position.x += position.x - previous.x;
+        active.position.y += position.y - previous.y;
+
+        let size = window.inner_size();
+        let wrap = |value: f64, max: u32| {
+            let max = f64::from(max.saturating_sub(1));
+            if value <= 0. {
+                max - 1.
+            } else if value >= max {
+                1.
+            } else {
+                value
+            }
+        };
+        let wrapped =
+            PhysicalPosition::new(wrap(position.x, size.width), wrap(position.y, size.height));
+        if wrapped != position && window.set_cursor_position(wrapped).is_ok() {
+            // The warp's own move then has no delta.
+            self.cursor = wrapped;
+        }
+        Some(active.position)
+    }
+
+    /// Returns the position egui should see for raw mouse motion, if any.
+    pub fn on_mouse_motion(
+        &mut self,
+        delta: (f64, f64),
+    ) -> Option<(DeviceId, PhysicalPosition<f64>)> {
+        let active = self.active.as_mut().filter(|active| active.raw_deltas)?;
+        active.position.x += delta.0;
+        active.position.y += delta.1;
+        Some((self.device_id?, active.position))
+    }
+}
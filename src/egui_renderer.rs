@@ -1,49 +1,280 @@
-use std::{sync::Arc, time::Instant};
+use std::hash::{Hash, Hasher};
 
 use egui::{
-    ahash::HashSet, epaint::textures, ClippedPrimitive, FullOutput, ViewportId, ViewportIdMap,
-    ViewportInfo, ViewportOutput,
+    ahash::HashSet,
+    epaint::{textures, Primitive},
+    ClippedPrimitive, FullOutput, ViewportId, ViewportIdMap, ViewportInfo, ViewportOutput,
 };
 use egui_wgpu::{Renderer, ScreenDescriptor};
-use egui_winit::{winit::window::Window, ActionRequested, EventResponse};
-use wgpu::{CommandEncoder, Device, Queue, StoreOp, SurfaceConfiguration, TextureView};
+use egui_winit::{
+    winit::{
+        dpi::PhysicalPosition,
+        event::{ElementState, MouseButton, WindowEvent},
+    },
+    ActionRequested, EventResponse,
+};
+use web_time::{Duration, Instant};
+use wgpu::{
+    CommandEncoder, Device, Queue, RenderPassTimestampWrites, StoreOp, SurfaceConfiguration,
+    TextureView,
+};
+
+#[cfg(feature = "remote-control")]
+use crate::remote_control::RemoteControl;
+use crate::{
+    background_drag::BackgroundDrag,
+    benchmark,
+    clipboard::{self, Clipboard, ClipboardContent},
+    close::{CloseRequest, ForceClose},
+    command_palette::{Command, CommandPalette},
+    crash_report,
+    debug_window::DebugWindow,
+    drag_pointer::DragPointer,
+    error::AppError,
+    event::EventResult,
+    event_trace,
+    frame_stats::{FrameMetrics, FrameStats, FrameTimeline},
+    gpu_memory::{self, GpuMemory},
+    host_window::HostWindow,
+    input_middleware::{self, InputMiddleware},
+    input_replay::{InputRecorder, InputReplay},
+    latency::LatencyTracker,
+    letterbox::DesignResolution,
+    log_console::{self, LogConsoleState},
+    magnifier,
+    open_url::{self, UrlDecision},
+    options::EguiOptions,
+    overlay::{self, ErrorOverlay},
+    overlay_context::{OverlayContext, OverlayOutput},
+    pointer::{take_full_pointer_path_request, PointerCoalescer},
+    render_stats::RenderStats,
+    repaint_flash::RepaintFlash,
+    tessellation_cache::TessellationCache,
+    texture_registry::{TextureRegistry, TextureSort},
+    user_app::UserApp,
+    window_shadow::WindowShadow,
+};
+
+const WARM_UP_SIZE: f32 = 1024.;
+const WARM_UP_TEXT: &str =
+    "The quick brown fox jumps over the lazy dog. THE QUICK BROWN FOX JUMPS OVER THE LAZY DOG! 0123456789 +-*/=<>()[]{}#$%&@^_|~'\"`,.:;?";
+
+/// How long the window may stay hidden waiting for its first present.
+const SHOW_WINDOW_TIMEOUT: Duration = Duration::from_secs(1);
+
+const FRAME_OVERLAY_COMMAND: &str = "debug.frame_overlay";
+const RENDER_STATS_COMMAND: &str = "debug.render_stats";
+const TEXTURES_COMMAND: &str = "debug.textures";
+const LOG_CONSOLE_COMMAND: &str = "debug.log_console";
+const DEBUG_WINDOW_COMMAND: &str = "debug.window";
+const MAGNIFIER_COMMAND: &str = "accessibility.magnifier";
+const MAGNIFIER_ZOOM_IN_COMMAND: &str = "accessibility.magnifier_zoom_in";
+const MAGNIFIER_ZOOM_OUT_COMMAND: &str = "accessibility.magnifier_zoom_out";
+
+fn builtin_command(
+    id: &'static str,
+    title: &str,
+    shortcut: Option<egui::KeyboardShortcut>,
+) -> Command {
+    Command {
+        id,
+        title: title.to_owned(),
+        category: "Debug".to_owned(),
+        shortcut,
+    }
+}
 
-use crate::{event::EventResult, AppState};
+fn builtin_commands() -> CommandPalette {
+    let key = |key| Some(egui::KeyboardShortcut::new(egui::Modifiers::NONE, key));
+    let mut palette = CommandPalette::default();
+    palette.register(builtin_command(
+        FRAME_OVERLAY_COMMAND,
+        "Toggle frame overlay",
+        key(egui::Key::F3),
+    ));
+    palette.register(builtin_command(
+        RENDER_STATS_COMMAND,
+        "Toggle render stats",
+        key(egui::Key::F4),
+    ));
+    palette.register(builtin_command(
+        TEXTURES_COMMAND,
+        "Toggle textures",
+        key(egui::Key::F5),
+    ));
+    palette.register(builtin_command(
+        LOG_CONSOLE_COMMAND,
+        "Toggle log console",
+        key(egui::Key::F6),
+    ));
+    let magnifier_key = |key| {
+        Some(egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND.plus(egui::Modifiers::ALT),
+            key,
+        ))
+    };
+    for (id, title, key) in [
+        (MAGNIFIER_COMMAND, "Toggle magnifier", egui::Key::M),
+        (
+            MAGNIFIER_ZOOM_IN_COMMAND,
+            "Magnifier zoom in",
+            egui::Key::Equals,
+        ),
+        (
+            MAGNIFIER_ZOOM_OUT_COMMAND,
+            "Magnifier zoom out",
+            egui::Key::Minus,
+        ),
+    ] {
+        palette.register(Command {
+            category: "Accessibility".to_owned(),
+            ..builtin_command(id, title, magnifier_key(key))
+        });
+    }
+    palette
+}
+
+/// Where [`EguiRenderer`] paints a frame.
+pub struct WgpuTarget<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    pub encoder: &'a mut CommandEncoder,
+    pub view: &'a TextureView,
+    pub tessellation_generation: Option<u64>,
+    pub timestamp_writes: Option<RenderPassTimestampWrites<'a>>,
+}
+
+pub struct PreparedFrame {
+    pub clipped_primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: textures::TexturesDelta,
+    pub viewport_output: ViewportIdMap<ViewportOutput>,
+    pub unchanged: bool,
+    pub tessellation_generation: Option<u64>,
+    /// egui's own, fractional pixels per point, including the zoom factor.
+    pub pixels_per_point: f32,
+}
 
 pub struct EguiRenderer {
-    pub beginning: Instant,
     pub egui_winit: egui_winit::State,
-    renderer: egui_wgpu::Renderer,
+    renderer: Option<egui_wgpu::Renderer>,
     pub info: ViewportInfo,
     deferred_commands: Vec<egui::viewport::ViewportCommand>,
     actions_requested: HashSet<ActionRequested>,
     pending_full_output: egui::FullOutput,
     pub close: bool,
+    pub force_close: ForceClose,
+    /// Leaves every close request unanswered, e.g. in kiosk mode. Forced
+    /// closes still go through.
+    pub ignore_close_requests: bool,
+    /// Set when the close skipped the app's confirmation.
+    pub forced_close: bool,
     is_first_frame: bool,
+    /// The window is created hidden and shown after its first present.
+    window_shown: bool,
+    /// When the first frame finished, for showing the window even if no
+    /// frame makes it to the screen.
+    first_frame_finished: Option<Instant>,
+    /// Clears to egui's panel fill instead of transparent, so that an opaque
+    /// window never shows black or white where egui paints nothing.
+    pub opaque: bool,
+    last_primitives_hash: Option<u64>,
+    pub frame_stats: FrameStats,
+    pub frame_metrics: FrameMetrics,
+    /// Window events since the last frame, for the next `FrameMetrics`.
+    events_since_frame: u32,
+    pub timeline: FrameTimeline,
+    pub latency: Option<LatencyTracker>,
+    pub pointer_coalescer: Option<PointerCoalescer>,
+    wants_full_pointer_path: bool,
+    drag_pointer: DragPointer,
+    background_drag: BackgroundDrag,
+    pub clipboard: Clipboard,
+    show_frame_overlay: bool,
+    pub render_stats: RenderStats,
+    show_render_stats: bool,
+    pub stress_ui: bool,
+    pub gpu_memory: GpuMemory,
+    pub textures: TextureRegistry,
+    show_textures: bool,
+    texture_sort: TextureSort,
+    show_log_console: bool,
+    log_console: LogConsoleState,
+    debug_window: Option<DebugWindow>,
+    error_overlay: Option<ErrorOverlay>,
+    /// Draws the debug window, the error overlay and the frame overlay.
+    overlay: OverlayContext,
+    pub command_palette: CommandPalette,
+    pub repaint_flash: RepaintFlash,
+    pub tessellation_cache: Option<TessellationCache>,
+    pub render_scale: f32,
+    /// Below 1 while the surface is smaller than the window because of the
+    /// device's texture limit.
+    pub surface_scale: f32,
+    uploaded_generation: Option<u64>,
+    pub input_recorder: Option<InputRecorder>,
+    pub input_replay: Option<InputReplay>,
+    #[cfg(feature = "remote-control")]
+    pub remote_control: Option<RemoteControl>,
+    input_middleware: Vec<Box<dyn InputMiddleware>>,
+    /// From [`UserApp::input_middleware`], after the others.
+    app_input_middleware: Vec<Box<dyn InputMiddleware>>,
+    pub window_shadow: Option<WindowShadow>,
+    pub design_resolution: Option<DesignResolution>,
+    /// Logs each frame's input for the event trace.
+    pub trace_input: bool,
+    pub redact_trace: bool,
+    /// The app's output of the last frame, for `testing::TestApp`.
+    #[cfg(any(test, feature = "testing"))]
+    pub last_output: FullOutput,
 }
 
 impl EguiRenderer {
     pub fn new(
         device: &Device,
         egui_ctx: egui::Context,
-        window: Arc<Window>,
+        window: &dyn HostWindow,
         surface_config: &SurfaceConfiguration,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self, AppError> {
+        let renderer = Renderer::new(device, surface_config.format, None, 1, true);
+        Ok(Self::with_renderer(
+            egui_ctx,
+            window,
+            device.limits().max_texture_dimension_2d,
+            Some(renderer),
+        ))
+    }
+
+    /// For frames painted by an [`crate::painter::EguiPainter`] instead of
+    /// the built-in wgpu renderer.
+    pub fn for_painter(
+        egui_ctx: egui::Context,
+        window: &dyn HostWindow,
+        max_texture_side: usize,
+    ) -> Self {
+        let max_texture_side = max_texture_side.try_into().unwrap_or(u32::MAX);
+        Self::with_renderer(egui_ctx, window, max_texture_side, None)
+    }
+
+    fn with_renderer(
+        egui_ctx: egui::Context,
+        window: &dyn HostWindow,
+        max_texture_side: u32,
+        renderer: Option<Renderer>,
+    ) -> Self {
         let egui_winit = egui_winit::State::new(
             egui_ctx,
             egui::viewport::ViewportId::ROOT,
             &window,
             Some(window.scale_factor() as f32),
             None,
-            Some(2 * 1024),
+            Some(max_texture_side.min(2 * 1024) as usize),
         );
-        let renderer = Renderer::new(device, surface_config.format, None, 1, true);
 
         let mut info = ViewportInfo::default();
-        egui_winit::update_viewport_info(&mut info, egui_winit.egui_ctx(), &window, true);
+        window.update_viewport_info(&mut info, egui_winit.egui_ctx(), true);
+        let overlay = OverlayContext::new(egui_winit.egui_ctx());
 
-        Ok(Self {
-            beginning: Instant::now(),
+        Self {
             egui_winit,
             renderer,
             info,
@@ -51,99 +282,537 @@ impl EguiRenderer {
             pending_full_output: Default::default(),
             actions_requested: Default::default(),
             close: false,
+            ignore_close_requests: false,
+            force_close: ForceClose::new(3, Duration::from_secs(2)),
+            forced_close: false,
             is_first_frame: true,
-        })
+            window_shown: false,
+            first_frame_finished: None,
+            opaque: false,
+            last_primitives_hash: None,
+            frame_stats: Default::default(),
+            frame_metrics: Default::default(),
+            events_since_frame: 0,
+            timeline: FrameTimeline::new(Instant::now()),
+            latency: None,
+            pointer_coalescer: None,
+            wants_full_pointer_path: false,
+            drag_pointer: DragPointer::default(),
+            background_drag: BackgroundDrag::default(),
+            clipboard: Clipboard::default(),
+            show_frame_overlay: false,
+            render_stats: Default::default(),
+            show_render_stats: false,
+            stress_ui: false,
+            gpu_memory: Default::default(),
+            textures: Default::default(),
+            show_textures: false,
+            texture_sort: TextureSort::Size,
+            show_log_console: false,
+            log_console: Default::default(),
+            debug_window: None,
+            error_overlay: None,
+            overlay,
+            command_palette: builtin_commands(),
+            repaint_flash: Default::default(),
+            tessellation_cache: None,
+            render_scale: 1.,
+            surface_scale: 1.,
+            uploaded_generation: None,
+            input_recorder: None,
+            input_replay: None,
+            #[cfg(feature = "remote-control")]
+            remote_control: None,
+            input_middleware: Vec::new(),
+            app_input_middleware: Vec::new(),
+            window_shadow: None,
+            design_resolution: None,
+            trace_input: false,
+            redact_trace: false,
+            #[cfg(any(test, feature = "testing"))]
+            last_output: Default::default(),
+        }
     }
 
-    pub fn create_egui_context() -> egui::Context {
-        let egui_ctx = egui::Context::default();
-        egui_ctx.set_embed_viewports(false);
-        egui_ctx.options_mut(|o| {
-            o.max_passes = 2.try_into().unwrap();
+    pub fn warm_up(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        format: wgpu::TextureFormat,
+        pixels_per_point: f32,
+    ) {
+        profiling::scope!("warm_up");
+
+        let started = Instant::now();
+        let egui_ctx = self.egui_winit.egui_ctx().clone();
+
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::Vec2::splat(WARM_UP_SIZE),
+            )),
+            ..Default::default()
+        };
+        raw_input.viewports.insert(
+            ViewportId::ROOT,
+            ViewportInfo {
+                native_pixels_per_point: Some(pixels_per_point),
+                ..Default::default()
+            },
+        );
+
+        let full_output = egui_ctx.run(raw_input, |egui_ctx| {
+            egui::Area::new(egui::Id::new("warm_up")).show(egui_ctx, |ui| {
+                for text_style in [
+                    egui::TextStyle::Small,
+                    egui::TextStyle::Body,
+                    egui::TextStyle::Button,
+                    egui::TextStyle::Heading,
+                    egui::TextStyle::Monospace,
+                ] {
+                    ui.label(egui::RichText::new(WARM_UP_TEXT).text_style(text_style));
+                }
+            });
+        });
+        let clipped_primitives =
+            egui_ctx.tessellate(full_output.shapes, full_output.pixels_per_point);
+
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("egui warm-up target"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("egui warm-up encoder"),
         });
+        self.paint_and_update_textures(
+            device,
+            queue,
+            &mut encoder,
+            &target_view,
+            ScreenDescriptor {
+                size_in_pixels: [1, 1],
+                pixels_per_point,
+            },
+            clipped_primitives,
+            full_output.textures_delta,
+            None,
+            None,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        log::info!("warm-up took {:?}", started.elapsed());
+    }
+
+    pub fn create_egui_context(options: &EguiOptions) -> egui::Context {
+        let egui_ctx = egui::Context::default();
+        options.apply(&egui_ctx);
         egui_ctx
     }
 
-    fn update(&mut self, mut raw_input: egui::RawInput, app: &mut AppState) -> FullOutput {
-        raw_input.time = Some(self.beginning.elapsed().as_secs_f64());
+    fn update(&mut self, raw_input: egui::RawInput, app: &mut impl UserApp) -> FullOutput {
+        profiling::scope!("update");
 
-        let close_requested = raw_input.viewport().close_requested();
+        let close_request = CloseRequest::ask(
+            self.egui_winit.egui_ctx(),
+            &raw_input,
+            self.ignore_close_requests,
+            &mut self.force_close,
+            app,
+        );
 
-        let full_output = self.egui_winit.egui_ctx().run(raw_input, |egui_ctx| {
-            app.update(egui_ctx);
+        let Self {
+            egui_winit,
+            frame_metrics,
+            show_frame_overlay,
+            render_stats,
+            show_render_stats,
+            stress_ui,
+            gpu_memory,
+            textures,
+            show_textures,
+            texture_sort,
+            show_log_console,
+            log_console,
+            debug_window,
+            command_palette,
+            repaint_flash,
+            window_shadow,
+            ..
+        } = self;
+        let full_output = egui_winit.egui_ctx().run(raw_input, |egui_ctx| {
+            if let Some(window_shadow) = window_shadow {
+                window_shadow.show(egui_ctx);
+            }
+            for command in command_palette.update(egui_ctx, app) {
+                match command {
+                    FRAME_OVERLAY_COMMAND => *show_frame_overlay = !*show_frame_overlay,
+                    RENDER_STATS_COMMAND => *show_render_stats = !*show_render_stats,
+                    TEXTURES_COMMAND => *show_textures = !*show_textures,
+                    LOG_CONSOLE_COMMAND => *show_log_console = !*show_log_console,
+                    MAGNIFIER_COMMAND => magnifier::toggle(egui_ctx),
+                    MAGNIFIER_ZOOM_IN_COMMAND => magnifier::zoom_by(egui_ctx, 1),
+                    MAGNIFIER_ZOOM_OUT_COMMAND => magnifier::zoom_by(egui_ctx, -1),
+                    DEBUG_WINDOW_COMMAND => {
+                        if let Some(debug_window) = debug_window {
+                            debug_window.toggle();
+                        }
+                    }
+                    command => app.on_command(egui_ctx, command),
+                }
+            }
+            if *stress_ui {
+                benchmark::stress_ui(egui_ctx);
+            } else {
+                app.on_frame_metrics(frame_metrics);
+                app.update(egui_ctx);
+            }
+            if *show_render_stats {
+                overlay::render_stats_window(egui_ctx, render_stats, gpu_memory, show_render_stats);
+            }
+            if *show_textures {
+                overlay::textures_window(egui_ctx, textures, texture_sort, show_textures);
+            }
+            if *show_log_console {
+                log_console::window(egui_ctx, log_console, show_log_console);
+            }
+            repaint_flash.show(egui_ctx);
         });
 
-        if close_requested {
-            let canceled = full_output.viewport_output[&ViewportId::ROOT]
-                .commands
-                .contains(&egui::ViewportCommand::CancelClose);
-            if !canceled {
-                self.close = true;
-            }
+        if let Some(forced) = close_request.resolve(self.egui_winit.egui_ctx(), &full_output) {
+            self.close = true;
+            self.forced_close |= forced;
         }
 
         self.pending_full_output.append(full_output);
         std::mem::take(&mut self.pending_full_output)
     }
 
-    #[allow(clippy::too_many_arguments)]
-    pub fn run_ui_and_paint(
+    /// The wrapper's own windows, on the overlay context; see
+    /// [`OverlayContext`].
+    fn run_overlay(&mut self, input: egui::RawInput, pixels_per_point: f32) -> OverlayOutput {
+        let Self {
+            egui_winit,
+            overlay,
+            debug_window,
+            error_overlay,
+            show_frame_overlay,
+            frame_stats,
+            latency,
+            repaint_flash,
+            ..
+        } = self;
+        let app_ctx = egui_winit.egui_ctx();
+        overlay.run(input, app_ctx.zoom_factor(), pixels_per_point, |ctx| {
+            if let Some(debug_window) = debug_window {
+                debug_window.show(ctx, app_ctx, repaint_flash);
+            }
+            if let Some(error_overlay) = error_overlay {
+                error_overlay.show(ctx);
+            }
+            if *show_frame_overlay {
+                overlay::frame_overlay(ctx, frame_stats, latency.as_ref());
+            }
+        })
+    }
+
+    pub fn set_frame_overlay_visible(&mut self, visible: bool) {
+        self.show_frame_overlay = visible;
+    }
+
+    pub fn set_render_stats_visible(&mut self, visible: bool) {
+        self.show_render_stats = visible;
+    }
+
+    pub fn set_textures_visible(&mut self, visible: bool) {
+        self.show_textures = visible;
+    }
+
+    pub fn set_log_console_visible(&mut self, visible: bool) {
+        self.show_log_console = visible;
+    }
+
+    /// Runs after the ones added before, on every frame's input. Recordings
+    /// hold the input from before any middleware.
+    pub fn add_input_middleware(&mut self, middleware: impl InputMiddleware + 'static) {
+        self.input_middleware.push(Box::new(middleware));
+    }
+
+    /// Replaces the app's middleware, which runs after the others.
+    pub fn set_app_input_middleware(&mut self, middleware: Vec<Box<dyn InputMiddleware>>) {
+        self.app_input_middleware = middleware;
+    }
+
+    pub fn overlay_ctx(&self) -> &egui::Context {
+        self.overlay.ctx()
+    }
+
+    pub fn set_error_overlay(&mut self, enabled: bool) {
+        self.error_overlay = enabled.then(ErrorOverlay::default);
+    }
+
+    /// Turns the debug window on, toggled by `hotkey`, or off.
+    pub fn set_debug_window(&mut self, hotkey: Option<egui::KeyboardShortcut>) {
+        self.debug_window = hotkey.map(|_| DebugWindow::default());
+        if hotkey.is_some() {
+            self.command_palette.register(builtin_command(
+                DEBUG_WINDOW_COMMAND,
+                "Toggle debug window",
+                hotkey,
+            ));
+        }
+    }
+
+    /// Lets egui show an app-owned texture, e.g. a 3D viewport, through
+    /// `egui::Image`. The texture needs `TEXTURE_BINDING` usage and must stay
+    /// alive until [`Self::free_native_texture`]. Not while a render thread
+    /// owns the wgpu renderer.
+    pub fn register_native_texture(
         &mut self,
         device: &Device,
-        queue: &Queue,
-        encoder: &mut CommandEncoder,
-        window_surface_view: &TextureView,
-        screen_descriptor: ScreenDescriptor,
-        window: &Window,
-        app: &mut AppState,
-    ) -> anyhow::Result<EventResult> {
-        let raw_input = {
-            egui_winit::update_viewport_info(
-                &mut self.info,
-                self.egui_winit.egui_ctx(),
-                window,
-                false,
-            );
+        texture: &wgpu::Texture,
+        texture_filter: wgpu::FilterMode,
+    ) -> egui::TextureId {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let id = self
+            .wgpu_renderer()
+            .register_native_texture(device, &view, texture_filter);
+        let bytes = gpu_memory::texture_bytes(texture);
+        self.gpu_memory.record_native_texture(id, bytes);
+        self.textures.record_native(id, texture, bytes);
+        id
+    }
 
-            let mut raw_input = self.egui_winit.take_egui_input(window);
+    /// Points a registered id at another texture, e.g. after the old one was
+    /// recreated at a new size. The old texture may go once this returns.
+    pub fn update_native_texture(
+        &mut self,
+        device: &Device,
+        id: egui::TextureId,
+        texture: &wgpu::Texture,
+        texture_filter: wgpu::FilterMode,
+    ) {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.wgpu_renderer().update_egui_texture_from_wgpu_texture(
+            device,
+            &view,
+            texture_filter,
+            id,
+        );
+        let bytes = gpu_memory::texture_bytes(texture);
+        self.gpu_memory.record_native_texture(id, bytes);
+        self.textures.record_native(id, texture, bytes);
+    }
 
-            raw_input.time = Some(self.beginning.elapsed().as_secs_f64());
-            raw_input
-                .viewports
-                .insert(ViewportId::ROOT, self.info.clone());
+    pub fn free_native_texture(&mut self, id: egui::TextureId) {
+        self.wgpu_renderer().free_texture(&id);
+        self.gpu_memory.record_native_texture_freed(id);
+        self.textures.record_freed(&[id]);
+    }
+
+    pub fn run_ui(&mut self, window: &dyn HostWindow, app: &mut impl UserApp) -> PreparedFrame {
+        profiling::scope!("EguiRenderer::run_ui");
+
+        self.flush_pointer_moves(window);
+        if let Some(content) = self.clipboard.take_pasted() {
+            self.paste(app, content);
+        }
+
+        let mut raw_input = {
+            window.update_viewport_info(&mut self.info, self.egui_winit.egui_ctx(), false);
+
+            // egui-winit keeps the viewport info between frames, so it only
+            // has to be replaced when something changed.
+            let viewports = &mut self.egui_winit.egui_input_mut().viewports;
+            if viewports.get(&ViewportId::ROOT) != Some(&self.info) {
+                viewports.insert(ViewportId::ROOT, self.info.clone());
+            }
+
+            let mut raw_input = window.take_egui_input(&mut self.egui_winit);
+            let scale = self.render_scale * self.surface_scale;
+            if scale != 1. {
+                // Scales egui's pixels per point, so that text is rasterized
+                // for the supersampled or clamped target. Sizes in points,
+                // and with them input coordinates, stay the same.
+                if let Some(native_pixels_per_point) = raw_input
+                    .viewports
+                    .get_mut(&ViewportId::ROOT)
+                    .and_then(|info| info.native_pixels_per_point.as_mut())
+                {
+                    *native_pixels_per_point *= scale;
+                }
+            }
+            // In window points, like egui-winit's events.
+            #[cfg(feature = "remote-control")]
+            if let Some(remote_control) = &mut self.remote_control {
+                remote_control.apply_input(self.egui_winit.egui_ctx(), &mut raw_input);
+            }
+            if let Some(design_resolution) = &mut self.design_resolution {
+                design_resolution.apply(self.egui_winit.egui_ctx(), window, &mut raw_input);
+            }
+            if let Some(window_shadow) = &mut self.window_shadow {
+                window_shadow.inset(&mut raw_input);
+            }
+            if let Some(input_replay) = &mut self.input_replay {
+                input_replay.apply(&mut raw_input);
+            }
+            if let Some(input_recorder) = &mut self.input_recorder {
+                input_recorder.record(&raw_input);
+            }
+            input_middleware::run(
+                self.input_middleware
+                    .iter_mut()
+                    .chain(&mut self.app_input_middleware),
+                self.egui_winit.egui_ctx(),
+                &mut raw_input,
+            );
+            if self.trace_input {
+                event_trace::log_input(&raw_input, self.redact_trace);
+            }
             raw_input
         };
+        let overlay_input = self.overlay.route_input(&mut raw_input);
+        self.timeline.input = Instant::now();
 
         let full_output = self.update(raw_input, app);
+        #[cfg(any(test, feature = "testing"))]
+        {
+            self.last_output = full_output.clone();
+        }
+        let overlay_output = self.run_overlay(overlay_input, full_output.pixels_per_point);
+        self.timeline.update = Instant::now();
+        crash_report::record_repaint_causes(self.egui_winit.egui_ctx());
+        if let Some((device_id, position)) = window
+            .native()
+            .and_then(|native| self.drag_pointer.update(self.egui_winit.egui_ctx(), native))
+        {
+            self.move_pointer(window, device_id, position);
+        }
+        self.background_drag.update(self.egui_winit.egui_ctx());
+        if let (Some(window_shadow), Some(native)) = (&mut self.window_shadow, window.native()) {
+            window_shadow.update(self.egui_winit.egui_ctx(), native);
+        }
+        if let Some(delay) = self.input_replay.as_ref().and_then(InputReplay::next_delay) {
+            self.egui_winit.egui_ctx().request_repaint_after(delay);
+        }
+
+        if let Some(pointer_coalescer) = &self.pointer_coalescer {
+            let egui_ctx = self.egui_winit.egui_ctx();
+            self.wants_full_pointer_path =
+                egui_ctx.dragged_id().is_some() || take_full_pointer_path_request(egui_ctx);
+            self.frame_stats.pointer_moves_coalesced = pointer_coalescer.coalesced;
+        }
 
         let FullOutput {
-            platform_output,
+            mut platform_output,
             shapes,
             pixels_per_point,
             viewport_output,
-            textures_delta,
+            mut textures_delta,
         } = full_output;
+        self.overlay
+            .merge_platform_output(&mut platform_output, overlay_output.platform_output);
 
         self.info.events.clear();
+        #[cfg(feature = "remote-control")]
+        if let Some(remote_control) = &mut self.remote_control {
+            remote_control.observe_output(&platform_output);
+        }
 
-        self.egui_winit
-            .handle_platform_output(window, platform_output);
+        // egui-winit would open the URL right away.
+        if let Some(url) = platform_output.open_url.take() {
+            match app.on_open_url(&url) {
+                UrlDecision::Allow => platform_output.open_url = Some(url),
+                UrlDecision::Deny => log::info!("denied opening {}", url.url),
+                UrlDecision::Ask => open_url::set_pending(self.egui_winit.egui_ctx(), url),
+            }
+        }
+        if let Some(url) = open_url::take_approved(self.egui_winit.egui_ctx()) {
+            platform_output.open_url = Some(url);
+        }
+        if let Some(design_resolution) = &self.design_resolution {
+            design_resolution.ime_to_window(
+                self.egui_winit.egui_ctx(),
+                window,
+                &mut platform_output,
+            );
+        }
+        window.handle_platform_output(&mut self.egui_winit, platform_output);
+        self.clipboard.update(self.egui_winit.egui_ctx());
 
-        let clipped_primitives = self
-            .egui_winit
-            .egui_ctx()
-            .tessellate(shapes, pixels_per_point);
+        let egui_ctx = self.egui_winit.egui_ctx();
+        let (mut clipped_primitives, mut tessellation_generation) = {
+            profiling::scope!("tessellate");
+            match &mut self.tessellation_cache {
+                Some(cache) => {
+                    let previous_generation = cache.generation();
+                    let clipped_primitives =
+                        cache.tessellate(egui_ctx, shapes, pixels_per_point, &textures_delta);
+                    self.render_stats.tessellation_cache_hits = cache.hits;
+                    self.render_stats.tessellation_cache_misses = cache.misses;
+                    let generation = cache.generation();
+                    let reused = generation.is_some() && generation == previous_generation;
+                    (clipped_primitives, generation.map(|g| (g, reused)))
+                }
+                None => (egui_ctx.tessellate(shapes, pixels_per_point), None),
+            }
+        };
+        if !overlay_output.clipped_primitives.is_empty() {
+            clipped_primitives.extend(overlay_output.clipped_primitives);
+            // The cache's generation only covers the app's primitives.
+            tessellation_generation = None;
+        }
+        textures_delta.append(overlay_output.textures_delta);
+        self.timeline.tessellate = Instant::now();
 
-        self.paint_and_update_textures(
-            device,
-            queue,
-            encoder,
-            window_surface_view,
-            screen_descriptor,
+        let metrics = &mut self.frame_metrics;
+        metrics.events = std::mem::take(&mut self.events_since_frame);
+        metrics.clipped_primitives = clipped_primitives.len();
+        metrics.textures_set = textures_delta.set.len();
+        metrics.textures_freed = textures_delta.free.len();
+
+        let primitives_hash = match tessellation_generation {
+            Some((_, true)) => self.last_primitives_hash,
+            _ => primitives_hash(&clipped_primitives, pixels_per_point),
+        };
+        let unchanged = !self.is_first_frame
+            && textures_delta.is_empty()
+            && primitives_hash.is_some()
+            && primitives_hash == self.last_primitives_hash;
+        self.last_primitives_hash = primitives_hash;
+
+        PreparedFrame {
             clipped_primitives,
             textures_delta,
-        );
+            viewport_output,
+            unchanged,
+            tessellation_generation: tessellation_generation.map(|(generation, _)| generation),
+            pixels_per_point,
+        }
+    }
 
+    pub fn invalidate_tessellation_cache(&mut self) {
+        if let Some(cache) = &mut self.tessellation_cache {
+            cache.invalidate();
+        }
+    }
+
+    pub fn finish_frame(
+        &mut self,
+        window: &dyn HostWindow,
+        mut viewport_output: ViewportIdMap<ViewportOutput>,
+    ) -> EventResult {
         for action in self.actions_requested.drain() {
             match action {
                 ActionRequested::Cut => {
@@ -158,6 +827,9 @@ impl EguiRenderer {
                         .events
                         .push(egui::Event::Copy);
                 }
+                ActionRequested::Paste if self.clipboard.rich_paste => {
+                    self.clipboard.request_paste(self.egui_winit.egui_ctx());
+                }
                 ActionRequested::Paste => {
                     if let Some(contents) = self.egui_winit.clipboard_text() {
                         let contents = contents.replace("\r\n", "\n");
@@ -173,21 +845,78 @@ impl EguiRenderer {
             }
         }
 
-        if std::mem::take(&mut self.is_first_frame) {
+        self.is_first_frame = false;
+        let first_frame_finished = *self.first_frame_finished.get_or_insert_with(Instant::now);
+        if !self.window_shown && first_frame_finished.elapsed() >= SHOW_WINDOW_TIMEOUT {
+            log::warn!(
+                "no frame presented after {SHOW_WINDOW_TIMEOUT:?}, showing the window anyway"
+            );
+            self.window_shown = true;
             window.set_visible(true);
         }
 
-        self.handle_viewport_output(&viewport_output, window);
+        self.handle_viewport_output(&mut viewport_output, window);
 
         if window.is_minimized() == Some(true) {
             std::thread::sleep(std::time::Duration::from_millis(10));
         }
 
         if self.close {
-            Ok(EventResult::Exit)
+            EventResult::Exit
         } else {
-            Ok(EventResult::Wait)
+            EventResult::Wait
+        }
+    }
+
+    /// Shows the hidden window once a frame is on screen, so that it never
+    /// flashes its background first. Call after each present. Returns `true`
+    /// the first time: some X11 compositors drop presents to unmapped
+    /// windows, so the frame has to be presented again. On Wayland the first
+    /// present is what maps the window.
+    pub fn on_frame_presented(&mut self, window: &dyn HostWindow) -> bool {
+        if self.window_shown {
+            return false;
         }
+        self.window_shown = true;
+        window.set_visible(true);
+        true
+    }
+
+    /// What egui's render pass clears to: see [`Self::opaque`].
+    pub fn clear_color(&self) -> wgpu::Color {
+        if !self.opaque {
+            return wgpu::Color::TRANSPARENT;
+        }
+        let fill = egui::Rgba::from(self.egui_winit.egui_ctx().style().visuals.panel_fill);
+        wgpu::Color {
+            r: fill.r().into(),
+            g: fill.g().into(),
+            b: fill.b().into(),
+            a: 1.,
+        }
+    }
+
+    /// Applies egui's texture changes and draws the tessellated primitives
+    /// into `target`.
+    pub fn paint_frame(
+        &mut self,
+        target: WgpuTarget<'_>,
+        screen_descriptor: ScreenDescriptor,
+        clipped_primitives: Vec<ClippedPrimitive>,
+        textures_delta: textures::TexturesDelta,
+    ) {
+        self.paint_and_update_textures(
+            target.device,
+            target.queue,
+            target.encoder,
+            target.view,
+            screen_descriptor,
+            clipped_primitives,
+            textures_delta,
+            target.tessellation_generation,
+            target.timestamp_writes,
+        );
+        self.timeline.encode = Instant::now();
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -200,70 +929,90 @@ impl EguiRenderer {
         screen_descriptor: ScreenDescriptor,
         clipped_primitives: Vec<ClippedPrimitive>,
         textures_delta: textures::TexturesDelta,
+        tessellation_generation: Option<u64>,
+        timestamp_writes: Option<RenderPassTimestampWrites>,
     ) {
-        self.egui_winit
-            .egui_ctx()
-            .set_pixels_per_point(screen_descriptor.pixels_per_point);
+        profiling::scope!("paint_and_update_textures");
 
-        for (id, image_delta) in &textures_delta.set {
-            self.renderer
-                .update_texture(device, queue, *id, image_delta);
-        }
-        self.renderer.update_buffers(
+        self.record_frame(&clipped_primitives, &textures_delta);
+        let upload_buffers = needs_upload(&mut self.uploaded_generation, tessellation_generation);
+        let clear_color = self.clear_color();
+        paint_egui(
+            self.wgpu_renderer(),
             device,
             queue,
             encoder,
-            &clipped_primitives,
+            window_surface_view,
+            clear_color,
             &screen_descriptor,
-        );
-        let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: window_surface_view,
-                resolve_target: None,
-                ops: egui_wgpu::wgpu::Operations {
-                    load: egui_wgpu::wgpu::LoadOp::Load,
-                    store: StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            label: Some("egui main render pass"),
-            occlusion_query_set: None,
-        });
-
-        self.renderer.render(
-            &mut rpass.forget_lifetime(),
             &clipped_primitives,
-            &screen_descriptor,
+            &textures_delta,
+            upload_buffers,
+            timestamp_writes,
         );
-        for x in &textures_delta.free {
-            self.renderer.free_texture(x)
-        }
+    }
+
+    /// Applies a frame's texture changes without painting it, so that frames
+    /// which fail before painting neither lose uploads nor leak freed textures.
+    pub fn discard_frame(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        textures_delta: textures::TexturesDelta,
+    ) {
+        self.record_textures(&textures_delta);
+        let renderer = self.wgpu_renderer();
+        update_textures(renderer, device, queue, &textures_delta);
+        free_textures(renderer, &textures_delta);
+    }
+
+    /// Bookkeeping for a frame that is painted here or on the render thread.
+    pub fn record_frame(
+        &mut self,
+        clipped_primitives: &[ClippedPrimitive],
+        textures_delta: &textures::TexturesDelta,
+    ) {
+        self.render_stats.record_primitives(clipped_primitives);
+        self.record_textures(textures_delta);
+    }
+
+    fn record_textures(&mut self, textures_delta: &textures::TexturesDelta) {
+        self.render_stats.record_textures_set(textures_delta);
+        self.textures
+            .record_set(textures_delta, self.egui_winit.egui_ctx());
+        self.render_stats.record_textures_freed(textures_delta);
+        self.textures.record_freed(&textures_delta.free);
+    }
+
+    fn wgpu_renderer(&mut self) -> &mut Renderer {
+        self.renderer
+            .as_mut()
+            .expect("the egui renderer is owned by the render thread")
+    }
+
+    /// Hands the wgpu renderer over to a render thread.
+    pub fn take_wgpu_renderer(&mut self) -> Renderer {
+        self.renderer
+            .take()
+            .expect("the egui renderer was already handed over")
+    }
+
+    pub fn restore_wgpu_renderer(&mut self, renderer: Renderer) {
+        self.renderer = Some(renderer);
+        self.uploaded_generation = None;
     }
 
     fn handle_viewport_output(
         &mut self,
-        viewport_output: &ViewportIdMap<ViewportOutput>,
-        window: &Window,
+        viewport_output: &mut ViewportIdMap<ViewportOutput>,
+        window: &dyn HostWindow,
     ) {
-        for (
-            _,
-            ViewportOutput {
-                parent: _,
-                class: _,
-                builder: _,
-                viewport_ui_cb: _,
-                mut commands,
-                repaint_delay: _,
-            },
-        ) in viewport_output.clone()
-        {
-            self.deferred_commands.append(&mut commands);
-            egui_winit::process_viewport_commands(
+        for output in viewport_output.values_mut() {
+            self.deferred_commands.append(&mut output.commands);
+            window.process_viewport_commands(
                 self.egui_winit.egui_ctx(),
                 &mut self.info,
                 std::mem::take(&mut self.deferred_commands),
-                window,
                 &mut self.actions_requested,
             );
         }
@@ -271,9 +1020,249 @@ impl EguiRenderer {
 
     pub(crate) fn on_window_event(
         &mut self,
-        window: &Window,
-        event: &egui_winit::winit::event::WindowEvent,
+        window: &dyn HostWindow,
+        event: &WindowEvent,
     ) -> EventResponse {
-        self.egui_winit.on_window_event(window, event)
+        self.events_since_frame += 1;
+        match event {
+            WindowEvent::CursorMoved {
+                device_id,
+                position,
+            } => {
+                // Without a native window the cursor cannot be grabbed, so
+                // no drag is ever active.
+                if let Some(native) = window.native() {
+                    let moved = self
+                        .drag_pointer
+                        .on_cursor_moved(native, *device_id, *position);
+                    if self.drag_pointer.is_active() {
+                        if let Some(position) = moved {
+                            self.move_pointer(window, *device_id, position);
+                        }
+                        return EventResponse {
+                            consumed: false,
+                            repaint: true,
+                        };
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if window.native().is_some_and(|native| {
+                self.background_drag
+                    .on_press(self.egui_winit.egui_ctx(), native)
+            }) =>
+            {
+                return EventResponse {
+                    consumed: true,
+                    repaint: false,
+                };
+            }
+            WindowEvent::KeyboardInput { event, .. }
+                if self.clipboard.rich_paste
+                    && clipboard::is_paste_command(
+                        self.egui_winit.egui_input().modifiers,
+                        event,
+                    ) =>
+            {
+                // egui-winit would read the clipboard right here.
+                self.clipboard.request_paste(self.egui_winit.egui_ctx());
+                return EventResponse {
+                    consumed: true,
+                    repaint: false,
+                };
+            }
+            WindowEvent::Focused(false) => {
+                if let Some((device_id, position)) = window
+                    .native()
+                    .and_then(|native| self.drag_pointer.stop(native))
+                {
+                    self.move_pointer(window, device_id, position);
+                }
+            }
+            _ => {}
+        }
+        if let Some(pointer_coalescer) = &mut self.pointer_coalescer {
+            if matches!(event, WindowEvent::CursorMoved { .. }) && !self.wants_full_pointer_path {
+                pointer_coalescer.defer(event);
+                return EventResponse {
+                    consumed: false,
+                    repaint: true,
+                };
+            }
+            self.flush_pointer_moves(window);
+        }
+        window.on_window_event(&mut self.egui_winit, event)
+    }
+
+    /// Hands a finished clipboard read to the app, and its text to egui
+    /// unless the app took it.
+    fn paste(&mut self, app: &mut impl UserApp, content: ClipboardContent) {
+        if app.on_paste(&content) {
+            return;
+        }
+        // Files go where egui-winit puts dropped ones, so that the same code
+        // handles both.
+        if !content.files.is_empty() {
+            self.egui_winit
+                .egui_input_mut()
+                .dropped_files
+                .extend(content.files.into_iter().map(|path| egui::DroppedFile {
+                    path: Some(path),
+                    ..Default::default()
+                }));
+            return;
+        }
+        // egui-winit's clipboard may work where the worker's could not open.
+        let Some(text) = content.text.or_else(|| self.egui_winit.clipboard_text()) else {
+            return;
+        };
+        let text = text.replace("\r\n", "\n");
+        if !text.is_empty() {
+            self.egui_winit
+                .egui_input_mut()
+                .events
+                .push(egui::Event::Paste(text));
+        }
+    }
+
+    pub(crate) fn on_mouse_motion(&mut self, window: &dyn HostWindow, delta: (f64, f64)) {
+        self.egui_winit.on_mouse_motion(delta);
+        if let Some((device_id, position)) = self.drag_pointer.on_mouse_motion(delta) {
+            self.move_pointer(window, device_id, position);
+        }
+    }
+
+    /// Tells egui the pointer is at `position`, wherever the cursor is.
+    fn move_pointer(
+        &mut self,
+        window: &dyn HostWindow,
+        device_id: egui_winit::winit::event::DeviceId,
+        position: PhysicalPosition<f64>,
+    ) {
+        self.flush_pointer_moves(window);
+        let _ = window.on_window_event(
+            &mut self.egui_winit,
+            &WindowEvent::CursorMoved {
+                device_id,
+                position,
+            },
+        );
+    }
+
+    fn flush_pointer_moves(&mut self, window: &dyn HostWindow) {
+        if let Some(event) = self
+            .pointer_coalescer
+            .as_mut()
+            .and_then(PointerCoalescer::take)
+        {
+            let _ = window.on_window_event(&mut self.egui_winit, &event);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn paint_egui(
+    renderer: &mut Renderer,
+    device: &Device,
+    queue: &Queue,
+    encoder: &mut CommandEncoder,
+    window_surface_view: &TextureView,
+    clear_color: wgpu::Color,
+    screen_descriptor: &ScreenDescriptor,
+    clipped_primitives: &[ClippedPrimitive],
+    textures_delta: &textures::TexturesDelta,
+    upload_buffers: bool,
+    timestamp_writes: Option<RenderPassTimestampWrites>,
+) {
+    update_textures(renderer, device, queue, textures_delta);
+    if upload_buffers {
+        renderer.update_buffers(
+            device,
+            queue,
+            encoder,
+            clipped_primitives,
+            screen_descriptor,
+        );
+    }
+    let rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: window_surface_view,
+            resolve_target: None,
+            ops: egui_wgpu::wgpu::Operations {
+                load: egui_wgpu::wgpu::LoadOp::Clear(clear_color),
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes,
+        label: Some("egui main render pass"),
+        occlusion_query_set: None,
+    });
+
+    renderer.render(
+        &mut rpass.forget_lifetime(),
+        clipped_primitives,
+        screen_descriptor,
+    );
+    free_textures(renderer, textures_delta);
+}
+
+/// Whether vertex and index data has to be uploaded, i.e. the renderer's
+/// buffers do not already hold this tessellation.
+pub fn needs_upload(uploaded_generation: &mut Option<u64>, generation: Option<u64>) -> bool {
+    let previous = std::mem::replace(uploaded_generation, generation);
+    generation.is_none() || previous != generation
+}
+
+pub fn update_textures(
+    renderer: &mut Renderer,
+    device: &Device,
+    queue: &Queue,
+    textures_delta: &textures::TexturesDelta,
+) {
+    for (id, image_delta) in &textures_delta.set {
+        renderer.update_texture(device, queue, *id, image_delta);
+    }
+}
+
+pub fn free_textures(renderer: &mut Renderer, textures_delta: &textures::TexturesDelta) {
+    for x in &textures_delta.free {
+        renderer.free_texture(x)
+    }
+}
+
+fn primitives_hash(clipped_primitives: &[ClippedPrimitive], pixels_per_point: f32) -> Option<u64> {
+    profiling::scope!("primitives_hash");
+
+    let mut hasher = egui::ahash::AHasher::default();
+    pixels_per_point.to_bits().hash(&mut hasher);
+    for ClippedPrimitive {
+        clip_rect,
+        primitive,
+    } in clipped_primitives
+    {
+        for v in [
+            clip_rect.min.x,
+            clip_rect.min.y,
+            clip_rect.max.x,
+            clip_rect.max.y,
+        ] {
+            v.to_bits().hash(&mut hasher);
+        }
+        let Primitive::Mesh(mesh) = primitive else {
+            return None;
+        };
+        mesh.texture_id.hash(&mut hasher);
+        mesh.indices.hash(&mut hasher);
+        for vertex in &mesh.vertices {
+            for v in [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y] {
+                v.to_bits().hash(&mut hasher);
+            }
+            vertex.color.hash(&mut hasher);
+        }
     }
+    Some(hasher.finish())
 }
@@ -0,0 +1,464 @@
+use std::{io::BufRead as _, thread::JoinHandle, time::Duration};
+
+use anyhow::Context as _;
+#[cfg(not(target_os = "ios"))]
+use egui_winit::winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+use egui_winit::winit::{
+    event_loop::{ActiveEventLoop, EventLoop},
+    raw_window_handle::RawWindowHandle,
+    window::Window,
+};
+
+use crate::{
+    app::App,
+    error::AppError,
+    event::{EventSender, UserEvent},
+    options::Options,
+    user_app::UserApp,
+};
+
+/// A host application's window to show the app in: an `HWND` on Windows,
+/// an X11 window id, or an `NSView` pointer on macOS. Wayland has no way to
+/// parent another client's window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParentWindow(pub usize);
+
+impl std::str::FromStr for ParentWindow {
+    type Err = anyhow::Error;
+
+    /// Decimal, or hexadecimal with `0x`, as hosts tend to print handles.
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        let handle = match text.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => text.parse(),
+        }
+        .with_context(|| format!("not a window handle: {text}"))?;
+        anyhow::ensure!(handle != 0, "the window handle is null");
+        Ok(Self(handle))
+    }
+}
+
+impl ParentWindow {
+    #[cfg(windows)]
+    pub fn raw_window_handle(
+        self,
+        _event_loop: &ActiveEventLoop,
+    ) -> anyhow::Result<RawWindowHandle> {
+        use egui_winit::winit::raw_window_handle::Win32WindowHandle;
+        let hwnd = std::num::NonZeroIsize::new(self.0 as isize).context("null HWND")?;
+        Ok(RawWindowHandle::Win32(Win32WindowHandle::new(hwnd)))
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn raw_window_handle(
+        self,
+        _event_loop: &ActiveEventLoop,
+    ) -> anyhow::Result<RawWindowHandle> {
+        use egui_winit::winit::raw_window_handle::AppKitWindowHandle;
+        let view =
+            std::ptr::NonNull::new(self.0 as *mut std::ffi::c_void).context("null NSView")?;
+        Ok(RawWindowHandle::AppKit(AppKitWindowHandle::new(view)))
+    }
+
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    pub fn raw_window_handle(
+        self,
+        event_loop: &ActiveEventLoop,
+    ) -> anyhow::Result<RawWindowHandle> {
+        use egui_winit::winit::raw_window_handle::{
+            HasDisplayHandle as _, RawDisplayHandle, XlibWindowHandle,
+        };
+        match event_loop.display_handle()?.as_raw() {
+            RawDisplayHandle::Xlib(_) | RawDisplayHandle::Xcb(_) => Ok(RawWindowHandle::Xlib(
+                XlibWindowHandle::new(self.0 as std::ffi::c_ulong),
+            )),
+            _ => anyhow::bail!("embedding needs X11; Wayland cannot parent foreign windows"),
+        }
+    }
+
+    #[cfg(not(any(
+        windows,
+        target_os = "macos",
+        all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        )
+    )))]
+    pub fn raw_window_handle(
+        self,
+        _event_loop: &ActiveEventLoop,
+    ) -> anyhow::Result<RawWindowHandle> {
+        anyhow::bail!("embedding is not supported on this platform")
+    }
+}
+
+#[derive(Debug)]
+pub enum HostRequest {
+    /// The region the host gives the app, in physical pixels.
+    Resize([u32; 2]),
+    /// Ends the app without asking it, e.g. because the host closes.
+    Close,
+}
+
+impl HostRequest {
+    /// `resize <width> <height>` or `close`, as read by [`read_requests`].
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["resize", width, height] => Ok(Self::Resize([width.parse()?, height.parse()?])),
+            ["close"] => Ok(Self::Close),
+            _ => anyhow::bail!("unknown host request: {line}"),
+        }
+    }
+}
+
+/// Lets the host resize or close the embedded app from any thread. A child
+/// window gets no resize events when the host's layout changes, so the host
+/// reports its size here instead.
+#[derive(Clone)]
+pub struct HostHandle {
+    sender: EventSender,
+}
+
+impl HostHandle {
+    pub(crate) fn new(sender: EventSender) -> Self {
+        Self { sender }
+    }
+
+    pub fn resize(&self, width: u32, height: u32) {
+        self.request(HostRequest::Resize([width, height]));
+    }
+
+    pub fn close(&self) {
+        self.request(HostRequest::Close);
+    }
+
+    fn request(&self, request: HostRequest) {
+        self.sender.send(UserEvent::Host(request));
+    }
+}
+
+/// Gives the app's child window the keyboard focus, which clicks do not do
+/// on their own.
+pub fn focus(window: &Window) {
+    #[cfg(windows)]
+    if let Some(hwnd) = crate::titlebar::hwnd(window) {
+        // SAFETY: `hwnd` belongs to a live window owned by this thread.
+        unsafe { windows_sys::Win32::UI::Input::KeyboardAndMouse::SetFocus(hwnd) };
+        return;
+    }
+    window.focus_window();
+}
+
+/// The app driven from the host's own loop, which calls [`Self::pump`]
+/// regularly. Needs no extra thread, but the host has to keep pumping.
+#[cfg(not(target_os = "ios"))]
+pub struct Embedded<T> {
+    event_loop: EventLoop<UserEvent>,
+    app: App<T>,
+}
+
+#[cfg(not(target_os = "ios"))]
+impl<T: UserApp> Embedded<T> {
+    pub fn new(options: Options, app_state: T) -> Result<Self, AppError> {
+        let event_loop = EventLoop::with_user_event().build()?;
+        let app = App::new(&event_loop, options, app_state);
+        Ok(Self { event_loop, app })
+    }
+
+    pub fn handle(&self) -> HostHandle {
+        self.app.host_handle()
+    }
+
+    /// Handles the events that arrived since the last call and paints when
+    /// due, waiting up to `timeout` for events. Returns the app's result once
+    /// it has exited.
+    pub fn pump(&mut self, timeout: Option<Duration>) -> Option<Result<(), AppError>> {
+        match self.event_loop.pump_app_events(timeout, &mut self.app) {
+            PumpStatus::Continue => None,
+            PumpStatus::Exit(_) => Some(std::mem::replace(&mut self.app.return_result, Ok(()))),
+        }
+    }
+}
+
+/// The app on a thread of its own, for hosts that cannot pump often enough.
+/// Windows and X11 only: macOS runs windows on the main thread alone.
+pub struct EmbedThread {
+    pub handle: HostHandle,
+    thread: JoinHandle<Result<(), AppError>>,
+}
+
+impl EmbedThread {
+    #[cfg(windows)]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    pub fn join(self) -> Result<(), AppError> {
+        join_thread(self.thread)
+    }
+}
+
+/// Runs the app on a new thread. `make_state` runs there too, as the state
+/// need not be `Send`.
+pub fn spawn<T: UserApp>(
+    options: Options,
+    make_state: impl FnOnce() -> T + Send + 'static,
+) -> Result<EmbedThread, AppError> {
+    let (handle_sender, handle_receiver) = std::sync::mpsc::channel();
+    let thread = std::thread::Builder::new()
+        .name("embedded-app".to_owned())
+        .spawn(move || {
+            profiling::register_thread!("embedded-app");
+            let event_loop = any_thread_event_loop()?;
+            let mut app = App::new(&event_loop, options, make_state());
+            let _ = handle_sender.send(app.host_handle());
+            event_loop.run_app(&mut app)?;
+            app.return_result
+        })?;
+    match handle_receiver.recv() {
+        Ok(handle) => Ok(EmbedThread { handle, thread }),
+        // The thread ended before it had an event loop.
+        Err(_) => Err(join_thread(thread)
+            .err()
+            .unwrap_or_else(|| AppError::User(anyhow::anyhow!("the app's thread ended early")))),
+    }
+}
+
+fn join_thread(thread: JoinHandle<Result<(), AppError>>) -> Result<(), AppError> {
+    thread
+        .join()
+        .unwrap_or_else(|_| Err(AppError::User(anyhow::anyhow!("the app's thread panicked"))))
+}
+
+fn any_thread_event_loop() -> Result<EventLoop<UserEvent>, AppError> {
+    #[allow(unused_mut)]
+    let mut builder = EventLoop::with_user_event();
+    #[cfg(windows)]
+    {
+        use egui_winit::winit::platform::windows::EventLoopBuilderExtWindows;
+        builder.with_any_thread(true);
+    }
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    {
+        use egui_winit::winit::platform::{wayland, x11};
+        x11::EventLoopBuilderExtX11::with_any_thread(&mut builder, true);
+        wayland::EventLoopBuilderExtWayland::with_any_thread(&mut builder, true);
+    }
+    if cfg!(target_vendor = "apple") {
+        return Err(AppError::NotMainThread);
+    }
+    Ok(builder.build()?)
+}
+
+/// Reads host requests from stdin, one per line, for hosts in another
+/// process. The end of stdin closes the app, as the host has gone away.
+pub fn read_requests(handle: HostHandle) -> std::io::Result<()> {
+    std::thread::Builder::new()
+        .name("host-requests".to_owned())
+        .spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match HostRequest::parse(&line) {
+                    Ok(HostRequest::Resize([width, height])) => handle.resize(width, height),
+                    Ok(HostRequest::Close) => handle.close(),
+                    Err(err) => log::warn!("{err}"),
+                }
+            }
+            handle.close();
+        })?;
+    Ok(())
+}
+
+/// A plain Win32 window hosting the app as a child, with the app on a
+/// thread of its own. The host keeps a strip at the bottom to itself.
+#[cfg(windows)]
+pub mod demo_host {
+    use std::cell::RefCell;
+
+    use windows_sys::Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM},
+        System::LibraryLoader::GetModuleHandleW,
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetClientRect,
+            LoadCursorW, MsgWaitForMultipleObjects, PeekMessageW, RegisterClassW, ShowWindow,
+            TranslateMessage, COLOR_WINDOW, CW_USEDEFAULT, IDC_ARROW, MSG, PM_REMOVE, QS_ALLINPUT,
+            SW_SHOW, WM_CLOSE, WM_SIZE, WNDCLASSW, WS_CLIPCHILDREN, WS_OVERLAPPEDWINDOW,
+        },
+    };
+
+    use super::{spawn, HostHandle, ParentWindow};
+    use crate::{error::AppError, options::Options, user_app::UserApp};
+
+    const STRIP_HEIGHT: i32 = 48;
+
+    thread_local! {
+        static HOST_HANDLE: RefCell<Option<HostHandle>> = const { RefCell::new(None) };
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    fn resize_app(width: i32, height: i32) {
+        HOST_HANDLE.with_borrow(|handle| {
+            if let Some(handle) = handle {
+                handle.resize(width.max(1) as u32, (height - STRIP_HEIGHT).max(1) as u32);
+            }
+        });
+    }
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        match message {
+            WM_SIZE => {
+                resize_app((lparam & 0xffff) as i32, ((lparam >> 16) & 0xffff) as i32);
+                0
+            }
+            // The window goes once the app has ended, see `run`.
+            WM_CLOSE => {
+                HOST_HANDLE.with_borrow(|handle| {
+                    if let Some(handle) = handle {
+                        handle.close();
+                    }
+                });
+                0
+            }
+            _ => DefWindowProcW(hwnd, message, wparam, lparam),
+        }
+    }
+
+    pub fn run<T: UserApp>(
+        options: Options,
+        make_state: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<(), AppError> {
+        let class_name = wide("EguiDemoHost");
+        let title = wide("Demo host");
+        // SAFETY: the strings are null-terminated and outlive the calls,
+        // and the window procedure matches the WNDPROC signature.
+        let hwnd = unsafe {
+            let instance = GetModuleHandleW(std::ptr::null());
+            let class = WNDCLASSW {
+                style: 0,
+                lpfnWndProc: Some(window_proc),
+                cbClsExtra: 0,
+                cbWndExtra: 0,
+                hInstance: instance,
+                hIcon: std::ptr::null_mut(),
+                hCursor: LoadCursorW(std::ptr::null_mut(), IDC_ARROW),
+                hbrBackground: (COLOR_WINDOW + 1) as usize as _,
+                lpszMenuName: std::ptr::null(),
+                lpszClassName: class_name.as_ptr(),
+            };
+            RegisterClassW(&class);
+            CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                title.as_ptr(),
+                WS_OVERLAPPEDWINDOW | WS_CLIPCHILDREN,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                900,
+                700,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                instance,
+                std::ptr::null(),
+            )
+        };
+        if hwnd.is_null() {
+            return Err(AppError::User(anyhow::anyhow!(
+                "cannot create the host window"
+            )));
+        }
+        let options = Options {
+            parent_window: Some(ParentWindow(hwnd as usize)),
+            ..options
+        };
+        let embedded = spawn(options, make_state)?;
+        HOST_HANDLE.set(Some(embedded.handle.clone()));
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        // SAFETY: `hwnd` is the live window created above, on this thread.
+        unsafe {
+            GetClientRect(hwnd, &mut rect);
+            ShowWindow(hwnd, SW_SHOW);
+        }
+        resize_app(rect.right, rect.bottom);
+        // The host's own loop, waking up now and then to notice the app's
+        // thread ending.
+        while !embedded.is_finished() {
+            // SAFETY: `msg` is written by PeekMessageW before it is read.
+            unsafe {
+                let mut msg: MSG = std::mem::zeroed();
+                if PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                    TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                } else {
+                    MsgWaitForMultipleObjects(0, std::ptr::null(), 0, 50, QS_ALLINPUT);
+                }
+            }
+        }
+        HOST_HANDLE.set(None);
+        // SAFETY: as above; the app's child window is gone with its thread.
+        unsafe {
+            DestroyWindow(hwnd);
+        }
+        embedded.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parent_window_handles_parse_as_hosts_print_them() {
+        assert_eq!("1234".parse::<ParentWindow>().unwrap(), ParentWindow(1234));
+        assert_eq!("0x4d2".parse::<ParentWindow>().unwrap(), ParentWindow(1234));
+        assert!("0".parse::<ParentWindow>().is_err());
+        assert!("0x0".parse::<ParentWindow>().is_err());
+        assert!("window".parse::<ParentWindow>().is_err());
+        assert!("-1".parse::<ParentWindow>().is_err());
+    }
+
+    #[test]
+    fn host_requests_parse_one_per_line() {
+        assert!(matches!(
+            HostRequest::parse("resize 800 600"),
+            Ok(HostRequest::Resize([800, 600]))
+        ));
+        assert!(matches!(
+            HostRequest::parse("  resize\t1 2 "),
+            Ok(HostRequest::Resize([1, 2]))
+        ));
+        assert!(matches!(
+            HostRequest::parse("close"),
+            Ok(HostRequest::Close)
+        ));
+        for line in [
+            "",
+            "resize 800",
+            "resize 800 600 1",
+            "resize -1 600",
+            "quit",
+        ] {
+            assert!(HostRequest::parse(line).is_err(), "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use egui_winit::winit;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    /// No backend yielded an adapter and a device. Holds why each one failed.
+    #[error("no usable GPU adapter found{}", attempts(.0))]
+    NoAdapter(Vec<String>),
+    #[error("failed to create a device: {0}")]
+    DeviceRequest(#[from] wgpu::RequestDeviceError),
+    #[error("failed to create a surface: {0}")]
+    SurfaceCreation(#[from] wgpu::CreateSurfaceError),
+    #[error("the surface offers no sRGB format")]
+    SurfaceFormat,
+    #[error("failed to configure the surface: {0}")]
+    SurfaceConfiguration(String),
+    #[error("failed to acquire a surface texture: {0}")]
+    Surface(#[from] wgpu::SurfaceError),
+    #[error("failed to create the window: {0}")]
+    WindowCreation(#[from] winit::error::OsError),
+    #[error("the event loop has to run on the main thread on this platform")]
+    NotMainThread,
+    #[error("event loop error: {0}")]
+    EventLoop(#[from] winit::error::EventLoopError),
+    #[error("failed to spawn a thread: {0}")]
+    Thread(#[from] std::io::Error),
+    #[error("the render thread has exited")]
+    RenderThreadExited,
+    #[error("the render thread panicked")]
+    RenderThreadPanicked,
+    /// Errors from the app's own code and from the tooling that reports
+    /// through anyhow, e.g. the benchmark and the recorders.
+    #[error(transparent)]
+    User(#[from] anyhow::Error),
+}
+
+fn attempts(errors: &[String]) -> String {
+    errors.iter().map(|error| format!("\n  {error}")).collect()
+}
+
+impl AppError {
+    /// Whether the app can carry on, usually after reconfiguring the
+    /// surface. Everything else ends the event loop.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Self::SurfaceConfiguration(_)
+                | Self::Surface(
+                    wgpu::SurfaceError::Timeout
+                        | wgpu::SurfaceError::Outdated
+                        | wgpu::SurfaceError::Lost
+                )
+        )
+    }
+
+    /// Whether the surface has to be configured again before the next frame.
+    pub fn needs_reconfigure(&self) -> bool {
+        matches!(
+            self,
+            Self::SurfaceConfiguration(_)
+                | Self::Surface(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)
+        )
+    }
+}
@@ -1,4 +1,21 @@
-use std::time::Instant;
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use egui_winit::winit::event_loop::EventLoopProxy;
+use web_time::Instant;
+
+#[cfg(feature = "dialogs")]
+use crate::file_dialog::{DialogId, DialogResult};
+#[cfg(feature = "notifications")]
+use crate::notifications::{NotificationEvent, NotificationId};
+#[cfg(feature = "remote-control")]
+use crate::remote_control::RemoteRequest;
+use crate::{embed::HostRequest, error::AppError, gpu::GpuContext};
 
 #[derive(Debug)]
 pub enum UserEvent {
@@ -6,8 +23,31 @@ pub enum UserEvent {
         when: Instant,
         cumulative_pass_nr: u64,
     },
+    GpuReady(Box<Result<GpuContext, AppError>>),
+    /// The app's state built in the background, see
+    /// [`crate::app::App::new_loading`], or why that failed: a
+    /// `Result<T, String>` for the app's [`crate::user_app::UserApp`].
+    StateReady(Box<dyn Any + Send>),
+    /// From the application embedding this one, see [`crate::embed`].
+    Host(HostRequest),
+    /// Answered right away, so that [`crate::metrics`] can tell a stalled
+    /// event loop from an idle one.
+    MetricsHeartbeat,
+    /// From a remote control client, see [`crate::remote_control`].
+    #[cfg(feature = "remote-control")]
+    Remote(RemoteRequest),
+    /// A closed file dialog's answer, see [`crate::file_dialog`].
+    #[cfg(feature = "dialogs")]
+    Dialog(DialogId, DialogResult),
+    /// What the user did with a notification, see [`crate::notifications`].
+    #[cfg(feature = "notifications")]
+    Notification(NotificationId, NotificationEvent),
+    /// The [`crate::dock::DockMenuItem::id`] the user selected.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    DockMenu(String),
 }
 
+#[derive(Debug)]
 pub enum EventResult {
     Wait,
     RepaintNow,
@@ -15,3 +55,45 @@ pub enum EventResult {
     RepaintAt(Instant),
     Exit,
 }
+
+impl EventResult {
+    /// The more urgent of both results.
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Exit, _) | (_, Self::Exit) => Self::Exit,
+            (Self::RepaintNow, _) | (_, Self::RepaintNow) => Self::RepaintNow,
+            (Self::RepaintNext, _) | (_, Self::RepaintNext) => Self::RepaintNext,
+            (Self::RepaintAt(a), Self::RepaintAt(b)) => Self::RepaintAt(a.min(b)),
+            (Self::RepaintAt(when), Self::Wait) | (Self::Wait, Self::RepaintAt(when)) => {
+                Self::RepaintAt(when)
+            }
+            (Self::Wait, Self::Wait) => Self::Wait,
+        }
+    }
+}
+
+/// Sends user events to the event loop from any thread without locking.
+/// Once the event loop has gone away, further sends are skipped.
+#[derive(Clone)]
+pub struct EventSender {
+    proxy: EventLoopProxy<UserEvent>,
+    closed: Arc<AtomicBool>,
+}
+
+impl EventSender {
+    pub fn new(proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self {
+            proxy,
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn send(&self, event: UserEvent) {
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.proxy.send_event(event).is_err() {
+            self.closed.store(true, Ordering::Relaxed);
+        }
+    }
+}
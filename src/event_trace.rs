@@ -0,0 +1,126 @@
+use std::{fmt::Debug, path::Path};
+
+use egui_winit::winit::event::{DeviceEvent, WindowEvent};
+use web_time::Instant;
+
+use crate::{error::AppError, event::EventResult, input_replay};
+
+pub const TARGET: &str = "event_trace";
+/// Prefix of the trace lines that carry egui input in the replay format.
+const INPUT_PREFIX: &str = "input ";
+
+/// Logs every event reaching the app on one line each, with the frame it
+/// arrived in and what the app made of it, plus a summary per frame. Goes
+/// through `log`, so it ends up in the log console and the log file.
+pub struct EventTrace {
+    started: Instant,
+    redact: bool,
+    frame: u64,
+    window_events: u32,
+    consumed: u32,
+    device_events: u32,
+    user_events: u32,
+}
+
+impl EventTrace {
+    pub fn new(redact: bool) -> Self {
+        Self {
+            started: Instant::now(),
+            redact,
+            frame: 0,
+            window_events: 0,
+            consumed: 0,
+            device_events: 0,
+            user_events: 0,
+        }
+    }
+
+    fn log(&self, kind: &str, event: &dyn Debug, result: &Result<EventResult, AppError>) {
+        let result: &dyn Debug = match result {
+            Ok(result) => result,
+            Err(err) => err,
+        };
+        log::info!(
+            target: TARGET,
+            "f={} t={:.3} {kind} {event:?} -> {result:?}",
+            self.frame,
+            self.started.elapsed().as_secs_f64() * 1000.,
+        );
+    }
+
+    pub fn window_event(
+        &mut self,
+        event: &WindowEvent,
+        consumed: bool,
+        result: &Result<EventResult, AppError>,
+    ) {
+        if let WindowEvent::RedrawRequested = event {
+            self.log("window", event, result);
+            log::info!(
+                target: TARGET,
+                "f={} summary: {} window events ({} consumed by egui, {} forwarded), {} device, {} user",
+                self.frame,
+                self.window_events,
+                self.consumed,
+                self.window_events - self.consumed,
+                self.device_events,
+                self.user_events,
+            );
+            self.frame += 1;
+            self.window_events = 0;
+            self.consumed = 0;
+            self.device_events = 0;
+            self.user_events = 0;
+            return;
+        }
+        self.window_events += 1;
+        self.consumed += consumed as u32;
+        match event {
+            WindowEvent::KeyboardInput { .. } if self.redact => {
+                self.log("window", &"KeyboardInput(redacted)", result)
+            }
+            WindowEvent::Ime(_) if self.redact => self.log("window", &"Ime(redacted)", result),
+            _ => self.log("window", event, result),
+        }
+    }
+
+    pub fn device_event(&mut self, event: &DeviceEvent, result: &Result<EventResult, AppError>) {
+        self.device_events += 1;
+        match event {
+            DeviceEvent::Key(_) if self.redact => self.log("device", &"Key(redacted)", result),
+            _ => self.log("device", event, result),
+        }
+    }
+
+    pub fn user_event(&mut self, event: &dyn Debug, result: &Result<EventResult, AppError>) {
+        self.user_events += 1;
+        self.log("user", event, result);
+    }
+}
+
+/// Logs the egui input of every frame in the replay format, so that a trace
+/// can be turned into a replay with [`trace_to_replay`].
+pub fn log_input(raw_input: &egui::RawInput, redact: bool) {
+    let mut frame = String::new();
+    input_replay::write_frame(&mut frame, raw_input, redact);
+    for line in frame.lines() {
+        log::info!(target: TARGET, "{INPUT_PREFIX}{line}");
+    }
+}
+
+/// Extracts the input lines of a trace, e.g. a file written with
+/// `--log-file`, into a file for `--replay-input`.
+pub fn trace_to_replay(trace: &Path, replay: &Path) -> anyhow::Result<()> {
+    let trace = std::fs::read_to_string(trace)?;
+    let marker = format!("{TARGET}: {INPUT_PREFIX}");
+    let mut out = String::new();
+    for line in trace.lines() {
+        if let Some((_, input)) = line.split_once(&marker) {
+            out.push_str(input);
+            out.push('\n');
+        }
+    }
+    anyhow::ensure!(!out.is_empty(), "the trace contains no input");
+    std::fs::write(replay, out)?;
+    Ok(())
+}
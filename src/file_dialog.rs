@@ -0,0 +1,610 @@
+//! Native open and save dialogs that do not block the UI. The app asks with
+//! [`pick_file`], [`pick_folder`] or [`save_file`], the dialog runs on a
+//! thread of its own, and the answer arrives as [`UserEvent::Dialog`] for
+//! [`take_result`] to pick up in a later frame.
+
+use std::path::PathBuf;
+
+use egui_winit::winit::window::Window;
+
+use crate::event::{EventSender, UserEvent};
+
+fn requests_id() -> egui::Id {
+    egui::Id::new("file_dialog_requests")
+}
+
+fn next_id() -> egui::Id {
+    egui::Id::new("file_dialog_next")
+}
+
+fn result_id(id: DialogId) -> egui::Id {
+    egui::Id::new(("file_dialog_result", id.0))
+}
+
+/// Tells the answers to several open dialogs apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DialogId(u64);
+
+/// Files the open dialog offers, e.g. "Images" with `["png", "jpg"]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileFilter {
+    pub name: String,
+    /// Without the dot.
+    pub extensions: Vec<String>,
+}
+
+impl FileFilter {
+    pub fn new(name: &str, extensions: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            extensions: extensions.iter().map(|&ext| ext.to_owned()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DialogKind {
+    OpenFile(Vec<FileFilter>),
+    OpenFolder,
+    SaveFile { default_name: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DialogRequest {
+    pub id: DialogId,
+    pub kind: DialogKind,
+}
+
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum DialogError {
+    #[cfg_attr(
+        any(
+            windows,
+            all(
+                unix,
+                not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+            )
+        ),
+        allow(dead_code)
+    )]
+    #[error("file dialogs are not supported on this platform")]
+    Unsupported,
+    #[error("the file dialog failed: {0}")]
+    Failed(String),
+}
+
+/// The chosen path, or `None` when the user cancelled.
+pub type DialogResult = Result<Option<PathBuf>, DialogError>;
+
+/// Asks for a file to open, offering the files that match one of the
+/// filters, or every file without filters. Opens after the current frame.
+pub fn pick_file(ctx: &egui::Context, filters: Vec<FileFilter>) -> DialogId {
+    request(ctx, DialogKind::OpenFile(filters))
+}
+
+pub fn pick_folder(ctx: &egui::Context) -> DialogId {
+    request(ctx, DialogKind::OpenFolder)
+}
+
+/// Asks where to save, suggesting `default_name`.
+pub fn save_file(ctx: &egui::Context, default_name: &str) -> DialogId {
+    request(
+        ctx,
+        DialogKind::SaveFile {
+            default_name: default_name.to_owned(),
+        },
+    )
+}
+
+/// The dialog's answer, once it has closed. Returned once.
+pub fn take_result(ctx: &egui::Context, id: DialogId) -> Option<DialogResult> {
+    ctx.data_mut(|d| d.remove_temp::<Option<DialogResult>>(result_id(id)))
+        .flatten()
+}
+
+fn request(ctx: &egui::Context, kind: DialogKind) -> DialogId {
+    let id = ctx.data_mut(|d| {
+        let next = d.get_temp_mut_or_default::<u64>(next_id());
+        *next += 1;
+        let id = DialogId(*next);
+        d.get_temp_mut_or_default::<Vec<DialogRequest>>(requests_id())
+            .push(DialogRequest { id, kind });
+        id
+    });
+    ctx.request_repaint();
+    id
+}
+
+pub fn take_requests(ctx: &egui::Context) -> Vec<DialogRequest> {
+    ctx.data_mut(|d| d.remove_temp(requests_id()))
+        .unwrap_or_default()
+}
+
+/// Stores an answer from [`UserEvent::Dialog`] for [`take_result`].
+pub fn set_result(ctx: &egui::Context, id: DialogId, result: DialogResult) {
+    ctx.data_mut(|d| d.insert_temp(result_id(id), Some(result)));
+    ctx.request_repaint();
+}
+
+/// Shows the dialog modal to `window` on a thread of its own and sends the
+/// answer through `sender`.
+pub fn open(request: DialogRequest, window: &Window, sender: EventSender) {
+    let parent = platform::Parent::of(window);
+    let id = request.id;
+    let thread_sender = sender.clone();
+    let spawned = std::thread::Builder::new()
+        .name("file-dialog".to_owned())
+        .spawn(move || {
+            let result = platform::run(&request, &parent);
+            if let Err(err) = &result {
+                log::warn!("{err}");
+            }
+            thread_sender.send(UserEvent::Dialog(request.id, result));
+        });
+    if let Err(err) = spawned {
+        sender.send(UserEvent::Dialog(
+            id,
+            Err(DialogError::Failed(format!(
+                "cannot start its thread: {err}"
+            ))),
+        ));
+    }
+}
+
+/// The filter list of Windows' common dialogs: pairs of a name and
+/// semicolon-separated patterns, each null-terminated, with an extra null at
+/// the end. Every file when there are no filters.
+#[cfg(any(windows, test))]
+fn filter_string(filters: &[FileFilter]) -> Vec<u16> {
+    let mut text = String::new();
+    for filter in filters {
+        let patterns: Vec<String> = filter
+            .extensions
+            .iter()
+            .map(|ext| format!("*.{ext}"))
+            .collect();
+        text += &format!("{}\0{}\0", filter.name, patterns.join(";"));
+    }
+    if filters.is_empty() {
+        text += "All files\0*.*\0";
+    }
+    text.encode_utf16().chain([0]).collect()
+}
+
+/// Through the XDG desktop portal, so that it works inside a Flatpak too.
+/// The portal picks the dialog, e.g. GTK's or KDE's.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod platform {
+    use std::collections::HashMap;
+
+    use egui_winit::winit::{raw_window_handle::RawWindowHandle, window::Window};
+    use zbus::{
+        blocking::{Connection, MessageIterator},
+        message::Type,
+        zvariant::{OwnedObjectPath, OwnedValue, Value},
+        MatchRule,
+    };
+
+    use super::{DialogError, DialogKind, DialogRequest, DialogResult, FileFilter};
+    use crate::{clipboard, window_handles};
+
+    /// The portal's name for the window, `x11:<xid>`. Wayland windows would
+    /// need exporting through xdg-foreign first, so their dialogs are not
+    /// modal.
+    pub struct Parent(String);
+
+    impl Parent {
+        pub fn of(window: &Window) -> Self {
+            let id = match window_handles::raw_handles(window) {
+                Some((RawWindowHandle::Xlib(handle), _)) => format!("x11:{:x}", handle.window),
+                Some((RawWindowHandle::Xcb(handle), _)) => format!("x11:{:x}", handle.window),
+                _ => String::new(),
+            };
+            Self(id)
+        }
+    }
+
+    fn failed(err: zbus::Error) -> DialogError {
+        DialogError::Failed(err.to_string())
+    }
+
+    /// `a(sa(us))`: each filter's name and its glob patterns, type 0.
+    fn portal_filters(filters: &[FileFilter]) -> Vec<(String, Vec<(u32, String)>)> {
+        filters
+            .iter()
+            .map(|filter| {
+                let patterns = filter
+                    .extensions
+                    .iter()
+                    .map(|ext| (0, format!("*.{ext}")))
+                    .collect();
+                (filter.name.clone(), patterns)
+            })
+            .collect()
+    }
+
+    pub fn run(request: &DialogRequest, parent: &Parent) -> DialogResult {
+        let connection = Connection::session().map_err(failed)?;
+        // The portal answers on a request object named after the caller
+        // and the token. Listening before the call keeps a quick answer
+        // from getting lost.
+        let token = format!("egui_dialog_{}", request.id.0);
+        let sender = connection
+            .unique_name()
+            .ok_or_else(|| DialogError::Failed("no name on the session bus".to_owned()))?
+            .trim_start_matches(':')
+            .replace('.', "_");
+        let handle = format!("/org/freedesktop/portal/desktop/request/{sender}/{token}");
+        let responses = listen(&connection, &handle)?;
+
+        let mut options: HashMap<&str, Value> = HashMap::new();
+        options.insert("handle_token", token.as_str().into());
+        options.insert("modal", true.into());
+        let (method, title) = match &request.kind {
+            DialogKind::OpenFile(filters) => {
+                if !filters.is_empty() {
+                    options.insert("filters", portal_filters(filters).into());
+                }
+                ("OpenFile", "Open")
+            }
+            DialogKind::OpenFolder => {
+                options.insert("directory", true.into());
+                ("OpenFile", "Open folder")
+            }
+            DialogKind::SaveFile { default_name } => {
+                options.insert("current_name", default_name.as_str().into());
+                ("SaveFile", "Save")
+            }
+        };
+        let reply = connection
+            .call_method(
+                Some("org.freedesktop.portal.Desktop"),
+                "/org/freedesktop/portal/desktop",
+                Some("org.freedesktop.portal.FileChooser"),
+                method,
+                &(parent.0.as_str(), title, options),
+            )
+            .map_err(failed)?;
+        let returned: OwnedObjectPath = reply.body().deserialize().map_err(failed)?;
+        // Portals older than the token choose the object themselves.
+        let mut responses = if returned.as_str() == handle {
+            responses
+        } else {
+            listen(&connection, returned.as_str())?
+        };
+
+        let message = responses
+            .next()
+            .ok_or_else(|| DialogError::Failed("the portal went away".to_owned()))?
+            .map_err(failed)?;
+        let (response, results): (u32, HashMap<String, OwnedValue>) =
+            message.body().deserialize().map_err(failed)?;
+        match response {
+            0 => {
+                let uris = results
+                    .get("uris")
+                    .and_then(|uris| Vec::<String>::try_from(uris.try_clone().ok()?).ok())
+                    .unwrap_or_default();
+                Ok(clipboard::parse_uri_list(uris.join("\n").as_bytes())
+                    .into_iter()
+                    .next())
+            }
+            1 => Ok(None),
+            _ => Err(DialogError::Failed(
+                "the portal ended the dialog".to_owned(),
+            )),
+        }
+    }
+
+    fn listen(connection: &Connection, handle: &str) -> Result<MessageIterator, DialogError> {
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface("org.freedesktop.portal.Request")
+            .and_then(|rule| rule.member("Response"))
+            .and_then(|rule| rule.path(handle))
+            .map_err(failed)?
+            .build();
+        MessageIterator::for_match_rule(rule, connection, Some(1)).map_err(failed)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use zbus::fdo::{RequestNameFlags, RequestNameReply};
+
+        use super::*;
+        use crate::file_dialog::DialogId;
+
+        #[test]
+        fn portal_filters_are_globs() {
+            assert_eq!(
+                portal_filters(&[FileFilter::new("Images", &["png", "jpg"])]),
+                [(
+                    "Images".to_owned(),
+                    vec![(0, "*.png".to_owned()), (0, "*.jpg".to_owned())]
+                )]
+            );
+        }
+
+        /// Stands in for the portal on a session bus without one, e.g. under
+        /// `dbus-run-session`. Skipped without a session bus, or with a real
+        /// portal on it.
+        #[test]
+        fn portal_answers_become_paths() {
+            let Ok(portal) = Connection::session() else {
+                return;
+            };
+            let owner = portal.request_name_with_flags(
+                "org.freedesktop.portal.Desktop",
+                RequestNameFlags::DoNotQueue.into(),
+            );
+            if !matches!(owner, Ok(RequestNameReply::PrimaryOwner)) {
+                return;
+            }
+            let calls = MessageIterator::from(&portal);
+
+            let parent = Parent("x11:2a".to_owned());
+            let dialogs = std::thread::spawn(move || {
+                let open = DialogRequest {
+                    id: DialogId(7),
+                    kind: DialogKind::OpenFile(vec![FileFilter::new("Images", &["png"])]),
+                };
+                let save = DialogRequest {
+                    id: DialogId(8),
+                    kind: DialogKind::SaveFile {
+                        default_name: "notes.txt".to_owned(),
+                    },
+                };
+                (run(&open, &parent), run(&save, &parent))
+            });
+
+            let mut answers = [(0, vec!["file:///tmp/a%20b.png"]), (1, Vec::new())].into_iter();
+            let mut methods = Vec::new();
+            for call in calls {
+                let call = call.unwrap();
+                let header = call.header();
+                if header.interface().map(|name| name.as_str())
+                    != Some("org.freedesktop.portal.FileChooser")
+                {
+                    continue;
+                }
+                let (window, _title, options): (String, String, HashMap<String, OwnedValue>) =
+                    call.body().deserialize().unwrap();
+                assert_eq!(window, "x11:2a");
+                assert_eq!(bool::try_from(&options["modal"]), Ok(true));
+                let token = String::try_from(options["handle_token"].try_clone().unwrap()).unwrap();
+                let sender = header.sender().unwrap().to_string();
+                let handle = format!(
+                    "/org/freedesktop/portal/desktop/request/{}/{token}",
+                    sender.trim_start_matches(':').replace('.', "_")
+                );
+                portal
+                    .reply(&call, &OwnedObjectPath::try_from(handle.as_str()).unwrap())
+                    .unwrap();
+                methods.push(header.member().unwrap().to_string());
+
+                let (response, uris) = answers.next().unwrap();
+                let results = HashMap::from([("uris", Value::from(uris))]);
+                portal
+                    .emit_signal(
+                        Some(sender.as_str()),
+                        handle.as_str(),
+                        "org.freedesktop.portal.Request",
+                        "Response",
+                        &(response as u32, results),
+                    )
+                    .unwrap();
+                if methods.len() == 2 {
+                    break;
+                }
+            }
+
+            let (open, save) = dialogs.join().unwrap();
+            assert_eq!(methods, ["OpenFile", "SaveFile"]);
+            assert_eq!(open.unwrap(), Some("/tmp/a b.png".into()));
+            assert_eq!(save.unwrap(), None);
+        }
+    }
+}
+
+/// The classic common dialogs, which need no COM objects.
+#[cfg(windows)]
+mod platform {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+
+    use egui_winit::winit::window::Window;
+    use windows_sys::Win32::{
+        System::Com::{CoInitializeEx, CoTaskMemFree, COINIT_APARTMENTTHREADED},
+        UI::{
+            Controls::Dialogs::{
+                CommDlgExtendedError, GetOpenFileNameW, GetSaveFileNameW, OFN_EXPLORER,
+                OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_OVERWRITEPROMPT, OFN_PATHMUSTEXIST,
+                OPENFILENAMEW,
+            },
+            Shell::{
+                SHBrowseForFolderW, SHGetPathFromIDListW, BIF_NEWDIALOGSTYLE, BIF_RETURNONLYFSDIRS,
+                BROWSEINFOW,
+            },
+        },
+    };
+
+    use super::{filter_string, DialogError, DialogKind, DialogRequest, DialogResult};
+
+    const MAX_PATH: usize = 32 * 1024;
+
+    /// The window's HWND, which common dialogs disable while they show.
+    pub struct Parent(usize);
+
+    impl Parent {
+        pub fn of(window: &Window) -> Self {
+            Self(crate::titlebar::hwnd(window).map_or(0, |hwnd| hwnd as usize))
+        }
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    fn path(buffer: &[u16]) -> PathBuf {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        PathBuf::from(OsString::from_wide(&buffer[..len]))
+    }
+
+    pub fn run(request: &DialogRequest, parent: &Parent) -> DialogResult {
+        match &request.kind {
+            DialogKind::OpenFile(filters) => file(parent, &filter_string(filters), "", false),
+            DialogKind::SaveFile { default_name } => {
+                file(parent, &filter_string(&[]), default_name, true)
+            }
+            DialogKind::OpenFolder => folder(parent),
+        }
+    }
+
+    fn file(parent: &Parent, filter: &[u16], default_name: &str, save: bool) -> DialogResult {
+        let mut buffer = vec![0u16; MAX_PATH];
+        let name: Vec<u16> = default_name.encode_utf16().take(MAX_PATH - 1).collect();
+        buffer[..name.len()].copy_from_slice(&name);
+        // SAFETY: All fields are plain data or pointers, for which zero is
+        // "none".
+        let mut ofn: OPENFILENAMEW = unsafe { std::mem::zeroed() };
+        ofn.lStructSize = std::mem::size_of::<OPENFILENAMEW>() as u32;
+        ofn.hwndOwner = parent.0 as _;
+        ofn.lpstrFilter = filter.as_ptr();
+        ofn.nFilterIndex = 1;
+        ofn.lpstrFile = buffer.as_mut_ptr();
+        ofn.nMaxFile = buffer.len() as u32;
+        ofn.Flags = OFN_EXPLORER | OFN_NOCHANGEDIR | OFN_PATHMUSTEXIST;
+        // SAFETY: The strings and the buffer outlive the call, and
+        // `nMaxFile` is the buffer's length.
+        let chosen = unsafe {
+            if save {
+                ofn.Flags |= OFN_OVERWRITEPROMPT;
+                GetSaveFileNameW(&mut ofn)
+            } else {
+                ofn.Flags |= OFN_FILEMUSTEXIST;
+                GetOpenFileNameW(&mut ofn)
+            }
+        };
+        if chosen != 0 {
+            return Ok(Some(path(&buffer)));
+        }
+        // SAFETY: Takes no arguments.
+        match unsafe { CommDlgExtendedError() } {
+            0 => Ok(None),
+            code => Err(DialogError::Failed(format!("error {code:#x}"))),
+        }
+    }
+
+    fn folder(parent: &Parent) -> DialogResult {
+        // The new dialog style needs COM on the calling thread. Each dialog
+        // has a thread of its own, which ends with it.
+        // SAFETY: The reserved argument is null.
+        unsafe { CoInitializeEx(std::ptr::null(), COINIT_APARTMENTTHREADED as u32) };
+        let title = wide("Choose a folder");
+        // SAFETY: As for `OPENFILENAMEW`.
+        let mut info: BROWSEINFOW = unsafe { std::mem::zeroed() };
+        info.hwndOwner = parent.0 as _;
+        info.lpszTitle = title.as_ptr();
+        info.ulFlags = BIF_RETURNONLYFSDIRS | BIF_NEWDIALOGSTYLE;
+        // SAFETY: The title outlives the call.
+        let list = unsafe { SHBrowseForFolderW(&info) };
+        if list.is_null() {
+            return Ok(None);
+        }
+        let mut buffer = vec![0u16; MAX_PATH];
+        // SAFETY: `list` came from the dialog and is freed once, after use;
+        // the buffer holds more than MAX_PATH characters.
+        let found = unsafe {
+            let found = SHGetPathFromIDListW(list, buffer.as_mut_ptr());
+            CoTaskMemFree(list as _);
+            found
+        };
+        if found == 0 {
+            return Err(DialogError::Failed(
+                "the folder is not in the file system".to_owned(),
+            ));
+        }
+        Ok(Some(path(&buffer)))
+    }
+}
+
+/// NSOpenPanel and NSSavePanel have to run on the main thread, where they
+/// would block the event loop.
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+mod platform {
+    use egui_winit::winit::window::Window;
+
+    use super::{DialogError, DialogRequest, DialogResult};
+
+    pub struct Parent;
+
+    impl Parent {
+        pub fn of(_window: &Window) -> Self {
+            Self
+        }
+    }
+
+    pub fn run(_request: &DialogRequest, _parent: &Parent) -> DialogResult {
+        Err(DialogError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_wait_for_the_frame_and_results_for_their_dialog() {
+        let ctx = egui::Context::default();
+        let image = pick_file(&ctx, vec![FileFilter::new("Images", &["png"])]);
+        let folder = pick_folder(&ctx);
+        let save = save_file(&ctx, "notes.txt");
+        assert_ne!(image, folder);
+        assert_eq!(
+            take_requests(&ctx),
+            [
+                DialogRequest {
+                    id: image,
+                    kind: DialogKind::OpenFile(vec![FileFilter::new("Images", &["png"])]),
+                },
+                DialogRequest {
+                    id: folder,
+                    kind: DialogKind::OpenFolder,
+                },
+                DialogRequest {
+                    id: save,
+                    kind: DialogKind::SaveFile {
+                        default_name: "notes.txt".to_owned()
+                    },
+                },
+            ]
+        );
+        assert!(take_requests(&ctx).is_empty());
+
+        set_result(&ctx, folder, Ok(None));
+        assert!(take_result(&ctx, image).is_none());
+        assert!(matches!(take_result(&ctx, folder), Some(Ok(None))));
+        assert!(take_result(&ctx, folder).is_none());
+    }
+
+    #[test]
+    fn windows_filters_are_null_separated() {
+        let text = |filters: &[FileFilter]| String::from_utf16(&filter_string(filters)).unwrap();
+        assert_eq!(
+            text(&[
+                FileFilter::new("Images", &["png", "jpg"]),
+                FileFilter::new("Text", &["txt"]),
+            ]),
+            "Images\0*.png;*.jpg\0Text\0*.txt\0\0"
+        );
+        assert_eq!(text(&[]), "All files\0*.*\0\0");
+    }
+}
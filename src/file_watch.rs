@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, Weak,
+    },
+    time::SystemTime,
+};
+
+use web_time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A file's identity while it may still be being written.
+#[derive(Clone, Copy, PartialEq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct FileState {
+    /// What the last change reported, or what the file was when watched.
+    reported: Option<Stamp>,
+    /// What the last poll saw.
+    seen: Option<Stamp>,
+}
+
+#[derive(Default)]
+struct Watched {
+    files: HashMap<PathBuf, FileState>,
+    directories: Vec<PathBuf>,
+}
+
+/// Polls files for changes on a thread of its own and reports each change
+/// once the file has stayed the same for a poll, so that half-written files
+/// are skipped. Polling works the same everywhere and costs a `stat` per
+/// file and poll, which is fine for a handful of development files.
+pub struct FileWatcher {
+    watched: Arc<Mutex<Watched>>,
+    changes: Receiver<PathBuf>,
+}
+
+impl FileWatcher {
+    /// `on_change` runs on the watcher's thread after changes were queued,
+    /// e.g. to request a repaint.
+    pub fn new(on_change: impl Fn() + Send + 'static) -> std::io::Result<Self> {
+        let watched = Arc::new(Mutex::new(Watched::default()));
+        let (sender, changes) = mpsc::channel();
+        let weak = Arc::downgrade(&watched);
+        std::thread::Builder::new()
+            .name("file-watcher".to_owned())
+            .spawn(move || poll_until_dropped(&weak, &sender, on_change))?;
+        Ok(Self { watched, changes })
+    }
+
+    /// Reports changes to `path` from now on, including its removal. A
+    /// directory reports changes to the files directly in it, new ones too.
+    pub fn watch(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let mut watched = self.watched.lock().unwrap();
+        if path.is_dir() {
+            for file in files_in(&path) {
+                watched
+                    .files
+                    .entry(file.clone())
+                    .or_insert_with(|| current(&file));
+            }
+            if !watched.directories.contains(&path) {
+                watched.directories.push(path);
+            }
+        } else {
+            watched
+                .files
+                .entry(path.clone())
+                .or_insert_with(|| current(&path));
+        }
+    }
+
+    /// The files that changed since the last call, each once.
+    pub fn changes(&self) -> Vec<PathBuf> {
+        let mut changes: Vec<_> = self.changes.try_iter().collect();
+        changes.sort();
+        changes.dedup();
+        changes
+    }
+}
+
+fn current(path: &Path) -> FileState {
+    let stamp = Stamp::of(path);
+    FileState {
+        reported: stamp,
+        seen: stamp,
+    }
+}
+
+fn files_in(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+fn poll_until_dropped(
+    watched: &Weak<Mutex<Watched>>,
+    sender: &Sender<PathBuf>,
+    on_change: impl Fn(),
+) {
+    profiling::register_thread!("file-watcher");
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let Some(watched) = watched.upgrade() else {
+            return;
+        };
+        let mut watched = watched.lock().unwrap();
+        let Watched { files, directories } = &mut *watched;
+        for directory in directories.iter() {
+            for file in files_in(directory) {
+                // New files count as changed, once they have settled.
+                files.entry(file).or_default();
+            }
+        }
+        let mut changed = false;
+        for (path, state) in files.iter_mut() {
+            let stamp = Stamp::of(path);
+            let settled = stamp == state.seen;
+            state.seen = stamp;
+            if settled && stamp != state.reported {
+                state.reported = stamp;
+                changed = true;
+                if sender.send(path.clone()).is_err() {
+                    return;
+                }
+            }
+        }
+        drop(watched);
+        if changed {
+            on_change();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::gpu_poll::{GpuFuture, GpuPoller};
+
+/// A frame's size and its tightly packed RGBA rows.
+pub type Pixels = ([u32; 2], Vec<u8>);
+
+/// A painted frame on its way from the GPU: copied out of the texture in
+/// the frame's encoder, then mapped without waiting for the GPU.
+pub struct FrameCapture {
+    buffer: wgpu::Buffer,
+    size: [u32; 2],
+    padded_row: u32,
+    bgra: bool,
+    mapped: Option<GpuFuture<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl FrameCapture {
+    /// Copies `texture`. Call before submitting. Fails with the texture's
+    /// format unless it is 8-bit RGBA or BGRA.
+    pub fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        label: &str,
+    ) -> Result<Self, wgpu::TextureFormat> {
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            format => return Err(format),
+        };
+        let size = [texture.width(), texture.height()];
+        let padded_row = (size[0] * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: u64::from(padded_row) * u64::from(size[1]),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        Ok(Self {
+            buffer,
+            size,
+            padded_row,
+            bgra,
+            mapped: None,
+        })
+    }
+
+    /// Maps the copy. Call after submitting.
+    pub fn after_submit(&mut self, gpu_poller: &GpuPoller) {
+        if self.mapped.is_none() {
+            self.mapped = Some(gpu_poller.map_buffer(self.buffer.slice(..), wgpu::MapMode::Read));
+        }
+    }
+
+    /// The frame, once mapped.
+    pub fn try_read(&self) -> Option<Result<Pixels, wgpu::BufferAsyncError>> {
+        let result = self.mapped.as_ref()?.try_take()?;
+        Some(result.map(|()| self.read()))
+    }
+
+    fn read(&self) -> Pixels {
+        let [width, height] = self.size;
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_row as usize) {
+                pixels.extend_from_slice(&row[..width as usize * 4]);
+            }
+        }
+        self.buffer.unmap();
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        (self.size, pixels)
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The CRC-32 of the chunks one after another, as PNG and zip use it.
+pub fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for chunk in chunks {
+        for &byte in *chunk {
+            crc = CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    !crc
+}
+
+/// An RGBA PNG. The image data goes in stored deflate blocks: larger than
+/// it has to be, but nothing to depend on.
+pub fn encode_png([width, height]: [u32; 2], pixels: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(pixels.len() + height as usize);
+    for row in pixels.chunks(width as usize * 4) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(u8::from(blocks.peek().is_none()));
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in &raw {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    zlib.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filter and no
+    // interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", &header[..]), (b"IDAT", &zlib), (b"IEND", &[])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        png.extend_from_slice(&crc32(&[kind, data]).to_be_bytes());
+    }
+    png
+}
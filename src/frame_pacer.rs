@@ -0,0 +1,141 @@
+use web_time::{Duration, Instant};
+
+use crate::frame_stats::FrameTimeline;
+
+const DEFAULT_REFRESH_MILLIHERTZ: u32 = 60_000;
+const FRAME_COST_SMOOTHING: f64 = 0.1;
+const WAKE_UP_MARGIN: Duration = Duration::from_millis(1);
+/// Consecutive presents more than a refresh interval off the prediction
+/// before the reported refresh rate is no longer trusted.
+const MAX_MISPREDICTIONS: u32 = 8;
+
+pub struct FramePacer {
+    interval: Duration,
+    limit_rate: bool,
+    next_slot: Option<Instant>,
+    pub resyncs: u64,
+    snap_to_vsync: bool,
+    vsync_trusted: bool,
+    last_present: Option<Instant>,
+    frame_cost: Duration,
+    predicted_present: Option<Instant>,
+    mispredictions: u32,
+    /// Actual minus predicted present time of the last snapped frame, in ms.
+    pub prediction_error: Option<f32>,
+}
+
+impl FramePacer {
+    pub fn new(refresh_millihertz: Option<u32>, limit_rate: bool, snap_to_vsync: bool) -> Self {
+        let mut pacer = Self {
+            interval: Duration::ZERO,
+            limit_rate,
+            next_slot: None,
+            resyncs: 0,
+            snap_to_vsync,
+            vsync_trusted: true,
+            last_present: None,
+            frame_cost: Duration::ZERO,
+            predicted_present: None,
+            mispredictions: 0,
+            prediction_error: None,
+        };
+        pacer.set_refresh_rate(refresh_millihertz);
+        pacer
+    }
+
+    pub fn uses_present_mode(present_mode: wgpu::PresentMode) -> bool {
+        matches!(
+            present_mode,
+            wgpu::PresentMode::Mailbox
+                | wgpu::PresentMode::Immediate
+                | wgpu::PresentMode::AutoNoVsync
+        )
+    }
+
+    pub fn set_refresh_rate(&mut self, refresh_millihertz: Option<u32>) {
+        let millihertz = refresh_millihertz
+            .filter(|mhz| *mhz > 0)
+            .unwrap_or(DEFAULT_REFRESH_MILLIHERTZ);
+        let interval = Duration::from_secs_f64(1000. / millihertz as f64);
+        if interval != self.interval {
+            self.interval = interval;
+            self.next_slot = None;
+            self.predicted_present = None;
+            self.mispredictions = 0;
+            self.vsync_trusted = true;
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn snapping(&self) -> bool {
+        self.snap_to_vsync && self.vsync_trusted
+    }
+
+    pub fn on_present(&mut self, timeline: &FrameTimeline) {
+        let presented = timeline.present;
+        self.next_slot = match self.next_slot.map(|slot| slot + self.interval) {
+            Some(slot) if slot >= presented => Some(slot),
+            Some(_) => {
+                self.resyncs += 1;
+                Some(presented)
+            }
+            None => Some(presented),
+        };
+        self.last_present = Some(presented);
+        self.frame_cost = self.frame_cost.mul_f64(1. - FRAME_COST_SMOOTHING)
+            + timeline.cpu_time().mul_f64(FRAME_COST_SMOOTHING);
+
+        if let Some(predicted) = self.predicted_present.take() {
+            let error = if presented >= predicted {
+                presented.duration_since(predicted).as_secs_f32()
+            } else {
+                -predicted.duration_since(presented).as_secs_f32()
+            };
+            self.prediction_error = Some(error * 1000.);
+            if error.abs() > self.interval.as_secs_f32() {
+                self.mispredictions += 1;
+                if self.mispredictions == MAX_MISPREDICTIONS {
+                    log::warn!(
+                        "presents do not line up with the reported refresh interval of {:?}, no longer snapping to vsync",
+                        self.interval
+                    );
+                    self.vsync_trusted = false;
+                }
+            } else {
+                self.mispredictions = 0;
+            }
+        }
+    }
+
+    /// Delays a repaint so that at most one frame is presented per refresh interval.
+    pub fn paced(&self, when: Instant) -> Instant {
+        match self.next_slot {
+            Some(slot) if self.limit_rate => when.max(slot),
+            _ => when,
+        }
+    }
+
+    /// Moves a scheduled repaint to just before the first vsync at or after
+    /// `when`, leaving enough time to finish the frame.
+    pub fn snap(&mut self, when: Instant) -> Instant {
+        if !self.snapping() {
+            return when;
+        }
+        let Some(last_present) = self.last_present else {
+            return when;
+        };
+        let lead = self.frame_cost + WAKE_UP_MARGIN;
+        let target = when + lead;
+        let periods = if target > last_present {
+            (target.duration_since(last_present).as_secs_f64() / self.interval.as_secs_f64()).ceil()
+        } else {
+            1.
+        };
+        let vsync = last_present + self.interval.mul_f64(periods);
+        self.predicted_present = Some(vsync);
+        vsync.checked_sub(lead).map_or(when, |wake| wake.max(when))
+    }
+}
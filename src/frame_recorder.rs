@@ -0,0 +1,310 @@
+use std::{fmt::Write as _, path::PathBuf};
+
+use web_time::{Duration, Instant};
+
+use crate::{
+    frame_stats::FrameTimeline,
+    latency::{InputClass, LatencyHistogram, LatencyTracker, RepaintPath},
+    render_stats::RenderStats,
+};
+
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Csv,
+        }
+    }
+}
+
+struct FrameRecord {
+    frame: u64,
+    start: f64,
+    input: f64,
+    update: f64,
+    tessellate: f64,
+    encode: f64,
+    submit: f64,
+    present: f64,
+    clipped_primitives: usize,
+    vertices: usize,
+    indices: usize,
+}
+
+impl FrameRecord {
+    fn cpu_time(&self) -> f64 {
+        self.present - self.start
+    }
+}
+
+pub struct FrameRecorder {
+    path: PathBuf,
+    format: ExportFormat,
+    origin: Instant,
+    records: Vec<FrameRecord>,
+}
+
+impl FrameRecorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            format: ExportFormat::from_path(&path),
+            path,
+            origin: Instant::now(),
+            records: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, timeline: &FrameTimeline, render_stats: &RenderStats) {
+        let secs = |t: Instant| t.saturating_duration_since(self.origin).as_secs_f64();
+        let record = FrameRecord {
+            frame: self.records.len() as u64,
+            start: secs(timeline.start),
+            input: secs(timeline.input),
+            update: secs(timeline.update),
+            tessellate: secs(timeline.tessellate),
+            encode: secs(timeline.encode),
+            submit: secs(timeline.submit),
+            present: secs(timeline.present),
+            clipped_primitives: render_stats.clipped_primitives,
+            vertices: render_stats.vertices,
+            indices: render_stats.indices,
+        };
+        self.records.push(record);
+    }
+
+    /// A CSV file keeps one row per frame; the summary goes next to it, to
+    /// `<name>.summary.csv`.
+    pub fn write(&self, latency: Option<&LatencyTracker>) -> anyhow::Result<()> {
+        let summary = Summary::new(&self.records);
+        match self.format {
+            ExportFormat::Csv => {
+                std::fs::write(&self.path, self.to_csv())?;
+                std::fs::write(
+                    self.path.with_extension("summary.csv"),
+                    summary_csv(&summary, latency),
+                )?;
+            }
+            ExportFormat::Json => std::fs::write(&self.path, self.to_json(&summary, latency))?,
+        }
+        Ok(())
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::from(
+            "frame,start,input,update,tessellate,encode,submit,present,clipped_primitives,vertices,indices\n",
+        );
+        for r in &self.records {
+            let _ = writeln!(
+                out,
+                "{},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{},{},{}",
+                r.frame,
+                r.start,
+                r.input,
+                r.update,
+                r.tessellate,
+                r.encode,
+                r.submit,
+                r.present,
+                r.clipped_primitives,
+                r.vertices,
+                r.indices
+            );
+        }
+        out
+    }
+
+    fn to_json(&self, summary: &Summary, latency: Option<&LatencyTracker>) -> String {
+        let mut out = String::from("{\n  \"frames\": [\n");
+        for (i, r) in self.records.iter().enumerate() {
+            let separator = if i + 1 < self.records.len() { "," } else { "" };
+            let _ = writeln!(
+                out,
+                "    {{\"frame\": {}, \"start\": {:.6}, \"input\": {:.6}, \"update\": {:.6}, \"tessellate\": {:.6}, \"encode\": {:.6}, \"submit\": {:.6}, \"present\": {:.6}, \"clipped_primitives\": {}, \"vertices\": {}, \"indices\": {}}}{}",
+                r.frame,
+                r.start,
+                r.input,
+                r.update,
+                r.tessellate,
+                r.encode,
+                r.submit,
+                r.present,
+                r.clipped_primitives,
+                r.vertices,
+                r.indices,
+                separator
+            );
+        }
+        let _ = write!(
+            out,
+            "  ],\n  \"summary\": {{\"frames\": {}, \"cpu_time_ms\": {{\"p50\": {:.3}, \"p95\": {:.3}, \"p99\": {:.3}, \"max\": {:.3}}}}}",
+            summary.frames,
+            ms(summary.p50),
+            ms(summary.p95),
+            ms(summary.p99),
+            ms(summary.max)
+        );
+        if let Some(latency) = latency {
+            out.push_str(",\n  \"latency_ms\": [");
+            for (i, (class, path, histogram)) in latency_histograms(latency).enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                let _ = write!(
+                    out,
+                    "{}\n    {{\"class\": \"{}\", \"path\": \"{}\", \"count\": {}, \"mean\": {:.3}, \"p50\": {:.3}, \"p95\": {:.3}, \"p99\": {:.3}, \"max\": {:.3}}}",
+                    separator,
+                    class.name(),
+                    path.name(),
+                    histogram.count(),
+                    ms(histogram.mean()),
+                    ms(histogram.percentile(0.50)),
+                    ms(histogram.percentile(0.95)),
+                    ms(histogram.percentile(0.99)),
+                    ms(histogram.max())
+                );
+            }
+            out.push_str("\n  ]");
+        }
+        out.push_str("\n}\n");
+        out
+    }
+}
+
+fn summary_csv(summary: &Summary, latency: Option<&LatencyTracker>) -> String {
+    let mut out = String::from("metric,class,path,count,mean_ms,p50_ms,p95_ms,p99_ms,max_ms\n");
+    let _ = writeln!(
+        out,
+        "cpu_time,,,{},{:.3},{:.3},{:.3},{:.3},{:.3}",
+        summary.frames,
+        ms(summary.mean),
+        ms(summary.p50),
+        ms(summary.p95),
+        ms(summary.p99),
+        ms(summary.max)
+    );
+    if let Some(latency) = latency {
+        for (class, path, histogram) in latency_histograms(latency) {
+            let _ = writeln!(
+                out,
+                "latency,{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3}",
+                class.name(),
+                path.name(),
+                histogram.count(),
+                ms(histogram.mean()),
+                ms(histogram.percentile(0.50)),
+                ms(histogram.percentile(0.95)),
+                ms(histogram.percentile(0.99)),
+                ms(histogram.max())
+            );
+        }
+    }
+    out
+}
+
+fn latency_histograms(
+    latency: &LatencyTracker,
+) -> impl Iterator<Item = (InputClass, RepaintPath, &LatencyHistogram)> {
+    InputClass::ALL.into_iter().flat_map(move |class| {
+        RepaintPath::ALL
+            .into_iter()
+            .map(move |path| (class, path, latency.histogram(class, path)))
+            .filter(|(_, _, histogram)| histogram.count() > 0)
+    })
+}
+
+struct Summary {
+    frames: usize,
+    mean: Duration,
+    p50: Duration,
+    p95: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl Summary {
+    fn new(records: &[FrameRecord]) -> Self {
+        let mut times: Vec<f64> = records.iter().map(FrameRecord::cpu_time).collect();
+        times.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            if times.is_empty() {
+                return Duration::ZERO;
+            }
+            let index = ((times.len() - 1) as f64 * p).round() as usize;
+            Duration::from_secs_f64(times[index])
+        };
+        Self {
+            frames: times.len(),
+            mean: Duration::from_secs_f64(times.iter().sum::<f64>() / times.len().max(1) as f64),
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: percentile(1.),
+        }
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::EventResult;
+
+    use super::*;
+
+    fn record(frame: u64, cpu_time: f64) -> FrameRecord {
+        let start = frame as f64 / 60.;
+        FrameRecord {
+            frame,
+            start,
+            input: start,
+            update: start,
+            tessellate: start,
+            encode: start,
+            submit: start,
+            present: start + cpu_time,
+            clipped_primitives: 3,
+            vertices: 120,
+            indices: 180,
+        }
+    }
+
+    fn assert_rectangular(csv: &str) {
+        let mut lines = csv.lines();
+        let columns = lines.next().unwrap().split(',').count();
+        for line in lines {
+            assert_eq!(line.split(',').count(), columns, "{line:?} in\n{csv}");
+        }
+    }
+
+    #[test]
+    fn csv_keeps_the_summary_next_to_it() {
+        let dir = std::env::temp_dir().join(format!("{}-frame-recorder", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut recorder = FrameRecorder::new(dir.join("frames.csv"));
+        recorder.records = (0..4).map(|frame| record(frame, 0.002)).collect();
+        let mut latency = LatencyTracker::default();
+        let arrival = Instant::now();
+        latency.on_input(InputClass::KeyPress, arrival, &EventResult::RepaintNow);
+        latency.begin_frame();
+        latency.end_frame(arrival + Duration::from_millis(5));
+        recorder.write(Some(&latency)).unwrap();
+
+        let frames = std::fs::read_to_string(dir.join("frames.csv")).unwrap();
+        let summary = std::fs::read_to_string(dir.join("frames.summary.csv")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(frames.lines().count(), 5);
+        assert_rectangular(&frames);
+        assert_eq!(
+            summary,
+            "metric,class,path,count,mean_ms,p50_ms,p95_ms,p99_ms,max_ms\n\
+             cpu_time,,,4,2.000,2.000,2.000,2.000,2.000\n\
+             latency,key_press,now,1,5.000,5.000,5.000,5.000,5.000\n"
+        );
+        assert_rectangular(&summary);
+    }
+}
@@ -0,0 +1,296 @@
+use web_time::{Duration, Instant};
+
+use crate::adaptive_quality::QualityStatus;
+
+pub const FRAME_HISTORY_LEN: usize = 240;
+
+const SMOOTHING: f32 = 0.1;
+
+#[derive(Clone, Copy)]
+pub struct FrameTimeline {
+    pub start: Instant,
+    pub input: Instant,
+    pub update: Instant,
+    pub tessellate: Instant,
+    /// After the surface texture was acquired.
+    pub acquire: Instant,
+    pub encode: Instant,
+    pub submit: Instant,
+    pub present: Instant,
+}
+
+impl FrameTimeline {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            start,
+            input: start,
+            update: start,
+            tessellate: start,
+            acquire: start,
+            encode: start,
+            submit: start,
+            present: start,
+        }
+    }
+
+    pub fn cpu_time(&self) -> Duration {
+        self.present.saturating_duration_since(self.start)
+    }
+
+    /// [`Self::cpu_time`] without waiting to acquire and present, which
+    /// block on vsync.
+    pub fn busy_time(&self) -> Duration {
+        self.tessellate.saturating_duration_since(self.start)
+            + self.submit.saturating_duration_since(self.acquire)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct FrameTiming {
+    pub interval: Duration,
+    pub cpu_time: Duration,
+}
+
+pub struct FrameStats {
+    history: [FrameTiming; FRAME_HISTORY_LEN],
+    next: usize,
+    len: usize,
+    last_present: Option<Instant>,
+    smoothed_interval: f32,
+    pub gpu_time: Option<Duration>,
+    pub frames_elided: u64,
+    pub pointer_moves_coalesced: u64,
+    pub pacer_interval: Option<Duration>,
+    pub pacer_resyncs: u64,
+    pub vsync_snapping: bool,
+    pub vsync_prediction_error: Option<f32>,
+    pub idle: bool,
+    pub quality: Option<QualityStatus>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            history: [FrameTiming::default(); FRAME_HISTORY_LEN],
+            next: 0,
+            len: 0,
+            last_present: None,
+            smoothed_interval: 0.,
+            gpu_time: None,
+            frames_elided: 0,
+            pointer_moves_coalesced: 0,
+            pacer_interval: None,
+            pacer_resyncs: 0,
+            vsync_snapping: false,
+            vsync_prediction_error: None,
+            idle: false,
+            quality: None,
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn record(&mut self, timeline: &FrameTimeline) {
+        let cpu_time = timeline.cpu_time();
+        let Some(last_present) = self.last_present.replace(timeline.present) else {
+            return;
+        };
+        let interval = timeline.present.saturating_duration_since(last_present);
+
+        self.history[self.next] = FrameTiming { interval, cpu_time };
+        self.next = (self.next + 1) % FRAME_HISTORY_LEN;
+        self.len = (self.len + 1).min(FRAME_HISTORY_LEN);
+
+        let interval = interval.as_secs_f32();
+        self.smoothed_interval = if self.len == 1 {
+            interval
+        } else {
+            self.smoothed_interval + (interval - self.smoothed_interval) * SMOOTHING
+        };
+    }
+
+    pub fn latest(&self) -> Option<FrameTiming> {
+        (self.len > 0)
+            .then(|| self.history[(self.next + FRAME_HISTORY_LEN - 1) % FRAME_HISTORY_LEN])
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.latest()
+            .map_or(0., |timing| fps_from_secs(timing.interval.as_secs_f32()))
+    }
+
+    pub fn smoothed_interval(&self) -> Duration {
+        Duration::from_secs_f32(self.smoothed_interval)
+    }
+
+    pub fn smoothed_fps(&self) -> f32 {
+        fps_from_secs(self.smoothed_interval)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &FrameTiming> + ExactSizeIterator {
+        let start = (self.next + FRAME_HISTORY_LEN - self.len) % FRAME_HISTORY_LEN;
+        (0..self.len).map(move |i| &self.history[(start + i) % FRAME_HISTORY_LEN])
+    }
+}
+
+/// Metrics of the previous completed frame, handed to the app's update so it
+/// can adapt, e.g. lower a chart's resolution when frames get slow. During a
+/// frame they describe the one before it, since the current one is not done
+/// yet.
+#[derive(Clone, Copy, Default)]
+pub struct FrameMetrics {
+    /// From the start of the frame to present.
+    pub cpu_time: Duration,
+    pub smoothed_cpu_time: Duration,
+    /// `None` unless GPU timing is enabled and supported.
+    pub gpu_time: Option<Duration>,
+    pub smoothed_gpu_time: Option<Duration>,
+    /// Window events that arrived since the frame before.
+    pub events: u32,
+    pub frames_last_second: u32,
+    pub clipped_primitives: usize,
+    pub textures_set: usize,
+    pub textures_freed: usize,
+    /// Nothing changed, so the frame was not painted.
+    pub elided: bool,
+}
+
+fn smooth(smoothed: Duration, sample: Duration) -> Duration {
+    if smoothed.is_zero() {
+        return sample;
+    }
+    let smoothed = smoothed.as_secs_f32();
+    Duration::from_secs_f32(smoothed + (sample.as_secs_f32() - smoothed) * SMOOTHING)
+}
+
+impl FrameMetrics {
+    pub fn on_presented(&mut self, stats: &FrameStats) {
+        let Some(latest) = stats.latest() else {
+            return;
+        };
+        self.cpu_time = latest.cpu_time;
+        self.smoothed_cpu_time = smooth(self.smoothed_cpu_time, latest.cpu_time);
+        self.gpu_time = stats.gpu_time;
+        self.smoothed_gpu_time = stats
+            .gpu_time
+            .map(|gpu_time| smooth(self.smoothed_gpu_time.unwrap_or_default(), gpu_time));
+        let mut elapsed = Duration::ZERO;
+        self.frames_last_second = stats
+            .iter()
+            .rev()
+            .take_while(|timing| {
+                elapsed += timing.interval;
+                elapsed <= Duration::from_secs(1)
+            })
+            .count() as u32;
+    }
+}
+
+fn fps_from_secs(secs: f32) -> f32 {
+    if secs > 0. {
+        1. / secs
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presents `count` frames `interval` apart, each 4 ms after it started.
+    fn present(stats: &mut FrameStats, start: &mut Instant, count: usize, interval: Duration) {
+        for _ in 0..count {
+            let mut timeline = FrameTimeline::new(*start);
+            timeline.present = *start + Duration::from_millis(4);
+            stats.record(&timeline);
+            *start += interval;
+        }
+    }
+
+    #[test]
+    fn first_frame_has_no_interval() {
+        let mut stats = FrameStats::default();
+        let mut metrics = FrameMetrics::default();
+        present(
+            &mut stats,
+            &mut Instant::now(),
+            1,
+            Duration::from_millis(16),
+        );
+        assert!(stats.latest().is_none());
+        metrics.on_presented(&stats);
+        assert_eq!(metrics.frames_last_second, 0);
+        assert_eq!(stats.fps(), 0.);
+    }
+
+    #[test]
+    fn counts_frames_of_the_last_second() {
+        let mut stats = FrameStats::default();
+        let mut metrics = FrameMetrics::default();
+        let mut start = Instant::now();
+        present(&mut stats, &mut start, 121, Duration::from_millis(10));
+        metrics.on_presented(&stats);
+        assert_eq!(metrics.frames_last_second, 100);
+        assert_eq!(metrics.cpu_time, Duration::from_millis(4));
+
+        // After a pause only the frames since count; the first of them
+        // ends the long interval.
+        present(&mut stats, &mut start, 1, Duration::from_secs(2));
+        present(&mut stats, &mut start, 5, Duration::from_millis(100));
+        metrics.on_presented(&stats);
+        assert_eq!(metrics.frames_last_second, 4);
+        assert!((stats.fps() - 10.).abs() < 0.01);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_frames() {
+        let mut stats = FrameStats::default();
+        let mut start = Instant::now();
+        present(&mut stats, &mut start, 1, Duration::from_millis(1));
+        present(
+            &mut stats,
+            &mut start,
+            FRAME_HISTORY_LEN,
+            Duration::from_millis(1),
+        );
+        present(&mut stats, &mut start, 10, Duration::from_millis(2));
+        assert_eq!(stats.iter().len(), FRAME_HISTORY_LEN);
+        let intervals: Vec<_> = stats.iter().map(|timing| timing.interval).collect();
+        assert!(intervals[..FRAME_HISTORY_LEN - 10]
+            .iter()
+            .all(|&interval| interval == Duration::from_millis(1)));
+        // The first of the slower frames still ends a 1 ms interval.
+        assert_eq!(intervals[FRAME_HISTORY_LEN - 10], Duration::from_millis(1));
+        assert_eq!(intervals[FRAME_HISTORY_LEN - 1], Duration::from_millis(2));
+    }
+
+    #[test]
+    fn smoothing_follows_the_samples() {
+        let mut stats = FrameStats::default();
+        let mut metrics = FrameMetrics::default();
+        let mut start = Instant::now();
+        present(&mut stats, &mut start, 2, Duration::from_millis(10));
+        assert_eq!(stats.smoothed_interval(), Duration::from_millis(10));
+
+        stats.gpu_time = Some(Duration::from_millis(2));
+        metrics.on_presented(&stats);
+        // The first sample is taken as it is.
+        assert_eq!(metrics.smoothed_gpu_time, Some(Duration::from_millis(2)));
+        stats.gpu_time = Some(Duration::from_millis(12));
+        metrics.on_presented(&stats);
+        assert_eq!(metrics.gpu_time, Some(Duration::from_millis(12)));
+        let smoothed = metrics.smoothed_gpu_time.unwrap().as_secs_f32();
+        assert!((smoothed - 0.003).abs() < 1e-6);
+
+        // Slower frames move the smoothed interval a tenth of the way.
+        present(&mut stats, &mut start, 1, Duration::from_millis(30));
+        present(&mut stats, &mut start, 1, Duration::from_millis(30));
+        let interval = stats.smoothed_interval().as_secs_f32();
+        assert!(interval > 0.010 && interval < 0.030);
+
+        stats.gpu_time = None;
+        metrics.on_presented(&stats);
+        assert_eq!(metrics.smoothed_gpu_time, None);
+    }
+}
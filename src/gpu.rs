@@ -0,0 +1,667 @@
+use std::sync::Arc;
+
+use egui_winit::winit::window::Window;
+
+use crate::{crash_report, error::AppError, options::Options, surface::RenderSurface};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitsPreset {
+    DownlevelWebgl2,
+    Downlevel,
+    Default,
+    AdapterMax,
+}
+
+impl LimitsPreset {
+    /// The preset's limits, clamped to what the adapter supports.
+    pub fn resolve(self, adapter_limits: &wgpu::Limits) -> wgpu::Limits {
+        let requested = match self {
+            Self::DownlevelWebgl2 => wgpu::Limits::downlevel_webgl2_defaults(),
+            Self::Downlevel => wgpu::Limits::downlevel_defaults(),
+            Self::Default => wgpu::Limits::default(),
+            Self::AdapterMax => return adapter_limits.clone(),
+        };
+        requested.check_limits_with_fail_fn(adapter_limits, false, |name, requested, allowed| {
+            log::warn!("limit {name} of {requested} is not supported, clamping to {allowed}");
+        });
+        clamp_limits(requested, adapter_limits)
+    }
+}
+
+fn clamp_limits(mut limits: wgpu::Limits, allowed: &wgpu::Limits) -> wgpu::Limits {
+    macro_rules! clamp {
+        (max: $($name:ident),* ; min: $($alignment:ident),*) => {
+            $(limits.$name = limits.$name.min(allowed.$name);)*
+            $(limits.$alignment = limits.$alignment.max(allowed.$alignment);)*
+        };
+    }
+    clamp!(
+        max:
+            max_texture_dimension_1d,
+            max_texture_dimension_2d,
+            max_texture_dimension_3d,
+            max_texture_array_layers,
+            max_bind_groups,
+            max_bindings_per_bind_group,
+            max_dynamic_uniform_buffers_per_pipeline_layout,
+            max_dynamic_storage_buffers_per_pipeline_layout,
+            max_sampled_textures_per_shader_stage,
+            max_samplers_per_shader_stage,
+            max_storage_buffers_per_shader_stage,
+            max_storage_textures_per_shader_stage,
+            max_uniform_buffers_per_shader_stage,
+            max_uniform_buffer_binding_size,
+            max_storage_buffer_binding_size,
+            max_vertex_buffers,
+            max_buffer_size,
+            max_vertex_attributes,
+            max_vertex_buffer_array_stride,
+            max_inter_stage_shader_components,
+            max_color_attachments,
+            max_color_attachment_bytes_per_sample,
+            max_compute_workgroup_storage_size,
+            max_compute_invocations_per_workgroup,
+            max_compute_workgroup_size_x,
+            max_compute_workgroup_size_y,
+            max_compute_workgroup_size_z,
+            max_compute_workgroups_per_dimension,
+            max_push_constant_size,
+            max_non_sampler_bindings;
+        min:
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment
+    );
+    limits
+}
+
+#[derive(Debug)]
+pub struct GpuContext {
+    /// The instance of the backend that worked, for recreating the surface.
+    pub instance: Arc<wgpu::Instance>,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub surface: RenderSurface,
+    pub surface_config: wgpu::SurfaceConfiguration,
+    pub timestamp_queries: bool,
+    pub adapter_info: wgpu::AdapterInfo,
+    /// Set when the preferred adapter could not be used and the GL backend
+    /// with WebGL2 limits was picked instead.
+    pub downlevel_fallback: bool,
+}
+
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    options: &Options,
+    limits_preset: LimitsPreset,
+) -> Result<(wgpu::Device, wgpu::Queue, bool), AppError> {
+    let timestamp_queries = (options.gpu_profiling || options.benchmark.is_some())
+        && adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+    let mut features = if timestamp_queries {
+        wgpu::Features::TIMESTAMP_QUERY
+    } else {
+        wgpu::Features::empty()
+    };
+    // Whatever the adapter samples natively, so compressed images need no
+    // CPU decoding, see `compressed_texture::upload`.
+    features |= adapter.features()
+        & (wgpu::Features::TEXTURE_COMPRESSION_BC
+            | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+            | wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+    let limits = limits_preset.resolve(&adapter.limits());
+    log::info!(
+        "requesting {:?} limits (max 2D texture {}, max buffer {} bytes) with {:?} memory hints",
+        limits_preset,
+        limits.max_texture_dimension_2d,
+        limits.max_buffer_size,
+        options.memory_hints
+    );
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("egui device"),
+                required_features: features,
+                required_limits: limits,
+                memory_hints: options.memory_hints.clone(),
+            },
+            None,
+        )
+        .await?;
+    Ok((device, queue, timestamp_queries))
+}
+
+/// Whether the adapter rasterizes on the CPU, which is a lot slower.
+pub fn is_software(adapter_info: &wgpu::AdapterInfo) -> bool {
+    adapter_info.device_type == wgpu::DeviceType::Cpu
+}
+
+/// Shrinks a surface size beyond the device's texture limit, e.g. a window
+/// spanning several monitors, keeping the aspect ratio. Returns the size and
+/// the factor it was scaled by; the compositor stretches the smaller surface
+/// over the window.
+pub fn clamp_surface_size(device: &wgpu::Device, size: [u32; 2]) -> ([u32; 2], f32) {
+    clamp_size(size, device.limits().max_texture_dimension_2d)
+}
+
+fn clamp_size([width, height]: [u32; 2], max: u32) -> ([u32; 2], f32) {
+    if width <= max && height <= max {
+        return ([width, height], 1.);
+    }
+    let scale = max as f32 / width.max(height) as f32;
+    let scaled = |side: u32| ((side as f32 * scale) as u32).clamp(1, max);
+    let clamped = [scaled(width), scaled(height)];
+    log::warn!(
+        "surface size {width}x{height} exceeds the device limit of {max}, using {clamped:?}"
+    );
+    (clamped, scale)
+}
+
+/// Configures the surface, returning validation failures as errors instead
+/// of leaving a surface that only shows black frames. wgpu 23 reports them
+/// as panics rather than through the device's error scopes, so both are
+/// caught.
+pub fn configure_surface(
+    device: &wgpu::Device,
+    surface: &wgpu::Surface,
+    surface_config: &wgpu::SurfaceConfiguration,
+) -> Result<(), AppError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let configured = crash_report::catch_unwind(|| surface.configure(device, surface_config));
+    let scope_error = pollster::block_on(device.pop_error_scope());
+    match (configured, scope_error) {
+        (Ok(()), None) => Ok(()),
+        (_, Some(err)) => Err(AppError::SurfaceConfiguration(err.to_string())),
+        (Err(panic), None) => Err(AppError::SurfaceConfiguration(
+            panic
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|text| text.to_string()))
+                .unwrap_or_default(),
+        )),
+    }
+}
+
+impl GpuContext {
+    /// `backends` are the ones `instance` was created with. Fails with
+    /// [`AppError::NoAdapter`] listing the adapters that were tried.
+    pub async fn new(
+        instance: Arc<wgpu::Instance>,
+        surface: wgpu::Surface<'static>,
+        backends: wgpu::Backends,
+        options: &Options,
+    ) -> Result<Self, AppError> {
+        let power_pref = wgpu::PowerPreference::default();
+        let request_adapter = |force_fallback_adapter| {
+            instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: power_pref,
+                force_fallback_adapter,
+                compatible_surface: Some(&surface),
+            })
+        };
+        let mut adapter = request_adapter(options.force_software).await;
+        if adapter.is_none() && !options.force_software {
+            // Headless machines and VMs often only have a software
+            // rasterizer such as llvmpipe or WARP.
+            log::warn!("no hardware adapter found, trying a software adapter");
+            adapter = request_adapter(true).await;
+        }
+
+        // What was tried, for the error if nothing works.
+        let mut tried = Vec::new();
+        let primary = match &adapter {
+            Some(adapter) => match request_device(adapter, options, options.limits_preset).await {
+                Ok(device) => Some(device),
+                Err(err) => {
+                    let info = adapter.get_info();
+                    log::warn!("failed to create a device on {:?}: {err}", info.backend);
+                    tried.push(format!("{} ({:?}): {err}", info.name, info.backend));
+                    None
+                }
+            },
+            None => {
+                let adapters = instance.enumerate_adapters(backends);
+                if adapters.is_empty() {
+                    tried.push("no adapter".to_owned());
+                }
+                for adapter in adapters {
+                    let info = adapter.get_info();
+                    let why = if !adapter.is_surface_supported(&surface) {
+                        "cannot present to the window"
+                    } else if options.force_software && !is_software(&info) {
+                        "not a software adapter"
+                    } else {
+                        "not chosen"
+                    };
+                    tried.push(format!("{} ({:?}): {why}", info.name, info.backend));
+                }
+                None
+            }
+        };
+        let (adapter, (device, queue, timestamp_queries), downlevel_fallback) =
+            match (adapter, primary) {
+                (Some(adapter), Some(device)) => (adapter, device, false),
+                _ if !gl_fallback(backends) => {
+                    // The surface belongs to this instance, so GL would need
+                    // a candidate of its own, see `backend_order`.
+                    log::info!("GL is not among {backends:?}, skipping the GL fallback");
+                    return Err(AppError::NoAdapter(tried));
+                }
+                _ => {
+                    // Old drivers often only manage GL, and only with the
+                    // limits WebGL2 guarantees.
+                    let Some(gl_adapter) = instance
+                        .enumerate_adapters(wgpu::Backends::GL)
+                        .into_iter()
+                        .find(|gl| {
+                            gl.is_surface_supported(&surface)
+                                && (!options.force_software || is_software(&gl.get_info()))
+                        })
+                    else {
+                        tried.push("no GL adapter for the WebGL2 fallback".to_owned());
+                        return Err(AppError::NoAdapter(tried));
+                    };
+                    let name = gl_adapter.get_info().name;
+                    log::warn!("falling back to {name} on the GL backend with WebGL2 limits");
+                    match request_device(&gl_adapter, options, LimitsPreset::DownlevelWebgl2).await
+                    {
+                        Ok(device) => (gl_adapter, device, true),
+                        Err(err) => {
+                            tried.push(format!("{name} (Gl) with WebGL2 limits: {err}"));
+                            return Err(AppError::NoAdapter(tried));
+                        }
+                    }
+                }
+            };
+
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "using {} adapter {} on {:?}",
+            if is_software(&adapter_info) {
+                "software"
+            } else {
+                "hardware"
+            },
+            adapter_info.name,
+            adapter_info.backend
+        );
+
+        let swapchain_capabilities = surface.get_capabilities(&adapter);
+        // GL surfaces usually only offer RGBA formats.
+        let swapchain_format = swapchain_capabilities
+            .formats
+            .iter()
+            .find(|format| **format == wgpu::TextureFormat::Bgra8UnormSrgb)
+            .or_else(|| {
+                swapchain_capabilities
+                    .formats
+                    .iter()
+                    .find(|format| format.is_srgb())
+            })
+            .ok_or(AppError::SurfaceFormat)?;
+
+        let present_mode = match options.present_mode {
+            mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync) => mode,
+            mode if swapchain_capabilities.present_modes.contains(&mode) => mode,
+            mode => {
+                log::warn!("present mode {mode:?} is not supported, falling back to AutoVsync");
+                wgpu::PresentMode::AutoVsync
+            }
+        };
+
+        let ([width, height], _) = clamp_surface_size(
+            &device,
+            [options.window_size.x as u32, options.window_size.y as u32],
+        );
+        // egui's output is premultiplied; the compositor only blends it with
+        // what is behind a transparent window in a non-opaque mode.
+        let alpha_mode = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ]
+        .into_iter()
+        .filter(|_| options.click_through || options.drawn_window_shadow())
+        .find(|mode| swapchain_capabilities.alpha_modes.contains(mode))
+        .unwrap_or(swapchain_capabilities.alpha_modes[0]);
+        // Copies feed the screenshots of bug reports.
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | (swapchain_capabilities.usages & wgpu::TextureUsages::COPY_SRC);
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage,
+            format: *swapchain_format,
+            width,
+            height,
+            present_mode,
+            desired_maximum_frame_latency: 0,
+            alpha_mode,
+            view_formats: vec![],
+        };
+
+        configure_surface(&device, &surface, &surface_config)?;
+
+        Ok(Self {
+            instance,
+            device,
+            queue,
+            surface: RenderSurface::Window(surface),
+            surface_config,
+            timestamp_queries,
+            adapter_info,
+            downlevel_fallback,
+        })
+    }
+}
+
+/// An instance restricted to one set of backends and a surface created with
+/// it. Surfaces have to be created on the main thread, so all candidates are
+/// prepared up front and tried in order on the init thread.
+pub struct Candidate {
+    backends: wgpu::Backends,
+    instance: Arc<wgpu::Instance>,
+    surface: wgpu::Surface<'static>,
+}
+
+/// Whether an instance with `backends` retries on GL with WebGL2 limits
+/// when no device could be created on the preferred adapter.
+fn gl_fallback(backends: wgpu::Backends) -> bool {
+    backends.contains(wgpu::Backends::GL)
+}
+
+fn platform_backends() -> Vec<wgpu::Backends> {
+    if cfg!(target_arch = "wasm32") {
+        // Browsers without WebGPU, or whose WebGPU has no adapter, still
+        // have WebGL2.
+        vec![wgpu::Backends::BROWSER_WEBGPU, wgpu::Backends::GL]
+    } else if cfg!(windows) {
+        vec![
+            wgpu::Backends::DX12,
+            wgpu::Backends::VULKAN,
+            wgpu::Backends::GL,
+        ]
+    } else if cfg!(target_vendor = "apple") {
+        vec![wgpu::Backends::METAL]
+    } else {
+        vec![wgpu::Backends::VULKAN, wgpu::Backends::GL]
+    }
+}
+
+/// The backends to try in order. An explicit choice is only tried on its own
+/// unless `backend_fallback` is set; a backend that worked on a previous
+/// launch goes first.
+fn backend_order(options: &Options) -> Vec<wgpu::Backends> {
+    order_backends(platform_backends(), options)
+}
+
+fn order_backends(platform: Vec<wgpu::Backends>, options: &Options) -> Vec<wgpu::Backends> {
+    let mut order = Vec::new();
+    if let Some(backends) = options.backends {
+        order.push(backends);
+        if !options.backend_fallback {
+            return order;
+        }
+    } else if let Some(last_working) = options
+        .backend_cache
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+    {
+        let backends = wgpu::util::parse_backends_from_comma_list(last_working.trim());
+        if !backends.is_empty() {
+            order.push(backends);
+        }
+    }
+    for backends in platform {
+        if !order.iter().any(|tried| tried.contains(backends)) {
+            order.push(backends);
+        }
+    }
+    order
+}
+
+/// Creates an instance and a surface for every backend to try. Failures are
+/// returned as messages for the final error.
+pub fn candidates(window: &Arc<Window>, options: &Options) -> (Vec<Candidate>, Vec<String>) {
+    let mut candidates = Vec::new();
+    let mut errors = Vec::new();
+    for backends in backend_order(options) {
+        let instance = Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            flags: options.instance_flags,
+            ..Default::default()
+        }));
+        match instance.create_surface(window.clone()) {
+            Ok(surface) => candidates.push(Candidate {
+                backends,
+                instance,
+                surface,
+            }),
+            Err(err) => errors.push(format!("{backends:?}: {err}")),
+        }
+    }
+    (candidates, errors)
+}
+
+/// Tries the candidates in order and keeps the first that yields a device.
+pub async fn init(
+    candidates: Vec<Candidate>,
+    mut errors: Vec<String>,
+    options: &Options,
+) -> Result<GpuContext, AppError> {
+    for candidate in candidates {
+        match GpuContext::new(
+            candidate.instance,
+            candidate.surface,
+            candidate.backends,
+            options,
+        )
+        .await
+        {
+            Ok(gpu) => {
+                if let Some(path) = &options.backend_cache {
+                    if let Err(err) = std::fs::write(path, gpu.adapter_info.backend.to_str()) {
+                        log::warn!(
+                            "failed to remember the backend in {}: {err}",
+                            path.display()
+                        );
+                    }
+                }
+                return Ok(gpu);
+            }
+            Err(AppError::NoAdapter(tried)) => {
+                log::warn!("{:?} found no usable adapter", candidate.backends);
+                errors.extend(
+                    tried
+                        .into_iter()
+                        .map(|tried| format!("{:?}: {tried}", candidate.backends)),
+                );
+            }
+            Err(err) => {
+                log::warn!("{:?} failed: {err}", candidate.backends);
+                errors.push(format!("{:?}: {err}", candidate.backends));
+            }
+        }
+    }
+    Err(AppError::NoAdapter(errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_within_the_limit_are_kept() {
+        assert_eq!(clamp_size([8192, 8192], 8192), ([8192, 8192], 1.));
+        assert_eq!(clamp_size([1, 1], 2048), ([1, 1], 1.));
+    }
+
+    #[test]
+    fn sizes_beyond_the_limit_keep_the_aspect_ratio() {
+        // A window over two 8K monitors on an adapter limited to 8192.
+        let (size, scale) = clamp_size([15360, 4320], 8192);
+        assert_eq!(size, [8192, 2304]);
+        assert!((scale - 8192. / 15360.).abs() < 1e-6);
+
+        let (size, scale) = clamp_size([300, 4096], 1024);
+        assert_eq!(size, [75, 1024]);
+        assert_eq!(scale, 0.25);
+    }
+
+    #[test]
+    fn thin_sizes_stay_at_least_one_pixel() {
+        let (size, _) = clamp_size([100_000, 2], 4096);
+        assert_eq!(size, [4096, 1]);
+    }
+
+    #[test]
+    fn presets_are_clamped_to_the_adapter() {
+        let adapter = wgpu::Limits {
+            max_texture_dimension_2d: 1024,
+            max_bind_groups: 2,
+            min_uniform_buffer_offset_alignment: 512,
+            ..wgpu::Limits::default()
+        };
+        let limits = LimitsPreset::Default.resolve(&adapter);
+        assert_eq!(limits.max_texture_dimension_2d, 1024);
+        assert_eq!(limits.max_bind_groups, 2);
+        // Alignments are minimums, so the adapter's larger one wins.
+        assert_eq!(limits.min_uniform_buffer_offset_alignment, 512);
+        assert!(limits.check_limits(&adapter));
+
+        let webgl2 = LimitsPreset::DownlevelWebgl2.resolve(&adapter);
+        assert_eq!(
+            webgl2.max_storage_buffers_per_shader_stage,
+            wgpu::Limits::downlevel_webgl2_defaults().max_storage_buffers_per_shader_stage
+        );
+        assert_eq!(LimitsPreset::AdapterMax.resolve(&adapter), adapter);
+    }
+
+    #[test]
+    fn browsers_fall_back_from_webgpu_to_webgl2() {
+        use wgpu::Backends;
+        let browser = || vec![Backends::BROWSER_WEBGPU, Backends::GL];
+        let options = Options::default();
+        assert_eq!(
+            order_backends(browser(), &options),
+            [Backends::BROWSER_WEBGPU, Backends::GL]
+        );
+        // A failed WebGPU candidate moves on instead of retrying, and the GL
+        // one retries with WebGL2 limits when the default ones fail.
+        assert!(!gl_fallback(Backends::BROWSER_WEBGPU));
+        assert!(gl_fallback(Backends::GL));
+
+        let webgpu_only = Options {
+            backends: Some(Backends::BROWSER_WEBGPU),
+            ..Options::default()
+        };
+        assert_eq!(
+            order_backends(browser(), &webgpu_only),
+            [Backends::BROWSER_WEBGPU]
+        );
+        let with_fallback = Options {
+            backend_fallback: true,
+            ..webgpu_only
+        };
+        assert_eq!(
+            order_backends(browser(), &with_fallback),
+            [Backends::BROWSER_WEBGPU, Backends::GL]
+        );
+    }
+
+    #[test]
+    fn cached_backend_goes_first_once() {
+        use wgpu::Backends;
+        let path = std::env::temp_dir().join(format!("{}-backend-cache", std::process::id()));
+        std::fs::write(&path, "gl\n").unwrap();
+        let options = Options {
+            backend_cache: Some(path.clone()),
+            ..Options::default()
+        };
+        assert_eq!(
+            order_backends(vec![Backends::VULKAN, Backends::GL], &options),
+            [Backends::GL, Backends::VULKAN]
+        );
+        std::fs::remove_file(&path).unwrap();
+        // Without the file the platform's order stays.
+        assert_eq!(
+            order_backends(vec![Backends::VULKAN, Backends::GL], &options),
+            [Backends::VULKAN, Backends::GL]
+        );
+    }
+
+    #[test]
+    fn clamped_surface_renders_on_a_low_limit_device() {
+        let Some((device, queue)) = crate::testing::software_device_with_limits(
+            wgpu::Features::empty(),
+            wgpu::Limits {
+                max_texture_dimension_2d: 1024,
+                ..wgpu::Limits::downlevel_defaults()
+            },
+        ) else {
+            return;
+        };
+        let (size, scale) = clamp_surface_size(&device, [4096, 2048]);
+        assert_eq!((size, scale), ([1024, 512], 0.25));
+        // The UI is laid out for the window's size and drawn into the
+        // smaller surface at a quarter of the scale.
+        let ctx = egui::Context::default();
+        let raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(4096., 2048.),
+            )),
+            // As egui-winit is told, so that the font atlas fits too.
+            max_texture_side: Some(1024),
+            ..Default::default()
+        };
+        let output = ctx.run(raw_input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| ui.label("clamped"));
+        });
+        let primitives = ctx.tessellate(output.shapes, scale);
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut renderer = egui_wgpu::Renderer::new(&device, target.format(), None, 1, true);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        crate::egui_renderer::paint_egui(
+            &mut renderer,
+            &device,
+            &queue,
+            &mut encoder,
+            &target.create_view(&Default::default()),
+            wgpu::Color::BLACK,
+            &egui_wgpu::ScreenDescriptor {
+                size_in_pixels: size,
+                pixels_per_point: scale,
+            },
+            &primitives,
+            &output.textures_delta,
+            true,
+            None,
+        );
+        let (read_size, pixels) = crate::testing::read_texture(&device, &queue, encoder, &target);
+        assert_eq!(read_size, size);
+        // The panel covers the whole surface, the black clear color nowhere
+        // shows.
+        let fill = ctx.style().visuals.panel_fill.to_array();
+        let corner = |x: u32, y: u32| {
+            let at = ((y * size[0] + x) * 4) as usize;
+            <[u8; 4]>::try_from(&pixels[at..at + 4]).unwrap()
+        };
+        for (x, y) in [
+            (0, 0),
+            (size[0] - 1, 0),
+            (0, size[1] - 1),
+            (size[0] - 1, size[1] - 1),
+        ] {
+            assert_eq!(corner(x, y), fill);
+        }
+    }
+}
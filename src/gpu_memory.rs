@@ -0,0 +1,107 @@
+use web_time::{Duration, Instant};
+
+use egui::{ahash::HashMap, TextureId};
+
+const QUERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// GPU memory as reported by wgpu, where the backend exposes it.
+#[derive(Clone, Copy, Default)]
+pub struct BackendMemory {
+    pub allocated: Option<u64>,
+    pub reserved: Option<u64>,
+    pub buffers: Option<u64>,
+    pub textures: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct GpuMemory {
+    pub backend: BackendMemory,
+    pub egui_texture_bytes: u64,
+    pub native_texture_bytes: u64,
+    native_textures: HashMap<TextureId, u64>,
+    pub warning_threshold: Option<u64>,
+    above_threshold: bool,
+    last_query: Option<Instant>,
+}
+
+impl GpuMemory {
+    pub fn poll(&mut self, device: &wgpu::Device, egui_texture_bytes: u64) {
+        self.egui_texture_bytes = egui_texture_bytes;
+        if self
+            .last_query
+            .is_some_and(|last_query| last_query.elapsed() < QUERY_INTERVAL)
+        {
+            return;
+        }
+        self.last_query = Some(Instant::now());
+
+        let report = device.generate_allocator_report();
+        // All hal counters read zero unless wgpu is built with `counters`.
+        let counters = device.get_internal_counters().hal;
+        let counter = |value: isize| (value > 0).then_some(value as u64);
+        self.backend = BackendMemory {
+            allocated: report.as_ref().map(|r| r.total_allocated_bytes),
+            reserved: report.as_ref().map(|r| r.total_reserved_bytes),
+            buffers: counter(counters.buffer_memory.read()),
+            textures: counter(counters.texture_memory.read()),
+        };
+
+        self.check_threshold();
+    }
+
+    pub fn record_native_texture(&mut self, id: TextureId, bytes: u64) {
+        if let Some(old) = self.native_textures.insert(id, bytes) {
+            self.native_texture_bytes -= old;
+        }
+        self.native_texture_bytes += bytes;
+    }
+
+    pub fn record_native_texture_freed(&mut self, id: TextureId) {
+        if let Some(bytes) = self.native_textures.remove(&id) {
+            self.native_texture_bytes -= bytes;
+        }
+    }
+
+    /// Our own ledger of egui and native texture memory, available on every backend.
+    pub fn ledger_bytes(&self) -> u64 {
+        self.egui_texture_bytes + self.native_texture_bytes
+    }
+
+    /// The best available total: the backend allocator if it reports, otherwise the ledger.
+    pub fn total_bytes(&self) -> u64 {
+        self.backend
+            .allocated
+            .or_else(|| Some(self.backend.buffers? + self.backend.textures?))
+            .unwrap_or_else(|| self.ledger_bytes())
+    }
+
+    fn check_threshold(&mut self) {
+        let Some(threshold) = self.warning_threshold else {
+            return;
+        };
+        let total = self.total_bytes();
+        let above_threshold = total > threshold;
+        if above_threshold && !self.above_threshold {
+            log::warn!("GPU memory usage {total} bytes exceeds the threshold of {threshold} bytes");
+        }
+        self.above_threshold = above_threshold;
+    }
+}
+
+pub fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let size = texture
+                .size()
+                .mip_level_size(level, texture.dimension())
+                .physical_size(format);
+            let (block_width, block_height) = format.block_dimensions();
+            (size.width / block_width) as u64
+                * (size.height / block_height) as u64
+                * size.depth_or_array_layers as u64
+                * block_size
+        })
+        .sum()
+}
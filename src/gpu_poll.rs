@@ -0,0 +1,206 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// How often outstanding operations are polled for. Polling without waiting
+/// keeps the thread off the device's fence, which submits need.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The result of an async wgpu operation, filled in by its callback.
+pub struct GpuFuture<T> {
+    result: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> GpuFuture<T> {
+    /// Takes the result once the operation completed.
+    pub fn try_take(&self) -> Option<T> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+struct Shared {
+    device: Arc<wgpu::Device>,
+    egui_ctx: egui::Context,
+    pending: AtomicUsize,
+    stop: AtomicBool,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Completes wgpu callbacks, which only run while the device is polled.
+/// Frames poll once after submit; a thread polls while operations are
+/// outstanding and no frames are rendered, e.g. in reactive mode. Each
+/// completion requests a repaint so the UI can pick up the result.
+#[derive(Clone)]
+pub struct GpuPoller {
+    shared: Arc<Shared>,
+}
+
+impl GpuPoller {
+    pub fn new(device: Arc<wgpu::Device>, egui_ctx: egui::Context) -> std::io::Result<Self> {
+        let shared = Arc::new(Shared {
+            device,
+            egui_ctx,
+            pending: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+            thread: Mutex::new(None),
+        });
+        let thread = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("gpu poll".to_owned())
+                .spawn(move || {
+                    profiling::register_thread!("gpu poll");
+                    while !shared.stop.load(Ordering::Acquire) {
+                        if shared.pending.load(Ordering::Acquire) == 0 {
+                            std::thread::park();
+                            continue;
+                        }
+                        shared.device.poll(wgpu::Maintain::Poll);
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                })?
+        };
+        *shared.thread.lock().unwrap() = Some(thread);
+        Ok(Self { shared })
+    }
+
+    /// Maps `slice` and completes the mapping even while no frames render.
+    pub fn map_buffer(
+        &self,
+        slice: wgpu::BufferSlice<'_>,
+        mode: wgpu::MapMode,
+    ) -> GpuFuture<Result<(), wgpu::BufferAsyncError>> {
+        let result = Arc::new(Mutex::new(None));
+        let future = GpuFuture {
+            result: result.clone(),
+        };
+        self.shared.pending.fetch_add(1, Ordering::AcqRel);
+        let shared = self.shared.clone();
+        slice.map_async(mode, move |map_result| {
+            *result.lock().unwrap() = Some(map_result);
+            shared.pending.fetch_sub(1, Ordering::AcqRel);
+            shared.egui_ctx.request_repaint();
+        });
+        self.wake();
+        future
+    }
+
+    /// Runs callbacks that are ready. Call after each submit.
+    pub fn poll(&self) {
+        self.shared.device.poll(wgpu::Maintain::Poll);
+    }
+
+    fn wake(&self) {
+        if let Some(thread) = &*self.shared.thread.lock().unwrap() {
+            thread.thread().unpark();
+        }
+    }
+
+    /// Stops the polling thread. Outstanding operations complete with the
+    /// next [`Self::poll`], if any.
+    pub fn stop(&self) {
+        self.shared.stop.store(true, Ordering::Release);
+        let thread = self.shared.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use super::*;
+    use crate::testing;
+
+    fn readback_buffer(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Buffer {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("poller test buffer"),
+            size: 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&buffer, 0, &[1, 2, 3, 4]);
+        queue.submit([]);
+        buffer
+    }
+
+    fn wait_for<T>(future: &GpuFuture<T>) -> Option<T> {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while std::time::Instant::now() < deadline {
+            if let Some(result) = future.try_take() {
+                return Some(result);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        None
+    }
+
+    #[test]
+    fn thread_completes_mappings_and_requests_a_repaint() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let ctx = egui::Context::default();
+        let repaints = Arc::new(AtomicU32::new(0));
+        ctx.set_request_repaint_callback({
+            let repaints = repaints.clone();
+            move |_| {
+                repaints.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let poller = GpuPoller::new(device.clone(), ctx).unwrap();
+        let buffer = readback_buffer(&device, &queue);
+
+        // Nothing here polls the device; only the poller's thread does.
+        let future = poller.map_buffer(buffer.slice(..), wgpu::MapMode::Read);
+        assert!(matches!(wait_for(&future), Some(Ok(()))));
+        assert_eq!(&*buffer.slice(..).get_mapped_range(), &[1, 2, 3, 4]);
+        assert!(repaints.load(Ordering::Relaxed) >= 1);
+        assert_eq!(poller.shared.pending.load(Ordering::Acquire), 0);
+        // Taken once.
+        assert!(future.try_take().is_none());
+        poller.stop();
+    }
+
+    #[test]
+    fn thread_sleeps_again_after_completions() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let poller = GpuPoller::new(device.clone(), egui::Context::default()).unwrap();
+        for _ in 0..3 {
+            let buffer = readback_buffer(&device, &queue);
+            let future = poller.map_buffer(buffer.slice(..), wgpu::MapMode::Read);
+            assert!(matches!(wait_for(&future), Some(Ok(()))));
+            buffer.unmap();
+        }
+        poller.stop();
+        assert!(poller.shared.thread.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn poll_completes_after_stop() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let poller = GpuPoller::new(device.clone(), egui::Context::default()).unwrap();
+        poller.stop();
+        let buffer = readback_buffer(&device, &queue);
+        let future = poller.map_buffer(buffer.slice(..), wgpu::MapMode::Read);
+        // Without the thread only frames poll.
+        let result = (0..5000).find_map(|_| {
+            poller.poll();
+            std::thread::sleep(POLL_INTERVAL);
+            future.try_take()
+        });
+        assert!(matches!(result, Some(Ok(()))));
+    }
+}
@@ -0,0 +1,253 @@
+use std::time::Duration;
+
+use wgpu::{
+    Buffer, BufferAsyncError, CommandEncoder, Device, QuerySet, Queue, RenderPassTimestampWrites,
+};
+
+use crate::gpu_poll::{GpuFuture, GpuPoller};
+
+const QUERY_COUNT: u32 = 2;
+const QUERY_SIZE: u64 = std::mem::size_of::<u64>() as u64 * QUERY_COUNT as u64;
+
+enum TimerState {
+    Idle,
+    Recorded,
+    Mapping(GpuFuture<Result<(), BufferAsyncError>>),
+}
+
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    period: f32,
+    state: TimerState,
+    poller: GpuPoller,
+    last: Option<Duration>,
+    #[cfg(feature = "tracy")]
+    tracy: Option<TracyGpu>,
+}
+
+impl GpuTimer {
+    /// `backend` tells Tracy how to read the timestamps.
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        poller: GpuPoller,
+        #[cfg_attr(not(feature = "tracy"), allow(unused_variables))] backend: wgpu::Backend,
+    ) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("egui timestamp queries"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("egui timestamp resolve buffer"),
+            size: QUERY_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("egui timestamp readback buffer"),
+            size: QUERY_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            state: TimerState::Idle,
+            poller,
+            last: None,
+            #[cfg(feature = "tracy")]
+            tracy: TracyGpu::new(device, queue, backend),
+        }
+    }
+
+    pub fn timestamp_writes(&mut self) -> Option<RenderPassTimestampWrites<'_>> {
+        if !matches!(self.state, TimerState::Idle) {
+            return None;
+        }
+        self.state = TimerState::Recorded;
+        #[cfg(feature = "tracy")]
+        if let Some(tracy) = &mut self.tracy {
+            tracy.begin();
+        }
+        Some(RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        })
+    }
+
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        if !matches!(self.state, TimerState::Recorded) {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            QUERY_SIZE,
+        );
+    }
+
+    pub fn after_submit(&mut self) {
+        if !matches!(self.state, TimerState::Recorded) {
+            return;
+        }
+        self.state = TimerState::Mapping(
+            self.poller
+                .map_buffer(self.readback_buffer.slice(..), wgpu::MapMode::Read),
+        );
+        #[cfg(feature = "tracy")]
+        if let Some(tracy) = &mut self.tracy {
+            tracy.end();
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<Duration> {
+        if let TimerState::Mapping(future) = &self.state {
+            match future.try_take() {
+                Some(Ok(())) => {
+                    let view = self.readback_buffer.slice(..).get_mapped_range();
+                    let begin = u64::from_le_bytes(view[0..8].try_into().unwrap());
+                    let end = u64::from_le_bytes(view[8..16].try_into().unwrap());
+                    let nanos = end.saturating_sub(begin) as f64 * self.period as f64;
+                    self.last = Some(Duration::from_nanos(nanos as u64));
+                    #[cfg(feature = "tracy")]
+                    if let Some(tracy) = &mut self.tracy {
+                        tracy.upload(begin, end);
+                    }
+                    drop(view);
+                    self.readback_buffer.unmap();
+                    self.state = TimerState::Idle;
+                }
+                Some(Err(_)) => self.state = TimerState::Idle,
+                None => {}
+            }
+        }
+        self.last
+    }
+}
+
+/// The egui pass as a zone on Tracy's GPU timeline, from the same timestamp
+/// queries. Only while a Tracy client is running.
+#[cfg(feature = "tracy")]
+struct TracyGpu {
+    context: profiling::tracy_client::GpuContext,
+    /// The zone of the pass being timed, until its timestamps are read.
+    span: Option<profiling::tracy_client::GpuSpan>,
+}
+
+#[cfg(feature = "tracy")]
+impl TracyGpu {
+    fn new(device: &Device, queue: &Queue, backend: wgpu::Backend) -> Option<Self> {
+        use profiling::tracy_client::{Client, GpuContextType};
+
+        let client = Client::running()?;
+        let ty = match backend {
+            wgpu::Backend::Vulkan => GpuContextType::Vulkan,
+            wgpu::Backend::Dx12 => GpuContextType::Direct3D12,
+            wgpu::Backend::Gl => GpuContextType::OpenGL,
+            _ => GpuContextType::Invalid,
+        };
+        let gpu_timestamp = gpu_timestamp_now(device, queue)?;
+        match client.new_gpu_context(
+            Some("wgpu"),
+            ty,
+            gpu_timestamp as i64,
+            queue.get_timestamp_period(),
+        ) {
+            Ok(context) => Some(Self {
+                context,
+                span: None,
+            }),
+            Err(err) => {
+                log::warn!("cannot create Tracy's GPU context: {err:?}");
+                None
+            }
+        }
+    }
+
+    fn begin(&mut self) {
+        self.span = self
+            .context
+            .span_alloc("egui pass", "paint_egui", file!(), line!())
+            .ok();
+    }
+
+    /// The pass is submitted; its timestamps come later.
+    fn end(&mut self) {
+        if let Some(span) = &mut self.span {
+            span.end_zone();
+        }
+    }
+
+    fn upload(&mut self, begin: u64, end: u64) {
+        if let Some(span) = self.span.take() {
+            span.upload_timestamp_start(begin as i64);
+            span.upload_timestamp_end(end as i64);
+        }
+    }
+}
+
+/// The GPU's clock now, which Tracy lines its timeline up with the CPU's
+/// by. Waits for the GPU, so only at startup.
+#[cfg(feature = "tracy")]
+fn gpu_timestamp_now(device: &Device, queue: &Queue) -> Option<u64> {
+    let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+        label: Some("tracy calibration query"),
+        ty: wgpu::QueryType::Timestamp,
+        count: 1,
+    });
+    let buffer = |label, usage| {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: 8,
+            usage,
+            mapped_at_creation: false,
+        })
+    };
+    let resolve_buffer = buffer(
+        "tracy calibration resolve buffer",
+        wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+    );
+    let readback_buffer = buffer(
+        "tracy calibration readback buffer",
+        wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+    );
+    let mut encoder = device.create_command_encoder(&Default::default());
+    // An empty pass, as writing timestamps between passes needs a feature
+    // of its own.
+    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        label: Some("tracy calibration pass"),
+        timestamp_writes: Some(wgpu::ComputePassTimestampWrites {
+            query_set: &query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: None,
+        }),
+    });
+    encoder.resolve_query_set(&query_set, 0..1, &resolve_buffer, 0);
+    encoder.copy_buffer_to_buffer(&resolve_buffer, 0, &readback_buffer, 0, 8);
+    queue.submit(Some(encoder.finish()));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    readback_buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().ok()?.ok()?;
+    let timestamp = u64::from_le_bytes(
+        readback_buffer.slice(..).get_mapped_range()[..8]
+            .try_into()
+            .unwrap(),
+    );
+    readback_buffer.unmap();
+    Some(timestamp)
+}
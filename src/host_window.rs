@@ -0,0 +1,134 @@
+//! The window as the renderer sees it. Sizes, input and egui's viewport info
+//! go through [`HostWindow`], so that tests can run the renderer on a window
+//! that only exists in memory, see `testing::HeadlessWindow`.
+
+use std::sync::Arc;
+
+use egui::{ahash::HashSet, ViewportCommand, ViewportInfo};
+use egui_winit::{
+    winit::{
+        dpi::PhysicalSize, event::WindowEvent, raw_window_handle::HasDisplayHandle, window::Window,
+    },
+    ActionRequested, EventResponse,
+};
+
+/// The display handle is for egui-winit's clipboard.
+pub trait HostWindow: HasDisplayHandle {
+    /// The native window, for platform features like the kiosk mode or
+    /// click-through, which are off without one.
+    fn native(&self) -> Option<&Arc<Window>>;
+
+    fn inner_size(&self) -> PhysicalSize<u32>;
+
+    fn scale_factor(&self) -> f64;
+
+    fn is_minimized(&self) -> Option<bool>;
+
+    fn set_visible(&self, visible: bool);
+
+    fn pre_present_notify(&self);
+
+    /// Turns a window event into egui input, like
+    /// [`egui_winit::State::on_window_event`].
+    fn on_window_event(
+        &self,
+        egui_winit: &mut egui_winit::State,
+        event: &WindowEvent,
+    ) -> EventResponse;
+
+    /// The input gathered since the last frame, with the window's size and
+    /// scale, like [`egui_winit::State::take_egui_input`].
+    fn take_egui_input(&self, egui_winit: &mut egui_winit::State) -> egui::RawInput;
+
+    /// Applies the cursor, clipboard, URL and IME output.
+    fn handle_platform_output(
+        &self,
+        egui_winit: &mut egui_winit::State,
+        platform_output: egui::PlatformOutput,
+    );
+
+    fn update_viewport_info(
+        &self,
+        info: &mut ViewportInfo,
+        egui_ctx: &egui::Context,
+        is_init: bool,
+    );
+
+    fn process_viewport_commands(
+        &self,
+        egui_ctx: &egui::Context,
+        info: &mut ViewportInfo,
+        commands: Vec<ViewportCommand>,
+        actions_requested: &mut HashSet<ActionRequested>,
+    );
+}
+
+impl HostWindow for Arc<Window> {
+    fn native(&self) -> Option<&Arc<Window>> {
+        Some(self)
+    }
+
+    fn inner_size(&self) -> PhysicalSize<u32> {
+        Window::inner_size(self)
+    }
+
+    fn scale_factor(&self) -> f64 {
+        Window::scale_factor(self)
+    }
+
+    fn is_minimized(&self) -> Option<bool> {
+        Window::is_minimized(self)
+    }
+
+    fn set_visible(&self, visible: bool) {
+        Window::set_visible(self, visible);
+    }
+
+    fn pre_present_notify(&self) {
+        Window::pre_present_notify(self);
+    }
+
+    fn on_window_event(
+        &self,
+        egui_winit: &mut egui_winit::State,
+        event: &WindowEvent,
+    ) -> EventResponse {
+        egui_winit.on_window_event(self, event)
+    }
+
+    fn take_egui_input(&self, egui_winit: &mut egui_winit::State) -> egui::RawInput {
+        egui_winit.take_egui_input(self)
+    }
+
+    fn handle_platform_output(
+        &self,
+        egui_winit: &mut egui_winit::State,
+        platform_output: egui::PlatformOutput,
+    ) {
+        egui_winit.handle_platform_output(self, platform_output);
+    }
+
+    fn update_viewport_info(
+        &self,
+        info: &mut ViewportInfo,
+        egui_ctx: &egui::Context,
+        is_init: bool,
+    ) {
+        egui_winit::update_viewport_info(info, egui_ctx, self, is_init);
+    }
+
+    fn process_viewport_commands(
+        &self,
+        egui_ctx: &egui::Context,
+        info: &mut ViewportInfo,
+        commands: Vec<ViewportCommand>,
+        actions_requested: &mut HashSet<ActionRequested>,
+    ) {
+        egui_winit::process_viewport_commands(egui_ctx, info, commands, self, actions_requested);
+    }
+}
+
+/// [`egui_winit::pixels_per_point`] for any [`HostWindow`].
+pub fn pixels_per_point(egui_ctx: &egui::Context, window: &dyn HostWindow) -> f32 {
+    egui_ctx.zoom_factor() * window.scale_factor() as f32
+}
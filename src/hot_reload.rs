@@ -0,0 +1,420 @@
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr},
+    path::PathBuf,
+};
+
+use anyhow::Context as _;
+use hot_reload_abi::{decode_output, encode_input, Output, ABI};
+use libloading::Library;
+
+use crate::file_watch::FileWatcher;
+
+type AbiFn = unsafe extern "C" fn() -> *const c_char;
+/// Builds the library's state from what `egui_hot_save` wrote, or a fresh
+/// one from zero bytes. Null if it cannot read them.
+type NewFn = unsafe extern "C" fn(*const u8, usize) -> *mut c_void;
+/// Runs the UI for a frame from the encoded input, and writes the encoded
+/// output by calling the `WriteFn` with the output pointer. `false` if it
+/// panicked.
+type UpdateFn = unsafe extern "C" fn(*mut c_void, *const u8, usize, WriteFn, *mut c_void) -> bool;
+type WriteFn = extern "C" fn(*mut c_void, *const u8, usize);
+/// Serializes the state by calling the `WriteFn` with the output pointer.
+type SaveFn = unsafe extern "C" fn(*const c_void, WriteFn, *mut c_void);
+type DropFn = unsafe extern "C" fn(*mut c_void);
+
+/// The functions of one loaded copy of the library.
+struct HotLibrary {
+    new: NewFn,
+    update: UpdateFn,
+    save: SaveFn,
+    drop: DropFn,
+}
+
+impl HotLibrary {
+    /// # Safety
+    ///
+    /// Loading runs the library's initializers, and its functions must have
+    /// the signatures above.
+    unsafe fn load(library: &Library) -> anyhow::Result<Self> {
+        let abi = *library.get::<AbiFn>(b"egui_hot_abi\0")?;
+        let abi = CStr::from_ptr(abi());
+        anyhow::ensure!(
+            abi == ABI,
+            "the library was built for {abi:?}, the app for {ABI:?}"
+        );
+        Ok(Self {
+            new: *library.get(b"egui_hot_new\0")?,
+            update: *library.get(b"egui_hot_update\0")?,
+            save: *library.get(b"egui_hot_save\0")?,
+            drop: *library.get(b"egui_hot_drop\0")?,
+        })
+    }
+
+    /// `None` if the UI panicked.
+    fn update(&self, app: *mut c_void, input: &[u8]) -> Option<Vec<u8>> {
+        let mut bytes = Vec::new();
+        // SAFETY: `app` came from this library's `new`, and `input` is
+        // readable for its length.
+        let ok = unsafe {
+            (self.update)(
+                app,
+                input.as_ptr(),
+                input.len(),
+                write,
+                (&mut bytes as *mut Vec<u8>).cast(),
+            )
+        };
+        ok.then_some(bytes)
+    }
+
+    fn save(&self, app: *mut c_void) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // SAFETY: `app` came from this library's `new`.
+        unsafe { (self.save)(app, write, (&mut bytes as *mut Vec<u8>).cast()) };
+        bytes
+    }
+}
+
+extern "C" fn write(out: *mut c_void, data: *const u8, len: usize) {
+    // SAFETY: `out` is a `Vec<u8>` of the caller's, and the library passes
+    // `len` readable bytes.
+    unsafe { (*out.cast::<Vec<u8>>()).extend_from_slice(std::slice::from_raw_parts(data, len)) };
+}
+
+/// Runs the UI from a `cdylib` and swaps in a rebuilt one while the app
+/// runs, see `examples/hot_reload_ui`. The library's state moves across as
+/// the bytes it serializes itself; the window, device and egui context stay.
+///
+/// Nothing of the library's egui crosses into the app's: the library runs
+/// its own context from the input in [`hot_reload_abi`] bytes, and the app
+/// paints the meshes and uploads the textures that come back.
+///
+/// Libraries are never unloaded: the standard library may have registered
+/// thread-local destructors from one on the UI thread.
+pub struct HotReload {
+    path: PathBuf,
+    libraries: Vec<Library>,
+    copies: Vec<PathBuf>,
+    current: Option<(HotLibrary, *mut c_void)>,
+    /// The app's texture for each of the current library's.
+    textures: HashMap<egui::TextureId, egui::TextureId>,
+    panicked: bool,
+    /// Started with the first frame, which brings the context to wake up.
+    watcher: Option<FileWatcher>,
+    error: Option<String>,
+}
+
+// SAFETY: the library's state is only used from the thread that runs the UI,
+// and the template asserts that it is `Send` for the move there.
+unsafe impl Send for HotReload {}
+
+impl HotReload {
+    /// `path` is the library cargo builds, e.g.
+    /// `target/debug/libhot_reload_ui.so`.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            libraries: Vec::new(),
+            copies: Vec::new(),
+            current: None,
+            textures: HashMap::new(),
+            panicked: false,
+            watcher: None,
+            error: None,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context) {
+        match &self.watcher {
+            Some(watcher) => {
+                if !watcher.changes().is_empty() {
+                    self.load(ctx);
+                }
+            }
+            None => {
+                let repaint_ctx = ctx.clone();
+                match FileWatcher::new(move || repaint_ctx.request_repaint()) {
+                    Ok(watcher) => {
+                        watcher.watch(&self.path);
+                        self.watcher = Some(watcher);
+                        self.load(ctx);
+                    }
+                    Err(err) => self.error = Some(format!("cannot watch the library: {err}")),
+                }
+            }
+        }
+
+        if let Some(error) = &self.error {
+            egui::TopBottomPanel::bottom("hot_reload_error").show(ctx, |ui| {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Hot reload: {error}"));
+            });
+        }
+        if let Some((library, app)) = &self.current {
+            if self.panicked {
+                return;
+            }
+            // The library's screen is what the error panel leaves.
+            let mut raw_input = ctx.input(|i| i.raw.clone());
+            raw_input.screen_rect = Some(ctx.available_rect());
+            let input = encode_input(&raw_input, ctx.pixels_per_point());
+            match library
+                .update(*app, &input)
+                .map(|bytes| decode_output(&bytes))
+            {
+                Some(Some(output)) => self.apply(ctx, output),
+                Some(None) => {
+                    self.panicked = true;
+                    self.error = Some("the library's output does not decode".to_owned());
+                }
+                None => {
+                    self.panicked = true;
+                    self.error = Some("the UI panicked, see the log".to_owned());
+                }
+            }
+        }
+    }
+
+    /// Paints the library's frame into the app's.
+    fn apply(&mut self, ctx: &egui::Context, output: Output) {
+        let tex_manager = ctx.tex_manager();
+        let mut tex_manager = tex_manager.write();
+        for (id, delta) in output.textures_delta.set {
+            match self.textures.get(&id) {
+                Some(app_id) => tex_manager.set(*app_id, delta),
+                None => {
+                    let app_id =
+                        tex_manager.alloc(format!("hot reload {id:?}"), delta.image, delta.options);
+                    self.textures.insert(id, app_id);
+                }
+            }
+        }
+        for id in output.textures_delta.free {
+            if let Some(app_id) = self.textures.remove(&id) {
+                tex_manager.free(app_id);
+            }
+        }
+        drop(tex_manager);
+
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        for (clip_rect, mut mesh) in output.meshes {
+            let Some(texture_id) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            mesh.texture_id = *texture_id;
+            painter.with_clip_rect(clip_rect).add(mesh);
+        }
+
+        ctx.set_cursor_icon(output.cursor_icon);
+        if !output.copied_text.is_empty() {
+            ctx.copy_text(output.copied_text);
+        }
+        if let Some(open_url) = output.open_url {
+            ctx.open_url(open_url);
+        }
+        if let Some(delay) = output.repaint_after {
+            ctx.request_repaint_after(delay);
+        }
+    }
+
+    fn load(&mut self, ctx: &egui::Context) {
+        if !self.path.exists() {
+            if self.current.is_none() {
+                self.error = Some(format!("waiting for {}", self.path.display()));
+            }
+            return;
+        }
+        match self.reload(ctx) {
+            Ok(()) => {
+                log::info!("hot reloaded {}", self.path.display());
+                self.error = None;
+                self.panicked = false;
+            }
+            Err(err) => {
+                log::error!("hot reload: {err:#}");
+                self.error = Some(format!("{err:#}, keeping the previous library"));
+            }
+        }
+    }
+
+    fn reload(&mut self, ctx: &egui::Context) -> anyhow::Result<()> {
+        // A copy of its own for every load: cargo overwrites the original,
+        // and the loader would hand out the cached library for its path.
+        let file_name = self.path.file_name().context("no file name")?;
+        let copy = std::env::temp_dir().join(format!(
+            "{}-{}-{}",
+            std::process::id(),
+            self.copies.len(),
+            file_name.to_string_lossy()
+        ));
+        std::fs::copy(&self.path, &copy)
+            .with_context(|| format!("cannot copy {}", self.path.display()))?;
+        self.copies.push(copy.clone());
+        // SAFETY: the library is one built for this, see `HotLibrary::load`.
+        let (library, functions) = unsafe {
+            let library = Library::new(&copy)?;
+            let functions = HotLibrary::load(&library)?;
+            (library, functions)
+        };
+
+        let state = self
+            .current
+            .as_ref()
+            .map(|(library, app)| library.save(*app))
+            .unwrap_or_default();
+        // SAFETY: `state` is readable for its length.
+        let app = unsafe { (functions.new)(state.as_ptr(), state.len()) };
+        anyhow::ensure!(
+            !app.is_null(),
+            "the new library cannot read the state, its layout may have changed"
+        );
+        if let Some((library, app)) = self.current.take() {
+            // SAFETY: `app` came from this library's `new`.
+            unsafe { (library.drop)(app) };
+        }
+        // The new library's context starts over with its textures.
+        let tex_manager = ctx.tex_manager();
+        for (_, app_id) in self.textures.drain() {
+            tex_manager.write().free(app_id);
+        }
+        self.current = Some((functions, app));
+        self.libraries.push(library);
+        Ok(())
+    }
+}
+
+impl Drop for HotReload {
+    fn drop(&mut self) {
+        if let Some((library, app)) = self.current.take() {
+            // SAFETY: `app` came from this library's `new`.
+            unsafe { (library.drop)(app) };
+        }
+        // See the type's docs.
+        for library in self.libraries.drain(..) {
+            std::mem::forget(library);
+        }
+        // Fails for mapped files on Windows, which leaves them to the OS's
+        // temp cleanup.
+        for copy in &self.copies {
+            let _ = std::fs::remove_file(copy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, process::Command};
+
+    use super::*;
+
+    /// Builds `examples/hot_reload_ui` and returns its library.
+    fn build_template() -> PathBuf {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let status = Command::new(std::env::var_os("CARGO").unwrap_or("cargo".into()))
+            .current_dir(manifest_dir)
+            .args(["build", "--quiet", "-p", "hot_reload_ui"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+            .map_or_else(|| manifest_dir.join("target"), PathBuf::from);
+        target_dir.join("debug").join(format!(
+            "{}hot_reload_ui{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ))
+    }
+
+    fn saved(hot_reload: &HotReload) -> String {
+        let (library, app) = hot_reload.current.as_ref().unwrap();
+        String::from_utf8(library.save(*app)).unwrap()
+    }
+
+    #[test]
+    fn loads_reloads_and_refuses() {
+        let ctx = egui::Context::default();
+        let mut hot_reload = HotReload::new(build_template());
+        hot_reload.load(&ctx);
+        assert_eq!(hot_reload.error, None);
+        assert_eq!(saved(&hot_reload), "name=\nage=0\n");
+
+        // The library's frame comes back as meshes and textures, which end
+        // up in the app's.
+        let output = {
+            let (library, app) = hot_reload.current.as_ref().unwrap();
+            assert_eq!(library.update(*app, b"not input"), None);
+            let raw_input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(400., 300.),
+                )),
+                ..Default::default()
+            };
+            let bytes = library.update(*app, &encode_input(&raw_input, 2.)).unwrap();
+            decode_output(&bytes).unwrap()
+        };
+        assert!(!output.meshes.is_empty());
+        let mut output = Some(output);
+        let full_output = ctx.run(Default::default(), |ctx| {
+            hot_reload.apply(ctx, output.take().unwrap());
+        });
+        let font_texture = hot_reload.textures[&egui::TextureId::default()];
+        assert!(full_output
+            .textures_delta
+            .set
+            .iter()
+            .any(|(id, _)| *id == font_texture));
+        assert!(full_output.shapes.iter().any(|clipped| matches!(
+            &clipped.shape,
+            egui::Shape::Mesh(mesh) if mesh.texture_id == font_texture
+        )));
+
+        // Give the state something to carry over.
+        let (library, app) = hot_reload.current.take().unwrap();
+        // SAFETY: `app` came from this library's `new`, and the bytes are
+        // readable.
+        unsafe {
+            (library.drop)(app);
+            let state = b"name=Ferris\nage=7\n";
+            let app = (library.new)(state.as_ptr(), state.len());
+            hot_reload.current = Some((library, app));
+        }
+        hot_reload.load(&ctx);
+        assert_eq!(hot_reload.error, None);
+        assert_eq!(hot_reload.libraries.len(), 2);
+        assert_eq!(saved(&hot_reload), "name=Ferris\nage=7\n");
+
+        // Something that is not a library keeps the last one running.
+        let broken = std::env::temp_dir().join(format!(
+            "{}-broken{}",
+            std::process::id(),
+            std::env::consts::DLL_SUFFIX
+        ));
+        std::fs::write(&broken, b"not a library").unwrap();
+        let path = std::mem::replace(&mut hot_reload.path, broken.clone());
+        hot_reload.load(&ctx);
+        std::fs::remove_file(&broken).unwrap();
+        assert!(hot_reload
+            .error
+            .as_ref()
+            .unwrap()
+            .ends_with("keeping the previous library"));
+        assert_eq!(hot_reload.libraries.len(), 2);
+        assert_eq!(saved(&hot_reload), "name=Ferris\nage=7\n");
+        hot_reload.path = path;
+
+        // A rebuild reaches the app through the file watcher.
+        let _ = ctx.run(Default::default(), |ctx| hot_reload.update(ctx));
+        assert_eq!(hot_reload.libraries.len(), 3);
+        let bytes = std::fs::read(&hot_reload.path).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        std::fs::write(&hot_reload.path, bytes).unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while hot_reload.libraries.len() == 3 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let _ = ctx.run(Default::default(), |ctx| hot_reload.update(ctx));
+        }
+        assert_eq!(hot_reload.libraries.len(), 4);
+        assert_eq!(saved(&hot_reload), "name=Ferris\nage=7\n");
+    }
+}
@@ -0,0 +1,98 @@
+use web_time::{Duration, Instant};
+
+use egui_winit::winit::event::WindowEvent;
+
+fn activity_id() -> egui::Id {
+    egui::Id::new("idle_activity")
+}
+
+/// Keeps the app out of idle, e.g. while a background task streams updates.
+/// Can be called from any thread.
+pub fn report_activity(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(activity_id(), Instant::now()));
+}
+
+pub fn is_user_input(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::KeyboardInput { .. }
+            | WindowEvent::Ime(_)
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::Touch(_)
+            | WindowEvent::PinchGesture { .. }
+            | WindowEvent::PanGesture { .. }
+            | WindowEvent::RotationGesture { .. }
+            | WindowEvent::DoubleTapGesture { .. }
+            | WindowEvent::DroppedFile(_)
+    )
+}
+
+pub struct IdleTracker {
+    threshold: Duration,
+    repaint_interval: Duration,
+    stop_animations: bool,
+    last_activity: Instant,
+    last_repaint: Instant,
+    idle: bool,
+    saved_animation_time: Option<f32>,
+}
+
+impl IdleTracker {
+    pub fn new(threshold: Duration, repaint_interval: Duration, stop_animations: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            threshold,
+            repaint_interval,
+            stop_animations,
+            last_activity: now,
+            last_repaint: now,
+            idle: false,
+            saved_animation_time: None,
+        }
+    }
+
+    /// Returns `true` if this input woke the app from idle.
+    pub fn on_input(&mut self) -> bool {
+        self.last_activity = Instant::now();
+        self.idle
+    }
+
+    /// Updates the idle state at the start of a frame and returns the new
+    /// state when it changed.
+    pub fn on_frame(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let now = Instant::now();
+        self.last_repaint = now;
+        if let Some(activity) = ctx.data(|d| d.get_temp::<Instant>(activity_id())) {
+            self.last_activity = self.last_activity.max(activity);
+        }
+
+        let idle = now.saturating_duration_since(self.last_activity) >= self.threshold;
+        if idle == self.idle {
+            return None;
+        }
+        self.idle = idle;
+
+        if self.stop_animations {
+            if idle {
+                ctx.style_mut(|style| {
+                    self.saved_animation_time = Some(style.animation_time);
+                    style.animation_time = 0.;
+                });
+            } else if let Some(animation_time) = self.saved_animation_time.take() {
+                ctx.style_mut(|style| style.animation_time = animation_time);
+            }
+        }
+        Some(idle)
+    }
+
+    /// Clamps repaints requested while idle to the idle repaint rate.
+    pub fn throttle(&self, when: Instant) -> Instant {
+        if self.idle {
+            when.max(self.last_repaint + self.repaint_interval)
+        } else {
+            when
+        }
+    }
+}
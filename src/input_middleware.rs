@@ -0,0 +1,223 @@
+use egui::{Event, Key, Modifiers, PointerButton};
+
+fn blocked_id() -> egui::Id {
+    egui::Id::new("input_blocked")
+}
+
+/// Makes [`InputBlocker`] drop input from the next frame on, until called
+/// with false.
+pub fn set_input_blocked(ctx: &egui::Context, blocked: bool) {
+    ctx.data_mut(|d| d.insert_temp(blocked_id(), blocked));
+}
+
+pub fn is_input_blocked(ctx: &egui::Context) -> bool {
+    ctx.data(|d| d.get_temp(blocked_id())).unwrap_or(false)
+}
+
+/// The part of a frame's input a middleware may change. Time, viewports and
+/// the rest of [`egui::RawInput`] stay out of reach.
+pub struct InputView<'a> {
+    pub ctx: &'a egui::Context,
+    /// What egui gets: drop, change or add events in place.
+    pub events: &'a mut Vec<Event>,
+    /// Held at the end of the frame.
+    pub modifiers: Modifiers,
+}
+
+/// Sees each frame's input after egui-winit collected it and before egui
+/// does. Middlewares run in the order they were added, each on what the
+/// previous one left.
+pub trait InputMiddleware {
+    fn process(&mut self, input: InputView<'_>);
+}
+
+impl<F: FnMut(InputView<'_>)> InputMiddleware for F {
+    fn process(&mut self, input: InputView<'_>) {
+        self(input)
+    }
+}
+
+/// Runs `middleware` in order over a frame's events, each on what the
+/// previous one left.
+pub fn run<'a>(
+    middleware: impl IntoIterator<Item = &'a mut Box<dyn InputMiddleware>>,
+    ctx: &egui::Context,
+    raw_input: &mut egui::RawInput,
+) {
+    for middleware in middleware {
+        middleware.process(InputView {
+            ctx,
+            events: &mut raw_input.events,
+            modifiers: raw_input.modifiers,
+        });
+    }
+}
+
+/// Drops all input but the allowed keys while [`set_input_blocked`] is on,
+/// e.g. while a long operation runs and Escape cancels it. Apps that block
+/// input return one from [`crate::user_app::UserApp::input_middleware`].
+pub struct InputBlocker {
+    allow: Vec<Key>,
+}
+
+impl InputBlocker {
+    pub fn new(allow: impl IntoIterator<Item = Key>) -> Self {
+        Self {
+            allow: allow.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMiddleware for InputBlocker {
+    fn process(&mut self, input: InputView<'_>) {
+        if !is_input_blocked(input.ctx) {
+            return;
+        }
+        input.events.retain(|event| match event {
+            Event::Key { key, .. } => self.allow.contains(key),
+            // Not input, and egui's state would be off without them.
+            Event::WindowFocused(_) | Event::Screenshot { .. } | Event::PointerGone => true,
+            _ => false,
+        });
+    }
+}
+
+/// Logs every event at debug level, as it is at this point of the chain.
+pub struct EventLogger;
+
+impl InputMiddleware for EventLogger {
+    fn process(&mut self, input: InputView<'_>) {
+        for event in input.events.iter() {
+            log::debug!("input event with {:?}: {event:?}", input.modifiers);
+        }
+    }
+}
+
+/// Turns presses and releases of one pointer button into another's, e.g. a
+/// trackpad's right click into a middle click.
+pub fn remap_button(from: PointerButton, to: PointerButton) -> impl InputMiddleware {
+    move |input: InputView<'_>| {
+        for event in input.events.iter_mut() {
+            if let Event::PointerButton { button, .. } = event {
+                if *button == from {
+                    *button = to;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn key(key: Key) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    fn click(button: PointerButton) -> Event {
+        Event::PointerButton {
+            pos: egui::pos2(1., 2.),
+            button,
+            pressed: true,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    fn run_one(
+        middleware: impl InputMiddleware + 'static,
+        ctx: &egui::Context,
+        events: Vec<Event>,
+    ) -> Vec<Event> {
+        let mut raw_input = egui::RawInput {
+            events,
+            ..Default::default()
+        };
+        run(
+            [&mut (Box::new(middleware) as Box<dyn InputMiddleware>)],
+            ctx,
+            &mut raw_input,
+        );
+        raw_input.events
+    }
+
+    #[test]
+    fn each_middleware_sees_what_the_previous_left() {
+        let ctx = egui::Context::default();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let record = |name: &'static str| {
+            let seen = seen.clone();
+            move |input: InputView<'_>| seen.borrow_mut().push((name, input.events.clone()))
+        };
+        let mut middleware: Vec<Box<dyn InputMiddleware>> = vec![
+            Box::new(record("first")),
+            Box::new(remap_button(
+                PointerButton::Secondary,
+                PointerButton::Middle,
+            )),
+            Box::new(record("second")),
+            Box::new(|input: InputView<'_>| input.events.push(Event::Text("added".to_owned()))),
+            Box::new(record("third")),
+        ];
+        let mut raw_input = egui::RawInput {
+            events: vec![click(PointerButton::Secondary)],
+            ..Default::default()
+        };
+        run(&mut middleware, &ctx, &mut raw_input);
+
+        let remapped = click(PointerButton::Middle);
+        let added = Event::Text("added".to_owned());
+        assert_eq!(
+            *seen.borrow(),
+            [
+                ("first", vec![click(PointerButton::Secondary)]),
+                ("second", vec![remapped.clone()]),
+                ("third", vec![remapped.clone(), added.clone()]),
+            ]
+        );
+        assert_eq!(raw_input.events, [remapped, added]);
+    }
+
+    #[test]
+    fn blocker_keeps_only_allowed_keys_while_blocked() {
+        let ctx = egui::Context::default();
+        let events = || {
+            vec![
+                key(Key::Escape),
+                key(Key::A),
+                click(PointerButton::Primary),
+                Event::Text("a".to_owned()),
+                Event::WindowFocused(true),
+                Event::PointerGone,
+            ]
+        };
+        let blocker = || InputBlocker::new([Key::Escape]);
+        assert_eq!(run_one(blocker(), &ctx, events()), events());
+
+        set_input_blocked(&ctx, true);
+        assert_eq!(
+            run_one(blocker(), &ctx, events()),
+            [
+                key(Key::Escape),
+                Event::WindowFocused(true),
+                Event::PointerGone
+            ]
+        );
+        // Without an allowlist no key gets through.
+        assert_eq!(
+            run_one(InputBlocker::new([]), &ctx, events()),
+            [Event::WindowFocused(true), Event::PointerGone]
+        );
+
+        set_input_blocked(&ctx, false);
+        assert_eq!(run_one(blocker(), &ctx, events()), events());
+    }
+}
@@ -0,0 +1,322 @@
+use std::{fmt::Write as _, path::PathBuf};
+
+use egui::{Event, Modifiers, MouseWheelUnit, PointerButton, RawInput};
+use web_time::{Duration, Instant};
+
+// One `frame` line per frame followed by one line per event. Floats are
+// written with `{}`, which round-trips exactly, and strings as hex so they
+// need no escaping. Events without an encoding (IME, touch, screenshots) are
+// dropped.
+
+fn modifiers_bits(modifiers: Modifiers) -> u8 {
+    modifiers.alt as u8
+        | (modifiers.ctrl as u8) << 1
+        | (modifiers.shift as u8) << 2
+        | (modifiers.mac_cmd as u8) << 3
+        | (modifiers.command as u8) << 4
+}
+
+fn modifiers_from_bits(bits: u8) -> Modifiers {
+    Modifiers {
+        alt: bits & 1 != 0,
+        ctrl: bits & 2 != 0,
+        shift: bits & 4 != 0,
+        mac_cmd: bits & 8 != 0,
+        command: bits & 16 != 0,
+    }
+}
+
+fn hex(text: &str) -> String {
+    let mut hex = String::with_capacity(text.len() * 2);
+    for byte in text.bytes() {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+fn unhex(hex: &str) -> anyhow::Result<String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(hex.get(i..i + 2).unwrap_or("-"), 16)?))
+        .collect::<anyhow::Result<Vec<u8>>>()?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn button_from_index(index: u8) -> anyhow::Result<PointerButton> {
+    Ok(match index {
+        0 => PointerButton::Primary,
+        1 => PointerButton::Secondary,
+        2 => PointerButton::Middle,
+        3 => PointerButton::Extra1,
+        4 => PointerButton::Extra2,
+        _ => anyhow::bail!("unknown pointer button {index}"),
+    })
+}
+
+fn write_event(out: &mut String, event: &Event) {
+    let _ = match event {
+        Event::PointerMoved(pos) => writeln!(out, "move {} {}", pos.x, pos.y),
+        Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers,
+        } => writeln!(
+            out,
+            "button {} {} {} {} {}",
+            pos.x,
+            pos.y,
+            *button as u8,
+            *pressed as u8,
+            modifiers_bits(*modifiers)
+        ),
+        Event::PointerGone => writeln!(out, "gone"),
+        Event::MouseWheel {
+            unit,
+            delta,
+            modifiers,
+        } => {
+            let unit = match unit {
+                MouseWheelUnit::Point => "point",
+                MouseWheelUnit::Line => "line",
+                MouseWheelUnit::Page => "page",
+            };
+            writeln!(
+                out,
+                "wheel {unit} {} {} {}",
+                delta.x,
+                delta.y,
+                modifiers_bits(*modifiers)
+            )
+        }
+        Event::Key {
+            key,
+            physical_key,
+            pressed,
+            repeat,
+            modifiers,
+        } => writeln!(
+            out,
+            "key {} {} {} {} {}",
+            key.name(),
+            physical_key.map_or("-", |key| key.name()),
+            *pressed as u8,
+            *repeat as u8,
+            modifiers_bits(*modifiers)
+        ),
+        Event::Text(text) => writeln!(out, "text {}", hex(text)),
+        Event::Paste(text) => writeln!(out, "paste {}", hex(text)),
+        Event::Copy => writeln!(out, "copy"),
+        Event::Cut => writeln!(out, "cut"),
+        Event::Zoom(factor) => writeln!(out, "zoom {factor}"),
+        Event::WindowFocused(focused) => writeln!(out, "focus {}", *focused as u8),
+        _ => Ok(()),
+    };
+}
+
+fn parse_event(line: &str) -> anyhow::Result<Event> {
+    let mut fields = line.split(' ');
+    let mut next = || {
+        fields
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("missing field in {line:?}"))
+    };
+    let tag = next()?;
+    let event = match tag {
+        "move" => Event::PointerMoved(egui::pos2(next()?.parse()?, next()?.parse()?)),
+        "button" => Event::PointerButton {
+            pos: egui::pos2(next()?.parse()?, next()?.parse()?),
+            button: button_from_index(next()?.parse()?)?,
+            pressed: next()? == "1",
+            modifiers: modifiers_from_bits(next()?.parse()?),
+        },
+        "gone" => Event::PointerGone,
+        "wheel" => Event::MouseWheel {
+            unit: match next()? {
+                "point" => MouseWheelUnit::Point,
+                "line" => MouseWheelUnit::Line,
+                "page" => MouseWheelUnit::Page,
+                unit => anyhow::bail!("unknown wheel unit {unit}"),
+            },
+            delta: egui::vec2(next()?.parse()?, next()?.parse()?),
+            modifiers: modifiers_from_bits(next()?.parse()?),
+        },
+        "key" => {
+            let name = next()?;
+            Event::Key {
+                key: egui::Key::from_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown key {name}"))?,
+                physical_key: egui::Key::from_name(next()?),
+                pressed: next()? == "1",
+                repeat: next()? == "1",
+                modifiers: modifiers_from_bits(next()?.parse()?),
+            }
+        }
+        "text" => Event::Text(unhex(next()?)?),
+        "paste" => Event::Paste(unhex(next()?)?),
+        "copy" => Event::Copy,
+        "cut" => Event::Cut,
+        "zoom" => Event::Zoom(next()?.parse()?),
+        "focus" => Event::WindowFocused(next()? == "1"),
+        _ => anyhow::bail!("unknown event {tag}"),
+    };
+    Ok(event)
+}
+
+/// Appends one frame in the recording format. `redact` leaves out key, text
+/// and paste events, so that typed text does not end up in logs.
+pub fn write_frame(out: &mut String, raw_input: &RawInput, redact: bool) {
+    let _ = write!(out, "frame {}", raw_input.time.unwrap_or_default());
+    let _ = match raw_input.screen_rect {
+        Some(rect) => write!(
+            out,
+            " {} {} {} {}",
+            rect.min.x, rect.min.y, rect.max.x, rect.max.y
+        ),
+        None => write!(out, " -"),
+    };
+    let _ = writeln!(
+        out,
+        " {} {}",
+        modifiers_bits(raw_input.modifiers),
+        raw_input.focused as u8
+    );
+    for event in &raw_input.events {
+        if redact && matches!(event, Event::Key { .. } | Event::Text(_) | Event::Paste(_)) {
+            continue;
+        }
+        write_event(out, event);
+    }
+}
+
+/// Writes the input of every frame to a file, for replaying a session with
+/// [`InputReplay`].
+pub struct InputRecorder {
+    path: PathBuf,
+    recording: String,
+}
+
+impl InputRecorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            recording: String::new(),
+        }
+    }
+
+    pub fn record(&mut self, raw_input: &RawInput) {
+        write_frame(&mut self.recording, raw_input, false);
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        std::fs::write(&self.path, &self.recording)?;
+        log::info!("input recording written to {}", self.path.display());
+        Ok(())
+    }
+}
+
+struct RecordedFrame {
+    time: f64,
+    screen_rect: Option<egui::Rect>,
+    modifiers: Modifiers,
+    focused: bool,
+    events: Vec<Event>,
+}
+
+fn parse_frame(line: &str) -> anyhow::Result<RecordedFrame> {
+    let fields: Vec<&str> = line.split(' ').skip(1).collect();
+    let (time, screen_rect, rest) = match fields.as_slice() {
+        [time, "-", rest @ ..] => (time, None, rest),
+        [time, x0, y0, x1, y1, rest @ ..] => (
+            time,
+            Some(egui::Rect::from_min_max(
+                egui::pos2(x0.parse()?, y0.parse()?),
+                egui::pos2(x1.parse()?, y1.parse()?),
+            )),
+            rest,
+        ),
+        _ => anyhow::bail!("malformed frame {line:?}"),
+    };
+    let [modifiers, focused] = rest else {
+        anyhow::bail!("malformed frame {line:?}");
+    };
+    Ok(RecordedFrame {
+        time: time.parse()?,
+        screen_rect,
+        modifiers: modifiers_from_bits(modifiers.parse()?),
+        focused: *focused == "1",
+        events: Vec::new(),
+    })
+}
+
+/// Feeds a recorded session back through the normal frame pipeline, one
+/// recorded frame per frame. Live input is ignored until the recording ends.
+///
+/// egui only sees the recorded times, so animations and double clicks behave
+/// as they did when recording, whether replayed at the original pace or as
+/// fast as frames can be rendered.
+pub struct InputReplay {
+    frames: Vec<RecordedFrame>,
+    next: usize,
+    realtime: bool,
+    started: Option<Instant>,
+}
+
+impl InputReplay {
+    pub fn load(path: &std::path::Path, realtime: bool) -> anyhow::Result<Self> {
+        let recording = std::fs::read_to_string(path)?;
+        let mut frames: Vec<RecordedFrame> = Vec::new();
+        for (number, line) in recording.lines().enumerate() {
+            let parsed = if line.starts_with("frame ") {
+                parse_frame(line).map(|frame| frames.push(frame))
+            } else {
+                match frames.last_mut() {
+                    Some(frame) => parse_event(line).map(|event| frame.events.push(event)),
+                    None => Err(anyhow::anyhow!("event before the first frame")),
+                }
+            };
+            parsed.map_err(|err| anyhow::anyhow!("{}:{}: {err}", path.display(), number + 1))?;
+        }
+        log::info!("replaying {} frames from {}", frames.len(), path.display());
+        Ok(Self {
+            frames,
+            next: 0,
+            realtime,
+            started: None,
+        })
+    }
+
+    /// Replaces this frame's input with the next recorded frame.
+    pub fn apply(&mut self, raw_input: &mut RawInput) {
+        let Some(frame) = self.frames.get_mut(self.next) else {
+            return;
+        };
+        self.next += 1;
+        self.started.get_or_insert_with(Instant::now);
+        raw_input.time = Some(frame.time);
+        if frame.screen_rect.is_some() {
+            raw_input.screen_rect = frame.screen_rect;
+        }
+        raw_input.modifiers = frame.modifiers;
+        raw_input.focused = frame.focused;
+        raw_input.events = std::mem::take(&mut frame.events);
+        if self.next == self.frames.len() {
+            log::info!("input replay finished");
+        }
+    }
+
+    /// How long to wait before the next recorded frame, `None` once the
+    /// replay is over.
+    pub fn next_delay(&self) -> Option<Duration> {
+        let next = self.frames.get(self.next)?;
+        if !self.realtime {
+            return Some(Duration::ZERO);
+        }
+        let first = self.frames.first()?.time;
+        let due = Duration::from_secs_f64((next.time - first).max(0.));
+        let elapsed = self
+            .started
+            .map_or(Duration::ZERO, |started| started.elapsed());
+        Some(due.saturating_sub(elapsed))
+    }
+}
@@ -0,0 +1,432 @@
+//! Tasks in the taskbar icon's jump list on Windows, e.g. "New capture",
+//! and the recent documents above them. A task starts the app with its
+//! arguments, which reach the new instance's command line; the example has
+//! no single-instance channel to forward them to a running one. Elsewhere
+//! the functions do nothing, so app code needs no `cfg`.
+
+use std::path::{Path, PathBuf};
+
+fn tasks_id() -> egui::Id {
+    egui::Id::new("jump_list_tasks")
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JumpListTask {
+    pub title: String,
+    /// Passed to the app's executable, e.g. `--task new_capture`.
+    pub arguments: String,
+    /// A file and the index of an icon in it; the executable's own icon
+    /// without.
+    pub icon: Option<(PathBuf, i32)>,
+}
+
+impl JumpListTask {
+    pub fn new(title: impl Into<String>, arguments: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            arguments: arguments.into(),
+            icon: None,
+        }
+    }
+}
+
+/// Replaces the jump list's tasks. Can be called every frame; the list is
+/// only rebuilt when they change.
+pub fn set_jump_list_tasks(ctx: &egui::Context, tasks: Vec<JumpListTask>) {
+    let changed = ctx.data_mut(|d| {
+        let current = d.get_temp_mut_or_default::<Vec<JumpListTask>>(tasks_id());
+        let changed = *current != tasks;
+        *current = tasks;
+        changed
+    });
+    if changed {
+        ctx.request_repaint();
+    }
+}
+
+/// Lists `path` under the jump list's recent documents, e.g. after the user
+/// opened or saved it.
+pub fn add_recent_document(path: &Path) {
+    platform::add_recent_document(path);
+}
+
+/// Keeps the jump list in step with what the app asked for. The first
+/// update replaces whatever list an earlier run left, as Windows keeps them.
+#[derive(Default)]
+pub struct JumpList {
+    applied: Option<Vec<JumpListTask>>,
+}
+
+impl JumpList {
+    /// Call after each pass, on the event loop's thread.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        if let Some(tasks) = self.changes(ctx) {
+            if let Err(err) = platform::set_tasks(&tasks) {
+                log::warn!("cannot update the jump list: {err}");
+            }
+        }
+    }
+
+    fn changes(&mut self, ctx: &egui::Context) -> Option<Vec<JumpListTask>> {
+        let wanted = ctx
+            .data(|d| d.get_temp::<Vec<JumpListTask>>(tasks_id()))
+            .unwrap_or_default();
+        if self.applied.as_ref() == Some(&wanted) {
+            return None;
+        }
+        self.applied = Some(wanted.clone());
+        Some(wanted)
+    }
+}
+
+/// Through `ICustomDestinationList`, with the COM interfaces' vtables
+/// written out, as `windows-sys` only has the functions.
+#[cfg(windows)]
+mod platform {
+    use std::{
+        ffi::c_void,
+        os::windows::ffi::OsStrExt,
+        path::Path,
+        ptr::{null, null_mut},
+    };
+
+    use windows_sys::{
+        core::{GUID, HRESULT, PCWSTR},
+        Win32::{
+            System::Com::{
+                CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER,
+                COINIT_APARTMENTTHREADED,
+            },
+            UI::Shell::{SHAddToRecentDocs, SHARD_PATHW},
+        },
+    };
+
+    use super::JumpListTask;
+
+    const CLSID_DESTINATION_LIST: GUID = GUID::from_u128(0x77f10cf0_3db5_4966_b520_b7c54fd35ed6);
+    const CLSID_ENUMERABLE_OBJECT_COLLECTION: GUID =
+        GUID::from_u128(0x2d3468c1_36a7_43b6_ac24_d3f02fd9607a);
+    const CLSID_SHELL_LINK: GUID = GUID::from_u128(0x00021401_0000_0000_c000_000000000046);
+    const IID_ICUSTOM_DESTINATION_LIST: GUID =
+        GUID::from_u128(0x6332debf_87b5_4670_90c0_5e57b408a49e);
+    const IID_IOBJECT_ARRAY: GUID = GUID::from_u128(0x92ca9dcd_5622_4bba_a805_5e9f541bd8c9);
+    const IID_IOBJECT_COLLECTION: GUID = GUID::from_u128(0x5632b1a4_e38a_400a_928a_d4cd63230295);
+    const IID_ISHELL_LINK_W: GUID = GUID::from_u128(0x000214f9_0000_0000_c000_000000000046);
+    const IID_IPROPERTY_STORE: GUID = GUID::from_u128(0x886d8eeb_8cf2_4446_8d02_cdba1dbdcf99);
+    /// `PKEY_Title`, which a task's shell link shows as its title.
+    const PKEY_TITLE: PropertyKey = PropertyKey {
+        fmtid: GUID::from_u128(0xf29f85e0_4ff9_1068_ab91_08002b27b3d9),
+        pid: 2,
+    };
+    const KDC_RECENT: i32 = 2;
+    const VT_LPWSTR: u16 = 31;
+
+    #[repr(C)]
+    struct PropertyKey {
+        fmtid: GUID,
+        pid: u32,
+    }
+
+    /// A `PROPVARIANT` holding a string, padded to the full union.
+    #[repr(C)]
+    struct PropVariant {
+        vt: u16,
+        reserved: [u16; 3],
+        value: *const u16,
+        padding: usize,
+    }
+
+    /// A method that is not called, kept for the layout.
+    type Slot = usize;
+
+    #[repr(C)]
+    struct IUnknownVtbl {
+        query_interface:
+            unsafe extern "system" fn(*mut c_void, *const GUID, *mut *mut c_void) -> HRESULT,
+        _add_ref: Slot,
+        release: unsafe extern "system" fn(*mut c_void) -> u32,
+    }
+
+    #[repr(C)]
+    struct ICustomDestinationListVtbl {
+        unknown: IUnknownVtbl,
+        _set_app_id: Slot,
+        begin_list: unsafe extern "system" fn(
+            *mut c_void,
+            *mut u32,
+            *const GUID,
+            *mut *mut c_void,
+        ) -> HRESULT,
+        _append_category: Slot,
+        append_known_category: unsafe extern "system" fn(*mut c_void, i32) -> HRESULT,
+        add_user_tasks: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+        commit_list: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+        _get_removed_destinations: Slot,
+        _delete_list: Slot,
+        abort_list: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+    }
+
+    #[repr(C)]
+    struct IObjectCollectionVtbl {
+        unknown: IUnknownVtbl,
+        _get_count: Slot,
+        _get_at: Slot,
+        add_object: unsafe extern "system" fn(*mut c_void, *mut c_void) -> HRESULT,
+        _add_from_array: Slot,
+        _remove_object_at: Slot,
+        _clear: Slot,
+    }
+
+    #[repr(C)]
+    struct IShellLinkWVtbl {
+        unknown: IUnknownVtbl,
+        _get_path: Slot,
+        _get_id_list: Slot,
+        _set_id_list: Slot,
+        _get_description: Slot,
+        _set_description: Slot,
+        _get_working_directory: Slot,
+        _set_working_directory: Slot,
+        _get_arguments: Slot,
+        set_arguments: unsafe extern "system" fn(*mut c_void, PCWSTR) -> HRESULT,
+        _get_hotkey: Slot,
+        _set_hotkey: Slot,
+        _get_show_cmd: Slot,
+        _set_show_cmd: Slot,
+        _get_icon_location: Slot,
+        set_icon_location: unsafe extern "system" fn(*mut c_void, PCWSTR, i32) -> HRESULT,
+        _set_relative_path: Slot,
+        _resolve: Slot,
+        set_path: unsafe extern "system" fn(*mut c_void, PCWSTR) -> HRESULT,
+    }
+
+    #[repr(C)]
+    struct IPropertyStoreVtbl {
+        unknown: IUnknownVtbl,
+        _get_count: Slot,
+        _get_at: Slot,
+        _get_value: Slot,
+        set_value: unsafe extern "system" fn(
+            *mut c_void,
+            *const PropertyKey,
+            *const PropVariant,
+        ) -> HRESULT,
+        commit: unsafe extern "system" fn(*mut c_void) -> HRESULT,
+    }
+
+    /// An interface pointer that is released on drop. `V` is its vtable,
+    /// which starts with `IUnknown`'s.
+    struct Com<V>(*mut *const V);
+
+    impl<V> Com<V> {
+        fn create(clsid: &GUID, iid: &GUID) -> Result<Self, String> {
+            let mut object = null_mut();
+            // SAFETY: `object` receives an interface pointer of `iid`, whose
+            // vtable the caller names with `V`.
+            check("CoCreateInstance", unsafe {
+                CoCreateInstance(clsid, null_mut(), CLSCTX_INPROC_SERVER, iid, &mut object)
+            })?;
+            Ok(Self(object.cast()))
+        }
+
+        fn raw(&self) -> *mut c_void {
+            self.0.cast()
+        }
+
+        fn vtbl(&self) -> &V {
+            // SAFETY: A live interface pointer points to its vtable.
+            unsafe { &**self.0 }
+        }
+
+        fn unknown(&self) -> &IUnknownVtbl {
+            // SAFETY: Every vtable starts with `IUnknown`'s.
+            unsafe { &**self.0.cast::<*const IUnknownVtbl>() }
+        }
+
+        fn query<W>(&self, iid: &GUID) -> Result<Com<W>, String> {
+            let mut object = null_mut();
+            // SAFETY: As in `create`.
+            check("QueryInterface", unsafe {
+                (self.unknown().query_interface)(self.raw(), iid, &mut object)
+            })?;
+            Ok(Com(object.cast()))
+        }
+    }
+
+    impl<V> Drop for Com<V> {
+        fn drop(&mut self) {
+            // SAFETY: The reference this holds is released once.
+            unsafe { (self.unknown().release)(self.raw()) };
+        }
+    }
+
+    fn check(call: &str, result: HRESULT) -> Result<(), String> {
+        if result < 0 {
+            return Err(format!("{call} failed: {:#x}", result as u32));
+        }
+        Ok(())
+    }
+
+    fn wide(text: impl AsRef<std::ffi::OsStr>) -> Vec<u16> {
+        text.as_ref().encode_wide().chain([0]).collect()
+    }
+
+    /// COM for the calling thread, for as long as this lives. winit has
+    /// usually initialized it already, in which case this only counts.
+    struct Apartment(bool);
+
+    impl Apartment {
+        fn enter() -> Self {
+            // SAFETY: Balanced by `drop` when it succeeds.
+            let result = unsafe { CoInitializeEx(null(), COINIT_APARTMENTTHREADED as u32) };
+            Self(result >= 0)
+        }
+    }
+
+    impl Drop for Apartment {
+        fn drop(&mut self) {
+            if self.0 {
+                // SAFETY: Balances the successful `CoInitializeEx`.
+                unsafe { CoUninitialize() };
+            }
+        }
+    }
+
+    fn shell_link(task: &JumpListTask, exe: &[u16]) -> Result<Com<IShellLinkWVtbl>, String> {
+        let link = Com::<IShellLinkWVtbl>::create(&CLSID_SHELL_LINK, &IID_ISHELL_LINK_W)?;
+        let arguments = wide(&task.arguments);
+        let title = wide(&task.title);
+        let (icon, index) = match &task.icon {
+            Some((path, index)) => (wide(path), *index),
+            None => (exe.to_vec(), 0),
+        };
+        let store = link.query::<IPropertyStoreVtbl>(&IID_IPROPERTY_STORE)?;
+        let value = PropVariant {
+            vt: VT_LPWSTR,
+            reserved: [0; 3],
+            value: title.as_ptr(),
+            padding: 0,
+        };
+        // SAFETY: The strings are null terminated and outlive the calls,
+        // which copy them.
+        unsafe {
+            let vtbl = link.vtbl();
+            check("SetPath", (vtbl.set_path)(link.raw(), exe.as_ptr()))?;
+            check(
+                "SetArguments",
+                (vtbl.set_arguments)(link.raw(), arguments.as_ptr()),
+            )?;
+            check(
+                "SetIconLocation",
+                (vtbl.set_icon_location)(link.raw(), icon.as_ptr(), index),
+            )?;
+            check(
+                "SetValue",
+                (store.vtbl().set_value)(store.raw(), &PKEY_TITLE, &value),
+            )?;
+            check("Commit", (store.vtbl().commit)(store.raw()))?;
+        }
+        Ok(link)
+    }
+
+    pub fn set_tasks(tasks: &[JumpListTask]) -> Result<(), String> {
+        let _apartment = Apartment::enter();
+        let exe = std::env::current_exe().map_err(|err| err.to_string())?;
+        let exe = wide(&exe);
+        let list = Com::<ICustomDestinationListVtbl>::create(
+            &CLSID_DESTINATION_LIST,
+            &IID_ICUSTOM_DESTINATION_LIST,
+        )?;
+        let vtbl = list.vtbl();
+        let mut slots = 0;
+        let mut removed = null_mut();
+        // SAFETY: `removed` receives an `IObjectArray`, released below.
+        check("BeginList", unsafe {
+            (vtbl.begin_list)(list.raw(), &mut slots, &IID_IOBJECT_ARRAY, &mut removed)
+        })?;
+        if !removed.is_null() {
+            drop(Com::<IUnknownVtbl>(removed.cast()));
+        }
+
+        let build = || {
+            // Only lists anything for file types the app is registered for.
+            // SAFETY: Takes a plain value.
+            let recent = unsafe { (vtbl.append_known_category)(list.raw(), KDC_RECENT) };
+            if let Err(err) = check("AppendKnownCategory", recent) {
+                log::debug!("{err}");
+            }
+            if tasks.is_empty() {
+                return Ok(());
+            }
+            let collection = Com::<IObjectCollectionVtbl>::create(
+                &CLSID_ENUMERABLE_OBJECT_COLLECTION,
+                &IID_IOBJECT_COLLECTION,
+            )?;
+            for task in tasks {
+                let link = shell_link(task, &exe)?;
+                // SAFETY: The collection takes its own reference.
+                check("AddObject", unsafe {
+                    (collection.vtbl().add_object)(collection.raw(), link.raw())
+                })?;
+            }
+            // SAFETY: An `IObjectCollection` is an `IObjectArray`.
+            check("AddUserTasks", unsafe {
+                (vtbl.add_user_tasks)(list.raw(), collection.raw())
+            })
+        };
+        let result = build().and_then(|()| {
+            // SAFETY: Between `BeginList` and nothing else.
+            check("CommitList", unsafe { (vtbl.commit_list)(list.raw()) })
+        });
+        if result.is_err() {
+            // SAFETY: Ends the list that `BeginList` started.
+            unsafe { (vtbl.abort_list)(list.raw()) };
+        }
+        result
+    }
+
+    pub fn add_recent_document(path: &Path) {
+        let path = wide(path);
+        // SAFETY: `path` is a null terminated wide string.
+        unsafe { SHAddToRecentDocs(SHARD_PATHW as u32, path.as_ptr().cast()) };
+    }
+}
+
+/// Only Windows has jump lists.
+#[cfg(not(windows))]
+mod platform {
+    use std::path::Path;
+
+    use super::JumpListTask;
+
+    pub fn set_tasks(_tasks: &[JumpListTask]) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn add_recent_document(_path: &Path) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_update_and_changes_rebuild_the_list() {
+        let ctx = egui::Context::default();
+        let mut jump_list = JumpList::default();
+        assert_eq!(jump_list.changes(&ctx), Some(Vec::new()));
+        assert_eq!(jump_list.changes(&ctx), None);
+
+        let tasks = vec![
+            JumpListTask::new("New capture", "--task new_capture"),
+            JumpListTask::new("Open log folder", "--task log_folder"),
+        ];
+        set_jump_list_tasks(&ctx, tasks.clone());
+        assert_eq!(jump_list.changes(&ctx), Some(tasks.clone()));
+        set_jump_list_tasks(&ctx, tasks);
+        assert_eq!(jump_list.changes(&ctx), None);
+        set_jump_list_tasks(&ctx, Vec::new());
+        assert_eq!(jump_list.changes(&ctx), Some(Vec::new()));
+    }
+}
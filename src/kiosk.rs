@@ -0,0 +1,108 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+use egui_winit::winit::window::{Fullscreen, Window};
+use web_time::{Duration, Instant};
+
+use crate::close;
+
+pub const DEFAULT_EXIT_CHORD: KeyboardShortcut = KeyboardShortcut::new(
+    Modifiers::CTRL.plus(Modifiers::ALT).plus(Modifiers::SHIFT),
+    Key::Q,
+);
+
+/// A locked-down window for unattended installations: borderless
+/// fullscreen, always on top, and no way to close it but the exit chord or a
+/// signal.
+#[derive(Clone, Debug)]
+pub struct KioskOptions {
+    /// Name of the monitor to cover. The window's current monitor is used
+    /// when unset or when that monitor is gone.
+    pub monitor: Option<String>,
+    pub hide_cursor_after: Option<Duration>,
+    pub exit_chord: KeyboardShortcut,
+    /// Resets the app's state after this long without input, see
+    /// [`crate::user_app::UserApp::on_inactivity_reset`].
+    pub reset_after: Option<Duration>,
+}
+
+impl Default for KioskOptions {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            hide_cursor_after: None,
+            exit_chord: DEFAULT_EXIT_CHORD,
+            reset_after: None,
+        }
+    }
+}
+
+pub struct Kiosk {
+    options: KioskOptions,
+    last_input: Instant,
+    cursor_hidden: bool,
+    reset: bool,
+}
+
+impl Kiosk {
+    pub fn new(options: KioskOptions) -> Self {
+        Self {
+            options,
+            last_input: Instant::now(),
+            cursor_hidden: false,
+            reset: false,
+        }
+    }
+
+    /// Goes fullscreen again if the window left it or is not on the
+    /// configured monitor, e.g. after monitors were plugged in or out.
+    pub fn ensure_fullscreen(&self, window: &Window) {
+        let wanted = self.options.monitor.as_deref().and_then(|name| {
+            window
+                .available_monitors()
+                .find(|monitor| monitor.name().as_deref() == Some(name))
+        });
+        let misplaced = wanted
+            .as_ref()
+            .is_some_and(|wanted| window.current_monitor().as_ref() != Some(wanted));
+        if window.fullscreen().is_none() || misplaced {
+            log::info!("kiosk: entering fullscreen");
+            window.set_fullscreen(Some(Fullscreen::Borderless(
+                wanted.or_else(|| window.current_monitor()),
+            )));
+        }
+    }
+
+    pub fn on_input(&mut self, window: &Window) {
+        self.last_input = Instant::now();
+        self.reset = false;
+        if std::mem::take(&mut self.cursor_hidden) {
+            window.set_cursor_visible(true);
+        }
+    }
+
+    /// Call after each pass. Returns whether the app's state should be reset
+    /// for inactivity, once per inactive period.
+    pub fn update(&mut self, ctx: &egui::Context, window: &Window) -> bool {
+        if ctx.input_mut(|i| i.consume_shortcut(&self.options.exit_chord)) {
+            log::info!("kiosk: exit chord pressed");
+            close::force_exit(ctx);
+        }
+        let inactive = self.last_input.elapsed();
+        if let Some(after) = self.options.hide_cursor_after {
+            if inactive >= after {
+                if !std::mem::replace(&mut self.cursor_hidden, true) {
+                    window.set_cursor_visible(false);
+                }
+            } else {
+                ctx.request_repaint_after(after - inactive);
+            }
+        }
+        let Some(after) = self.options.reset_after else {
+            return false;
+        };
+        if inactive < after {
+            ctx.request_repaint_after(after - inactive);
+            return false;
+        }
+        !std::mem::replace(&mut self.reset, true)
+    }
+}
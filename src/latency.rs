@@ -0,0 +1,171 @@
+use web_time::{Duration, Instant};
+
+use egui_winit::winit::event::{ElementState, WindowEvent};
+
+use crate::event::EventResult;
+
+const BUCKET_WIDTH: Duration = Duration::from_millis(1);
+const BUCKETS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputClass {
+    PointerMove,
+    PointerButton,
+    KeyPress,
+}
+
+impl InputClass {
+    pub const ALL: [Self; 3] = [Self::PointerMove, Self::PointerButton, Self::KeyPress];
+
+    pub fn of(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::CursorMoved { .. } => Some(Self::PointerMove),
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            } => Some(Self::PointerButton),
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                Some(Self::KeyPress)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PointerMove => "pointer_move",
+            Self::PointerButton => "pointer_button",
+            Self::KeyPress => "key_press",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RepaintPath {
+    Now,
+    Next,
+    Deferred,
+}
+
+impl RepaintPath {
+    pub const ALL: [Self; 3] = [Self::Now, Self::Next, Self::Deferred];
+
+    pub fn of(result: &EventResult) -> Self {
+        match result {
+            EventResult::RepaintNow => Self::Now,
+            EventResult::RepaintNext => Self::Next,
+            _ => Self::Deferred,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Now => "now",
+            Self::Next => "next",
+            Self::Deferred => "deferred",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct LatencyHistogram {
+    buckets: [u32; BUCKETS],
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            sum: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn add(&mut self, latency: Duration) {
+        let bucket = (latency.as_nanos() / BUCKET_WIDTH.as_nanos()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.sum += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.sum / self.count as u32
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn percentile(&self, p: f64) -> Duration {
+        let target = (self.count as f64 * p).ceil().max(1.) as u64;
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += *n as u64;
+            if seen >= target {
+                return (BUCKET_WIDTH * (i as u32 + 1)).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PendingInput {
+    arrival: Instant,
+    path: RepaintPath,
+}
+
+#[derive(Default)]
+pub struct LatencyTracker {
+    pending: [Option<PendingInput>; InputClass::ALL.len()],
+    in_flight: [Option<PendingInput>; InputClass::ALL.len()],
+    histograms: [[LatencyHistogram; RepaintPath::ALL.len()]; InputClass::ALL.len()],
+}
+
+impl LatencyTracker {
+    pub fn on_input(&mut self, class: InputClass, arrival: Instant, result: &EventResult) {
+        let pending = &mut self.pending[class as usize];
+        if pending.is_none() {
+            *pending = Some(PendingInput {
+                arrival,
+                path: RepaintPath::of(result),
+            });
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        for (in_flight, pending) in self.in_flight.iter_mut().zip(self.pending.iter_mut()) {
+            if in_flight.is_none() {
+                *in_flight = pending.take();
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self, presented: Instant) {
+        for (class, in_flight) in self.in_flight.iter_mut().enumerate() {
+            if let Some(input) = in_flight.take() {
+                self.histograms[class][input.path as usize]
+                    .add(presented.saturating_duration_since(input.arrival));
+            }
+        }
+    }
+
+    pub fn histogram(&self, class: InputClass, path: RepaintPath) -> &LatencyHistogram {
+        &self.histograms[class as usize][path as usize]
+    }
+}
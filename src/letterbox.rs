@@ -0,0 +1,447 @@
+use egui::{Pos2, Vec2, ViewportId};
+use wgpu::{CommandEncoder, Device, TextureView};
+
+use crate::{
+    host_window::{self, HostWindow},
+    supersample,
+};
+
+fn transform_id() -> egui::Id {
+    egui::Id::new("letterbox_transform")
+}
+
+/// Where the fixed-resolution picture lands in the window, while
+/// [`crate::options::Options::design_resolution`] is set.
+pub fn transform(ctx: &egui::Context) -> Option<LetterboxTransform> {
+    ctx.data(|d| d.get_temp(transform_id()))
+}
+
+/// Maps between egui's points in the design resolution and physical pixels
+/// in the window. The picture keeps its aspect ratio and is centered, with
+/// black bars on the sides it does not fill.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LetterboxTransform {
+    /// In pixels of the offscreen target.
+    pub design_size: [u32; 2],
+    /// Physical pixels in the window per pixel of the target.
+    pub scale: f32,
+    /// The picture's top left corner in physical pixels.
+    pub offset: Vec2,
+    /// egui's pixels per point in the target: its zoom factor.
+    pub pixels_per_point: f32,
+}
+
+impl LetterboxTransform {
+    pub fn fit(design_size: [u32; 2], window_size: [u32; 2], pixels_per_point: f32) -> Self {
+        let [design_width, design_height] = design_size.map(|side| side.max(1) as f32);
+        let [width, height] = window_size.map(|side| side as f32);
+        let scale = (width / design_width).min(height / design_height);
+        // Whole pixels, so that nearest filtering does not shift by half a
+        // pixel.
+        let offset = Vec2::new(
+            ((width - design_width * scale) / 2.).floor(),
+            ((height - design_height * scale) / 2.).floor(),
+        );
+        Self {
+            design_size,
+            scale,
+            offset,
+            pixels_per_point,
+        }
+    }
+
+    /// The picture in physical pixels.
+    pub fn picture_rect(&self) -> egui::Rect {
+        let [width, height] = self.design_size;
+        egui::Rect::from_min_size(
+            self.offset.to_pos2(),
+            Vec2::new(width as f32, height as f32) * self.scale,
+        )
+    }
+
+    /// From a position in egui's points to physical pixels in the window.
+    pub fn design_to_window(&self, pos: Pos2) -> Pos2 {
+        pos * self.pixels_per_point * self.scale + self.offset
+    }
+
+    /// From physical pixels in the window to egui's points. Positions in the
+    /// bars end up outside the screen rect.
+    pub fn window_to_design(&self, pos: Pos2) -> Pos2 {
+        ((pos - self.offset).to_vec2() / (self.scale * self.pixels_per_point)).to_pos2()
+    }
+}
+
+/// Lays egui out at a fixed resolution whatever the window's size, and maps
+/// input from the window to it. [`Letterbox`] paints the result.
+pub struct DesignResolution {
+    size: [u32; 2],
+    transform: Option<LetterboxTransform>,
+}
+
+impl DesignResolution {
+    pub fn new(size: [u32; 2]) -> Self {
+        Self {
+            size,
+            transform: None,
+        }
+    }
+
+    /// Replaces egui-winit's screen rect and scale with the design's and
+    /// moves pointer positions into it. Call on each frame's input.
+    pub fn apply(
+        &mut self,
+        ctx: &egui::Context,
+        window: &dyn HostWindow,
+        raw_input: &mut egui::RawInput,
+    ) {
+        let window_size = window.inner_size();
+        let window_pixels_per_point = host_window::pixels_per_point(ctx, window);
+        self.apply_to(
+            ctx,
+            [window_size.width, window_size.height],
+            window_pixels_per_point,
+            raw_input,
+        );
+    }
+
+    /// [`Self::apply`] for a window of `window_size` physical pixels.
+    fn apply_to(
+        &mut self,
+        ctx: &egui::Context,
+        window_size: [u32; 2],
+        window_pixels_per_point: f32,
+        raw_input: &mut egui::RawInput,
+    ) {
+        if window_size.contains(&0) {
+            return;
+        }
+        // One pixel of the target per point, times the zoom.
+        let zoom = ctx.zoom_factor();
+        let transform = LetterboxTransform::fit(self.size, window_size, zoom);
+        let [width, height] = self.size;
+        raw_input.screen_rect = Some(egui::Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(width as f32, height as f32) / zoom,
+        ));
+        if let Some(info) = raw_input.viewports.get_mut(&ViewportId::ROOT) {
+            info.native_pixels_per_point = Some(1.);
+        }
+        for event in &mut raw_input.events {
+            match event {
+                egui::Event::PointerMoved(pos)
+                | egui::Event::PointerButton { pos, .. }
+                | egui::Event::Touch { pos, .. } => {
+                    *pos = transform.window_to_design(*pos * window_pixels_per_point);
+                }
+                _ => {}
+            }
+        }
+        if self.transform != Some(transform) {
+            self.transform = Some(transform);
+            ctx.data_mut(|d| d.insert_temp(transform_id(), transform));
+        }
+    }
+
+    /// Moves the IME's rects from the design back into the window, where
+    /// egui-winit expects them.
+    pub fn ime_to_window(
+        &self,
+        ctx: &egui::Context,
+        window: &dyn HostWindow,
+        platform_output: &mut egui::PlatformOutput,
+    ) {
+        let (Some(transform), Some(ime)) = (self.transform, &mut platform_output.ime) else {
+            return;
+        };
+        let window_pixels_per_point = host_window::pixels_per_point(ctx, window);
+        let to_window = |rect: egui::Rect| {
+            egui::Rect::from_min_max(
+                transform.design_to_window(rect.min) / window_pixels_per_point,
+                transform.design_to_window(rect.max) / window_pixels_per_point,
+            )
+        };
+        ime.rect = to_window(ime.rect);
+        ime.cursor_rect = to_window(ime.cursor_rect);
+    }
+}
+
+/// The offscreen target egui paints into at the design resolution, and the
+/// pass that scales it onto the surface between black bars. The last pass
+/// of the frame, like [`supersample::Supersampler::downsample`].
+pub struct Letterbox {
+    size: [u32; 2],
+    view: TextureView,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Letterbox {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        size: [u32; 2],
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let max = device.limits().max_texture_dimension_2d;
+        let size = size.map(|side| side.clamp(1, max));
+        let (bind_group_layout, pipeline) = supersample::create_blit_pipeline(device, format);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("letterbox sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        let view = supersample::create_target(device, "letterbox target", format, size);
+        let bind_group =
+            supersample::create_bind_group(device, &bind_group_layout, &view, &sampler);
+        Self {
+            size,
+            view,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn target(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn blit(
+        &self,
+        encoder: &mut CommandEncoder,
+        surface_view: &TextureView,
+        surface_size: [u32; 2],
+    ) {
+        profiling::scope!("letterbox");
+        blit_fit(
+            encoder,
+            &self.pipeline,
+            &self.bind_group,
+            self.size,
+            surface_view,
+            surface_size,
+        );
+    }
+}
+
+/// Draws `bind_group`'s texture of `content_size` onto the surface as large
+/// as it fits with its aspect ratio, between black bars.
+pub fn blit_fit(
+    encoder: &mut CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    content_size: [u32; 2],
+    surface_view: &TextureView,
+    surface_size: [u32; 2],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("letterbox pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: surface_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    let picture = LetterboxTransform::fit(content_size, surface_size, 1.).picture_rect();
+    if picture.width() < 1. || picture.height() < 1. {
+        return;
+    }
+    // Rounding must not push the viewport past the surface.
+    let [width, height] = surface_size.map(|side| side as f32);
+    render_pass.set_viewport(
+        picture.min.x,
+        picture.min.y,
+        picture.width().min(width - picture.min.x),
+        picture.height().min(height - picture.min.y),
+        0.,
+        1.,
+    );
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{options::Options, testing, user_app::SimpleApp};
+
+    #[test]
+    fn fits_with_bars() {
+        // A 4:3 design in a 16:9 window: bars left and right.
+        let transform = LetterboxTransform::fit([800, 600], [1920, 1080], 1.);
+        assert_eq!(transform.scale, 1.8);
+        assert_eq!(transform.offset, Vec2::new(240., 0.));
+        assert_eq!(
+            transform.picture_rect(),
+            egui::Rect::from_min_size(egui::pos2(240., 0.), Vec2::new(1440., 1080.))
+        );
+        // A wide design in a tall window: bars above and below, offsets in
+        // whole pixels.
+        let transform = LetterboxTransform::fit([400, 100], [201, 301], 1.);
+        assert_eq!(transform.offset, Vec2::new(0., 125.));
+    }
+
+    #[test]
+    fn maps_positions_both_ways() {
+        let transform = LetterboxTransform::fit([800, 600], [1920, 1080], 2.);
+        let pos = egui::pos2(100., 50.);
+        let window = transform.design_to_window(pos);
+        assert_eq!(window, egui::pos2(240. + 100. * 2. * 1.8, 50. * 2. * 1.8));
+        assert!((transform.window_to_design(window) - pos).length() < 1e-3);
+        // The bars are outside the design.
+        assert!(transform.window_to_design(egui::pos2(100., 500.)).x < 0.);
+    }
+
+    #[test]
+    fn moves_input_into_the_design() {
+        let ctx = egui::Context::default();
+        let mut design = DesignResolution::new([800, 600]);
+        let mut raw_input = egui::RawInput {
+            events: vec![
+                egui::Event::PointerMoved(egui::pos2(960., 540.)),
+                egui::Event::Text("a".to_owned()),
+            ],
+            ..Default::default()
+        };
+        raw_input
+            .viewports
+            .insert(ViewportId::ROOT, Default::default());
+        // A 1920x1080 window at a scale of 2: input comes in half of that.
+        design.apply_to(&ctx, [1920, 1080], 2., &mut raw_input);
+        assert_eq!(
+            raw_input.screen_rect,
+            Some(egui::Rect::from_min_size(Pos2::ZERO, Vec2::new(800., 600.)))
+        );
+        assert_eq!(
+            raw_input.viewports[&ViewportId::ROOT].native_pixels_per_point,
+            Some(1.)
+        );
+        let egui::Event::PointerMoved(pos) = raw_input.events[0] else {
+            panic!("{:?}", raw_input.events[0]);
+        };
+        // The window's bottom right corner, in the right bar past the
+        // design's width.
+        assert!((pos - egui::pos2((1920. - 240.) / 1.8, 1080. / 1.8)).length() < 1e-3);
+        assert_eq!(raw_input.events[1], egui::Event::Text("a".to_owned()));
+        assert_eq!(transform(&ctx), design.transform);
+
+        // Minimized windows leave the input alone.
+        let mut raw_input = egui::RawInput::default();
+        design.apply_to(&ctx, [0, 0], 2., &mut raw_input);
+        assert_eq!(raw_input.screen_rect, None);
+    }
+
+    #[test]
+    fn blit_keeps_aspect_between_bars() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let letterbox = Letterbox::new(&device, format, [4, 2], wgpu::FilterMode::Nearest);
+        let surface = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 8,
+                height: 8,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: letterbox.target(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        let view = surface.create_view(&Default::default());
+        letterbox.blit(&mut encoder, &view, [8, 8]);
+        let ([width, _], pixels) = testing::read_texture(&device, &queue, encoder, &surface);
+        let rows: Vec<bool> = pixels
+            .chunks_exact(width as usize * 4)
+            .map(|row| row.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]))
+            .collect();
+        // 4x2 scaled by 2 to 8x4, centered with two black rows either side.
+        assert_eq!(rows, [false, false, true, true, true, true, false, false]);
+        assert!(pixels[..4 * 8 * 2]
+            .chunks_exact(4)
+            .all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn clicks_reach_the_design_through_the_bars() {
+        let clicks = Rc::new(Cell::new(0));
+        let app = {
+            let clicks = clicks.clone();
+            SimpleApp(move |ctx: &egui::Context| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if ui.button("Press").clicked() {
+                        clicks.set(clicks.get() + 1);
+                    }
+                });
+            })
+        };
+        // A 2:1 design in a square window: scaled by 2, with bars of 100
+        // pixels above and below.
+        let options = Options {
+            window_size: egui::vec2(400., 400.),
+            design_resolution: Some([200, 100]),
+            ..Default::default()
+        };
+        let Some(mut test_app) = testing::TestApp::with_options(app, options) else {
+            return;
+        };
+        let frame = test_app.step(Vec::new());
+        let button = frame.find_text("Press").unwrap();
+        assert_eq!(test_app.ctx().screen_rect().size(), Vec2::new(200., 100.));
+        let transform = transform(test_app.ctx()).unwrap();
+        assert_eq!(transform.scale, 2.);
+        assert_eq!(transform.offset, Vec2::new(0., 100.));
+
+        // The button's place in the window, scale factor 1.
+        let pos = transform.design_to_window(button.center());
+        assert!(pos.y > 100.);
+        test_app.click(pos);
+        assert_eq!(clicks.get(), 1);
+        assert!(
+            (test_app.ctx().input(|i| i.pointer.latest_pos()).unwrap() - button.center()).length()
+                < 1e-3
+        );
+
+        // Where the button would be without the letterbox is in the bar.
+        test_app.click(button.center());
+        assert_eq!(clicks.get(), 1);
+
+        let ([width, _], pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        let pixel = |x: u32, y: u32| {
+            let i = 4 * (y * width + x) as usize;
+            pixels[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(200, 50), [0, 0, 0, 255]);
+        assert_eq!(pixel(200, 350), [0, 0, 0, 255]);
+        assert_ne!(pixel(200, 200), [0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,93 @@
+//! A window, renderer and event loop for egui on wgpu and winit, without
+//! `eframe`. An app implements [`UserApp`] and hands it to [`App::run`];
+//! the modules are the pieces around it, e.g. [`embed`] for running inside
+//! another application's window or `testing` for driving an app in tests.
+//! `examples/demo` shows most of them.
+
+pub mod adaptive_quality;
+pub mod app;
+pub mod assets;
+pub mod background_drag;
+pub mod benchmark;
+pub mod bug_report;
+pub mod capture;
+pub mod click_through;
+pub mod clipboard;
+pub mod close;
+pub mod command_palette;
+pub mod compressed_texture;
+pub mod crash_report;
+pub mod debug_window;
+pub mod dock;
+pub mod document;
+pub mod drag_pointer;
+pub mod egui_renderer;
+pub mod embed;
+pub mod error;
+pub mod event;
+pub mod event_trace;
+#[cfg(feature = "dialogs")]
+pub mod file_dialog;
+#[cfg(any(feature = "hot-reload", feature = "asset-watch"))]
+mod file_watch;
+mod frame_capture;
+mod frame_pacer;
+mod frame_recorder;
+pub mod frame_stats;
+pub mod gpu;
+mod gpu_memory;
+mod gpu_poll;
+mod gpu_timer;
+mod host_window;
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
+pub mod idle;
+pub mod input_middleware;
+mod input_replay;
+pub mod jump_list;
+pub mod kiosk;
+pub mod latency;
+pub mod letterbox;
+pub mod loading;
+pub mod log_console;
+pub mod magnifier;
+pub mod metrics;
+pub mod mirror;
+pub mod monitor_scale;
+pub mod monitors;
+#[cfg(feature = "notifications")]
+pub mod notifications;
+pub mod open_url;
+pub mod options;
+pub mod overlay;
+mod overlay_context;
+pub mod painter;
+pub mod paths;
+pub mod pointer;
+#[cfg(feature = "remote-control")]
+pub mod remote_control;
+mod render_stats;
+pub mod render_thread;
+pub mod repaint_flash;
+pub mod scene;
+mod scheduler;
+pub mod sleep_inhibit;
+#[cfg(feature = "software-renderer")]
+pub mod software;
+pub mod supersample;
+mod surface;
+pub mod tessellation_cache;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod texture_registry;
+pub mod titlebar;
+pub mod user_app;
+mod window_background;
+pub mod window_handles;
+pub mod window_shadow;
+
+pub use app::App;
+pub use error::AppError;
+pub use loading::LoadingProgress;
+pub use options::Options;
+pub use user_app::{GpuTextures, Orientation, SimpleApp, UserApp};
@@ -0,0 +1,342 @@
+use std::sync::{mpsc, Arc};
+
+use egui_winit::winit::{
+    event::{DeviceEvent, DeviceId},
+    window::Window,
+};
+
+use crate::{
+    adaptive_quality::QualityChange,
+    clipboard::ClipboardContent,
+    close::CloseDecision,
+    command_palette::Command,
+    event::EventResult,
+    frame_stats::FrameMetrics,
+    input_middleware::{self, InputBlocker, InputMiddleware},
+    monitors::Monitors,
+    open_url::UrlDecision,
+    user_app::{GpuTextures, Orientation, UserApp},
+};
+
+enum Update {
+    Progress {
+        fraction: Option<f32>,
+        message: String,
+    },
+    Failed(String),
+}
+
+/// Reports how the app's initialization is getting on to the loading view.
+/// Reports after the view is gone are dropped.
+#[derive(Clone)]
+pub struct LoadingProgress {
+    sender: mpsc::Sender<Update>,
+}
+
+impl LoadingProgress {
+    /// `fraction` goes from 0 to 1; `None` while the remaining work is
+    /// unknown.
+    pub fn report(&self, fraction: Option<f32>, message: impl Into<String>) {
+        let _ = self.sender.send(Update::Progress {
+            fraction,
+            message: message.into(),
+        });
+    }
+
+    pub(crate) fn fail(&self, message: String) {
+        let _ = self.sender.send(Update::Failed(message));
+    }
+}
+
+/// Shown while the app's state is built in the background: the app's name,
+/// a spinner and the latest progress, or why the initialization failed.
+/// Blocks egui's input, so only the OS's window management works.
+pub struct LoadingView {
+    updates: mpsc::Receiver<Update>,
+    fraction: Option<f32>,
+    message: String,
+    error: Option<String>,
+}
+
+pub fn channel() -> (LoadingProgress, LoadingView) {
+    let (sender, updates) = mpsc::channel();
+    let view = LoadingView {
+        updates,
+        fraction: None,
+        message: String::new(),
+        error: None,
+    };
+    (LoadingProgress { sender }, view)
+}
+
+impl LoadingView {
+    pub fn show(&mut self, ctx: &egui::Context) {
+        for update in self.updates.try_iter() {
+            match update {
+                Update::Progress { fraction, message } => {
+                    self.fraction = fraction;
+                    self.message = message;
+                }
+                Update::Failed(error) => self.error = Some(error),
+            }
+        }
+        input_middleware::set_input_blocked(ctx, true);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.);
+                ui.heading(env!("CARGO_PKG_NAME"));
+                ui.add_space(8.);
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                    ui.label("Close the window to exit.");
+                    return;
+                }
+                ui.spinner();
+                if let Some(fraction) = self.fraction {
+                    ui.add(egui::ProgressBar::new(fraction).desired_width(240.));
+                }
+                if !self.message.is_empty() {
+                    ui.weak(&self.message);
+                }
+            });
+        });
+    }
+}
+
+/// The loading view until the state built by
+/// [`crate::app::App::new_loading`] replaces it; every hook goes to that
+/// state from then on.
+pub enum LoadingApp<T> {
+    Loading(LoadingView),
+    Ready(T),
+}
+
+impl<T: UserApp> UserApp for LoadingApp<T> {
+    fn update(&mut self, ctx: &egui::Context) {
+        match self {
+            Self::Loading(view) => view.show(ctx),
+            Self::Ready(app) => app.update(ctx),
+        }
+    }
+
+    fn on_frame_metrics(&mut self, metrics: &FrameMetrics) {
+        if let Self::Ready(app) = self {
+            app.on_frame_metrics(metrics);
+        }
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        match self {
+            Self::Loading(_) => Vec::new(),
+            Self::Ready(app) => app.commands(),
+        }
+    }
+
+    /// The loading view blocks all input until the app is ready.
+    fn input_middleware(&mut self) -> Vec<Box<dyn InputMiddleware>> {
+        match self {
+            Self::Loading(_) => vec![Box::new(InputBlocker::new([]))],
+            Self::Ready(app) => app.input_middleware(),
+        }
+    }
+
+    fn is_command_enabled(&self, id: &str) -> bool {
+        match self {
+            Self::Loading(_) => true,
+            Self::Ready(app) => app.is_command_enabled(id),
+        }
+    }
+
+    fn on_command(&mut self, ctx: &egui::Context, id: &str) {
+        if let Self::Ready(app) = self {
+            app.on_command(ctx, id);
+        }
+    }
+
+    fn bug_report_context(&self) -> String {
+        match self {
+            Self::Loading(_) => String::new(),
+            Self::Ready(app) => app.bug_report_context(),
+        }
+    }
+
+    fn on_open_url(&mut self, url: &egui::OpenUrl) -> UrlDecision {
+        match self {
+            Self::Loading(_) => UrlDecision::Allow,
+            Self::Ready(app) => app.on_open_url(url),
+        }
+    }
+
+    fn on_paste(&mut self, content: &ClipboardContent) -> bool {
+        match self {
+            Self::Loading(_) => false,
+            Self::Ready(app) => app.on_paste(content),
+        }
+    }
+
+    fn on_device_event(&mut self, device_id: DeviceId, event: &DeviceEvent) -> Option<EventResult> {
+        match self {
+            Self::Loading(_) => None,
+            Self::Ready(app) => app.on_device_event(device_id, event),
+        }
+    }
+
+    fn on_inactivity_reset(&mut self) {
+        if let Self::Ready(app) = self {
+            app.on_inactivity_reset();
+        }
+    }
+
+    fn on_exit(&mut self, forced: bool) {
+        if let Self::Ready(app) = self {
+            app.on_exit(forced);
+        }
+    }
+
+    fn on_close_requested(&mut self, default: CloseDecision) -> CloseDecision {
+        match self {
+            Self::Loading(_) => default,
+            Self::Ready(app) => app.on_close_requested(default),
+        }
+    }
+
+    fn on_gpu_ready(&mut self, adapter_info: &wgpu::AdapterInfo, downlevel_fallback: bool) {
+        if let Self::Ready(app) = self {
+            app.on_gpu_ready(adapter_info, downlevel_fallback);
+        }
+    }
+
+    fn on_software_rendering(&mut self) {
+        if let Self::Ready(app) = self {
+            app.on_software_rendering();
+        }
+    }
+
+    fn on_memory_warning(&mut self) {
+        if let Self::Ready(app) = self {
+            app.on_memory_warning();
+        }
+    }
+
+    fn on_titlebar_inset(&mut self, inset: f32) {
+        if let Self::Ready(app) = self {
+            app.on_titlebar_inset(inset);
+        }
+    }
+
+    fn on_orientation_changed(&mut self, orientation: Orientation) {
+        if let Self::Ready(app) = self {
+            app.on_orientation_changed(orientation);
+        }
+    }
+
+    fn on_monitors_changed(&mut self, monitors: &Monitors) {
+        if let Self::Ready(app) = self {
+            app.on_monitors_changed(monitors);
+        }
+    }
+
+    fn on_quality_changed(&mut self, change: &QualityChange) {
+        if let Self::Ready(app) = self {
+            app.on_quality_changed(change);
+        }
+    }
+
+    fn on_idle(&mut self, idle: bool) {
+        if let Self::Ready(app) = self {
+            app.on_idle(idle);
+        }
+    }
+
+    fn on_gpu_textures(&mut self, gpu: &mut GpuTextures) {
+        if let Self::Ready(app) = self {
+            app.on_gpu_textures(gpu);
+        }
+    }
+
+    fn prepare_frame(
+        &mut self,
+        ctx: &egui::Context,
+        gpu: &mut GpuTextures,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if let Self::Ready(app) = self {
+            app.prepare_frame(ctx, gpu, encoder);
+        }
+    }
+
+    fn on_window_ready(&mut self, window: &Arc<Window>) {
+        if let Self::Ready(app) = self {
+            app.on_window_ready(window);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[derive(Default)]
+    struct Counting {
+        updates: u32,
+        exits: u32,
+    }
+
+    impl UserApp for Counting {
+        fn update(&mut self, _ctx: &egui::Context) {
+            self.updates += 1;
+        }
+
+        fn on_exit(&mut self, _forced: bool) {
+            self.exits += 1;
+        }
+    }
+
+    #[test]
+    fn view_shows_the_latest_progress() {
+        let (progress, view) = channel();
+        let Some(mut test_app) = TestApp::new(LoadingApp::<Counting>::Loading(view)) else {
+            return;
+        };
+        progress.report(Some(0.2), "Reading fonts");
+        progress.report(Some(0.6), "Loading the scene");
+        let frame = test_app.step(Vec::new());
+        assert!(frame.find_text("Loading the scene").is_some());
+        assert!(frame.find_text("Reading fonts").is_none());
+        assert!(input_middleware::is_input_blocked(test_app.ctx()));
+
+        progress.fail("no scene file".to_owned());
+        let frame = test_app.step(Vec::new());
+        assert!(frame.find_text("no scene file").is_some());
+        assert!(frame.find_text("Close the window to exit.").is_some());
+    }
+
+    #[test]
+    fn reports_after_the_view_is_gone_are_dropped() {
+        let (progress, view) = channel();
+        drop(view);
+        progress.report(None, "late");
+    }
+
+    #[test]
+    fn hooks_reach_the_app_once_ready() {
+        let (_progress, view) = channel();
+        let mut loading = LoadingApp::<Counting>::Loading(view);
+        assert_eq!(loading.input_middleware().len(), 1);
+        loading.on_exit(false);
+
+        let mut ready = LoadingApp::Ready(Counting::default());
+        assert!(ready.input_middleware().is_empty());
+        let Some(mut test_app) = TestApp::new(ready) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        test_app.request_close();
+        test_app.step(Vec::new());
+        let LoadingApp::Ready(app) = test_app.app() else {
+            unreachable!();
+        };
+        assert_eq!((app.updates, app.exits), (2, 1));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::Write as _,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+use egui::Id;
+use web_time::{Duration, Instant};
+
+const CAPACITY: usize = 4096;
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct LogEntry {
+    /// Since the first record.
+    pub time: Duration,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+static ENTRIES: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Adds a record to the console. Apps that install their own logger can
+/// forward records here to still see them in the console.
+pub fn capture(record: &log::Record) {
+    let entry = LogEntry {
+        time: START.get_or_init(Instant::now).elapsed(),
+        level: record.level(),
+        target: record.target().to_owned(),
+        message: record.args().to_string(),
+    };
+    if let Ok(mut entries) = ENTRIES.lock() {
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+}
+
+/// The last `count` entries, oldest first.
+pub fn recent(count: usize) -> Vec<LogEntry> {
+    ENTRIES
+        .lock()
+        .map(|entries| {
+            let skip = entries.len().saturating_sub(count);
+            entries.iter().skip(skip).cloned().collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Up to `count` of the latest errors logged after `after`, oldest first.
+pub fn errors_after(after: Option<Duration>, count: usize) -> Vec<LogEntry> {
+    ENTRIES
+        .lock()
+        .map(|entries| {
+            let mut errors: Vec<_> = entries
+                .iter()
+                .rev()
+                .take_while(|entry| after.is_none_or(|after| entry.time > after))
+                .filter(|entry| entry.level == log::Level::Error)
+                .take(count)
+                .cloned()
+                .collect();
+            errors.reverse();
+            errors
+        })
+        .unwrap_or_default()
+}
+
+/// Appends to a file and moves it to `<path>.1` once it gets too large.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> std::io::Result<Self> {
+        let file = File::options().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+        })
+    }
+
+    fn write(&mut self, record: &log::Record) -> std::io::Result<()> {
+        if self.written >= MAX_FILE_BYTES {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            std::fs::rename(&self.path, rotated)?;
+            *self = Self::open(self.path.clone())?;
+        }
+        let line = format!(
+            "{:<5} {}: {}\n",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+}
+
+struct ConsoleLogger {
+    file: Option<Mutex<LogFile>>,
+}
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        capture(record);
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+/// Installs the global logger that feeds the console and, optionally, a
+/// rotating log file. Fails if a logger is already installed; use
+/// [`capture`] from that logger instead.
+pub fn install(level: log::LevelFilter, file: Option<PathBuf>) -> anyhow::Result<()> {
+    let file = file.map(LogFile::open).transpose()?.map(Mutex::new);
+    log::set_boxed_logger(Box::new(ConsoleLogger { file }))
+        .map_err(|_| anyhow::anyhow!("a logger is already installed"))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+pub struct LogConsoleState {
+    level: log::LevelFilter,
+    search: String,
+    auto_scroll: bool,
+}
+
+impl Default for LogConsoleState {
+    fn default() -> Self {
+        Self {
+            level: log::LevelFilter::Trace,
+            search: String::new(),
+            auto_scroll: true,
+        }
+    }
+}
+
+pub fn window(ctx: &egui::Context, state: &mut LogConsoleState, open: &mut bool) {
+    egui::Window::new("Log")
+        .id(Id::new("log_console"))
+        .open(open)
+        .default_size([480., 240.])
+        .show(ctx, |ui| {
+            // Copied out, so that logging from inside the UI cannot deadlock.
+            let search = state.search.to_lowercase();
+            let shown: Vec<LogEntry> = ENTRIES
+                .lock()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter(|entry| entry.level <= state.level)
+                        .filter(|entry| {
+                            search.is_empty() || entry.message.to_lowercase().contains(&search)
+                        })
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("log_level")
+                    .selected_text(state.level.as_str())
+                    .show_ui(ui, |ui| {
+                        for level in log::LevelFilter::iter().skip(1) {
+                            ui.selectable_value(&mut state.level, level, level.as_str());
+                        }
+                    });
+                ui.add(egui::TextEdit::singleline(&mut state.search).hint_text("Search"));
+                ui.checkbox(&mut state.auto_scroll, "Auto-scroll");
+                if ui.button("Copy all").clicked() {
+                    let text: String = shown
+                        .iter()
+                        .map(|entry| {
+                            format!("{} {}: {}\n", entry.level, entry.target, entry.message)
+                        })
+                        .collect();
+                    ui.ctx().copy_text(text);
+                }
+            });
+            ui.separator();
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .auto_shrink(false)
+                .stick_to_bottom(state.auto_scroll)
+                .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                    for entry in &shown[rows] {
+                        let color = match entry.level {
+                            log::Level::Error => ui.visuals().error_fg_color,
+                            log::Level::Warn => ui.visuals().warn_fg_color,
+                            _ => ui.visuals().text_color(),
+                        };
+                        ui.colored_label(
+                            color,
+                            egui::RichText::new(format!(
+                                "{:>8.3} {:<5} {}: {}",
+                                entry.time.as_secs_f32(),
+                                entry.level,
+                                entry.target,
+                                entry.message
+                            ))
+                            .monospace(),
+                        );
+                    }
+                });
+        });
+}
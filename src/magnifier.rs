@@ -0,0 +1,574 @@
+use egui::{Id, Pos2, Vec2};
+use wgpu::{CommandEncoder, Device, Queue, TextureView};
+
+use crate::{letterbox::Letterbox, paths};
+
+pub const MIN_ZOOM: u32 = 2;
+pub const MAX_ZOOM: u32 = 8;
+
+const SETTINGS_FILE: &str = "magnifier";
+
+/// Per second: how quickly the lens catches up with the pointer or focus.
+const FOLLOW_SPEED: f32 = 12.;
+
+/// The lens's outline, in physical pixels.
+const BORDER: f32 = 2.;
+
+const SHADER: &str = r"
+struct Lens {
+    // In surface pixels.
+    center: vec2<f32>,
+    half_size: vec2<f32>,
+    // The point shown at the lens's center, in source pixels.
+    source_center: vec2<f32>,
+    source_size: vec2<f32>,
+    // Source pixels per surface pixel inside the lens.
+    source_step: f32,
+    border: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the whole target; the scissor rect cuts
+    // out the lens.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> lens: Lens;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let offset = position.xy - lens.center;
+    let uv = (lens.source_center + offset * lens.source_step) / lens.source_size;
+    let color = textureSampleLevel(source, source_sampler, uv, 0.0);
+    if any(abs(offset) > lens.half_size - vec2<f32>(lens.border)) {
+        return vec4<f32>(1.0, 0.8, 0.0, 1.0);
+    }
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return color;
+}
+";
+
+fn settings_id() -> Id {
+    Id::new("magnifier_settings")
+}
+
+/// How the magnifier shows, remembered across runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MagnifierSettings {
+    pub enabled: bool,
+    /// From [`MIN_ZOOM`] to [`MAX_ZOOM`].
+    pub zoom: u32,
+    /// `Nearest` keeps pixels sharp, `Linear` smooths them.
+    pub filter: wgpu::FilterMode,
+    /// A panel along the top edge, or the bottom one while the focus is in
+    /// the top third, instead of a lens over the focus.
+    pub docked: bool,
+}
+
+impl Default for MagnifierSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            zoom: 2,
+            filter: wgpu::FilterMode::Nearest,
+            docked: false,
+        }
+    }
+}
+
+pub fn settings(ctx: &egui::Context) -> MagnifierSettings {
+    ctx.data(|d| d.get_temp(settings_id())).unwrap_or_default()
+}
+
+/// Applied from the next frame on. The zoom is clamped to its range.
+pub fn set_settings(ctx: &egui::Context, mut settings: MagnifierSettings) {
+    settings.zoom = settings.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    ctx.data_mut(|d| d.insert_temp(settings_id(), settings));
+}
+
+pub fn toggle(ctx: &egui::Context) {
+    let settings = settings(ctx);
+    set_settings(
+        ctx,
+        MagnifierSettings {
+            enabled: !settings.enabled,
+            ..settings
+        },
+    );
+}
+
+/// Changes the zoom by `steps`, turning the magnifier on.
+pub fn zoom_by(ctx: &egui::Context, steps: i32) {
+    let settings = settings(ctx);
+    set_settings(
+        ctx,
+        MagnifierSettings {
+            enabled: true,
+            zoom: settings.zoom.saturating_add_signed(steps),
+            ..settings
+        },
+    );
+}
+
+pub fn load_settings() -> MagnifierSettings {
+    let mut settings = MagnifierSettings::default();
+    let Some(text) =
+        paths::data_dir().and_then(|dir| std::fs::read_to_string(dir.join(SETTINGS_FILE)).ok())
+    else {
+        return settings;
+    };
+    for (key, value) in text.lines().filter_map(|line| line.split_once('=')) {
+        match (key, value) {
+            ("enabled", value) => settings.enabled = value == "true",
+            ("zoom", value) => settings.zoom = value.parse().unwrap_or(settings.zoom),
+            ("filter", "linear") => settings.filter = wgpu::FilterMode::Linear,
+            ("filter", _) => settings.filter = wgpu::FilterMode::Nearest,
+            ("docked", value) => settings.docked = value == "true",
+            _ => {}
+        }
+    }
+    settings.zoom = settings.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    settings
+}
+
+fn save_settings(settings: &MagnifierSettings) {
+    let Some(dir) = paths::data_dir() else {
+        return;
+    };
+    let filter = match settings.filter {
+        wgpu::FilterMode::Nearest => "nearest",
+        wgpu::FilterMode::Linear => "linear",
+    };
+    let text = format!(
+        "enabled={}\nzoom={}\nfilter={filter}\ndocked={}\n",
+        settings.enabled, settings.zoom, settings.docked
+    );
+    let result =
+        std::fs::create_dir_all(&dir).and_then(|()| std::fs::write(dir.join(SETTINGS_FILE), text));
+    if let Err(err) = result {
+        log::warn!("cannot save the magnifier settings: {err}");
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Follow {
+    Pointer,
+    Focus,
+}
+
+/// What the magnifier samples: egui's target before anything is drawn over
+/// it, so the lens never shows itself.
+pub struct MagnifierSource<'a> {
+    pub view: &'a TextureView,
+    pub size: [u32; 2],
+    /// egui's pixels per point in the source.
+    pub pixels_per_point: f32,
+    /// Surface pixels per source pixel, and where the source's origin lands
+    /// on the surface.
+    pub scale: f32,
+    pub offset: Vec2,
+}
+
+/// Shows the area around the pointer, or around the keyboard focus while
+/// navigating by keyboard, enlarged in a lens or a docked panel.
+///
+/// egui paints into a target as usual, the letterbox's, the supersampler's
+/// or one of the magnifier's own; the lens is a last pass over the surface
+/// that samples it.
+pub struct Magnifier {
+    settings: MagnifierSettings,
+    follow: Follow,
+    focused: Option<Id>,
+    /// In egui's points, trailing the pointer or focus.
+    center: Option<Pos2>,
+    pass: Option<LensPass>,
+    /// egui's target when nothing else provides one.
+    target: Option<Letterbox>,
+}
+
+impl Magnifier {
+    /// Starts with `initial` and shares it through the context.
+    pub fn new(ctx: &egui::Context, initial: MagnifierSettings) -> Self {
+        set_settings(ctx, initial);
+        Self {
+            settings: settings(ctx),
+            follow: Follow::Pointer,
+            focused: None,
+            center: None,
+            pass: None,
+            target: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.settings.enabled
+    }
+
+    /// Picks up changed settings and moves the lens. Call after each pass;
+    /// returns whether the frame has to be painted even if egui's output is
+    /// unchanged.
+    pub fn update(&mut self, ctx: &egui::Context) -> bool {
+        let settings = settings(ctx);
+        let mut changed = settings != self.settings;
+        if changed {
+            self.settings = settings;
+            save_settings(&settings);
+        }
+        if !settings.enabled {
+            self.center = None;
+            self.pass = None;
+            self.target = None;
+            return changed;
+        }
+
+        let focused = ctx.memory(|m| m.focused());
+        if focused != self.focused {
+            self.focused = focused;
+            if focused.is_some() {
+                self.follow = Follow::Focus;
+            }
+        }
+        if ctx.input(|i| i.pointer.is_moving()) {
+            self.follow = Follow::Pointer;
+        }
+        let focus_rect = || Some(ctx.read_response(focused?)?.rect.center());
+        let target = match self.follow {
+            Follow::Focus => focus_rect(),
+            Follow::Pointer => None,
+        }
+        .or_else(|| ctx.input(|i| i.pointer.latest_pos()))
+        .or(self.center)
+        .unwrap_or_else(|| ctx.screen_rect().center());
+
+        let center = match self.center {
+            Some(center) => {
+                let dt = ctx.input(|i| i.stable_dt).min(0.1);
+                let moved = center + (target - center) * (1. - (-dt * FOLLOW_SPEED).exp());
+                if (target - moved).length() < 0.5 {
+                    target
+                } else {
+                    ctx.request_repaint();
+                    moved
+                }
+            }
+            None => target,
+        };
+        changed |= self.center != Some(center);
+        self.center = Some(center);
+        changed
+    }
+
+    /// Sets up the pass, and the own target at the surface's size when
+    /// `own_target`, i.e. when nothing else gives egui a target. Call before
+    /// [`Self::target`] and [`Self::draw`].
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        format: wgpu::TextureFormat,
+        size: [u32; 2],
+        own_target: bool,
+    ) {
+        if !self.settings.enabled {
+            return;
+        }
+        self.pass
+            .get_or_insert_with(|| LensPass::new(device, format));
+        if !own_target {
+            self.target = None;
+        } else if self
+            .target
+            .as_ref()
+            .is_none_or(|target| target.size() != size)
+        {
+            self.target = Some(Letterbox::new(
+                device,
+                format,
+                size,
+                wgpu::FilterMode::Nearest,
+            ));
+        }
+    }
+
+    pub fn target(&self) -> Option<&TextureView> {
+        self.target.as_ref().map(Letterbox::target)
+    }
+
+    /// Draws the own target onto the surface, under the lens.
+    pub fn show_target(
+        &self,
+        encoder: &mut CommandEncoder,
+        surface_view: &TextureView,
+        surface_size: [u32; 2],
+    ) {
+        if let Some(target) = &self.target {
+            target.blit(encoder, surface_view, surface_size);
+        }
+    }
+
+    /// Draws the lens over the surface, which already shows the frame.
+    pub fn draw(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        source: MagnifierSource<'_>,
+        surface_view: &TextureView,
+        surface_size: [u32; 2],
+    ) {
+        let (Some(center), Some(pass)) = (self.center, &self.pass) else {
+            return;
+        };
+        profiling::scope!("magnifier");
+
+        let surface = Vec2::new(surface_size[0] as f32, surface_size[1] as f32);
+        let source_center = center.to_vec2() * source.pixels_per_point;
+        let focus = source_center * source.scale + source.offset;
+        let (lens_center, half_size) = if self.settings.docked {
+            let half_size = Vec2::new(surface.x, surface.y / 3.) / 2.;
+            let y = if focus.y < surface.y / 3. {
+                surface.y - half_size.y
+            } else {
+                half_size.y
+            };
+            (Vec2::new(surface.x / 2., y), half_size)
+        } else {
+            let half_size = (surface / 3. / 2.).max(Vec2::splat(BORDER + 1.));
+            (
+                focus.clamp(half_size, (surface - half_size).max(half_size)),
+                half_size,
+            )
+        };
+        let min = (lens_center - half_size).max(Vec2::ZERO).floor();
+        let max = (lens_center + half_size).min(surface).ceil();
+        if min.x >= max.x || min.y >= max.y {
+            return;
+        }
+
+        let source_step = 1. / (self.settings.zoom as f32 * source.scale);
+        let lens = [
+            lens_center.x,
+            lens_center.y,
+            half_size.x,
+            half_size.y,
+            source_center.x,
+            source_center.y,
+            source.size[0] as f32,
+            source.size[1] as f32,
+            source_step,
+            BORDER,
+            0.,
+            0.,
+        ];
+        let bytes: Vec<u8> = lens.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        queue.write_buffer(&pass.uniforms, 0, &bytes);
+        let sampler = match self.settings.filter {
+            wgpu::FilterMode::Nearest => &pass.nearest,
+            wgpu::FilterMode::Linear => &pass.linear,
+        };
+        // The source changes with the window's size and the mode, so the
+        // bind group is made per frame.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("magnifier bind group"),
+            layout: &pass.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: pass.uniforms.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("magnifier pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_scissor_rect(
+            min.x as u32,
+            min.y as u32,
+            (max.x - min.x) as u32,
+            (max.y - min.y) as u32,
+        );
+        render_pass.set_pipeline(&pass.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+struct LensPass {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    uniforms: wgpu::Buffer,
+    nearest: wgpu::Sampler,
+    linear: wgpu::Sampler,
+}
+
+impl LensPass {
+    fn new(device: &Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("magnifier shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("magnifier bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("magnifier pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("magnifier pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("magnifier uniforms"),
+            size: 48,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = |filter, label| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(label),
+                mag_filter: filter,
+                min_filter: filter,
+                ..Default::default()
+            })
+        };
+        Self {
+            bind_group_layout,
+            pipeline,
+            uniforms,
+            nearest: sampler(wgpu::FilterMode::Nearest, "magnifier nearest sampler"),
+            linear: sampler(wgpu::FilterMode::Linear, "magnifier linear sampler"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, testing::TestApp, user_app::SimpleApp};
+
+    #[test]
+    fn settings_keep_the_zoom_in_range() {
+        let ctx = egui::Context::default();
+        assert_eq!(settings(&ctx), MagnifierSettings::default());
+        zoom_by(&ctx, 100);
+        assert_eq!(settings(&ctx).zoom, MAX_ZOOM);
+        assert!(settings(&ctx).enabled);
+        zoom_by(&ctx, -100);
+        assert_eq!(settings(&ctx).zoom, MIN_ZOOM);
+        toggle(&ctx);
+        assert!(!settings(&ctx).enabled);
+    }
+
+    #[test]
+    fn lens_enlarges_around_the_pointer() {
+        // Red left of x = 45, blue right of it.
+        let app = SimpleApp(|ctx: &egui::Context| {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none().fill(egui::Color32::BLUE))
+                .show(ctx, |ui| {
+                    ui.painter().rect_filled(
+                        egui::Rect::from_min_max(Pos2::ZERO, egui::pos2(45., 90.)),
+                        0.,
+                        egui::Color32::RED,
+                    );
+                });
+        });
+        let options = Options {
+            window_size: egui::vec2(90., 90.),
+            magnifier: true,
+            skip_unchanged_frames: false,
+            ..Default::default()
+        };
+        let Some(mut test_app) = TestApp::with_options(app, options) else {
+            return;
+        };
+        test_app.move_pointer(egui::pos2(40., 45.));
+        let ([width, _], pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        let pixel = |pixels: &[u8], x: u32, y: u32| {
+            let i = 4 * (y * width + x) as usize;
+            pixels[i..i + 4].to_vec()
+        };
+        // A 30 pixel lens centered on the pointer, at twice the size: 8
+        // pixels right of the pointer shows what is 4 pixels right of it.
+        assert_eq!(pixel(&pixels, 48, 45), [255, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 54, 45), [255, 204, 0, 255]);
+        assert_eq!(pixel(&pixels, 60, 45), [0, 0, 255, 255]);
+
+        toggle(test_app.ctx());
+        let (_, pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        assert_eq!(pixel(&pixels, 48, 45), [0, 0, 255, 255]);
+        assert_eq!(pixel(&pixels, 54, 45), [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+#[cfg(feature = "metrics-server")]
+use std::{
+    io::{BufRead as _, BufReader, Read as _, Write as _},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use anyhow::Context as _;
+use web_time::{Duration, Instant};
+
+use crate::event::{EventSender, UserEvent};
+
+/// Frame times are summarized over this span.
+const WINDOW: Duration = Duration::from_secs(60);
+const PREFIX: &str = "egui_app";
+#[cfg(feature = "metrics-server")]
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9464";
+#[cfg(feature = "metrics-server")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// More than a scraper sends; the rest of a request is not read.
+#[cfg(feature = "metrics-server")]
+const REQUEST_LIMIT: u64 = 8 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct MetricsOptions {
+    /// Rewritten every `interval`: JSON for a `.json` extension, the
+    /// Prometheus text format otherwise.
+    pub textfile: Option<PathBuf>,
+    /// Also how often the event loop is checked for stalls.
+    pub interval: Duration,
+    /// Serves `GET /metrics`. Only loopback addresses keep other machines
+    /// out; anything else is logged as a warning.
+    #[cfg(feature = "metrics-server")]
+    pub address: Option<SocketAddr>,
+}
+
+impl Default for MetricsOptions {
+    fn default() -> Self {
+        Self {
+            textfile: None,
+            interval: Duration::from_secs(15),
+            #[cfg(feature = "metrics-server")]
+            address: None,
+        }
+    }
+}
+
+pub fn default_textfile() -> Option<PathBuf> {
+    crate::paths::data_dir().map(|dir| dir.join("metrics.prom"))
+}
+
+/// What the window is doing, most telling first, so that a hidden or idle
+/// app is not mistaken for a hung one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    Suspended,
+    Hidden,
+    Idle,
+    Rendering,
+}
+
+impl Activity {
+    const ALL: [Self; 4] = [Self::Suspended, Self::Hidden, Self::Idle, Self::Rendering];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Suspended => "suspended",
+            Self::Hidden => "hidden",
+            Self::Idle => "idle",
+            Self::Rendering => "rendering",
+        }
+    }
+}
+
+struct Collected {
+    started: Instant,
+    frames_presented: u64,
+    /// Presentation time and cost of the frames within [`WINDOW`].
+    frame_times: VecDeque<(Instant, Duration)>,
+    last_present: Option<Instant>,
+    gpu_memory_bytes: u64,
+    surface_errors_recovered: u64,
+    last_input: Option<Instant>,
+    suspended: bool,
+    hidden: bool,
+    idle: bool,
+    /// When the unanswered heartbeat was sent.
+    heartbeat_sent: Option<Instant>,
+}
+
+impl Collected {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .frame_times
+            .front()
+            .is_some_and(|(present, _)| now.saturating_duration_since(*present) > WINDOW)
+        {
+            self.frame_times.pop_front();
+        }
+    }
+
+    fn activity(&self) -> Activity {
+        if self.suspended {
+            Activity::Suspended
+        } else if self.hidden {
+            Activity::Hidden
+        } else if self.idle {
+            Activity::Idle
+        } else {
+            Activity::Rendering
+        }
+    }
+}
+
+/// Counters for long-running deployments, recorded on the UI thread and
+/// exported from threads of their own. Clones share them.
+#[derive(Clone)]
+pub struct Metrics {
+    collected: Arc<Mutex<Collected>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            collected: Arc::new(Mutex::new(Collected {
+                started: Instant::now(),
+                frames_presented: 0,
+                frame_times: VecDeque::new(),
+                last_present: None,
+                gpu_memory_bytes: 0,
+                surface_errors_recovered: 0,
+                last_input: None,
+                suspended: false,
+                hidden: false,
+                idle: false,
+                heartbeat_sent: None,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Collected> {
+        self.collected.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// `frame_time` leaves out waiting for vsync.
+    pub fn on_presented(&self, present: Instant, frame_time: Duration, gpu_memory_bytes: u64) {
+        let mut collected = self.lock();
+        collected.frames_presented += 1;
+        collected.prune(present);
+        collected.frame_times.push_back((present, frame_time));
+        collected.last_present = Some(present);
+        collected.gpu_memory_bytes = gpu_memory_bytes;
+    }
+
+    pub fn on_input(&self) {
+        self.lock().last_input = Some(Instant::now());
+    }
+
+    pub fn on_surface_error_recovered(&self) {
+        self.lock().surface_errors_recovered += 1;
+    }
+
+    pub fn set_suspended(&self, suspended: bool) {
+        self.lock().suspended = suspended;
+    }
+
+    pub fn set_hidden(&self, hidden: bool) {
+        self.lock().hidden = hidden;
+    }
+
+    pub fn set_idle(&self, idle: bool) {
+        self.lock().idle = idle;
+    }
+
+    /// Answers [`UserEvent::MetricsHeartbeat`].
+    pub fn on_heartbeat(&self) {
+        self.lock().heartbeat_sent = None;
+    }
+
+    /// Sends a heartbeat to the event loop unless the last one is still
+    /// unanswered.
+    fn send_heartbeat(&self, sender: &EventSender) {
+        let mut collected = self.lock();
+        if collected.heartbeat_sent.is_none() {
+            collected.heartbeat_sent = Some(Instant::now());
+            drop(collected);
+            sender.send(UserEvent::MetricsHeartbeat);
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let now = Instant::now();
+        let mut collected = self.lock();
+        collected.prune(now);
+        let mut frame_times: Vec<_> = collected.frame_times.iter().map(|(_, t)| *t).collect();
+        frame_times.sort_unstable();
+        let percentile = |p: f64| {
+            frame_times
+                .get(((frame_times.len().max(1) - 1) as f64 * p).round() as usize)
+                .copied()
+        };
+        let age = |when: Option<Instant>| when.map(|when| now.saturating_duration_since(when));
+        Snapshot {
+            uptime: now.saturating_duration_since(collected.started),
+            frames_presented: collected.frames_presented,
+            frame_time_mean: (!frame_times.is_empty())
+                .then(|| frame_times.iter().sum::<Duration>() / frame_times.len() as u32),
+            frame_time_sum: frame_times.iter().sum(),
+            frame_time_count: frame_times.len(),
+            frame_time_p50: percentile(0.50),
+            frame_time_p95: percentile(0.95),
+            frame_time_p99: percentile(0.99),
+            frames_per_second: frame_times.len() as f64
+                / WINDOW
+                    .min(now.saturating_duration_since(collected.started))
+                    .as_secs_f64()
+                    .max(1.),
+            gpu_memory_bytes: collected.gpu_memory_bytes,
+            surface_errors_recovered: collected.surface_errors_recovered,
+            last_input_age: age(collected.last_input),
+            last_frame_age: age(collected.last_present),
+            activity: collected.activity(),
+            event_loop_stall: age(collected.heartbeat_sent).unwrap_or_default(),
+        }
+    }
+}
+
+struct Snapshot {
+    uptime: Duration,
+    frames_presented: u64,
+    frame_time_mean: Option<Duration>,
+    frame_time_sum: Duration,
+    frame_time_count: usize,
+    frame_time_p50: Option<Duration>,
+    frame_time_p95: Option<Duration>,
+    frame_time_p99: Option<Duration>,
+    frames_per_second: f64,
+    gpu_memory_bytes: u64,
+    surface_errors_recovered: u64,
+    last_input_age: Option<Duration>,
+    last_frame_age: Option<Duration>,
+    activity: Activity,
+    event_loop_stall: Duration,
+}
+
+impl Snapshot {
+    /// The Prometheus text exposition format. Metrics without a value yet,
+    /// e.g. the last input's age before any input, are left out.
+    fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        // Samples are given by what follows the name: labels, or a suffix
+        // like a summary's `_sum`.
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+            let _ = writeln!(text, "# HELP {PREFIX}_{name} {help}");
+            let _ = writeln!(text, "# TYPE {PREFIX}_{name} {kind}");
+            for (suffix, value) in samples {
+                let _ = writeln!(text, "{PREFIX}_{name}{suffix} {value}");
+            }
+        };
+        let seconds = |value: Option<Duration>| value.map(|value| ("", value.as_secs_f64()));
+
+        metric(
+            "uptime_seconds",
+            "gauge",
+            "Time since the app started.",
+            &[("", self.uptime.as_secs_f64())],
+        );
+        metric(
+            "frames_presented_total",
+            "counter",
+            "Frames presented since the app started.",
+            &[("", self.frames_presented as f64)],
+        );
+        let quantiles = [
+            ("{quantile=\"0.5\"}", self.frame_time_p50),
+            ("{quantile=\"0.95\"}", self.frame_time_p95),
+            ("{quantile=\"0.99\"}", self.frame_time_p99),
+        ];
+        let mut summary: Vec<_> = quantiles
+            .into_iter()
+            .filter_map(|(labels, value)| Some((labels, value?.as_secs_f64())))
+            .collect();
+        summary.push(("_sum", self.frame_time_sum.as_secs_f64()));
+        summary.push(("_count", self.frame_time_count as f64));
+        metric(
+            "frame_time_seconds",
+            "summary",
+            "Frame time over the last minute, without waiting for vsync.",
+            &summary,
+        );
+        metric(
+            "frame_time_mean_seconds",
+            "gauge",
+            "Mean frame time over the last minute.",
+            seconds(self.frame_time_mean).as_slice(),
+        );
+        metric(
+            "frames_per_second",
+            "gauge",
+            "Frames presented per second over the last minute.",
+            &[("", self.frames_per_second)],
+        );
+        metric(
+            "gpu_memory_bytes",
+            "gauge",
+            "GPU memory as reported by the backend, or the app's own estimate.",
+            &[("", self.gpu_memory_bytes as f64)],
+        );
+        metric(
+            "surface_errors_recovered_total",
+            "counter",
+            "Surface errors recovered from by reconfiguring the surface.",
+            &[("", self.surface_errors_recovered as f64)],
+        );
+        metric(
+            "last_input_age_seconds",
+            "gauge",
+            "Time since the last user input.",
+            seconds(self.last_input_age).as_slice(),
+        );
+        metric(
+            "last_frame_age_seconds",
+            "gauge",
+            "Time since the last frame was presented.",
+            seconds(self.last_frame_age).as_slice(),
+        );
+        let states: Vec<_> = Activity::ALL
+            .iter()
+            .map(|activity| format!("{{state=\"{}\"}}", activity.name()))
+            .collect();
+        let activity: Vec<_> = Activity::ALL
+            .iter()
+            .zip(&states)
+            .map(|(activity, labels)| {
+                (
+                    labels.as_str(),
+                    f64::from(u8::from(*activity == self.activity)),
+                )
+            })
+            .collect();
+        metric(
+            "activity",
+            "gauge",
+            "1 for what the window is doing.",
+            &activity,
+        );
+        metric(
+            "event_loop_stall_seconds",
+            "gauge",
+            "How long the event loop has not answered a heartbeat; high while hung.",
+            &[("", self.event_loop_stall.as_secs_f64())],
+        );
+        text
+    }
+
+    fn to_json(&self) -> String {
+        let seconds = |value: Option<Duration>| {
+            value.map_or_else(
+                || "null".to_owned(),
+                |value| value.as_secs_f64().to_string(),
+            )
+        };
+        format!(
+            "{{\"uptime_seconds\": {}, \"frames_presented\": {}, \
+             \"frame_time_seconds\": {{\"mean\": {}, \"p50\": {}, \"p95\": {}, \"p99\": {}}}, \
+             \"frames_per_second\": {}, \"gpu_memory_bytes\": {}, \
+             \"surface_errors_recovered\": {}, \"last_input_age_seconds\": {}, \
+             \"last_frame_age_seconds\": {}, \"activity\": \"{}\", \
+             \"event_loop_stall_seconds\": {}}}\n",
+            self.uptime.as_secs_f64(),
+            self.frames_presented,
+            seconds(self.frame_time_mean),
+            seconds(self.frame_time_p50),
+            seconds(self.frame_time_p95),
+            seconds(self.frame_time_p99),
+            self.frames_per_second,
+            self.gpu_memory_bytes,
+            self.surface_errors_recovered,
+            seconds(self.last_input_age),
+            seconds(self.last_frame_age),
+            self.activity.name(),
+            self.event_loop_stall.as_secs_f64(),
+        )
+    }
+}
+
+/// Starts sending heartbeats and writing the textfile on a thread, and
+/// serving scrapes on another.
+pub fn start(options: &MetricsOptions, sender: EventSender) -> anyhow::Result<Metrics> {
+    let metrics = Metrics::new();
+
+    #[cfg(feature = "metrics-server")]
+    if let Some(address) = options.address {
+        spawn_server(address, metrics.clone())?;
+    }
+
+    let textfile = options.textfile.clone();
+    if let Some(dir) = textfile.as_deref().and_then(Path::parent) {
+        std::fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    }
+    let interval = options.interval;
+    let exporter = metrics.clone();
+    std::thread::Builder::new()
+        .name("metrics".to_owned())
+        .spawn(move || {
+            profiling::register_thread!("metrics");
+            let mut failing = false;
+            loop {
+                exporter.send_heartbeat(&sender);
+                std::thread::sleep(interval);
+                let Some(path) = &textfile else {
+                    continue;
+                };
+                match write_textfile(path, &exporter.snapshot()) {
+                    Ok(()) => failing = false,
+                    // Once per failure, not every interval.
+                    Err(err) if !failing => {
+                        log::warn!("cannot write metrics to {}: {err}", path.display());
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+            }
+        })?;
+    Ok(metrics)
+}
+
+/// Through a temporary file, so that collectors never read half of it.
+fn write_textfile(path: &Path, snapshot: &Snapshot) -> std::io::Result<()> {
+    let contents = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("json") => snapshot.to_json(),
+        _ => snapshot.to_prometheus(),
+    };
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)
+}
+
+#[cfg(feature = "metrics-server")]
+fn spawn_server(address: SocketAddr, metrics: Metrics) -> anyhow::Result<()> {
+    let listener =
+        TcpListener::bind(address).with_context(|| format!("cannot listen on {address}"))?;
+    if !address.ip().is_loopback() {
+        log::warn!("metrics are served on {address}, which other machines can reach");
+    }
+    log::info!(
+        "serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+    std::thread::Builder::new()
+        .name("metrics-server".to_owned())
+        .spawn(move || {
+            profiling::register_thread!("metrics-server");
+            // Scrapes are rare and quick, so they are answered one by one.
+            for stream in listener.incoming() {
+                if let Err(err) = stream.and_then(|stream| serve(stream, &metrics)) {
+                    log::debug!("metrics server: {err}");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(feature = "metrics-server")]
+fn serve(stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(REQUEST_LIMIT));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are read so that closing does not reset the connection.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.snapshot().to_prometheus()),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_owned()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_owned()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            uptime: Duration::from_secs(90),
+            frames_presented: 5400,
+            frame_time_mean: Some(Duration::from_millis(4)),
+            frame_time_sum: Duration::from_millis(14_400),
+            frame_time_count: 3600,
+            frame_time_p50: Some(Duration::from_millis(3)),
+            frame_time_p95: Some(Duration::from_millis(8)),
+            frame_time_p99: Some(Duration::from_millis(12)),
+            frames_per_second: 60.0,
+            gpu_memory_bytes: 1 << 20,
+            surface_errors_recovered: 0,
+            last_input_age: None,
+            last_frame_age: Some(Duration::from_millis(16)),
+            activity: Activity::Rendering,
+            event_loop_stall: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn frame_times_are_a_summary() {
+        let text = snapshot().to_prometheus();
+        let summary = "# TYPE egui_app_frame_time_seconds summary\n\
+                       egui_app_frame_time_seconds{quantile=\"0.5\"} 0.003\n\
+                       egui_app_frame_time_seconds{quantile=\"0.95\"} 0.008\n\
+                       egui_app_frame_time_seconds{quantile=\"0.99\"} 0.012\n\
+                       egui_app_frame_time_seconds_sum 14.4\n\
+                       egui_app_frame_time_seconds_count 3600\n";
+        assert!(text.contains(summary), "{text}");
+        assert!(!text.contains("\negui_app_last_input_age_seconds"));
+        assert!(text.contains("egui_app_activity{state=\"rendering\"} 1\n"));
+    }
+
+    #[cfg(feature = "metrics-server")]
+    mod server {
+        use std::io::{Read as _, Write as _};
+
+        use super::*;
+
+        /// Serves one connection, which `client` makes to the address it is
+        /// given.
+        fn serve_one(client: impl FnOnce(SocketAddr) + Send + 'static) -> std::io::Result<()> {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let client = std::thread::spawn(move || client(address));
+            let (stream, _) = listener.accept().unwrap();
+            let served = serve(stream, &Metrics::new());
+            client.join().unwrap();
+            served
+        }
+
+        #[test]
+        fn answers_scrapes() {
+            let (sender, response) = std::sync::mpsc::channel();
+            serve_one(move |address| {
+                let mut stream = TcpStream::connect(address).unwrap();
+                stream
+                    .write_all(b"GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n")
+                    .unwrap();
+                let mut text = String::new();
+                stream.read_to_string(&mut text).unwrap();
+                sender.send(text).unwrap();
+            })
+            .unwrap();
+            let response = response.recv().unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.contains("# TYPE egui_app_frame_time_seconds summary\n"));
+        }
+
+        #[test]
+        fn stops_reading_long_requests() {
+            let (sender, done) = std::sync::mpsc::channel::<()>();
+            let start = Instant::now();
+            // Headers that never end, from a client that keeps the
+            // connection open; the response is not waited for.
+            serve_one(move |address| {
+                let mut stream = TcpStream::connect(address).unwrap();
+                let _ = stream.write_all(b"GET /metrics HTTP/1.1\r\n");
+                for _ in 0..1000 {
+                    let _ = stream.write_all(b"X-Padding: 0123456789\r\n");
+                }
+                std::thread::spawn(move || {
+                    let _ = done.recv();
+                    drop(stream);
+                });
+            })
+            .unwrap();
+            assert!(start.elapsed() < REQUEST_TIMEOUT);
+            drop(sender);
+        }
+    }
+}
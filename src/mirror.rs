@@ -0,0 +1,360 @@
+use std::sync::Arc;
+
+use egui_winit::winit::{
+    dpi::PhysicalSize,
+    event_loop::ActiveEventLoop,
+    window::{Fullscreen, Window, WindowAttributes, WindowId},
+};
+use wgpu::{CommandEncoder, Device, TextureView};
+
+use crate::{
+    error::AppError,
+    gpu,
+    letterbox::{self, Letterbox},
+    supersample,
+    surface::{RenderSurface, SurfaceFrame},
+};
+
+fn request_id() -> egui::Id {
+    egui::Id::new("mirror_request")
+}
+
+fn state_id() -> egui::Id {
+    egui::Id::new("mirror")
+}
+
+/// Asks for the mirror window to be opened or closed. Applied after the
+/// current frame.
+pub fn set_mirror_open(ctx: &egui::Context, open: bool) {
+    ctx.data_mut(|d| d.insert_temp(request_id(), open));
+}
+
+/// Whether the mirror window is showing.
+pub fn is_mirror_open(ctx: &egui::Context) -> bool {
+    ctx.data(|d| d.get_temp(state_id())).unwrap_or(false)
+}
+
+pub fn take_request(ctx: &egui::Context) -> Option<bool> {
+    ctx.data_mut(|d| d.remove_temp(request_id()))
+}
+
+pub fn set_state(ctx: &egui::Context, open: bool) {
+    ctx.data_mut(|d| d.insert_temp(state_id(), open));
+}
+
+/// Borderless fullscreen on the monitor called `monitor`, or on the first
+/// one the main window is not on. With a single monitor the mirror is an
+/// ordinary window, so that it does not cover the main one.
+pub fn window_attributes(
+    event_loop: &ActiveEventLoop,
+    main_window: &Window,
+    monitor: Option<&str>,
+) -> WindowAttributes {
+    let attributes =
+        Window::default_attributes().with_title(format!("{} (mirror)", env!("CARGO_PKG_NAME")));
+    let current = main_window.current_monitor();
+    let target = match monitor {
+        Some(name) => {
+            let found = event_loop
+                .available_monitors()
+                .find(|monitor| monitor.name().as_deref() == Some(name));
+            if found.is_none() {
+                log::warn!("no monitor called {name} to mirror to");
+            }
+            found
+        }
+        None => event_loop
+            .available_monitors()
+            .find(|monitor| Some(monitor) != current.as_ref()),
+    };
+    match target {
+        Some(monitor) => attributes
+            .with_decorations(false)
+            .with_fullscreen(Some(Fullscreen::Borderless(Some(monitor)))),
+        None => {
+            log::info!("no other monitor, mirroring into a window");
+            attributes.with_inner_size(main_window.inner_size())
+        }
+    }
+}
+
+/// Shows the main window's frame in a second window, e.g. on a projector.
+/// egui runs and tessellates once; the frame it paints is drawn into both
+/// surfaces, scaled to fit each with its aspect ratio. The mirror takes no
+/// input.
+///
+/// egui needs a target the mirror can sample: the design resolution's
+/// letterbox when there is one, otherwise the mirror's own target of the
+/// main surface's size, which is then drawn onto the main surface too.
+pub struct Mirror {
+    /// `None` for an offscreen mirror in tests.
+    window: Option<Arc<Window>>,
+    surface: RenderSurface,
+    config: wgpu::SurfaceConfiguration,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    /// egui's target without a letterbox, and the bind group that samples
+    /// it for the mirror.
+    target: Option<(Letterbox, wgpu::BindGroup)>,
+    /// Samples the letterbox's target, which never changes.
+    letterbox_bind_group: Option<wgpu::BindGroup>,
+    reconfigure: bool,
+}
+
+impl Mirror {
+    /// The mirror's surface uses the main surface's `format`.
+    pub fn new(
+        instance: &wgpu::Instance,
+        device: &Device,
+        window: Window,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, AppError> {
+        let window = Arc::new(window);
+        let surface = RenderSurface::Window(instance.create_surface(window.clone())?);
+        let size = window.inner_size();
+        Self::with_surface(
+            device,
+            Some(window),
+            surface,
+            [size.width, size.height],
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    }
+
+    /// A mirror of `size` drawing into a texture, see [`Self::texture`].
+    #[cfg(any(test, feature = "testing"))]
+    pub fn offscreen(
+        device: &Device,
+        size: [u32; 2],
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, AppError> {
+        Self::with_surface(
+            device,
+            None,
+            RenderSurface::Offscreen(None),
+            size,
+            format,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        )
+    }
+
+    fn with_surface(
+        device: &Device,
+        window: Option<Arc<Window>>,
+        mut surface: RenderSurface,
+        [width, height]: [u32; 2],
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+    ) -> Result<Self, AppError> {
+        let ([width, height], _) = gpu::clamp_surface_size(device, [width.max(1), height.max(1)]);
+        let config = wgpu::SurfaceConfiguration {
+            usage,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        surface.configure(device, &config)?;
+        let (bind_group_layout, pipeline) = supersample::create_blit_pipeline(device, format);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mirror sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Ok(Self {
+            window,
+            surface,
+            config,
+            bind_group_layout,
+            pipeline,
+            sampler,
+            target: None,
+            letterbox_bind_group: None,
+            reconfigure: false,
+        })
+    }
+
+    pub fn window_id(&self) -> Option<WindowId> {
+        self.window.as_ref().map(|window| window.id())
+    }
+
+    /// What the offscreen mirror last drew.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn texture(&self) -> Option<&Arc<wgpu::Texture>> {
+        match &self.surface {
+            RenderSurface::Offscreen(texture) => texture.as_ref(),
+            RenderSurface::Window(_) => None,
+        }
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        self.reconfigure = true;
+    }
+
+    /// Makes sure the own target has the main surface's size. Call before
+    /// [`Self::target`] on frames without a letterbox.
+    pub fn prepare_target(&mut self, device: &Device, format: wgpu::TextureFormat, size: [u32; 2]) {
+        if self
+            .target
+            .as_ref()
+            .is_some_and(|(target, _)| target.size() == size)
+        {
+            return;
+        }
+        let target = Letterbox::new(device, format, size, wgpu::FilterMode::Nearest);
+        let bind_group = supersample::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            target.target(),
+            &self.sampler,
+        );
+        self.target = Some((target, bind_group));
+    }
+
+    /// egui's target, after [`Self::prepare_target`].
+    pub fn target(&self) -> Option<&TextureView> {
+        self.target.as_ref().map(|(target, _)| target.target())
+    }
+
+    /// Draws the own target onto the main surface.
+    pub fn show_target(
+        &self,
+        encoder: &mut CommandEncoder,
+        surface_view: &TextureView,
+        surface_size: [u32; 2],
+    ) {
+        if let Some((target, _)) = &self.target {
+            target.blit(encoder, surface_view, surface_size);
+        }
+    }
+
+    /// Draws the frame into the mirror's surface. The returned texture is
+    /// presented with [`Self::present`] after the main window's.
+    pub fn blit(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        letterbox: Option<&Letterbox>,
+    ) -> Option<SurfaceFrame> {
+        profiling::scope!("mirror");
+        if std::mem::take(&mut self.reconfigure) {
+            let ([width, height], _) =
+                gpu::clamp_surface_size(device, [self.config.width, self.config.height]);
+            [self.config.width, self.config.height] = [width, height];
+            if let Err(err) = self.surface.configure(device, &self.config) {
+                log::warn!("mirror: {err}");
+                self.reconfigure = true;
+                return None;
+            }
+        }
+        let (bind_group, content_size) = match letterbox {
+            Some(letterbox) => {
+                let bind_group = self.letterbox_bind_group.get_or_insert_with(|| {
+                    supersample::create_bind_group(
+                        device,
+                        &self.bind_group_layout,
+                        letterbox.target(),
+                        &self.sampler,
+                    )
+                });
+                (&*bind_group, letterbox.size())
+            }
+            None => {
+                let (target, bind_group) = self.target.as_ref()?;
+                (bind_group, target.size())
+            }
+        };
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(err) => {
+                // Skips a frame; the main window carries on either way.
+                log::debug!("mirror: {err}");
+                if matches!(err, wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) {
+                    self.reconfigure = true;
+                }
+                return None;
+            }
+        };
+        let view = frame
+            .texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        letterbox::blit_fit(
+            encoder,
+            &self.pipeline,
+            bind_group,
+            content_size,
+            &view,
+            [self.config.width, self.config.height],
+        );
+        Some(frame)
+    }
+
+    pub fn present(&self, frame: SurfaceFrame) {
+        if let Some(window) = &self.window {
+            window.pre_present_notify();
+        }
+        frame.present();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{options::Options, testing::TestApp, user_app::SimpleApp};
+
+    #[test]
+    fn mirror_shows_the_frame_fitted() {
+        let open = Rc::new(Cell::new(true));
+        let app = {
+            let open = open.clone();
+            SimpleApp(move |ctx: &egui::Context| {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none().fill(egui::Color32::RED))
+                    .show(ctx, |_| {});
+                if open.get() != is_mirror_open(ctx) {
+                    set_mirror_open(ctx, open.get());
+                }
+            })
+        };
+        let options = Options {
+            window_size: egui::vec2(200., 100.),
+            skip_unchanged_frames: false,
+            ..Default::default()
+        };
+        let Some(mut test_app) = TestApp::with_options(app, options) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        assert!(is_mirror_open(test_app.ctx()));
+
+        // The mirror keeps its size when the main window becomes square:
+        // bars left and right.
+        test_app.resize([100, 100]);
+        let (_, main) = test_app.step(Vec::new()).pixels.unwrap();
+        assert_eq!(main[4 * (50 * 100 + 50)..][..4], [255, 0, 0, 255]);
+        let ([width, height], mirror) = test_app.mirror_pixels().unwrap();
+        assert_eq!([width, height], [200, 100]);
+        let pixel = |x: u32| {
+            let i = 4 * (50 * width + x) as usize;
+            mirror[i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(10), [0, 0, 0, 255]);
+        assert_eq!(pixel(100), [255, 0, 0, 255]);
+        assert_eq!(pixel(190), [0, 0, 0, 255]);
+
+        open.set(false);
+        test_app.step(Vec::new());
+        assert!(test_app.mirror_pixels().is_none());
+        assert!(!is_mirror_open(test_app.ctx()));
+    }
+}
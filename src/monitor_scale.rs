@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::monitors::MonitorInfo;
+
+fn request_id() -> egui::Id {
+    egui::Id::new("monitor_scale_request")
+}
+
+fn state_id() -> egui::Id {
+    egui::Id::new("monitor_scale")
+}
+
+/// Sets the UI scale for the monitor the window is on. Applied after the
+/// current frame, so a slider previews it live.
+pub fn set_current(ctx: &egui::Context, scale: f32) {
+    ctx.data_mut(|d| d.insert_temp(request_id(), Some(scale)));
+}
+
+/// The UI scale for the monitor the window is on, 1 without an override.
+pub fn current(ctx: &egui::Context) -> f32 {
+    ctx.data(|d| d.get_temp(state_id())).unwrap_or(1.)
+}
+
+/// Per-monitor UI scales for displays whose reported DPI is wrong, e.g. a TV
+/// across the room. egui's zoom factor is the global zoom (Ctrl +/-) times
+/// the scale of the monitor the window is on; the global part survives
+/// moving between monitors.
+pub struct MonitorScales {
+    /// One `scale<TAB>monitor` line per override.
+    path: Option<PathBuf>,
+    scales: BTreeMap<String, f32>,
+    monitor: Option<String>,
+    applied: f32,
+}
+
+impl MonitorScales {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let scales = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (scale, monitor) = line.split_once('\t')?;
+                Some((monitor.to_owned(), scale.parse().ok()?))
+            })
+            .collect();
+        Self {
+            path,
+            scales,
+            monitor: None,
+            applied: 1.,
+        }
+    }
+
+    /// Switches to the scale of the monitor the window is now on.
+    pub fn on_monitor(&mut self, ctx: &egui::Context, monitor: Option<&MonitorInfo>) {
+        let monitor = monitor.map(MonitorInfo::key);
+        if monitor == self.monitor {
+            return;
+        }
+        let scale = monitor
+            .as_ref()
+            .and_then(|monitor| self.scales.get(monitor))
+            .copied()
+            .unwrap_or(1.);
+        self.monitor = monitor;
+        self.apply(ctx, scale);
+    }
+
+    /// Takes a new scale from the UI. Call after each pass.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        let Some(scale) = ctx
+            .data_mut(|d| d.remove_temp::<Option<f32>>(request_id()))
+            .flatten()
+        else {
+            return;
+        };
+        let Some(monitor) = &self.monitor else {
+            return;
+        };
+        if scale == 1. {
+            self.scales.remove(monitor);
+        } else {
+            self.scales.insert(monitor.clone(), scale);
+        }
+        self.save();
+        self.apply(ctx, scale);
+    }
+
+    fn apply(&mut self, ctx: &egui::Context, scale: f32) {
+        if scale != self.applied {
+            let global = ctx.zoom_factor() / self.applied;
+            ctx.set_zoom_factor(global * scale);
+            self.applied = scale;
+        }
+        ctx.data_mut(|d| d.insert_temp(state_id(), scale));
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let text: String = self
+            .scales
+            .iter()
+            .map(|(monitor, scale)| format!("{scale}\t{monitor}\n"))
+            .collect();
+        if let Err(err) = std::fs::write(path, text) {
+            log::warn!(
+                "failed to save the monitor scales to {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desk() -> MonitorInfo {
+        MonitorInfo::simulated(1, "DELL U2720Q", [3840, 2160], 2.)
+    }
+
+    fn tv() -> MonitorInfo {
+        MonitorInfo::simulated(2, "LG TV", [3840, 2160], 1.)
+    }
+
+    /// egui applies a new zoom factor with the next pass, as in the app,
+    /// where the monitor changes between frames.
+    fn zoom_after_pass(ctx: &egui::Context) -> f32 {
+        let _ = ctx.run(Default::default(), |_| {});
+        ctx.zoom_factor()
+    }
+
+    fn scales_file(name: &str, text: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("{}-monitor-scales-{name}.txt", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn looks_up_the_scale_of_the_current_monitor() {
+        let path = scales_file("lookup", "1.75\tLG TV 3840x2160\nbroken\nx\tDELL\n");
+        let mut scales = MonitorScales::load(Some(path.clone()));
+        let ctx = egui::Context::default();
+
+        scales.on_monitor(&ctx, Some(&desk()));
+        assert_eq!(current(&ctx), 1.);
+        assert_eq!(zoom_after_pass(&ctx), 1.);
+
+        scales.on_monitor(&ctx, Some(&tv()));
+        assert_eq!(current(&ctx), 1.75);
+        assert_eq!(zoom_after_pass(&ctx), 1.75);
+
+        // The same model at another size is another monitor.
+        let small_tv = MonitorInfo::simulated(3, "LG TV", [1920, 1080], 1.);
+        scales.on_monitor(&ctx, Some(&small_tv));
+        assert_eq!(current(&ctx), 1.);
+
+        scales.on_monitor(&ctx, None);
+        assert_eq!(zoom_after_pass(&ctx), 1.);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn global_zoom_survives_moving_between_monitors() {
+        let path = scales_file("global", "2\tLG TV 3840x2160\n");
+        let mut scales = MonitorScales::load(Some(path.clone()));
+        let ctx = egui::Context::default();
+        scales.on_monitor(&ctx, Some(&desk()));
+        // Ctrl +
+        ctx.set_zoom_factor(1.1);
+        zoom_after_pass(&ctx);
+
+        scales.on_monitor(&ctx, Some(&tv()));
+        assert!((zoom_after_pass(&ctx) - 2.2).abs() < 1e-6);
+        scales.on_monitor(&ctx, Some(&desk()));
+        assert!((zoom_after_pass(&ctx) - 1.1).abs() < 1e-6);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn new_scales_are_applied_and_saved() {
+        let path = scales_file("save", "");
+        let mut scales = MonitorScales::load(Some(path.clone()));
+        let ctx = egui::Context::default();
+
+        // Without a known monitor there is nothing to save it for.
+        set_current(&ctx, 1.5);
+        scales.update(&ctx);
+        assert_eq!(current(&ctx), 1.);
+
+        scales.on_monitor(&ctx, Some(&tv()));
+        set_current(&ctx, 1.5);
+        scales.update(&ctx);
+        assert_eq!(zoom_after_pass(&ctx), 1.5);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "1.5\tLG TV 3840x2160\n"
+        );
+
+        // A fresh start picks it up again.
+        let mut reloaded = MonitorScales::load(Some(path.clone()));
+        let ctx = egui::Context::default();
+        reloaded.on_monitor(&ctx, Some(&tv()));
+        assert_eq!(current(&ctx), 1.5);
+
+        // Back to 1 removes the override.
+        set_current(&ctx, 1.);
+        reloaded.update(&ctx);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(path).unwrap();
+    }
+}
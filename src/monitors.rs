@@ -0,0 +1,138 @@
+use egui_winit::winit::{monitor::MonitorHandle, window::Window};
+
+/// Stays valid when monitors come and go; a disconnected monitor's id simply
+/// no longer matches any entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MonitorId(u64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorInfo {
+    pub id: MonitorId,
+    pub name: Option<String>,
+    /// Top-left corner in global desktop coordinates, physical pixels.
+    pub position: [i32; 2],
+    pub size: [u32; 2],
+    pub scale_factor: f64,
+    pub refresh_millihertz: Option<u32>,
+}
+
+/// The connected monitors as last seen by the window. winit does not report
+/// work areas, so those are not included.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Monitors {
+    pub monitors: Vec<MonitorInfo>,
+    /// The monitor that contains the window.
+    pub current: Option<MonitorId>,
+    /// Top-left corner of the window's client area, physical pixels.
+    window_origin: [i32; 2],
+    pixels_per_point: f32,
+}
+
+impl MonitorInfo {
+    /// Identifies the monitor across runs, unlike [`MonitorId`].
+    pub fn key(&self) -> String {
+        let [width, height] = self.size;
+        format!(
+            "{} {width}x{height}",
+            self.name.as_deref().unwrap_or("Unknown")
+        )
+    }
+}
+
+#[cfg(test)]
+impl MonitorInfo {
+    /// A monitor as winit would report it, for tests.
+    pub fn simulated(id: u64, name: &str, size: [u32; 2], scale_factor: f64) -> Self {
+        Self {
+            id: MonitorId(id),
+            name: Some(name.to_owned()),
+            position: [0, 0],
+            size,
+            scale_factor,
+            refresh_millihertz: Some(60_000),
+        }
+    }
+}
+
+impl Monitors {
+    pub fn get(&self, id: MonitorId) -> Option<&MonitorInfo> {
+        self.monitors.iter().find(|monitor| monitor.id == id)
+    }
+
+    pub fn current(&self) -> Option<&MonitorInfo> {
+        self.current.and_then(|id| self.get(id))
+    }
+
+    /// Converts global desktop coordinates in physical pixels to egui points
+    /// relative to the window.
+    pub fn desktop_to_window(&self, [x, y]: [i32; 2]) -> egui::Pos2 {
+        egui::pos2(
+            (x - self.window_origin[0]) as f32 / self.pixels_per_point,
+            (y - self.window_origin[1]) as f32 / self.pixels_per_point,
+        )
+    }
+
+    pub fn window_to_desktop(&self, pos: egui::Pos2) -> [i32; 2] {
+        [
+            self.window_origin[0] + (pos.x * self.pixels_per_point).round() as i32,
+            self.window_origin[1] + (pos.y * self.pixels_per_point).round() as i32,
+        ]
+    }
+}
+
+#[derive(Default)]
+pub struct MonitorTracker {
+    handles: Vec<(MonitorHandle, MonitorId)>,
+    next_id: u64,
+    monitors: Monitors,
+}
+
+impl MonitorTracker {
+    pub fn monitors(&self) -> &Monitors {
+        &self.monitors
+    }
+
+    /// Re-reads the monitors and returns them if anything changed.
+    pub fn refresh(&mut self, window: &Window, egui_ctx: &egui::Context) -> Option<&Monitors> {
+        profiling::function_scope!();
+        let available: Vec<MonitorHandle> = window.available_monitors().collect();
+        self.handles
+            .retain(|(handle, _)| available.iter().any(|available| available == handle));
+        let monitors = available
+            .iter()
+            .map(|handle| MonitorInfo {
+                id: self.id(handle),
+                name: handle.name(),
+                position: handle.position().into(),
+                size: handle.size().into(),
+                scale_factor: handle.scale_factor(),
+                refresh_millihertz: handle.refresh_rate_millihertz(),
+            })
+            .collect();
+        let current = window
+            .current_monitor()
+            .and_then(|current| self.handles.iter().find(|(handle, _)| *handle == current))
+            .map(|(_, id)| *id);
+        let monitors = Monitors {
+            monitors,
+            current,
+            window_origin: window.inner_position().map_or([0, 0], Into::into),
+            pixels_per_point: egui_winit::pixels_per_point(egui_ctx, window),
+        };
+        if monitors == self.monitors {
+            return None;
+        }
+        self.monitors = monitors;
+        Some(&self.monitors)
+    }
+
+    fn id(&mut self, handle: &MonitorHandle) -> MonitorId {
+        if let Some((_, id)) = self.handles.iter().find(|(known, _)| known == handle) {
+            return *id;
+        }
+        let id = MonitorId(self.next_id);
+        self.next_id += 1;
+        self.handles.push((handle.clone(), id));
+        id
+    }
+}
@@ -0,0 +1,419 @@
+//! Native desktop notifications, e.g. for a long export that finished while
+//! the app was in the background. [`notify`] shows one after the current
+//! frame; what the user does with it arrives as [`UserEvent::Notification`]
+//! for [`take_events`], and a click also brings the window to the front.
+
+use crate::event::{EventSender, UserEvent};
+
+fn requests_id() -> egui::Id {
+    egui::Id::new("notification_requests")
+}
+
+fn next_id() -> egui::Id {
+    egui::Id::new("notification_next")
+}
+
+fn events_id() -> egui::Id {
+    egui::Id::new("notification_events")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NotificationId(u64);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// The label of a button on the notification, e.g. "Show".
+    pub action: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotificationEvent {
+    /// The user clicked the notification itself.
+    Clicked,
+    /// The user clicked [`Notification::action`].
+    Action,
+    /// Dismissed or expired without a click.
+    Closed,
+    /// Not shown, so the app should tell the user some other way, e.g. in
+    /// the window.
+    Failed(NotifyError),
+}
+
+impl NotificationEvent {
+    /// Whether the window comes to the front.
+    pub fn activates(&self) -> bool {
+        matches!(self, Self::Clicked | Self::Action)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum NotifyError {
+    #[cfg_attr(
+        all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ),
+        allow(dead_code)
+    )]
+    #[error("notifications are not supported on this platform")]
+    Unsupported,
+    /// No session bus, or no notification daemon on it.
+    #[error("no notification service is running: {0}")]
+    NoService(String),
+    #[error("the notification failed: {0}")]
+    Failed(String),
+}
+
+/// Shows a notification after the current frame.
+pub fn notify(ctx: &egui::Context, notification: Notification) -> NotificationId {
+    let id = ctx.data_mut(|d| {
+        let next = d.get_temp_mut_or_default::<u64>(next_id());
+        *next += 1;
+        let id = NotificationId(*next);
+        d.get_temp_mut_or_default::<Vec<(NotificationId, Notification)>>(requests_id())
+            .push((id, notification));
+        id
+    });
+    ctx.request_repaint();
+    id
+}
+
+/// What happened to the app's notifications since the last call, oldest
+/// first.
+pub fn take_events(ctx: &egui::Context) -> Vec<(NotificationId, NotificationEvent)> {
+    ctx.data_mut(|d| d.remove_temp(events_id()))
+        .unwrap_or_default()
+}
+
+pub fn take_requests(ctx: &egui::Context) -> Vec<(NotificationId, Notification)> {
+    ctx.data_mut(|d| d.remove_temp(requests_id()))
+        .unwrap_or_default()
+}
+
+/// Stores an event from [`UserEvent::Notification`] for [`take_events`].
+pub fn push_event(ctx: &egui::Context, id: NotificationId, event: NotificationEvent) {
+    ctx.data_mut(|d| {
+        d.get_temp_mut_or_default::<Vec<(NotificationId, NotificationEvent)>>(events_id())
+            .push((id, event));
+    });
+    ctx.request_repaint();
+}
+
+/// Shows the notification and follows it on a thread of its own until it
+/// closes, sending what happens through `sender`.
+pub fn show(id: NotificationId, notification: Notification, sender: EventSender) {
+    let thread_sender = sender.clone();
+    let spawned = std::thread::Builder::new()
+        .name("notification".to_owned())
+        .spawn(move || {
+            let send = |event| thread_sender.send(UserEvent::Notification(id, event));
+            if let Err(err) = platform::run(&notification, &send) {
+                log::warn!("{err}");
+                send(NotificationEvent::Failed(err));
+            }
+        });
+    if let Err(err) = spawned {
+        sender.send(UserEvent::Notification(
+            id,
+            NotificationEvent::Failed(NotifyError::Failed(format!(
+                "cannot start its thread: {err}"
+            ))),
+        ));
+    }
+}
+
+/// Through the freedesktop notification service, which desktops and most
+/// window manager setups run.
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod platform {
+    use std::collections::HashMap;
+
+    use zbus::{
+        blocking::{Connection, MessageIterator},
+        message::Type,
+        zvariant::Value,
+        MatchRule,
+    };
+
+    use super::{Notification, NotificationEvent, NotifyError};
+
+    const SERVICE: &str = "org.freedesktop.Notifications";
+    const PATH: &str = "/org/freedesktop/Notifications";
+
+    /// The action the service reports for a click on the notification.
+    const DEFAULT_ACTION: &str = "default";
+    const ACTION: &str = "action";
+
+    fn failed(err: zbus::Error) -> NotifyError {
+        NotifyError::Failed(err.to_string())
+    }
+
+    /// Returns once the notification has closed, or once the user clicked
+    /// it, as not every service closes it then.
+    pub fn run(
+        notification: &Notification,
+        send: &dyn Fn(NotificationEvent),
+    ) -> Result<(), NotifyError> {
+        let connection =
+            Connection::session().map_err(|err| NotifyError::NoService(err.to_string()))?;
+        // Signals from before the call's reply would be lost otherwise.
+        let rule = MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface(SERVICE)
+            .and_then(|rule| rule.path(PATH))
+            .map_err(failed)?
+            .build();
+        let signals = MessageIterator::for_match_rule(rule, &connection, None).map_err(failed)?;
+
+        let mut actions = vec![DEFAULT_ACTION, ""];
+        if let Some(label) = &notification.action {
+            actions.extend([ACTION, label.as_str()]);
+        }
+        let hints: HashMap<&str, Value> = HashMap::new();
+        let reply = connection
+            .call_method(
+                Some(SERVICE),
+                PATH,
+                Some(SERVICE),
+                "Notify",
+                &(
+                    env!("CARGO_PKG_NAME"),
+                    0u32,
+                    "",
+                    notification.title.as_str(),
+                    notification.body.as_str(),
+                    actions,
+                    hints,
+                    -1i32,
+                ),
+            )
+            .map_err(|err| match &err {
+                zbus::Error::MethodError(name, ..)
+                    if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown" =>
+                {
+                    NotifyError::NoService(err.to_string())
+                }
+                _ => failed(err),
+            })?;
+        let id: u32 = reply.body().deserialize().map_err(failed)?;
+
+        for signal in signals {
+            let signal = signal.map_err(failed)?;
+            let header = signal.header();
+            match header.member().map(|member| member.as_str()) {
+                Some("ActionInvoked") => {
+                    let (invoked, key): (u32, String) =
+                        signal.body().deserialize().map_err(failed)?;
+                    if invoked != id {
+                        continue;
+                    }
+                    send(if key == ACTION {
+                        NotificationEvent::Action
+                    } else {
+                        NotificationEvent::Clicked
+                    });
+                    return Ok(());
+                }
+                Some("NotificationClosed") => {
+                    let (closed, _reason): (u32, u32) =
+                        signal.body().deserialize().map_err(failed)?;
+                    if closed == id {
+                        send(NotificationEvent::Closed);
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::sync::Mutex;
+
+        use zbus::{
+            fdo::{RequestNameFlags, RequestNameReply},
+            zvariant::OwnedValue,
+        };
+
+        use super::*;
+
+        fn notification(action: Option<&str>) -> Notification {
+            Notification {
+                title: "Export finished".to_owned(),
+                body: "report.pdf".to_owned(),
+                action: action.map(str::to_owned),
+            }
+        }
+
+        /// Stands in for the notification service on a session bus without
+        /// one, e.g. under `dbus-run-session`. Skipped without a session bus,
+        /// or with a real service on it.
+        #[test]
+        fn clicks_and_closes_come_back_as_events() {
+            let Ok(service) = Connection::session() else {
+                return;
+            };
+            let events = Mutex::new(Vec::new());
+            let send = |event| events.lock().unwrap().push(event);
+            match run(&notification(None), &send) {
+                Err(NotifyError::NoService(_)) => {}
+                // A real service answered.
+                _ => return,
+            }
+            let owner =
+                service.request_name_with_flags(SERVICE, RequestNameFlags::DoNotQueue.into());
+            if !matches!(owner, Ok(RequestNameReply::PrimaryOwner)) {
+                return;
+            }
+            let calls = MessageIterator::from(&service);
+
+            let shown = std::thread::scope(|scope| {
+                let notifications = scope.spawn(|| {
+                    [
+                        run(&notification(Some("Show")), &send),
+                        run(&notification(None), &send),
+                        run(&notification(None), &send),
+                    ]
+                });
+                let mut shown = Vec::new();
+                for call in calls {
+                    let call = call.unwrap();
+                    let header = call.header();
+                    if header.member().map(|member| member.as_str()) != Some("Notify") {
+                        continue;
+                    }
+                    #[allow(clippy::type_complexity)]
+                    let (app, _, _, title, _, actions, _, _): (
+                        String,
+                        u32,
+                        String,
+                        String,
+                        String,
+                        Vec<String>,
+                        HashMap<String, OwnedValue>,
+                        i32,
+                    ) = call.body().deserialize().unwrap();
+                    assert_eq!(app, env!("CARGO_PKG_NAME"));
+                    assert_eq!(title, "Export finished");
+                    shown.push(actions);
+                    let id = shown.len() as u32 * 10;
+                    service.reply(&call, &id).unwrap();
+
+                    // Another notification's signal comes first each time,
+                    // and must not count.
+                    let sender = header.sender().unwrap().to_string();
+                    let answer = |id: u32| {
+                        let destination = Some(sender.as_str());
+                        match shown.len() {
+                            1 => service.emit_signal(
+                                destination,
+                                PATH,
+                                SERVICE,
+                                "ActionInvoked",
+                                &(id, ACTION),
+                            ),
+                            2 => service.emit_signal(
+                                destination,
+                                PATH,
+                                SERVICE,
+                                "ActionInvoked",
+                                &(id, DEFAULT_ACTION),
+                            ),
+                            _ => service.emit_signal(
+                                destination,
+                                PATH,
+                                SERVICE,
+                                "NotificationClosed",
+                                &(id, 2u32),
+                            ),
+                        }
+                        .unwrap();
+                    };
+                    answer(id + 1);
+                    answer(id);
+                    if shown.len() == 3 {
+                        break;
+                    }
+                }
+                for result in notifications.join().unwrap() {
+                    assert_eq!(result, Ok(()));
+                }
+                shown
+            });
+
+            assert_eq!(
+                shown[0],
+                [DEFAULT_ACTION, "", ACTION, "Show"].map(str::to_owned)
+            );
+            assert_eq!(shown[1], [DEFAULT_ACTION, ""].map(str::to_owned));
+            assert_eq!(
+                events.into_inner().unwrap(),
+                [
+                    NotificationEvent::Action,
+                    NotificationEvent::Clicked,
+                    NotificationEvent::Closed,
+                ]
+            );
+        }
+    }
+}
+
+/// Windows needs WinRT toasts and macOS `UNUserNotificationCenter`, neither
+/// of which the plain C APIs used here reach.
+#[cfg(not(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+)))]
+mod platform {
+    use super::{Notification, NotificationEvent, NotifyError};
+
+    pub fn run(
+        _notification: &Notification,
+        _send: &dyn Fn(NotificationEvent),
+    ) -> Result<(), NotifyError> {
+        Err(NotifyError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_wait_for_the_frame_and_events_for_the_app() {
+        let ctx = egui::Context::default();
+        let notification = Notification {
+            title: "Export finished".to_owned(),
+            body: String::new(),
+            action: None,
+        };
+        let first = notify(&ctx, notification.clone());
+        let second = notify(&ctx, notification.clone());
+        assert_ne!(first, second);
+        assert_eq!(
+            take_requests(&ctx),
+            [(first, notification.clone()), (second, notification)]
+        );
+        assert!(take_requests(&ctx).is_empty());
+
+        push_event(&ctx, second, NotificationEvent::Closed);
+        push_event(&ctx, first, NotificationEvent::Clicked);
+        assert_eq!(
+            take_events(&ctx),
+            [
+                (second, NotificationEvent::Closed),
+                (first, NotificationEvent::Clicked)
+            ]
+        );
+        assert!(take_events(&ctx).is_empty());
+        assert!(NotificationEvent::Action.activates());
+        assert!(!NotificationEvent::Closed.activates());
+    }
+}
@@ -0,0 +1,49 @@
+use egui::OpenUrl;
+
+fn pending_id() -> egui::Id {
+    egui::Id::new("open_url_pending")
+}
+
+fn approved_id() -> egui::Id {
+    egui::Id::new("open_url_approved")
+}
+
+/// What the app wants done about a URL egui asks to open, e.g. from a
+/// clicked hyperlink.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UrlDecision {
+    Allow,
+    Deny,
+    /// Leaves the URL to the app, see [`pending`], which then calls
+    /// [`open_url_now`] or [`dismiss`].
+    Ask,
+}
+
+/// The URL waiting for the app's confirmation.
+pub fn pending(ctx: &egui::Context) -> Option<OpenUrl> {
+    ctx.data(|d| d.get_temp::<Option<OpenUrl>>(pending_id()))
+        .flatten()
+}
+
+/// Opens the URL after the current frame, without asking the app again.
+pub fn open_url_now(ctx: &egui::Context, url: OpenUrl) {
+    ctx.data_mut(|d| {
+        d.insert_temp::<Option<OpenUrl>>(pending_id(), None);
+        d.insert_temp(approved_id(), Some(url));
+    });
+}
+
+pub fn dismiss(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp::<Option<OpenUrl>>(pending_id(), None));
+}
+
+/// Repaints, so that the app can ask right away.
+pub fn set_pending(ctx: &egui::Context, url: OpenUrl) {
+    ctx.data_mut(|d| d.insert_temp(pending_id(), Some(url)));
+    ctx.request_repaint();
+}
+
+pub fn take_approved(ctx: &egui::Context) -> Option<OpenUrl> {
+    ctx.data_mut(|d| d.remove_temp::<Option<OpenUrl>>(approved_id()))
+        .flatten()
+}
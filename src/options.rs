@@ -0,0 +1,326 @@
+use std::{num::NonZeroUsize, path::PathBuf, time::Duration};
+
+use egui::Vec2;
+
+#[cfg(feature = "remote-control")]
+use crate::remote_control::RemoteControlOptions;
+use crate::{
+    benchmark::BenchmarkLength, command_palette, debug_window, document, embed::ParentWindow,
+    gpu::LimitsPreset, kiosk::KioskOptions, metrics::MetricsOptions, titlebar::Backdrop,
+    window_shadow,
+};
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub window_size: Vec2,
+    pub gpu_profiling: bool,
+    pub show_frame_overlay: bool,
+    pub show_render_stats: bool,
+    pub frame_limit: Option<u64>,
+    pub timing_export: Option<PathBuf>,
+    pub measure_latency: bool,
+    pub warm_up: bool,
+    pub skip_unchanged_frames: bool,
+    pub tessellation_cache: bool,
+    /// Renders egui at this multiple of the surface resolution and
+    /// downsamples the result, see [`crate::supersample::Supersampler`].
+    pub render_scale: f32,
+    /// Lays egui out at this fixed size in points and scales the picture to
+    /// the window between black bars, see [`crate::letterbox::Letterbox`].
+    pub design_resolution: Option<[u32; 2]>,
+    /// How the design resolution's picture is scaled to the window.
+    pub design_filter: wgpu::FilterMode,
+    /// Opens a second window showing the same frame, see
+    /// [`crate::mirror::Mirror`].
+    pub mirror: bool,
+    /// The monitor to put the mirror on, by name. By default the first one
+    /// the main window is not on.
+    pub mirror_monitor: Option<String>,
+    /// Starts with the magnifier on, whatever was saved, see
+    /// [`crate::magnifier::Magnifier`].
+    pub magnifier: bool,
+    /// Steps quality down while the p95 frame time exceeds it, see
+    /// [`crate::adaptive_quality`].
+    pub frame_budget: Option<Duration>,
+    /// macOS only: content extends under a transparent titlebar, with the
+    /// traffic-light buttons floating over it.
+    pub unified_titlebar: bool,
+    /// Replaces the OS decorations with a titlebar drawn by egui.
+    pub custom_titlebar: bool,
+    /// Creates the window as a child of a host application's window, see
+    /// [`crate::embed`].
+    pub parent_window: Option<ParentWindow>,
+    /// Gives the custom titlebar's window a shadow and rounded corners,
+    /// drawn by the app where the OS does not, see
+    /// [`crate::window_shadow::WindowShadow`].
+    pub window_shadow: bool,
+    pub backdrop: Option<Backdrop>,
+    /// Hides the window from screenshots and screen sharing.
+    pub exclude_from_capture: bool,
+    /// The title while the app shows a document, see
+    /// [`crate::document::format_title`].
+    pub title_pattern: String,
+    /// A transparent, undecorated window on top that lets clicks through
+    /// except over egui's windows, see [`crate::click_through::ClickThrough`].
+    pub click_through: bool,
+    pub coalesce_pointer_moves: bool,
+    /// Sends pastes to [`crate::user_app::UserApp::on_paste`] with HTML as well as
+    /// text, see [`crate::clipboard::Clipboard`].
+    pub rich_paste: bool,
+    pub record_input: Option<PathBuf>,
+    /// Logs every event and each frame's input, see
+    /// [`crate::event_trace::EventTrace`].
+    pub trace_events: bool,
+    /// Leaves typed text out of the trace.
+    pub trace_redact: bool,
+    pub replay_input: Option<PathBuf>,
+    /// Turns right clicks into middle clicks, for trackpads without a
+    /// middle button.
+    pub right_click_as_middle: bool,
+    /// Logs the events egui gets, see [`crate::input_middleware::EventLogger`].
+    pub log_input_events: bool,
+    /// Replays as fast as frames render instead of at the recorded pace.
+    pub replay_fast: bool,
+    /// Serves [`crate::remote_control`] clients.
+    #[cfg(feature = "remote-control")]
+    pub remote_control: Option<RemoteControlOptions>,
+    /// Exports [`crate::metrics`] when set.
+    pub metrics: Option<MetricsOptions>,
+    pub present_mode: wgpu::PresentMode,
+    pub frame_pacing: bool,
+    pub vsync_pacing: bool,
+    pub benchmark: Option<BenchmarkLength>,
+    pub benchmark_warm_up: Duration,
+    pub benchmark_report: Option<PathBuf>,
+    pub stress_ui: bool,
+    pub gpu_memory_warning: Option<u64>,
+    pub show_textures: bool,
+    pub show_log_console: bool,
+    /// Only on by default in debug builds.
+    pub debug_window: bool,
+    pub debug_hotkey: egui::KeyboardShortcut,
+    /// Shows logged errors in a corner until dismissed. Only on by default
+    /// in debug builds.
+    pub error_overlay: bool,
+    /// Opens the command palette; `None` turns it off.
+    pub command_palette: Option<egui::KeyboardShortcut>,
+    pub repaint_flash: bool,
+    pub log_level: log::LevelFilter,
+    /// Also write the log here, rotated once it gets large.
+    pub log_file: Option<PathBuf>,
+    /// This many close requests within `force_close_within` skip the app's
+    /// confirmation; 0 turns that off. Shift-closing always skips it.
+    pub force_close_requests: usize,
+    pub force_close_within: Duration,
+    /// Force-closes the app after this long, like a supervisor would.
+    pub exit_after: Option<Duration>,
+    /// Writes a report on fatal errors and panics.
+    pub crash_reports: bool,
+    /// `None` uses the platform's data directory.
+    pub crash_report_dir: Option<PathBuf>,
+    /// Keeps persisted state beside the executable, see [`crate::paths`].
+    /// `None` follows [`crate::paths::PORTABLE_FLAG`].
+    pub portable: Option<bool>,
+    pub max_live_textures: Option<usize>,
+    pub texture_growth_warning: Option<Duration>,
+    pub render_thread: bool,
+    /// Validation and debug info cost a lot of CPU time per draw call and
+    /// GPU-based validation slows shaders down considerably, so they are only
+    /// on by default in debug builds. `WGPU_VALIDATION`, `WGPU_DEBUG` and
+    /// `WGPU_GPU_BASED_VALIDATION` override the defaults.
+    pub instance_flags: wgpu::InstanceFlags,
+    /// `None` tries the platform's backends in order until one works.
+    pub backends: Option<wgpu::Backends>,
+    /// Also try the other backends when an explicit choice fails.
+    pub backend_fallback: bool,
+    /// Remembers the backend that worked so that later launches try it first.
+    pub backend_cache: Option<PathBuf>,
+    /// Only use a software adapter, e.g. to reproduce rasterizer bugs.
+    pub force_software: bool,
+    /// Paints on the CPU when no adapter works at all, see
+    /// [`crate::software::SoftwarePainter`].
+    #[cfg(feature = "software-renderer")]
+    pub cpu_fallback: bool,
+    /// Paints on the CPU without trying the GPU.
+    #[cfg(feature = "software-renderer")]
+    pub cpu_rendering: bool,
+    pub memory_hints: wgpu::MemoryHints,
+    pub limits_preset: LimitsPreset,
+    pub idle_after: Option<Duration>,
+    pub idle_repaint_interval: Duration,
+    pub idle_stop_animations: bool,
+    /// Where the per-monitor UI scales are kept, see
+    /// [`crate::monitor_scale::MonitorScales`]. `None` keeps them for this
+    /// run only.
+    pub monitor_scales: Option<PathBuf>,
+    /// Set through [`Options::set_kiosk`].
+    pub kiosk: Option<KioskOptions>,
+    pub egui: EguiOptions,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            window_size: Vec2::new(320., 240.),
+            gpu_profiling: false,
+            show_frame_overlay: false,
+            show_render_stats: false,
+            frame_limit: None,
+            timing_export: None,
+            measure_latency: false,
+            warm_up: true,
+            skip_unchanged_frames: true,
+            tessellation_cache: true,
+            render_scale: 1.,
+            design_resolution: None,
+            design_filter: wgpu::FilterMode::Linear,
+            mirror: false,
+            mirror_monitor: None,
+            magnifier: false,
+            frame_budget: None,
+            unified_titlebar: false,
+            custom_titlebar: false,
+            parent_window: None,
+            window_shadow: false,
+            exclude_from_capture: false,
+            title_pattern: document::DEFAULT_TITLE_PATTERN.to_owned(),
+            click_through: false,
+            backdrop: None,
+            coalesce_pointer_moves: false,
+            rich_paste: false,
+            record_input: None,
+            trace_events: false,
+            trace_redact: false,
+            right_click_as_middle: false,
+            log_input_events: false,
+            replay_input: None,
+            replay_fast: false,
+            #[cfg(feature = "remote-control")]
+            remote_control: None,
+            metrics: None,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            frame_pacing: false,
+            vsync_pacing: false,
+            benchmark: None,
+            benchmark_warm_up: Duration::from_secs(2),
+            benchmark_report: None,
+            stress_ui: false,
+            gpu_memory_warning: None,
+            show_textures: false,
+            show_log_console: false,
+            debug_window: cfg!(debug_assertions),
+            debug_hotkey: debug_window::DEFAULT_HOTKEY,
+            error_overlay: cfg!(debug_assertions),
+            command_palette: Some(command_palette::DEFAULT_HOTKEY),
+            repaint_flash: false,
+            log_level: log::LevelFilter::Info,
+            log_file: None,
+            force_close_requests: 3,
+            force_close_within: Duration::from_secs(2),
+            exit_after: None,
+            crash_reports: true,
+            crash_report_dir: None,
+            portable: None,
+            max_live_textures: None,
+            texture_growth_warning: None,
+            render_thread: false,
+            instance_flags: wgpu::InstanceFlags::from_build_config().with_env(),
+            backends: wgpu::util::backend_bits_from_env(),
+            backend_fallback: false,
+            backend_cache: None,
+            force_software: false,
+            #[cfg(feature = "software-renderer")]
+            cpu_fallback: true,
+            #[cfg(feature = "software-renderer")]
+            cpu_rendering: false,
+            memory_hints: wgpu::MemoryHints::Performance,
+            limits_preset: LimitsPreset::Default,
+            idle_after: None,
+            idle_repaint_interval: Duration::from_secs(1),
+            idle_stop_animations: false,
+            monitor_scales: None,
+            kiosk: None,
+            egui: EguiOptions::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Whether the window is transparent with a shadow drawn in a margin.
+    /// Click-through overlays shape the window's input themselves.
+    pub fn drawn_window_shadow(&self) -> bool {
+        self.window_shadow && self.custom_titlebar && !self.click_through && !window_shadow::NATIVE
+    }
+
+    /// Whether the window has an alpha channel the compositor blends.
+    pub fn transparent_window(&self) -> bool {
+        self.click_through
+            || self.drawn_window_shadow()
+            || matches!(self.backdrop, Some(Backdrop::Mica | Backdrop::Acrylic))
+    }
+
+    /// Turns on kiosk mode and everything that would get in its way off:
+    /// the custom titlebar, the debug window, the error overlay, the command
+    /// palette and force-closing by repeated close requests.
+    pub fn set_kiosk(&mut self, kiosk: KioskOptions) {
+        self.kiosk = Some(kiosk);
+        self.custom_titlebar = false;
+        self.debug_window = false;
+        self.error_overlay = false;
+        self.command_palette = None;
+        self.force_close_requests = 0;
+    }
+}
+
+/// The parts of [`egui::Options`] an app usually wants to tune. Defaults
+/// match what the context was hardcoded to before.
+#[derive(Clone, Debug)]
+pub struct EguiOptions {
+    /// 1 is cheapest; more passes settle complex auto-sizing layouts.
+    pub max_passes: NonZeroUsize,
+    pub embed_viewports: bool,
+    /// Debugging aid: repaints whenever a widget changes, see
+    /// [`egui::Options::repaint_on_widget_change`].
+    pub repaint_on_widget_change: bool,
+    pub max_click_dist: f32,
+    /// In seconds.
+    pub max_click_duration: f64,
+    /// In seconds.
+    pub max_double_click_delay: f64,
+    pub line_scroll_speed: f32,
+    /// E.g. feathering off for pixel art, or coarse culling off to debug
+    /// clipping.
+    pub tessellation: egui::epaint::TessellationOptions,
+}
+
+impl Default for EguiOptions {
+    fn default() -> Self {
+        let egui = egui::Options::default();
+        Self {
+            max_passes: NonZeroUsize::new(2).unwrap(),
+            embed_viewports: false,
+            repaint_on_widget_change: egui.repaint_on_widget_change,
+            max_click_dist: egui.input_options.max_click_dist,
+            max_click_duration: egui.input_options.max_click_duration,
+            max_double_click_delay: egui.input_options.max_double_click_delay,
+            line_scroll_speed: egui.line_scroll_speed,
+            tessellation: egui.tessellation_options,
+        }
+    }
+}
+
+impl EguiOptions {
+    /// Applies the options to a context, also while it is running.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_embed_viewports(self.embed_viewports);
+        ctx.options_mut(|o| {
+            o.max_passes = self.max_passes;
+            o.repaint_on_widget_change = self.repaint_on_widget_change;
+            o.input_options.max_click_dist = self.max_click_dist;
+            o.input_options.max_click_duration = self.max_click_duration;
+            o.input_options.max_double_click_delay = self.max_double_click_delay;
+            o.line_scroll_speed = self.line_scroll_speed;
+            o.tessellation_options = self.tessellation;
+        });
+    }
+}
@@ -0,0 +1,356 @@
+use egui::{Align2, Color32, Id, Order, Pos2, Rect, Sense, Stroke, Vec2};
+use web_time::Duration;
+
+use crate::{
+    frame_stats::{FrameStats, FRAME_HISTORY_LEN},
+    gpu_memory::GpuMemory,
+    latency::{InputClass, LatencyTracker, RepaintPath},
+    log_console,
+    render_stats::RenderStats,
+    texture_registry::{TextureRegistry, TextureSort},
+};
+
+const GRAPH_HEIGHT: f32 = 40.;
+const GRAPH_MAX_SECS: f32 = 1. / 30.;
+const TARGET_SECS: f32 = 1. / 60.;
+const MAX_ERRORS_SHOWN: usize = 3;
+
+pub fn frame_overlay(ctx: &egui::Context, stats: &FrameStats, latency: Option<&LatencyTracker>) {
+    egui::Area::new(Id::new("frame_overlay"))
+        .order(Order::Foreground)
+        .anchor(Align2::RIGHT_TOP, Vec2::new(-8., 8.))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.style_mut().override_text_style = Some(egui::TextStyle::Monospace);
+
+                let latest = stats.latest().unwrap_or_default();
+                ui.label(format!(
+                    "FPS {:6.1} (avg {:6.1})",
+                    stats.fps(),
+                    stats.smoothed_fps()
+                ));
+                ui.label(format!(
+                    "CPU {:6.2} ms",
+                    latest.cpu_time.as_secs_f32() * 1000.
+                ));
+                if let Some(gpu_time) = stats.gpu_time {
+                    ui.label(format!("GPU {:6.2} ms", gpu_time.as_secs_f32() * 1000.));
+                }
+                ui.label(format!("Elided {:6}", stats.frames_elided));
+                if stats.idle {
+                    ui.label("Idle");
+                }
+                if let Some(pacer_interval) = stats.pacer_interval {
+                    ui.label(format!(
+                        "Pace {:5.2}/{:5.2} ms, resyncs {}",
+                        stats.smoothed_interval().as_secs_f32() * 1000.,
+                        pacer_interval.as_secs_f32() * 1000.,
+                        stats.pacer_resyncs
+                    ));
+                }
+                if let (true, Some(error)) = (stats.vsync_snapping, stats.vsync_prediction_error) {
+                    ui.label(format!("Vsync error {error:+6.2} ms"));
+                }
+                if let Some(quality) = stats.quality {
+                    ui.label(format!(
+                        "Budget {:5.2} ms, p95 {}, level {}",
+                        quality.budget.as_secs_f32() * 1000.,
+                        quality.p95.map_or_else(
+                            || "  -  ".to_owned(),
+                            |p95| format!("{:5.2}", p95.as_secs_f32() * 1000.)
+                        ),
+                        quality.level
+                    ));
+                    if let Some(change) = quality.last_change {
+                        ui.label(format!(
+                            "{} {}",
+                            if change.degraded {
+                                "Reduced"
+                            } else {
+                                "Restored"
+                            },
+                            change.step.name()
+                        ));
+                    }
+                }
+                if stats.pointer_moves_coalesced > 0 {
+                    ui.label(format!("Coalesced {:6}", stats.pointer_moves_coalesced));
+                }
+
+                let (rect, _) = ui.allocate_exact_size(
+                    Vec2::new(FRAME_HISTORY_LEN as f32, GRAPH_HEIGHT),
+                    Sense::hover(),
+                );
+                frame_graph(ui.painter(), rect, stats);
+
+                if let Some(latency) = latency {
+                    latency_rows(ui, latency);
+                }
+            });
+        });
+}
+
+/// The latest errors from the log in a corner, until dismissed.
+#[derive(Default)]
+pub struct ErrorOverlay {
+    /// When the last dismissed error was logged.
+    dismissed: Option<Duration>,
+}
+
+impl ErrorOverlay {
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let errors = log_console::errors_after(self.dismissed, MAX_ERRORS_SHOWN);
+        let Some(last) = errors.last() else {
+            return;
+        };
+        let last_time = last.time;
+        egui::Area::new(Id::new("error_overlay"))
+            .order(Order::Foreground)
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-8., -8.))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(360.);
+                    for entry in &errors {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("{}: {}", entry.target, entry.message),
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Copy").clicked() {
+                            let text: String = errors
+                                .iter()
+                                .map(|entry| format!("{}: {}\n", entry.target, entry.message))
+                                .collect();
+                            ui.ctx().copy_text(text);
+                        }
+                        if ui.button("Dismiss").clicked() {
+                            self.dismissed = Some(last_time);
+                        }
+                    });
+                });
+            });
+    }
+}
+
+fn latency_rows(ui: &mut egui::Ui, latency: &LatencyTracker) {
+    for class in InputClass::ALL {
+        for path in RepaintPath::ALL {
+            let histogram = latency.histogram(class, path);
+            if histogram.count() == 0 {
+                continue;
+            }
+            ui.label(format!(
+                "{:>14}/{:<8} p50 {:5.1} p95 {:5.1} ms",
+                class.name(),
+                path.name(),
+                histogram.percentile(0.50).as_secs_f32() * 1000.,
+                histogram.percentile(0.95).as_secs_f32() * 1000.,
+            ));
+        }
+    }
+}
+
+fn frame_graph(painter: &egui::Painter, rect: Rect, stats: &FrameStats) {
+    painter.rect_filled(rect, 0., Color32::from_black_alpha(128));
+
+    let y_of = |secs: f32| rect.bottom() - (secs / GRAPH_MAX_SECS).min(1.) * rect.height();
+
+    let target_y = y_of(TARGET_SECS);
+    painter.hline(
+        rect.x_range(),
+        target_y,
+        Stroke::new(1., Color32::DARK_GREEN),
+    );
+
+    let offset = FRAME_HISTORY_LEN - stats.iter().len();
+    for (i, timing) in stats.iter().enumerate() {
+        let x = rect.left() + (offset + i) as f32 + 0.5;
+        let interval_y = y_of(timing.interval.as_secs_f32());
+        let cpu_y = y_of(timing.cpu_time.as_secs_f32());
+        painter.line_segment(
+            [Pos2::new(x, rect.bottom()), Pos2::new(x, interval_y)],
+            Stroke::new(1., Color32::GRAY),
+        );
+        painter.line_segment(
+            [Pos2::new(x, rect.bottom()), Pos2::new(x, cpu_y)],
+            Stroke::new(1., Color32::LIGHT_BLUE),
+        );
+    }
+}
+
+pub fn render_stats_window(
+    ctx: &egui::Context,
+    stats: &RenderStats,
+    gpu_memory: &GpuMemory,
+    open: &mut bool,
+) {
+    egui::Window::new("Renderer statistics")
+        .id(Id::new("render_stats_window"))
+        .open(open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("render_stats_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    let mut row = |label: &str, value: std::fmt::Arguments| {
+                        ui.label(label);
+                        ui.monospace(value.to_string());
+                        ui.end_row();
+                    };
+                    row(
+                        "Clipped primitives",
+                        format_args!("{}", stats.clipped_primitives),
+                    );
+                    row(
+                        "Meshes / callbacks",
+                        format_args!("{} / {}", stats.meshes, stats.callbacks),
+                    );
+                    row("Vertices", format_args!("{}", stats.vertices));
+                    row("Indices", format_args!("{}", stats.indices));
+                    row(
+                        "Vertex buffer",
+                        format_args!(
+                            "{} / {}",
+                            format_bytes(stats.vertex_bytes()),
+                            format_bytes(stats.vertex_buffer_capacity)
+                        ),
+                    );
+                    row(
+                        "Index buffer",
+                        format_args!(
+                            "{} / {}",
+                            format_bytes(stats.index_bytes()),
+                            format_bytes(stats.index_buffer_capacity)
+                        ),
+                    );
+                    row("Live textures", format_args!("{}", stats.live_textures()));
+                    row(
+                        "Texture memory",
+                        format_args!("~{}", format_bytes(stats.texture_bytes)),
+                    );
+                    row(
+                        "Textures set / freed",
+                        format_args!("{} / {}", stats.textures_set, stats.textures_freed),
+                    );
+                    row(
+                        "Surface configures",
+                        format_args!("{}", stats.surface_configures),
+                    );
+                    row(
+                        "Tessellation cache",
+                        format_args!(
+                            "{} hits / {} misses",
+                            stats.tessellation_cache_hits, stats.tessellation_cache_misses
+                        ),
+                    );
+                    if stats.render_scale != 1. {
+                        row(
+                            "Render scale",
+                            format_args!(
+                                "{}x, {}x{} target ({}), +1 pass",
+                                stats.render_scale,
+                                stats.supersample_size[0],
+                                stats.supersample_size[1],
+                                format_bytes(stats.supersample_bytes)
+                            ),
+                        );
+                    }
+                    row("Instance flags", format_args!("{:?}", stats.instance_flags));
+                    row(
+                        "Keeping awake",
+                        format_args!("{}", if stats.sleep_inhibited { "yes" } else { "no" }),
+                    );
+                    row(
+                        "Max texture / buffer",
+                        format_args!(
+                            "{} px / {}",
+                            stats.limits.max_texture_dimension_2d,
+                            format_bytes(stats.limits.max_buffer_size)
+                        ),
+                    );
+                    let backend = gpu_memory.backend;
+                    let optional_bytes =
+                        |bytes: Option<u64>| bytes.map_or_else(|| "n/a".to_owned(), format_bytes);
+                    row(
+                        "GPU allocated / reserved",
+                        format_args!(
+                            "{} / {}",
+                            optional_bytes(backend.allocated),
+                            optional_bytes(backend.reserved)
+                        ),
+                    );
+                    row(
+                        "GPU buffers / textures",
+                        format_args!(
+                            "{} / {}",
+                            optional_bytes(backend.buffers),
+                            optional_bytes(backend.textures)
+                        ),
+                    );
+                    row(
+                        "egui / native textures",
+                        format_args!(
+                            "{} / {}",
+                            format_bytes(gpu_memory.egui_texture_bytes),
+                            format_bytes(gpu_memory.native_texture_bytes)
+                        ),
+                    );
+                });
+        });
+}
+
+pub fn textures_window(
+    ctx: &egui::Context,
+    textures: &TextureRegistry,
+    sort: &mut TextureSort,
+    open: &mut bool,
+) {
+    egui::Window::new("Textures")
+        .id(Id::new("textures_window"))
+        .open(open)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} live, sort by", textures.len()));
+                ui.radio_value(sort, TextureSort::Size, "size");
+                ui.radio_value(sort, TextureSort::Age, "age");
+            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("textures_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for record in textures.sorted(*sort) {
+                            ui.monospace(format!("{:?}", record.id));
+                            ui.label(&record.label);
+                            ui.monospace(format!(
+                                "{}x{} {}",
+                                record.size[0],
+                                record.size[1],
+                                format_bytes(record.bytes)
+                            ));
+                            ui.monospace(format!(
+                                "{:.0} s",
+                                record.created.elapsed().as_secs_f32()
+                            ));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const KIB: f64 = 1024.;
+    const MIB: f64 = KIB * 1024.;
+    let bytes = bytes as f64;
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes / MIB)
+    } else if bytes >= KIB {
+        format!("{:.1} KiB", bytes / KIB)
+    } else {
+        format!("{bytes} B")
+    }
+}
@@ -0,0 +1,392 @@
+use egui::{
+    epaint::{textures::TexturesDelta, Primitive},
+    ClippedPrimitive, Event, PlatformOutput, Pos2, RawInput, TextureId,
+};
+
+/// Set on the overlay context's managed texture ids, so that they do not
+/// collide with the app context's in the shared wgpu renderer.
+const TEXTURE_ID_BIT: u64 = 1 << 63;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    App,
+    Overlay,
+}
+
+/// A second egui context for the wrapper's own windows, painted above the
+/// app's UI in the same pass. Its ids, memory and style are its own, so the
+/// app can neither collide with nor restyle nor cover them. The pointer
+/// goes to it while over one of its interactable areas or while a press
+/// that started there is held, and the keyboard while it has the focus;
+/// the app gets everything else. Nothing of it is persisted.
+pub struct OverlayContext {
+    ctx: egui::Context,
+    pointer_pos: Option<Pos2>,
+    /// Where the held press went, which keeps the pointer until release.
+    captured: Option<Target>,
+    /// Whether the app saw the pointer last, so that it is told when the
+    /// pointer moves over the overlay.
+    app_has_pointer: bool,
+}
+
+pub struct OverlayOutput {
+    pub platform_output: PlatformOutput,
+    /// In the app's points; paint after the app's.
+    pub clipped_primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+}
+
+impl OverlayContext {
+    /// Repaints requested by the overlay are forwarded to `app_ctx`.
+    pub fn new(app_ctx: &egui::Context) -> Self {
+        let ctx = egui::Context::default();
+        // The app's zoom applies, see `run`.
+        ctx.options_mut(|o| o.zoom_with_keyboard = false);
+        let app_ctx = app_ctx.clone();
+        ctx.set_request_repaint_callback(move |info| app_ctx.request_repaint_after(info.delay));
+        Self {
+            ctx,
+            pointer_pos: None,
+            captured: None,
+            app_has_pointer: false,
+        }
+    }
+
+    pub fn ctx(&self) -> &egui::Context {
+        &self.ctx
+    }
+
+    /// Whether the overlay has the pointer, from the last routed input.
+    pub fn has_pointer(&self) -> bool {
+        self.pointer_target() == Target::Overlay
+    }
+
+    fn pointer_target(&self) -> Target {
+        // egui keeps a background area covering the whole screen.
+        let over_area = self.pointer_pos.is_some_and(|pos| {
+            self.ctx
+                .layer_id_at(pos)
+                .is_some_and(|layer| layer.order != egui::Order::Background)
+        });
+        self.captured.unwrap_or(if over_area {
+            Target::Overlay
+        } else {
+            Target::App
+        })
+    }
+
+    /// Splits `raw_input`'s events between the contexts, by the overlay's
+    /// areas from its last pass. `raw_input` keeps the app's events; the
+    /// returned input, otherwise the same, has the overlay's.
+    pub fn route_input(&mut self, raw_input: &mut RawInput) -> RawInput {
+        let events = std::mem::take(&mut raw_input.events);
+        let mut overlay_input = RawInput {
+            hovered_files: Vec::new(),
+            dropped_files: Vec::new(),
+            ..raw_input.clone()
+        };
+        let keyboard_target = if self.ctx.wants_keyboard_input() {
+            Target::Overlay
+        } else {
+            Target::App
+        };
+
+        for event in events {
+            // Where the pointer is handed back to the app after a drag
+            // that started on the overlay.
+            let mut enter_app = None;
+            let target = match &event {
+                Event::PointerMoved(pos) => {
+                    self.pointer_pos = Some(*pos);
+                    // The overlay follows the pointer everywhere to know
+                    // when it is over one of its areas.
+                    if self.pointer_target() == Target::App {
+                        raw_input.events.push(event.clone());
+                        self.app_has_pointer = true;
+                    } else {
+                        self.leave_app(raw_input);
+                    }
+                    overlay_input.events.push(event);
+                    continue;
+                }
+                Event::PointerGone => {
+                    self.pointer_pos = None;
+                    self.app_has_pointer = false;
+                    raw_input.events.push(event.clone());
+                    overlay_input.events.push(event);
+                    continue;
+                }
+                Event::PointerButton { pos, pressed, .. } => {
+                    self.pointer_pos = Some(*pos);
+                    let target = self.pointer_target();
+                    if *pressed {
+                        self.captured = Some(target);
+                        if target == Target::App {
+                            // As a click elsewhere would in one context.
+                            self.surrender_focus();
+                        }
+                    } else {
+                        self.captured = None;
+                        if target == Target::Overlay && self.pointer_target() == Target::App {
+                            enter_app = Some(*pos);
+                        }
+                    }
+                    target
+                }
+                Event::Touch { pos, .. } => {
+                    self.pointer_pos = Some(*pos);
+                    self.pointer_target()
+                }
+                Event::MouseWheel { .. } | Event::Zoom(_) => self.pointer_target(),
+                Event::Key { .. }
+                | Event::Text(_)
+                | Event::Copy
+                | Event::Cut
+                | Event::Paste(_)
+                | Event::Ime(_) => keyboard_target,
+                Event::WindowFocused(_) => {
+                    overlay_input.events.push(event.clone());
+                    Target::App
+                }
+                _ => Target::App,
+            };
+            match target {
+                Target::App => raw_input.events.push(event),
+                Target::Overlay => {
+                    self.leave_app(raw_input);
+                    overlay_input.events.push(event);
+                }
+            }
+            if let Some(pos) = enter_app {
+                raw_input.events.push(Event::PointerMoved(pos));
+                self.app_has_pointer = true;
+            }
+        }
+        overlay_input
+    }
+
+    fn leave_app(&mut self, raw_input: &mut RawInput) {
+        if std::mem::take(&mut self.app_has_pointer) {
+            raw_input.events.push(Event::PointerGone);
+        }
+    }
+
+    fn surrender_focus(&self) {
+        self.ctx.memory_mut(|memory| {
+            if let Some(id) = memory.focused() {
+                memory.surrender_focus(id);
+            }
+        });
+    }
+
+    /// Runs a pass at the app's zoom and tessellates it for painting with
+    /// the app's primitives at `pixels_per_point`.
+    pub fn run(
+        &mut self,
+        input: RawInput,
+        zoom_factor: f32,
+        pixels_per_point: f32,
+        ui: impl FnMut(&egui::Context),
+    ) -> OverlayOutput {
+        profiling::scope!("overlay");
+
+        self.ctx.set_zoom_factor(zoom_factor);
+        let full_output = self.ctx.run(input, ui);
+        let clipped_primitives = if full_output.pixels_per_point == pixels_per_point {
+            let mut clipped_primitives = self
+                .ctx
+                .tessellate(full_output.shapes, full_output.pixels_per_point);
+            for clipped in &mut clipped_primitives {
+                if let Primitive::Mesh(mesh) = &mut clipped.primitive {
+                    mesh.texture_id = remap(mesh.texture_id);
+                }
+            }
+            clipped_primitives
+        } else {
+            // The zoom changed in this pass and applies from the next one.
+            self.ctx.request_repaint();
+            Vec::new()
+        };
+        let mut textures_delta = full_output.textures_delta;
+        for (id, _) in &mut textures_delta.set {
+            *id = remap(*id);
+        }
+        for id in &mut textures_delta.free {
+            *id = remap(*id);
+        }
+        OverlayOutput {
+            platform_output: full_output.platform_output,
+            clipped_primitives,
+            textures_delta,
+        }
+    }
+
+    /// Takes the cursor, IME and copied text from the overlay where it has
+    /// the pointer or the keyboard.
+    pub fn merge_platform_output(&self, app: &mut PlatformOutput, overlay: PlatformOutput) {
+        if self.has_pointer() {
+            app.cursor_icon = overlay.cursor_icon;
+        }
+        if self.ctx.wants_keyboard_input() {
+            app.ime = overlay.ime;
+            app.mutable_text_under_cursor = overlay.mutable_text_under_cursor;
+        }
+        if !overlay.copied_text.is_empty() {
+            app.copied_text = overlay.copied_text;
+        }
+        if overlay.open_url.is_some() {
+            app.open_url = overlay.open_url;
+        }
+    }
+}
+
+fn remap(id: TextureId) -> TextureId {
+    match id {
+        TextureId::Managed(id) => TextureId::Managed(id | TEXTURE_ID_BIT),
+        user => user,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2, Id, PointerButton};
+
+    use super::*;
+
+    /// An overlay window over (0, 0) to (100, 100) with a text field.
+    fn overlay_ui(ctx: &egui::Context) {
+        egui::Area::new(Id::new("overlay"))
+            .fixed_pos(Pos2::ZERO)
+            .show(ctx, |ui| {
+                ui.set_min_size(vec2(100.0, 100.0));
+                ui.add(
+                    egui::TextEdit::singleline(&mut String::new())
+                        .id(Id::new("field"))
+                        .desired_width(80.0),
+                );
+            });
+    }
+
+    fn overlay() -> OverlayContext {
+        let mut overlay = OverlayContext::new(&egui::Context::default());
+        route(&mut overlay, Vec::new());
+        overlay
+    }
+
+    /// Routes `events` and runs an overlay pass; returns the events each
+    /// context got.
+    fn route(overlay: &mut OverlayContext, events: Vec<Event>) -> (Vec<Event>, Vec<Event>) {
+        let mut raw_input = RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(Pos2::ZERO, vec2(400.0, 400.0))),
+            events,
+            ..Default::default()
+        };
+        let overlay_input = overlay.route_input(&mut raw_input);
+        let overlay_events = overlay_input.events.clone();
+        overlay.run(overlay_input, 1.0, 1.0, overlay_ui);
+        (raw_input.events, overlay_events)
+    }
+
+    fn button(x: f32, y: f32, pressed: bool) -> Event {
+        Event::PointerButton {
+            pos: pos2(x, y),
+            button: PointerButton::Primary,
+            pressed,
+            modifiers: Default::default(),
+        }
+    }
+
+    fn moved(x: f32, y: f32) -> Event {
+        Event::PointerMoved(pos2(x, y))
+    }
+
+    #[test]
+    fn moves_and_presses_go_to_the_context_under_the_pointer() {
+        let mut overlay = overlay();
+        let (app, over) = route(&mut overlay, vec![moved(200.0, 200.0)]);
+        assert_eq!(
+            (app, over),
+            (vec![moved(200.0, 200.0)], vec![moved(200.0, 200.0)])
+        );
+        assert!(!overlay.has_pointer());
+
+        // The app is told the pointer left when it moves over the overlay.
+        let (app, over) = route(&mut overlay, vec![moved(50.0, 50.0)]);
+        assert_eq!(
+            (app, over),
+            (vec![Event::PointerGone], vec![moved(50.0, 50.0)])
+        );
+        assert!(overlay.has_pointer());
+        let events = vec![button(50.0, 50.0, true), button(50.0, 50.0, false)];
+        let (app, over) = route(&mut overlay, events.clone());
+        assert_eq!((app, over), (Vec::new(), events));
+
+        let (app, over) = route(&mut overlay, vec![moved(200.0, 200.0)]);
+        assert_eq!(
+            (app, over),
+            (vec![moved(200.0, 200.0)], vec![moved(200.0, 200.0)])
+        );
+        let events = vec![button(200.0, 200.0, true), button(200.0, 200.0, false)];
+        let (app, over) = route(&mut overlay, events.clone());
+        assert_eq!((app, over), (events, Vec::new()));
+    }
+
+    #[test]
+    fn pointer_gone_reaches_both() {
+        let mut overlay = overlay();
+        route(&mut overlay, vec![moved(50.0, 50.0)]);
+        let (app, over) = route(&mut overlay, vec![Event::PointerGone]);
+        assert_eq!(
+            (app, over),
+            (vec![Event::PointerGone], vec![Event::PointerGone])
+        );
+        assert!(!overlay.has_pointer());
+    }
+
+    #[test]
+    fn drags_stay_where_they_started() {
+        let mut overlay = overlay();
+        route(&mut overlay, vec![moved(50.0, 50.0)]);
+        route(&mut overlay, vec![button(50.0, 50.0, true)]);
+        let (app, over) = route(&mut overlay, vec![moved(200.0, 200.0)]);
+        assert_eq!((app, over), (Vec::new(), vec![moved(200.0, 200.0)]));
+        // Released over the app, which gets the pointer back.
+        let (app, over) = route(&mut overlay, vec![button(200.0, 200.0, false)]);
+        assert_eq!(app, vec![moved(200.0, 200.0)]);
+        assert_eq!(over, vec![button(200.0, 200.0, false)]);
+        assert!(!overlay.has_pointer());
+
+        // And the other way around.
+        route(&mut overlay, vec![button(200.0, 200.0, true)]);
+        let (app, over) = route(&mut overlay, vec![moved(50.0, 50.0)]);
+        assert_eq!(
+            (app, over),
+            (vec![moved(50.0, 50.0)], vec![moved(50.0, 50.0)])
+        );
+        assert!(!overlay.has_pointer());
+        let (app, over) = route(&mut overlay, vec![button(50.0, 50.0, false)]);
+        assert_eq!((app, over), (vec![button(50.0, 50.0, false)], Vec::new()));
+        assert!(overlay.has_pointer());
+    }
+
+    #[test]
+    fn keyboard_follows_the_overlay_focus() {
+        let mut overlay = overlay();
+        let text = || vec![Event::Text("a".to_owned())];
+        assert_eq!(route(&mut overlay, text()), (text(), Vec::new()));
+
+        overlay
+            .ctx()
+            .memory_mut(|memory| memory.request_focus(Id::new("field")));
+        route(&mut overlay, Vec::new());
+        assert_eq!(route(&mut overlay, text()), (Vec::new(), text()));
+
+        // A press on the app takes the focus away from the overlay.
+        route(
+            &mut overlay,
+            vec![moved(200.0, 200.0), button(200.0, 200.0, true)],
+        );
+        route(&mut overlay, vec![button(200.0, 200.0, false)]);
+        assert_eq!(route(&mut overlay, text()), (text(), Vec::new()));
+    }
+}
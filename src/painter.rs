@@ -0,0 +1,512 @@
+use std::{any::Any, sync::Arc};
+
+use anyhow::Context as _;
+use egui::{epaint::textures::TexturesDelta, ClippedPrimitive, ViewportId};
+use egui_wgpu::ScreenDescriptor;
+use egui_winit::winit::{event::WindowEvent, window::Window};
+
+use crate::{
+    crash_report,
+    egui_renderer::{self, EguiRenderer},
+    error::AppError,
+    event::{EventResult, UserEvent},
+    gpu::{self, GpuContext},
+    host_window::HostWindow,
+    input_middleware,
+    options::Options,
+    surface::SurfaceFrame,
+    user_app::UserApp,
+};
+
+/// Paints egui's output with something else than the built-in wgpu renderer,
+/// e.g. an app's own Vulkan renderer. [`PainterFrontend`] calls, per frame:
+/// [`Self::update_textures`], [`Self::acquire_frame`], [`Self::paint`] and
+/// [`Self::present`].
+pub trait EguiPainter {
+    /// The largest texture egui may ask for, e.g. the device's 2D limit.
+    fn max_texture_side(&self) -> usize;
+
+    /// Applies `textures_delta.set` before this frame paints. The textures in
+    /// `free` may still be drawn this frame and go after [`Self::paint`].
+    fn update_textures(&mut self, textures_delta: TexturesDelta);
+
+    /// The target of the next frame, e.g. a swapchain image. `None` skips
+    /// the frame, e.g. while the swapchain is out of date.
+    fn acquire_frame(&mut self) -> anyhow::Result<Option<Box<dyn Any>>>;
+
+    /// Draws into `frame`, which [`Self::acquire_frame`] returned.
+    fn paint(
+        &mut self,
+        clipped_primitives: &[ClippedPrimitive],
+        screen_descriptor: ScreenDescriptor,
+        frame: &mut dyn Any,
+    ) -> anyhow::Result<()>;
+
+    fn present(&mut self, frame: Box<dyn Any>) -> anyhow::Result<()>;
+
+    /// Follows the window's inner size in physical pixels.
+    fn resize(&mut self, width: u32, height: u32);
+
+    /// For crash reports, e.g. the adapter's name.
+    fn describe(&self) -> String;
+
+    /// Whether frames are painted on the CPU, which the app tells the user
+    /// about.
+    fn is_software(&self) -> bool {
+        false
+    }
+}
+
+/// Creates the painter once the window exists.
+pub type PainterFactory = Box<dyn FnOnce(&Arc<Window>) -> anyhow::Result<Box<dyn EguiPainter>>>;
+
+/// Runs the app with an [`EguiPainter`]: egui, input and scheduling as
+/// usual. The built-in renderer's GPU extras, like frame pacing, kiosk mode,
+/// native textures or the render thread, are left out.
+pub struct PainterFrontend<T> {
+    state: T,
+    egui_renderer: EguiRenderer,
+    painter: Box<dyn EguiPainter>,
+}
+
+impl<T: UserApp> PainterFrontend<T> {
+    pub fn new(
+        egui_ctx: egui::Context,
+        window: &dyn HostWindow,
+        options: &Options,
+        mut state: T,
+        mut painter: Box<dyn EguiPainter>,
+    ) -> Self {
+        crash_report::set_section("adapter", painter.describe());
+        let size = window.inner_size();
+        painter.resize(size.width, size.height);
+        let mut egui_renderer =
+            EguiRenderer::for_painter(egui_ctx, window, painter.max_texture_side());
+        egui_renderer.set_debug_window(options.debug_window.then_some(options.debug_hotkey));
+        egui_renderer.set_error_overlay(options.error_overlay);
+        egui_renderer.command_palette.hotkey = options.command_palette;
+        egui_renderer.clipboard.rich_paste = options.rich_paste;
+        prepare_state(&mut egui_renderer, painter.as_ref(), &mut state, window);
+        Self {
+            state,
+            egui_renderer,
+            painter,
+        }
+    }
+
+    /// Swaps in the state built by [`crate::app::App::new_loading`].
+    pub fn replace_state(&mut self, mut state: T, window: &dyn HostWindow) {
+        prepare_state(
+            &mut self.egui_renderer,
+            self.painter.as_ref(),
+            &mut state,
+            window,
+        );
+        self.state = state;
+        input_middleware::set_input_blocked(self.egui_renderer.egui_winit.egui_ctx(), false);
+    }
+
+    pub fn run_ui_and_paint(&mut self, window: &dyn HostWindow) -> Result<EventResult, AppError> {
+        profiling::function_scope!();
+
+        // winit still delivers the rest of the iteration after an exit.
+        if self.egui_renderer.close {
+            return Ok(EventResult::Exit);
+        }
+        let frame = self.egui_renderer.run_ui(window, &mut self.state);
+        let size = window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return Ok(self
+                .egui_renderer
+                .finish_frame(window, frame.viewport_output));
+        }
+        self.egui_renderer
+            .record_frame(&frame.clipped_primitives, &frame.textures_delta);
+        self.painter.update_textures(frame.textures_delta);
+        let Some(mut target) = self.painter.acquire_frame()? else {
+            self.egui_renderer
+                .finish_frame(window, frame.viewport_output);
+            return Ok(EventResult::RepaintNext);
+        };
+        self.painter.paint(
+            &frame.clipped_primitives,
+            ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point: frame.pixels_per_point,
+            },
+            target.as_mut(),
+        )?;
+        let result = self
+            .egui_renderer
+            .finish_frame(window, frame.viewport_output);
+        window.pre_present_notify();
+        self.painter.present(target)?;
+        if self.egui_renderer.on_frame_presented(window) {
+            self.egui_renderer.egui_winit.egui_ctx().request_repaint();
+        }
+        Ok(result)
+    }
+
+    pub fn on_window_event(&mut self, event: &WindowEvent, window: &dyn HostWindow) -> EventResult {
+        let mut repaint_asap = false;
+        match event {
+            WindowEvent::Resized(size) => {
+                self.painter.resize(size.width, size.height);
+                repaint_asap = true;
+            }
+            WindowEvent::CloseRequested => {
+                if self.egui_renderer.close {
+                    return EventResult::Exit;
+                }
+                self.egui_renderer
+                    .info
+                    .events
+                    .push(egui::ViewportEvent::Close);
+                self.egui_renderer
+                    .egui_winit
+                    .egui_ctx()
+                    .request_repaint_of(ViewportId::ROOT);
+            }
+            _ => {}
+        }
+        let response = self.egui_renderer.on_window_event(window, event);
+        if self.egui_renderer.close {
+            EventResult::Exit
+        } else if response.repaint && repaint_asap {
+            EventResult::RepaintNow
+        } else if response.repaint {
+            EventResult::RepaintNext
+        } else {
+            EventResult::Wait
+        }
+    }
+
+    pub fn egui_ctx(&self) -> &egui::Context {
+        self.egui_renderer.egui_winit.egui_ctx()
+    }
+
+    pub fn on_user_event(&self, event: UserEvent) -> EventResult {
+        let UserEvent::RequestRepaint {
+            when,
+            cumulative_pass_nr,
+        } = event
+        else {
+            return EventResult::Wait;
+        };
+        let current_pass_nr = self
+            .egui_renderer
+            .egui_winit
+            .egui_ctx()
+            .cumulative_pass_nr_for(ViewportId::ROOT);
+        if current_pass_nr == cumulative_pass_nr || current_pass_nr == cumulative_pass_nr + 1 {
+            EventResult::RepaintAt(when)
+        } else {
+            EventResult::Wait
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.state.on_exit(self.egui_renderer.forced_close);
+    }
+}
+
+fn prepare_state(
+    egui_renderer: &mut EguiRenderer,
+    painter: &dyn EguiPainter,
+    state: &mut impl UserApp,
+    window: &dyn HostWindow,
+) {
+    if painter.is_software() {
+        state.on_software_rendering();
+    }
+    if let Some(window) = window.native() {
+        state.on_window_ready(window);
+    }
+    for command in state.commands() {
+        egui_renderer.command_palette.register(command);
+    }
+    egui_renderer.set_app_input_middleware(state.input_middleware());
+}
+
+/// Paints nothing. Input, UI and scheduling still run, which makes it a
+/// baseline for the cost of everything but painting.
+pub struct NoopPainter;
+
+impl EguiPainter for NoopPainter {
+    fn max_texture_side(&self) -> usize {
+        2048
+    }
+
+    fn update_textures(&mut self, _textures_delta: TexturesDelta) {}
+
+    fn acquire_frame(&mut self) -> anyhow::Result<Option<Box<dyn Any>>> {
+        Ok(Some(Box::new(())))
+    }
+
+    fn paint(
+        &mut self,
+        _clipped_primitives: &[ClippedPrimitive],
+        _screen_descriptor: ScreenDescriptor,
+        _frame: &mut dyn Any,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn present(&mut self, _frame: Box<dyn Any>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    fn describe(&self) -> String {
+        "none, painting nothing".to_owned()
+    }
+}
+
+/// egui_wgpu behind [`EguiPainter`], on the device and surface that
+/// [`gpu::init`] picks. The built-in renderer does the same with all extras;
+/// this is what the trait takes to paint with wgpu.
+pub struct WgpuPainter {
+    gpu: GpuContext,
+    renderer: egui_wgpu::Renderer,
+    textures_to_free: Vec<egui::TextureId>,
+}
+
+impl WgpuPainter {
+    /// Blocks until a device is found, like [`gpu::init`] does on its own
+    /// thread for the built-in renderer.
+    pub fn new(window: &Arc<Window>, options: &Options) -> Result<Self, AppError> {
+        let (candidates, errors) = gpu::candidates(window, options);
+        let gpu = pollster::block_on(gpu::init(candidates, errors, options))?;
+        let renderer =
+            egui_wgpu::Renderer::new(&gpu.device, gpu.surface_config.format, None, 1, true);
+        Ok(Self {
+            gpu,
+            renderer,
+            textures_to_free: Vec::new(),
+        })
+    }
+}
+
+impl EguiPainter for WgpuPainter {
+    fn max_texture_side(&self) -> usize {
+        self.gpu.device.limits().max_texture_dimension_2d as usize
+    }
+
+    fn update_textures(&mut self, textures_delta: TexturesDelta) {
+        egui_renderer::update_textures(
+            &mut self.renderer,
+            &self.gpu.device,
+            &self.gpu.queue,
+            &textures_delta,
+        );
+        self.textures_to_free.extend(textures_delta.free);
+    }
+
+    fn acquire_frame(&mut self) -> anyhow::Result<Option<Box<dyn Any>>> {
+        match self.gpu.surface.get_current_texture() {
+            Ok(surface_texture) => Ok(Some(Box::new(surface_texture))),
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.gpu
+                    .surface
+                    .configure(&self.gpu.device, &self.gpu.surface_config)?;
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::Timeout) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn paint(
+        &mut self,
+        clipped_primitives: &[ClippedPrimitive],
+        screen_descriptor: ScreenDescriptor,
+        frame: &mut dyn Any,
+    ) -> anyhow::Result<()> {
+        let surface_texture = frame
+            .downcast_mut::<SurfaceFrame>()
+            .context("not a frame of this painter")?;
+        let view = surface_texture
+            .texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("egui painter encoder"),
+            });
+        let textures_delta = TexturesDelta {
+            set: Vec::new(),
+            free: std::mem::take(&mut self.textures_to_free),
+        };
+        egui_renderer::paint_egui(
+            &mut self.renderer,
+            &self.gpu.device,
+            &self.gpu.queue,
+            &mut encoder,
+            &view,
+            wgpu::Color::TRANSPARENT,
+            &screen_descriptor,
+            clipped_primitives,
+            &textures_delta,
+            true,
+            None,
+        );
+        self.gpu.queue.submit([encoder.finish()]);
+        Ok(())
+    }
+
+    fn present(&mut self, frame: Box<dyn Any>) -> anyhow::Result<()> {
+        let surface_texture = frame
+            .downcast::<SurfaceFrame>()
+            .ok()
+            .context("not a frame of this painter")?;
+        surface_texture.present();
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let ([width, height], _) = gpu::clamp_surface_size(&self.gpu.device, [width, height]);
+        self.gpu.surface_config.width = width;
+        self.gpu.surface_config.height = height;
+        if let Err(err) = self
+            .gpu
+            .surface
+            .configure(&self.gpu.device, &self.gpu.surface_config)
+        {
+            log::error!("{err}");
+        }
+    }
+
+    fn describe(&self) -> String {
+        let info = &self.gpu.adapter_info;
+        format!(
+            "{} ({:?}), through the painter trait",
+            info.name, info.backend
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use egui_winit::winit::dpi::PhysicalSize;
+
+    use super::*;
+    use crate::{testing::HeadlessWindow, user_app::SimpleApp};
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Resize(u32, u32),
+        UpdateTextures,
+        Acquire,
+        Paint([u32; 2]),
+        Present,
+    }
+
+    /// Records what the frontend asks of it.
+    struct Recording {
+        calls: Rc<RefCell<Vec<Call>>>,
+        swapchain_ready: Rc<RefCell<bool>>,
+    }
+
+    impl EguiPainter for Recording {
+        fn max_texture_side(&self) -> usize {
+            2048
+        }
+
+        fn update_textures(&mut self, _textures_delta: TexturesDelta) {
+            self.calls.borrow_mut().push(Call::UpdateTextures);
+        }
+
+        fn acquire_frame(&mut self) -> anyhow::Result<Option<Box<dyn Any>>> {
+            self.calls.borrow_mut().push(Call::Acquire);
+            Ok(self
+                .swapchain_ready
+                .borrow()
+                .then(|| Box::new(()) as Box<dyn Any>))
+        }
+
+        fn paint(
+            &mut self,
+            clipped_primitives: &[ClippedPrimitive],
+            screen_descriptor: ScreenDescriptor,
+            _frame: &mut dyn Any,
+        ) -> anyhow::Result<()> {
+            assert!(!clipped_primitives.is_empty());
+            self.calls
+                .borrow_mut()
+                .push(Call::Paint(screen_descriptor.size_in_pixels));
+            Ok(())
+        }
+
+        fn present(&mut self, _frame: Box<dyn Any>) -> anyhow::Result<()> {
+            self.calls.borrow_mut().push(Call::Present);
+            Ok(())
+        }
+
+        fn resize(&mut self, width: u32, height: u32) {
+            self.calls.borrow_mut().push(Call::Resize(width, height));
+        }
+
+        fn describe(&self) -> String {
+            "recording".to_owned()
+        }
+    }
+
+    #[test]
+    fn frontend_drives_the_painter() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let swapchain_ready = Rc::new(RefCell::new(true));
+        let painter = Recording {
+            calls: calls.clone(),
+            swapchain_ready: swapchain_ready.clone(),
+        };
+        let options = Options::default();
+        let window = HeadlessWindow::new(PhysicalSize::new(400, 300));
+        let mut frontend = PainterFrontend::new(
+            EguiRenderer::create_egui_context(&options.egui),
+            &window,
+            &options,
+            SimpleApp(|ctx: &egui::Context| {
+                egui::CentralPanel::default().show(ctx, |ui| ui.label("Hello"));
+            }),
+            Box::new(painter),
+        );
+        assert_eq!(calls.take(), [Call::Resize(400, 300)]);
+
+        frontend.run_ui_and_paint(&window).unwrap();
+        assert_eq!(
+            calls.take(),
+            [
+                Call::UpdateTextures,
+                Call::Acquire,
+                Call::Paint([400, 300]),
+                Call::Present
+            ]
+        );
+
+        let size = PhysicalSize::new(320, 200);
+        window.set_size(size);
+        let result = frontend.on_window_event(&WindowEvent::Resized(size), &window);
+        assert!(matches!(result, EventResult::RepaintNow));
+        assert_eq!(calls.take(), [Call::Resize(320, 200)]);
+        frontend.run_ui_and_paint(&window).unwrap();
+        assert!(calls.take().contains(&Call::Paint([320, 200])));
+
+        // Without a target the frame is skipped and tried again.
+        *swapchain_ready.borrow_mut() = false;
+        let result = frontend.run_ui_and_paint(&window).unwrap();
+        assert!(matches!(result, EventResult::RepaintNext));
+        assert_eq!(calls.take(), [Call::UpdateTextures, Call::Acquire]);
+
+        // Minimized windows run the UI but paint nothing.
+        window.set_size(PhysicalSize::new(0, 0));
+        frontend.run_ui_and_paint(&window).unwrap();
+        assert_eq!(calls.take(), []);
+    }
+}
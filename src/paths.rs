@@ -0,0 +1,145 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// A file of this name next to the executable turns portable mode on.
+pub const PORTABLE_FLAG: &str = "portable.flag";
+
+static DATA_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Decides where persisted state goes. `portable` comes from
+/// `--portable`/`--no-portable` and wins over [`PORTABLE_FLAG`], which wins
+/// over the platform's directory. Call once at startup, after logging is set
+/// up and before anything asks for [`data_dir`].
+pub fn init(portable: Option<bool>) {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.to_owned()));
+    if DATA_DIR.set(resolve(portable, exe_dir.as_deref())).is_err() {
+        log::warn!("the data directory was used before it was decided");
+    }
+}
+
+/// The data directory for an executable in `exe_dir`, see [`init`].
+fn resolve(portable: Option<bool>, exe_dir: Option<&Path>) -> Option<PathBuf> {
+    let portable =
+        portable.unwrap_or_else(|| exe_dir.is_some_and(|dir| dir.join(PORTABLE_FLAG).is_file()));
+    if portable {
+        let dir = portable_dir(exe_dir);
+        log::info!("portable mode: keeping data in {}", dir.display());
+        Some(dir)
+    } else {
+        platform_data_dir()
+    }
+}
+
+/// Where everything the app persists goes: `data/` beside the executable in
+/// portable mode, otherwise the platform's per-user data directory, e.g.
+/// `~/.local/share/egui-wgpu-winit-example`. Create what is needed below it
+/// on demand.
+pub fn data_dir() -> Option<PathBuf> {
+    // Tests run side by side, and keep out of each other's and the user's
+    // state.
+    if cfg!(test) {
+        return None;
+    }
+    DATA_DIR.get_or_init(platform_data_dir).clone()
+}
+
+/// Falls back to a temporary directory where the executable's directory is
+/// read-only, e.g. on a network share; nothing outlives the machine's
+/// cleanup then.
+fn portable_dir(exe_dir: Option<&Path>) -> PathBuf {
+    let temp = || std::env::temp_dir().join(concat!(env!("CARGO_PKG_NAME"), "-portable"));
+    let Some(dir) = exe_dir.map(|dir| dir.join("data")) else {
+        log::warn!("cannot locate the executable, keeping portable data in the temp directory");
+        return temp();
+    };
+    if let Err(err) = check_writable(&dir) {
+        let temp = temp();
+        log::warn!(
+            "{} is not writable ({err}), keeping portable data in {} instead",
+            dir.display(),
+            temp.display()
+        );
+        return temp;
+    }
+    dir
+}
+
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".write-test");
+    std::fs::write(&probe, [])?;
+    std::fs::remove_file(probe)
+}
+
+fn platform_data_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_vendor = "apple") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+    };
+    Some(base?.join(env!("CARGO_PKG_NAME")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory standing in for the executable's.
+    fn exe_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-paths-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn temp_fallback() -> PathBuf {
+        std::env::temp_dir().join(concat!(env!("CARGO_PKG_NAME"), "-portable"))
+    }
+
+    #[test]
+    fn flag_file_turns_portable_mode_on() {
+        let dir = exe_dir("flag");
+        assert_eq!(resolve(None, Some(&dir)), platform_data_dir());
+        std::fs::write(dir.join(PORTABLE_FLAG), "").unwrap();
+        assert_eq!(resolve(None, Some(&dir)), Some(dir.join("data")));
+        assert!(dir.join("data").is_dir());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn command_line_wins_over_the_flag_file() {
+        let dir = exe_dir("command-line");
+        assert_eq!(resolve(Some(true), Some(&dir)), Some(dir.join("data")));
+        std::fs::write(dir.join(PORTABLE_FLAG), "").unwrap();
+        assert_eq!(resolve(Some(false), Some(&dir)), platform_data_dir());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unwritable_directories_fall_back_to_temp() {
+        // A file where the data directory would go cannot be created below.
+        let dir = exe_dir("unwritable");
+        std::fs::write(dir.join("data"), "").unwrap();
+        assert_eq!(resolve(Some(true), Some(&dir)), Some(temp_fallback()));
+        assert_eq!(resolve(Some(true), None), Some(temp_fallback()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn platform_directory_is_per_app() {
+        if let Some(dir) = platform_data_dir() {
+            assert!(dir.ends_with(env!("CARGO_PKG_NAME")));
+            assert!(dir.is_absolute());
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use egui_winit::winit::event::WindowEvent;
+
+fn full_pointer_path_id() -> egui::Id {
+    egui::Id::new("full_pointer_path_requested")
+}
+
+pub fn request_full_pointer_path(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(full_pointer_path_id(), true));
+}
+
+pub fn take_full_pointer_path_request(ctx: &egui::Context) -> bool {
+    ctx.data_mut(|d| d.remove_temp::<bool>(full_pointer_path_id()))
+        .unwrap_or(false)
+}
+
+#[derive(Default)]
+pub struct PointerCoalescer {
+    pending: Option<WindowEvent>,
+    pub coalesced: u64,
+}
+
+impl PointerCoalescer {
+    pub fn defer(&mut self, event: &WindowEvent) {
+        if self.pending.replace(event.clone()).is_some() {
+            self.coalesced += 1;
+        }
+    }
+
+    pub fn take(&mut self) -> Option<WindowEvent> {
+        self.pending.take()
+    }
+}
@@ -0,0 +1,580 @@
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    hash::{BuildHasher as _, Hasher as _},
+    io::{BufRead as _, BufReader, Read as _, Write as _},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use egui::{Event, Key, Modifiers, PointerButton, Pos2};
+use egui_winit::winit::window::Window;
+
+use crate::{
+    event::{EventResult, EventSender, UserEvent},
+    frame_capture::{self, FrameCapture, Pixels},
+    gpu_poll::GpuPoller,
+};
+
+// A line-based protocol over TCP. The first line is `auth <token>`; every
+// request after it is answered with a line starting with `ok` or `error`:
+//
+//   tap <x> <y>                   press and release the primary button
+//   move <x> <y>
+//   press <x> <y> [button]        primary, secondary or middle
+//   release <x> <y> [button]
+//   scroll <dx> <dy>              in points
+//   key <name>                    press and release, e.g. `key Enter`
+//   text <text>                   the rest of the line
+//   state                         `ok size=<w>x<h> pixels_per_point=<p>
+//                                 fps=<fps> focused=<kind> <label>`
+//   screenshot                    `ok png <length>`, then the PNG's bytes
+//
+// Coordinates are in egui points of the window: screenshot pixels divided
+// by `pixels_per_point`.
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7870";
+
+/// Read from the environment when `--remote-control-token` is not given.
+pub const TOKEN_VARIABLE: &str = "REMOTE_CONTROL_TOKEN";
+
+const MAX_LINE: u64 = 4096;
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct RemoteControlOptions {
+    /// Only loopback addresses keep other machines out; anything else is
+    /// logged as a warning.
+    pub address: SocketAddr,
+    /// Clients have to send it first. See [`resolve_token`] when unset.
+    pub token: Option<String>,
+}
+
+impl Default for RemoteControlOptions {
+    fn default() -> Self {
+        Self {
+            address: DEFAULT_ADDRESS.parse().unwrap(),
+            token: None,
+        }
+    }
+}
+
+// The token must not end up in crash reports, which include the options.
+impl std::fmt::Debug for RemoteControlOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteControlOptions")
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+type Screenshot = Result<Pixels, String>;
+
+/// What a client asked the UI thread for.
+#[derive(Debug)]
+pub enum RemoteRequest {
+    /// Events for consecutive frames.
+    Input(Vec<Vec<Event>>),
+    Screenshot(Sender<Screenshot>),
+    State(Sender<String>),
+}
+
+/// Accepts clients on a thread, each on a thread of its own, and hands their
+/// requests to the event loop.
+pub fn spawn_server(options: &RemoteControlOptions, sender: EventSender) -> anyhow::Result<()> {
+    let token = options.token.clone().unwrap_or_else(resolve_token);
+    anyhow::ensure!(!token.is_empty(), "the remote control token is empty");
+    let listener = TcpListener::bind(options.address)
+        .with_context(|| format!("cannot listen on {}", options.address))?;
+    if !options.address.ip().is_loopback() {
+        log::warn!(
+            "remote control listens on {}, which other machines can reach",
+            options.address
+        );
+    }
+    log::info!("remote control listening on {}", listener.local_addr()?);
+    std::thread::Builder::new()
+        .name("remote-control".to_owned())
+        .spawn(move || {
+            profiling::register_thread!("remote-control");
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        log::warn!("remote control: {err}");
+                        continue;
+                    }
+                };
+                let token = token.clone();
+                let sender = sender.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("remote-client".to_owned())
+                    .spawn(move || {
+                        let peer = stream.peer_addr().ok();
+                        let send = |request| sender.send(UserEvent::Remote(request));
+                        if let Err(err) = serve(stream, &token, &send) {
+                            log::warn!("remote control client {peer:?}: {err:#}");
+                        }
+                    });
+                if let Err(err) = spawned {
+                    log::warn!("cannot start a remote control client thread: {err}");
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Taken from [`TOKEN_VARIABLE`], or generated and printed to stderr. Never
+/// logged: the log ends up in bug and crash reports.
+pub fn resolve_token() -> String {
+    std::env::var(TOKEN_VARIABLE).unwrap_or_else(|_| {
+        let token = generate_token();
+        eprintln!("remote control token: {token}");
+        token
+    })
+}
+
+/// 128 bits from the OS's randomness that seeds std's hash maps.
+fn generate_token() -> String {
+    (0..2)
+        .map(|_| format!("{:016x}", RandomState::new().build_hasher().finish()))
+        .collect()
+}
+
+/// Compares in time that does not depend on where the tokens differ.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> anyhow::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.take(MAX_LINE).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    anyhow::ensure!(line.ends_with('\n'), "request line too long");
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned()))
+}
+
+/// Answers one client, handing its requests to `send`.
+fn serve(stream: TcpStream, token: &str, send: &dyn Fn(RemoteRequest)) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    reader.get_ref().set_read_timeout(Some(AUTH_TIMEOUT))?;
+    let authorized = read_line(&mut reader)?
+        .and_then(|line| {
+            line.strip_prefix("auth ")
+                .map(|given| token_matches(given, token))
+        })
+        .unwrap_or(false);
+    if !authorized {
+        writeln!(writer, "error unauthorized")?;
+        anyhow::bail!("unauthorized");
+    }
+    reader.get_ref().set_read_timeout(None)?;
+    writeln!(writer, "ok")?;
+
+    while let Some(line) = read_line(&mut reader)? {
+        match handle(&line, send) {
+            Ok(Reply::Line(reply)) => writeln!(writer, "ok{reply}")?,
+            Ok(Reply::Png(png)) => {
+                writeln!(writer, "ok png {}", png.len())?;
+                writer.write_all(&png)?;
+            }
+            Err(err) => writeln!(writer, "error {}", single_line(&format!("{err:#}")))?,
+        }
+    }
+    Ok(())
+}
+
+enum Reply {
+    /// Appended to `ok`.
+    Line(String),
+    Png(Vec<u8>),
+}
+
+fn handle(line: &str, send: &dyn Fn(RemoteRequest)) -> anyhow::Result<Reply> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let args: Vec<&str> = rest.split_whitespace().collect();
+    let pos = || -> anyhow::Result<Pos2> {
+        match args[..] {
+            [x, y, ..] => Ok(Pos2::new(x.parse()?, y.parse()?)),
+            _ => anyhow::bail!("expected <x> <y>"),
+        }
+    };
+    let button = || -> anyhow::Result<PointerButton> {
+        Ok(match args.get(2).copied() {
+            None | Some("primary") => PointerButton::Primary,
+            Some("secondary") => PointerButton::Secondary,
+            Some("middle") => PointerButton::Middle,
+            Some(other) => anyhow::bail!("unknown button: {other}"),
+        })
+    };
+    let pointer_button = |pos, button, pressed| Event::PointerButton {
+        pos,
+        button,
+        pressed,
+        modifiers: Modifiers::NONE,
+    };
+    let key = |key, pressed| Event::Key {
+        key,
+        physical_key: None,
+        pressed,
+        repeat: false,
+        modifiers: Modifiers::NONE,
+    };
+    // Each press and release in a frame of its own, as from a real device.
+    let frames = match command {
+        "tap" => {
+            let pos = pos()?;
+            vec![
+                vec![Event::PointerMoved(pos)],
+                vec![pointer_button(pos, PointerButton::Primary, true)],
+                vec![pointer_button(pos, PointerButton::Primary, false)],
+            ]
+        }
+        "move" => vec![vec![Event::PointerMoved(pos()?)]],
+        "press" => {
+            let pos = pos()?;
+            vec![
+                vec![Event::PointerMoved(pos)],
+                vec![pointer_button(pos, button()?, true)],
+            ]
+        }
+        "release" => {
+            let pos = pos()?;
+            vec![
+                vec![Event::PointerMoved(pos)],
+                vec![pointer_button(pos, button()?, false)],
+            ]
+        }
+        "scroll" => {
+            let delta = pos()?.to_vec2();
+            vec![vec![Event::MouseWheel {
+                unit: egui::MouseWheelUnit::Point,
+                delta,
+                modifiers: Modifiers::NONE,
+            }]]
+        }
+        "key" => {
+            let name = rest.trim();
+            let parsed = Key::from_name(name).with_context(|| format!("unknown key: {name}"))?;
+            vec![vec![key(parsed, true)], vec![key(parsed, false)]]
+        }
+        "text" => vec![vec![Event::Text(rest.to_owned())]],
+        "state" => {
+            let (reply, state) = mpsc::channel();
+            send(RemoteRequest::State(reply));
+            let state = state.recv().context("the app is not rendering")?;
+            return Ok(Reply::Line(format!(" {state}")));
+        }
+        "screenshot" => {
+            let (reply, screenshot) = mpsc::channel();
+            send(RemoteRequest::Screenshot(reply));
+            let (size, pixels) = screenshot
+                .recv()
+                .context("the app is not rendering")?
+                .map_err(anyhow::Error::msg)?;
+            return Ok(Reply::Png(frame_capture::encode_png(size, &pixels)));
+        }
+        _ => anyhow::bail!("unknown request: {command}"),
+    };
+    send(RemoteRequest::Input(frames));
+    Ok(Reply::Line(String::new()))
+}
+
+fn single_line(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// The UI thread's side: feeds client input into the frames and answers
+/// state and screenshot requests.
+pub struct RemoteControl {
+    /// One entry per frame.
+    input: VecDeque<Vec<Event>>,
+    screenshots: Vec<Sender<Screenshot>>,
+    capture: Option<(FrameCapture, Vec<Sender<Screenshot>>)>,
+    can_capture: bool,
+    /// From egui's output, as it has no other record of labels.
+    focused: Option<egui::WidgetInfo>,
+}
+
+impl RemoteControl {
+    /// Screenshots fail unless `can_capture`, like bug reports'.
+    pub fn new(can_capture: bool) -> Self {
+        Self {
+            input: VecDeque::new(),
+            screenshots: Vec::new(),
+            capture: None,
+            can_capture,
+            focused: None,
+        }
+    }
+
+    pub fn request(
+        &mut self,
+        request: RemoteRequest,
+        ctx: &egui::Context,
+        window: &Window,
+        fps: f32,
+    ) -> EventResult {
+        match request {
+            RemoteRequest::Input(frames) => {
+                self.input.extend(frames);
+                EventResult::RepaintNow
+            }
+            RemoteRequest::Screenshot(reply) => {
+                if self.can_capture {
+                    self.screenshots.push(reply);
+                    EventResult::RepaintNow
+                } else {
+                    let _ = reply.send(Err("this surface cannot be captured".to_owned()));
+                    EventResult::Wait
+                }
+            }
+            RemoteRequest::State(reply) => {
+                let size = window.inner_size();
+                let focused = match &self.focused {
+                    Some(info) if ctx.memory(|mem| mem.focused().is_some()) => format!(
+                        "{:?} {}",
+                        info.typ,
+                        single_line(info.label.as_deref().unwrap_or_default())
+                    ),
+                    _ => "none".to_owned(),
+                };
+                let _ = reply.send(format!(
+                    "size={}x{} pixels_per_point={} fps={fps:.1} focused={focused}",
+                    size.width,
+                    size.height,
+                    egui_winit::pixels_per_point(ctx, window),
+                ));
+                EventResult::Wait
+            }
+        }
+    }
+
+    /// Adds the next frame's client input to what egui-winit collected.
+    pub fn apply_input(&mut self, ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        if let Some(events) = self.input.pop_front() {
+            raw_input.events.extend(events);
+        }
+        if !self.input.is_empty() {
+            ctx.request_repaint();
+        }
+    }
+
+    /// Remembers the widget that gained focus in the frame.
+    pub fn observe_output(&mut self, platform_output: &egui::PlatformOutput) {
+        for event in &platform_output.events {
+            if let egui::output::OutputEvent::FocusGained(info) = event {
+                self.focused = Some(info.clone());
+            }
+        }
+    }
+
+    /// Whether the next painted frame is to be captured.
+    pub fn wants_capture(&self) -> bool {
+        !self.screenshots.is_empty() && self.capture.is_none()
+    }
+
+    /// Copies the frame painted to `texture` for the waiting clients. Call
+    /// before submitting.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        if !self.wants_capture() {
+            return;
+        }
+        let replies = std::mem::take(&mut self.screenshots);
+        match FrameCapture::new(device, encoder, texture, "remote control capture") {
+            Ok(capture) => self.capture = Some((capture, replies)),
+            Err(format) => {
+                self.can_capture = false;
+                for reply in replies {
+                    let _ = reply.send(Err(format!("cannot capture a {format:?} surface")));
+                }
+            }
+        }
+    }
+
+    /// Maps the copy. Call after submitting.
+    pub fn after_submit(&mut self, gpu_poller: &GpuPoller) {
+        if let Some((capture, _)) = &mut self.capture {
+            capture.after_submit(gpu_poller);
+        }
+    }
+
+    /// Hands a finished capture to its clients.
+    pub fn poll(&mut self) {
+        let Some(result) = self
+            .capture
+            .as_ref()
+            .and_then(|(capture, _)| capture.try_read())
+        else {
+            return;
+        };
+        let Some((_, replies)) = self.capture.take() else {
+            return;
+        };
+        let result = result.map_err(|err| err.to_string());
+        for reply in replies {
+            let _ = reply.send(result.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::BufRead as _};
+
+    use super::*;
+
+    /// The frames of input `line` asks for.
+    fn input(line: &str) -> anyhow::Result<Vec<Vec<Event>>> {
+        let sent = RefCell::new(Vec::new());
+        handle(line, &|request| sent.borrow_mut().push(request))?;
+        match &mut sent.into_inner()[..] {
+            [RemoteRequest::Input(frames)] => Ok(std::mem::take(frames)),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn tokens_match_only_in_full() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret ", "secret"));
+        assert!(!token_matches("", "secret"));
+        assert_eq!(generate_token().len(), 32);
+        assert_ne!(generate_token(), generate_token());
+    }
+
+    #[test]
+    fn requests_become_frames_of_input() {
+        let pos = Pos2::new(10., 20.5);
+        let button = |button, pressed| Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers: Modifiers::NONE,
+        };
+        assert_eq!(
+            input("tap 10 20.5").unwrap(),
+            [
+                vec![Event::PointerMoved(pos)],
+                vec![button(PointerButton::Primary, true)],
+                vec![button(PointerButton::Primary, false)],
+            ]
+        );
+        assert_eq!(
+            input("press 10 20.5 secondary").unwrap()[1],
+            [button(PointerButton::Secondary, true)]
+        );
+        assert_eq!(
+            input("release 10 20.5 middle").unwrap()[1],
+            [button(PointerButton::Middle, false)]
+        );
+        assert_eq!(input("move 10 20.5").unwrap(), [[Event::PointerMoved(pos)]]);
+        let frames = input("key Enter").unwrap();
+        assert!(matches!(
+            frames[..],
+            [ref press, ref release]
+                if matches!(press[..], [Event::Key { key: Key::Enter, pressed: true, .. }])
+                    && matches!(release[..], [Event::Key { key: Key::Enter, pressed: false, .. }])
+        ));
+        // Text keeps its spaces.
+        assert_eq!(
+            input("text a  b").unwrap(),
+            [[Event::Text("a  b".to_owned())]]
+        );
+
+        for line in [
+            "tap 10",
+            "tap x 20",
+            "press 1 2 fourth",
+            "key NotAKey",
+            "",
+            "jump",
+        ] {
+            let sent = RefCell::new(0);
+            assert!(
+                handle(line, &|_| *sent.borrow_mut() += 1).is_err(),
+                "{line:?}"
+            );
+            assert_eq!(*sent.borrow(), 0, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn state_and_screenshots_wait_for_the_app() {
+        let reply = |request| match request {
+            RemoteRequest::State(reply) => reply.send("size=1x1".to_owned()).unwrap(),
+            RemoteRequest::Screenshot(reply) => {
+                reply.send(Ok(([1, 1], vec![255, 0, 0, 255]))).unwrap();
+            }
+            RemoteRequest::Input(_) => panic!("not input"),
+        };
+        assert!(matches!(handle("state", &reply), Ok(Reply::Line(line)) if line == " size=1x1"));
+        let Ok(Reply::Png(png)) = handle("screenshot", &reply) else {
+            panic!("no PNG");
+        };
+        assert!(png.starts_with(b"\x89PNG"));
+
+        // Dropped without an answer, as when the app is gone.
+        assert!(handle("state", &drop).is_err());
+        let failed = |request| {
+            if let RemoteRequest::Screenshot(reply) = request {
+                reply.send(Err("cannot\ncapture".to_owned())).unwrap();
+            }
+        };
+        let Err(err) = handle("screenshot", &failed) else {
+            panic!("a screenshot");
+        };
+        assert_eq!(single_line(&format!("{err:#}")), "cannot capture");
+    }
+
+    #[test]
+    fn clients_authenticate_first() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (sent_sender, sent) = mpsc::channel();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let sent_sender = sent_sender.clone();
+                let _ = serve(stream, "secret", &move |request| {
+                    sent_sender.send(format!("{request:?}")).unwrap();
+                });
+            }
+            drop(sent_sender);
+            sent.iter().collect::<Vec<_>>()
+        });
+        let talk = |lines: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(lines.as_bytes()).unwrap();
+            stream.shutdown(std::net::Shutdown::Write).unwrap();
+            BufReader::new(stream)
+                .lines()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(talk("auth wrong\ntap 1 2\n"), ["error unauthorized"]);
+        assert_eq!(
+            talk("auth secret\ntap 1 2\njump\n"),
+            ["ok", "ok", "error unknown request: jump"]
+        );
+        let sent = server.join().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].starts_with("Input("));
+    }
+}
@@ -0,0 +1,132 @@
+use egui::{
+    ahash::HashMap,
+    epaint::{textures::TexturesDelta, Primitive, Vertex},
+    ClippedPrimitive, TextureId,
+};
+
+const BYTES_PER_TEXEL: u64 = 4;
+const VERTEX_SIZE: u64 = std::mem::size_of::<Vertex>() as u64;
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+// Mirrors the start capacities and growth policy of `egui_wgpu::Renderer`,
+// whose buffers are not observable from outside.
+const VERTEX_BUFFER_START_CAPACITY: u64 = VERTEX_SIZE * 1024;
+const INDEX_BUFFER_START_CAPACITY: u64 = INDEX_SIZE * 1024 * 3;
+
+pub struct RenderStats {
+    pub clipped_primitives: usize,
+    pub meshes: usize,
+    pub callbacks: usize,
+    pub vertices: usize,
+    pub indices: usize,
+    pub textures_set: usize,
+    pub textures_freed: usize,
+    pub texture_bytes: u64,
+    pub vertex_buffer_capacity: u64,
+    pub index_buffer_capacity: u64,
+    pub surface_configures: u64,
+    pub tessellation_cache_hits: u64,
+    pub tessellation_cache_misses: u64,
+    pub render_scale: f32,
+    pub supersample_size: [u32; 2],
+    pub supersample_bytes: u64,
+    pub instance_flags: wgpu::InstanceFlags,
+    pub limits: wgpu::Limits,
+    pub sleep_inhibited: bool,
+    texture_sizes: HashMap<TextureId, u64>,
+}
+
+impl Default for RenderStats {
+    fn default() -> Self {
+        Self {
+            clipped_primitives: 0,
+            meshes: 0,
+            callbacks: 0,
+            vertices: 0,
+            indices: 0,
+            textures_set: 0,
+            textures_freed: 0,
+            texture_bytes: 0,
+            vertex_buffer_capacity: VERTEX_BUFFER_START_CAPACITY,
+            index_buffer_capacity: INDEX_BUFFER_START_CAPACITY,
+            surface_configures: 0,
+            tessellation_cache_hits: 0,
+            tessellation_cache_misses: 0,
+            render_scale: 1.,
+            supersample_size: [0, 0],
+            supersample_bytes: 0,
+            instance_flags: wgpu::InstanceFlags::empty(),
+            limits: Default::default(),
+            sleep_inhibited: false,
+            texture_sizes: Default::default(),
+        }
+    }
+}
+
+impl RenderStats {
+    pub fn record_textures_set(&mut self, textures_delta: &TexturesDelta) {
+        self.textures_set = textures_delta.set.len();
+        for (id, image_delta) in &textures_delta.set {
+            if image_delta.pos.is_some() {
+                continue;
+            }
+            let [w, h] = image_delta.image.size();
+            let bytes = w as u64 * h as u64 * BYTES_PER_TEXEL;
+            if let Some(old) = self.texture_sizes.insert(*id, bytes) {
+                self.texture_bytes -= old;
+            }
+            self.texture_bytes += bytes;
+        }
+    }
+
+    pub fn record_textures_freed(&mut self, textures_delta: &TexturesDelta) {
+        self.textures_freed = textures_delta.free.len();
+        for id in &textures_delta.free {
+            if let Some(bytes) = self.texture_sizes.remove(id) {
+                self.texture_bytes -= bytes;
+            }
+        }
+    }
+
+    pub fn record_primitives(&mut self, clipped_primitives: &[ClippedPrimitive]) {
+        self.clipped_primitives = clipped_primitives.len();
+        self.meshes = 0;
+        self.callbacks = 0;
+        self.vertices = 0;
+        self.indices = 0;
+        for clipped_primitive in clipped_primitives {
+            match &clipped_primitive.primitive {
+                Primitive::Mesh(mesh) => {
+                    self.meshes += 1;
+                    self.vertices += mesh.vertices.len();
+                    self.indices += mesh.indices.len();
+                }
+                Primitive::Callback(_) => self.callbacks += 1,
+            }
+        }
+
+        self.vertex_buffer_capacity =
+            grown_capacity(self.vertex_buffer_capacity, self.vertex_bytes());
+        self.index_buffer_capacity = grown_capacity(self.index_buffer_capacity, self.index_bytes());
+    }
+
+    pub fn live_textures(&self) -> usize {
+        self.texture_sizes.len()
+    }
+
+    pub fn vertex_bytes(&self) -> u64 {
+        self.vertices as u64 * VERTEX_SIZE
+    }
+
+    pub fn index_bytes(&self) -> u64 {
+        self.indices as u64 * INDEX_SIZE
+    }
+}
+
+fn grown_capacity(capacity: u64, required: u64) -> u64 {
+    if capacity < required {
+        (capacity * 2).max(required)
+    } else {
+        capacity
+    }
+}
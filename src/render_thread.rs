@@ -0,0 +1,267 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TryIter},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+use egui::{epaint::textures::TexturesDelta, ClippedPrimitive};
+use egui_wgpu::ScreenDescriptor;
+use egui_winit::winit::window::Window;
+use web_time::{Duration, Instant};
+
+use crate::{
+    egui_renderer::{self, paint_egui},
+    error::AppError,
+    frame_stats::FrameTimeline,
+    gpu_timer::GpuTimer,
+    supersample::Supersampler,
+    surface::RenderSurface,
+};
+
+pub struct RenderJob {
+    pub timeline: FrameTimeline,
+    pub clipped_primitives: Vec<ClippedPrimitive>,
+    pub textures_delta: TexturesDelta,
+    pub tessellation_generation: Option<u64>,
+    pub screen_descriptor: ScreenDescriptor,
+    pub clear_color: wgpu::Color,
+    /// Set when the surface has to be reconfigured before this frame.
+    pub surface_config: Option<wgpu::SurfaceConfiguration>,
+}
+
+enum Message {
+    Render(RenderJob),
+    /// Replaces the surface, e.g. `None` while the app is suspended.
+    Surface(Option<(RenderSurface, wgpu::SurfaceConfiguration)>),
+}
+
+pub struct RenderOutput {
+    pub timeline: FrameTimeline,
+    pub gpu_time: Option<Duration>,
+    pub result: Result<(), AppError>,
+}
+
+struct RenderThreadState {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    surface: Option<RenderSurface>,
+    renderer: egui_wgpu::Renderer,
+    gpu_timer: Option<GpuTimer>,
+    supersampler: Option<Supersampler>,
+    window: Arc<Window>,
+    uploaded_generation: Option<u64>,
+}
+
+/// Owns the surface and the egui wgpu renderer, so that submit and present
+/// never block the event loop. At most one frame is in flight.
+pub struct RenderThread {
+    jobs: Option<SyncSender<Message>>,
+    outputs: Receiver<RenderOutput>,
+    handle: Option<JoinHandle<egui_wgpu::Renderer>>,
+}
+
+impl RenderThread {
+    pub fn spawn(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        surface: RenderSurface,
+        renderer: egui_wgpu::Renderer,
+        gpu_timer: Option<GpuTimer>,
+        supersampler: Option<Supersampler>,
+        window: Arc<Window>,
+    ) -> Result<Self, AppError> {
+        // A rendezvous channel: sending the next job waits for the render
+        // thread to finish the previous one.
+        let (jobs, job_receiver) = mpsc::sync_channel::<Message>(0);
+        let (output_sender, outputs) = mpsc::channel();
+        let mut state = RenderThreadState {
+            device,
+            queue,
+            surface: Some(surface),
+            renderer,
+            gpu_timer,
+            supersampler,
+            window,
+            uploaded_generation: None,
+        };
+        let handle = std::thread::Builder::new()
+            .name("render".to_owned())
+            .spawn(move || {
+                profiling::register_thread!("render");
+                for message in job_receiver {
+                    match message {
+                        Message::Render(job) => {
+                            let output = state.render(job);
+                            if output_sender.send(output).is_err() {
+                                break;
+                            }
+                        }
+                        Message::Surface(surface) => state.set_surface(surface),
+                    }
+                }
+                state.renderer
+            })?;
+        Ok(Self {
+            jobs: Some(jobs),
+            outputs,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn submit(&self, job: RenderJob) -> Result<(), AppError> {
+        profiling::scope!("wait for render thread");
+        self.send(Message::Render(job))
+    }
+
+    pub fn set_surface(
+        &self,
+        surface: Option<(RenderSurface, wgpu::SurfaceConfiguration)>,
+    ) -> Result<(), AppError> {
+        self.send(Message::Surface(surface))
+    }
+
+    fn send(&self, message: Message) -> Result<(), AppError> {
+        self.jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send(message).ok())
+            .ok_or(AppError::RenderThreadExited)
+    }
+
+    pub fn outputs(&self) -> TryIter<'_, RenderOutput> {
+        self.outputs.try_iter()
+    }
+
+    /// Stops the thread after its last frame and returns the renderer along
+    /// with any outputs that were not collected yet.
+    pub fn join(mut self) -> Result<(egui_wgpu::Renderer, Vec<RenderOutput>), AppError> {
+        self.jobs = None;
+        let renderer = self
+            .handle
+            .take()
+            .expect("render thread joined twice")
+            .join()
+            .map_err(|_| AppError::RenderThreadPanicked)?;
+        Ok((renderer, self.outputs.try_iter().collect()))
+    }
+}
+
+impl RenderThreadState {
+    fn set_surface(&mut self, surface: Option<(RenderSurface, wgpu::SurfaceConfiguration)>) {
+        self.surface = surface.map(|(mut surface, surface_config)| {
+            if let Err(err) = self.configure(&mut surface, &surface_config) {
+                // Reported with the next frame, which configures again.
+                log::warn!("{err}");
+            }
+            surface
+        });
+    }
+
+    fn configure(
+        &mut self,
+        surface: &mut RenderSurface,
+        surface_config: &wgpu::SurfaceConfiguration,
+    ) -> Result<(), AppError> {
+        if let Some(supersampler) = &mut self.supersampler {
+            supersampler.resize(&self.device, [surface_config.width, surface_config.height]);
+        }
+        surface.configure(&self.device, surface_config)
+    }
+
+    fn render(&mut self, mut job: RenderJob) -> RenderOutput {
+        profiling::scope!("render thread frame");
+
+        let gpu_time = self.gpu_timer.as_mut().and_then(GpuTimer::poll);
+
+        let mut surface = self.surface.take();
+        let configured = match (&mut surface, &job.surface_config) {
+            (Some(surface), Some(surface_config)) => self.configure(surface, surface_config),
+            _ => Ok(()),
+        };
+        self.surface = surface;
+
+        // Frames are not submitted while suspended, but one may still be in
+        // the channel when the surface goes away.
+        let acquired = configured.and_then(|()| {
+            self.surface
+                .as_ref()
+                .ok_or(wgpu::SurfaceError::Lost)
+                .and_then(RenderSurface::get_current_texture)
+                .map_err(AppError::from)
+        });
+        let surface_texture = match acquired {
+            Ok(surface_texture) => surface_texture,
+            Err(err) => {
+                egui_renderer::update_textures(
+                    &mut self.renderer,
+                    &self.device,
+                    &self.queue,
+                    &job.textures_delta,
+                );
+                egui_renderer::free_textures(&mut self.renderer, &job.textures_delta);
+                return RenderOutput {
+                    timeline: job.timeline,
+                    gpu_time,
+                    result: Err(err),
+                };
+            }
+        };
+        job.timeline.acquire = Instant::now();
+        let surface_view = surface_texture
+            .texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("egui frame encoder"),
+            });
+        paint_egui(
+            &mut self.renderer,
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            self.supersampler
+                .as_ref()
+                .map_or(&surface_view, Supersampler::target),
+            job.clear_color,
+            &job.screen_descriptor,
+            &job.clipped_primitives,
+            &job.textures_delta,
+            egui_renderer::needs_upload(&mut self.uploaded_generation, job.tessellation_generation),
+            self.gpu_timer.as_mut().and_then(GpuTimer::timestamp_writes),
+        );
+        if let Some(supersampler) = &self.supersampler {
+            supersampler.downsample(&mut encoder, &surface_view);
+        }
+        job.timeline.encode = Instant::now();
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.resolve(&mut encoder);
+        }
+        {
+            profiling::scope!("submit");
+            self.queue.submit(Some(encoder.finish()));
+        }
+        job.timeline.submit = Instant::now();
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.after_submit();
+        }
+        // Runs ready callbacks; see `GpuPoller`.
+        self.device.poll(wgpu::Maintain::Poll);
+
+        self.window.pre_present_notify();
+        {
+            profiling::scope!("present");
+            surface_texture.present();
+        }
+        job.timeline.present = Instant::now();
+
+        RenderOutput {
+            timeline: job.timeline,
+            gpu_time,
+            result: Ok(()),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt::Write as _;
+
+use egui::{Color32, Id, LayerId, Order};
+
+const COLORS: [Color32; 4] = [Color32::RED, Color32::GREEN, Color32::BLUE, Color32::YELLOW];
+
+/// Tints the window border with a different color every painted frame and
+/// counts the paints of the last second, to find code that repaints
+/// constantly.
+///
+/// Only draws during frames that happen anyway and never requests a repaint
+/// itself, so what it shows is the app's own repaint behavior.
+pub struct RepaintFlash {
+    pub enabled: bool,
+    /// Ring buffer of recent paint times, so counting allocates nothing.
+    /// Counts above its length show as the length.
+    times: [f64; 256],
+    next: usize,
+    frame: usize,
+    label: String,
+}
+
+impl Default for RepaintFlash {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            times: [f64::NEG_INFINITY; 256],
+            next: 0,
+            frame: 0,
+            label: String::with_capacity(16),
+        }
+    }
+}
+
+impl RepaintFlash {
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.enabled {
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        self.times[self.next] = now;
+        self.next = (self.next + 1) % self.times.len();
+        self.frame = self.frame.wrapping_add(1);
+        let paints = self.times.iter().filter(|&&time| now - time < 1.).count();
+
+        let color = COLORS[self.frame % COLORS.len()];
+        let painter = ctx.layer_painter(LayerId::new(Order::Debug, Id::new("repaint_flash")));
+        let rect = ctx.screen_rect();
+        painter.rect_stroke(rect.shrink(1.), 0., (2., color));
+        self.label.clear();
+        let _ = write!(self.label, "{paints}/s");
+        painter.text(
+            rect.right_bottom() - egui::vec2(6., 6.),
+            egui::Align2::RIGHT_BOTTOM,
+            &self.label,
+            egui::FontId::monospace(12.),
+            color,
+        );
+    }
+}
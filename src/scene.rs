@@ -0,0 +1,228 @@
+use wgpu::{CommandEncoder, Device};
+
+use crate::user_app::GpuTextures;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+const SHADER: &str = r"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var positions = array<vec2<f32>, 3>(
+        vec2<f32>(0.0, 0.8),
+        vec2<f32>(-0.8, -0.8),
+        vec2<f32>(0.8, -0.8),
+    );
+    var colors = array<vec3<f32>, 3>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+    );
+    var out: VertexOutput;
+    out.position = vec4<f32>(positions[index], 0.0, 1.0);
+    out.color = colors[index];
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
+";
+
+fn size_id() -> egui::Id {
+    egui::Id::new("scene_size")
+}
+
+/// Asks for the scene to be rendered at `size` pixels from the next frame
+/// on, e.g. to match the widget showing it.
+pub fn request_size(ctx: &egui::Context, size: [u32; 2]) {
+    ctx.data_mut(|d| d.insert_temp(size_id(), Some(size)));
+}
+
+/// A triangle rendered into an offscreen texture before egui paints, and
+/// shown by egui through a native texture. The texture is recreated when
+/// the UI asks for another size; its [`egui::TextureId`] stays the same.
+pub struct TriangleScene {
+    pipeline: wgpu::RenderPipeline,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    pub texture_id: egui::TextureId,
+}
+
+impl TriangleScene {
+    pub fn new(gpu: &mut GpuTextures) -> Self {
+        let device = gpu.device;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("scene shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("scene pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(FORMAT.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let texture = create_target(device, [256, 256]);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture_id = gpu.register(&texture, wgpu::FilterMode::Linear);
+        Self {
+            pipeline,
+            texture,
+            view,
+            texture_id,
+        }
+    }
+
+    /// Recreates the texture at the size the UI asked for. Call after each
+    /// pass.
+    pub fn update(&mut self, ctx: &egui::Context, gpu: &mut GpuTextures) {
+        let Some([width, height]) = ctx
+            .data_mut(|d| d.remove_temp::<Option<[u32; 2]>>(size_id()))
+            .flatten()
+        else {
+            return;
+        };
+        let max = gpu.device.limits().max_texture_dimension_2d;
+        let size = [width.clamp(1, max), height.clamp(1, max)];
+        if size == [self.texture.width(), self.texture.height()] {
+            return;
+        }
+        self.texture = create_target(gpu.device, size);
+        self.view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        gpu.update(self.texture_id, &self.texture, wgpu::FilterMode::Linear);
+    }
+
+    /// Encodes the scene's pass. Call before egui paints.
+    pub fn render(&self, encoder: &mut CommandEncoder) {
+        profiling::scope!("scene");
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("scene pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.05,
+                        g: 0.05,
+                        b: 0.08,
+                        a: 1.,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_target(device: &Device, [width, height]: [u32; 2]) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("scene target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::TestApp, user_app::UserApp};
+
+    /// Shows the scene at `side` points in the top left corner.
+    struct SceneApp {
+        scene: Option<TriangleScene>,
+        side: u32,
+    }
+
+    impl UserApp for SceneApp {
+        fn update(&mut self, ctx: &egui::Context) {
+            egui::CentralPanel::default()
+                .frame(egui::Frame::none())
+                .show(ctx, |ui| {
+                    if let Some(scene) = &self.scene {
+                        request_size(ctx, [self.side; 2]);
+                        ui.image((scene.texture_id, egui::vec2(1., 1.) * self.side as f32));
+                    }
+                });
+        }
+
+        fn on_gpu_textures(&mut self, gpu: &mut GpuTextures) {
+            self.scene = Some(TriangleScene::new(gpu));
+        }
+
+        fn prepare_frame(
+            &mut self,
+            ctx: &egui::Context,
+            gpu: &mut GpuTextures,
+            encoder: &mut CommandEncoder,
+        ) {
+            let scene = self.scene.as_mut().unwrap();
+            scene.update(ctx, gpu);
+            scene.render(encoder);
+        }
+    }
+
+    #[test]
+    fn scene_is_rendered_at_the_requested_size() {
+        let Some(mut test_app) = TestApp::new(SceneApp {
+            scene: None,
+            side: 200,
+        }) else {
+            return;
+        };
+        let texture_id = test_app.app().scene.as_ref().unwrap().texture_id;
+        let ([width, _], pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        let scene = test_app.app().scene.as_ref().unwrap();
+        assert_eq!([scene.texture.width(), scene.texture.height()], [200, 200]);
+        assert_eq!(scene.texture_id, texture_id);
+        let pixel = |x: u32, y: u32| {
+            let i = 4 * (y * width + x) as usize;
+            pixels[i..i + 3].to_vec()
+        };
+        // The triangle covers the middle, the clear color the corners.
+        let (corner, middle) = (pixel(5, 5), pixel(100, 120));
+        assert!(corner.iter().all(|&channel| channel < 100));
+        assert_ne!(middle, corner);
+
+        test_app.app_mut().side = 64;
+        test_app.step(Vec::new());
+        let scene = test_app.app().scene.as_ref().unwrap();
+        assert_eq!([scene.texture.width(), scene.texture.height()], [64, 64]);
+        assert_eq!(scene.texture_id, texture_id);
+    }
+}
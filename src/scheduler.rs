@@ -0,0 +1,721 @@
+use egui_winit::winit::event_loop::ControlFlow;
+use web_time::Instant;
+
+use crate::event::EventResult;
+
+/// What to do with an event's result right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    /// Paint within the current event and feed the result back.
+    PaintNow,
+    Exit,
+}
+
+/// What the event loop should do until the next event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Poll {
+    pub control_flow: ControlFlow,
+    pub redraw: bool,
+}
+
+/// Merges event results into the next repaint deadline. Time, pacing and the
+/// winit calls are left to the caller, so the same inputs always give the
+/// same decisions.
+pub struct Scheduler {
+    deadline: Option<Instant>,
+    /// On Windows a requested redraw waits behind the whole message queue,
+    /// so immediate repaints happen within the event instead.
+    paint_inline: bool,
+}
+
+impl Scheduler {
+    pub fn new(paint_inline: bool) -> Self {
+        Self {
+            deadline: None,
+            paint_inline,
+        }
+    }
+
+    /// Requests a repaint at `when` unless an earlier one is pending.
+    pub fn request(&mut self, when: Instant) {
+        self.deadline = Some(self.deadline.map_or(when, |deadline| deadline.min(when)));
+    }
+
+    pub fn clear(&mut self) {
+        self.deadline = None;
+    }
+
+    /// Folds in an event's result. `pace` maps a repaint time to when it
+    /// should happen and is told whether to snap it to vsync. `painted` is
+    /// set for the result of an inline paint, which is never followed by
+    /// another one.
+    pub fn on_result(
+        &mut self,
+        result: EventResult,
+        now: Instant,
+        painted: bool,
+        mut pace: impl FnMut(Instant, bool) -> Instant,
+    ) -> Action {
+        match result {
+            EventResult::Wait => {}
+            EventResult::RepaintNow if self.paint_inline && !painted => return Action::PaintNow,
+            EventResult::RepaintNow => self.request(now),
+            EventResult::RepaintNext => self.request(pace(now, false)),
+            EventResult::RepaintAt(when) => self.request(pace(when, true)),
+            EventResult::Exit => return Action::Exit,
+        }
+        Action::None
+    }
+
+    /// Decides how long to wait, and whether the pending repaint is due.
+    pub fn poll(&mut self, now: Instant) -> Poll {
+        match self.deadline {
+            Some(deadline) if now >= deadline => {
+                self.deadline = None;
+                Poll {
+                    control_flow: ControlFlow::Wait,
+                    redraw: true,
+                }
+            }
+            Some(deadline) => Poll {
+                control_flow: ControlFlow::WaitUntil(deadline),
+                redraw: false,
+            },
+            None => Poll {
+                control_flow: ControlFlow::Wait,
+                redraw: false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui_winit::winit::{
+        event::{ElementState, MouseButton, WindowEvent},
+        keyboard::ModifiersState,
+    };
+    use web_time::Duration;
+
+    use super::*;
+    use crate::{
+        close::{self, CloseDecision, ForceClose},
+        options::Options,
+        testing::TestApp,
+        user_app::UserApp,
+    };
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Pacing that delays by 1 ms, and snapping by 2 more.
+    fn pace(when: Instant, snap: bool) -> Instant {
+        when + ms(if snap { 3 } else { 1 })
+    }
+
+    enum Input {
+        Wait,
+        Now,
+        Next,
+        At(u64),
+        Exit,
+    }
+
+    impl Input {
+        fn at(&self, base: Instant) -> EventResult {
+            match *self {
+                Self::Wait => EventResult::Wait,
+                Self::Now => EventResult::RepaintNow,
+                Self::Next => EventResult::RepaintNext,
+                Self::At(offset) => EventResult::RepaintAt(base + ms(offset)),
+                Self::Exit => EventResult::Exit,
+            }
+        }
+    }
+
+    #[test]
+    fn on_result() {
+        use Input::*;
+        // Paint inline, already painted, pending deadline, result at 10 ms,
+        // expected action and deadline, all in ms.
+        #[rustfmt::skip]
+        let cases = [
+            (false, false, None,      Wait,    Action::None,     None),
+            (false, false, Some(50),  Wait,    Action::None,     Some(50)),
+            (false, false, None,      Now,     Action::None,     Some(10)),
+            (false, false, Some(5),   Now,     Action::None,     Some(5)),
+            (false, false, None,      Next,    Action::None,     Some(11)),
+            (false, false, Some(50),  Next,    Action::None,     Some(11)),
+            (false, false, None,      At(40),  Action::None,     Some(43)),
+            (false, false, Some(20),  At(40),  Action::None,     Some(20)),
+            (false, false, Some(50),  At(40),  Action::None,     Some(43)),
+            // Deadlines that already passed stay due.
+            (false, false, None,      At(0),   Action::None,     Some(3)),
+            (false, false, Some(50),  Exit,    Action::Exit,     Some(50)),
+            (true,  false, Some(50),  Now,     Action::PaintNow, Some(50)),
+            (true,  true,  Some(50),  Now,     Action::None,     Some(10)),
+            (true,  false, None,      Next,    Action::None,     Some(11)),
+            (true,  false, None,      At(40),  Action::None,     Some(43)),
+            (true,  true,  None,      Exit,    Action::Exit,     None),
+        ];
+        let base = Instant::now();
+        for (i, (paint_inline, painted, pending, result, action, deadline)) in
+            cases.into_iter().enumerate()
+        {
+            let mut scheduler = Scheduler::new(paint_inline);
+            if let Some(pending) = pending {
+                scheduler.request(base + ms(pending));
+            }
+            let now = base + ms(10);
+            assert_eq!(
+                scheduler.on_result(result.at(base), now, painted, pace),
+                action,
+                "case {i}"
+            );
+            assert_eq!(
+                scheduler.deadline,
+                deadline.map(|d| base + ms(d)),
+                "case {i}"
+            );
+        }
+    }
+
+    #[test]
+    fn results_in_any_order_keep_the_earliest() {
+        let base = Instant::now();
+        let results = [Input::At(40), Input::Next, Input::Wait, Input::At(20)];
+        for first in 0..results.len() {
+            let mut scheduler = Scheduler::new(false);
+            for result in results.iter().cycle().skip(first).take(results.len()) {
+                scheduler.on_result(result.at(base), base, false, pace);
+            }
+            assert_eq!(scheduler.deadline, Some(base + ms(1)));
+        }
+    }
+
+    #[test]
+    fn poll() {
+        let base = Instant::now();
+        let mut scheduler = Scheduler::new(false);
+        let waiting = |deadline| Poll {
+            control_flow: ControlFlow::WaitUntil(deadline),
+            redraw: false,
+        };
+        let idle = Poll {
+            control_flow: ControlFlow::Wait,
+            redraw: false,
+        };
+        assert_eq!(scheduler.poll(base), idle);
+
+        // A delayed repaint waits, and is due once its time came.
+        scheduler.request(base + ms(16));
+        assert_eq!(scheduler.poll(base), waiting(base + ms(16)));
+        assert_eq!(scheduler.poll(base + ms(15)), waiting(base + ms(16)));
+        let due = scheduler.poll(base + ms(16));
+        assert!(due.redraw);
+        assert_eq!(due.control_flow, ControlFlow::Wait);
+        // Each deadline redraws once.
+        assert_eq!(scheduler.poll(base + ms(17)), idle);
+
+        // An ASAP repaint is due on the next poll.
+        scheduler.request(base + ms(100));
+        scheduler.on_result(EventResult::RepaintNow, base + ms(20), false, pace);
+        assert!(scheduler.poll(base + ms(20)).redraw);
+        // It replaced the later one instead of keeping both.
+        assert_eq!(scheduler.poll(base + ms(20)), idle);
+
+        scheduler.request(base + ms(30));
+        scheduler.clear();
+        assert_eq!(scheduler.poll(base + ms(40)), idle);
+    }
+
+    #[test]
+    fn merge_prefers_the_more_urgent() {
+        let base = Instant::now();
+        let urgency = [
+            Input::Wait,
+            Input::At(20),
+            Input::At(10),
+            Input::Next,
+            Input::Now,
+            Input::Exit,
+        ];
+        for (i, a) in urgency.iter().enumerate() {
+            for (j, b) in urgency.iter().enumerate() {
+                let expected = format!("{:?}", urgency[i.max(j)].at(base));
+                assert_eq!(format!("{:?}", a.at(base).merge(b.at(base))), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn force_close_after_repeated_requests() {
+        let base = Instant::now();
+        let mut force_close = ForceClose::new(3, ms(2000));
+        assert!(!force_close.on_request(base, false));
+        assert!(!force_close.on_request(base + ms(500), false));
+        assert!(force_close.on_request(base + ms(1000), false));
+
+        // Requests further apart never add up.
+        let mut force_close = ForceClose::new(3, ms(2000));
+        for second in 0..10 {
+            assert!(!force_close.on_request(base + ms(second * 1500), false));
+        }
+        // Shift forces the first one.
+        assert!(ForceClose::new(3, ms(2000)).on_request(base, true));
+        // A count of zero only forces with Shift.
+        let mut force_close = ForceClose::new(0, ms(2000));
+        assert!((0..5).all(|_| !force_close.on_request(base, false)));
+    }
+
+    /// Cancels every close, like an app stuck in a modal.
+    #[derive(Default)]
+    struct Stuck {
+        asked: u32,
+        exited: Option<bool>,
+    }
+
+    impl UserApp for Stuck {
+        fn update(&mut self, _ctx: &egui::Context) {}
+
+        fn on_close_requested(&mut self, _default: CloseDecision) -> CloseDecision {
+            self.asked += 1;
+            CloseDecision::Cancel
+        }
+
+        fn on_exit(&mut self, forced: bool) {
+            self.exited = Some(forced);
+        }
+    }
+
+    #[test]
+    fn stuck_app_is_force_closed() {
+        let Some(mut test_app) = TestApp::new(Stuck::default()) else {
+            return;
+        };
+        for _ in 0..2 {
+            test_app.request_close();
+            test_app.step(Vec::new());
+            assert!(!test_app.exit);
+        }
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(test_app.exit);
+        // The app was not asked the third time, and knows it was forced.
+        assert_eq!(test_app.app().asked, 2);
+        assert_eq!(test_app.app().exited, Some(true));
+    }
+
+    #[test]
+    fn force_exit_skips_the_app() {
+        let Some(mut test_app) = TestApp::new(Stuck::default()) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        close::force_exit(test_app.ctx());
+        test_app.step(Vec::new());
+        assert!(test_app.exit);
+        assert_eq!(test_app.app().asked, 0);
+        assert_eq!(test_app.app().exited, Some(true));
+    }
+
+    #[test]
+    fn shift_close_is_forced() {
+        let Some(mut test_app) = TestApp::new(Stuck::default()) else {
+            return;
+        };
+        test_app.set_modifiers(ModifiersState::SHIFT);
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(test_app.exit);
+        assert_eq!(test_app.app().asked, 0);
+    }
+
+    /// xorshift64*, reproducible from the seed in a failure message.
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Self {
+            Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % n
+        }
+    }
+
+    /// Checks `cases` random sequences of up to `len` ops. A failing one is
+    /// shrunk by dropping ops while it still fails, so that every op in the
+    /// reported sequence matters.
+    fn check_sequences<T: Clone + std::fmt::Debug>(
+        cases: u64,
+        len: u64,
+        generate: impl Fn(&mut Rng) -> T,
+        check: impl Fn(&[T]) -> std::result::Result<(), String>,
+    ) {
+        let check = |ops: &[T]| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| check(ops)))
+                .unwrap_or_else(|_| Err("panicked".to_owned()))
+        };
+        for seed in 0..cases {
+            let mut rng = Rng::new(seed);
+            let mut ops: Vec<T> = (0..=rng.below(len)).map(|_| generate(&mut rng)).collect();
+            let Err(mut error) = check(&ops) else {
+                continue;
+            };
+            let mut i = 0;
+            while i < ops.len() {
+                let mut shorter = ops.clone();
+                shorter.remove(i);
+                match check(&shorter) {
+                    Ok(()) => i += 1,
+                    Err(shorter_error) => (ops, error) = (shorter, shorter_error),
+                }
+            }
+            panic!("seed {seed}: {error}\n{ops:#?}");
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        /// Wait, RepaintNow, RepaintNext, RepaintAt or Exit, with the
+        /// RepaintAt time relative to now.
+        Result {
+            kind: u64,
+            at_ms: i64,
+            painted: bool,
+        },
+        Advance(u64),
+        Poll,
+        Clear,
+    }
+
+    fn check_scheduler(paint_inline: bool, ops: &[Op]) -> std::result::Result<(), String> {
+        let base = Instant::now() + Duration::from_secs(1);
+        let mut now = base;
+        let mut scheduler = Scheduler::new(paint_inline);
+        // The earliest repaint asked for since the last due one.
+        let mut expected: Option<Instant> = None;
+        let request = |expected: &mut Option<Instant>, when: Instant| {
+            *expected = Some(expected.map_or(when, |expected| expected.min(when)));
+        };
+        for (i, op) in ops.iter().enumerate() {
+            match *op {
+                Op::Result {
+                    kind,
+                    at_ms,
+                    painted,
+                } => {
+                    let at = if at_ms < 0 {
+                        now - ms(at_ms.unsigned_abs())
+                    } else {
+                        now + ms(at_ms as u64)
+                    };
+                    let (result, action) = match kind {
+                        0 => (EventResult::Wait, Action::None),
+                        1 if paint_inline && !painted => {
+                            (EventResult::RepaintNow, Action::PaintNow)
+                        }
+                        1 => {
+                            request(&mut expected, now);
+                            (EventResult::RepaintNow, Action::None)
+                        }
+                        2 => {
+                            request(&mut expected, pace(now, false));
+                            (EventResult::RepaintNext, Action::None)
+                        }
+                        3 => {
+                            request(&mut expected, pace(at, true));
+                            (EventResult::RepaintAt(at), Action::None)
+                        }
+                        _ => (EventResult::Exit, Action::Exit),
+                    };
+                    let got = scheduler.on_result(result, now, painted, pace);
+                    if got != action {
+                        return Err(format!("op {i}: {got:?} instead of {action:?}"));
+                    }
+                }
+                Op::Advance(by) => now += ms(by),
+                Op::Poll => {
+                    let poll = scheduler.poll(now);
+                    let due = expected.is_some_and(|expected| now >= expected);
+                    if poll.redraw != due {
+                        return Err(format!(
+                            "op {i}: redraw {} at a deadline {expected:?} now {now:?}",
+                            poll.redraw
+                        ));
+                    }
+                    if due {
+                        expected = None;
+                    }
+                    let control_flow = match expected {
+                        Some(expected) => ControlFlow::WaitUntil(expected),
+                        None => ControlFlow::Wait,
+                    };
+                    if poll.control_flow != control_flow {
+                        return Err(format!(
+                            "op {i}: {:?} instead of {control_flow:?}",
+                            poll.control_flow
+                        ));
+                    }
+                    if let ControlFlow::WaitUntil(deadline) = poll.control_flow {
+                        if deadline <= now {
+                            return Err(format!("op {i}: waits until a deadline that passed"));
+                        }
+                    }
+                }
+                Op::Clear => {
+                    scheduler.clear();
+                    expected = None;
+                }
+            }
+            if scheduler.deadline != expected {
+                return Err(format!(
+                    "op {i}: deadline {:?} instead of {expected:?}",
+                    scheduler.deadline
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn random_op(rng: &mut Rng) -> Op {
+        match rng.below(10) {
+            0..=4 => Op::Result {
+                kind: rng.below(5),
+                at_ms: rng.below(150) as i64 - 50,
+                painted: rng.below(2) == 0,
+            },
+            5..=6 => Op::Advance(rng.below(40)),
+            7..=8 => Op::Poll,
+            _ => Op::Clear,
+        }
+    }
+
+    #[test]
+    fn random_sequences_match_the_model() {
+        for paint_inline in [false, true] {
+            check_sequences(500, 60, random_op, |ops| check_scheduler(paint_inline, ops));
+        }
+    }
+
+    /// Repaints at least this often.
+    const REPAINT_AFTER: Duration = Duration::from_millis(250);
+
+    /// Cancels every other close request.
+    #[derive(Default)]
+    struct Flaky {
+        asked: u32,
+        exits: u32,
+    }
+
+    impl UserApp for Flaky {
+        fn update(&mut self, ctx: &egui::Context) {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let _ = ui.button("Button");
+            });
+            ctx.request_repaint_after(REPAINT_AFTER);
+        }
+
+        fn on_close_requested(&mut self, _default: CloseDecision) -> CloseDecision {
+            self.asked += 1;
+            if self.asked % 2 == 1 {
+                CloseDecision::Cancel
+            } else {
+                CloseDecision::Close
+            }
+        }
+
+        fn on_exit(&mut self, _forced: bool) {
+            self.exits += 1;
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    enum WindowInput {
+        Resize([u32; 2]),
+        Close,
+        Click(egui::Pos2),
+        Shift(bool),
+        Focus(bool),
+        Occluded(bool),
+        Redraw,
+    }
+
+    #[derive(Clone, Debug)]
+    enum WindowOp {
+        /// Delivered in one iteration of the event loop.
+        Events(Vec<WindowInput>),
+        /// Up to this many ms pass, until the event loop wakes up.
+        Wait(u64),
+    }
+
+    fn random_input(rng: &mut Rng) -> WindowInput {
+        let side = |rng: &mut Rng| match rng.below(8) {
+            0 => 0,
+            1 => 1,
+            2 => 100_000,
+            _ => 1 + rng.below(1200) as u32,
+        };
+        match rng.below(12) {
+            0..=1 => WindowInput::Resize([side(rng), side(rng)]),
+            2..=3 => WindowInput::Close,
+            4..=5 => WindowInput::Click(egui::pos2(rng.below(1300) as f32, rng.below(1300) as f32)),
+            6 => WindowInput::Shift(rng.below(4) == 0),
+            7 => WindowInput::Focus(rng.below(2) == 0),
+            8 => WindowInput::Occluded(rng.below(2) == 0),
+            _ => WindowInput::Redraw,
+        }
+    }
+
+    fn random_window_op(rng: &mut Rng) -> WindowOp {
+        if rng.below(3) == 0 {
+            WindowOp::Wait(rng.below(500))
+        } else {
+            WindowOp::Events((0..=rng.below(4)).map(|_| random_input(rng)).collect())
+        }
+    }
+
+    /// Whether the surface fits a window of `size` after a frame.
+    fn check_surface(test_app: &TestApp<Flaky>, size: [u32; 2]) -> std::result::Result<(), String> {
+        let surface_size = test_app.surface_size();
+        let max = test_app.max_surface_side();
+        let fits = if size.iter().all(|&side| side <= max) {
+            surface_size == size
+        } else {
+            !surface_size.contains(&0) && surface_size.iter().all(|&side| side <= max)
+        };
+        if !fits || test_app.surface_texture_size() != Some(surface_size) {
+            return Err(format!("surface {surface_size:?} for a {size:?} window"));
+        }
+        Ok(())
+    }
+
+    /// Runs `ops` through the renderer and the scheduler, and checks that
+    /// nothing runs after the exit, that the event loop never sleeps through
+    /// the repaint the app asked for, and that every close request is
+    /// answered once the loop woke up.
+    fn check_window(ops: &[WindowOp]) -> std::result::Result<(), String> {
+        // Only the app's answers and Shift decide here, not how fast
+        // requests came.
+        let options = Options {
+            window_size: egui::vec2(800., 600.),
+            force_close_requests: 0,
+            ..Default::default()
+        };
+        let Some(mut test_app) = TestApp::with_options(Flaky::default(), options) else {
+            return Ok(());
+        };
+        test_app.step(Vec::new());
+        let mut size = test_app.surface_size();
+        // How often the app was asked when the first unanswered close
+        // request came.
+        let mut unanswered = None;
+        for (i, op) in ops.iter().enumerate() {
+            let mut painted_size = None;
+            match op {
+                WindowOp::Events(inputs) => {
+                    let mut events = Vec::new();
+                    for input in inputs {
+                        match *input {
+                            WindowInput::Resize(new_size) => {
+                                events.push(test_app.resize_event(new_size));
+                                if !new_size.contains(&0) {
+                                    size = new_size;
+                                }
+                            }
+                            WindowInput::Close => {
+                                unanswered.get_or_insert(test_app.app().asked);
+                                events.push(WindowEvent::CloseRequested);
+                            }
+                            WindowInput::Click(pos) => events.extend([
+                                test_app.cursor_moved(pos),
+                                TestApp::<Flaky>::mouse_input(
+                                    MouseButton::Left,
+                                    ElementState::Pressed,
+                                ),
+                                TestApp::<Flaky>::mouse_input(
+                                    MouseButton::Left,
+                                    ElementState::Released,
+                                ),
+                            ]),
+                            WindowInput::Shift(held) => {
+                                let modifiers = if held {
+                                    ModifiersState::SHIFT
+                                } else {
+                                    ModifiersState::empty()
+                                };
+                                events.push(WindowEvent::ModifiersChanged(modifiers.into()));
+                            }
+                            WindowInput::Focus(focused) => {
+                                events.push(WindowEvent::Focused(focused))
+                            }
+                            WindowInput::Occluded(occluded) => {
+                                events.push(WindowEvent::Occluded(occluded))
+                            }
+                            WindowInput::Redraw => {
+                                painted_size = Some(size);
+                                events.push(WindowEvent::RedrawRequested);
+                            }
+                        }
+                    }
+                    test_app.window_events(events);
+                }
+                WindowOp::Wait(ms_later) => {
+                    let now = test_app.now();
+                    let until = now + ms(*ms_later);
+                    let wake = test_app.next_wake().filter(|&wake| wake <= until);
+                    test_app.advance(wake.unwrap_or(until).saturating_duration_since(now));
+                    let last_frame = test_app.last_frame();
+                    test_app.poll();
+                    if test_app.last_frame() != last_frame {
+                        painted_size = Some(size);
+                    }
+                    let asked = test_app.app().asked;
+                    if unanswered.take().is_some_and(|before| before == asked) && !test_app.exit {
+                        return Err(format!("op {i}: a close request went unanswered"));
+                    }
+                }
+            }
+
+            if test_app.frames_after_exit() > 0 {
+                return Err(format!(
+                    "op {i}: {} frames after the exit",
+                    test_app.frames_after_exit()
+                ));
+            }
+            if test_app.app().exits != u32::from(test_app.exit) {
+                return Err(format!(
+                    "op {i}: on_exit ran {} times, exit is {}",
+                    test_app.app().exits,
+                    test_app.exit
+                ));
+            }
+            if test_app.exit {
+                break;
+            }
+            if let Some(size) = painted_size {
+                check_surface(&test_app, size).map_err(|err| format!("op {i}: {err}"))?;
+            }
+            if let Some(last_frame) = test_app.last_frame() {
+                let wake = test_app.next_wake();
+                if wake.is_none_or(|wake| wake > last_frame + REPAINT_AFTER) {
+                    return Err(format!(
+                        "op {i}: sleeps until {wake:?} after a frame at {last_frame:?}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn random_window_events_keep_invariants() {
+        check_sequences(24, 16, random_window_op, check_window);
+    }
+}
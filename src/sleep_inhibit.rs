@@ -0,0 +1,250 @@
+fn count_id() -> egui::Id {
+    egui::Id::new("sleep_inhibit_count")
+}
+
+fn condition_id() -> egui::Id {
+    egui::Id::new("sleep_inhibit_condition")
+}
+
+/// Keeps the display and the system awake. Callers compose: every `true`
+/// needs a matching `false` before the machine may sleep again.
+pub fn set_sleep_inhibited(ctx: &egui::Context, inhibited: bool) {
+    ctx.data_mut(|d| {
+        let count = d.get_temp_mut_or_default::<usize>(count_id());
+        *count = if inhibited {
+            *count + 1
+        } else {
+            count.saturating_sub(1)
+        };
+    });
+    ctx.request_repaint();
+}
+
+/// Keeps the machine awake for as long as this is called with a true
+/// condition every frame, e.g. while a video plays.
+pub fn inhibit_sleep_while(ctx: &egui::Context, condition: bool) {
+    if condition {
+        ctx.data_mut(|d| d.insert_temp(condition_id(), true));
+    }
+}
+
+/// Holds the platform's sleep inhibition while the app asks for it and the
+/// event loop is not suspended. Released on drop, so on exit too.
+#[derive(Default)]
+pub struct SleepInhibitor {
+    inhibition: Option<platform::Inhibition>,
+    suspended: bool,
+    /// Set after a failure was reported, until the app stops asking.
+    failed: bool,
+}
+
+impl SleepInhibitor {
+    pub fn is_active(&self) -> bool {
+        self.inhibition.is_some()
+    }
+
+    /// Call after each pass.
+    pub fn update(&mut self, ctx: &egui::Context) {
+        let count = ctx.data(|d| d.get_temp::<usize>(count_id())).unwrap_or(0);
+        let condition = ctx
+            .data_mut(|d| d.remove_temp::<bool>(condition_id()))
+            .unwrap_or(false);
+        let wanted = (count > 0 || condition) && !self.suspended;
+        if !wanted {
+            self.failed = false;
+            if self.inhibition.take().is_some() {
+                log::info!("allowing the display to sleep again");
+            }
+        } else if self.inhibition.is_none() && !self.failed {
+            match platform::Inhibition::new() {
+                Ok(inhibition) => {
+                    log::info!("keeping the display awake");
+                    self.inhibition = Some(inhibition);
+                }
+                Err(err) => {
+                    log::warn!("cannot keep the display awake: {err}");
+                    self.failed = true;
+                }
+            }
+        }
+    }
+
+    /// Releases the inhibition while suspended; the next frame after
+    /// resuming takes it again.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+        if suspended {
+            self.inhibition = None;
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows_sys::Win32::System::Power::{
+        SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+    };
+
+    /// Applies to the thread that created it, which is the event loop's.
+    pub struct Inhibition;
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, String> {
+            // SAFETY: Only takes flags.
+            let previous = unsafe {
+                SetThreadExecutionState(ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED)
+            };
+            if previous == 0 {
+                return Err("SetThreadExecutionState failed".to_owned());
+            }
+            Ok(Self)
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            // SAFETY: Only takes flags.
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::{c_char, c_void, CString};
+
+    type CFStringRef = *const c_void;
+
+    const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    const ASSERTION_LEVEL_ON: u32 = 255;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            allocator: *const c_void,
+            string: *const c_char,
+            encoding: u32,
+        ) -> CFStringRef;
+        fn CFRelease(object: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    struct CFString(CFStringRef);
+
+    impl CFString {
+        fn new(text: &str) -> Option<Self> {
+            let text = CString::new(text).ok()?;
+            // SAFETY: `text` is a valid C string for the duration of the
+            // call; the default allocator is requested with null.
+            let string = unsafe {
+                CFStringCreateWithCString(std::ptr::null(), text.as_ptr(), CF_STRING_ENCODING_UTF8)
+            };
+            (!string.is_null()).then_some(Self(string))
+        }
+    }
+
+    impl Drop for CFString {
+        fn drop(&mut self) {
+            // SAFETY: Created by `CFStringCreateWithCString` and released once.
+            unsafe { CFRelease(self.0) };
+        }
+    }
+
+    pub struct Inhibition(u32);
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, String> {
+            let (Some(assertion_type), Some(name)) = (
+                CFString::new("PreventUserIdleDisplaySleep"),
+                CFString::new(env!("CARGO_PKG_NAME")),
+            ) else {
+                return Err("failed to create a CFString".to_owned());
+            };
+            let mut id = 0;
+            // SAFETY: Both strings are valid and `id` outlives the call.
+            let result = unsafe {
+                IOPMAssertionCreateWithName(assertion_type.0, ASSERTION_LEVEL_ON, name.0, &mut id)
+            };
+            if result != 0 {
+                return Err(format!("IOPMAssertionCreateWithName failed: {result:#x}"));
+            }
+            Ok(Self(id))
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            // SAFETY: The assertion was created by `new` and is released once.
+            unsafe { IOPMAssertionRelease(self.0) };
+        }
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod platform {
+    use std::process::{Child, Command, Stdio};
+
+    /// An idle and sleep inhibitor taken through logind by `systemd-inhibit`.
+    /// Screensavers that ignore logind's idle inhibitors may still blank the
+    /// display. The child exits with the app even if the app crashes.
+    pub struct Inhibition(Child);
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, String> {
+            Command::new("systemd-inhibit")
+                .args([
+                    "--what=idle:sleep",
+                    concat!("--who=", env!("CARGO_PKG_NAME")),
+                    "--why=Keeping the display awake",
+                    "tail",
+                    &format!("--pid={}", std::process::id()),
+                    "-f",
+                    "/dev/null",
+                ])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map(Self)
+                .map_err(|err| format!("cannot run systemd-inhibit: {err}"))
+        }
+    }
+
+    impl Drop for Inhibition {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "macos",
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+mod platform {
+    pub struct Inhibition;
+
+    impl Inhibition {
+        pub fn new() -> Result<Self, String> {
+            Err("not supported on this platform".to_owned())
+        }
+    }
+}
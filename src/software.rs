@@ -0,0 +1,641 @@
+use std::{any::Any, collections::HashMap};
+
+use anyhow::Context as _;
+
+use egui::{
+    epaint::{textures, ImageData, Primitive},
+    ClippedPrimitive, Color32, Mesh, Pos2, Rect, TextureFilter, TextureId,
+};
+use egui_wgpu::ScreenDescriptor;
+use egui_winit::winit::window::Window;
+
+use crate::painter::EguiPainter;
+
+/// Premultiplied pixels in B, G, R, A byte order, which is what both X11 and
+/// GDI take.
+#[derive(Default)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width as usize;
+        self.height = height as usize;
+        self.pixels.resize(self.width * self.height * 4, 0);
+    }
+
+    fn blend(&mut self, x: usize, y: usize, [r, g, b, a]: [f32; 4]) {
+        let index = (y * self.width + x) * 4;
+        let pixel = &mut self.pixels[index..index + 4];
+        let keep = 1. - a / 255.;
+        for (dst, src) in pixel.iter_mut().zip([b, g, r, a]) {
+            *dst = (src + f32::from(*dst) * keep).round().min(255.) as u8;
+        }
+    }
+}
+
+struct Texture {
+    size: [usize; 2],
+    pixels: Vec<Color32>,
+    nearest: bool,
+}
+
+impl Texture {
+    fn texel(&self, x: isize, y: isize) -> [f32; 4] {
+        let x = x.clamp(0, self.size[0] as isize - 1) as usize;
+        let y = y.clamp(0, self.size[1] as isize - 1) as usize;
+        let c = self.pixels[y * self.size[0] + x];
+        [c.r(), c.g(), c.b(), c.a()].map(f32::from)
+    }
+
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let x = u * self.size[0] as f32 - 0.5;
+        let y = v * self.size[1] as f32 - 0.5;
+        if self.nearest {
+            return self.texel(x.round() as isize, y.round() as isize);
+        }
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let [a, b, c, d] = [
+            self.texel(x0, y0),
+            self.texel(x0 + 1, y0),
+            self.texel(x0, y0 + 1),
+            self.texel(x0 + 1, y0 + 1),
+        ];
+        std::array::from_fn(|i| {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+}
+
+/// Rasterizes egui's meshes on the CPU, blending in gamma space like egui's
+/// own renderers. Slow, but it needs nothing from the GPU. Paint callbacks
+/// are skipped.
+#[derive(Default)]
+pub struct SoftwareRenderer {
+    textures: HashMap<TextureId, Texture>,
+}
+
+impl SoftwareRenderer {
+    pub fn paint_frame(
+        &mut self,
+        canvas: &mut Canvas,
+        screen_descriptor: ScreenDescriptor,
+        clipped_primitives: &[ClippedPrimitive],
+        textures_delta: textures::TexturesDelta,
+    ) {
+        profiling::scope!("SoftwareRenderer::paint_frame");
+
+        for (id, delta) in &textures_delta.set {
+            self.set_texture(*id, delta);
+        }
+        let [width, height] = screen_descriptor.size_in_pixels;
+        canvas.resize(width, height);
+        canvas.pixels.fill(0);
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in clipped_primitives
+        {
+            if let Primitive::Mesh(mesh) = primitive {
+                self.draw_mesh(
+                    canvas,
+                    *clip_rect * screen_descriptor.pixels_per_point,
+                    mesh,
+                    screen_descriptor.pixels_per_point,
+                );
+            }
+        }
+        for id in &textures_delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    fn set_texture(&mut self, id: TextureId, delta: &egui::epaint::ImageDelta) {
+        let (size, pixels) = match &delta.image {
+            ImageData::Color(image) => (image.size, image.pixels.clone()),
+            ImageData::Font(image) => (image.size, image.srgba_pixels(None).collect()),
+        };
+        let nearest = delta.options.magnification == TextureFilter::Nearest;
+        match (delta.pos, self.textures.get_mut(&id)) {
+            (Some([x, y]), Some(texture)) => {
+                for (row, pixels) in pixels.chunks(size[0]).enumerate() {
+                    let start = (y + row) * texture.size[0] + x;
+                    texture.pixels[start..start + size[0]].copy_from_slice(pixels);
+                }
+            }
+            (Some(_), None) => log::warn!("partial update of unknown texture {id:?}"),
+            (None, _) => {
+                self.textures.insert(
+                    id,
+                    Texture {
+                        size,
+                        pixels,
+                        nearest,
+                    },
+                );
+            }
+        }
+    }
+
+    fn draw_mesh(&self, canvas: &mut Canvas, clip: Rect, mesh: &Mesh, pixels_per_point: f32) {
+        let Some(texture) = self.textures.get(&mesh.texture_id) else {
+            return;
+        };
+        let min_x = clip.min.x.round().max(0.) as usize;
+        let min_y = clip.min.y.round().max(0.) as usize;
+        let max_x = (clip.max.x.round().max(0.) as usize).min(canvas.width);
+        let max_y = (clip.max.y.round().max(0.) as usize).min(canvas.height);
+        if min_x >= max_x || min_y >= max_y {
+            return;
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let mut vertices = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+            let mut p = vertices.map(|v| v.pos * pixels_per_point);
+            let mut area = edge(p[0], p[1], p[2]);
+            if area == 0. {
+                continue;
+            }
+            if area < 0. {
+                vertices.swap(1, 2);
+                p.swap(1, 2);
+                area = -area;
+            }
+            let colors = vertices.map(|v| {
+                let c = v.color;
+                [c.r(), c.g(), c.b(), c.a()].map(f32::from)
+            });
+            let top_left = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])].map(|(a, b)| {
+                let (dx, dy) = (b.x - a.x, b.y - a.y);
+                dy < 0. || (dy == 0. && dx > 0.)
+            });
+
+            let x0 = (p
+                .iter()
+                .map(|p| p.x)
+                .fold(f32::INFINITY, f32::min)
+                .floor()
+                .max(0.) as usize)
+                .max(min_x);
+            let y0 = (p
+                .iter()
+                .map(|p| p.y)
+                .fold(f32::INFINITY, f32::min)
+                .floor()
+                .max(0.) as usize)
+                .max(min_y);
+            let x1 = (p.iter().map(|p| p.x).fold(0., f32::max).ceil() as usize).min(max_x);
+            let y1 = (p.iter().map(|p| p.y).fold(0., f32::max).ceil() as usize).min(max_y);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let center = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let w = [
+                        edge(p[1], p[2], center),
+                        edge(p[2], p[0], center),
+                        edge(p[0], p[1], center),
+                    ];
+                    let inside = (0..3).all(|i| w[i] > 0. || (w[i] == 0. && top_left[i]));
+                    if !inside {
+                        continue;
+                    }
+                    let w = w.map(|w| w / area);
+                    let u =
+                        w[0] * vertices[0].uv.x + w[1] * vertices[1].uv.x + w[2] * vertices[2].uv.x;
+                    let v =
+                        w[0] * vertices[0].uv.y + w[1] * vertices[1].uv.y + w[2] * vertices[2].uv.y;
+                    let texel = texture.sample(u, v);
+                    let color = std::array::from_fn(|i| {
+                        let vertex =
+                            w[0] * colors[0][i] + w[1] * colors[1][i] + w[2] * colors[2][i];
+                        vertex * texel[i] / 255.
+                    });
+                    canvas.blend(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Twice the signed area of `a`, `b`, `p`; positive when `p` is right of
+/// `a` to `b` with y pointing down.
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Paints with [`SoftwareRenderer`] and copies frames to the window through
+/// the platform. Works on X11 and Windows. The canvas is the frame, handed
+/// out and back so it keeps its allocation.
+pub struct SoftwarePainter {
+    renderer: SoftwareRenderer,
+    canvas: Option<Canvas>,
+    textures_delta: textures::TexturesDelta,
+    presenter: Presenter,
+}
+
+impl SoftwarePainter {
+    pub fn new(window: &Window) -> anyhow::Result<Self> {
+        Ok(Self {
+            renderer: SoftwareRenderer::default(),
+            canvas: Some(Canvas::default()),
+            textures_delta: Default::default(),
+            presenter: Presenter::new(window)?,
+        })
+    }
+}
+
+impl EguiPainter for SoftwarePainter {
+    fn max_texture_side(&self) -> usize {
+        usize::MAX
+    }
+
+    fn update_textures(&mut self, textures_delta: textures::TexturesDelta) {
+        self.textures_delta.append(textures_delta);
+    }
+
+    fn acquire_frame(&mut self) -> anyhow::Result<Option<Box<dyn Any>>> {
+        let canvas = self.canvas.take().unwrap_or_default();
+        Ok(Some(Box::new(canvas)))
+    }
+
+    fn paint(
+        &mut self,
+        clipped_primitives: &[ClippedPrimitive],
+        screen_descriptor: ScreenDescriptor,
+        frame: &mut dyn Any,
+    ) -> anyhow::Result<()> {
+        let canvas = frame
+            .downcast_mut::<Canvas>()
+            .context("not a frame of this painter")?;
+        self.renderer.paint_frame(
+            canvas,
+            screen_descriptor,
+            clipped_primitives,
+            std::mem::take(&mut self.textures_delta),
+        );
+        Ok(())
+    }
+
+    fn present(&mut self, frame: Box<dyn Any>) -> anyhow::Result<()> {
+        let canvas = frame
+            .downcast::<Canvas>()
+            .ok()
+            .context("not a frame of this painter")?;
+        self.presenter.present(&canvas)?;
+        self.canvas = Some(*canvas);
+        Ok(())
+    }
+
+    fn resize(&mut self, _width: u32, _height: u32) {}
+
+    fn describe(&self) -> String {
+        "none, painting on the CPU".to_owned()
+    }
+
+    fn is_software(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+use x11::Presenter;
+
+#[cfg(windows)]
+use win32::Presenter;
+
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+struct Presenter;
+
+#[cfg(not(any(
+    windows,
+    all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    )
+)))]
+impl Presenter {
+    fn new(_window: &Window) -> anyhow::Result<Self> {
+        anyhow::bail!("painting on the CPU is not supported on this platform")
+    }
+
+    fn present(&self, _canvas: &Canvas) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod x11 {
+    use anyhow::Context as _;
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+    use x11rb::{
+        connection::{Connection, RequestConnection},
+        protocol::xproto::{ConnectionExt, CreateGCAux, ImageFormat, ImageOrder},
+        rust_connection::RustConnection,
+    };
+
+    use super::Canvas;
+
+    /// Bytes of a PutImage request besides the image.
+    const PUT_IMAGE_HEADER: usize = 24;
+
+    /// Copies frames with PutImage over a connection of our own.
+    pub struct Presenter {
+        connection: RustConnection,
+        window: u32,
+        gc: u32,
+        depth: u8,
+    }
+
+    impl Presenter {
+        pub fn new(window: &Window) -> anyhow::Result<Self> {
+            let window = match window.window_handle().map(|handle| handle.as_raw()) {
+                Ok(RawWindowHandle::Xlib(handle)) => handle.window as u32,
+                Ok(RawWindowHandle::Xcb(handle)) => handle.window.get(),
+                _ => anyhow::bail!("painting on the CPU needs an X11 window"),
+            };
+            let (connection, _) = x11rb::connect(None).context("cannot connect to the X server")?;
+            let depth = connection
+                .get_geometry(window)
+                .context("cannot query the window")?
+                .reply()
+                .context("cannot query the window")?
+                .depth;
+            let setup = connection.setup();
+            let bits_per_pixel = setup
+                .pixmap_formats
+                .iter()
+                .find(|format| format.depth == depth)
+                .map(|format| format.bits_per_pixel);
+            if bits_per_pixel != Some(32) || setup.image_byte_order != ImageOrder::LSB_FIRST {
+                anyhow::bail!("the X server takes no 32-bit little-endian images at depth {depth}");
+            }
+            let gc = connection
+                .generate_id()
+                .context("cannot create a graphics context")?;
+            connection
+                .create_gc(gc, window, &CreateGCAux::new())
+                .context("cannot create a graphics context")?;
+            Ok(Self {
+                connection,
+                window,
+                gc,
+                depth,
+            })
+        }
+
+        pub fn present(&self, canvas: &Canvas) -> anyhow::Result<()> {
+            let row_bytes = canvas.width * 4;
+            let max_rows = (self.connection.maximum_request_bytes() - PUT_IMAGE_HEADER) / row_bytes;
+            for (band, rows) in canvas
+                .pixels
+                .chunks(row_bytes * max_rows.max(1))
+                .enumerate()
+            {
+                self.connection
+                    .put_image(
+                        ImageFormat::Z_PIXMAP,
+                        self.window,
+                        self.gc,
+                        canvas.width as u16,
+                        (rows.len() / row_bytes) as u16,
+                        0,
+                        (band * max_rows) as i16,
+                        0,
+                        self.depth,
+                        rows,
+                    )
+                    .context("cannot copy the frame to the window")?;
+            }
+            self.connection
+                .flush()
+                .context("cannot copy the frame to the window")?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win32 {
+    use anyhow::Context as _;
+    use egui_winit::winit::window::Window;
+    use windows_sys::Win32::{
+        Foundation::HWND,
+        Graphics::Gdi::{
+            GetDC, ReleaseDC, SetDIBitsToDevice, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+            DIB_RGB_COLORS,
+        },
+    };
+
+    use super::Canvas;
+
+    /// Copies frames with GDI.
+    pub struct Presenter {
+        hwnd: HWND,
+    }
+
+    impl Presenter {
+        pub fn new(window: &Window) -> anyhow::Result<Self> {
+            let hwnd = crate::titlebar::hwnd(window)
+                .context("painting on the CPU needs a Win32 window")?;
+            Ok(Self { hwnd })
+        }
+
+        pub fn present(&self, canvas: &Canvas) -> anyhow::Result<()> {
+            let info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: canvas.width as i32,
+                    // Negative for rows from the top down.
+                    biHeight: -(canvas.height as i32),
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB,
+                    biSizeImage: 0,
+                    biXPelsPerMeter: 0,
+                    biYPelsPerMeter: 0,
+                    biClrUsed: 0,
+                    biClrImportant: 0,
+                },
+                bmiColors: [Default::default()],
+            };
+            // SAFETY: `hwnd` belongs to a live window owned by this thread,
+            // and the canvas holds `width * height` 32-bit pixels.
+            let copied = unsafe {
+                let dc = GetDC(self.hwnd);
+                let copied = SetDIBitsToDevice(
+                    dc,
+                    0,
+                    0,
+                    canvas.width as u32,
+                    canvas.height as u32,
+                    0,
+                    0,
+                    0,
+                    canvas.height as u32,
+                    canvas.pixels.as_ptr().cast(),
+                    &info,
+                    DIB_RGB_COLORS,
+                );
+                ReleaseDC(self.hwnd, dc);
+                copied
+            };
+            anyhow::ensure!(copied != 0, "cannot copy the frame to the window");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{egui_renderer, testing};
+
+    const SIZE: [u32; 2] = [240, 160];
+    const PIXELS_PER_POINT: f32 = 1.5;
+
+    /// Text, a button, filled and stroked shapes and a gradient, at a
+    /// fractional scale.
+    fn frame() -> (Vec<ClippedPrimitive>, textures::TexturesDelta) {
+        let ctx = egui::Context::default();
+        let input = egui::RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                egui::vec2(SIZE[0] as f32, SIZE[1] as f32) / PIXELS_PER_POINT,
+            )),
+            ..Default::default()
+        };
+        ctx.set_pixels_per_point(PIXELS_PER_POINT);
+        let output = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading("Snapshot");
+                let _ = ui.button("Button");
+                let painter = ui.painter();
+                painter.rect_filled(
+                    Rect::from_min_size(egui::pos2(100., 10.), egui::vec2(40., 30.)),
+                    6.,
+                    Color32::from_rgb(200, 60, 30),
+                );
+                painter.circle_stroke(egui::pos2(120., 75.), 18., (3., Color32::LIGHT_BLUE));
+                let mut mesh = Mesh::default();
+                let rect = Rect::from_min_size(egui::pos2(10., 70.), egui::vec2(60., 20.));
+                mesh.colored_vertex(rect.left_top(), Color32::RED);
+                mesh.colored_vertex(rect.right_top(), Color32::GREEN);
+                mesh.colored_vertex(rect.right_bottom(), Color32::BLUE);
+                mesh.colored_vertex(rect.left_bottom(), Color32::TRANSPARENT);
+                mesh.add_triangle(0, 1, 2);
+                mesh.add_triangle(0, 2, 3);
+                painter.add(mesh);
+            });
+        });
+        let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+        (primitives, output.textures_delta)
+    }
+
+    fn screen_descriptor() -> ScreenDescriptor {
+        ScreenDescriptor {
+            size_in_pixels: SIZE,
+            pixels_per_point: PIXELS_PER_POINT,
+        }
+    }
+
+    /// The canvas as RGBA.
+    fn paint_on_cpu() -> Vec<u8> {
+        let (primitives, textures_delta) = frame();
+        let mut canvas = Canvas::default();
+        SoftwareRenderer::default().paint_frame(
+            &mut canvas,
+            screen_descriptor(),
+            &primitives,
+            textures_delta,
+        );
+        let mut pixels = canvas.pixels;
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+        pixels
+    }
+
+    fn pixel(pixels: &[u8], [x, y]: [f32; 2]) -> &[u8] {
+        let [x, y] = [x, y].map(|v| (v * PIXELS_PER_POINT) as usize);
+        let index = (y * SIZE[0] as usize + x) * 4;
+        &pixels[index..index + 4]
+    }
+
+    #[test]
+    fn shapes_land_where_egui_put_them() {
+        let pixels = paint_on_cpu();
+        assert_eq!(pixels.len(), (SIZE[0] * SIZE[1] * 4) as usize);
+        // Inside the rounded rect, and on the gradient's opaque corner.
+        assert_eq!(pixel(&pixels, [120., 25.]), [200, 60, 30, 255]);
+        let red = pixel(&pixels, [11., 71.]);
+        assert!(red[0] > 230 && red[1] < 30 && red[2] < 30, "{red:?}");
+        // The panel fill covers the rest.
+        let panel = pixel(&pixels, [200. / PIXELS_PER_POINT, 150. / PIXELS_PER_POINT]);
+        assert_eq!(panel[3], 255);
+    }
+
+    /// Compares against egui-wgpu on a software adapter. Antialiased edges
+    /// and dithering differ by a few levels, and a few pixels where triangle
+    /// edges meet pixel centers may be off by more.
+    #[test]
+    fn matches_the_gpu_renderer() {
+        let Some((device, queue)) = testing::software_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let (primitives, textures_delta) = frame();
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let mut renderer = egui_wgpu::Renderer::new(&device, format, None, 1, true);
+        let target = testing::create_target(&device, SIZE);
+        let view = target.create_view(&Default::default());
+        let mut encoder = device.create_command_encoder(&Default::default());
+        egui_renderer::paint_egui(
+            &mut renderer,
+            &device,
+            &queue,
+            &mut encoder,
+            &view,
+            wgpu::Color::TRANSPARENT,
+            &screen_descriptor(),
+            &primitives,
+            &textures_delta,
+            true,
+            None,
+        );
+        let (size, gpu) = testing::read_texture(&device, &queue, encoder, &target);
+        assert_eq!(size, SIZE);
+
+        let cpu = paint_on_cpu();
+        let mut total = 0u64;
+        let mut off = 0;
+        for (cpu, gpu) in cpu.iter().zip(&gpu) {
+            let diff = cpu.abs_diff(*gpu);
+            total += u64::from(diff);
+            if diff > 16 {
+                off += 1;
+            }
+        }
+        let mean = total as f64 / cpu.len() as f64;
+        assert!(mean < 0.25, "mean difference {mean}");
+        assert!(off * 1000 < cpu.len(), "{off} channels off");
+    }
+}
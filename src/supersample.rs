@@ -0,0 +1,235 @@
+use wgpu::{CommandEncoder, Device, TextureView};
+
+const SHADER: &str = r"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // A single triangle covering the whole target.
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
+";
+
+/// Renders egui at a multiple of the surface resolution and filters the
+/// result down onto the surface, which makes text and thin lines look
+/// smoother on low-DPI displays.
+///
+/// egui is painted into the supersampled target first; the downsample pass
+/// is the last pass of the frame and writes straight to the surface. A
+/// bilinear sample at each surface pixel is an exact box filter at 2x and a
+/// slightly softer one at fractional scales.
+pub struct Supersampler {
+    scale: f32,
+    format: wgpu::TextureFormat,
+    size: [u32; 2],
+    view: TextureView,
+    bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Supersampler {
+    pub fn new(
+        device: &Device,
+        format: wgpu::TextureFormat,
+        scale: f32,
+        surface_size: [u32; 2],
+    ) -> Self {
+        let (bind_group_layout, pipeline) = create_blit_pipeline(device, format);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("supersample sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let size = scaled_size(device, surface_size, scale);
+        let view = create_target(device, "supersample target", format, size);
+        let bind_group = create_bind_group(device, &bind_group_layout, &view, &sampler);
+        Self {
+            scale,
+            format,
+            size,
+            view,
+            bind_group,
+            bind_group_layout,
+            sampler,
+            pipeline,
+        }
+    }
+
+    pub fn resize(&mut self, device: &Device, surface_size: [u32; 2]) {
+        let size = scaled_size(device, surface_size, self.scale);
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.view = create_target(device, "supersample target", self.format, size);
+        self.bind_group =
+            create_bind_group(device, &self.bind_group_layout, &self.view, &self.sampler);
+    }
+
+    pub fn target(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn downsample(&self, encoder: &mut CommandEncoder, surface_view: &TextureView) {
+        profiling::scope!("downsample");
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("supersample downsample pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// The size of the supersampled target for a surface of `surface_size`.
+pub fn scaled_size(device: &Device, [width, height]: [u32; 2], scale: f32) -> [u32; 2] {
+    let max = device.limits().max_texture_dimension_2d;
+    let scaled = |side: u32| ((side as f32 * scale).round() as u32).clamp(1, max);
+    [scaled(width), scaled(height)]
+}
+
+pub fn target_bytes(format: wgpu::TextureFormat, [width, height]: [u32; 2]) -> u64 {
+    let bytes_per_texel = format.block_copy_size(None).unwrap_or(4);
+    width as u64 * height as u64 * bytes_per_texel as u64
+}
+
+/// A pipeline that draws a texture over the whole viewport, with the layout
+/// of [`create_bind_group`]'s bind groups.
+pub fn create_blit_pipeline(
+    device: &Device,
+    format: wgpu::TextureFormat,
+) -> (wgpu::BindGroupLayout, wgpu::RenderPipeline) {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("blit shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("blit bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("blit pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("blit pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+    (bind_group_layout, pipeline)
+}
+
+/// A texture egui can paint into and a blit pipeline can sample.
+pub fn create_target(
+    device: &Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    [width, height]: [u32; 2],
+) -> TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+pub fn create_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    view: &TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("blit bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
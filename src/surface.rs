@@ -0,0 +1,82 @@
+//! What frames are presented to: the window's surface, or in tests a
+//! texture standing in for it.
+
+#[cfg(any(test, feature = "testing"))]
+use std::sync::Arc;
+
+use crate::{error::AppError, gpu};
+
+#[derive(Debug)]
+pub enum RenderSurface {
+    Window(wgpu::Surface<'static>),
+    /// Created again on each configure, like a swapchain's textures.
+    #[cfg(any(test, feature = "testing"))]
+    Offscreen(Option<Arc<wgpu::Texture>>),
+}
+
+impl RenderSurface {
+    /// See [`gpu::configure_surface`].
+    pub fn configure(
+        &mut self,
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+    ) -> Result<(), AppError> {
+        match self {
+            Self::Window(surface) => gpu::configure_surface(device, surface, surface_config),
+            #[cfg(any(test, feature = "testing"))]
+            Self::Offscreen(texture) => {
+                *texture = Some(Arc::new(device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("offscreen surface"),
+                    size: wgpu::Extent3d {
+                        width: surface_config.width,
+                        height: surface_config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: surface_config.format,
+                    usage: surface_config.usage,
+                    view_formats: &surface_config.view_formats,
+                })));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get_current_texture(&self) -> Result<SurfaceFrame, wgpu::SurfaceError> {
+        match self {
+            Self::Window(surface) => surface.get_current_texture().map(SurfaceFrame::Window),
+            #[cfg(any(test, feature = "testing"))]
+            Self::Offscreen(texture) => texture
+                .clone()
+                .map(SurfaceFrame::Offscreen)
+                .ok_or(wgpu::SurfaceError::Outdated),
+        }
+    }
+}
+
+/// The texture of one frame, see [`RenderSurface::get_current_texture`].
+pub enum SurfaceFrame {
+    Window(wgpu::SurfaceTexture),
+    #[cfg(any(test, feature = "testing"))]
+    Offscreen(Arc<wgpu::Texture>),
+}
+
+impl SurfaceFrame {
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Self::Window(surface_texture) => &surface_texture.texture,
+            #[cfg(any(test, feature = "testing"))]
+            Self::Offscreen(texture) => texture,
+        }
+    }
+
+    pub fn present(self) {
+        match self {
+            Self::Window(surface_texture) => surface_texture.present(),
+            #[cfg(any(test, feature = "testing"))]
+            Self::Offscreen(_) => {}
+        }
+    }
+}
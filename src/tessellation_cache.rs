@@ -0,0 +1,109 @@
+use egui::{
+    epaint::{textures::TexturesDelta, ClippedShape, Primitive, TessellationOptions},
+    ClippedPrimitive,
+};
+
+/// Reuses the previous frame's tessellation when egui produced identical
+/// shapes, at the same scale and tessellation options and without texture
+/// changes.
+#[derive(Default)]
+pub struct TessellationCache {
+    shapes: Vec<ClippedShape>,
+    pixels_per_point: f32,
+    options: TessellationOptions,
+    clipped_primitives: Option<Vec<ClippedPrimitive>>,
+    generation: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl TessellationCache {
+    pub fn invalidate(&mut self) {
+        self.clipped_primitives = None;
+        self.shapes.clear();
+    }
+
+    /// Identifies the cached tessellation, so that vertex data which was
+    /// already uploaded for it need not be uploaded again.
+    pub fn generation(&self) -> Option<u64> {
+        self.clipped_primitives.as_ref().map(|_| self.generation)
+    }
+
+    pub fn tessellate(
+        &mut self,
+        ctx: &egui::Context,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+        textures_delta: &TexturesDelta,
+    ) -> Vec<ClippedPrimitive> {
+        if !textures_delta.is_empty() {
+            self.invalidate();
+        }
+        // Compared rather than invalidated on change, because egui's own
+        // settings UI changes them too.
+        let options = ctx.tessellation_options(|o| *o);
+        if let Some(clipped_primitives) = &self.clipped_primitives {
+            if pixels_per_point == self.pixels_per_point
+                && options == self.options
+                && shapes == self.shapes
+            {
+                self.hits += 1;
+                return clipped_primitives.clone();
+            }
+        }
+
+        self.misses += 1;
+        self.generation += 1;
+        self.pixels_per_point = pixels_per_point;
+        self.options = options;
+        self.shapes = shapes.clone();
+        let clipped_primitives = ctx.tessellate(shapes, pixels_per_point);
+        // Paint callbacks have to be prepared every frame.
+        let cacheable = clipped_primitives
+            .iter()
+            .all(|p| matches!(p.primitive, Primitive::Mesh(_)));
+        self.clipped_primitives = cacheable.then(|| clipped_primitives.clone());
+        if !cacheable {
+            self.shapes.clear();
+        }
+        clipped_primitives
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::EguiOptions;
+
+    fn vertices(clipped_primitives: &[ClippedPrimitive]) -> usize {
+        clipped_primitives
+            .iter()
+            .map(|clipped| match &clipped.primitive {
+                Primitive::Mesh(mesh) => mesh.vertices.len(),
+                Primitive::Callback(_) => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn changed_options_miss_the_cache() {
+        let ctx = egui::Context::default();
+        let output = ctx.run(Default::default(), |ctx| {
+            ctx.layer_painter(egui::LayerId::background())
+                .circle_filled(egui::pos2(50., 50.), 30., egui::Color32::WHITE);
+        });
+        let no_changes = TexturesDelta::default();
+        let mut cache = TessellationCache::default();
+        let feathered = cache.tessellate(&ctx, output.shapes.clone(), 1., &no_changes);
+        cache.tessellate(&ctx, output.shapes.clone(), 1., &no_changes);
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+
+        let mut options = EguiOptions::default();
+        options.tessellation.feathering = false;
+        options.apply(&ctx);
+        let hard = cache.tessellate(&ctx, output.shapes, 1., &no_changes);
+        assert_eq!((cache.hits, cache.misses), (1, 2));
+        // Feathering adds a ring of transparent vertices around the edge.
+        assert!(vertices(&hard) < vertices(&feathered));
+    }
+}
@@ -0,0 +1,1034 @@
+//! A headless harness for tests: runs a [`UserApp`] on the app's own
+//! [`Renderer`] and [`Scheduler`], in a window that only exists in memory
+//! and on a software adapter such as llvmpipe. Frames are presented to an
+//! offscreen texture, and see a clock of their own.
+
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+use egui::{
+    accesskit::{self, ActionRequest, Node, NodeId, Role},
+    ahash::{HashMap, HashSet},
+    Event, FullOutput, Pos2, Rect, Shape, ViewportCommand, ViewportEvent, ViewportId, ViewportInfo,
+};
+use egui_winit::{
+    winit::{
+        dpi::{PhysicalPosition, PhysicalSize},
+        event::{DeviceId, ElementState, Ime, MouseButton, MouseScrollDelta, WindowEvent},
+        event_loop::ControlFlow,
+        keyboard::ModifiersState,
+        raw_window_handle::{DisplayHandle, HandleError, HasDisplayHandle},
+        window::Window,
+    },
+    ActionRequested, EventResponse,
+};
+use web_time::{Duration, Instant};
+
+use crate::{
+    app::Renderer,
+    egui_renderer::EguiRenderer,
+    error::AppError,
+    event::{EventResult, UserEvent},
+    frame_capture::{FrameCapture, Pixels},
+    gpu::GpuContext,
+    gpu_poll::GpuPoller,
+    host_window::{self, HostWindow},
+    options::Options,
+    scheduler::{Action, Scheduler},
+    surface::RenderSurface,
+    user_app::UserApp,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub struct Frame {
+    pub output: FullOutput,
+    /// What the offscreen surface shows after the frame.
+    pub pixels: Option<Pixels>,
+    /// When egui wants the next frame, `Duration::MAX` for never.
+    pub repaint_delay: Duration,
+}
+
+impl Frame {
+    /// Where a text shape with exactly this text was painted, in points.
+    pub fn find_text(&self, text: &str) -> Option<Rect> {
+        fn find(shape: &Shape, text: &str) -> Option<Rect> {
+            match shape {
+                Shape::Text(shape) if shape.galley.text() == text => {
+                    Some(shape.visual_bounding_rect())
+                }
+                Shape::Vec(shapes) => shapes.iter().find_map(|shape| find(shape, text)),
+                _ => None,
+            }
+        }
+        self.output
+            .shapes
+            .iter()
+            .find_map(|clipped| find(&clipped.shape, text))
+    }
+
+    /// The widgets in the accessibility tree egui built for this frame.
+    pub fn widgets(&self) -> Vec<Widget> {
+        let Some(update) = &self.output.platform_output.accesskit_update else {
+            return Vec::new();
+        };
+        let nodes: HashMap<NodeId, &Node> =
+            update.nodes.iter().map(|(id, node)| (*id, node)).collect();
+        // Labels carry their text as the value.
+        let name = |node: &Node| node.label().or(node.value()).map(str::to_owned);
+        update
+            .nodes
+            .iter()
+            .map(|(id, node)| Widget {
+                id: *id,
+                role: node.role(),
+                label: node.label().map(str::to_owned).or_else(|| {
+                    let names: Vec<String> = node
+                        .labelled_by()
+                        .iter()
+                        .filter_map(|id| name(nodes.get(id)?))
+                        .collect();
+                    (!names.is_empty()).then(|| names.concat())
+                }),
+                value: node.value().map(str::to_owned),
+                rect: node.bounds().map(|bounds| {
+                    Rect::from_min_max(
+                        egui::pos2(bounds.x0 as f32, bounds.y0 as f32),
+                        egui::pos2(bounds.x1 as f32, bounds.y1 as f32),
+                    )
+                }),
+            })
+            .collect()
+    }
+
+    /// The first widget labelled exactly `label`, also by another widget
+    /// like a text edit by the label in front of it.
+    pub fn widget(&self, label: &str) -> Option<Widget> {
+        self.widgets()
+            .into_iter()
+            .find(|widget| widget.label.as_deref() == Some(label))
+    }
+}
+
+/// A widget in egui's accessibility tree, see [`Frame::widgets`].
+#[derive(Clone, Debug)]
+pub struct Widget {
+    pub id: NodeId,
+    pub role: Role,
+    pub label: Option<String>,
+    /// What a text edit holds, or the text of a label.
+    pub value: Option<String>,
+    /// In points.
+    pub rect: Option<Rect>,
+}
+
+/// Set to skip the tests that need a software adapter on machines without
+/// one; without it, a missing adapter fails them.
+pub const SKIP_GPU_TESTS: &str = "SKIP_GPU_TESTS";
+
+/// A device on a software adapter with whichever of `features` it has, or
+/// `None` on machines without one when [`SKIP_GPU_TESTS`] is set.
+pub fn software_device(features: wgpu::Features) -> Option<(Arc<wgpu::Device>, wgpu::Queue)> {
+    software_device_with_limits(features, wgpu::Limits::default())
+}
+
+/// [`software_device`] with lower `limits`, e.g. to test what happens at
+/// the texture size limit.
+pub fn software_device_with_limits(
+    features: wgpu::Features,
+    limits: wgpu::Limits,
+) -> Option<(Arc<wgpu::Device>, wgpu::Queue)> {
+    let (_, _, device, queue) = software_adapter(features, limits)?;
+    Some((Arc::new(device), queue))
+}
+
+fn software_adapter(
+    features: wgpu::Features,
+    limits: wgpu::Limits,
+) -> Option<(wgpu::Instance, wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    }));
+    let Some(adapter) = adapter else {
+        return skip("no software adapter");
+    };
+    let descriptor = wgpu::DeviceDescriptor {
+        required_features: adapter.features() & features,
+        required_limits: limits,
+        ..Default::default()
+    };
+    match pollster::block_on(adapter.request_device(&descriptor, None)) {
+        Ok((device, queue)) => Some((instance, adapter, device, queue)),
+        Err(err) => skip(&format!("no device on the software adapter: {err}")),
+    }
+}
+
+/// Fails the test, unless [`SKIP_GPU_TESTS`] asks to skip it.
+fn skip<T>(reason: &str) -> Option<T> {
+    assert!(
+        std::env::var_os(SKIP_GPU_TESTS).is_some(),
+        "{reason}. Install a software adapter such as Mesa's lavapipe, \
+         or set {SKIP_GPU_TESTS}=1 to skip the tests that need one"
+    );
+    log::warn!("{reason}, skipping");
+    None
+}
+
+/// Submits `encoder` with a copy of `texture`, and waits for its pixels.
+pub fn read_texture(
+    device: &Arc<wgpu::Device>,
+    queue: &wgpu::Queue,
+    mut encoder: wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+) -> Pixels {
+    let mut capture = FrameCapture::new(device, &mut encoder, texture, "test readback")
+        .expect("the texture is RGBA");
+    queue.submit([encoder.finish()]);
+    // Its repaint requests would reach no app's context.
+    let poller = GpuPoller::new(device.clone(), egui::Context::default()).unwrap();
+    capture.after_submit(&poller);
+    let pixels = loop {
+        device.poll(wgpu::Maintain::Wait);
+        if let Some(pixels) = capture.try_read() {
+            break pixels.expect("mapping the texture failed");
+        }
+    };
+    poller.stop();
+    pixels
+}
+
+/// A texture like the offscreen surface, for painting by other means.
+#[cfg(feature = "software-renderer")]
+pub fn create_target(device: &wgpu::Device, [width, height]: [u32; 2]) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("test target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+/// A window that only exists in memory. It turns window events into egui
+/// input like egui-winit does for a native one, and dates frames by the
+/// test clock.
+pub struct HeadlessWindow {
+    size: Cell<PhysicalSize<u32>>,
+    scale_factor: Cell<f64>,
+    /// Only moves with [`TestApp::advance`].
+    time: Cell<Duration>,
+    /// In points. Button events have no position of their own.
+    pointer: Cell<Option<Pos2>>,
+    visible: Cell<bool>,
+}
+
+impl HeadlessWindow {
+    pub fn new(size: PhysicalSize<u32>) -> Self {
+        Self {
+            size: Cell::new(size),
+            scale_factor: Cell::new(1.),
+            time: Cell::new(Duration::ZERO),
+            pointer: Cell::new(None),
+            // Like the app's windows, which show once a frame is presented.
+            visible: Cell::new(false),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible.get()
+    }
+
+    /// Like the OS resizing the window, before it sends `Resized`.
+    pub fn set_size(&self, size: PhysicalSize<u32>) {
+        self.size.set(size);
+    }
+}
+
+impl HasDisplayHandle for HeadlessWindow {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        Err(HandleError::Unavailable)
+    }
+}
+
+impl HostWindow for HeadlessWindow {
+    fn native(&self) -> Option<&Arc<Window>> {
+        None
+    }
+
+    fn inner_size(&self) -> PhysicalSize<u32> {
+        self.size.get()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.scale_factor.get()
+    }
+
+    fn is_minimized(&self) -> Option<bool> {
+        None
+    }
+
+    fn set_visible(&self, visible: bool) {
+        self.visible.set(visible);
+    }
+
+    fn pre_present_notify(&self) {}
+
+    fn on_window_event(
+        &self,
+        egui_winit: &mut egui_winit::State,
+        event: &WindowEvent,
+    ) -> EventResponse {
+        let egui_ctx = egui_winit.egui_ctx().clone();
+        let pixels_per_point = host_window::pixels_per_point(&egui_ctx, self);
+        let input = egui_winit.egui_input_mut();
+        let (event, consumed) = match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pos = egui::pos2(position.x as f32, position.y as f32) / pixels_per_point;
+                self.pointer.set(Some(pos));
+                (Some(Event::PointerMoved(pos)), egui_ctx.is_using_pointer())
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer.set(None);
+                (Some(Event::PointerGone), false)
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => Some(egui::PointerButton::Primary),
+                    MouseButton::Right => Some(egui::PointerButton::Secondary),
+                    MouseButton::Middle => Some(egui::PointerButton::Middle),
+                    MouseButton::Back => Some(egui::PointerButton::Extra1),
+                    MouseButton::Forward => Some(egui::PointerButton::Extra2),
+                    MouseButton::Other(_) => None,
+                };
+                let event =
+                    self.pointer
+                        .get()
+                        .zip(button)
+                        .map(|(pos, button)| Event::PointerButton {
+                            pos,
+                            button,
+                            pressed: *state == ElementState::Pressed,
+                            modifiers: input.modifiers,
+                        });
+                (event, egui_ctx.wants_pointer_input())
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match *delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (egui::MouseWheelUnit::Line, egui::vec2(x, y))
+                    }
+                    MouseScrollDelta::PixelDelta(delta) => (
+                        egui::MouseWheelUnit::Point,
+                        egui::vec2(delta.x as f32, delta.y as f32) / pixels_per_point,
+                    ),
+                };
+                let event = Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers: input.modifiers,
+                };
+                (Some(event), egui_ctx.wants_pointer_input())
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                let command = if cfg!(target_os = "macos") {
+                    state.super_key()
+                } else {
+                    state.control_key()
+                };
+                input.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: cfg!(target_os = "macos") && state.super_key(),
+                    command,
+                };
+                (None, false)
+            }
+            WindowEvent::Focused(focused) => {
+                input.focused = *focused;
+                (Some(Event::WindowFocused(*focused)), false)
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => (
+                Some(Event::Ime(egui::ImeEvent::Commit(text.clone()))),
+                egui_ctx.wants_keyboard_input(),
+            ),
+            WindowEvent::DroppedFile(path) => {
+                input.dropped_files.push(egui::DroppedFile {
+                    path: Some(path.clone()),
+                    ..Default::default()
+                });
+                (None, false)
+            }
+            _ => (None, false),
+        };
+        input.events.extend(event);
+        EventResponse {
+            consumed,
+            repaint: true,
+        }
+    }
+
+    fn take_egui_input(&self, egui_winit: &mut egui_winit::State) -> egui::RawInput {
+        let size = self.size.get();
+        let size_in_points = egui::vec2(size.width as f32, size.height as f32)
+            / host_window::pixels_per_point(egui_winit.egui_ctx(), self);
+        let input = egui_winit.egui_input_mut();
+        input.time = Some(self.time.get().as_secs_f64());
+        input.screen_rect = (size_in_points.x > 0. && size_in_points.y > 0.)
+            .then(|| Rect::from_min_size(Pos2::ZERO, size_in_points));
+        input.viewport_id = ViewportId::ROOT;
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.scale_factor.get() as f32);
+        input.take()
+    }
+
+    fn handle_platform_output(
+        &self,
+        _egui_winit: &mut egui_winit::State,
+        _platform_output: egui::PlatformOutput,
+    ) {
+    }
+
+    fn update_viewport_info(
+        &self,
+        info: &mut ViewportInfo,
+        egui_ctx: &egui::Context,
+        _is_init: bool,
+    ) {
+        let size = self.size.get();
+        let pixels_per_point = host_window::pixels_per_point(egui_ctx, self);
+        info.native_pixels_per_point = Some(self.scale_factor.get() as f32);
+        info.inner_rect = Some(Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(size.width as f32, size.height as f32) / pixels_per_point,
+        ));
+        info.focused = Some(true);
+        info.minimized = Some(false);
+    }
+
+    /// Only what egui asks of the app itself: closing and the clipboard.
+    fn process_viewport_commands(
+        &self,
+        _egui_ctx: &egui::Context,
+        info: &mut ViewportInfo,
+        commands: Vec<ViewportCommand>,
+        actions_requested: &mut HashSet<ActionRequested>,
+    ) {
+        for command in commands {
+            match command {
+                ViewportCommand::Close => info.events.push(ViewportEvent::Close),
+                ViewportCommand::RequestCut => {
+                    actions_requested.insert(ActionRequested::Cut);
+                }
+                ViewportCommand::RequestCopy => {
+                    actions_requested.insert(ActionRequested::Copy);
+                }
+                ViewportCommand::RequestPaste => {
+                    actions_requested.insert(ActionRequested::Paste);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Drives a [`UserApp`] through the app's [`Renderer`] and [`Scheduler`]:
+/// window events go through [`Renderer::on_window_event`], and each step is
+/// a redraw painted to an offscreen surface, see [`Self::step`].
+pub struct TestApp<T> {
+    renderer: Renderer<T>,
+    window: HeadlessWindow,
+    scheduler: Scheduler,
+    /// egui's repaint requests, delivered like the event loop's user events.
+    repaints: Arc<Mutex<Vec<(Duration, u64)>>>,
+    /// What the test clock counts from.
+    start: Instant,
+    /// What the event loop was told to do until the next event.
+    control_flow: ControlFlow,
+    redraw_requested: bool,
+    /// When the last frame ran, on the test clock.
+    last_frame: Option<Instant>,
+    /// Set once the scheduler exited. Like with winit, the rest of the
+    /// event loop's iteration is still delivered.
+    pub exit: bool,
+    /// egui's pass count when the scheduler exited.
+    exit_pass_nr: Option<u64>,
+    /// Set at the end of the iteration that exited, once the renderer
+    /// finished like in the app's shutdown. Nothing runs after that.
+    finished: bool,
+}
+
+impl<T: UserApp> TestApp<T> {
+    /// In an 800×600 window, or `None` on machines without a software
+    /// adapter when [`SKIP_GPU_TESTS`] is set.
+    pub fn new(app: T) -> Option<Self> {
+        Self::with_options(
+            app,
+            Options {
+                window_size: egui::vec2(800., 600.),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// With the window size and the renderer's settings from `options`.
+    pub fn with_options(app: T, options: Options) -> Option<Self> {
+        let (instance, adapter, device, queue) =
+            software_adapter(wgpu::Features::empty(), wgpu::Limits::default())?;
+        let window = HeadlessWindow::new(PhysicalSize::new(
+            options.window_size.x as u32,
+            options.window_size.y as u32,
+        ));
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: FORMAT,
+            width: window.inner_size().width,
+            height: window.inner_size().height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: Vec::new(),
+        };
+        let mut surface = RenderSurface::Offscreen(None);
+        surface
+            .configure(&device, &surface_config)
+            .expect("offscreen surfaces always configure");
+        let gpu = GpuContext {
+            instance: Arc::new(instance),
+            device,
+            queue,
+            surface,
+            surface_config,
+            timestamp_queries: false,
+            adapter_info: adapter.get_info(),
+            downlevel_fallback: false,
+        };
+
+        let egui_ctx = EguiRenderer::create_egui_context(&options.egui);
+        // For finding widgets, see `Frame::widgets`.
+        egui_ctx.enable_accesskit();
+        let repaints = Arc::new(Mutex::new(Vec::new()));
+        {
+            let repaints = repaints.clone();
+            egui_ctx.set_request_repaint_callback(move |info| {
+                repaints
+                    .lock()
+                    .unwrap()
+                    .push((info.delay, info.current_cumulative_pass_nr));
+            });
+        }
+        let renderer = Renderer::new(gpu, egui_ctx, &window, &options, app)
+            .expect("the renderer starts headless");
+        Some(Self {
+            renderer,
+            window,
+            scheduler: Scheduler::new(false),
+            repaints,
+            start: Instant::now(),
+            control_flow: ControlFlow::Wait,
+            redraw_requested: false,
+            last_frame: None,
+            exit: false,
+            exit_pass_nr: None,
+            finished: false,
+        })
+    }
+
+    pub fn app(&self) -> &T {
+        self.renderer.state()
+    }
+
+    pub fn app_mut(&mut self) -> &mut T {
+        self.renderer.state_mut()
+    }
+
+    pub fn window(&self) -> &HeadlessWindow {
+        &self.window
+    }
+
+    pub fn ctx(&self) -> &egui::Context {
+        self.renderer.egui_ctx()
+    }
+
+    /// Now on the test clock.
+    pub fn now(&self) -> Instant {
+        self.start + self.window.time.get()
+    }
+
+    /// Moves the clock the next frames see.
+    pub fn advance(&mut self, by: Duration) {
+        self.window.time.set(self.window.time.get() + by);
+    }
+
+    /// Delivers `event` in an iteration of its own, see
+    /// [`Self::window_events`].
+    pub fn window_event(&mut self, event: WindowEvent) {
+        self.window_events([event]);
+    }
+
+    /// Delivers `events` like one iteration of the event loop: each goes
+    /// through the renderer and then the scheduler, and redraws paint.
+    pub fn window_events(&mut self, events: impl IntoIterator<Item = WindowEvent>) {
+        assert!(!self.finished, "the app exited");
+        for event in events {
+            let result = match event {
+                WindowEvent::RedrawRequested => {
+                    self.last_frame = Some(self.now());
+                    self.renderer.run_ui_and_paint(&self.window)
+                }
+                event => Ok(self.renderer.on_window_event(&event, &self.window)),
+            };
+            self.handle(result);
+        }
+        if self.exit && !self.finished {
+            self.finished = true;
+            self.renderer.finish().expect("the renderer finishes");
+        }
+    }
+
+    /// Like the event loop waking up: redraws if one was asked for or a
+    /// repaint is due on the test clock.
+    pub fn poll(&mut self) {
+        self.check_redraw_requests();
+        if std::mem::take(&mut self.redraw_requested) {
+            self.window_event(WindowEvent::RedrawRequested);
+        }
+    }
+
+    /// When the event loop wakes up next to repaint, `None` for never.
+    pub fn next_wake(&self) -> Option<Instant> {
+        match self.control_flow {
+            _ if self.redraw_requested => Some(self.now()),
+            ControlFlow::WaitUntil(deadline) => Some(deadline),
+            ControlFlow::Wait | ControlFlow::Poll => None,
+        }
+    }
+
+    pub fn last_frame(&self) -> Option<Instant> {
+        self.last_frame
+    }
+
+    /// How many frames ran after the scheduler exited; never any.
+    pub fn frames_after_exit(&self) -> u64 {
+        self.exit_pass_nr.map_or(0, |exit_pass_nr| {
+            self.ctx().cumulative_pass_nr_for(ViewportId::ROOT) - exit_pass_nr
+        })
+    }
+
+    /// Resizes the window. Zero sizes are what a minimized window reports.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        let event = self.resize_event(size);
+        self.window_event(event);
+    }
+
+    /// Resizes the window without telling yet, for [`Self::window_events`].
+    pub fn resize_event(&mut self, [width, height]: [u32; 2]) -> WindowEvent {
+        let size = PhysicalSize::new(width, height);
+        self.window.set_size(size);
+        WindowEvent::Resized(size)
+    }
+
+    /// The size the surface was last configured with.
+    pub fn surface_size(&self) -> [u32; 2] {
+        let surface_config = self.renderer.surface_config();
+        [surface_config.width, surface_config.height]
+    }
+
+    /// The size of the texture frames are presented to.
+    pub fn surface_texture_size(&self) -> Option<[u32; 2]> {
+        let texture = self.renderer.offscreen_texture()?;
+        Some([texture.width(), texture.height()])
+    }
+
+    /// The device's texture limit.
+    pub fn max_surface_side(&self) -> u32 {
+        self.renderer.device().limits().max_texture_dimension_2d
+    }
+
+    /// Asks to close the window, like its close button.
+    pub fn request_close(&mut self) {
+        self.window_event(WindowEvent::CloseRequested);
+    }
+
+    /// Holds `modifiers` from now on, like pressing them.
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.window_event(WindowEvent::ModifiersChanged(modifiers.into()));
+    }
+
+    /// Moves the pointer to `pos` in points.
+    pub fn move_pointer(&mut self, pos: Pos2) {
+        self.window_event(self.cursor_moved(pos));
+    }
+
+    pub fn mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        self.window_event(Self::mouse_input(button, state));
+    }
+
+    /// The event for the pointer at `pos` in points.
+    pub fn cursor_moved(&self, pos: Pos2) -> WindowEvent {
+        let pixels_per_point = host_window::pixels_per_point(self.ctx(), &self.window);
+        WindowEvent::CursorMoved {
+            device_id: DeviceId::dummy(),
+            position: PhysicalPosition::new(
+                f64::from(pos.x * pixels_per_point),
+                f64::from(pos.y * pixels_per_point),
+            ),
+        }
+    }
+
+    pub fn mouse_input(button: MouseButton, state: ElementState) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button,
+        }
+    }
+
+    /// Runs one frame and paints it, like a redraw the window asked for.
+    /// `events` are for input winit's events cannot be made up for, such
+    /// as keys; they go straight to egui.
+    pub fn step(&mut self, events: Vec<Event>) -> Frame {
+        self.renderer
+            .egui_renderer()
+            .egui_winit
+            .egui_input_mut()
+            .events
+            .extend(events);
+        self.window_event(WindowEvent::RedrawRequested);
+
+        let output = self.renderer.egui_renderer().last_output.clone();
+        let repaint_delay = output
+            .viewport_output
+            .get(&ViewportId::ROOT)
+            .map_or(Duration::MAX, |viewport| viewport.repaint_delay);
+        let pixels = self.renderer.offscreen_texture().map(|texture| {
+            let device = self.renderer.device();
+            let encoder = device.create_command_encoder(&Default::default());
+            read_texture(device, self.renderer.queue(), encoder, texture)
+        });
+        Frame {
+            output,
+            pixels,
+            repaint_delay,
+        }
+    }
+
+    /// What the mirror showed in the last frame, while it is open.
+    pub fn mirror_pixels(&self) -> Option<([u32; 2], Vec<u8>)> {
+        let texture = self.renderer.mirror_texture()?;
+        let device = self.renderer.device();
+        let encoder = device.create_command_encoder(&Default::default());
+        Some(read_texture(
+            device,
+            self.renderer.queue(),
+            encoder,
+            texture,
+        ))
+    }
+
+    /// Steps a second later, once animations like opening a window ended.
+    pub fn settle(&mut self) -> Frame {
+        self.advance(Duration::from_secs(1));
+        self.step(Vec::new())
+    }
+
+    /// Presses and releases the primary button at `pos`, one frame each.
+    pub fn click(&mut self, pos: Pos2) -> Frame {
+        self.move_pointer(pos);
+        self.mouse_button(MouseButton::Left, ElementState::Pressed);
+        self.step(Vec::new());
+        self.mouse_button(MouseButton::Left, ElementState::Released);
+        self.step(Vec::new())
+    }
+
+    /// Clicks `widget` the way a screen reader does, which needs no pointer
+    /// or the widget on screen.
+    pub fn click_widget(&mut self, widget: &Widget) -> Frame {
+        self.step(vec![action_request(widget, accesskit::Action::Click)]);
+        self.step(Vec::new())
+    }
+
+    /// Focuses the text edit `widget` and types `text` into it.
+    pub fn type_into(&mut self, widget: &Widget, text: &str) -> Frame {
+        self.step(vec![action_request(widget, accesskit::Action::Focus)]);
+        self.step(vec![Event::Text(text.to_owned())]);
+        self.step(Vec::new())
+    }
+
+    /// Like `App::handle_event_result`, on the test clock, followed by the
+    /// repaint requests egui made meanwhile.
+    fn handle(&mut self, mut event_result: Result<EventResult, AppError>) {
+        let mut painted = false;
+        loop {
+            let result = match event_result {
+                Ok(result) => result,
+                Err(err) if err.is_recoverable() => {
+                    self.renderer.recover(&err);
+                    self.scheduler.request(self.now());
+                    break;
+                }
+                Err(err) => panic!("{err:?}"),
+            };
+            let now = self.now();
+            let renderer = &mut self.renderer;
+            let action = self
+                .scheduler
+                .on_result(result, now, painted, |when, snap| {
+                    let when = renderer.paced(when);
+                    if snap {
+                        renderer.snap_to_vsync(when)
+                    } else {
+                        when
+                    }
+                });
+            match action {
+                Action::None => break,
+                Action::Exit => {
+                    if !std::mem::replace(&mut self.exit, true) {
+                        self.exit_pass_nr =
+                            Some(self.ctx().cumulative_pass_nr_for(ViewportId::ROOT));
+                    }
+                    break;
+                }
+                Action::PaintNow => {
+                    painted = true;
+                    self.last_frame = Some(now);
+                    event_result = self.renderer.run_ui_and_paint(&self.window);
+                }
+            }
+        }
+        if !self.exit {
+            self.update_mirror();
+        }
+        self.check_redraw_requests();
+
+        if self.exit {
+            return;
+        }
+        let repaints = std::mem::take(&mut *self.repaints.lock().unwrap());
+        for (delay, cumulative_pass_nr) in repaints {
+            let result = self.renderer.on_user_event(UserEvent::RequestRepaint {
+                when: self.now() + delay,
+                cumulative_pass_nr,
+            });
+            self.handle(Ok(result));
+        }
+    }
+
+    /// Like `App::update_mirror` with a single monitor: the mirror has the
+    /// window's size.
+    fn update_mirror(&mut self) {
+        match self.renderer.take_mirror_request() {
+            Some(true) => {
+                let size = self.window.inner_size();
+                self.renderer
+                    .open_offscreen_mirror([size.width, size.height]);
+            }
+            Some(false) => self.renderer.close_mirror(),
+            None => {}
+        }
+    }
+
+    fn check_redraw_requests(&mut self) {
+        let poll = self.scheduler.poll(self.now());
+        self.control_flow = poll.control_flow;
+        self.redraw_requested |= poll.redraw;
+    }
+}
+
+fn action_request(widget: &Widget, action: accesskit::Action) -> Event {
+    Event::AccessKitActionRequest(ActionRequest {
+        action,
+        target: widget.id,
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::{close::CloseDecision, user_app::SimpleApp};
+
+    struct Refusing;
+
+    impl UserApp for Refusing {
+        fn update(&mut self, _ctx: &egui::Context) {}
+
+        fn on_close_requested(&mut self, _default: CloseDecision) -> CloseDecision {
+            CloseDecision::Cancel
+        }
+    }
+
+    #[test]
+    fn close_request_sets_exit() {
+        let Some(mut test_app) = TestApp::new(SimpleApp(|_: &egui::Context| {})) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        assert!(!test_app.exit);
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(test_app.exit);
+    }
+
+    #[test]
+    fn canceled_close_keeps_running() {
+        let Some(mut test_app) = TestApp::new(Refusing) else {
+            return;
+        };
+        test_app.request_close();
+        test_app.step(Vec::new());
+        assert!(!test_app.exit);
+    }
+
+    #[test]
+    fn resize_reconfigures_surface() {
+        let Some(mut test_app) = TestApp::new(SimpleApp(|ctx: &egui::Context| {
+            egui::CentralPanel::default().show(ctx, |_| {});
+        })) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        assert_eq!(test_app.surface_texture_size(), Some([800, 600]));
+
+        // The surface follows with the next frame.
+        test_app.resize([320, 200]);
+        assert_eq!(test_app.surface_size(), [800, 600]);
+        let frame = test_app.step(Vec::new());
+        assert_eq!(test_app.surface_size(), [320, 200]);
+        assert_eq!(test_app.surface_texture_size(), Some([320, 200]));
+        assert_eq!(frame.pixels.as_ref().unwrap().0, [320, 200]);
+        assert!(!frame.output.shapes.is_empty());
+
+        // A minimized window keeps its surface.
+        test_app.resize([0, 0]);
+        test_app.step(Vec::new());
+        assert_eq!(test_app.surface_texture_size(), Some([320, 200]));
+
+        // Beyond the device's limit the surface keeps the aspect ratio.
+        test_app.resize([100_000, 25_000]);
+        let frame = test_app.step(Vec::new());
+        let [width, height] = test_app.surface_size();
+        assert!(width <= test_app.max_surface_side() && width == 4 * height);
+        assert_eq!(test_app.surface_texture_size(), Some([width, height]));
+        assert_eq!(frame.pixels.unwrap().0, [width, height]);
+    }
+
+    #[test]
+    fn panel_fill_is_painted() {
+        let Some(mut test_app) = TestApp::new(SimpleApp(|ctx: &egui::Context| {
+            egui::CentralPanel::default().show(ctx, |_| {});
+        })) else {
+            return;
+        };
+        let (_, pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        let fill = test_app.ctx().style().visuals.panel_fill;
+        assert_eq!(pixels[..4], fill.to_array());
+    }
+
+    /// A white square from 10 to 30 points on black.
+    fn square(ctx: &egui::Context) {
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+            .show(ctx, |ui| {
+                let rect = Rect::from_min_size(egui::pos2(10., 10.), egui::vec2(20., 20.));
+                ui.painter().rect_filled(rect, 0., egui::Color32::WHITE);
+            });
+    }
+
+    #[test]
+    fn zoom_factor_scales_what_is_painted() {
+        let Some(mut test_app) = TestApp::new(SimpleApp(square)) else {
+            return;
+        };
+        test_app.ctx().set_zoom_factor(1.5);
+        // Frames after the zoom must not reset it.
+        test_app.step(Vec::new());
+        test_app.step(Vec::new());
+        let frame = test_app.step(Vec::new());
+        assert_eq!(test_app.ctx().pixels_per_point(), 1.5);
+        assert_eq!(frame.output.pixels_per_point, 1.5);
+        let ([width, _], pixels) = frame.pixels.unwrap();
+        let red = |x: u32, y: u32| pixels[4 * (y * width + x) as usize];
+        // 10 to 30 points are 15 to 45 pixels.
+        assert_eq!([red(16, 16), red(44, 44)], [255, 255]);
+        assert_eq!([red(14, 30), red(46, 30), red(30, 46)], [0, 0, 0]);
+    }
+
+    /// How many pixels are neither black nor white.
+    fn blended_pixels(feathering: bool) -> Option<usize> {
+        let mut options = Options {
+            window_size: egui::vec2(100., 100.),
+            ..Default::default()
+        };
+        options.egui.tessellation.feathering = feathering;
+        let mut test_app = TestApp::with_options(
+            SimpleApp(|ctx: &egui::Context| {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none().fill(egui::Color32::BLACK))
+                    .show(ctx, |ui| {
+                        ui.painter()
+                            .circle_filled(egui::pos2(50., 50.), 30., egui::Color32::WHITE);
+                    });
+            }),
+            options,
+        )?;
+        let (_, pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        Some(
+            pixels
+                .chunks(4)
+                .filter(|pixel| pixel[0] != 0 && pixel[0] != 255)
+                .count(),
+        )
+    }
+
+    #[test]
+    fn feathering_option_reaches_the_tessellator() {
+        let (Some(feathered), Some(hard)) = (blended_pixels(true), blended_pixels(false)) else {
+            return;
+        };
+        assert!(feathered > 0);
+        assert_eq!(hard, 0);
+    }
+
+    #[test]
+    fn frames_see_the_test_clock() {
+        let time = Rc::new(Cell::new(-1.));
+        let seen = time.clone();
+        let Some(mut test_app) = TestApp::new(SimpleApp(move |ctx: &egui::Context| {
+            seen.set(ctx.input(|i| i.time));
+            ctx.request_repaint_after(Duration::from_secs(1));
+        })) else {
+            return;
+        };
+        test_app.step(Vec::new());
+        assert_eq!(time.get(), 0.);
+        // The window shows after the first present, which is then repeated.
+        let frame = test_app.step(Vec::new());
+        assert_eq!(frame.repaint_delay, Duration::ZERO);
+        test_app.advance(Duration::from_millis(400));
+        let frame = test_app.step(Vec::new());
+        assert_eq!(time.get(), 0.4);
+        // Less the frame egui expects painting to take.
+        assert!(
+            (Duration::from_millis(900)..=Duration::from_secs(1)).contains(&frame.repaint_delay)
+        );
+    }
+}
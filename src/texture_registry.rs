@@ -0,0 +1,179 @@
+use web_time::{Duration, Instant};
+
+use egui::{ahash::HashMap, epaint::textures::TexturesDelta, TextureId};
+
+const BYTES_PER_TEXEL: u64 = 4;
+
+pub struct TextureRecord {
+    pub id: TextureId,
+    pub label: String,
+    pub size: [usize; 2],
+    pub bytes: u64,
+    pub created: Instant,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TextureSort {
+    Size,
+    Age,
+}
+
+/// Live egui and native textures, audited for leaks.
+pub struct TextureRegistry {
+    textures: HashMap<TextureId, TextureRecord>,
+    pub max_live: Option<usize>,
+    pub growth_window: Option<Duration>,
+    growth_since: (Instant, usize),
+    last_live: usize,
+    over_cap: bool,
+}
+
+impl Default for TextureRegistry {
+    fn default() -> Self {
+        Self {
+            textures: Default::default(),
+            max_live: None,
+            growth_window: None,
+            growth_since: (Instant::now(), 0),
+            last_live: 0,
+            over_cap: false,
+        }
+    }
+}
+
+impl TextureRegistry {
+    pub fn record_set(&mut self, textures_delta: &TexturesDelta, ctx: &egui::Context) {
+        if textures_delta.set.is_empty() {
+            return;
+        }
+        let tex_manager = ctx.tex_manager();
+        let tex_manager = tex_manager.read();
+        for (id, image_delta) in &textures_delta.set {
+            if image_delta.pos.is_some() {
+                continue;
+            }
+            let size = image_delta.image.size();
+            let label = tex_manager
+                .meta(*id)
+                .map(|meta| meta.name.clone())
+                .unwrap_or_default();
+            self.insert(
+                *id,
+                label,
+                size,
+                size[0] as u64 * size[1] as u64 * BYTES_PER_TEXEL,
+            );
+        }
+    }
+
+    pub fn record_native(&mut self, id: TextureId, texture: &wgpu::Texture, bytes: u64) {
+        let mut label = format!("native {:?}", texture.format());
+        if texture.format().is_compressed() {
+            let uncompressed = texture_rgba8_bytes(texture);
+            label += &format!(
+                ", {:.1}x smaller than RGBA8",
+                uncompressed as f64 / bytes as f64
+            );
+        }
+        let size = [texture.width() as usize, texture.height() as usize];
+        self.insert(id, label, size, bytes);
+    }
+
+    fn insert(&mut self, id: TextureId, label: String, size: [usize; 2], bytes: u64) {
+        let created = self
+            .textures
+            .get(&id)
+            .map_or_else(Instant::now, |record| record.created);
+        self.textures.insert(
+            id,
+            TextureRecord {
+                id,
+                label,
+                size,
+                bytes,
+                created,
+            },
+        );
+        self.check_growth();
+    }
+
+    pub fn record_freed(&mut self, ids: &[TextureId]) {
+        for id in ids {
+            self.textures.remove(id);
+        }
+        self.check_growth();
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    pub fn sorted(&self, sort: TextureSort) -> Vec<&TextureRecord> {
+        let mut records: Vec<_> = self.textures.values().collect();
+        match sort {
+            TextureSort::Size => records.sort_by_key(|record| std::cmp::Reverse(record.bytes)),
+            TextureSort::Age => records.sort_by_key(|record| record.created),
+        }
+        records
+    }
+
+    fn check_growth(&mut self) {
+        let live = self.textures.len();
+        let now = Instant::now();
+
+        if let Some(max_live) = self.max_live {
+            let over_cap = live > max_live;
+            if over_cap && !self.over_cap {
+                log::warn!("{live} live textures exceed the cap of {max_live}");
+            }
+            self.over_cap = over_cap;
+        }
+
+        if live < self.last_live {
+            self.growth_since = (now, live);
+        } else if let Some(window) = self.growth_window {
+            let (since, baseline) = self.growth_since;
+            if live > baseline && now.duration_since(since) >= window {
+                log::warn!(
+                    "live textures grew from {baseline} to {live} over {:?} without any being freed",
+                    now.duration_since(since)
+                );
+                self.growth_since = (now, live);
+            }
+        }
+        self.last_live = live;
+    }
+
+    /// Logs textures that were never freed, except the font atlas egui manages itself.
+    pub fn log_live(&self) {
+        let font_texture = TextureId::default();
+        for record in self.sorted(TextureSort::Age) {
+            if record.id == font_texture {
+                continue;
+            }
+            log::warn!(
+                "texture {:?} {:?} ({}x{}, {} bytes) was never freed, alive for {:?}",
+                record.id,
+                record.label,
+                record.size[0],
+                record.size[1],
+                record.bytes,
+                record.created.elapsed()
+            );
+        }
+    }
+}
+
+/// What `texture` would take with the same size and mips as RGBA8.
+fn texture_rgba8_bytes(texture: &wgpu::Texture) -> u64 {
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let size = texture.size().mip_level_size(level, texture.dimension());
+            size.width as u64 * size.height as u64 * BYTES_PER_TEXEL
+        })
+        .sum()
+}
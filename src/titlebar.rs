@@ -0,0 +1,400 @@
+use egui_winit::winit::window::Window;
+
+/// Parts of a titlebar drawn by egui that the OS needs to know about, in
+/// points.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct TitlebarRegions {
+    pub maximize_button: Option<egui::Rect>,
+    pub drag_region: Option<egui::Rect>,
+}
+
+/// Windows 11 system backdrop drawn behind the window's client area. Only
+/// visible where egui leaves the background translucent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backdrop {
+    None,
+    Mica,
+    Acrylic,
+}
+
+fn regions_id() -> egui::Id {
+    egui::Id::new("titlebar_regions")
+}
+
+fn window_menu_id() -> egui::Id {
+    egui::Id::new("titlebar_window_menu")
+}
+
+pub const DEFAULT_HEIGHT: f32 = 32.;
+
+/// A titlebar drawn by egui for undecorated windows: the window title,
+/// minimize, maximize/restore and close buttons, a region that moves or
+/// maximizes the window, and the system menu on a right click or long
+/// press. Without `buttons` it only leaves room for the OS's own buttons.
+/// Call it first thing in the update callback.
+pub fn show(ctx: &egui::Context, height: f32, buttons: bool) {
+    egui::TopBottomPanel::top("titlebar")
+        .exact_height(height)
+        .show_separator_line(false)
+        .show(ctx, |ui| {
+            let (maximized, title) = ctx.input(|i| {
+                let viewport = i.viewport();
+                (
+                    viewport.maximized.unwrap_or(false),
+                    viewport.title.clone().unwrap_or_default(),
+                )
+            });
+            let mut maximize_button = None;
+            let mut buttons_left = None;
+            if buttons {
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("🗙").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                    let maximize = ui.button(if maximized { "🗗" } else { "🗖" });
+                    if maximize.clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(!maximized));
+                    }
+                    maximize_button = Some(maximize.rect);
+                    let minimize = ui.button("🗕");
+                    if minimize.clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+                    }
+                    buttons_left = Some(minimize.rect.min.x);
+                });
+            }
+            let mut drag_region = ui.max_rect();
+            if let Some(buttons_left) = buttons_left {
+                drag_region.max.x = buttons_left;
+            }
+            if buttons && !title.is_empty() {
+                ui.painter().text(
+                    drag_region.center(),
+                    egui::Align2::CENTER_CENTER,
+                    title,
+                    egui::TextStyle::Body.resolve(ui.style()),
+                    ui.visuals().text_color(),
+                );
+            }
+            let response = ui.interact(
+                drag_region,
+                egui::Id::new("titlebar"),
+                egui::Sense::click_and_drag(),
+            );
+            if response.double_clicked() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(!maximized));
+            } else if response.drag_started_by(egui::PointerButton::Primary) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+            } else if response.secondary_clicked() {
+                if let Some(pos) = response.interact_pointer_pos() {
+                    ctx.data_mut(|d| d.insert_temp(window_menu_id(), Some(pos)));
+                }
+            }
+            set_regions(
+                ctx,
+                TitlebarRegions {
+                    maximize_button,
+                    drag_region: Some(drag_region),
+                },
+            );
+        });
+}
+
+/// Opens the system menu that [`show`] asked for, which no viewport command
+/// covers. A no-op where winit does not support it.
+pub fn show_window_menu(ctx: &egui::Context, window: &Window) {
+    let pos = ctx
+        .data_mut(|d| d.remove_temp::<Option<egui::Pos2>>(window_menu_id()))
+        .flatten();
+    if let Some(pos) = pos {
+        let pixels_per_point = egui_winit::pixels_per_point(ctx, window);
+        window.show_window_menu(egui_winit::winit::dpi::PhysicalPosition::new(
+            f64::from(pos.x * pixels_per_point),
+            f64::from(pos.y * pixels_per_point),
+        ));
+    }
+}
+
+/// Registers this frame's titlebar regions. Call it from the update callback
+/// every frame the titlebar is shown.
+pub fn set_regions(ctx: &egui::Context, regions: TitlebarRegions) {
+    ctx.data_mut(|d| d.insert_temp(regions_id(), regions));
+}
+
+/// Matches the titlebar drawn by Windows to egui's theme. A no-op on other
+/// platforms and on Windows versions without dark titlebars.
+pub fn set_dark_titlebar(window: &Window, dark: bool) {
+    #[cfg(windows)]
+    windows::set_dark_titlebar(window, dark);
+    #[cfg(not(windows))]
+    let _ = (window, dark);
+}
+
+/// A no-op on other platforms and before Windows 11 22H2.
+pub fn set_backdrop(window: &Window, backdrop: Backdrop) {
+    #[cfg(windows)]
+    windows::set_backdrop(window, backdrop);
+    #[cfg(not(windows))]
+    let _ = (window, backdrop);
+}
+
+/// Windows 11 rounds the corners of decorated windows only unless asked. A
+/// no-op on other platforms and before Windows 11.
+pub fn set_rounded_corners(window: &Window, rounded: bool) {
+    #[cfg(windows)]
+    windows::set_rounded_corners(window, rounded);
+    #[cfg(not(windows))]
+    let _ = (window, rounded);
+}
+
+/// Tells Windows where the maximize button and the drag region of a custom
+/// titlebar are, so that hovering the button shows the Windows 11 snap
+/// layouts and dragging moves the window natively. A no-op elsewhere.
+pub struct TitlebarHitTest {
+    #[cfg(windows)]
+    subclass: Option<windows::Subclass>,
+}
+
+impl TitlebarHitTest {
+    pub fn new(window: &Window) -> Self {
+        #[cfg(windows)]
+        {
+            let subclass = windows::Subclass::install(window);
+            if subclass.is_none() {
+                log::warn!("failed to subclass the window for titlebar hit testing");
+            }
+            Self { subclass }
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = window;
+            Self {}
+        }
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context, window: &Window) {
+        let regions = ctx
+            .data(|d| d.get_temp::<TitlebarRegions>(regions_id()))
+            .unwrap_or_default();
+        #[cfg(windows)]
+        if let Some(subclass) = &self.subclass {
+            let pixels_per_point = egui_winit::pixels_per_point(ctx, window);
+            subclass.set_regions(
+                regions
+                    .maximize_button
+                    .map(|rect| windows::PixelRect::new(rect, pixels_per_point)),
+                regions
+                    .drag_region
+                    .map(|rect| windows::PixelRect::new(rect, pixels_per_point)),
+            );
+        }
+        #[cfg(not(windows))]
+        let _ = (regions, window);
+    }
+}
+
+#[cfg(windows)]
+pub(crate) use windows::hwnd;
+
+#[cfg(windows)]
+mod windows {
+    use std::sync::{Arc, Mutex};
+
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+    use windows_sys::Win32::{
+        Foundation::{BOOL, HWND, LPARAM, LRESULT, POINT, WPARAM},
+        Graphics::{
+            Dwm::{
+                DwmSetWindowAttribute, DWMSBT_MAINWINDOW, DWMSBT_NONE, DWMSBT_TRANSIENTWINDOW,
+                DWMWA_SYSTEMBACKDROP_TYPE, DWMWA_USE_IMMERSIVE_DARK_MODE,
+                DWMWA_WINDOW_CORNER_PREFERENCE, DWMWCP_DONOTROUND, DWMWCP_ROUND,
+            },
+            Gdi::ScreenToClient,
+        },
+        UI::{
+            Shell::{DefSubclassProc, RemoveWindowSubclass, SetWindowSubclass},
+            WindowsAndMessaging::{
+                IsZoomed, ShowWindow, HTCAPTION, HTMAXBUTTON, SW_MAXIMIZE, SW_RESTORE,
+                WM_NCHITTEST, WM_NCLBUTTONDOWN, WM_NCLBUTTONUP,
+            },
+        },
+    };
+
+    use super::Backdrop;
+
+    const SUBCLASS_ID: usize = 1;
+
+    pub(crate) fn hwnd(window: &Window) -> Option<HWND> {
+        match window.window_handle().ok()?.as_raw() {
+            RawWindowHandle::Win32(handle) => Some(handle.hwnd.get() as HWND),
+            _ => None,
+        }
+    }
+
+    /// Failures are ignored: older Windows versions reject attributes they
+    /// do not know.
+    fn set_attribute<T>(window: &Window, attribute: u32, value: T) {
+        let Some(hwnd) = hwnd(window) else {
+            return;
+        };
+        // SAFETY: `value` lives for the duration of the call and its size
+        // is passed along.
+        unsafe {
+            DwmSetWindowAttribute(
+                hwnd,
+                attribute as _,
+                (&value as *const T).cast(),
+                std::mem::size_of::<T>() as u32,
+            );
+        }
+    }
+
+    pub fn set_dark_titlebar(window: &Window, dark: bool) {
+        set_attribute(window, DWMWA_USE_IMMERSIVE_DARK_MODE as u32, dark as BOOL);
+    }
+
+    pub fn set_backdrop(window: &Window, backdrop: Backdrop) {
+        let backdrop = match backdrop {
+            Backdrop::None => DWMSBT_NONE,
+            Backdrop::Mica => DWMSBT_MAINWINDOW,
+            Backdrop::Acrylic => DWMSBT_TRANSIENTWINDOW,
+        };
+        set_attribute(window, DWMWA_SYSTEMBACKDROP_TYPE as u32, backdrop);
+    }
+
+    pub fn set_rounded_corners(window: &Window, rounded: bool) {
+        let preference = if rounded {
+            DWMWCP_ROUND
+        } else {
+            DWMWCP_DONOTROUND
+        };
+        set_attribute(window, DWMWA_WINDOW_CORNER_PREFERENCE as u32, preference);
+    }
+
+    /// A rectangle in physical client coordinates.
+    #[derive(Clone, Copy)]
+    pub struct PixelRect {
+        min: [i32; 2],
+        max: [i32; 2],
+    }
+
+    impl PixelRect {
+        pub fn new(rect: egui::Rect, pixels_per_point: f32) -> Self {
+            Self {
+                min: [
+                    (rect.min.x * pixels_per_point).floor() as i32,
+                    (rect.min.y * pixels_per_point).floor() as i32,
+                ],
+                max: [
+                    (rect.max.x * pixels_per_point).ceil() as i32,
+                    (rect.max.y * pixels_per_point).ceil() as i32,
+                ],
+            }
+        }
+
+        fn contains(&self, point: POINT) -> bool {
+            (self.min[0]..self.max[0]).contains(&point.x)
+                && (self.min[1]..self.max[1]).contains(&point.y)
+        }
+    }
+
+    #[derive(Default)]
+    struct Regions {
+        maximize_button: Option<PixelRect>,
+        drag_region: Option<PixelRect>,
+    }
+
+    pub struct Subclass {
+        hwnd: HWND,
+        regions: *const Mutex<Regions>,
+    }
+
+    impl Subclass {
+        pub fn install(window: &Window) -> Option<Self> {
+            let hwnd = hwnd(window)?;
+            let regions = Arc::into_raw(Arc::new(Mutex::new(Regions::default())));
+            // SAFETY: `hwnd` belongs to a live window created on this thread,
+            // and `regions` stays valid until the subclass is removed in `drop`.
+            let installed = unsafe {
+                SetWindowSubclass(hwnd, Some(subclass_proc), SUBCLASS_ID, regions as usize)
+            };
+            if installed == 0 {
+                // SAFETY: the pointer came from `Arc::into_raw` above and was
+                // not handed to the window.
+                drop(unsafe { Arc::from_raw(regions) });
+                return None;
+            }
+            Some(Self { hwnd, regions })
+        }
+
+        pub fn set_regions(
+            &self,
+            maximize_button: Option<PixelRect>,
+            drag_region: Option<PixelRect>,
+        ) {
+            // SAFETY: valid until `drop`.
+            let regions = unsafe { &*self.regions };
+            if let Ok(mut regions) = regions.lock() {
+                regions.maximize_button = maximize_button;
+                regions.drag_region = drag_region;
+            }
+        }
+    }
+
+    impl Drop for Subclass {
+        fn drop(&mut self) {
+            // SAFETY: after removing the subclass the window no longer
+            // references `regions`.
+            unsafe {
+                RemoveWindowSubclass(self.hwnd, Some(subclass_proc), SUBCLASS_ID);
+                drop(Arc::from_raw(self.regions));
+            }
+        }
+    }
+
+    unsafe extern "system" fn subclass_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        ref_data: usize,
+    ) -> LRESULT {
+        let regions = &*(ref_data as *const Mutex<Regions>);
+        match message {
+            WM_NCHITTEST => {
+                let mut point = POINT {
+                    x: (lparam & 0xffff) as i16 as i32,
+                    y: ((lparam >> 16) & 0xffff) as i16 as i32,
+                };
+                if ScreenToClient(hwnd, &mut point) != 0 {
+                    if let Ok(regions) = regions.lock() {
+                        if regions.maximize_button.is_some_and(|r| r.contains(point)) {
+                            return HTMAXBUTTON as LRESULT;
+                        }
+                        if regions.drag_region.is_some_and(|r| r.contains(point)) {
+                            return HTCAPTION as LRESULT;
+                        }
+                    }
+                }
+            }
+            // The button is non-client area now, so egui never sees the
+            // click; act on it here like the egui button would.
+            WM_NCLBUTTONDOWN if wparam == HTMAXBUTTON as WPARAM => return 0,
+            WM_NCLBUTTONUP if wparam == HTMAXBUTTON as WPARAM => {
+                let command = if IsZoomed(hwnd) != 0 {
+                    SW_RESTORE
+                } else {
+                    SW_MAXIMIZE
+                };
+                ShowWindow(hwnd, command);
+                return 0;
+            }
+            _ => {}
+        }
+        DefSubclassProc(hwnd, message, wparam, lparam)
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use egui::TextureId;
+use egui_winit::winit::{
+    event::{DeviceEvent, DeviceId},
+    window::Window,
+};
+
+use crate::{
+    adaptive_quality::QualityChange, clipboard::ClipboardContent, close::CloseDecision,
+    command_palette::Command, egui_renderer::EguiRenderer, event::EventResult,
+    frame_stats::FrameMetrics, input_middleware::InputMiddleware, monitors::Monitors,
+    open_url::UrlDecision,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+impl Orientation {
+    pub fn from_size(width: u32, height: u32) -> Self {
+        if height > width {
+            Self::Portrait
+        } else {
+            Self::Landscape
+        }
+    }
+}
+
+/// egui's renderer and the GPU, for textures of the app's own that egui
+/// shows, e.g. a 3D viewport. See [`UserApp::on_gpu_textures`].
+pub struct GpuTextures<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub(crate) egui_renderer: &'a mut EguiRenderer,
+    /// Freed by the renderer at exit.
+    pub(crate) registered: &'a mut Vec<TextureId>,
+}
+
+impl GpuTextures<'_> {
+    /// See [`EguiRenderer::register_native_texture`]. The renderer frees it
+    /// at exit.
+    pub fn register(&mut self, texture: &wgpu::Texture, filter: wgpu::FilterMode) -> TextureId {
+        let id = self
+            .egui_renderer
+            .register_native_texture(self.device, texture, filter);
+        self.registered.push(id);
+        id
+    }
+
+    /// See [`EguiRenderer::update_native_texture`].
+    pub fn update(&mut self, id: TextureId, texture: &wgpu::Texture, filter: wgpu::FilterMode) {
+        self.egui_renderer
+            .update_native_texture(self.device, id, texture, filter);
+    }
+}
+
+/// The application run by [`crate::app::App`]: its UI, and hooks for what
+/// happens to the window around it. Only [`Self::update`] is required; the
+/// hooks do nothing by default.
+pub trait UserApp: 'static {
+    /// Shows the UI, once per egui pass.
+    fn update(&mut self, ctx: &egui::Context);
+
+    /// The previous frame's metrics, before each [`Self::update`]. Apps can
+    /// adapt to them, e.g. lower a chart's resolution when frames get slow.
+    fn on_frame_metrics(&mut self, _metrics: &FrameMetrics) {}
+
+    /// Commands for the palette, registered once at startup. Their ids come
+    /// back through [`Self::on_command`].
+    fn commands(&self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    /// Input middleware to run after the built-in ones, taken once at
+    /// startup like [`Self::commands`]. Apps that call
+    /// [`crate::input_middleware::set_input_blocked`] return an
+    /// [`crate::input_middleware::InputBlocker`] here.
+    fn input_middleware(&mut self) -> Vec<Box<dyn InputMiddleware>> {
+        Vec::new()
+    }
+
+    /// Greys a command out and ignores its shortcut while false.
+    fn is_command_enabled(&self, _id: &str) -> bool {
+        true
+    }
+
+    /// Runs a command from [`Self::commands`], picked in the palette or
+    /// activated by its shortcut.
+    fn on_command(&mut self, _ctx: &egui::Context, id: &str) {
+        log::warn!("unknown command {id}");
+    }
+
+    /// Collected into every bug report, see
+    /// [`crate::bug_report::create_bug_report`].
+    fn bug_report_context(&self) -> String {
+        String::new()
+    }
+
+    /// Called for every URL egui asks to open, before anything is opened.
+    fn on_open_url(&mut self, _url: &egui::OpenUrl) -> UrlDecision {
+        UrlDecision::Allow
+    }
+
+    /// `--rich-paste` only: called with every format on the clipboard when
+    /// the user pastes. Returns whether the app took the paste; otherwise its
+    /// text goes to egui as usual.
+    fn on_paste(&mut self, _content: &ClipboardContent) -> bool {
+        false
+    }
+
+    /// Called for every raw device event after egui saw it, including mouse
+    /// motion at the mouse's polling rate, so keep it cheap. Raw keys ignore
+    /// egui's focus, and the window's focus depends on the platform: Windows
+    /// and X11 deliver them only while a window is focused, macOS delivers
+    /// them regardless, and Wayland delivers little besides mouse motion.
+    /// Check [`Window::has_focus`] to ignore input meant for other apps.
+    fn on_device_event(
+        &mut self,
+        _device_id: DeviceId,
+        _event: &DeviceEvent,
+    ) -> Option<EventResult> {
+        None
+    }
+
+    /// Kiosk mode only: nobody used the app for a while, so the next
+    /// visitor starts fresh.
+    fn on_inactivity_reset(&mut self) {}
+
+    /// Last call before shutdown. `forced` is set when the close skipped
+    /// the confirmation, so slow work like saving can be skipped.
+    fn on_exit(&mut self, _forced: bool) {}
+
+    /// `default` asks first while [`crate::document::set_document_state`]
+    /// says the document is edited.
+    fn on_close_requested(&mut self, default: CloseDecision) -> CloseDecision {
+        default
+    }
+
+    fn on_gpu_ready(&mut self, _adapter_info: &wgpu::AdapterInfo, _downlevel_fallback: bool) {}
+
+    /// Frames are painted on the CPU, e.g. because no GPU adapter worked,
+    /// see [`crate::painter::EguiPainter::is_software`].
+    fn on_software_rendering(&mut self) {}
+
+    /// Called when the OS is low on memory; drop anything that can be
+    /// recomputed.
+    fn on_memory_warning(&mut self) {}
+
+    /// Height in points that the window's titlebar covers at the top of the
+    /// content area.
+    fn on_titlebar_inset(&mut self, _inset: f32) {}
+
+    fn on_orientation_changed(&mut self, _orientation: Orientation) {}
+
+    fn on_monitors_changed(&mut self, _monitors: &Monitors) {}
+
+    /// A step down or back up the adaptive quality ladder, see
+    /// [`crate::adaptive_quality::set_settings`]. Apps can shed their own
+    /// load while `change.level` is above zero.
+    fn on_quality_changed(&mut self, _change: &QualityChange) {}
+
+    fn on_idle(&mut self, _idle: bool) {}
+
+    /// Called once egui's renderer can take the app's own textures. Neither
+    /// this nor [`Self::prepare_frame`] is called with `--render-thread`,
+    /// where egui's renderer lives on the render thread, or with an app's
+    /// own painter.
+    fn on_gpu_textures(&mut self, _gpu: &mut GpuTextures) {}
+
+    /// Called after each pass that is painted, before egui paints: renders
+    /// into the app's own textures, and recreates them at the size the UI
+    /// asked for with [`GpuTextures::update`]. Passes whose UI did not
+    /// change are not painted unless `--no-frame-skip`.
+    fn prepare_frame(
+        &mut self,
+        _ctx: &egui::Context,
+        _gpu: &mut GpuTextures,
+        _encoder: &mut wgpu::CommandEncoder,
+    ) {
+    }
+
+    /// Called once the window exists. Integrations like native dialogs can
+    /// keep it, or take its raw handles with
+    /// [`crate::window_handles::raw_handles`].
+    fn on_window_ready(&mut self, _window: &Arc<Window>) {}
+}
+
+/// A closure as the whole UI, see [`crate::app::App::run_simple`].
+pub struct SimpleApp<F>(pub F);
+
+impl<F: FnMut(&egui::Context) + 'static> UserApp for SimpleApp<F> {
+    fn update(&mut self, ctx: &egui::Context) {
+        (self.0)(ctx);
+    }
+}
@@ -0,0 +1,216 @@
+use egui_winit::winit::window::{Theme, Window};
+
+/// The color the OS fills the window with before the first frame is
+/// presented, and wherever a resize gets ahead of the frames: the window
+/// class's brush on Windows, the background pixel on X11 and the window's
+/// background color on macOS. For opaque windows only. A no-op on Wayland,
+/// where a window shows nothing until its first present.
+pub fn set_background_color(window: &Window, color: egui::Color32) {
+    #[cfg(windows)]
+    windows::set_background_color(window, color);
+    #[cfg(target_os = "macos")]
+    if let Err(err) = macos::set_background_color(window, color) {
+        log::warn!("cannot set the window's background color: {err}");
+    }
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    x11::set_background_color(window, color);
+    #[cfg(not(any(windows, all(unix, not(any(target_os = "ios", target_os = "android"))))))]
+    let _ = (window, color);
+}
+
+/// egui's panel fill in the theme the first frame will use, given the
+/// system's as the window reports it.
+pub fn initial_color(ctx: &egui::Context, system_theme: Option<Theme>) -> egui::Color32 {
+    let theme = match ctx.options(|o| o.theme_preference) {
+        egui::ThemePreference::Dark => egui::Theme::Dark,
+        egui::ThemePreference::Light => egui::Theme::Light,
+        egui::ThemePreference::System => match system_theme {
+            Some(Theme::Dark) => egui::Theme::Dark,
+            Some(Theme::Light) => egui::Theme::Light,
+            None => ctx.options(|o| o.fallback_theme),
+        },
+    };
+    ctx.style_of(theme).visuals.panel_fill
+}
+
+#[cfg(windows)]
+mod windows {
+    use egui_winit::winit::window::Window;
+    use windows_sys::Win32::{Graphics::Gdi::CreateSolidBrush, UI::WindowsAndMessaging};
+
+    /// The brush stays alive with the class; winit registers one class for
+    /// all its windows, so the last color set wins.
+    pub fn set_background_color(window: &Window, color: egui::Color32) {
+        let Some(hwnd) = crate::titlebar::hwnd(window) else {
+            return;
+        };
+        let [r, g, b, _] = color.to_array();
+        // SAFETY: COLORREF is 0x00BBGGRR; the handle belongs to a live
+        // window.
+        unsafe {
+            let brush = CreateSolidBrush(u32::from(r) | u32::from(g) << 8 | u32::from(b) << 16);
+            if brush.is_null() {
+                return;
+            }
+            #[cfg(target_pointer_width = "64")]
+            WindowsAndMessaging::SetClassLongPtrW(
+                hwnd,
+                WindowsAndMessaging::GCLP_HBRBACKGROUND,
+                brush as isize,
+            );
+            #[cfg(not(target_pointer_width = "64"))]
+            WindowsAndMessaging::SetClassLongW(
+                hwnd,
+                WindowsAndMessaging::GCL_HBRBACKGROUND,
+                brush as i32,
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::{c_char, c_void};
+
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+
+    type Id = *mut c_void;
+    type Sel = *const c_void;
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> Id;
+        fn sel_registerName(name: *const c_char) -> Sel;
+        fn objc_msgSend();
+    }
+
+    pub fn set_background_color(window: &Window, color: egui::Color32) -> anyhow::Result<()> {
+        let RawWindowHandle::AppKit(handle) = window.window_handle()?.as_raw() else {
+            anyhow::bail!("the window has no AppKit handle");
+        };
+        let [r, g, b] = [color.r(), color.g(), color.b()].map(|c| f64::from(c) / 255.);
+        // SAFETY: the view belongs to a live window, the selectors exist on
+        // NSView, NSColor and NSWindow with these signatures, and winit runs
+        // this on the main thread.
+        unsafe {
+            let send_id: unsafe extern "C" fn(Id, Sel) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_rgba: unsafe extern "C" fn(Id, Sel, f64, f64, f64, f64) -> Id =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+            let send_set: unsafe extern "C" fn(Id, Sel, Id) =
+                std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+            let ns_window = send_id(
+                handle.ns_view.as_ptr(),
+                sel_registerName(c"window".as_ptr()),
+            );
+            anyhow::ensure!(!ns_window.is_null(), "the view has no window");
+            let ns_color = send_rgba(
+                objc_getClass(c"NSColor".as_ptr()),
+                sel_registerName(c"colorWithSRGBRed:green:blue:alpha:".as_ptr()),
+                r,
+                g,
+                b,
+                1.,
+            );
+            send_set(
+                ns_window,
+                sel_registerName(c"setBackgroundColor:".as_ptr()),
+                ns_color,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod x11 {
+    use egui_winit::winit::{
+        raw_window_handle::{HasWindowHandle, RawWindowHandle},
+        window::Window,
+    };
+    use x11rb::protocol::xproto::{ChangeWindowAttributesAux, ConnectionExt};
+
+    /// Through a connection of our own, as any client may change any
+    /// window. Assumes a TrueColor visual, which opaque windows get.
+    pub fn set_background_color(window: &Window, color: egui::Color32) {
+        let window = match window.window_handle().map(|handle| handle.as_raw()) {
+            Ok(RawWindowHandle::Xlib(handle)) => handle.window as u32,
+            Ok(RawWindowHandle::Xcb(handle)) => handle.window.get(),
+            _ => return,
+        };
+        let Ok((connection, _)) = x11rb::connect(None) else {
+            return;
+        };
+        let [r, g, b, _] = color.to_array();
+        let pixel = u32::from(r) << 16 | u32::from(g) << 8 | u32::from(b);
+        let result = (|| -> Result<(), x11rb::errors::ReplyError> {
+            connection.change_window_attributes(
+                window,
+                &ChangeWindowAttributesAux::new().background_pixel(pixel),
+            )?;
+            // A round trip, so that the color is set before winit maps the
+            // window on its own connection.
+            connection.get_input_focus()?.reply()?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            log::warn!("cannot set the window's background color: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, testing::TestApp, user_app::SimpleApp};
+
+    #[test]
+    fn initial_color_follows_the_theme() {
+        let ctx = egui::Context::default();
+        let dark = ctx.style_of(egui::Theme::Dark).visuals.panel_fill;
+        let light = ctx.style_of(egui::Theme::Light).visuals.panel_fill;
+        assert_eq!(initial_color(&ctx, Some(Theme::Dark)), dark);
+        assert_eq!(initial_color(&ctx, Some(Theme::Light)), light);
+        ctx.options_mut(|o| o.fallback_theme = egui::Theme::Light);
+        assert_eq!(initial_color(&ctx, None), light);
+        ctx.set_theme(egui::ThemePreference::Dark);
+        assert_eq!(initial_color(&ctx, Some(Theme::Light)), dark);
+    }
+
+    #[test]
+    fn window_shows_once_a_frame_is_presented() {
+        // Paints nothing, so what shows is what the pass cleared to.
+        let options = Options {
+            window_size: egui::vec2(64., 64.),
+            ..Default::default()
+        };
+        let Some(mut test_app) = TestApp::with_options(SimpleApp(|_: &egui::Context| {}), options)
+        else {
+            return;
+        };
+        assert!(!test_app.window().is_visible());
+        let (_, pixels) = test_app.step(Vec::new()).pixels.unwrap();
+        assert!(test_app.window().is_visible());
+        // Presented again for compositors that dropped the first present.
+        assert_eq!(test_app.next_wake(), Some(test_app.now()));
+        // The test surface is not sRGB, so it holds the fill's linear values.
+        let fill = egui::Rgba::from(initial_color(test_app.ctx(), None)).to_array();
+        for (pixel, fill) in pixels[..4].iter().zip(fill) {
+            assert!(
+                (f32::from(*pixel) - fill * 255.).abs() <= 1.,
+                "{:?}",
+                &pixels[..4]
+            );
+        }
+    }
+}
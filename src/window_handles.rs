@@ -0,0 +1,14 @@
+use egui_winit::winit::{
+    raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle},
+    window::Window,
+};
+
+/// The window's raw handles, e.g. to parent a native dialog or hand the
+/// window to a media framework. They stay valid as long as the window, except
+/// on Android, where the native window goes away on suspend: take them again
+/// after resuming.
+pub fn raw_handles(window: &Window) -> Option<(RawWindowHandle, RawDisplayHandle)> {
+    let window_handle = window.window_handle().ok()?.as_raw();
+    let display_handle = window.display_handle().ok()?.as_raw();
+    Some((window_handle, display_handle))
+}
@@ -0,0 +1,247 @@
+use egui::{Color32, CursorIcon, Rect, ViewportId};
+use egui_winit::winit::window::{ResizeDirection, Window};
+
+/// Room around the content for the shadow, in points.
+const MARGIN: f32 = 12.;
+const ROUNDING: f32 = 8.;
+/// How far the resize grips reach out of the content, in points.
+const GRIP: f32 = 6.;
+
+/// Whether the OS gives undecorated windows a shadow and rounded corners:
+/// DWM on Windows 11, the NSWindow shadow with a hidden titlebar on macOS.
+/// Elsewhere the app draws them, see [`WindowShadow`].
+pub const NATIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+fn resize_direction(rect: Rect, pos: egui::Pos2) -> Option<ResizeDirection> {
+    if rect.contains(pos) || !rect.expand(GRIP).contains(pos) {
+        return None;
+    }
+    let west = pos.x < rect.min.x;
+    let east = pos.x > rect.max.x;
+    Some(match (pos.y < rect.min.y, pos.y > rect.max.y) {
+        (true, _) if west => ResizeDirection::NorthWest,
+        (true, _) if east => ResizeDirection::NorthEast,
+        (true, _) => ResizeDirection::North,
+        (_, true) if west => ResizeDirection::SouthWest,
+        (_, true) if east => ResizeDirection::SouthEast,
+        (_, true) => ResizeDirection::South,
+        _ if west => ResizeDirection::West,
+        _ => ResizeDirection::East,
+    })
+}
+
+fn cursor_icon(direction: ResizeDirection) -> CursorIcon {
+    match direction {
+        ResizeDirection::North => CursorIcon::ResizeNorth,
+        ResizeDirection::NorthEast => CursorIcon::ResizeNorthEast,
+        ResizeDirection::East => CursorIcon::ResizeEast,
+        ResizeDirection::SouthEast => CursorIcon::ResizeSouthEast,
+        ResizeDirection::South => CursorIcon::ResizeSouth,
+        ResizeDirection::SouthWest => CursorIcon::ResizeSouthWest,
+        ResizeDirection::West => CursorIcon::ResizeWest,
+        ResizeDirection::NorthWest => CursorIcon::ResizeNorthWest,
+    }
+}
+
+/// Draws a soft shadow and rounded corners for an undecorated, transparent
+/// window. egui lays out inside a margin that holds the shadow, panels are
+/// left transparent over a rounded rect in their fill, and the margin's
+/// inner edge resizes the window. On X11 clicks on the rest of the margin
+/// go to what is behind the window; elsewhere the window takes them.
+/// Maximized and fullscreen windows get neither margin nor rounding.
+pub struct WindowShadow {
+    margin: f32,
+    fill: Color32,
+    resize: Option<ResizeDirection>,
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    input_shape: Option<crate::click_through::InputShape>,
+}
+
+impl WindowShadow {
+    pub fn new(window: &Window) -> Self {
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))]
+        let input_shape = crate::click_through::InputShape::for_window(window);
+        #[cfg(not(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        )))]
+        let _ = window;
+        Self {
+            margin: MARGIN,
+            fill: Color32::TRANSPARENT,
+            resize: None,
+            #[cfg(all(
+                unix,
+                not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+            ))]
+            input_shape,
+        }
+    }
+
+    /// Shrinks the screen rect egui lays out in. Call on each frame's input.
+    pub fn inset(&mut self, raw_input: &mut egui::RawInput) {
+        let viewport = raw_input.viewports.get(&ViewportId::ROOT);
+        let filled = viewport.is_some_and(|viewport| {
+            viewport.maximized.unwrap_or(false) || viewport.fullscreen.unwrap_or(false)
+        });
+        self.margin = if filled { 0. } else { MARGIN };
+        if let Some(screen_rect) = &mut raw_input.screen_rect {
+            *screen_rect = screen_rect.shrink(self.margin);
+        }
+    }
+
+    /// Paints the shadow and the window's background. Call first thing in
+    /// the pass.
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let panel_fill = ctx.style().visuals.panel_fill;
+        if panel_fill != Color32::TRANSPARENT {
+            // Checked every pass, as switching themes brings the fill back.
+            self.fill = panel_fill;
+            ctx.style_mut(|style| style.visuals.panel_fill = Color32::TRANSPARENT);
+        }
+        let rect = ctx.screen_rect();
+        let rounding = if self.margin > 0. { ROUNDING } else { 0. };
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        if self.margin > 0. {
+            let shadow = egui::Shadow {
+                offset: egui::vec2(0., 2.),
+                blur: self.margin,
+                spread: 0.,
+                color: Color32::from_black_alpha(96),
+            };
+            painter.add(shadow.as_shape(rect, rounding));
+        }
+        painter.rect(rect, rounding, self.fill, ctx.style().visuals.window_stroke);
+
+        self.resize = None;
+        if self.margin == 0. {
+            return;
+        }
+        let (hover, pressed) = ctx.input(|i| (i.pointer.hover_pos(), i.pointer.primary_pressed()));
+        if let Some(direction) = hover.and_then(|pos| resize_direction(rect, pos)) {
+            ctx.set_cursor_icon(cursor_icon(direction));
+            if pressed {
+                self.resize = Some(direction);
+            }
+        }
+    }
+
+    /// Starts resizing from a grip and updates the input shape. Call after
+    /// each pass.
+    pub fn update(&mut self, ctx: &egui::Context, window: &Window) {
+        if let Some(direction) = self.resize.take() {
+            if let Err(err) = window.drag_resize_window(direction) {
+                log::warn!("cannot resize the window: {err}");
+            }
+        }
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))]
+        if let Some(input_shape) = &mut self.input_shape {
+            let pixels_per_point = egui_winit::pixels_per_point(ctx, window);
+            let rect = ctx
+                .screen_rect()
+                .expand(if self.margin > 0. { GRIP } else { 0. });
+            input_shape.set(vec![crate::click_through::PixelRect::new(
+                rect,
+                pixels_per_point,
+            )]);
+        }
+        #[cfg(not(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        )))]
+        let _ = ctx;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use super::*;
+
+    fn shadow() -> WindowShadow {
+        WindowShadow {
+            margin: MARGIN,
+            fill: Color32::TRANSPARENT,
+            resize: None,
+            #[cfg(all(
+                unix,
+                not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+            ))]
+            input_shape: None,
+        }
+    }
+
+    #[test]
+    fn grips_surround_the_content() {
+        let rect = Rect::from_min_max(pos2(12., 12.), pos2(112., 62.));
+        let direction = |x, y| resize_direction(rect, pos2(x, y));
+        assert_eq!(direction(50., 30.), None);
+        assert_eq!(direction(50., 8.), Some(ResizeDirection::North));
+        assert_eq!(direction(50., 66.), Some(ResizeDirection::South));
+        assert_eq!(direction(8., 30.), Some(ResizeDirection::West));
+        assert_eq!(direction(116., 30.), Some(ResizeDirection::East));
+        assert_eq!(direction(8., 8.), Some(ResizeDirection::NorthWest));
+        assert_eq!(direction(116., 8.), Some(ResizeDirection::NorthEast));
+        assert_eq!(direction(8., 66.), Some(ResizeDirection::SouthWest));
+        assert_eq!(direction(116., 66.), Some(ResizeDirection::SouthEast));
+        // The outer part of the margin only holds the shadow.
+        assert_eq!(direction(50., 2.), None);
+        assert_eq!(direction(2., 2.), None);
+    }
+
+    #[test]
+    fn margin_goes_away_when_the_window_fills_the_screen() {
+        let input = |maximized| {
+            let mut raw_input = egui::RawInput {
+                screen_rect: Some(Rect::from_min_size(pos2(0., 0.), egui::vec2(400., 300.))),
+                ..Default::default()
+            };
+            raw_input
+                .viewports
+                .entry(ViewportId::ROOT)
+                .or_default()
+                .maximized = Some(maximized);
+            raw_input
+        };
+        let mut shadow = shadow();
+        let mut raw_input = input(false);
+        shadow.inset(&mut raw_input);
+        assert_eq!(
+            raw_input.screen_rect,
+            Some(Rect::from_min_max(pos2(12., 12.), pos2(388., 288.)))
+        );
+
+        let mut raw_input = input(true);
+        shadow.inset(&mut raw_input);
+        assert_eq!(
+            raw_input.screen_rect,
+            Some(Rect::from_min_size(pos2(0., 0.), egui::vec2(400., 300.)))
+        );
+        assert_eq!(shadow.margin, 0.);
+    }
+
+    #[test]
+    fn panels_become_transparent_over_the_rounded_fill() {
+        let ctx = egui::Context::default();
+        let fill = ctx.style().visuals.panel_fill;
+        let mut shadow = shadow();
+        let _ = ctx.run(Default::default(), |ctx| shadow.show(ctx));
+        assert_eq!(shadow.fill, fill);
+        assert_eq!(ctx.style().visuals.panel_fill, Color32::TRANSPARENT);
+        // A theme switch brings the fill back, and it is taken again.
+        ctx.set_visuals(egui::Visuals::light());
+        let _ = ctx.run(Default::default(), |ctx| shadow.show(ctx));
+        assert_eq!(shadow.fill, egui::Visuals::light().panel_fill);
+        assert_eq!(ctx.style().visuals.panel_fill, Color32::TRANSPARENT);
+    }
+}